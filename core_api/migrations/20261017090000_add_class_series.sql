@@ -0,0 +1,38 @@
+-- 循环排课 (Class Series): 规则 + 例外日期, 由后端展开为 classes 行
+CREATE TABLE IF NOT EXISTS class_series (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    base_id UUID NOT NULL REFERENCES bases(id),
+    course_id UUID NOT NULL REFERENCES courses(id),
+    room_id UUID NOT NULL REFERENCES rooms(id),
+    teacher_ids UUID[] NOT NULL DEFAULT '{}',
+    max_capacity INTEGER NOT NULL,
+
+    -- 规则: weekly / biweekly
+    recurrence_type VARCHAR(20) NOT NULL CHECK (recurrence_type IN ('weekly', 'biweekly')),
+    first_start_time TIMESTAMPTZ NOT NULL, -- 首节课开始时间 (决定星期几与上课时刻)
+    first_end_time TIMESTAMPTZ NOT NULL,
+    end_date DATE NOT NULL,                -- 最后一节课不晚于该日期
+    excluded_dates DATE[] NOT NULL DEFAULT '{}', -- 节假日等例外日期
+
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_class_series_base ON class_series(base_id);
+
+ALTER TABLE classes ADD COLUMN IF NOT EXISTS series_id UUID REFERENCES class_series(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_classes_series ON classes(series_id) WHERE series_id IS NOT NULL;
+
+-- 报名整个系列的学员 (新展开 / 后续场次自动带入)
+CREATE TABLE IF NOT EXISTS class_series_enrollments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    series_id UUID NOT NULL REFERENCES class_series(id) ON DELETE CASCADE,
+    participant_id UUID NOT NULL REFERENCES participants(id) ON DELETE CASCADE,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    customer_membership_id UUID REFERENCES customer_memberships(id),
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (series_id, participant_id)
+);
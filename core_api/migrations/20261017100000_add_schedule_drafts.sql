@@ -0,0 +1,30 @@
+-- AI 排课草稿: 先生成预览, 人工调整后再一次性提交到 classes
+CREATE TABLE IF NOT EXISTS schedule_drafts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    base_id UUID NOT NULL REFERENCES bases(id),
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    density INTEGER NOT NULL,
+    max_capacity INTEGER NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'draft', -- draft / committed / discarded
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    committed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_schedule_drafts_base ON schedule_drafts(base_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS schedule_draft_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    draft_id UUID NOT NULL REFERENCES schedule_drafts(id) ON DELETE CASCADE,
+    course_id UUID NOT NULL REFERENCES courses(id),
+    teacher_id UUID NOT NULL REFERENCES users(id),
+    room_id UUID NOT NULL REFERENCES rooms(id),
+    start_time TIMESTAMPTZ NOT NULL,
+    end_time TIMESTAMPTZ NOT NULL,
+    max_capacity INTEGER NOT NULL,
+    class_id UUID REFERENCES classes(id) ON DELETE SET NULL -- 提交后对应的正式排课
+);
+
+CREATE INDEX IF NOT EXISTS idx_schedule_draft_items_draft ON schedule_draft_items(draft_id);
@@ -0,0 +1,2 @@
+-- 记录草稿由哪个排课引擎生成 (ai / local), 便于对照
+ALTER TABLE schedule_drafts ADD COLUMN IF NOT EXISTS engine VARCHAR(20) NOT NULL DEFAULT 'ai';
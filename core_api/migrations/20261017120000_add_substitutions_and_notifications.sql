@@ -0,0 +1,33 @@
+-- C 端个人消息 (调课 / 候补转正 / 会员卡提醒等), 与基地公告 notices 区分
+CREATE TABLE IF NOT EXISTS customer_notifications (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    category VARCHAR(50) NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    content TEXT NOT NULL,
+    related_entity_id UUID,
+    is_read BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_customer_notifications_customer ON customer_notifications(customer_id, created_at DESC);
+
+-- 老师请假后的代课处理
+ALTER TABLE classes ADD COLUMN IF NOT EXISTS needs_cover BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE IF NOT EXISTS class_substitutions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    base_id UUID NOT NULL REFERENCES bases(id),
+    class_id UUID NOT NULL REFERENCES classes(id) ON DELETE CASCADE,
+    leave_request_id UUID REFERENCES leave_requests(id) ON DELETE SET NULL,
+    original_teacher_id UUID NOT NULL REFERENCES users(id),
+    substitute_teacher_id UUID REFERENCES users(id),
+    candidate_teacher_ids UUID[] NOT NULL DEFAULT '{}',
+    status VARCHAR(20) NOT NULL, -- reassigned / needs_cover
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_class_substitutions_base ON class_substitutions(base_id, status);
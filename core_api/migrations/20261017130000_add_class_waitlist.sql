@@ -0,0 +1,17 @@
+-- 课程候补队列: 报名满员后进入候补, 有人退课 / 请假时按先后顺序自动转正
+CREATE TABLE IF NOT EXISTS class_waitlist (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    class_id UUID NOT NULL REFERENCES classes(id) ON DELETE CASCADE,
+    participant_id UUID NOT NULL REFERENCES participants(id) ON DELETE CASCADE,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    customer_membership_id UUID REFERENCES customer_memberships(id),
+    status VARCHAR(20) NOT NULL DEFAULT 'waiting', -- waiting / promoted / cancelled
+    enrollment_id UUID REFERENCES class_enrollments(id) ON DELETE SET NULL, -- 转正后对应的报名
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    promoted_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS uq_class_waitlist_waiting
+    ON class_waitlist(class_id, participant_id) WHERE status = 'waiting';
+CREATE INDEX IF NOT EXISTS idx_class_waitlist_queue ON class_waitlist(class_id, created_at) WHERE status = 'waiting';
@@ -0,0 +1,22 @@
+-- 会员卡生命周期: 冻结 / 延期 / 转让 / 退卡
+-- frozen_at 非空表示冻结中, 解冻时按冻结天数顺延 expiry_date
+ALTER TABLE customer_memberships ADD COLUMN IF NOT EXISTS frozen_at TIMESTAMPTZ;
+
+-- 会员卡变更记录 (审计用)
+CREATE TABLE IF NOT EXISTS membership_adjustments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id),
+    membership_id UUID NOT NULL REFERENCES customer_memberships(id) ON DELETE CASCADE,
+    action VARCHAR(20) NOT NULL, -- freeze / unfreeze / extend / transfer / refund
+    days INTEGER,                -- 顺延天数 (unfreeze / extend)
+    uses INTEGER,                -- 转出次数 (transfer)
+    amount_in_cents INTEGER,     -- 退款金额 (refund)
+    from_participant_id UUID REFERENCES participants(id),
+    to_participant_id UUID REFERENCES participants(id),
+    target_membership_id UUID REFERENCES customer_memberships(id), -- 转让拆分出的新卡
+    reason TEXT,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_membership_adjustments_membership ON membership_adjustments(membership_id, created_at);
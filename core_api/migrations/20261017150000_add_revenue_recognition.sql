@@ -0,0 +1,7 @@
+-- 期限卡收入确认: 按天直线摊销 (daily) 或按实际上课次数 (per_class), 按卡种配置
+ALTER TABLE membership_tiers ADD COLUMN IF NOT EXISTS recognition_method VARCHAR(20) NOT NULL DEFAULT 'daily';
+ALTER TABLE membership_tiers ADD COLUMN IF NOT EXISTS expected_class_count INTEGER; -- per_class: 预计上课次数, 单次确认 = 售价 / 该值
+
+-- 每张卡已确认的收入与按天摊销的进度 (revenue_recognized_until 为空表示从 start_date 起算)
+ALTER TABLE customer_memberships ADD COLUMN IF NOT EXISTS revenue_recognized_in_cents INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE customer_memberships ADD COLUMN IF NOT EXISTS revenue_recognized_until TIMESTAMPTZ;
@@ -0,0 +1,21 @@
+-- 会员卡续费提醒: 即将到期 / 剩余次数不足, 由后台任务每天扫描生成
+-- cycle_key 用于去重: expiring 为到期日, low_balance 固定为 'low', 同一张卡同一周期只提醒一次
+CREATE TABLE IF NOT EXISTS membership_reminders (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    base_id UUID REFERENCES bases(id),
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    membership_id UUID NOT NULL REFERENCES customer_memberships(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL,   -- expiring / low_balance
+    cycle_key VARCHAR(32) NOT NULL,
+    expiry_date TIMESTAMPTZ,
+    remaining_uses INTEGER,
+    status VARCHAR(20) NOT NULL DEFAULT 'open', -- open / followed_up
+    follow_up_note TEXT,
+    followed_up_by UUID REFERENCES users(id),
+    followed_up_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (membership_id, kind, cycle_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_membership_reminders_base_open ON membership_reminders(base_id, created_at) WHERE status = 'open';
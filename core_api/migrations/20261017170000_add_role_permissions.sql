@@ -0,0 +1,131 @@
+-- 权限模型: 权限点挂在 roles 上 (role_permissions), 路由在 main.rs 中声明所需权限
+-- role_permission_defaults 按角色 name_key 定义默认权限, 新建角色时由触发器自动带出
+CREATE TABLE IF NOT EXISTS permissions (
+    key VARCHAR(64) PRIMARY KEY,
+    description TEXT
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role_id UUID NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    permission_key VARCHAR(64) NOT NULL REFERENCES permissions(key) ON DELETE CASCADE,
+    PRIMARY KEY (role_id, permission_key)
+);
+
+CREATE TABLE IF NOT EXISTS role_permission_defaults (
+    name_key VARCHAR(100) NOT NULL,
+    permission_key VARCHAR(64) NOT NULL REFERENCES permissions(key) ON DELETE CASCADE,
+    PRIMARY KEY (name_key, permission_key)
+);
+
+INSERT INTO permissions (key, description) VALUES
+    ('bases.read', '查看基地'),
+    ('bases.manage', '新建 / 修改基地'),
+    ('assets.read', '查看资产'),
+    ('assets.manage', '资产登记 / 调拨'),
+    ('materials.read', '查看物料'),
+    ('materials.manage', '物料维护'),
+    ('customers.read', '查看客户 / 学员'),
+    ('customers.manage', '新建客户 / 学员'),
+    ('workspace.read', '基地工作台'),
+    ('notices.manage', '发布通知'),
+    ('approvals.manage', '审批'),
+    ('finance.read', '查看财务'),
+    ('finance.manage', '财务操作'),
+    ('staff.read', '查看员工'),
+    ('staff.manage', '员工维护'),
+    ('tiers.manage', '会员卡种维护'),
+    ('memberships.read', '查看会员卡'),
+    ('memberships.manage', '开卡 / 冻结 / 延期 / 转让'),
+    ('memberships.refund', '会员卡退费'),
+    ('courses.read', '查看课程'),
+    ('courses.manage', '课程维护'),
+    ('rooms.read', '查看教室'),
+    ('rooms.manage', '教室维护'),
+    ('schedule.read', '查看排课'),
+    ('schedule.manage', '排课 / 代课 / 老师配置'),
+    ('enrollments.manage', '报名 / 候补'),
+    ('attendance.record', '消课签到'),
+    ('teaching.self', '老师个人工作台'),
+    ('honor.read', '查看荣誉体系'),
+    ('honor.manage', '荣誉体系维护'),
+    ('stock.read', '查看库存'),
+    ('procurement.manage', '采购申请'),
+    ('procurement.approve', '采购审批'),
+    ('supply.read', '查看供应链'),
+    ('supply.order', '基地订货 / 收货'),
+    ('supply.manage', '总部供应链管理'),
+    ('inventory.manage', '库存出入库'),
+    ('reports.hq', '总部报表'),
+    ('leads.manage', '线索管理'),
+    ('trials.manage', '试听课管理'),
+    ('qrcodes.manage', '二维码批次管理')
+ON CONFLICT (key) DO NOTHING;
+
+-- 管理员拥有全部权限 (作用域仍由路由限定: 基地管理员调不了总部接口)
+INSERT INTO role_permission_defaults (name_key, permission_key)
+SELECT n.name_key, p.key
+FROM (VALUES ('role.hq.admin'), ('role.base.admin')) AS n(name_key)
+CROSS JOIN permissions p
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permission_defaults (name_key, permission_key) VALUES
+    ('role.hq.finance', 'finance.read'), ('role.hq.finance', 'finance.manage'), ('role.hq.finance', 'reports.hq'),
+    ('role.hq.finance', 'bases.read'), ('role.hq.finance', 'customers.read'), ('role.hq.finance', 'memberships.read'),
+    ('role.hq.finance', 'memberships.refund'), ('role.hq.finance', 'courses.read'), ('role.hq.finance', 'procurement.manage'),
+    ('role.hq.finance', 'procurement.approve'), ('role.hq.finance', 'supply.read'), ('role.hq.finance', 'supply.manage'),
+
+    ('role.base.finance', 'finance.read'), ('role.base.finance', 'finance.manage'), ('role.base.finance', 'workspace.read'),
+    ('role.base.finance', 'customers.read'), ('role.base.finance', 'memberships.read'), ('role.base.finance', 'memberships.manage'),
+    ('role.base.finance', 'memberships.refund'), ('role.base.finance', 'courses.read'), ('role.base.finance', 'stock.read'),
+    ('role.base.finance', 'supply.read'), ('role.base.finance', 'supply.order'), ('role.base.finance', 'procurement.manage'),
+
+    ('role.base.academic', 'workspace.read'), ('role.base.academic', 'schedule.read'), ('role.base.academic', 'schedule.manage'),
+    ('role.base.academic', 'enrollments.manage'), ('role.base.academic', 'attendance.record'), ('role.base.academic', 'customers.read'),
+    ('role.base.academic', 'customers.manage'), ('role.base.academic', 'memberships.read'), ('role.base.academic', 'memberships.manage'),
+    ('role.base.academic', 'courses.read'), ('role.base.academic', 'rooms.read'), ('role.base.academic', 'rooms.manage'),
+    ('role.base.academic', 'trials.manage'), ('role.base.academic', 'honor.read'), ('role.base.academic', 'materials.read'),
+
+    ('role.teacher', 'teaching.self'), ('role.teacher', 'schedule.read'), ('role.teacher', 'attendance.record'),
+    ('role.teacher', 'customers.read'), ('role.teacher', 'courses.read'), ('role.teacher', 'rooms.read'),
+    ('role.teacher', 'honor.read'), ('role.teacher', 'materials.read'),
+
+    ('role.hq.hr', 'staff.read'), ('role.hq.hr', 'staff.manage'), ('role.hq.hr', 'bases.read'), ('role.hq.hr', 'reports.hq'),
+    ('role.base.hr', 'staff.read'), ('role.base.hr', 'staff.manage'), ('role.base.hr', 'workspace.read'),
+
+    ('role.hq.marketing', 'customers.read'), ('role.hq.marketing', 'reports.hq'), ('role.hq.marketing', 'qrcodes.manage'),
+    ('role.hq.marketing', 'bases.read'), ('role.hq.marketing', 'courses.read'), ('role.hq.marketing', 'memberships.read'),
+    ('role.hq.marketing', 'honor.read'),
+
+    ('role.base.marketing', 'workspace.read'), ('role.base.marketing', 'leads.manage'), ('role.base.marketing', 'trials.manage'),
+    ('role.base.marketing', 'customers.read'), ('role.base.marketing', 'customers.manage'), ('role.base.marketing', 'memberships.read'),
+    ('role.base.marketing', 'courses.read'),
+
+    ('role.hq.operation', 'bases.read'), ('role.hq.operation', 'reports.hq'), ('role.hq.operation', 'assets.read'),
+    ('role.hq.operation', 'assets.manage'), ('role.hq.operation', 'materials.read'), ('role.hq.operation', 'materials.manage'),
+    ('role.hq.operation', 'supply.read'), ('role.hq.operation', 'supply.manage'), ('role.hq.operation', 'procurement.manage'),
+    ('role.hq.operation', 'procurement.approve'), ('role.hq.operation', 'courses.read'), ('role.hq.operation', 'rooms.read')
+ON CONFLICT DO NOTHING;
+
+-- 存量角色 (各总部) 按默认模板授予
+INSERT INTO role_permissions (role_id, permission_key)
+SELECT r.id, d.permission_key
+FROM roles r
+JOIN role_permission_defaults d ON d.name_key = r.name_key
+ON CONFLICT DO NOTHING;
+
+-- 新建角色时自动授予默认权限
+CREATE OR REPLACE FUNCTION grant_default_role_permissions() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO role_permissions (role_id, permission_key)
+    SELECT NEW.id, d.permission_key
+    FROM role_permission_defaults d
+    WHERE d.name_key = NEW.name_key
+    ON CONFLICT DO NOTHING;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_roles_default_permissions ON roles;
+CREATE TRIGGER trg_roles_default_permissions
+    AFTER INSERT ON roles
+    FOR EACH ROW EXECUTE FUNCTION grant_default_role_permissions();
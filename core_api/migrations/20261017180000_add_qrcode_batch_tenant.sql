@@ -0,0 +1,9 @@
+-- 防伪码批次按总部隔离: 历史批次按创建人回填 hq_id
+ALTER TABLE qrcode_batches ADD COLUMN IF NOT EXISTS hq_id UUID REFERENCES hqs(id) ON DELETE CASCADE;
+
+UPDATE qrcode_batches b
+SET hq_id = u.hq_id
+FROM users u
+WHERE b.created_by = u.id AND b.hq_id IS NULL;
+
+CREATE INDEX IF NOT EXISTS idx_qrcode_batches_hq ON qrcode_batches(hq_id, created_at DESC);
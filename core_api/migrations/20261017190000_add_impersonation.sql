@@ -0,0 +1,29 @@
+-- 开发调试: 总部管理员模拟登录 (替代原 dev_token_ 后门), 每次签发都记审计日志
+INSERT INTO permissions (key, description) VALUES
+    ('users.impersonate', '模拟登录 (开发调试)')
+ON CONFLICT (key) DO NOTHING;
+
+-- 只给总部管理员
+INSERT INTO role_permission_defaults (name_key, permission_key) VALUES
+    ('role.hq.admin', 'users.impersonate')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_key)
+SELECT r.id, 'users.impersonate'
+FROM roles r
+WHERE r.name_key = 'role.hq.admin'
+ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS impersonation_logs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    actor_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    target_type VARCHAR(20) NOT NULL,   -- user | customer
+    target_id UUID NOT NULL,
+    target_name VARCHAR(255),
+    reason TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_impersonation_logs_hq ON impersonation_logs(hq_id, created_at DESC);
@@ -0,0 +1,28 @@
+-- 登录会话: 短时 Access Token + 服务端保存的轮换 Refresh Token
+-- 退出登录 / 改密码 / 员工停用时吊销会话, auth_middleware 拒绝已吊销会话签发的 Token
+CREATE TABLE IF NOT EXISTS auth_sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subject_type VARCHAR(20) NOT NULL,   -- user | customer
+    subject_id UUID NOT NULL,
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    impersonated_by UUID REFERENCES users(id) ON DELETE CASCADE, -- 模拟登录签发的会话
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    revoked_reason VARCHAR(50),
+    last_refreshed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_sessions_subject ON auth_sessions(subject_type, subject_id) WHERE revoked_at IS NULL;
+
+-- 只保存 SHA-256 摘要, 每个 Refresh Token 只能使用一次
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    session_id UUID NOT NULL REFERENCES auth_sessions(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_session ON refresh_tokens(session_id);
@@ -0,0 +1,22 @@
+-- 微信登录接入 jscode2session: 保存 session_key, 按 unionid 关联小程序 / 公众号下的同一位家长
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS wechat_unionid VARCHAR(64);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_customers_wechat_unionid ON customers(wechat_unionid) WHERE wechat_unionid IS NOT NULL;
+
+-- 每个微信应用 (app_id) 下的 openid; session_key 只用于服务端解密, 不下发给客户端
+CREATE TABLE IF NOT EXISTS customer_wechat_accounts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    app_id VARCHAR(64) NOT NULL,
+    openid VARCHAR(64) NOT NULL,
+    unionid VARCHAR(64),
+    session_key VARCHAR(64),
+    session_key_updated_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (app_id, openid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_customer_wechat_accounts_customer ON customer_wechat_accounts(customer_id);
+
+-- 微信新用户绑定手机号前 phone_number 为空串, 同一总部下允许存在多个
+ALTER TABLE customers DROP CONSTRAINT IF EXISTS customers_hq_id_phone_number_key;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_customers_hq_phone ON customers(hq_id, phone_number) WHERE phone_number <> '';
@@ -0,0 +1,15 @@
+-- 短信验证码: 只存哈希 (手机号 + 验证码 + 服务端密钥), 带过期时间和错误次数
+CREATE TABLE IF NOT EXISTS sms_verification_codes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    phone_number VARCHAR(50) NOT NULL,
+    purpose VARCHAR(32) NOT NULL,          -- bind_phone
+    code_hash VARCHAR(64) NOT NULL,
+    request_ip VARCHAR(64),                -- 按 IP 限流
+    attempts INT NOT NULL DEFAULT 0,       -- 校验失败次数, 达到上限后作废
+    expires_at TIMESTAMPTZ NOT NULL,
+    consumed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_sms_codes_phone ON sms_verification_codes(phone_number, purpose, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_sms_codes_ip ON sms_verification_codes(request_ip, created_at DESC);
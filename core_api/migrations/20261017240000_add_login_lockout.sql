@@ -0,0 +1,6 @@
+-- 登录防爆破: 失败记录也写入 user_login_history, 按邮箱 / IP 统计最近的失败次数
+CREATE INDEX IF NOT EXISTS idx_login_history_email ON user_login_history(lower(email_attempted), login_timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_login_history_ip ON user_login_history(ip_address, login_timestamp DESC);
+
+-- 管理员解锁时间: 之前的失败记录不再计入锁定
+ALTER TABLE users ADD COLUMN IF NOT EXISTS login_unlocked_at TIMESTAMPTZ;
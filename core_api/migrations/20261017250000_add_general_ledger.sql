@@ -0,0 +1,162 @@
+-- 总账 (复式记账): 每个总部一套会计科目, 凭证分录借贷必须相等, 按月结账
+-- 售卡 / 退卡 / 消课 / 收入确认 / 订单收款 / 费用报销 均通过凭证记账, financial_transactions 不再写入, 仅保留历史
+
+-- 默认科目表: 新建总部时由触发器带出; system_key 供业务流程按用途找科目, 总部可改名改编号但不能停用
+CREATE TABLE IF NOT EXISTS ledger_account_defaults (
+    system_key VARCHAR(50) PRIMARY KEY,
+    code VARCHAR(20) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    account_type VARCHAR(20) NOT NULL CHECK (account_type IN ('asset', 'liability', 'equity', 'revenue', 'expense'))
+);
+
+INSERT INTO ledger_account_defaults (system_key, code, name, account_type) VALUES
+    ('cash', '1001', '库存现金', 'asset'),
+    ('bank', '1002', '银行存款', 'asset'),
+    ('accounts_receivable', '1122', '应收账款', 'asset'),
+    ('inventory', '1405', '库存商品', 'asset'),
+    ('accounts_payable', '2202', '应付账款', 'liability'),
+    ('contract_liability', '2204', '合同负债', 'liability'),
+    ('other_payable', '2241', '其他应付款', 'liability'),
+    ('paid_in_capital', '4001', '实收资本', 'equity'),
+    ('retained_earnings', '4104', '利润分配', 'equity'),
+    ('revenue', '6001', '主营业务收入', 'revenue'),
+    ('other_revenue', '6051', '其他业务收入', 'revenue'),
+    ('cost_of_sales', '6401', '主营业务成本', 'expense'),
+    ('selling_expense', '6601', '销售费用', 'expense'),
+    ('admin_expense', '6602', '管理费用', 'expense')
+ON CONFLICT (system_key) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS ledger_accounts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    code VARCHAR(20) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    account_type VARCHAR(20) NOT NULL CHECK (account_type IN ('asset', 'liability', 'equity', 'revenue', 'expense')),
+    system_key VARCHAR(50),  -- 为空表示总部自建科目
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT ledger_accounts_hq_id_code_key UNIQUE (hq_id, code),
+    CONSTRAINT ledger_accounts_hq_id_system_key_key UNIQUE (hq_id, system_key)
+);
+
+-- 凭证: base_id 为空表示总部级凭证
+CREATE TABLE IF NOT EXISTS journal_entries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    base_id UUID REFERENCES bases(id) ON DELETE CASCADE,
+    entry_date DATE NOT NULL,
+    source_type VARCHAR(50) NOT NULL,  -- membership_sale / membership_refund / course_revenue / membership_revenue / order_payment / order_revenue / expense / manual
+    source_id UUID,                    -- 业务单据 id (会员卡 / 报名 / 收款记录 / 订单 / 报销单)
+    description TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_journal_entries_hq_date ON journal_entries(hq_id, entry_date);
+CREATE INDEX IF NOT EXISTS idx_journal_entries_base_date ON journal_entries(base_id, entry_date) WHERE base_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_journal_entries_source ON journal_entries(source_type, source_id);
+
+-- 分录: 每行只能记借方或贷方
+CREATE TABLE IF NOT EXISTS journal_lines (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    entry_id UUID NOT NULL REFERENCES journal_entries(id) ON DELETE CASCADE,
+    account_id UUID NOT NULL REFERENCES ledger_accounts(id),
+    debit_cents BIGINT NOT NULL DEFAULT 0,
+    credit_cents BIGINT NOT NULL DEFAULT 0,
+    memo TEXT,
+    CONSTRAINT journal_lines_one_side CHECK (debit_cents >= 0 AND credit_cents >= 0 AND (debit_cents = 0) <> (credit_cents = 0))
+);
+
+CREATE INDEX IF NOT EXISTS idx_journal_lines_entry ON journal_lines(entry_id);
+CREATE INDEX IF NOT EXISTS idx_journal_lines_account ON journal_lines(account_id);
+
+-- 借贷平衡: 事务提交时校验, 每张凭证至少两行且借贷合计相等
+CREATE OR REPLACE FUNCTION check_journal_entry_balanced() RETURNS TRIGGER AS $$
+DECLARE
+    target UUID := CASE WHEN TG_OP = 'DELETE' THEN OLD.entry_id ELSE NEW.entry_id END;
+    diff BIGINT;
+    line_count INTEGER;
+BEGIN
+    -- 整张凭证被删除 (级联) 时不再校验
+    IF NOT EXISTS (SELECT 1 FROM journal_entries WHERE id = target) THEN
+        RETURN NULL;
+    END IF;
+    SELECT COALESCE(SUM(debit_cents - credit_cents), 0), COUNT(*) INTO diff, line_count
+    FROM journal_lines WHERE entry_id = target;
+    IF diff <> 0 OR line_count < 2 THEN
+        RAISE EXCEPTION 'journal entry % is unbalanced (diff %, lines %)', target, diff, line_count
+            USING ERRCODE = 'check_violation', CONSTRAINT = 'journal_entry_balanced';
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_journal_lines_balanced ON journal_lines;
+CREATE CONSTRAINT TRIGGER trg_journal_lines_balanced
+    AFTER INSERT OR UPDATE OR DELETE ON journal_lines
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION check_journal_entry_balanced();
+
+-- 已结账月份 (有记录即为已结账); 反结账删除记录
+CREATE TABLE IF NOT EXISTS accounting_periods (
+    hq_id UUID NOT NULL REFERENCES hqs(id) ON DELETE CASCADE,
+    period_start DATE NOT NULL CHECK (EXTRACT(DAY FROM period_start) = 1),
+    closed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    closed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    PRIMARY KEY (hq_id, period_start)
+);
+
+-- 存量总部按默认模板建科目, 新建总部时自动带出
+INSERT INTO ledger_accounts (hq_id, code, name, account_type, system_key)
+SELECT h.id, d.code, d.name, d.account_type, d.system_key
+FROM hqs h CROSS JOIN ledger_account_defaults d
+ON CONFLICT DO NOTHING;
+
+CREATE OR REPLACE FUNCTION seed_default_ledger_accounts() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO ledger_accounts (hq_id, code, name, account_type, system_key)
+    SELECT NEW.id, d.code, d.name, d.account_type, d.system_key
+    FROM ledger_account_defaults d
+    ON CONFLICT DO NOTHING;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_hqs_default_ledger_accounts ON hqs;
+CREATE TRIGGER trg_hqs_default_ledger_accounts
+    AFTER INSERT ON hqs
+    FOR EACH ROW EXECUTE FUNCTION seed_default_ledger_accounts();
+
+-- 结账 / 反结账
+INSERT INTO permissions (key, description) VALUES
+    ('ledger.close', '总账结账 / 反结账')
+ON CONFLICT (key) DO NOTHING;
+
+INSERT INTO role_permission_defaults (name_key, permission_key) VALUES
+    ('role.hq.admin', 'ledger.close'), ('role.hq.finance', 'ledger.close')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_key)
+SELECT r.id, 'ledger.close'
+FROM roles r
+WHERE r.name_key IN ('role.hq.admin', 'role.hq.finance')
+ON CONFLICT DO NOTHING;
+
+-- 历史流水转为凭证 (沿用流水 id, 重复执行不会重复导入); 0 元流水只是上课记录, 不入账
+INSERT INTO journal_entries (id, hq_id, base_id, entry_date, source_type, source_id, description, created_by, created_at)
+SELECT ft.id, ft.hq_id, ft.base_id, COALESCE(ft.created_at, NOW())::date, ft.category, ft.related_entity_id,
+       ft.description, ft.created_by, COALESCE(ft.created_at, NOW())
+FROM financial_transactions ft
+WHERE ft.amount_in_cents > 0
+  AND EXISTS (SELECT 1 FROM ledger_accounts a WHERE a.hq_id = ft.hq_id AND a.system_key = ft.debit_subject)
+  AND EXISTS (SELECT 1 FROM ledger_accounts a WHERE a.hq_id = ft.hq_id AND a.system_key = ft.credit_subject)
+ON CONFLICT (id) DO NOTHING;
+
+INSERT INTO journal_lines (entry_id, account_id, debit_cents, credit_cents)
+SELECT ft.id, a.id, side.debit, side.credit
+FROM financial_transactions ft
+JOIN journal_entries je ON je.id = ft.id
+CROSS JOIN LATERAL (VALUES (ft.debit_subject, ft.amount_in_cents::bigint, 0::bigint),
+                           (ft.credit_subject, 0::bigint, ft.amount_in_cents::bigint)) AS side(subject, debit, credit)
+JOIN ledger_accounts a ON a.hq_id = ft.hq_id AND a.system_key = side.subject
+WHERE NOT EXISTS (SELECT 1 FROM journal_lines l WHERE l.entry_id = ft.id);
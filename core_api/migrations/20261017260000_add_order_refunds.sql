@@ -0,0 +1,36 @@
+-- 订单退款: 退款申请可指定退回的订单明细, 审批通过后生成退款流水、冲减订单已付、记冲销凭证、停用会员卡、退回库存
+-- 订单明细可关联基地库存商品 (product_id) 或会员卡种 (tier_id): 订单付清交付时扣库存 / 开卡, 退款时按明细反向处理
+
+ALTER TABLE order_items
+    ADD COLUMN IF NOT EXISTS product_id UUID REFERENCES hq_products(id),
+    ADD COLUMN IF NOT EXISTS tier_id UUID REFERENCES membership_tiers(id),
+    ADD COLUMN IF NOT EXISTS membership_id UUID REFERENCES customer_memberships(id) ON DELETE SET NULL, -- 交付时开出的会员卡
+    ADD COLUMN IF NOT EXISTS refunded_quantity INTEGER NOT NULL DEFAULT 0;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'order_items_refunded_quantity_check') THEN
+        ALTER TABLE order_items ADD CONSTRAINT order_items_refunded_quantity_check
+            CHECK (refunded_quantity >= 0 AND refunded_quantity <= quantity);
+    END IF;
+    -- 一行明细只能是商品或卡种之一; 卡种每行一张卡
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'order_items_fulfillment_check') THEN
+        ALTER TABLE order_items ADD CONSTRAINT order_items_fulfillment_check
+            CHECK (NOT (product_id IS NOT NULL AND tier_id IS NOT NULL) AND (tier_id IS NULL OR quantity = 1));
+    END IF;
+END $$;
+
+-- 退款去向 (cash / bank / wechat ...) 及审批通过后生成的退款流水
+ALTER TABLE refund_requests
+    ADD COLUMN IF NOT EXISTS channel VARCHAR(20) NOT NULL DEFAULT 'cash',
+    ADD COLUMN IF NOT EXISTS payment_record_id UUID REFERENCES finance_payment_records(id);
+
+CREATE INDEX IF NOT EXISTS idx_refund_requests_order ON refund_requests(order_id);
+
+-- 随退款退回的明细数量 (只退钱不退货时为空)
+CREATE TABLE IF NOT EXISTS refund_request_items (
+    refund_id UUID NOT NULL REFERENCES refund_requests(id) ON DELETE CASCADE,
+    order_item_id UUID NOT NULL REFERENCES order_items(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    PRIMARY KEY (refund_id, order_item_id)
+);
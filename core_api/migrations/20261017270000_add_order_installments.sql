@@ -0,0 +1,56 @@
+-- 订单分期收款: 一个订单可约定多个付款节点 (到期日 + 金额), 合计等于订单总额
+-- 收款核实时按到期日先后核销到各期 (order_installment_payments 记录每笔流水核销到哪一期), 退款从最晚一期冲回
+-- order_receivables: 应收明细视图, 有分期的按每期未收部分, 没有分期的按订单未收部分 (到期日取 due_date, 为空取下单日), 账龄 / 逾期统计都以此为准
+
+CREATE TABLE IF NOT EXISTS order_installments (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    hq_id UUID NOT NULL REFERENCES hqs(id),
+    base_id UUID NOT NULL REFERENCES bases(id),
+    seq INTEGER NOT NULL,                -- 第几期, 从 1 开始, 按到期日排列
+    due_date DATE NOT NULL,
+    amount_cents INTEGER NOT NULL,
+    paid_cents INTEGER NOT NULL DEFAULT 0,
+    paid_at TIMESTAMPTZ,                 -- 该期收齐的时间
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (order_id, seq)
+);
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'order_installments_amount_check') THEN
+        ALTER TABLE order_installments ADD CONSTRAINT order_installments_amount_check
+            CHECK (amount_cents > 0 AND paid_cents >= 0 AND paid_cents <= amount_cents);
+    END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_order_installments_open
+    ON order_installments(base_id, due_date) WHERE paid_cents < amount_cents;
+
+-- 收款流水核销到分期的明细
+CREATE TABLE IF NOT EXISTS order_installment_payments (
+    installment_id UUID NOT NULL REFERENCES order_installments(id) ON DELETE CASCADE,
+    payment_record_id UUID NOT NULL REFERENCES finance_payment_records(id) ON DELETE CASCADE,
+    amount_cents INTEGER NOT NULL CHECK (amount_cents > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (installment_id, payment_record_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_installment_payments_record ON order_installment_payments(payment_record_id);
+
+CREATE OR REPLACE VIEW order_receivables AS
+SELECT i.hq_id, i.base_id, i.order_id, o.customer_id, i.id AS installment_id,
+       i.due_date, (i.amount_cents - i.paid_cents) AS outstanding_cents
+FROM order_installments i
+JOIN orders o ON o.id = i.order_id
+WHERE i.paid_cents < i.amount_cents
+  AND o.status IN ('pending', 'partial_paid')
+UNION ALL
+SELECT o.hq_id, o.base_id, o.id AS order_id, o.customer_id, NULL::uuid AS installment_id,
+       COALESCE(o.due_date, o.created_at::date) AS due_date,
+       (o.total_amount_cents - o.paid_amount_cents) AS outstanding_cents
+FROM orders o
+WHERE o.status IN ('pending', 'partial_paid')
+  AND o.paid_amount_cents < o.total_amount_cents
+  AND NOT EXISTS (SELECT 1 FROM order_installments i WHERE i.order_id = o.id);
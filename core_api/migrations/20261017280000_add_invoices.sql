@@ -0,0 +1,71 @@
+-- 电子发票: 开票申请 (含购方税务信息) -> 通过开票服务商开具, 保存发票号码及 PDF / XML 版式文件
+-- 蓝字发票明细取自订单明细; 退款审批通过或作废时开具红字发票 (金额为负, 指向原蓝字发票)
+-- 订单已开票金额 = 已开具发票金额合计 (蓝 + 红), 用于和已收款核对
+
+CREATE TABLE IF NOT EXISTS invoices (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    hq_id UUID NOT NULL REFERENCES hqs(id),
+    base_id UUID NOT NULL REFERENCES bases(id),
+    order_id UUID NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    kind VARCHAR(10) NOT NULL DEFAULT 'blue',             -- blue 蓝字 / red 红字
+    invoice_type VARCHAR(10) NOT NULL DEFAULT 'normal',   -- normal 普票 / special 专票
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',        -- pending / issuing / issued / failed / cancelled
+    original_invoice_id UUID REFERENCES invoices(id),     -- 红字发票冲销的蓝字发票
+    refund_request_id UUID REFERENCES refund_requests(id), -- 因退款开具的红字发票
+
+    buyer_name VARCHAR(200) NOT NULL,
+    buyer_tax_id VARCHAR(30),
+    buyer_address_phone VARCHAR(200),
+    buyer_bank_account VARCHAR(200),
+    buyer_email VARCHAR(200),
+
+    amount_cents INTEGER NOT NULL,   -- 含税金额, 红字为负
+    tax_cents INTEGER NOT NULL,
+    remark TEXT,
+
+    invoice_no VARCHAR(50) UNIQUE,
+    issued_at TIMESTAMPTZ,
+    pdf BYTEA,
+    xml TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+
+    requested_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'invoices_kind_check') THEN
+        ALTER TABLE invoices ADD CONSTRAINT invoices_kind_check CHECK (
+            (kind = 'blue' AND amount_cents > 0 AND original_invoice_id IS NULL)
+            OR (kind = 'red' AND amount_cents < 0 AND original_invoice_id IS NOT NULL)
+        );
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'invoices_status_check') THEN
+        ALTER TABLE invoices ADD CONSTRAINT invoices_status_check
+            CHECK (status IN ('pending', 'issuing', 'issued', 'failed', 'cancelled'));
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'invoices_type_check') THEN
+        ALTER TABLE invoices ADD CONSTRAINT invoices_type_check CHECK (invoice_type IN ('normal', 'special'));
+    END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_invoices_order ON invoices(order_id);
+CREATE INDEX IF NOT EXISTS idx_invoices_original ON invoices(original_invoice_id) WHERE original_invoice_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_invoices_refund ON invoices(refund_request_id) WHERE refund_request_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS invoice_lines (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    invoice_id UUID NOT NULL REFERENCES invoices(id) ON DELETE CASCADE,
+    line_no INTEGER NOT NULL,
+    order_item_id UUID REFERENCES order_items(id) ON DELETE SET NULL, -- 折让 / 部分冲红行为空
+    name VARCHAR(200) NOT NULL,
+    quantity INTEGER,                 -- 红字为负
+    unit_price_cents INTEGER,
+    amount_cents INTEGER NOT NULL,
+    tax_rate_bp INTEGER NOT NULL,     -- 税率 (万分比), 600 = 6%
+    tax_cents INTEGER NOT NULL,
+    UNIQUE (invoice_id, line_no)
+);
@@ -0,0 +1,88 @@
+-- 在线支付 (微信支付 JSAPI): 小程序拉起支付一次记一笔交易, 商户订单号 out_trade_no 全局唯一
+-- 支付成功 (回调 / 查单 / 对账补单) 后生成已审核的收款流水 (finance_payment_records), 与人工审核走同一套入账逻辑
+-- 每日对账: 下载前一天的交易账单, 与本地交易逐笔比对, 差异明细记入 payment_reconciliation_items
+
+CREATE TABLE IF NOT EXISTS payment_transactions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    hq_id UUID NOT NULL REFERENCES hqs(id),
+    base_id UUID NOT NULL REFERENCES bases(id),
+    order_id UUID NOT NULL REFERENCES orders(id),
+    customer_id UUID NOT NULL REFERENCES customers(id),
+    provider VARCHAR(20) NOT NULL,                     -- wechat_pay
+    purpose VARCHAR(20) NOT NULL DEFAULT 'order',      -- order 订单付款 / membership 小程序购卡
+    out_trade_no VARCHAR(32) NOT NULL UNIQUE,
+    description VARCHAR(127) NOT NULL,
+    amount_cents INTEGER NOT NULL,
+    payer_openid VARCHAR(64) NOT NULL,
+    prepay_id VARCHAR(64),
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',     -- pending 待支付 / paid 已支付 / closed 已关闭
+    provider_transaction_id VARCHAR(64) UNIQUE,        -- 微信支付订单号
+    payment_record_id UUID REFERENCES finance_payment_records(id),
+    paid_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ NOT NULL,
+    notify_count INTEGER NOT NULL DEFAULT 0,           -- 收到的支付回调次数 (含重发)
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'payment_transactions_amount_check') THEN
+        ALTER TABLE payment_transactions ADD CONSTRAINT payment_transactions_amount_check CHECK (amount_cents > 0);
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'payment_transactions_status_check') THEN
+        ALTER TABLE payment_transactions ADD CONSTRAINT payment_transactions_status_check
+            CHECK (status IN ('pending', 'paid', 'closed'));
+    END IF;
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'payment_transactions_purpose_check') THEN
+        ALTER TABLE payment_transactions ADD CONSTRAINT payment_transactions_purpose_check
+            CHECK (purpose IN ('order', 'membership'));
+    END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_payment_transactions_order ON payment_transactions(order_id);
+CREATE INDEX IF NOT EXISTS idx_payment_transactions_pending ON payment_transactions(expires_at) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS idx_payment_transactions_paid ON payment_transactions(paid_at) WHERE status = 'paid';
+
+-- 对账批次: 每个支付渠道每天一条, 重跑覆盖
+CREATE TABLE IF NOT EXISTS payment_reconciliations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    provider VARCHAR(20) NOT NULL,
+    bill_date DATE NOT NULL,
+    status VARCHAR(20) NOT NULL,                       -- completed / failed (账单下载失败, 见 error)
+    provider_count INTEGER NOT NULL DEFAULT 0,
+    provider_amount_cents BIGINT NOT NULL DEFAULT 0,
+    local_count INTEGER NOT NULL DEFAULT 0,
+    local_amount_cents BIGINT NOT NULL DEFAULT 0,
+    discrepancy_count INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (provider, bill_date)
+);
+
+-- 差异明细: hq_id 为空表示账单里的交易不是本系统发起的
+CREATE TABLE IF NOT EXISTS payment_reconciliation_items (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    reconciliation_id UUID NOT NULL REFERENCES payment_reconciliations(id) ON DELETE CASCADE,
+    hq_id UUID REFERENCES hqs(id),
+    base_id UUID REFERENCES bases(id),
+    transaction_id UUID REFERENCES payment_transactions(id) ON DELETE CASCADE,
+    out_trade_no VARCHAR(32) NOT NULL,
+    provider_transaction_id VARCHAR(64),
+    kind VARCHAR(20) NOT NULL,     -- missing_local 漏单 / missing_provider 账单缺失 / amount_mismatch 金额不符 / unknown_trade 非本系统交易
+    provider_amount_cents INTEGER,
+    local_amount_cents INTEGER,
+    resolved BOOLEAN NOT NULL DEFAULT false, -- 漏单已按账单自动补单
+    note TEXT
+);
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'payment_reconciliation_items_kind_check') THEN
+        ALTER TABLE payment_reconciliation_items ADD CONSTRAINT payment_reconciliation_items_kind_check
+            CHECK (kind IN ('missing_local', 'missing_provider', 'amount_mismatch', 'unknown_trade'));
+    END IF;
+END $$;
+
+CREATE INDEX IF NOT EXISTS idx_payment_reconciliation_items_run ON payment_reconciliation_items(reconciliation_id);
+CREATE INDEX IF NOT EXISTS idx_payment_reconciliation_items_hq ON payment_reconciliation_items(hq_id) WHERE hq_id IS NOT NULL;
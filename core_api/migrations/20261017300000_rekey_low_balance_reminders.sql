@@ -0,0 +1,11 @@
+-- low_balance 提醒的 cycle_key 原先固定为 'low', 一张卡只能提醒一次
+-- 改为 'low:<到期日>:<最近一次延期/转让时间戳>', 续期或转卡后会重新提醒; 已有记录按新规则回填, 避免上线后重复提醒
+UPDATE membership_reminders r
+SET cycle_key = 'low:' || COALESCE(to_char(cm.expiry_date, 'YYYY-MM-DD'), '-') || ':' || COALESCE(
+        (SELECT extract(epoch FROM max(ma.created_at))::bigint::text
+         FROM membership_adjustments ma
+         WHERE ma.membership_id = cm.id AND ma.action IN ('extend', 'transfer')), '0')
+FROM customer_memberships cm
+WHERE r.membership_id = cm.id
+  AND r.kind = 'low_balance'
+  AND r.cycle_key = 'low';
@@ -0,0 +1,2 @@
+-- 员工登录按 lower(email) 查找账号 (与登录防爆破的错误计数口径一致), 补函数索引
+CREATE INDEX IF NOT EXISTS idx_users_email_lower ON users (lower(email));
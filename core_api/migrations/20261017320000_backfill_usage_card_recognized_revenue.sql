@@ -0,0 +1,16 @@
+-- 次卡消课确认收入时原先没有累计到 revenue_recognized_in_cents, 退卡无法结转单次价格除不尽的零头
+-- 改为次卡也累计 (拆分转让时转出的负债同样计入); 已有次卡按剩余次数回填:
+--   本卡持有的负债 = 单次价格 × 剩余次数 + 零头 (拆分出来的新卡不带零头), 已确认 = 售价 - 负债
+UPDATE customer_memberships cm
+SET revenue_recognized_in_cents = GREATEST(
+        mt.price_in_cents
+        - (mt.price_in_cents / mt.usage_count) * GREATEST(COALESCE(cm.remaining_uses, 0), 0)
+        - CASE WHEN EXISTS (
+                   SELECT 1 FROM membership_adjustments ma
+                   WHERE ma.target_membership_id = cm.id AND ma.membership_id <> cm.id AND ma.action = 'transfer')
+               THEN 0 ELSE mt.price_in_cents % mt.usage_count END,
+        0)
+FROM membership_tiers mt
+WHERE cm.tier_id = mt.id
+  AND mt.tier_type = 'usage_based'
+  AND mt.usage_count > 0;
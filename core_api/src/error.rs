@@ -0,0 +1,322 @@
+/*
+ * src/error.rs
+ * 职责: 全局 API 错误类型
+ * 1. 接口出错时统一返回 JSON: { "code": "enrollment.duplicate", "message": "该学员已报名此课程", "details": ... }
+ *    code 是稳定的机器可读错误码, 前端据此分支; message 是给用户看的中文提示; details 可选
+ * 2. 数据库约束冲突按约束名映射为具体错误码 (唯一约束 409, 外键 / CHECK 400 / 409), 其余数据库错误记日志后返回 500
+ * 3. 兼容: 裸 StatusCode 可以用 ? / .into() 转换, 错误码按状态码生成
+ */
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(&'static str),            // 400 参数不合法
+    Unauthorized(&'static str),          // 401 未登录 / 凭证错误
+    Forbidden(&'static str),             // 403 无权限
+    NotFound(&'static str),              // 404 不存在 (或不属于当前租户)
+    Conflict(&'static str),              // 409 与现有数据冲突 (重复报名 / 状态不允许)
+    Rejected(&'static str),              // 422 业务规则不满足 (会员卡过期 / 库存不足)
+    Custom(StatusCode, &'static str),    // 其他状态码 (423 / 429 / 502 / 503 ...)
+    Status(StatusCode),                  // 只有状态码 (旧代码), 错误码按状态码生成
+    WithDetails(Box<AppError>, Value),   // 附带 details (如排课冲突列表)
+    Database { context: &'static str, source: sqlx::Error },
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    code: &'static str,
+    message: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<Value>,
+}
+
+impl AppError {
+    pub fn with_details(self, details: Value) -> Self {
+        AppError::WithDetails(Box::new(self), details)
+    }
+
+    // 给 map_err 用: .map_err(AppError::db("Failed to create class"))?
+    pub fn db(context: &'static str) -> impl FnOnce(sqlx::Error) -> AppError {
+        move |source| AppError::Database { context, source }
+    }
+
+    // 非数据库的内部错误 (线程池 / 签名 / 序列化): 记日志后返回 500
+    pub fn internal<E: std::fmt::Display>(context: &'static str) -> impl FnOnce(E) -> AppError {
+        move |e| {
+            tracing::error!("{}: {}", context, e);
+            AppError::Status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+
+    fn parts(self) -> (StatusCode, &'static str, Option<Value>) {
+        match self {
+            AppError::BadRequest(code) => (StatusCode::BAD_REQUEST, code, None),
+            AppError::Unauthorized(code) => (StatusCode::UNAUTHORIZED, code, None),
+            AppError::Forbidden(code) => (StatusCode::FORBIDDEN, code, None),
+            AppError::NotFound(code) => (StatusCode::NOT_FOUND, code, None),
+            AppError::Conflict(code) => (StatusCode::CONFLICT, code, None),
+            AppError::Rejected(code) => (StatusCode::UNPROCESSABLE_ENTITY, code, None),
+            AppError::Custom(status, code) => (status, code, None),
+            AppError::Status(status) => (status, status_code(status), None),
+            AppError::WithDetails(inner, details) => {
+                let (status, code, _) = inner.parts();
+                (status, code, Some(details))
+            }
+            AppError::Database { context, source } => database_parts(context, source),
+        }
+    }
+}
+
+impl From<StatusCode> for AppError {
+    fn from(status: StatusCode) -> Self {
+        AppError::Status(status)
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(source: sqlx::Error) -> Self {
+        AppError::Database { context: "Database error", source }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, code, details) = self.parts();
+        let message = message_for(code).unwrap_or_else(|| status_message(status));
+        (status, Json(ErrorBody { code, message, details })).into_response()
+    }
+}
+
+// --- 数据库错误映射 ---
+
+fn database_parts(context: &'static str, source: sqlx::Error) -> (StatusCode, &'static str, Option<Value>) {
+    let db_err = match &source {
+        sqlx::Error::RowNotFound => return (StatusCode::NOT_FOUND, "resource.not_found", None),
+        sqlx::Error::Database(db_err) => db_err,
+        _ => {
+            tracing::error!("{}: {}", context, source);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "internal", None);
+        }
+    };
+
+    let constraint = db_err.constraint().map(str::to_string);
+    let (status, code) = match db_err.code().as_deref() {
+        Some("23505") => (StatusCode::CONFLICT, unique_violation_code(constraint.as_deref())),
+        // 删除 / 修改仍被引用的数据 vs 引用了不存在的数据
+        Some("23503") if db_err.message().contains("still referenced") => (StatusCode::CONFLICT, "resource.in_use"),
+        Some("23503") => (StatusCode::BAD_REQUEST, "resource.reference_invalid"),
+        Some("23514") => (StatusCode::BAD_REQUEST, check_violation_code(constraint.as_deref())),
+        Some("23502") => (StatusCode::BAD_REQUEST, "request.missing_field"),
+        Some("22P02") => (StatusCode::BAD_REQUEST, "request.invalid"), // 非法的枚举值 / uuid 文本
+        _ => {
+            tracing::error!("{}: {}", context, source);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "internal", None);
+        }
+    };
+    tracing::warn!("{}: {} ({:?})", context, db_err.message(), constraint);
+    (status, code, constraint.map(|c| serde_json::json!({ "constraint": c })))
+}
+
+// 唯一约束 -> 错误码 (约束名见各迁移 / 初始建表)
+fn unique_violation_code(constraint: Option<&str>) -> &'static str {
+    match constraint {
+        Some("users_email_key") => "user.email_taken",
+        Some("idx_customers_hq_phone") => "customer.phone_taken",
+        Some("class_enrollments_class_id_participant_id_key")
+        | Some("class_series_enrollments_series_id_participant_id_key") => "enrollment.duplicate",
+        Some("uq_class_waitlist_waiting") => "waitlist.duplicate",
+        Some("roles_hq_id_name_key_key") => "role.duplicate",
+        Some("asset_types_hq_id_name_key_key") => "asset_type.duplicate",
+        Some("honor_ranks_hq_id_rank_level_key") | Some("honor_ranks_hq_id_name_key_key") => "honor_rank.duplicate",
+        Some("orders_order_no_key") => "order.duplicate_no",
+        Some("ledger_accounts_hq_id_code_key") => "ledger.account_code_taken",
+        Some("qrcode_batches_batch_no_key") | Some("qrcode_items_short_code_key") => "qrcode.duplicate",
+        _ => "resource.duplicate",
+    }
+}
+
+fn check_violation_code(constraint: Option<&str>) -> &'static str {
+    match constraint {
+        Some("order_items_refunded_quantity_check") => "refund.quantity_exceeded",
+        Some("order_items_fulfillment_check") => "order.item_invalid",
+        Some(c) if c.contains("quantity") || c.contains("stock") => "stock.insufficient",
+        Some(c) if c.contains("remaining_uses") => "membership.no_remaining_uses",
+        Some("journal_entry_balanced") => "ledger.unbalanced",
+        Some("journal_lines_one_side") => "ledger.line_invalid",
+        Some("order_installments_amount_check") => "installment.invalid_amount",
+        Some("invoices_kind_check") => "invoice.invalid_amount",
+        _ => "request.constraint_violation",
+    }
+}
+
+// 只有状态码时的默认错误码
+fn status_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "request.invalid",
+        StatusCode::UNAUTHORIZED => "auth.unauthorized",
+        StatusCode::FORBIDDEN => "auth.forbidden",
+        StatusCode::NOT_FOUND => "resource.not_found",
+        StatusCode::CONFLICT => "resource.conflict",
+        StatusCode::PRECONDITION_FAILED => "request.precondition_failed",
+        StatusCode::UNPROCESSABLE_ENTITY => "request.unprocessable",
+        StatusCode::LOCKED => "auth.locked",
+        StatusCode::TOO_MANY_REQUESTS => "request.too_many",
+        StatusCode::BAD_GATEWAY => "upstream.failed",
+        StatusCode::SERVICE_UNAVAILABLE => "service.unavailable",
+        _ if status.is_client_error() => "request.invalid",
+        _ => "internal",
+    }
+}
+
+fn status_message(status: StatusCode) -> &'static str {
+    message_for(status_code(status)).unwrap_or("请求失败")
+}
+
+// --- 错误码 -> 中文提示 ---
+fn message_for(code: &str) -> Option<&'static str> {
+    Some(match code {
+        // 通用
+        "request.invalid" => "请求参数不正确",
+        "request.missing_field" => "缺少必填字段",
+        "request.constraint_violation" => "数据不符合要求",
+        "request.precondition_failed" => "前置条件不满足",
+        "request.unprocessable" => "当前操作不满足业务规则",
+        "request.too_many" => "操作过于频繁, 请稍后再试",
+        "list.invalid_page" => "分页参数不正确",
+        "list.invalid_sort" => "不支持按该字段排序",
+        "list.invalid_filter" => "筛选条件格式不正确",
+        "resource.not_found" => "数据不存在或无权访问",
+        "resource.conflict" => "当前状态不允许此操作",
+        "resource.duplicate" => "数据已存在",
+        "resource.in_use" => "数据仍在使用中, 无法删除",
+        "resource.reference_invalid" => "引用的数据不存在",
+        "upstream.failed" => "第三方服务调用失败, 请稍后再试",
+        "service.unavailable" => "服务暂不可用",
+        "internal" => "服务器内部错误",
+        // 登录 / 账号
+        "auth.unauthorized" => "未登录或登录已过期",
+        "auth.forbidden" => "没有权限执行此操作",
+        "auth.base_required" => "仅基地账号可执行此操作",
+        "auth.token_invalid" => "登录已过期, 请重新登录",
+        "auth.session_revoked" => "账号已在其他地方退出或密码已修改, 请重新登录",
+        "auth.invalid_credentials" => "邮箱或密码错误",
+        "auth.account_disabled" => "账号已停用",
+        "auth.locked" => "密码错误次数过多, 账号已临时锁定",
+        "auth.throttled" => "尝试过于频繁, 请稍后再试",
+        "auth.weak_password" => "密码至少 8 位, 需同时包含字母和数字, 且不能与邮箱相同",
+        "auth.same_password" => "新密码不能与当前密码相同",
+        "auth.reset_token_invalid" => "链接已失效, 请重新获取",
+        "auth.impersonation_forbidden" => "模拟登录会话不能执行此操作",
+        "user.email_taken" => "该邮箱已被使用",
+        // C 端 / 短信 / 微信
+        "customer.phone_taken" => "该手机号已绑定其他账号",
+        "customer.phone_invalid" => "手机号格式不正确",
+        "customer.wechat_required" => "请先通过微信登录",
+        "sms.code_invalid" => "验证码错误或已失效",
+        "sms.rate_limited" => "验证码发送过于频繁, 请稍后再试",
+        "sms.not_configured" => "短信服务未配置",
+        "wechat.not_configured" => "微信登录未配置",
+        "wechat.invalid_code" => "微信登录凭证无效, 请重试",
+        "wechat.rate_limited" => "微信登录过于频繁, 请稍后再试",
+        "wechat.api_failed" => "微信服务调用失败, 请稍后再试",
+        "wechat.decrypt_failed" => "微信数据解密失败",
+        // 排课 / 报名
+        "schedule.conflict" => "排课时间冲突",
+        "schedule.draft_not_editable" => "排课草稿已提交或已作废",
+        "schedule.teacher_unqualified" => "老师不具备该课程的授课资质",
+        "enrollment.duplicate" => "该学员已报名此课程",
+        "enrollment.already_closed" => "该报名已消课或请假, 不能重复操作",
+        "waitlist.duplicate" => "该学员已在候补名单中",
+        "room.in_use" => "教室仍有未结束的课程",
+        "substitution.teacher_unavailable" => "该老师不在可代课范围内 (资质 / 时间冲突 / 请假)",
+        // 会员卡
+        "membership.not_found" => "会员卡不存在",
+        "membership.not_owner" => "会员卡不属于该学员",
+        "membership.inactive" => "会员卡已停用",
+        "membership.frozen" => "会员卡已冻结",
+        "membership.not_frozen" => "会员卡未冻结",
+        "membership.not_started" => "会员卡尚未生效",
+        "membership.expired" => "会员卡已过期",
+        "membership.no_remaining_uses" => "会员卡剩余次数不足",
+        "membership.has_reservations" => "会员卡还有未消课的预约, 请先取消预约",
+        // 库存 / 订单
+        "stock.insufficient" => "库存不足",
+        "order.duplicate_no" => "订单号重复",
+        "order.customer_required" => "购买会员卡的订单须关联客户",
+        "order.item_invalid" => "订单明细不正确: 商品和卡种只能选一个, 卡种数量须为 1",
+        "payment.not_pending" => "该收款记录已审核",
+        // 订单退款
+        "refund.invalid_amount" => "退款金额须大于 0",
+        "refund.reason_required" => "请填写退款原因",
+        "refund.order_not_paid" => "订单没有可退的已付金额",
+        "refund.exceeds_paid" => "退款金额超过订单可退金额",
+        "refund.invalid_item" => "退回明细不属于该订单或数量不正确",
+        "refund.quantity_exceeded" => "退回数量超过可退数量",
+        // 订单分期
+        "installment.invalid_amount" => "每期金额须大于 0",
+        "installment.sum_mismatch" => "各期金额合计须等于订单总额",
+        "installment.order_closed" => "订单已取消或已退款, 不能设置分期",
+        // 电子发票
+        "invoice.buyer_required" => "请填写购方名称",
+        "invoice.type_invalid" => "发票类型不正确",
+        "invoice.tax_id_required" => "开具专票须填写购方纳税人识别号",
+        "invoice.tax_rate_invalid" => "税率不正确",
+        "invoice.order_cancelled" => "订单已取消, 不能开票",
+        "invoice.invalid_item" => "开票明细不属于该订单或数量不正确",
+        "invoice.quantity_exceeded" => "开票数量超过可开票数量",
+        "invoice.nothing_to_invoice" => "订单没有可开票的金额",
+        "invoice.exceeds_order" => "开票金额超过订单可开票金额",
+        "invoice.invalid_amount" => "发票金额不正确",
+        "invoice.not_pending" => "发票不是待开具或开具失败状态",
+        "invoice.not_reversible" => "只有已开具的蓝字发票可以冲红",
+        "invoice.already_reversed" => "发票已全部冲红",
+        "invoice.reason_required" => "请填写冲红原因",
+        "invoice.not_issued" => "发票尚未开具",
+        "invoice.not_configured" => "未配置电子发票服务",
+        "invoice.provider_rejected" => "开票平台拒绝开具, 请检查购方信息",
+        "invoice.issue_failed" => "开票失败, 请稍后重试",
+        // 在线支付
+        "payment.not_configured" => "未开通在线支付",
+        "payment.invalid_signature" => "支付通知签名校验失败",
+        "payment.trade_not_found" => "支付平台查无此交易",
+        "payment.provider_rejected" => "支付平台拒绝下单",
+        "payment.provider_failed" => "支付平台暂时不可用, 请稍后重试",
+        "payment.order_not_payable" => "订单当前不能在线支付",
+        "payment.tier_unavailable" => "该会员卡暂不支持在线购买",
+        "payment.base_required" => "请选择购卡基地",
+        "payment.amount_mismatch" => "支付金额与订单不符",
+        "payment.simulator_disabled" => "未启用支付模拟器",
+        "payment.bill_not_ready" => "只能对账今天之前的交易",
+        // 总账
+        "ledger.unbalanced" => "凭证借贷不平衡",
+        "ledger.line_invalid" => "分录不正确: 至少两行, 每行只能填借方或贷方金额",
+        "ledger.account_invalid" => "会计科目不存在或已停用",
+        "ledger.account_type_invalid" => "科目类别不正确",
+        "ledger.account_code_taken" => "科目编号已存在",
+        "ledger.system_account" => "系统科目不能停用",
+        "ledger.range_invalid" => "起止日期不正确",
+        "ledger.period_invalid" => "会计期间格式不正确 (YYYY-MM)",
+        "ledger.period_closed" => "该月份已结账, 不能再记账",
+        "ledger.period_open" => "该月份尚未结账",
+        "ledger.period_not_ended" => "该月份尚未结束, 不能结账",
+        "ledger.period_prior_open" => "之前还有未结账的月份, 请按顺序结账",
+        "ledger.period_later_closed" => "之后的月份已结账, 请先反结账之后的月份",
+        // 其他
+        "base.code_taken" => "该基地代号已存在",
+        "role.duplicate" => "角色已存在",
+        "asset_type.duplicate" => "资产类型已存在",
+        "honor_rank.duplicate" => "荣誉等级已存在",
+        "qrcode.duplicate" => "二维码编号重复",
+        "approval.invalid_type" => "不支持的审批类型",
+        "approval.invalid_action" => "不支持的审批操作",
+        "upload.invalid_file" => "上传文件读取失败",
+        _ => return None,
+    })
+}
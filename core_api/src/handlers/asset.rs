@@ -5,73 +5,67 @@
  */
 
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Path, State},
     http::StatusCode,
     Json,
 };
 use uuid::Uuid;
 
 use super::AppState;
+use crate::tenant::{Owned, TenantScope};
 // (★ 引入 AssetStatus)
 use crate::models::{
-    Asset, AssetDetail, AssetQuery, AssetStatus, AssetType, Claims, CreateAssetPayload, CreateAssetTypePayload, TransferAssetPayload,
+    Asset, AssetDetail, AssetStatus, AssetType, Claims, CreateAssetPayload, CreateAssetTypePayload, TransferAssetPayload,
 };
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
 
 // (GET) 总部查看全网资产台账
+// 分页; 筛选: base_id / status / asset_type_id; q 搜名称和序列号
+const ASSET_LIST: ListSpec = ListSpec {
+    sorts: &[
+        ("created_at", "a.created_at"),
+        ("purchase_date", "a.purchase_date"),
+        ("price", "a.price_in_cents"),
+        ("name", "a.name"),
+    ],
+    default_sort: "-created_at",
+    tiebreak: "a.id",
+    filters: &[
+        Filter::uuid("base_id", "a.base_id"),
+        Filter::text("status", "a.status"),
+        Filter::uuid("asset_type_id", "a.asset_type_id"),
+    ],
+    search: &["a.name", "a.serial_number"],
+};
+
 pub async fn get_all_assets_handler(
     State(state): State<AppState>,
     claims: Claims,
-    Query(params): Query<AssetQuery>,
-) -> Result<Json<Vec<AssetDetail>>, StatusCode> {
-    if !claims.roles.iter().any(|r| r == "role.hq.admin" || r == "role.hq.finance") {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
-    let mut query = String::from(
-        r#"
-        SELECT 
-            a.id, a.name, a.model_number, a.serial_number, 
-            a.status, -- (★ 不需要 ::text 了)
-            a.purchase_date, a.warranty_until, a.price_in_cents,
-            t.name_key as type_name,
-            b.id as base_id, b.name as base_name
-        FROM assets a
-        LEFT JOIN asset_types t ON a.asset_type_id = t.id
-        LEFT JOIN bases b ON a.base_id = b.id
-        WHERE a.hq_id = $1
-        "#,
-    );
-
-    if let Some(bid) = params.base_id {
-        query.push_str(&format!(" AND a.base_id = '{}'", bid));
-    }
-    if let Some(s) = params.status {
-        // 注意：status 是枚举，查询时可能需要单引号
-        // 但为了安全，最好用 bind，这里动态 SQL 拼接简单处理
-        query.push_str(&format!(" AND a.status = '{}'", s));
-    }
-    if let Some(k) = params.keyword {
-        if !k.is_empty() {
-            let safe_k = k.replace("'", "");
-            query.push_str(&format!(
-                " AND (a.name ILIKE '%{}%' OR a.serial_number ILIKE '%{}%')",
-                safe_k, safe_k
-            ));
-        }
-    }
-
-    query.push_str(" ORDER BY a.created_at DESC LIMIT 500");
-
-    let assets = sqlx::query_as::<_, AssetDetail>(&query)
-        .bind(claims.hq_id)
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to fetch assets: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    Ok(Json(assets))
+    list: ListQuery,
+) -> Result<Json<Page<AssetDetail>>, AppError> {
+    let hq_id = claims.hq_id;
+
+    let page = list
+        .fetch_page(&state.db_pool, &ASSET_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    a.id, a.name, a.model_number, a.serial_number,
+                    a.status, -- (★ 不需要 ::text 了)
+                    a.purchase_date, a.warranty_until, a.price_in_cents,
+                    t.name_key as type_name,
+                    b.id as base_id, b.name as base_name
+                FROM assets a
+                LEFT JOIN asset_types t ON a.asset_type_id = t.id
+                LEFT JOIN bases b ON a.base_id = b.id
+                WHERE a.hq_id = "#,
+            );
+            qb.push_bind(hq_id);
+        })
+        .await?;
+
+    Ok(Json(page))
 }
 
 // (POST) 录入新实物资产
@@ -79,9 +73,13 @@ pub async fn create_asset_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateAssetPayload>,
-) -> Result<Json<Asset>, StatusCode> {
-    if !claims.roles.contains(&"role.hq.admin".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
+) -> Result<Json<Asset>, AppError> {
+    let scope = TenantScope::from_claims(&claims);
+    if let Some(base_id) = payload.base_id {
+        scope.ensure_owned(&state.db_pool, Owned::Base(base_id)).await?;
+    }
+    if let Some(asset_type_id) = payload.asset_type_id {
+        scope.ensure_owned(&state.db_pool, Owned::AssetType(asset_type_id)).await?;
     }
 
     let price_cents = (payload.price.unwrap_or(0.0) * 100.0) as i32;
@@ -110,10 +108,7 @@ pub async fn create_asset_handler(
     .bind(price_cents)
     .fetch_one(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to create asset: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to create asset"))?;
 
     Ok(Json(new_asset))
 }
@@ -124,10 +119,8 @@ pub async fn transfer_asset_handler(
     claims: Claims,
     Path(id): Path<Uuid>,
     Json(payload): Json<TransferAssetPayload>,
-) -> Result<StatusCode, StatusCode> {
-    if !claims.roles.contains(&"role.hq.admin".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<StatusCode, AppError> {
+    TenantScope::from_claims(&claims).ensure_owned(&state.db_pool, Owned::Base(payload.target_base_id)).await?;
 
     let result = sqlx::query(
         "UPDATE assets SET base_id = $1, updated_at = NOW() WHERE id = $2 AND hq_id = $3",
@@ -137,13 +130,10 @@ pub async fn transfer_asset_handler(
     .bind(claims.hq_id)
     .execute(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to transfer asset: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to transfer asset"))?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(AppError::NotFound("resource.not_found"));
     }
     Ok(StatusCode::OK)
 }
@@ -153,20 +143,16 @@ pub async fn delete_asset_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(id): Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
-    if !claims.roles.contains(&"role.hq.admin".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<StatusCode, AppError> {
 
     let res = sqlx::query("DELETE FROM assets WHERE id = $1 AND hq_id = $2")
         .bind(id)
         .bind(claims.hq_id)
         .execute(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
     if res.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(AppError::NotFound("resource.not_found"));
     }
     Ok(StatusCode::NO_CONTENT)
 }
@@ -176,7 +162,7 @@ pub async fn delete_asset_handler(
 pub async fn get_asset_types_handler(
     State(state): State<AppState>,
     claims: Claims, // <-- 【修改】必须出示“钥匙”
-) -> Result<Json<Vec<AssetType>>, StatusCode> {
+) -> Result<Json<Vec<AssetType>>, AppError> {
     
     // (HACK 已移除!)
     let hq_id = claims.hq_id; // <-- 【修改】使用“钥匙”中的租户ID
@@ -194,10 +180,7 @@ pub async fn get_asset_types_handler(
     .await
     {
         Ok(types) => types,
-        Err(e) => {
-            tracing::error!("Failed to fetch asset types: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to fetch asset types", source: e }),
     };
 
     Ok(Json(asset_types))
@@ -209,22 +192,9 @@ pub async fn create_asset_type_handler(
     State(state): State<AppState>,
     claims: Claims, // <-- 【修改】必须出示“钥匙”
     Json(payload): Json<CreateAssetTypePayload>,
-) -> Result<Json<AssetType>, StatusCode> {
+) -> Result<Json<AssetType>, AppError> {
     
-    // --- (★ 新增：角色安全守卫 ★) ---
-    let is_authorized = claims.roles.iter().any(|role| 
-        role == "role.hq.admin"
-    );
-
-    if !is_authorized {
-        tracing::warn!(
-            "Unauthorized attempt to create asset type by user {} (roles: {:?})",
-            claims.sub,
-            claims.roles
-        );
-        return Err(StatusCode::FORBIDDEN); // 403 Forbidden
-    }
-    // --- (守卫结束) ---
+    // (权限: 路由声明 hq(perm::ASSETS_MANAGE))
 
     // (HACK 已移除!)
     let hq_id = claims.hq_id; // <-- 【修改】使用“钥匙”中的租户ID
@@ -243,10 +213,7 @@ pub async fn create_asset_type_handler(
     .await
     {
         Ok(asset_type) => asset_type,
-        Err(e) => {
-            tracing::error!("Failed to create asset type: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to create asset type", source: e }),
     };
 
     Ok(Json(new_asset_type))
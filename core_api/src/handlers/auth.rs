@@ -2,64 +2,43 @@
  * core_api/src/handlers/auth.rs
  * (★ V21.2 - 修复版: 修复 bcrypt 闭包类型推断错误 ★)
  */
+use std::net::SocketAddr;
+
 use axum::{
-    extract::{State},
-    http::{StatusCode},
-    response::{IntoResponse, Response},
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap},
     Json,
 };
+use serde::Serialize;
 use sqlx::{Row};
 use uuid::Uuid;
-use bcrypt::{hash, verify, DEFAULT_COST}; // 确保引入 hash 和 verify
-use jsonwebtoken::{encode, Header, EncodingKey};
-use chrono::{Utc, Duration};
-use tokio::task; 
+use chrono::Utc;
 
-use super::AppState;
+use super::{
+    check_login_allowed, check_password_policy, hash_password, issue_password_reset_token, open_session,
+    record_login_attempt, verify_password, AppState, LoginBlock, PasswordResetToken, LOGIN_FAILED_CREDENTIALS,
+    LOGIN_FAILED_DISABLED, LOGIN_FAILED_LOCKED, LOGIN_FAILED_THROTTLED, RESET_PURPOSE_FIRST_LOGIN, SUBJECT_USER,
+};
+use crate::middleware::client_ip;
 use crate::models::{Claims, User, AuthBody, AuthResponse}; 
-
-// 错误处理 (保留)
-#[derive(Debug)]
-pub enum CustomAuthError {
-    TokenMissing,
-    TokenInvalid,
-    InternalError,
-}
-
-impl IntoResponse for CustomAuthError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            CustomAuthError::TokenMissing => (StatusCode::UNAUTHORIZED, "Missing authorization token"),
-            CustomAuthError::TokenInvalid => (StatusCode::UNAUTHORIZED, "Invalid authorization token"),
-            CustomAuthError::InternalError => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
-        };
-        (status, Json(serde_json::json!({"error": error_message}))).into_response()
-    }
-}
+use crate::error::AppError;
 
 pub async fn register_handler(
     State(state): State<AppState>,
     Json(payload): Json<AuthBody>,
-) -> Result<Json<User>, StatusCode> {
+) -> Result<Json<User>, AppError> {
     
     // 1. 获取租户ID (取第一个作为默认)
     let hq_id = match sqlx::query_scalar::<_, Uuid>("SELECT id FROM hqs LIMIT 1")
         .fetch_one(&state.db_pool)
         .await {
         Ok(id) => id,
-        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => return Err(e.into()),
     };
 
-    // 2. 密码加密 (修复类型推断)
-    let password_to_hash = payload.password.clone();
-    
-    // 我们显式指定闭包的返回类型，帮助编译器推断
-    let password_hash = task::spawn_blocking(move || -> Result<String, bcrypt::BcryptError> {
-        hash(&password_to_hash, DEFAULT_COST)
-    })
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? // JoinError
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?; // BcryptError
+    // 2. 密码策略 + 加密
+    check_password_policy(&payload.password, &payload.email)?;
+    let password_hash = hash_password(payload.password.clone()).await?;
 
     // 3. 插入数据库 (★ 关键修复: NULL as role_name ★)
     let new_user = match sqlx::query_as::<_, User>(
@@ -80,107 +59,151 @@ pub async fn register_handler(
     .await
     {
         Ok(user) => user,
-        Err(e) => {
-            tracing::error!("Register DB Error: {:?}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        },
+        Err(e) => return Err(AppError::Database { context: "Register DB Error", source: e }),
     };
 
     Ok(Json(new_user))
 }
 
+// 登录结果: 正常签发会话; 或者必须先改密码 (管理员创建 / 重置的账号), 此时只返回一次性改密令牌
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Session(AuthResponse),
+    PasswordChangeRequired {
+        must_change_password: bool,
+        #[serde(flatten)]
+        reset: PasswordResetToken,
+    },
+}
+
 pub async fn login_handler(
     State(state): State<AppState>,
+    connect: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(payload): Json<AuthBody>,
-) -> Result<Json<AuthResponse>, StatusCode> {
-    
-    // 1. 查询用户
+) -> Result<Json<LoginResponse>, AppError> {
+    let ip = client_ip(connect, &headers);
+    let user_agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+    let record = |user: Option<(Uuid, Uuid)>, reason: Option<&'static str>| {
+        record_login_attempt(&state.db_pool, &payload.email, user, ip.as_deref(), user_agent, reason)
+    };
+
+    // 0. 防爆破: 连续输错后逐次延迟 / 锁定; 被拦截时不校验密码
+    let block = check_login_allowed(&state.db_pool, &payload.email, ip.as_deref())
+        .await
+        .map_err(AppError::db("Failed to check login throttle"))?;
+    if let Some(block) = block {
+        let reason = match block {
+            LoginBlock::Locked(_) => LOGIN_FAILED_LOCKED,
+            LoginBlock::Throttled => LOGIN_FAILED_THROTTLED,
+        };
+        tracing::warn!("Login blocked: email={} ip={:?} {:?}", payload.email, ip, block);
+        record(None, Some(reason)).await;
+        return Err(block.into());
+    }
+
+    // 1. 查询用户: 邮箱不区分大小写, 与防爆破的错误计数一致
     let user_query = sqlx::query(
         r#"
         SELECT 
-            u.id, u.password_hash, u.hq_id, u.base_id, u.is_active, u.password_changed_at, u.full_name,
-            b.name as base_name, b.logo_url as base_logo
+            u.id, u.password_hash, u.hq_id, u.is_active, u.must_change_password
         FROM users u
-        LEFT JOIN bases b ON u.base_id = b.id
-        WHERE u.email = $1
+        WHERE lower(u.email) = lower($1)
+        ORDER BY u.email = $1 DESC
+        LIMIT 1
         "#
     )
     .bind(&payload.email)
     .fetch_optional(&state.db_pool)
     .await;
 
+    // 邮箱不存在也按密码错误计数, 不暴露账号是否存在
     let user_row = match user_query {
         Ok(Some(row)) => row,
-        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
-        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Ok(None) => {
+            record(None, Some(LOGIN_FAILED_CREDENTIALS)).await;
+            return Err(AppError::Unauthorized(LOGIN_FAILED_CREDENTIALS));
+        }
+        Err(e) => return Err(e.into()),
     };
 
     let user_id: Uuid = user_row.get("id");
     let password_hash: String = user_row.get("password_hash");
     let hq_id: Uuid = user_row.get("hq_id");
-    let base_id: Option<Uuid> = user_row.get("base_id");
     let is_active: bool = user_row.get("is_active");
-    let full_name: String = user_row.get("full_name");
-    let base_name: Option<String> = user_row.get("base_name");
-    let base_logo: Option<String> = user_row.get("base_logo");
-
-    // 2. 验证密码 (bcrypt) - 修复类型推断
-    let password_to_verify = payload.password.clone();
-    let stored_hash = password_hash.clone(); // 克隆一份给闭包使用
-
-    // 同样显式指定返回类型
-    let valid_password = task::spawn_blocking(move || -> Result<bool, bcrypt::BcryptError> {
-        verify(&password_to_verify, &stored_hash)
-    })
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? // JoinError
-    .unwrap_or(false); // BcryptError -> treat as false (invalid password)
+    let must_change_password: bool = user_row.get("must_change_password");
+
+    // 2. 验证密码 (bcrypt)
+    let valid_password = verify_password(payload.password.clone(), password_hash).await?;
 
     if !valid_password || !is_active {
-        return Err(StatusCode::UNAUTHORIZED);
+        let reason = if valid_password { LOGIN_FAILED_DISABLED } else { LOGIN_FAILED_CREDENTIALS };
+        record(Some((user_id, hq_id)), Some(reason)).await;
+        return Err(AppError::Unauthorized(reason));
     }
 
-    // 3. 获取角色
-    let roles: Vec<String> = sqlx::query_scalar(
+    // 初始密码 / 重置后的账号: 不签发会话, 先用一次性令牌设置新密码 (POST /api/v1/auth/password/reset)
+    if must_change_password {
+        let mut conn = state.db_pool.acquire().await?;
+        let reset = issue_password_reset_token(&mut conn, user_id, RESET_PURPOSE_FIRST_LOGIN, None)
+            .await
+            .map_err(AppError::db("Failed to issue password change token"))?;
+        record(Some((user_id, hq_id)), None).await;
+        return Ok(Json(LoginResponse::PasswordChangeRequired { must_change_password: true, reset }));
+    }
+
+    // 3. 新建登录会话, 签发 Access Token + Refresh Token
+    let mut tx = state.db_pool.begin().await?;
+    let session = open_session(&mut tx, &state.jwt_secret, SUBJECT_USER, user_id)
+        .await?
+        .ok_or(AppError::Unauthorized("auth.unauthorized"))?;
+    tx.commit().await?;
+
+    // 4. 记录日志 (成功登录后错误次数重新计算)
+    record(Some((user_id, hq_id)), None).await;
+
+    Ok(Json(LoginResponse::Session(session.tokens)))
+}
+// 按用户 id 组装 Claims (与密码登录签发的内容一致), 用户不存在或已停用时返回 None
+pub async fn load_user_claims(
+    conn: &mut sqlx::PgConnection,
+    user_id: Uuid,
+    sid: Uuid,
+    expires_at: chrono::DateTime<Utc>,
+) -> Result<Option<Claims>, sqlx::Error> {
+    let row = sqlx::query(
         r#"
-        SELECT r.name_key
-        FROM user_roles ur
-        JOIN roles r ON ur.role_id = r.id
-        WHERE ur.user_id = $1
-        "#
+        SELECT u.hq_id, u.base_id, u.full_name, b.name as base_name, b.logo_url as base_logo
+        FROM users u
+        LEFT JOIN bases b ON u.base_id = b.id
+        WHERE u.id = $1 AND u.is_active = true
+        "#,
     )
     .bind(user_id)
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .fetch_optional(&mut *conn)
+    .await?;
 
-    // 4. 生成 Token
-    let claims = Claims {
-        sub: user_id.to_string(),
-        hq_id,
-        base_id,
-        roles,
-        base_name, 
-        base_logo,
-        full_name,
-        exp: (Utc::now() + Duration::days(1)).timestamp() as usize,
+    let Some(row) = row else {
+        return Ok(None);
     };
 
-    let token = encode(
-        &Header::default(), 
-        &claims, 
-        &EncodingKey::from_secret(state.jwt_secret.as_ref())
-    )
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // 5. 记录日志
-    let _ = sqlx::query(
-        r#"INSERT INTO user_login_history (email_attempted, user_id, hq_id, status) VALUES ($1, $2, $3, 'success')"#
+    let roles: Vec<String> = sqlx::query_scalar(
+        "SELECT r.name_key FROM user_roles ur JOIN roles r ON ur.role_id = r.id WHERE ur.user_id = $1",
     )
-    .bind(&payload.email)
     .bind(user_id)
-    .bind(hq_id)
-    .execute(&state.db_pool).await;
+    .fetch_all(&mut *conn)
+    .await?;
 
-    Ok(Json(AuthResponse { token }))
-}
\ No newline at end of file
+    Ok(Some(Claims {
+        sub: user_id.to_string(),
+        hq_id: row.get("hq_id"),
+        base_id: row.get("base_id"),
+        roles,
+        base_name: row.get("base_name"),
+        base_logo: row.get("base_logo"),
+        full_name: row.get("full_name"),
+        sid,
+        exp: expires_at.timestamp() as usize,
+    }))
+}
@@ -6,18 +6,18 @@
  */
 use axum::{
     extract::{State, Path}, 
-    http::StatusCode, 
     Json
 };
 use uuid::Uuid;
 use super::AppState;
 use crate::models::{Base, Claims, CreateBasePayload, UpdateBasePayload}; 
+use crate::error::AppError;
 
 // GET /api/v1/bases - 获取基地列表
 pub async fn get_hq_bases_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<Base>>, StatusCode> {
+) -> Result<Json<Vec<Base>>, AppError> {
     
     // ★★★ 修复后的 SQL ★★★
     let bases = match sqlx::query_as::<_, Base>(
@@ -63,26 +63,39 @@ pub async fn get_hq_bases_handler(
     .await
     {
         Ok(bases) => bases,
-        Err(e) => {
-            tracing::error!("Failed to fetch bases stats: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to fetch bases stats", source: e }),
     };
 
     Ok(Json(bases))
 }
 
+// 基地代号用于生成订单号等, 同一总部内不能重复
+async fn ensure_base_code_free(state: &AppState, hq_id: Uuid, code: &str, exclude: Option<Uuid>) -> Result<(), AppError> {
+    let taken: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM bases WHERE hq_id = $1 AND upper(code) = $2 AND id IS DISTINCT FROM $3)",
+    )
+    .bind(hq_id)
+    .bind(code)
+    .bind(exclude)
+    .fetch_one(&state.db_pool)
+    .await?;
+    if taken {
+        return Err(AppError::Conflict("base.code_taken"));
+    }
+    Ok(())
+}
+
 // POST /api/v1/bases - 创建
 pub async fn create_hq_base_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateBasePayload>,
-) -> Result<Json<Base>, StatusCode> {
-    let is_authorized = claims.roles.iter().any(|role| role == "role.hq.admin");
-    if !is_authorized { return Err(StatusCode::FORBIDDEN); }
+) -> Result<Json<Base>, AppError> {
 
     let code = payload.code.trim().to_uppercase();
-    if code.len() < 2 || code.len() > 5 { return Err(StatusCode::BAD_REQUEST); }
+    if code.len() < 2 || code.len() > 5 { return Err(AppError::BadRequest("request.invalid")); }
+
+    ensure_base_code_free(&state, claims.hq_id, &code, None).await?;
 
     let new_base = sqlx::query_as::<_, Base>(
         r#"
@@ -104,10 +117,7 @@ pub async fn create_hq_base_handler(
     .bind(payload.auth_end_date)
     .fetch_one(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to create base: {}", e);
-        StatusCode::CONFLICT 
-    })?;
+    .map_err(AppError::db("Failed to create base"))?;
 
     Ok(Json(new_base))
 }
@@ -118,12 +128,11 @@ pub async fn update_hq_base_handler(
     claims: Claims,
     Path(base_id): Path<Uuid>,
     Json(payload): Json<UpdateBasePayload>,
-) -> Result<Json<Base>, StatusCode> {
-    let is_authorized = claims.roles.iter().any(|role| role == "role.hq.admin");
-    if !is_authorized { return Err(StatusCode::FORBIDDEN); }
+) -> Result<Json<Base>, AppError> {
 
     let code = payload.code.trim().to_uppercase();
-    if code.len() < 2 || code.len() > 5 { return Err(StatusCode::BAD_REQUEST); }
+    if code.len() < 2 || code.len() > 5 { return Err(AppError::BadRequest("request.invalid")); }
+    ensure_base_code_free(&state, claims.hq_id, &code, Some(base_id)).await?;
 
     let updated_base = sqlx::query_as::<_, Base>(
         r#"
@@ -161,10 +170,7 @@ pub async fn update_hq_base_handler(
     .bind(claims.hq_id)
     .fetch_one(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to update base: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to update base"))?;
 
     Ok(Json(updated_base))
 }
\ No newline at end of file
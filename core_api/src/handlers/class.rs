@@ -14,8 +14,10 @@ use sqlx::{QueryBuilder};
 use uuid::Uuid;
 use chrono::{DateTime, Utc, Duration};
 
-use super::AppState;
+use super::{ensure_slot_in_scope, find_schedule_conflicts, AppState, ProposedSlot, schedule_conflict_error};
+use crate::tenant::TenantScope;
 use crate::models::{Claims, Class, CreateClassPayload, ClassDetail};
+use crate::error::AppError;
 
 // --- DTO: 查询参数 ---
 #[derive(Debug, Deserialize)]
@@ -37,7 +39,7 @@ pub async fn get_base_classes_handler(
     State(state): State<AppState>,
     claims: Claims, 
     Query(query): Query<GetClassesQuery>, 
-) -> Result<Json<Vec<ClassDetail>>, StatusCode> {
+) -> Result<Json<Vec<ClassDetail>>, AppError> {
 
     let hq_id = claims.hq_id;
 
@@ -45,7 +47,7 @@ pub async fn get_base_classes_handler(
         Some(id) => id,
         None => {
             tracing::warn!("User {} without base_id tried to access classes", claims.sub);
-            return Err(StatusCode::FORBIDDEN); 
+            return Err(AppError::Forbidden("auth.forbidden")); 
         }
     };
 
@@ -88,10 +90,7 @@ pub async fn get_base_classes_handler(
 
     let classes = match query_builder.build_query_as::<ClassDetail>().fetch_all(&state.db_pool).await {
         Ok(classes) => classes,
-        Err(e) => {
-            tracing::error!("Failed to fetch base classes: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to fetch base classes", source: e }),
     };
     
     Ok(Json(classes))
@@ -102,21 +101,25 @@ pub async fn create_base_class_handler(
     State(state): State<AppState>,
     claims: Claims, 
     Json(payload): Json<CreateClassPayload>,
-) -> Result<Json<Vec<Class>>, StatusCode> {
+) -> Result<Json<Vec<Class>>, AppError> {
 
     let hq_id = claims.hq_id;
     let base_id = match claims.base_id {
         Some(id) => id,
-        None => return Err(StatusCode::FORBIDDEN), 
+        None => return Err(AppError::Forbidden("auth.forbidden")), 
     };
 
     let recurrence = payload.recurrence_type.as_deref().unwrap_or("none");
     let count = if recurrence == "none" { 1 } else { payload.repeat_count.unwrap_or(1) };
     
-    if count > 50 { return Err(StatusCode::BAD_REQUEST); }
+    if count > 50 { return Err(AppError::BadRequest("request.invalid")); }
+    if payload.end_time <= payload.start_time { return Err(AppError::BadRequest("request.invalid")); }
 
     let mut created_classes = Vec::new();
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut conflicts = Vec::new();
+    let mut tx = state.db_pool.begin().await?;
+    let scope = TenantScope::from_claims(&claims);
+    ensure_slot_in_scope(&mut tx, &scope, Some(payload.course_id), Some(payload.room_id), &payload.teacher_ids).await?;
 
     for i in 0..count {
         let days_to_add = match recurrence {
@@ -128,6 +131,20 @@ pub async fn create_base_class_handler(
         let current_start = payload.start_time + Duration::days(days_to_add as i64);
         let current_end = payload.end_time + Duration::days(days_to_add as i64);
 
+        // (★ 冲突校验: 教室 / 老师占用 / 老师可上课时段)
+        let slot = ProposedSlot {
+            class_id: None,
+            room_id: payload.room_id,
+            teacher_ids: &payload.teacher_ids,
+            start_time: current_start,
+            end_time: current_end,
+        };
+        let found = find_schedule_conflicts(&mut tx, &slot).await.map_err(AppError::db("Failed to check schedule conflicts"))?;
+        if !found.is_empty() {
+            conflicts.extend(found);
+            continue;
+        }
+
         let new_class = sqlx::query_as::<_, Class>(
             r#"
             INSERT INTO classes (
@@ -147,10 +164,7 @@ pub async fn create_base_class_handler(
         .bind(payload.max_capacity)
         .fetch_one(&mut *tx)
         .await
-        .map_err(|e| {
-            tracing::error!("Failed to create class: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(AppError::db("Failed to create class"))?;
 
         for teacher_id in &payload.teacher_ids {
              sqlx::query("INSERT INTO class_teachers (class_id, teacher_id) VALUES ($1, $2)")
@@ -158,16 +172,18 @@ pub async fn create_base_class_handler(
                 .bind(teacher_id)
                 .execute(&mut *tx)
                 .await
-                .map_err(|e| {
-                    tracing::error!("Failed to link teacher: {}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?;
+                .map_err(AppError::db("Failed to link teacher"))?;
         }
 
         created_classes.push(new_class);
     }
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // 任意一节有冲突则整体回滚, 返回全部冲突明细
+    if !conflicts.is_empty() {
+        return Err(schedule_conflict_error(conflicts));
+    }
+
+    tx.commit().await?;
     Ok(Json(created_classes))
 }
 
@@ -177,12 +193,51 @@ pub async fn update_class_handler(
     claims: Claims,
     Path(class_id): Path<Uuid>,
     Json(payload): Json<UpdateClassPayload>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AppError> {
     
     let hq_id = claims.hq_id;
-    let base_id = match claims.base_id { Some(id) => id, None => return Err(StatusCode::FORBIDDEN) };
+    let base_id = match claims.base_id { Some(id) => id, None => return Err(AppError::Forbidden("auth.base_required")) };
+
+    let mut tx = state.db_pool.begin().await?;
+
+    // 1. 锁定当前排课, 合并出修改后的时段用于冲突校验
+    let current: Option<(Uuid, DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT room_id, start_time, end_time FROM classes WHERE id = $1 AND hq_id = $2 AND base_id = $3 FOR UPDATE"
+    )
+    .bind(class_id)
+    .bind(hq_id)
+    .bind(base_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to load class"))?;
+
+    let (cur_room, cur_start, cur_end) = current.ok_or(AppError::NotFound("resource.not_found"))?;
+    let scope = TenantScope::from_claims(&claims);
+    ensure_slot_in_scope(&mut tx, &scope, None, payload.room_id, payload.teacher_ids.as_deref().unwrap_or_default()).await?;
 
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let teacher_ids = match &payload.teacher_ids {
+        Some(ids) => ids.clone(),
+        None => sqlx::query_scalar("SELECT teacher_id FROM class_teachers WHERE class_id = $1")
+            .bind(class_id)
+            .fetch_all(&mut *tx)
+            .await?,
+    };
+
+    let slot = ProposedSlot {
+        class_id: Some(class_id),
+        room_id: payload.room_id.unwrap_or(cur_room),
+        teacher_ids: &teacher_ids,
+        start_time: payload.start_time.unwrap_or(cur_start),
+        end_time: payload.end_time.unwrap_or(cur_end),
+    };
+    if slot.end_time <= slot.start_time {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let conflicts = find_schedule_conflicts(&mut tx, &slot).await.map_err(AppError::db("Failed to check schedule conflicts"))?;
+    if !conflicts.is_empty() {
+        return Err(schedule_conflict_error(conflicts));
+    }
 
     if payload.room_id.is_some() || payload.start_time.is_some() || payload.end_time.is_some() {
         let mut query_builder: QueryBuilder<sqlx::Postgres> = QueryBuilder::new("UPDATE classes SET ");
@@ -208,30 +263,25 @@ pub async fn update_class_handler(
         query_builder.push(" AND base_id = ");
         query_builder.push_bind(base_id);
         
-        query_builder.build().execute(&mut *tx).await.map_err(|e| {
-            tracing::error!("Failed to update class info: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        query_builder.build().execute(&mut *tx).await.map_err(AppError::db("Failed to update class info"))?;
     }
 
     if let Some(teacher_ids) = payload.teacher_ids {
         sqlx::query("DELETE FROM class_teachers WHERE class_id = $1")
             .bind(class_id)
             .execute(&mut *tx)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .await?;
             
         for tid in teacher_ids {
             sqlx::query("INSERT INTO class_teachers (class_id, teacher_id) VALUES ($1, $2)")
                 .bind(class_id)
                 .bind(tid)
                 .execute(&mut *tx)
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                .await?;
         }
     }
     
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
     Ok(StatusCode::OK)
 }
 
@@ -240,9 +290,9 @@ pub async fn delete_class_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(class_id): Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AppError> {
     let hq_id = claims.hq_id;
-    let base_id = match claims.base_id { Some(id) => id, None => return Err(StatusCode::FORBIDDEN) };
+    let base_id = match claims.base_id { Some(id) => id, None => return Err(AppError::Forbidden("auth.base_required")) };
 
     let result = sqlx::query(
         "DELETE FROM classes WHERE id = $1 AND hq_id = $2 AND base_id = $3"
@@ -252,13 +302,10 @@ pub async fn delete_class_handler(
     .bind(base_id)
     .execute(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to delete class: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to delete class"))?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(AppError::NotFound("resource.not_found"));
     }
 
     Ok(StatusCode::NO_CONTENT)
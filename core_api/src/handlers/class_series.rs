@@ -0,0 +1,784 @@
+/*
+ * src/handlers/class_series.rs
+ * 职责: 循环排课 (Class Series) - 按周/双周规则展开为 classes 行
+ * 支持: 节假日例外、"仅本节" / "本节及以后" 修改、延长系列、系列报名自动带入每一节 (满员场次进入候补)
+ * 日期 (结束日期 / 例外日期) 一律按业务时区判断, 见 crate::timezone
+ */
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::{
+    enroll_or_waitlist, find_participant_conflicts, find_schedule_conflicts, AppState,
+    ensure_slot_in_scope, EnrollOutcome, ProposedSlot, schedule_conflict_error,
+};
+use crate::models::{Claims, Class};
+use crate::tenant::{Owned, TenantScope};
+use crate::timezone::business_date;
+use crate::error::AppError;
+
+// 单个系列最多展开的节数 (约两个学期)
+const MAX_SERIES_OCCURRENCES: i64 = 60;
+
+// ==========================================
+// DTOs
+// ==========================================
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ClassSeries {
+    pub id: Uuid,
+    pub hq_id: Uuid,
+    pub base_id: Uuid,
+    pub course_id: Uuid,
+    pub room_id: Uuid,
+    pub teacher_ids: Vec<Uuid>,
+    pub max_capacity: i32,
+    pub recurrence_type: String,
+    pub first_start_time: DateTime<Utc>,
+    pub first_end_time: DateTime<Utc>,
+    pub end_date: NaiveDate,
+    pub excluded_dates: Vec<NaiveDate>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateClassSeriesPayload {
+    pub course_id: Uuid,
+    pub teacher_ids: Vec<Uuid>,
+    pub room_id: Uuid,
+    pub max_capacity: i32,
+    pub recurrence_type: String, // weekly / biweekly
+    pub start_time: DateTime<Utc>, // 首节课
+    pub end_time: DateTime<Utc>,
+    pub end_date: NaiveDate,
+    #[serde(default)]
+    pub excluded_dates: Vec<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClassSeriesWithClasses {
+    pub series: ClassSeries,
+    pub classes: Vec<Class>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOccurrencePayload {
+    pub scope: String, // this / future
+    pub teacher_ids: Option<Vec<Uuid>>,
+    pub room_id: Option<Uuid>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddExclusionPayload {
+    pub date: NaiveDate,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExtendSeriesPayload {
+    pub end_date: NaiveDate,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnrollSeriesPayload {
+    pub participant_id: Uuid,
+    pub customer_membership_id: Option<Uuid>,
+}
+
+// ==========================================
+// 工具函数
+// ==========================================
+
+fn recurrence_step(recurrence_type: &str) -> Option<Duration> {
+    match recurrence_type {
+        "weekly" => Some(Duration::days(7)),
+        "biweekly" => Some(Duration::days(14)),
+        _ => None,
+    }
+}
+
+// 按规则计算全部场次 (跳过例外日期), 日期按业务时区判断
+fn expand_occurrences(
+    first_start: DateTime<Utc>,
+    first_end: DateTime<Utc>,
+    step: Duration,
+    end_date: NaiveDate,
+    excluded: &[NaiveDate],
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut result = Vec::new();
+    for i in 0..MAX_SERIES_OCCURRENCES {
+        let start = first_start + step * i as i32;
+        let date = business_date(start);
+        if date > end_date {
+            break;
+        }
+        if excluded.contains(&date) {
+            continue;
+        }
+        result.push((start, first_end + step * i as i32));
+    }
+    result
+}
+
+async fn fetch_series(
+    conn: &mut sqlx::PgConnection,
+    series_id: Uuid,
+    hq_id: Uuid,
+    base_id: Uuid,
+) -> Result<ClassSeries, AppError> {
+    sqlx::query_as::<_, ClassSeries>(
+        r#"
+        SELECT id, hq_id, base_id, course_id, room_id, teacher_ids, max_capacity, recurrence_type,
+               first_start_time, first_end_time, end_date, excluded_dates, created_at
+        FROM class_series
+        WHERE id = $1 AND hq_id = $2 AND base_id = $3
+        FOR UPDATE
+        "#,
+    )
+    .bind(series_id)
+    .bind(hq_id)
+    .bind(base_id)
+    .fetch_optional(conn)
+    .await
+    .map_err(AppError::db("Failed to fetch class series"))?
+    .ok_or(AppError::NotFound("resource.not_found"))
+}
+
+// 展开一节课: 写入 classes + class_teachers, 并把已报名整个系列的学员带入本节
+async fn materialise_occurrence(
+    conn: &mut sqlx::PgConnection,
+    series: &ClassSeries,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Class, AppError> {
+    let class = sqlx::query_as::<_, Class>(
+        r#"
+        INSERT INTO classes (
+            hq_id, base_id, course_id, room_id,
+            start_time, end_time, max_capacity, status, series_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, 'scheduled', $8)
+        RETURNING *
+        "#,
+    )
+    .bind(series.hq_id)
+    .bind(series.base_id)
+    .bind(series.course_id)
+    .bind(series.room_id)
+    .bind(start)
+    .bind(end)
+    .bind(series.max_capacity)
+    .bind(series.id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to create series class"))?;
+
+    for teacher_id in &series.teacher_ids {
+        sqlx::query("INSERT INTO class_teachers (class_id, teacher_id) VALUES ($1, $2)")
+            .bind(class.id)
+            .bind(teacher_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(AppError::db("Failed to link teacher"))?;
+    }
+
+    carry_series_enrollments(conn, series, class.id).await?;
+    Ok(class)
+}
+
+// 系列学员自动报名新展开的场次: 与学员其他课程冲突 / 会员卡不可用的跳过, 满员进入候补
+async fn carry_series_enrollments(
+    conn: &mut sqlx::PgConnection,
+    series: &ClassSeries,
+    class_id: Uuid,
+) -> Result<(), AppError> {
+    let enrollees: Vec<(Uuid, Uuid, Option<Uuid>)> = sqlx::query_as(
+        r#"
+        SELECT participant_id, customer_id, customer_membership_id
+        FROM class_series_enrollments
+        WHERE series_id = $1
+        ORDER BY created_at ASC
+        "#,
+    )
+    .bind(series.id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to fetch series enrollments"))?;
+
+    for (participant_id, customer_id, membership_id) in enrollees {
+        let found = find_participant_conflicts(&mut *conn, participant_id, class_id)
+            .await
+            .map_err(AppError::db("Failed to check participant conflicts"))?;
+        if !found.is_empty() {
+            continue;
+        }
+        enroll_or_waitlist(&mut *conn, series.hq_id, class_id, participant_id, customer_id, membership_id)
+            .await
+            .map_err(AppError::db("Failed to enroll series class"))?;
+    }
+    Ok(())
+}
+
+// ==========================================
+// 1. 创建系列并展开 (POST /api/v1/base/class-series)
+// ==========================================
+pub async fn create_class_series_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<CreateClassSeriesPayload>,
+) -> Result<Json<ClassSeriesWithClasses>, AppError> {
+    let hq_id = claims.hq_id;
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    let user_id = Uuid::parse_str(&claims.sub).ok();
+
+    let step = recurrence_step(&payload.recurrence_type).ok_or(AppError::BadRequest("request.invalid"))?;
+    if payload.end_time <= payload.start_time {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let occurrences = expand_occurrences(
+        payload.start_time,
+        payload.end_time,
+        step,
+        payload.end_date,
+        &payload.excluded_dates,
+    );
+    if occurrences.is_empty() {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let mut tx = state.db_pool.begin().await?;
+    let tenant = TenantScope::from_claims(&claims);
+    ensure_slot_in_scope(&mut tx, &tenant, Some(payload.course_id), Some(payload.room_id), &payload.teacher_ids).await?;
+
+    // 1. 系列规则
+    let series = sqlx::query_as::<_, ClassSeries>(
+        r#"
+        INSERT INTO class_series (
+            hq_id, base_id, course_id, room_id, teacher_ids, max_capacity,
+            recurrence_type, first_start_time, first_end_time, end_date, excluded_dates, created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING id, hq_id, base_id, course_id, room_id, teacher_ids, max_capacity, recurrence_type,
+                  first_start_time, first_end_time, end_date, excluded_dates, created_at
+        "#,
+    )
+    .bind(hq_id)
+    .bind(base_id)
+    .bind(payload.course_id)
+    .bind(payload.room_id)
+    .bind(&payload.teacher_ids)
+    .bind(payload.max_capacity)
+    .bind(&payload.recurrence_type)
+    .bind(payload.start_time)
+    .bind(payload.end_time)
+    .bind(payload.end_date)
+    .bind(&payload.excluded_dates)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to create class series"))?;
+
+    // 2. 逐节冲突校验 + 写入
+    let mut classes = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for (start, end) in occurrences {
+        let slot = ProposedSlot {
+            class_id: None,
+            room_id: payload.room_id,
+            teacher_ids: &payload.teacher_ids,
+            start_time: start,
+            end_time: end,
+        };
+        let found = find_schedule_conflicts(&mut tx, &slot).await.map_err(AppError::db("Failed to check schedule conflicts"))?;
+        if !found.is_empty() {
+            conflicts.extend(found);
+            continue;
+        }
+
+        classes.push(materialise_occurrence(&mut tx, &series, start, end).await?);
+    }
+
+    if !conflicts.is_empty() {
+        return Err(schedule_conflict_error(conflicts));
+    }
+
+    tx.commit().await?;
+    Ok(Json(ClassSeriesWithClasses { series, classes }))
+}
+
+// ==========================================
+// 2. 系列列表 (GET /api/v1/base/class-series)
+// ==========================================
+pub async fn get_class_series_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<ClassSeries>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let list = sqlx::query_as::<_, ClassSeries>(
+        r#"
+        SELECT id, hq_id, base_id, course_id, room_id, teacher_ids, max_capacity, recurrence_type,
+               first_start_time, first_end_time, end_date, excluded_dates, created_at
+        FROM class_series
+        WHERE hq_id = $1 AND base_id = $2
+        ORDER BY first_start_time DESC
+        "#,
+    )
+    .bind(claims.hq_id)
+    .bind(base_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to fetch class series"))?;
+
+    Ok(Json(list))
+}
+
+// ==========================================
+// 3. 修改某一节 (PATCH /api/v1/base/class-series/:id/occurrences/:class_id)
+// scope = this   : 仅修改本节
+// scope = future : 本节及以后所有未上的课按同样的偏移量平移, 并同步教室/老师
+// ==========================================
+pub async fn update_series_occurrence_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((series_id, class_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<UpdateOccurrencePayload>,
+) -> Result<Json<Vec<Class>>, AppError> {
+    let hq_id = claims.hq_id;
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let series = fetch_series(&mut tx, series_id, hq_id, base_id).await?;
+
+    let anchor = sqlx::query_as::<_, Class>(
+        "SELECT * FROM classes WHERE id = $1 AND series_id = $2 FOR UPDATE",
+    )
+    .bind(class_id)
+    .bind(series.id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    let tenant = TenantScope::from_claims(&claims);
+    ensure_slot_in_scope(&mut tx, &tenant, None, payload.room_id, payload.teacher_ids.as_deref().unwrap_or_default()).await?;
+
+    let new_start = payload.start_time.unwrap_or(anchor.start_time);
+    let new_end = payload.end_time.unwrap_or(anchor.end_time);
+    if new_end <= new_start {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+    let shift = new_start - anchor.start_time;
+    let length = new_end - new_start;
+
+    // 1. 确定受影响的场次
+    let targets: Vec<Class> = match payload.scope.as_str() {
+        "this" => vec![anchor],
+        "future" => sqlx::query_as::<_, Class>(
+            r#"
+            SELECT * FROM classes
+            WHERE series_id = $1 AND start_time >= $2
+              AND COALESCE(status, 'scheduled') = 'scheduled'
+            ORDER BY start_time ASC
+            FOR UPDATE
+            "#,
+        )
+        .bind(series.id)
+        .bind(anchor.start_time)
+        .fetch_all(&mut *tx)
+        .await?,
+        _ => return Err(AppError::BadRequest("request.invalid")),
+    };
+
+    // 2. 逐节校验并更新
+    let mut updated = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for class in targets {
+        let teacher_ids: Vec<Uuid> = match &payload.teacher_ids {
+            Some(ids) => ids.clone(),
+            None => sqlx::query_scalar("SELECT teacher_id FROM class_teachers WHERE class_id = $1")
+                .bind(class.id)
+                .fetch_all(&mut *tx)
+                .await?,
+        };
+        let start = class.start_time + shift;
+        let slot = ProposedSlot {
+            class_id: Some(class.id),
+            room_id: payload.room_id.unwrap_or(class.room_id),
+            teacher_ids: &teacher_ids,
+            start_time: start,
+            end_time: start + length,
+        };
+
+        let found = find_schedule_conflicts(&mut tx, &slot).await.map_err(AppError::db("Failed to check schedule conflicts"))?;
+        if !found.is_empty() {
+            conflicts.extend(found);
+            continue;
+        }
+
+        let row = sqlx::query_as::<_, Class>(
+            "UPDATE classes SET room_id = $1, start_time = $2, end_time = $3 WHERE id = $4 RETURNING *",
+        )
+        .bind(slot.room_id)
+        .bind(slot.start_time)
+        .bind(slot.end_time)
+        .bind(class.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to update series class"))?;
+
+        if payload.teacher_ids.is_some() {
+            sqlx::query("DELETE FROM class_teachers WHERE class_id = $1")
+                .bind(class.id)
+                .execute(&mut *tx)
+                .await?;
+            for tid in &teacher_ids {
+                sqlx::query("INSERT INTO class_teachers (class_id, teacher_id) VALUES ($1, $2)")
+                    .bind(class.id)
+                    .bind(tid)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        updated.push(row);
+    }
+
+    if !conflicts.is_empty() {
+        return Err(schedule_conflict_error(conflicts));
+    }
+
+    // 3. "本节及以后" 同步更新系列规则
+    if payload.scope == "future" {
+        sqlx::query(
+            r#"
+            UPDATE class_series
+            SET room_id = COALESCE($1, room_id),
+                teacher_ids = COALESCE($2, teacher_ids),
+                first_start_time = first_start_time + $3,
+                first_end_time = first_start_time + $3 + $4,
+                updated_at = NOW()
+            WHERE id = $5
+            "#,
+        )
+        .bind(payload.room_id)
+        .bind(payload.teacher_ids.as_ref())
+        .bind(shift)
+        .bind(length)
+        .bind(series.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to update class series"))?;
+    }
+
+    tx.commit().await?;
+    Ok(Json(updated))
+}
+
+// ==========================================
+// 4. 添加例外日期 (POST /api/v1/base/class-series/:id/exclusions)
+// 当天已展开的场次标记为 cancelled
+// ==========================================
+pub async fn add_series_exclusion_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(series_id): Path<Uuid>,
+    Json(payload): Json<AddExclusionPayload>,
+) -> Result<Json<ClassSeries>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let series = fetch_series(&mut tx, series_id, claims.hq_id, base_id).await?;
+
+    let occurrences: Vec<(Uuid, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT id, start_time FROM classes WHERE series_id = $1 AND COALESCE(status, 'scheduled') = 'scheduled'",
+    )
+    .bind(series.id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let cancel_ids: Vec<Uuid> = occurrences
+        .into_iter()
+        .filter(|(_, start)| business_date(*start) == payload.date)
+        .map(|(id, _)| id)
+        .collect();
+
+    sqlx::query("UPDATE classes SET status = 'cancelled' WHERE id = ANY($1)")
+        .bind(&cancel_ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to cancel series class"))?;
+
+    let series = sqlx::query_as::<_, ClassSeries>(
+        r#"
+        UPDATE class_series
+        SET excluded_dates = array_append(excluded_dates, $1), updated_at = NOW()
+        WHERE id = $2 AND NOT ($1 = ANY(excluded_dates))
+        RETURNING id, hq_id, base_id, course_id, room_id, teacher_ids, max_capacity, recurrence_type,
+                  first_start_time, first_end_time, end_date, excluded_dates, created_at
+        "#,
+    )
+    .bind(payload.date)
+    .bind(series.id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .unwrap_or(series);
+
+    tx.commit().await?;
+    Ok(Json(series))
+}
+
+// ==========================================
+// 5. 延长系列 (POST /api/v1/base/class-series/:id/extend)
+// 按当前规则 (含 "本节及以后" 修改后的时间 / 教室 / 老师) 展开新增的场次, 系列学员自动带入
+// ==========================================
+pub async fn extend_class_series_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(series_id): Path<Uuid>,
+    Json(payload): Json<ExtendSeriesPayload>,
+) -> Result<Json<ClassSeriesWithClasses>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let series = fetch_series(&mut tx, series_id, claims.hq_id, base_id).await?;
+    if payload.end_date < series.end_date {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+    let step = recurrence_step(&series.recurrence_type).ok_or(AppError::BadRequest("request.invalid"))?;
+
+    // 已展开 (含已取消) 的最后一节之后才是新场次
+    let last_start: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT MAX(start_time) FROM classes WHERE series_id = $1")
+        .bind(series.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let occurrences: Vec<_> = expand_occurrences(
+        series.first_start_time,
+        series.first_end_time,
+        step,
+        payload.end_date,
+        &series.excluded_dates,
+    )
+    .into_iter()
+    .filter(|(start, _)| last_start.is_none_or(|last| *start > last))
+    .collect();
+
+    let mut classes = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for (start, end) in occurrences {
+        let slot = ProposedSlot {
+            class_id: None,
+            room_id: series.room_id,
+            teacher_ids: &series.teacher_ids,
+            start_time: start,
+            end_time: end,
+        };
+        let found = find_schedule_conflicts(&mut tx, &slot).await.map_err(AppError::db("Failed to check schedule conflicts"))?;
+        if !found.is_empty() {
+            conflicts.extend(found);
+            continue;
+        }
+        classes.push(materialise_occurrence(&mut tx, &series, start, end).await?);
+    }
+
+    if !conflicts.is_empty() {
+        return Err(schedule_conflict_error(conflicts));
+    }
+
+    let series = sqlx::query_as::<_, ClassSeries>(
+        r#"
+        UPDATE class_series SET end_date = $1, updated_at = NOW()
+        WHERE id = $2
+        RETURNING id, hq_id, base_id, course_id, room_id, teacher_ids, max_capacity, recurrence_type,
+                  first_start_time, first_end_time, end_date, excluded_dates, created_at
+        "#,
+    )
+    .bind(payload.end_date)
+    .bind(series.id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to update class series"))?;
+
+    tx.commit().await?;
+    Ok(Json(ClassSeriesWithClasses { series, classes }))
+}
+
+// ==========================================
+// 6. 报名整个系列 (POST /api/v1/base/class-series/:id/enrollments)
+// 自动报名所有尚未开始的场次, 与学员其他课程冲突的场次跳过并返回明细
+// ==========================================
+pub async fn enroll_series_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(series_id): Path<Uuid>,
+    Json(payload): Json<EnrollSeriesPayload>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let hq_id = claims.hq_id;
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let series = fetch_series(&mut tx, series_id, hq_id, base_id).await?;
+    TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::Participant(payload.participant_id)).await?;
+
+    let customer_id: Uuid = sqlx::query_scalar("SELECT customer_id FROM participants WHERE id = $1 AND hq_id = $2")
+        .bind(payload.participant_id)
+        .bind(hq_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO class_series_enrollments (hq_id, series_id, participant_id, customer_id, customer_membership_id)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (series_id, participant_id) DO UPDATE SET customer_membership_id = EXCLUDED.customer_membership_id
+        "#,
+    )
+    .bind(hq_id)
+    .bind(series.id)
+    .bind(payload.participant_id)
+    .bind(customer_id)
+    .bind(payload.customer_membership_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to enroll series"))?;
+
+    let class_ids: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM classes
+        WHERE series_id = $1 AND start_time > NOW()
+          AND COALESCE(status, 'scheduled') = 'scheduled'
+        ORDER BY start_time ASC
+        "#,
+    )
+    .bind(series.id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut enrolled = 0;
+    let mut waitlisted = 0;
+    let mut rejected = Vec::new(); // 会员卡不可用 (如次数不足) 的场次
+    let mut conflicts = Vec::new();
+
+    for class_id in class_ids {
+        let found = find_participant_conflicts(&mut tx, payload.participant_id, class_id)
+            .await
+            .map_err(AppError::db("Failed to check participant conflicts"))?;
+        if !found.is_empty() {
+            conflicts.extend(found);
+            continue;
+        }
+
+        let exists: Option<Uuid> = sqlx::query_scalar(
+            "SELECT id FROM class_enrollments WHERE class_id = $1 AND participant_id = $2",
+        )
+        .bind(class_id)
+        .bind(payload.participant_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if exists.is_some() {
+            continue;
+        }
+
+        // 满员的场次进入候补
+        let outcome = enroll_or_waitlist(
+            &mut tx,
+            hq_id,
+            class_id,
+            payload.participant_id,
+            customer_id,
+            payload.customer_membership_id,
+        )
+        .await
+        .map_err(AppError::db("Failed to enroll series class"))?;
+        match outcome {
+            EnrollOutcome::Enrolled(_) => enrolled += 1,
+            EnrollOutcome::Waitlisted(_) => waitlisted += 1,
+            EnrollOutcome::Rejected(reason) => rejected.push(serde_json::json!({ "class_id": class_id, "error": reason })),
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(serde_json::json!({
+        "series_id": series.id,
+        "classes_enrolled": enrolled,
+        "classes_waitlisted": waitlisted,
+        "rejected": rejected,
+        "conflicts": conflicts,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn weekly_series_stops_at_end_date_inclusive() {
+        // 2026-10-17 (周六) 10:00 北京时间
+        let start = utc(2026, 10, 17, 2, 0);
+        let got = expand_occurrences(start, start + Duration::hours(1), Duration::days(7), date(2026, 10, 31), &[]);
+        let starts: Vec<_> = got.iter().map(|(s, _)| *s).collect();
+        assert_eq!(starts, vec![start, start + Duration::days(7), start + Duration::days(14)]);
+        assert!(got.iter().all(|(s, e)| *e - *s == Duration::hours(1)));
+    }
+
+    #[test]
+    fn biweekly_series_skips_alternate_weeks() {
+        let start = utc(2026, 10, 17, 2, 0);
+        let got = expand_occurrences(start, start + Duration::hours(1), Duration::days(14), date(2026, 11, 14), &[]);
+        let dates: Vec<_> = got.iter().map(|(s, _)| business_date(*s)).collect();
+        assert_eq!(dates, vec![date(2026, 10, 17), date(2026, 10, 31), date(2026, 11, 14)]);
+    }
+
+    #[test]
+    fn excluded_dates_are_skipped_without_shifting_the_rule() {
+        let start = utc(2026, 10, 17, 2, 0);
+        let got = expand_occurrences(start, start + Duration::hours(1), Duration::days(7), date(2026, 10, 31), &[date(2026, 10, 24)]);
+        let dates: Vec<_> = got.iter().map(|(s, _)| business_date(*s)).collect();
+        assert_eq!(dates, vec![date(2026, 10, 17), date(2026, 10, 31)]);
+    }
+
+    #[test]
+    fn dates_are_judged_in_business_timezone() {
+        // UTC 周五 16:30 = 北京时间周六 00:30, 应算作周六
+        let start = utc(2026, 10, 16, 16, 30);
+        let got = expand_occurrences(start, start + Duration::hours(1), Duration::days(7), date(2026, 10, 17), &[]);
+        assert_eq!(got.len(), 1);
+
+        // 例外日期同样按北京时间: 排除周六即排除这一节
+        let got = expand_occurrences(start, start + Duration::hours(1), Duration::days(7), date(2026, 10, 24), &[date(2026, 10, 17)]);
+        assert_eq!(got.iter().map(|(s, _)| business_date(*s)).collect::<Vec<_>>(), vec![date(2026, 10, 24)]);
+
+        // 排除 UTC 日期 (周五) 不影响
+        let got = expand_occurrences(start, start + Duration::hours(1), Duration::days(7), date(2026, 10, 17), &[date(2026, 10, 16)]);
+        assert_eq!(got.len(), 1);
+    }
+
+    #[test]
+    fn expansion_is_capped() {
+        let start = utc(2026, 1, 3, 2, 0);
+        let got = expand_occurrences(start, start + Duration::hours(1), Duration::days(7), date(2030, 1, 1), &[]);
+        assert_eq!(got.len() as i64, MAX_SERIES_OCCURRENCES);
+    }
+}
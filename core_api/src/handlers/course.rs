@@ -14,15 +14,16 @@ use uuid::Uuid;
 use super::{AppState, toggle_status_common};
 // (★ 修复: 添加 UpdateStatusPayload)
 use crate::models::{Claims, Course, CreateCoursePayload, UpdateStatusPayload, UpdateCoursePayload};
+use crate::error::AppError;
 
 // (GET)
 pub async fn get_courses_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<Course>>, StatusCode> {
+) -> Result<Json<Vec<Course>>, AppError> {
     let hq_id = claims.hq_id;
     let courses = sqlx::query_as::<_, Course>("SELECT * FROM courses WHERE hq_id = $1 ORDER BY name_key ASC")
-        .bind(hq_id).fetch_all(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .bind(hq_id).fetch_all(&state.db_pool).await?;
     Ok(Json(courses))
 }
 
@@ -30,8 +31,7 @@ pub async fn create_course_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateCoursePayload>,
-) -> Result<Json<Course>, StatusCode> {
-    if !claims.roles.contains(&"role.hq.admin".to_string()) { return Err(StatusCode::FORBIDDEN); }
+) -> Result<Json<Course>, AppError> {
     
     let new_course = sqlx::query_as::<_, Course>(
         r#"
@@ -53,10 +53,7 @@ pub async fn create_course_handler(
     .bind(payload.prerequisite_course_id)
     .bind(payload.cover_url)    // (★)
     .bind(payload.introduction) // (★)
-    .fetch_one(&state.db_pool).await.map_err(|e| {
-        tracing::error!("Create course failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .fetch_one(&state.db_pool).await.map_err(AppError::db("Create course failed"))?;
 
     Ok(Json(new_course))
 }
@@ -66,8 +63,7 @@ pub async fn update_course_handler(
     claims: Claims,
     Path(id): Path<Uuid>,
     Json(payload): Json<UpdateCoursePayload>,
-) -> Result<Json<Course>, StatusCode> {
-    if !claims.roles.contains(&"role.hq.admin".to_string()) { return Err(StatusCode::FORBIDDEN); }
+) -> Result<Json<Course>, AppError> {
 
     let updated = sqlx::query_as::<_, Course>(
         r#"
@@ -88,10 +84,7 @@ pub async fn update_course_handler(
     .bind(payload.introduction)
     .bind(id)
     .bind(claims.hq_id)
-    .fetch_one(&state.db_pool).await.map_err(|e| {
-        tracing::error!("Update course failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .fetch_one(&state.db_pool).await.map_err(AppError::db("Update course failed"))?;
 
     Ok(Json(updated))
 }
@@ -102,7 +95,6 @@ pub async fn toggle_course_status_handler(
     claims: Claims,
     Path(id): Path<Uuid>,
     Json(payload): Json<UpdateStatusPayload>,
-) -> Result<StatusCode, StatusCode> {
-    if !claims.roles.contains(&"role.hq.admin".to_string()) { return Err(StatusCode::FORBIDDEN); }
+) -> Result<StatusCode, AppError> {
     toggle_status_common(&state.db_pool, "courses", id, claims.hq_id, payload.is_active).await
 }
\ No newline at end of file
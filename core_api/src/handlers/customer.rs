@@ -5,7 +5,6 @@
 
 use axum::{
     extract::{State, Query},
-    http::StatusCode,
     Json,
 };
 use serde::Serialize;
@@ -16,6 +15,9 @@ use chrono::NaiveDate;
 use super::AppState;
 // 导入 models
 use crate::models::{Claims, Customer, CreateCustomerPayload};
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+use crate::tenant::TenantScope;
 
 // ==========================================
 // C端API响应模型
@@ -81,9 +83,9 @@ pub struct PointTrendItem {
 pub async fn get_customer_profile_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<CustomerProfileResponse>, StatusCode> {
+) -> Result<Json<CustomerProfileResponse>, AppError> {
     // 从claims.sub中获取customer_id
-    let customer_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let customer_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::BadRequest("request.invalid"))?;
 
     // 首先找到customer
     let customer = sqlx::query_as::<_, Customer>(
@@ -95,11 +97,8 @@ pub async fn get_customer_profile_handler(
     .bind(customer_id)
     .fetch_optional(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch customer: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .map_err(AppError::db("Failed to fetch customer"))?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
 
     // 获取该customer下的所有participants及其荣誉信息
     let participants = sqlx::query_as::<_, ParticipantWithHonorRaw>(
@@ -130,10 +129,7 @@ pub async fn get_customer_profile_handler(
     .bind(claims.hq_id)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch participants with honor: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch participants with honor"))?;
 
     let participants_with_honor: Vec<ParticipantWithHonor> = participants
         .into_iter()
@@ -187,7 +183,7 @@ pub async fn create_customer_handler(
     State(state): State<AppState>,
     claims: Claims, // <-- 自动验证 Token, 获取 Claims
     Json(payload): Json<CreateCustomerPayload>,
-) -> Result<Json<Customer>, StatusCode> {
+) -> Result<Json<Customer>, AppError> {
     
     let hq_id = claims.hq_id;
 
@@ -195,11 +191,12 @@ pub async fn create_customer_handler(
         Some(id) => id,
         None => {
             tracing::warn!("User {} without base_id tried to create customer", claims.sub);
-            return Err(StatusCode::FORBIDDEN); // 403 Forbidden
+            return Err(AppError::Forbidden("auth.forbidden")); // 403 Forbidden
         }
     };
 
-    let new_customer = match sqlx::query_as::<_, Customer>(
+    // 同总部手机号重复时返回 409 customer.phone_taken
+    let new_customer = sqlx::query_as::<_, Customer>(
         r#"
         INSERT INTO customers (hq_id, base_id, name, phone_number)
         VALUES ($1, $2, $3, $4)
@@ -212,78 +209,49 @@ pub async fn create_customer_handler(
     .bind(&payload.phone_number)
     .fetch_one(&state.db_pool)
     .await
-    {
-        Ok(customer) => customer,
-        Err(e) => {
-            if let Some(db_err) = e.as_database_error() {
-                if db_err.is_unique_violation() {
-                    tracing::warn!("Failed to create customer, phone number already exists: {}", payload.phone_number);
-                    return Err(StatusCode::CONFLICT); // 409 Conflict
-                }
-            }
-            tracing::error!("Failed to create customer: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
+    .map_err(AppError::db("Failed to create customer"))?;
 
     Ok(Json(new_customer))
 }
 
 // (GET /api/v1/customers - 获取客户列表)
+// 分页; 筛选: base_id / customer_type / lead_source / assigned_sales / created_from / created_to; q 搜姓名和手机号
+const CUSTOMER_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "created_at"), ("name", "name"), ("last_contact_at", "last_contact_at")],
+    default_sort: "-created_at",
+    tiebreak: "id",
+    filters: &[
+        Filter::uuid("base_id", "base_id"),
+        Filter::text("customer_type", "customer_type"),
+        Filter::text("lead_source", "lead_source"),
+        Filter::uuid("assigned_sales", "assigned_sales"),
+        Filter::date_from("created_from", "created_at"),
+        Filter::date_to("created_to", "created_at"),
+    ],
+    search: &["name", "phone_number"],
+};
+
 pub async fn get_customers_handler(
     State(state): State<AppState>,
-    claims: Claims, // <-- 自动验证 Token, 获取 Claims
-) -> Result<Json<Vec<Customer>>, StatusCode> {
-    
-    let customers: Vec<Customer>;
-
-    if let Some(base_id) = claims.base_id {
-        // --- 场景 A: 基地员工 ---
-        tracing::debug!("Fetching customers for base_id: {}", base_id);
-        customers = sqlx::query_as::<_, Customer>(
-            r#"
-            SELECT * FROM customers
-            WHERE hq_id = $1 AND base_id = $2
-            ORDER BY created_at DESC
-            "#,
-        )
-        .bind(claims.hq_id)
-        .bind(base_id)
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to fetch base customers: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-        
-    } else {
-        // --- 场景 B: 租户管理员 (无 base_id) ---
-        tracing::debug!("Fetching all customers for hq_id: {}", claims.hq_id);
-        customers = sqlx::query_as::<_, Customer>(
-            r#"
-            SELECT * FROM customers
-            WHERE hq_id = $1
-            ORDER BY base_id, created_at DESC
-            "#,
-        )
-        .bind(claims.hq_id)
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to fetch all hq customers: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    }
-
-    Ok(Json(customers))
+    scope: TenantScope, // 基地员工只看本基地, 总部看全部
+    list: ListQuery,
+) -> Result<Json<Page<Customer>>, AppError> {
+    let page = list
+        .fetch_page(&state.db_pool, &CUSTOMER_LIST, |qb| {
+            qb.push("SELECT * FROM customers WHERE 1 = 1");
+            scope.push_filter(qb, "customers");
+        })
+        .await?;
+
+    Ok(Json(page))
 }
 
 // GET /api/v1/customer/orders - 获取C端用户订单
 pub async fn get_customer_orders_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<crate::models::OrderDetail>>, StatusCode> {
-    let customer_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::BAD_REQUEST)?;
+) -> Result<Json<Vec<crate::models::OrderDetail>>, AppError> {
+    let customer_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::BadRequest("request.invalid"))?;
 
     let orders = sqlx::query_as::<_, crate::models::OrderDetail>(
         r#"
@@ -295,10 +263,7 @@ pub async fn get_customer_orders_handler(
     .bind(customer_id)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch customer orders: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch customer orders"))?;
 
     Ok(Json(orders))
 }
@@ -307,7 +272,7 @@ pub async fn get_customer_orders_handler(
 pub async fn get_customer_membership_tiers_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<crate::models::MembershipTier>>, StatusCode> {
+) -> Result<Json<Vec<crate::models::MembershipTier>>, AppError> {
     // 默认返回当前总部下处于激活状态的会员等级
     let tiers = sqlx::query_as::<_, crate::models::MembershipTier>(
         r#"
@@ -319,10 +284,7 @@ pub async fn get_customer_membership_tiers_handler(
     .bind(claims.hq_id)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch membership tiers: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch membership tiers"))?;
 
     Ok(Json(tiers))
 }
@@ -331,10 +293,19 @@ pub async fn get_customer_membership_tiers_handler(
 pub async fn get_customer_notices_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
+) -> Result<Json<Vec<serde_json::Value>>, AppError> {
     // 获取该客户所属的基地ID (从claims.base_id 获取，如果后端在登录时没塞，则需要从数据库查)
-    // 我们的 dev_token_ 已经塞了 base_id
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+    let customer_id = Uuid::parse_str(&claims.sub).ok();
+    let base_id = match claims.base_id {
+        Some(id) => id,
+        None => sqlx::query_scalar::<_, Option<Uuid>>("SELECT base_id FROM customers WHERE id = $1 AND hq_id = $2")
+            .bind(customer_id)
+            .bind(claims.hq_id)
+            .fetch_optional(&state.db_pool)
+            .await?
+            .flatten()
+            .ok_or(AppError::Forbidden("auth.forbidden"))?,
+    };
 
     let notices = sqlx::query!(
         r#"
@@ -347,12 +318,36 @@ pub async fn get_customer_notices_handler(
     )
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch customer notices: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch customer notices"))?;
 
-    let result = notices.into_iter().map(|n| {
+    // 个人续费提醒 (会员卡即将到期 / 次数不足), 未读的置顶显示
+    let reminders = sqlx::query!(
+        r#"
+        SELECT id, title, content, created_at
+        FROM customer_notifications
+        WHERE customer_id = $1 AND hq_id = $2 AND is_read = false
+          AND category IN ('membership_expiring', 'membership_low_balance')
+        ORDER BY created_at DESC
+        "#,
+        customer_id,
+        claims.hq_id
+    )
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to fetch membership reminders"))?;
+
+    let mut result: Vec<serde_json::Value> = reminders.into_iter().map(|r| {
+        serde_json::json!({
+            "id": r.id,
+            "title": r.title,
+            "content": r.content,
+            "priority": "high",
+            "category": "membership_reminder",
+            "created_at": r.created_at
+        })
+    }).collect();
+
+    result.extend(notices.into_iter().map(|n| {
         serde_json::json!({
             "id": n.id,
             "title": n.title,
@@ -360,7 +355,7 @@ pub async fn get_customer_notices_handler(
             "priority": n.priority,
             "created_at": n.created_at
         })
-    }).collect();
+    }));
 
     Ok(Json(result))
 }
@@ -370,9 +365,9 @@ pub async fn get_customer_participant_report_handler(
     State(state): State<AppState>,
     _claims: Claims,
     Query(params): Query<std::collections::HashMap<String, String>>,
-) -> Result<Json<CustomerReportResponse>, StatusCode> {
-    let participant_id_str = params.get("participant_id").ok_or(StatusCode::BAD_REQUEST)?;
-    let participant_id = Uuid::parse_str(participant_id_str).map_err(|_| StatusCode::BAD_REQUEST)?;
+) -> Result<Json<CustomerReportResponse>, AppError> {
+    let participant_id_str = params.get("participant_id").ok_or(AppError::BadRequest("request.invalid"))?;
+    let participant_id = Uuid::parse_str(participant_id_str).map_err(|_| AppError::BadRequest("request.invalid"))?;
 
     // 1. 获取学员基本信息与勋章级别
     let info = sqlx::query!(
@@ -387,7 +382,7 @@ pub async fn get_customer_participant_report_handler(
     )
     .fetch_one(&state.db_pool)
     .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
+    .map_err(|_| AppError::NotFound("resource.not_found"))?;
 
     // 2. 出勤统计
     let stats = sqlx::query!(
@@ -401,8 +396,7 @@ pub async fn get_customer_participant_report_handler(
         participant_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     let total = stats.total.unwrap_or(0);
     let attended = stats.attended.unwrap_or(0);
@@ -428,8 +422,7 @@ pub async fn get_customer_participant_report_handler(
         participant_id
     )
     .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     let points_trend = trend.into_iter().map(|t| PointTrendItem {
         date: t.day.unwrap_or_default().to_string(),
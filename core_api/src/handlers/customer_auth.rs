@@ -10,11 +10,17 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{Utc, Duration};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use chrono::{DateTime, Utc};
 
-use super::AppState;
-use crate::models::Claims;
+use sqlx::{PgConnection, Row};
+
+use super::{
+    is_valid_phone, open_session, revoke_subject_sessions, verify_sms_code, AppState, REVOKE_MERGED,
+    SMS_PURPOSE_BIND_PHONE, SUBJECT_CUSTOMER,
+};
+use crate::models::{AuthResponse, Claims};
+use crate::wechat::decrypt_data;
+use crate::error::AppError;
 
 // ==========================================
 // 请求/响应模型
@@ -31,12 +37,29 @@ pub struct WechatLoginPayload {
 #[derive(Debug, Deserialize)]
 pub struct PhoneBindPayload {
     pub phone_number: String,
-    pub code: String,           // 短信验证码（可选，或直接绑定）
+    pub code: String,           // 短信验证码, 先调用 /customer/sms-code 获取
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhoneBindResponse {
+    pub customer_id: Uuid,
+    pub phone_number: String,
+    pub merged: bool, // 手机号已有档案, 当前微信已合并到该档案
+    #[serde(flatten)]
+    pub tokens: Option<AuthResponse>, // 合并后当前 Token 失效, 返回新档案的 Token
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WechatPhonePayload {
+    pub encrypted_data: String, // getPhoneNumber 返回的 encryptedData
+    pub iv: String,
 }
 
 #[derive(Debug, Serialize)]
 pub struct WechatLoginResponse {
     pub token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: DateTime<Utc>, // Access Token 过期时间
     pub customer: CustomerInfo,
     pub is_new_user: bool,      // 是否新用户
     pub needs_phone: bool,       // 是否需要绑定手机号
@@ -64,155 +87,395 @@ pub struct MiniprogramCodeResponse {
     pub scene: String,            // 场景值
 }
 
+// 按家长 id 组装 Claims (微信登录 / 刷新 / 模拟登录共用), 家长不存在时返回 None
+pub async fn load_customer_claims(
+    conn: &mut sqlx::PgConnection,
+    customer_id: Uuid,
+    sid: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<Option<Claims>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (Uuid, Option<String>)>("SELECT hq_id, name FROM customers WHERE id = $1")
+        .bind(customer_id)
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(row.map(|(hq_id, name)| Claims {
+        sub: customer_id.to_string(),
+        hq_id,
+        base_id: None,
+        roles: vec!["customer".to_string()],
+        base_name: None,
+        base_logo: None,
+        full_name: name.unwrap_or_default(),
+        sid,
+        exp: expires_at.timestamp() as usize,
+    }))
+}
+
 // ==========================================
 // API Handlers
 // ==========================================
 
 // POST /api/v1/auth/wechat-login - 微信登录
+// 查找家长的顺序: 本小程序 openid → 旧数据 customers.wechat_openid → unionid (在公众号等其他微信应用登录过)
+//                → 同总部同手机号 (前台已建档) → 新建
 pub async fn wechat_login_handler(
     State(state): State<AppState>,
     Json(payload): Json<WechatLoginPayload>,
-) -> Result<Json<WechatLoginResponse>, StatusCode> {
-    
-    // TODO: 实际项目中需要调用微信API换取openid
-    // 这里暂时模拟一个openid用于开发测试
-    let wechat_openid = if payload.code == "test-code" {
-        "test-openid-12345".to_string()
-    } else {
-        // 真实场景：调用微信API
-        // let wx_response = get_wechat_openid(&payload.code).await?;
-        // wx_response.openid
-        format!("openid_{}", uuid::Uuid::new_v4().to_string())
+) -> Result<Json<WechatLoginResponse>, AppError> {
+
+    // 1. code 换 openid / session_key
+    let session = state.wechat.code2session(&payload.code).await.map_err(|e| {
+        tracing::warn!("WeChat code2session failed: {:?}", e);
+        AppError::from(e)
+    })?;
+    let app_id = state.wechat.app_id();
+
+    // 2. 登录时一并提交的加密数据 (getUserInfo / getPhoneNumber): 取 unionid 和手机号
+    let decrypted = match (&payload.encrypted_data, &payload.iv) {
+        (Some(data), Some(iv)) => Some(decrypt_data(app_id, &session.session_key, data, iv).map_err(|e| {
+            tracing::warn!("Failed to decrypt WeChat data: {:?}", e);
+            AppError::from(e)
+        })?),
+        _ => None,
     };
+    let unionid = session
+        .unionid
+        .clone()
+        .or_else(|| decrypted.as_ref().and_then(|v| v["unionId"].as_str()).map(str::to_string));
+    let phone = decrypted.as_ref().and_then(|v| v["purePhoneNumber"].as_str()).map(str::to_string);
+
+    let mut tx = state.db_pool.begin().await?;
 
-    // 查询是否已存在该openid的customer
+    // 3. 查询已关联该微信的家长
     let existing_customer = sqlx::query_as::<_, CustomerRow>(
         r#"
-        SELECT id, name, phone_number, avatar_url, wechat_openid, hq_id
-        FROM customers 
-        WHERE wechat_openid = $1
+        SELECT c.id, c.name, c.phone_number, c.avatar_url
+        FROM (
+            SELECT customer_id AS id, 1 AS priority FROM customer_wechat_accounts WHERE app_id = $1 AND openid = $2
+            UNION ALL
+            SELECT id, 2 FROM customers WHERE wechat_openid = $2
+            UNION ALL
+            SELECT id, 3 FROM customers WHERE wechat_unionid = $3
+        ) m
+        JOIN customers c ON c.id = m.id
+        ORDER BY m.priority
+        LIMIT 1
         "#,
     )
-    .bind(&wechat_openid)
-    .fetch_optional(&state.db_pool)
+    .bind(app_id)
+    .bind(&session.openid)
+    .bind(&unionid)
+    .fetch_optional(&mut *tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to query customer: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to query customer"))?;
 
     let (customer_row, is_new_user) = match existing_customer {
         Some(customer) => (customer, false),
         None => {
-            // 新用户：创建customer记录，关联到扫码的基地
+            // 新用户：关联到扫码的基地
             let base_id = payload.base_id.ok_or_else(|| {
                 tracing::error!("Missing base_id in login payload for new customer");
                 StatusCode::BAD_REQUEST
             })?;
-            
+
             // 从base_id查询hq_id
-            let base_info = sqlx::query!(
-                "SELECT hq_id FROM bases WHERE id = $1",
-                base_id
-            )
-            .fetch_one(&state.db_pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to query base info: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-            
-            let new_customer = sqlx::query_as::<_, CustomerRow>(
-                r#"
-                INSERT INTO customers (wechat_openid, phone_number, hq_id, base_id)
-                VALUES ($1, '', $2, $3)
-                RETURNING id, name, phone_number, avatar_url, wechat_openid, hq_id
-                "#,
-            )
-            .bind(&wechat_openid)
-            .bind(base_info.hq_id)
-            .bind(base_id)
-            .fetch_one(&state.db_pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to create customer: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-            
-            (new_customer, true)
+            let hq_id: Uuid = sqlx::query_scalar("SELECT hq_id FROM bases WHERE id = $1")
+                .bind(base_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(AppError::db("Failed to query base info"))?
+                .ok_or(AppError::BadRequest("request.invalid"))?;
+
+            // 微信验证过的手机号已在前台建档: 直接关联, 不重复建客户
+            let by_phone = match &phone {
+                Some(phone) => sqlx::query_as::<_, CustomerRow>(
+                    "SELECT id, name, phone_number, avatar_url FROM customers WHERE hq_id = $1 AND phone_number = $2",
+                )
+                .bind(hq_id)
+                .bind(phone)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(AppError::db("Failed to query customer by phone"))?,
+                None => None,
+            };
+
+            match by_phone {
+                Some(customer) => (customer, false),
+                None => {
+                    let new_customer = sqlx::query_as::<_, CustomerRow>(
+                        r#"
+                        INSERT INTO customers (wechat_openid, wechat_unionid, phone_number, hq_id, base_id)
+                        VALUES ($1, $2, $3, $4, $5)
+                        RETURNING id, name, phone_number, avatar_url
+                        "#,
+                    )
+                    .bind(&session.openid)
+                    .bind(&unionid)
+                    .bind(phone.as_deref().unwrap_or(""))
+                    .bind(hq_id)
+                    .bind(base_id)
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(AppError::db("Failed to create customer"))?;
+                    (new_customer, true)
+                }
+            }
         }
     };
 
-    // 生成JWT Token
-    let claims = Claims {
-        sub: customer_row.id.to_string(),
-        hq_id: customer_row.hq_id.unwrap_or(Uuid::nil()),
-        base_id: None,
-        roles: vec!["customer".to_string()],
-        exp: (Utc::now() + Duration::days(30)).timestamp() as usize,
-        base_name: None,
-        base_logo: None,
-        full_name: customer_row.name.clone().unwrap_or_default(),
-    };
+    // 4. 保存本应用下的 openid / session_key, 并补齐家长的 openid / unionid / 手机号
+    sqlx::query(
+        r#"
+        INSERT INTO customer_wechat_accounts (customer_id, app_id, openid, unionid, session_key, session_key_updated_at)
+        VALUES ($1, $2, $3, $4, $5, NOW())
+        ON CONFLICT (app_id, openid) DO UPDATE SET
+            unionid = COALESCE(EXCLUDED.unionid, customer_wechat_accounts.unionid),
+            session_key = EXCLUDED.session_key,
+            session_key_updated_at = NOW()
+        "#,
+    )
+    .bind(customer_row.id)
+    .bind(app_id)
+    .bind(&session.openid)
+    .bind(&unionid)
+    .bind(&session.session_key)
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to save wechat account"))?;
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
+    // unionid / 手机号已被其他家长占用时保持原值, 不在登录时合并客户
+    let customer_row = sqlx::query_as::<_, CustomerRow>(
+        r#"
+        UPDATE customers c SET
+            wechat_openid = COALESCE(c.wechat_openid, $2),
+            wechat_unionid = COALESCE(c.wechat_unionid,
+                CASE WHEN EXISTS (SELECT 1 FROM customers o WHERE o.wechat_unionid = $3 AND o.id <> c.id) THEN NULL ELSE $3 END),
+            phone_number = CASE
+                WHEN c.phone_number = '' AND $4::VARCHAR IS NOT NULL
+                     AND NOT EXISTS (SELECT 1 FROM customers o WHERE o.hq_id = c.hq_id AND o.phone_number = $4 AND o.id <> c.id)
+                THEN $4 ELSE c.phone_number END
+        WHERE c.id = $1
+        RETURNING c.id, c.name, c.phone_number, c.avatar_url
+        "#,
     )
-    .map_err(|e| {
-        tracing::error!("Failed to generate token: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .bind(customer_row.id)
+    .bind(&session.openid)
+    .bind(&unionid)
+    .bind(&phone)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to link wechat identity"))?;
+
+    // 5. 新建登录会话, 签发 Access Token + Refresh Token
+    let auth = open_session(&mut tx, &state.jwt_secret, SUBJECT_CUSTOMER, customer_row.id)
+        .await?
+        .ok_or(AppError::Unauthorized("auth.unauthorized"))?;
+    tx.commit().await?;
 
     Ok(Json(WechatLoginResponse {
-        token,
+        token: auth.tokens.token,
+        refresh_token: auth.tokens.refresh_token,
+        expires_at: auth.tokens.expires_at,
         customer: CustomerInfo {
             id: customer_row.id,
             name: customer_row.name.clone(),
             phone_number: customer_row.phone_number.clone(),
             avatar_url: customer_row.avatar_url,
-            wechat_openid,
+            wechat_openid: session.openid,
         },
         is_new_user,
         needs_phone: customer_row.phone_number.as_ref().map(|p| p.is_empty()).unwrap_or(true),
     }))
 }
 
-// POST /api/v1/customer/bind-phone - 绑定手机号
+// POST /api/v1/customer/wechat-phone - 解密 getPhoneNumber 返回的数据并绑定手机号
+// 使用最近一次 wx.login 换到的 session_key; 解密失败时前端需重新 wx.login 后再试
+pub async fn bind_wechat_phone_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<WechatPhonePayload>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let customer_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::BadRequest("request.invalid"))?;
+    let app_id = state.wechat.app_id();
+
+    let session_key: String = sqlx::query_scalar(
+        r#"
+        SELECT session_key FROM customer_wechat_accounts
+        WHERE customer_id = $1 AND app_id = $2 AND session_key IS NOT NULL
+        ORDER BY session_key_updated_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(customer_id)
+    .bind(app_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to load session_key"))?
+    .ok_or(AppError::Custom(StatusCode::PRECONDITION_FAILED, "customer.wechat_required"))?; // 未通过微信登录过
+
+    let data = decrypt_data(app_id, &session_key, &payload.encrypted_data, &payload.iv).map_err(|e| {
+        tracing::warn!("Failed to decrypt WeChat phone: {:?}", e);
+        AppError::from(e)
+    })?;
+    let phone = data["purePhoneNumber"].as_str().ok_or(AppError::BadRequest("request.invalid"))?;
+
+    // 同总部下手机号已属于其他家长时不覆盖
+    let result = sqlx::query(
+        r#"
+        UPDATE customers c SET phone_number = $2
+        WHERE c.id = $1
+          AND NOT EXISTS (SELECT 1 FROM customers o WHERE o.hq_id = c.hq_id AND o.phone_number = $2 AND o.id <> c.id)
+        "#,
+    )
+    .bind(customer_id)
+    .bind(phone)
+    .execute(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to bind phone"))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::Conflict("customer.phone_taken"));
+    }
+
+    Ok(Json(serde_json::json!({ "phone_number": phone })))
+}
+
+// POST /api/v1/customer/bind-phone - 短信验证码绑定手机号
+// 同总部下该手机号已有档案 (如前台录入 / 试听预约时建档) 时, 把当前微信客户合并进去
 pub async fn bind_phone_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<PhoneBindPayload>,
-) -> Result<StatusCode, StatusCode> {
-    
+) -> Result<Json<PhoneBindResponse>, AppError> {
     let customer_id = Uuid::parse_str(&claims.sub)
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+        .map_err(|_| AppError::BadRequest("request.invalid"))?;
+    let phone = payload.phone_number.trim();
+    if !is_valid_phone(phone) {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
 
-    // TODO: 验证短信验证码
-    // verify_sms_code(&payload.phone_number, &payload.code)?;
+    verify_sms_code(&state.db_pool, &state.jwt_secret, phone, SMS_PURPOSE_BIND_PHONE, &payload.code).await?;
 
-    // 更新手机号
-    sqlx::query(
-        "UPDATE customers SET phone_number = $1 WHERE id = $2"
+    let mut tx = state.db_pool.begin().await?;
+
+    let existing: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT o.id FROM customers o
+        JOIN customers c ON c.hq_id = o.hq_id
+        WHERE c.id = $1 AND o.phone_number = $2 AND o.id <> c.id
+        FOR UPDATE OF o
+        "#,
     )
-    .bind(&payload.phone_number)
     .bind(customer_id)
-    .execute(&state.db_pool)
+    .bind(phone)
+    .fetch_optional(&mut *tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to bind phone: {}", e);
+    .map_err(AppError::db("Failed to look up customer by phone"))?;
+
+    let Some(target_id) = existing else {
+        sqlx::query("UPDATE customers SET phone_number = $1, updated_at = NOW() WHERE id = $2")
+            .bind(phone)
+            .bind(customer_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::db("Failed to bind phone"))?;
+        tx.commit().await?;
+
+        return Ok(Json(PhoneBindResponse {
+            customer_id,
+            phone_number: phone.to_string(),
+            merged: false,
+            tokens: None,
+        }));
+    };
+
+    merge_customer_into(&mut tx, customer_id, target_id).await.map_err(|e| {
+        tracing::error!("Failed to merge customer {} into {}: {}", customer_id, target_id, e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
+    revoke_subject_sessions(&mut tx, SUBJECT_CUSTOMER, customer_id, REVOKE_MERGED)
+        .await
+        .map_err(AppError::db("Failed to revoke merged customer sessions"))?;
+    let session = open_session(&mut tx, &state.jwt_secret, SUBJECT_CUSTOMER, target_id)
+        .await?
+        .ok_or(AppError::Unauthorized("auth.unauthorized"))?;
+
+    tx.commit().await?;
+
+    tracing::info!("Customer {} merged into {} after phone verification", customer_id, target_id);
+    Ok(Json(PhoneBindResponse {
+        customer_id: target_id,
+        phone_number: phone.to_string(),
+        merged: true,
+        tokens: Some(session.tokens),
+    }))
+}
+
+// 把 source 客户并入 target: 微信身份、学员、会员卡、订单等全部改挂到 target, 再删除 source
+// 引用 customers 的表从外键目录里查, 以后新增的关联表无需改这里
+async fn merge_customer_into(conn: &mut PgConnection, source: Uuid, target: Uuid) -> Result<(), sqlx::Error> {
+    // 先清掉 source 上的唯一标识, 再补到 target (target 已有的不覆盖)
+    let src = sqlx::query(
+        r#"
+        UPDATE customers n SET wechat_openid = NULL, wechat_unionid = NULL
+        FROM customers o
+        WHERE n.id = $1 AND o.id = n.id
+        RETURNING o.wechat_openid, o.wechat_unionid, o.name, o.avatar_url
+        "#,
+    )
+    .bind(source)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE customers SET
+            wechat_openid = COALESCE(wechat_openid, $2),
+            wechat_unionid = COALESCE(wechat_unionid, $3),
+            name = COALESCE(NULLIF(name, ''), $4),
+            avatar_url = COALESCE(NULLIF(avatar_url, ''), $5),
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(target)
+    .bind(src.get::<Option<String>, _>("wechat_openid"))
+    .bind(src.get::<Option<String>, _>("wechat_unionid"))
+    .bind(src.get::<Option<String>, _>("name"))
+    .bind(src.get::<Option<String>, _>("avatar_url"))
+    .execute(&mut *conn)
+    .await?;
+
+    let references: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT cl.relname::text, att.attname::text
+        FROM pg_constraint con
+        JOIN pg_class cl ON cl.oid = con.conrelid
+        JOIN pg_attribute att ON att.attrelid = con.conrelid AND att.attnum = con.conkey[1]
+        WHERE con.contype = 'f' AND con.confrelid = 'customers'::regclass
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for (table, column) in references {
+        sqlx::query(&format!("UPDATE \"{}\" SET \"{}\" = $1 WHERE \"{}\" = $2", table, column, column))
+            .bind(target)
+            .bind(source)
+            .execute(&mut *conn)
+            .await?;
+    }
 
-    Ok(StatusCode::OK)
+    sqlx::query("DELETE FROM customers WHERE id = $1").bind(source).execute(&mut *conn).await?;
+    Ok(())
 }
 
 // POST /api/v1/base/generate-miniprogram-code - 生成小程序码
 pub async fn generate_miniprogram_code_handler(
     State(_state): State<AppState>,
     Json(payload): Json<MiniprogramCodePayload>,
-) -> Result<Json<MiniprogramCodeResponse>, StatusCode> {
+) -> Result<Json<MiniprogramCodeResponse>, AppError> {
     
     // 构造场景值（最多32个字符）
     let scene = format!(
@@ -245,6 +508,4 @@ struct CustomerRow {
     name: Option<String>,
     phone_number: Option<String>,
     avatar_url: Option<String>,
-    wechat_openid: String,
-    hq_id: Option<Uuid>,
 }
@@ -5,7 +5,6 @@
 
 use axum::{
     extract::{State, Query},
-    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
@@ -14,6 +13,7 @@ use chrono::{DateTime, Utc};
 
 use super::AppState;
 use crate::models::Claims;
+use crate::error::AppError;
 
 // ==========================================
 // 请求/响应模型
@@ -82,7 +82,7 @@ pub async fn get_customer_honor_handler(
     State(state): State<AppState>,
     claims: Claims,
     Query(params): Query<HonorQuery>,
-) -> Result<Json<HonorResponse>, StatusCode> {
+) -> Result<Json<HonorResponse>, AppError> {
     
     // 获取学员的积分档案
     let profile = sqlx::query_as::<_, ParticipantProfile>(
@@ -104,10 +104,7 @@ pub async fn get_customer_honor_handler(
     .bind(claims.hq_id)
     .fetch_optional(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch honor profile: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch honor profile"))?;
 
     // 如果没有档案,返回默认数据
     let total_points = profile.as_ref().map(|p| p.current_total_points).unwrap_or(0);
@@ -122,10 +119,7 @@ pub async fn get_customer_honor_handler(
             .bind(rank_id)
             .fetch_optional(&state.db_pool)
             .await
-            .map_err(|e| {
-                tracing::error!("Failed to fetch current rank: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?
+            .map_err(AppError::db("Failed to fetch current rank"))?
         } else {
             None
         }
@@ -147,10 +141,7 @@ pub async fn get_customer_honor_handler(
     .bind(current_rank_level + 1)
     .fetch_optional(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch next rank: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch next rank"))?;
 
     // 计算进度
     let points_to_next = next_rank_info.as_ref().map(|nr| nr.points_required - total_points);
@@ -244,7 +235,7 @@ pub async fn get_points_history_handler(
     State(state): State<AppState>,
     claims: Claims,
     Query(params): Query<PointsHistoryQuery>,
-) -> Result<Json<PointsHistoryResponse>, StatusCode> {
+) -> Result<Json<PointsHistoryResponse>, AppError> {
     
     let page = params.page.unwrap_or(1).max(1);
     let limit = params.limit.unwrap_or(20).min(100);
@@ -270,10 +261,7 @@ pub async fn get_points_history_handler(
     .bind(offset as i64)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch points history: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch points history"))?;
 
     // 查询总积分
     let total_points = sqlx::query_scalar::<_, i32>(
@@ -287,10 +275,7 @@ pub async fn get_points_history_handler(
     .bind(claims.hq_id)
     .fetch_optional(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch total points: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?
+    .map_err(AppError::db("Failed to fetch total points"))?
     .unwrap_or(0);
 
     // 查询总记录数
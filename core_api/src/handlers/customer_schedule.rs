@@ -5,7 +5,6 @@
 
 use axum::{
     extract::{State, Query},
-    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
@@ -14,6 +13,7 @@ use chrono::{DateTime, Utc};
 
 use super::AppState;
 use crate::models::Claims;
+use crate::error::AppError;
 
 // ==========================================
 // 请求/响应模型
@@ -82,7 +82,7 @@ pub async fn get_customer_schedule_handler(
     State(state): State<AppState>,
     claims: Claims,
     Query(params): Query<ScheduleQuery>,
-) -> Result<Json<ScheduleResponse>, StatusCode> {
+) -> Result<Json<ScheduleResponse>, AppError> {
     
     // 查询学员的课程安排
     let classes = sqlx::query_as::<_, ClassScheduleRaw>(
@@ -114,10 +114,7 @@ pub async fn get_customer_schedule_handler(
     .bind(claims.hq_id)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch customer schedule: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch customer schedule"))?;
 
     let schedule_items: Vec<ClassScheduleItem> = classes
         .into_iter()
@@ -161,7 +158,7 @@ pub async fn get_course_balance_handler(
     State(state): State<AppState>,
     claims: Claims,
     Query(params): Query<CourseBalanceQuery>,
-) -> Result<Json<CourseBalanceResponse>, StatusCode> {
+) -> Result<Json<CourseBalanceResponse>, AppError> {
     
     let memberships = sqlx::query_as::<_, MembershipBalanceRaw>(
         r#"
@@ -184,10 +181,7 @@ pub async fn get_course_balance_handler(
     .bind(claims.hq_id)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch course balance: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch course balance"))?;
 
     let balances: Vec<MembershipBalance> = memberships
         .into_iter()
@@ -5,13 +5,13 @@
 
 use axum::{
     extract::State,
-    http::StatusCode,
     Json,
 };
 use serde::Serialize;
 use super::AppState;
 use crate::models::Claims;
 use chrono::{Datelike, Utc};
+use crate::error::AppError;
 
 // ==========================================
 // 1. Data Models
@@ -183,15 +183,12 @@ pub struct Alert {
 pub async fn get_dashboard_overview_handler(
    State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<DashboardOverview>, StatusCode> {
+) -> Result<Json<DashboardOverview>, AppError> {
     // 检查权限：必须是基地角色
-    if !claims.roles.iter().any(|r| r.starts_with("role.base.")) {
-        return Err(StatusCode::FORBIDDEN);
-    }
 
     let base_id = match claims.base_id {
         Some(id) => id,
-        None => return Err(StatusCode::FORBIDDEN),
+        None => return Err(AppError::Forbidden("auth.forbidden")),
     };
 
     // 获取基地名称
@@ -200,8 +197,7 @@ pub async fn get_dashboard_overview_handler(
     )
     .bind(base_id)
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     // 获取各项指标
     let cash_flow = get_cash_flow_metric(&state, base_id).await?;
@@ -248,7 +244,7 @@ pub async fn get_dashboard_overview_handler(
 async fn get_cash_flow_metric(
     state: &AppState,
     base_id: uuid::Uuid,
-) -> Result<CashFlowMetric, StatusCode> {
+) -> Result<CashFlowMetric, AppError> {
     // 账上现金（简化版：从 finance_accounts 获取）
     let cash_on_hand: i32 = sqlx::query_scalar(
         "SELECT COALESCE(SUM(balance_cents), 0) FROM finance_accounts 
@@ -259,22 +255,19 @@ async fn get_cash_flow_metric(
     .await
     .unwrap_or(0);
 
-    // 应收账款（待收款订单总额）
+    // 应收账款（全部未收金额）及逾期订单数, 按分期 / 订单到期日 (order_receivables)
     let receivable_result = sqlx::query!(
         r#"
         SELECT 
-            COUNT(*) as "count!",
-            COALESCE(SUM(total_amount_cents - paid_amount_cents), 0) as "amount!"
-        FROM orders
-        WHERE base_id = $1 
-          AND status IN ('pending', 'partial_paid')
-          AND (due_date < CURRENT_DATE - INTERVAL '7 days' OR due_date IS NULL)
+            COUNT(DISTINCT order_id) FILTER (WHERE due_date < CURRENT_DATE) as "count!",
+            COALESCE(SUM(outstanding_cents), 0) as "amount!"
+        FROM order_receivables
+        WHERE base_id = $1
         "#,
         base_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     let accounts_receivable = receivable_result.amount as i32;
     let overdue_count = receivable_result.count;
@@ -296,8 +289,7 @@ async fn get_cash_flow_metric(
     )
     .bind(base_id)
     .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .await?
     .flatten();
 
     // 资金可用月数
@@ -334,7 +326,7 @@ async fn get_cash_flow_metric(
 async fn get_today_revenue_metric(
     state: &AppState,
     base_id: uuid::Uuid,
-) -> Result<TodayRevenueMetric, StatusCode> {
+) -> Result<TodayRevenueMetric, AppError> {
     let result = sqlx::query!(
         r#"
         SELECT 
@@ -350,8 +342,7 @@ async fn get_today_revenue_metric(
         base_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     Ok(TodayRevenueMetric {
         total: result.total as i32,
@@ -365,7 +356,7 @@ async fn get_today_revenue_metric(
 async fn get_students_metric(
     state: &AppState,
     base_id: uuid::Uuid,
-) -> Result<StudentsMetric, StatusCode> {
+) -> Result<StudentsMetric, AppError> {
     // 在校学员（通过 customer_id 关联到 base_id）
     let active_students: i64 = sqlx::query_scalar(
         r#"
@@ -438,7 +429,7 @@ async fn get_students_metric(
 async fn get_recruitment_metric(
     state: &AppState,
     base_id: uuid::Uuid,
-) -> Result<RecruitmentMetric, StatusCode> {
+) -> Result<RecruitmentMetric, AppError> {
     // 本月新线索数
     let leads_count: i64 = sqlx::query_scalar(
         "SELECT COUNT(*) FROM leads 
@@ -463,8 +454,7 @@ async fn get_recruitment_metric(
         base_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     let trial_scheduled = trial_stats.total;
     let trial_completed = trial_stats.completed;
@@ -525,7 +515,7 @@ async fn get_recruitment_metric(
 async fn get_revenue_progress_metric(
     state: &AppState,
     base_id: uuid::Uuid,
-) -> Result<RevenueProgressMetric, StatusCode> {
+) -> Result<RevenueProgressMetric, AppError> {
     // 本月实际营收
     let actual: i32 = sqlx::query_scalar(
         r#"
@@ -548,8 +538,7 @@ async fn get_revenue_progress_metric(
     )
     .bind(base_id)
     .fetch_optional(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .await?
     .flatten()
     .unwrap_or(30000000); // 默认 ¥300,000
 
@@ -597,7 +586,7 @@ async fn get_revenue_progress_metric(
 async fn get_profitability_metric(
     state: &AppState,
     base_id: uuid::Uuid,
-) -> Result<ProfitabilityMetric, StatusCode> {
+) -> Result<ProfitabilityMetric, AppError> {
     // 收入
     let revenue_result = sqlx::query!(
         r#"
@@ -613,8 +602,7 @@ async fn get_profitability_metric(
         base_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     let total_revenue = revenue_result.total as i32;
     let toc_revenue = revenue_result.toc as i32;
@@ -674,7 +662,7 @@ async fn get_profitability_metric(
 async fn get_tob_status_metric(
     state: &AppState,
     base_id: uuid::Uuid,
-) -> Result<ToBStatusMetric, StatusCode> {
+) -> Result<ToBStatusMetric, AppError> {
     // 明日活动数量
     let tomorrow_events_count: i64 = sqlx::query_scalar(
         r#"
@@ -705,8 +693,7 @@ async fn get_tob_status_metric(
         base_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     // 延期订单
     let overdue_count: i64 = sqlx::query_scalar(
@@ -738,7 +725,7 @@ async fn get_alerts_metric(
     _base_id: uuid::Uuid,
     cash_flow: &CashFlowMetric,
     profitability: &ProfitabilityMetric,
-) -> Result<AlertsMetric, StatusCode> {
+) -> Result<AlertsMetric, AppError> {
     let mut critical = Vec::new();
     let mut warning = Vec::new();
     let info = Vec::new();
@@ -790,7 +777,7 @@ async fn get_alerts_metric(
 // 4. 新增 Web 端专用的图表与列表指标
 // ==========================================
 
-async fn get_trends_metric(state: &AppState, base_id: uuid::Uuid) -> Result<TrendMetric, StatusCode> {
+async fn get_trends_metric(state: &AppState, base_id: uuid::Uuid) -> Result<TrendMetric, AppError> {
     // 获取最近7天数据
     let rows = sqlx::query!(
         r#"
@@ -807,10 +794,7 @@ async fn get_trends_metric(state: &AppState, base_id: uuid::Uuid) -> Result<Tren
     )
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Trend query failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Trend query failed"))?;
 
     let mut labels = Vec::new();
     let mut revenue = Vec::new();
@@ -825,7 +809,7 @@ async fn get_trends_metric(state: &AppState, base_id: uuid::Uuid) -> Result<Tren
     Ok(TrendMetric { labels, revenue, students })
 }
 
-async fn get_composition_metric(state: &AppState, base_id: uuid::Uuid) -> Result<Vec<CompositionMetric>, StatusCode> {
+async fn get_composition_metric(state: &AppState, base_id: uuid::Uuid) -> Result<Vec<CompositionMetric>, AppError> {
     let rows = sqlx::query!(
         r#"
         SELECT type::TEXT as "type_!", COUNT(*) as "count!"
@@ -836,8 +820,7 @@ async fn get_composition_metric(state: &AppState, base_id: uuid::Uuid) -> Result
         base_id
     )
     .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     let mut res = Vec::new();
     let colors = vec!["#3b82f6", "#10b981", "#f59e0b", "#8b5cf6"]; 
@@ -862,7 +845,7 @@ async fn get_composition_metric(state: &AppState, base_id: uuid::Uuid) -> Result
     Ok(res)
 }
 
-async fn get_upcoming_events_metric(state: &AppState, base_id: uuid::Uuid) -> Result<Vec<EventMetric>, StatusCode> {
+async fn get_upcoming_events_metric(state: &AppState, base_id: uuid::Uuid) -> Result<Vec<EventMetric>, AppError> {
     let rows = sqlx::query!(
         r#"
         SELECT 
@@ -878,8 +861,7 @@ async fn get_upcoming_events_metric(state: &AppState, base_id: uuid::Uuid) -> Re
         base_id
     )
     .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     let mut events = Vec::new();
     for r in rows {
@@ -893,7 +875,7 @@ async fn get_upcoming_events_metric(state: &AppState, base_id: uuid::Uuid) -> Re
     Ok(events)
 }
 
-async fn get_todo_list_metric(state: &AppState, base_id: uuid::Uuid) -> Result<Vec<TodoMetric>, StatusCode> {
+async fn get_todo_list_metric(state: &AppState, base_id: uuid::Uuid) -> Result<Vec<TodoMetric>, AppError> {
     let mut todos = Vec::new();
 
     // 1. Pending Approvals
@@ -914,7 +896,7 @@ async fn get_todo_list_metric(state: &AppState, base_id: uuid::Uuid) -> Result<V
 
     // 2. Overdue Payments
     let overdue = sqlx::query_scalar!(
-        "SELECT COUNT(*) FROM orders WHERE base_id = $1 AND status = 'pending' AND due_date < CURRENT_DATE",
+        "SELECT COUNT(DISTINCT order_id) FROM order_receivables WHERE base_id = $1 AND due_date < CURRENT_DATE",
         base_id
     ).fetch_one(&state.db_pool).await.unwrap_or(Some(0)).unwrap_or(0);
 
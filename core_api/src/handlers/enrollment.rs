@@ -1,18 +1,21 @@
 /*
  * src/handlers/enrollment.rs
  * 职责: 学员报名 (Enrollment) 管理
- * (★ V15.2 - 通用记账版: 期限卡也能产生流水 ★)
+ * 消课时按单次金额记 "借 合同负债 / 贷 主营业务收入" 凭证 (期限卡按天摊销的不在此记账)
  */
 
 use axum::{
     extract::{State, Path},
     http::StatusCode, 
+    response::{IntoResponse, Response},
     Json
 };
 use sqlx::Row;
 use uuid::Uuid;
 
-use super::AppState;
+use super::ledger::{post_transfer, LedgerTransfer, ACCOUNT_CONTRACT_LIABILITY, ACCOUNT_REVENUE, SOURCE_COURSE_REVENUE};
+use super::{enroll_or_waitlist, RECOGNITION_PER_CLASS, find_membership_problem, find_participant_conflicts, promote_waitlist, AppState, EnrollOutcome, schedule_conflict_error};
+use crate::tenant::{Owned, TenantScope};
 use crate::models::{
     Claims,
     ClassEnrollment, 
@@ -21,31 +24,42 @@ use crate::models::{
     MembershipTierType,
     EnrollmentDetail,
 };
+use crate::error::AppError;
 
 // (POST create_enrollment_handler ... 保持不变)
 pub async fn create_enrollment_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateEnrollmentPayload>,
-) -> Result<Json<ClassEnrollment>, StatusCode> {
+) -> Result<Response, AppError> {
     // ... (请保留原有的 create 逻辑) ...
     // (为节省篇幅，此处省略 create 代码，请直接复制之前的或保持原样)
     let hq_id = claims.hq_id;
-    let _base_id = match claims.base_id { Some(id) => id, None => return Err(StatusCode::FORBIDDEN) };
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let _base_id = match claims.base_id { Some(id) => id, None => return Err(AppError::Forbidden("auth.base_required")) };
+    let mut tx = state.db_pool.begin().await?;
+    let scope = TenantScope::from_claims(&claims);
+    scope.ensure_owned(&mut *tx, Owned::Class(payload.class_id)).await?;
+    scope.ensure_owned(&mut *tx, Owned::Participant(payload.participant_id)).await?;
 
     let exists = sqlx::query("SELECT id FROM class_enrollments WHERE class_id=$1 AND participant_id=$2").bind(payload.class_id).bind(payload.participant_id).fetch_optional(&mut *tx).await.unwrap_or(None);
-    if exists.is_some() { return Err(StatusCode::CONFLICT); }
+    if exists.is_some() { return Err(AppError::Conflict("enrollment.duplicate")); }
 
-    let customer_id: Uuid = sqlx::query_scalar("SELECT customer_id FROM participants WHERE id=$1").bind(payload.participant_id).fetch_one(&mut *tx).await.map_err(|_| StatusCode::NOT_FOUND)?;
+    // (★ 学员撞课校验)
+    let conflicts = find_participant_conflicts(&mut tx, payload.participant_id, payload.class_id).await.map_err(AppError::db("Failed to check participant conflicts"))?;
+    if !conflicts.is_empty() { return Err(schedule_conflict_error(conflicts)); }
 
-    let new_enrollment = sqlx::query_as::<_, ClassEnrollment>(
-        "INSERT INTO class_enrollments (hq_id, class_id, participant_id, customer_id, customer_membership_id, status) VALUES ($1, $2, $3, $4, $5, 'enrolled') RETURNING *"
-    ).bind(hq_id).bind(payload.class_id).bind(payload.participant_id).bind(customer_id).bind(payload.customer_membership_id)
-    .fetch_one(&mut *tx).await.map_err(|e| { tracing::error!("{}",e); StatusCode::INTERNAL_SERVER_ERROR })?;
+    let customer_id: Uuid = sqlx::query_scalar("SELECT customer_id FROM participants WHERE id=$1").bind(payload.participant_id).fetch_one(&mut *tx).await.map_err(|_| AppError::NotFound("resource.not_found"))?;
+
+    // (★ 容量控制: 满员进入候补, 返回 202)
+    let outcome = enroll_or_waitlist(&mut tx, hq_id, payload.class_id, payload.participant_id, customer_id, Some(payload.customer_membership_id))
+        .await.map_err(AppError::db("Failed to enroll participant"))?;
     
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(new_enrollment))
+    tx.commit().await?;
+    Ok(match outcome {
+        EnrollOutcome::Enrolled(new_enrollment) => Json(new_enrollment).into_response(),
+        EnrollOutcome::Waitlisted(entry) => (StatusCode::ACCEPTED, Json(entry)).into_response(),
+        EnrollOutcome::Rejected(reason) => return Err(AppError::Rejected(reason)),
+    })
 }
 
 // (GET get_enrollments_for_class_handler ... 保持不变)
@@ -53,9 +67,9 @@ pub async fn get_enrollments_for_class_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(class_id): Path<Uuid>,
-) -> Result<Json<Vec<EnrollmentDetail>>, StatusCode> {
+) -> Result<Json<Vec<EnrollmentDetail>>, AppError> {
     let hq_id = claims.hq_id;
-    let _base_id = match claims.base_id { Some(id) => id, None => return Err(StatusCode::FORBIDDEN) };
+    let _base_id = match claims.base_id { Some(id) => id, None => return Err(AppError::Forbidden("auth.base_required")) };
 
     let enrollments = match sqlx::query_as::<_, EnrollmentDetail>(
         r#"
@@ -68,7 +82,7 @@ pub async fn get_enrollments_for_class_handler(
         ORDER BY p.name ASC
         "#,
     ).bind(class_id).bind(hq_id).fetch_all(&state.db_pool).await
-    { Ok(list) => list, Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR) };
+    { Ok(list) => list, Err(e) => return Err(e.into()) };
     Ok(Json(enrollments))
 }
 
@@ -78,18 +92,18 @@ pub async fn complete_enrollment_handler(
     claims: Claims,
     Path(enrollment_id): Path<Uuid>,
     Json(payload): Json<UpdateEnrollmentPayload>,
-) -> Result<Json<ClassEnrollment>, StatusCode> {
+) -> Result<Json<ClassEnrollment>, AppError> {
 
     let hq_id = claims.hq_id;
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
     let user_id_uuid = Uuid::parse_str(&claims.sub).unwrap_or_default();
 
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut tx = state.db_pool.begin().await?;
 
     // 1. 锁定并查询
     let row = sqlx::query(
         r#"
-        SELECT e.participant_id, e.status, e.customer_membership_id, cl.course_id
+        SELECT e.participant_id, e.status, e.customer_membership_id, e.class_id, cl.course_id
         FROM class_enrollments e
         JOIN classes cl ON e.class_id = cl.id
         WHERE e.id = $1 AND cl.base_id = $2 AND e.hq_id = $3
@@ -97,20 +111,33 @@ pub async fn complete_enrollment_handler(
         "#
     )
     .bind(enrollment_id).bind(base_id).bind(hq_id)
-    .fetch_optional(&mut *tx).await.unwrap_or(None).ok_or(StatusCode::NOT_FOUND)?;
+    .fetch_optional(&mut *tx).await.unwrap_or(None).ok_or(AppError::NotFound("resource.not_found"))?;
 
     let current_status: String = row.get("status");
     if current_status == "completed" || current_status == "absent" || current_status == "leave" {
-        return Err(StatusCode::CONFLICT);
+        return Err(AppError::Conflict("enrollment.already_closed"));
     }
 
-    // 2. 更新状态
+    // 2. 扣次前再次校验会员卡 (归属 / 有效期 / 剩余次数)
     let new_status = payload.status.as_str();
+    if new_status == "completed" || new_status == "absent" {
+        let cm_id: Option<Uuid> = row.get("customer_membership_id");
+        let pid: Uuid = row.get("participant_id");
+        let class_id: Uuid = row.get("class_id");
+        if let Some(cmid) = cm_id {
+            let problem = find_membership_problem(&mut tx, cmid, pid, class_id, Some(enrollment_id)).await.map_err(AppError::db("Failed to validate membership"))?;
+            if let Some(reason) = problem {
+                return Err(AppError::Rejected(reason));
+            }
+        }
+    }
+
+    // 3. 更新状态
     let updated_enrollment = sqlx::query_as::<_, ClassEnrollment>(
         "UPDATE class_enrollments SET status = $1, teacher_feedback = $2 WHERE id = $3 RETURNING *"
     )
     .bind(new_status).bind(&payload.teacher_feedback).bind(enrollment_id)
-    .fetch_one(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .fetch_one(&mut *tx).await?;
 
     let cm_id: Option<Uuid> = row.get("customer_membership_id");
     let course_id: Uuid = row.get("course_id");
@@ -122,7 +149,8 @@ pub async fn complete_enrollment_handler(
             // 查询卡种信息
             let membership_info = sqlx::query(
                 r#"
-                SELECT mt.tier_type, mt.price_in_cents, mt.usage_count, mt.name_key
+                SELECT mt.tier_type, mt.price_in_cents, mt.usage_count, mt.name_key,
+                       mt.recognition_method, mt.expected_class_count, cm.revenue_recognized_in_cents
                 FROM membership_tiers mt 
                 JOIN customer_memberships cm ON mt.id = cm.tier_id 
                 WHERE cm.id = $1
@@ -138,6 +166,9 @@ pub async fn complete_enrollment_handler(
                 let price_total: i32 = info.get("price_in_cents");
                 let usage_total: Option<i32> = info.get("usage_count");
                 let tier_name: String = info.get("name_key");
+                let recognition_method: String = info.get("recognition_method");
+                let expected_classes: Option<i32> = info.get("expected_class_count");
+                let recognized: i32 = info.get("revenue_recognized_in_cents");
 
                 // 1. 扣次 (仅次卡)
                 if tier_type == MembershipTierType::UsageBased {
@@ -145,39 +176,51 @@ pub async fn complete_enrollment_handler(
                         .bind(cmid).execute(&mut *tx).await.ok();
                 }
 
-                // 2. (★ 修改: 通用记账逻辑)
-                // 即使是期限卡 (0元), 也要记一笔流水, 证明"上过课"
+                // 2. 确认收入: 借 合同负债 / 贷 主营业务收入 (0 元不记账)
                 let revenue_amount = if tier_type == MembershipTierType::UsageBased {
                     // 次卡: 均摊
                     if let Some(count) = usage_total {
                         if count > 0 { price_total / count } else { 0 }
                     } else { 0 }
+                } else if recognition_method == RECOGNITION_PER_CLASS {
+                    // 期限卡 (按次确认): 售价 / 预计次数, 不超过剩余未确认金额
+                    match expected_classes {
+                        Some(count) if count > 0 => (price_total / count).min((price_total - recognized).max(0)),
+                        _ => 0,
+                    }
                 } else {
-                    // 期限卡: 暂记 0 元 (未来可对接 AI 规则引擎算出每节课估值)
+                    // 期限卡 (按天摊销): 收入由定时任务确认
                     0 
                 };
 
                 let status_desc = if new_status == "completed" { "正常上课" } else { "旷课扣费" };
-                let desc = format!("消课: {} (卡种: {})", status_desc, tier_name);
-                
-                sqlx::query(
-                    r#"
-                    INSERT INTO financial_transactions 
-                    (hq_id, base_id, amount_in_cents, transaction_type, category, related_entity_id, description, created_by, debit_subject, credit_subject)
-                    VALUES ($1, $2, $3, 'usage', 'course_revenue', $4, $5, $6, 'contract_liability', 'revenue')
-                    "#
-                )
-                .bind(hq_id)
-                .bind(base_id)
-                .bind(revenue_amount)
-                .bind(enrollment_id)
-                .bind(desc)
-                .bind(user_id_uuid)
-                .execute(&mut *tx).await.ok();
+                post_transfer(&mut tx, LedgerTransfer {
+                    hq_id,
+                    base_id: Some(base_id),
+                    entry_date: None,
+                    source_type: SOURCE_COURSE_REVENUE,
+                    source_id: Some(enrollment_id),
+                    description: format!("消课: {} (卡种: {})", status_desc, tier_name),
+                    created_by: Some(user_id_uuid),
+                    debit: ACCOUNT_CONTRACT_LIABILITY,
+                    credit: ACCOUNT_REVENUE,
+                    amount_cents: revenue_amount as i64,
+                })
+                .await?;
+
+                if revenue_amount > 0 {
+                    sqlx::query("UPDATE customer_memberships SET revenue_recognized_in_cents = revenue_recognized_in_cents + $2 WHERE id = $1")
+                        .bind(cmid).bind(revenue_amount).execute(&mut *tx).await.ok();
+                }
             }
         }
     }
 
+    // --- A2. 请假释放名额: 候补自动转正 ---
+    if new_status == "leave" {
+        promote_waitlist(&mut tx, updated_enrollment.class_id).await.map_err(AppError::db("Failed to promote waitlist"))?;
+    }
+
     // --- B. 扣库存 (保持不变) ---
     if new_status == "completed" || new_status == "absent" {
         let materials: Vec<(Uuid, i32)> = sqlx::query_as(
@@ -200,7 +243,7 @@ pub async fn complete_enrollment_handler(
         }
     }
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
     Ok(Json(updated_enrollment))
 }
 
@@ -208,10 +251,21 @@ pub async fn complete_enrollment_handler(
 pub async fn delete_enrollment_handler(
     State(state): State<AppState>,
     claims: Claims,
+    scope: TenantScope,
     Path(enrollment_id): Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AppError> {
     let hq_id = claims.hq_id;
-    sqlx::query("DELETE FROM class_enrollments WHERE id = $1 AND hq_id = $2")
-        .bind(enrollment_id).bind(hq_id).execute(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut tx = state.db_pool.begin().await?;
+
+    scope.ensure_owned(&mut *tx, Owned::Enrollment(enrollment_id)).await?;
+
+    let class_id: Uuid = sqlx::query_scalar("DELETE FROM class_enrollments WHERE id = $1 AND hq_id = $2 RETURNING class_id")
+        .bind(enrollment_id).bind(hq_id).fetch_optional(&mut *tx).await?
+        .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    // 退课释放名额: 候补自动转正
+    promote_waitlist(&mut tx, class_id).await.map_err(AppError::db("Failed to promote waitlist"))?;
+
+    tx.commit().await?;
     Ok(StatusCode::NO_CONTENT)
 }
\ No newline at end of file
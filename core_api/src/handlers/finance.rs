@@ -7,14 +7,15 @@
  */
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
     Json,
 };
 use chrono::{Utc, Datelike};
 use uuid::Uuid;
 use sqlx::Row; // ✅ 添加Row trait导入
 
-use super::AppState;
+use super::ledger::{funds_account, post_transfer, LedgerTransfer, ACCOUNT_CONTRACT_LIABILITY, ACCOUNT_REVENUE, SOURCE_ORDER_PAYMENT, SOURCE_ORDER_REVENUE};
+use super::{match_payment_to_installments, AppState};
+use crate::tenant::{Owned, TenantScope};
 use crate::models::{
     BaseRankingItem,
     Claims,
@@ -25,11 +26,24 @@ use crate::models::{
     HqFinanceDashboardData,
     OrderItem,
     OrderType,
-    PaymentQuery,
     SubmitPaymentProofPayload, // ★ 修复：导入正确的结构体名
     UpdateInvoiceStatusPayload,
     UpdateOrderPayload,
 };
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+use crate::timezone::business_tz;
+
+// 订单号: 前缀-基地编码-下单时间 (精确到秒) + 3 位随机尾号, 同一分钟内的多笔订单不会撞号
+pub fn new_order_no(prefix: &str, base_code: &str) -> String {
+    format!(
+        "{}-{}-{}{:03}",
+        prefix,
+        base_code,
+        Utc::now().with_timezone(&business_tz()).format("%y%m%d%H%M%S"),
+        rand::random::<u16>() % 1000
+    )
+}
 
 // ==========================================
 // 1. 收入管理 (Orders)
@@ -40,8 +54,8 @@ pub async fn create_income_order_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateOrderPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     // 1. 生成订单号
     let base_code = sqlx::query_scalar::<_, Option<String>>("SELECT code FROM bases WHERE id = $1")
@@ -57,17 +71,9 @@ pub async fn create_income_order_handler(
         OrderType::B2g => "GOV",
         _ => "RET",
     };
-    let order_no = format!(
-        "{}-{}-{}",
-        prefix,
-        base_code,
-        Utc::now().format("%y%m%d%H%M")
-    );
+    let order_no = new_order_no(prefix, &base_code);
 
-    let mut tx = state.db_pool.begin().await.map_err(|e| {
-        tracing::error!("Begin tx failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let mut tx = state.db_pool.begin().await.map_err(AppError::db("Begin tx failed"))?;
 
     // 2. 计算金额
     let mut final_amount_cents = 0;
@@ -79,6 +85,10 @@ pub async fn create_income_order_handler(
         final_amount_cents = (amount * 100.0) as i32;
     }
 
+    if let Some(customer_id) = payload.customer_id {
+        TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::Customer(customer_id)).await?;
+    }
+
     let sales_id = payload
         .sales_id
         .unwrap_or_else(|| Uuid::parse_str(&claims.sub).unwrap_or_default());
@@ -106,21 +116,28 @@ pub async fn create_income_order_handler(
     .bind(payload.contract_url)
     .fetch_one(&mut *tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Insert order failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Insert order failed"))?;
 
-    // 4. 插入明细
+    // 4. 插入明细 (商品 / 卡种须属于本总部; 开卡需要订单关联客户)
     if let Some(items) = payload.items {
+        let scope = TenantScope::from_claims(&claims);
         for item in items {
             let unit_cents = (item.unit_price * 100.0) as i32;
             let total_cents = unit_cents * item.quantity;
+            if let Some(product_id) = item.product_id {
+                scope.ensure_visible(&mut *tx, Owned::Product(product_id)).await?;
+            }
+            if let Some(tier_id) = item.tier_id {
+                scope.ensure_visible(&mut *tx, Owned::Tier(tier_id)).await?;
+                if payload.customer_id.is_none() {
+                    return Err(AppError::BadRequest("order.customer_required"));
+                }
+            }
 
             sqlx::query(
                 r#"
-                INSERT INTO order_items (order_id, name, quantity, unit_price_cents, total_price_cents)
-                VALUES ($1, $2, $3, $4, $5)
+                INSERT INTO order_items (order_id, name, quantity, unit_price_cents, total_price_cents, product_id, tier_id)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
                 "#
             )
             .bind(order_id)
@@ -128,19 +145,15 @@ pub async fn create_income_order_handler(
             .bind(item.quantity)
             .bind(unit_cents)
             .bind(total_cents)
+            .bind(item.product_id)
+            .bind(item.tier_id)
             .execute(&mut *tx)
             .await
-            .map_err(|e| {
-                tracing::error!("Insert item failed: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+            .map_err(AppError::db("Insert item failed"))?;
         }
     }
 
-    tx.commit().await.map_err(|e| {
-        tracing::error!("Commit tx failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    tx.commit().await.map_err(AppError::db("Commit tx failed"))?;
 
     Ok(Json(
         serde_json::json!({ "success": true, "order_id": order_id }),
@@ -148,89 +161,118 @@ pub async fn create_income_order_handler(
 }
 
 // GET /api/v1/finance/orders
+// 分页; 筛选: status / type / customer_id / sales_id / invoice_status / created_from / created_to / event_from / event_to
+// q 搜订单号、客户名和联系人
+const ORDER_LIST: ListSpec = ListSpec {
+    sorts: &[
+        ("created_at", "o.created_at"),
+        ("event_date", "o.event_date"),
+        ("total_amount", "o.total_amount_cents"),
+        ("order_no", "o.order_no"),
+    ],
+    default_sort: "-created_at",
+    tiebreak: "o.id",
+    filters: &[
+        Filter::text("status", "o.status"),
+        Filter::text("type", "o.type"),
+        Filter::uuid("customer_id", "o.customer_id"),
+        Filter::uuid("sales_id", "o.sales_id"),
+        Filter::text("invoice_status", "o.invoice_status"),
+        Filter::date_from("created_from", "o.created_at"),
+        Filter::date_to("created_to", "o.created_at"),
+        Filter::date_from("event_from", "o.event_date"),
+        Filter::date_to("event_to", "o.event_date"),
+    ],
+    search: &["o.order_no", "c.name", "o.contact_name"],
+};
+
 pub async fn get_income_orders_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<crate::models::OrderDetail>>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+    list: ListQuery,
+) -> Result<Json<Page<crate::models::OrderDetail>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
-    let orders = sqlx::query_as::<_, crate::models::OrderDetail>(
-        r#"
-        SELECT 
-            o.id, 
-            o.order_no, 
-            o.type::TEXT as "type_", 
-            o.status::TEXT as status,
-            c.name as customer_name, 
-            o.contact_name, 
-            o.event_date,
-            COALESCE(o.expected_attendees, 0) as expected_attendees, 
-            o.total_amount_cents, 
-            o.paid_amount_cents, 
-            o.created_at,
-            CASE 
-                WHEN o.status = 'paid' THEN 'paid'
-                WHEN o.paid_amount_cents > 0 THEN 'partial'
-                ELSE 'unpaid'
-            END as payment_status,
-            
-            -- ★★★ 必需补全这三行，否则报错 500 ★★★
-            u.full_name as sales_name,
-            o.invoice_status,
-            o.contract_url,
-            o.invoice_no,
-            o.invoice_url
-
-        FROM orders o
-        LEFT JOIN customers c ON o.customer_id = c.id
-        LEFT JOIN users u ON o.sales_id = u.id  -- ★ 记得关联用户表查销售名
-        WHERE o.base_id = $1
-        ORDER BY o.created_at DESC
-        LIMIT 50
-        "#
-    )
-    .bind(base_id)
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| {
-        // 建议加上这行日志，方便以后看报错详情
-        tracing::error!("Get orders failed: {}", e); 
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let page = list
+        .fetch_page(&state.db_pool, &ORDER_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    o.id,
+                    o.order_no,
+                    o.type::TEXT as "type_",
+                    o.status::TEXT as status,
+                    c.name as customer_name,
+                    o.contact_name,
+                    o.event_date,
+                    COALESCE(o.expected_attendees, 0) as expected_attendees,
+                    o.total_amount_cents,
+                    o.paid_amount_cents,
+                    o.created_at,
+                    CASE
+                        WHEN o.status = 'paid' THEN 'paid'
+                        WHEN o.paid_amount_cents > 0 THEN 'partial'
+                        ELSE 'unpaid'
+                    END as payment_status,
+
+                    -- ★★★ 必需补全这三行，否则报错 500 ★★★
+                    u.full_name as sales_name,
+                    o.invoice_status,
+                    o.contract_url,
+                    o.invoice_no,
+                    o.invoice_url
+
+                FROM orders o
+                LEFT JOIN customers c ON o.customer_id = c.id
+                LEFT JOIN users u ON o.sales_id = u.id  -- ★ 记得关联用户表查销售名
+                WHERE o.base_id = "#,
+            );
+            qb.push_bind(base_id);
+        })
+        .await?;
 
-    Ok(Json(orders))
+    Ok(Json(page))
 }
 
 // GET /api/v1/finance/orders/:id/items
 pub async fn get_order_items_handler(
     State(state): State<AppState>,
+    scope: TenantScope,
     Path(order_id): Path<Uuid>,
-) -> Result<Json<Vec<OrderItem>>, StatusCode> {
+) -> Result<Json<Vec<OrderItem>>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::Order(order_id)).await?;
+
     let items = sqlx::query_as::<_, OrderItem>(
-        "SELECT id, name, quantity, unit_price_cents, total_price_cents FROM order_items WHERE order_id = $1"
+        r#"
+        SELECT id, name, quantity, unit_price_cents, total_price_cents, product_id, tier_id, membership_id, refunded_quantity
+        FROM order_items WHERE order_id = $1
+        "#
     )
     .bind(order_id)
     .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     Ok(Json(items))
 }
 
 // PUT /api/v1/finance/orders/:id/invoice
+// 手工登记线下 / 纸质发票; 电子发票走 invoicing.rs, 开具后会覆盖这里的状态和号码
 pub async fn update_invoice_status_handler(
     State(state): State<AppState>,
+    scope: TenantScope,
     Path(order_id): Path<Uuid>,
     Json(payload): Json<UpdateInvoiceStatusPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    sqlx::query("UPDATE orders SET invoice_status = $1, invoice_no = $2, invoice_url = $3 WHERE id = $4")
+) -> Result<Json<serde_json::Value>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::Order(order_id)).await?;
+
+    sqlx::query("UPDATE orders SET invoice_status = $1, invoice_no = $2, invoice_url = $3 WHERE id = $4 AND hq_id = $5")
         .bind(payload.status)
         .bind(payload.invoice_no)
         .bind(payload.invoice_url)
         .bind(order_id)
+        .bind(scope.hq_id)
         .execute(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
@@ -240,11 +282,11 @@ pub async fn update_income_order_handler(
     claims: Claims,
     Path(order_id): Path<Uuid>,
     Json(payload): Json<UpdateOrderPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
     let amount_cents = payload.total_amount.map(|v| (v * 100.0) as i32);
 
-    sqlx::query!(
+    let result = sqlx::query!(
         r#"
         UPDATE orders SET 
             event_date = COALESCE($1, event_date), 
@@ -259,9 +301,26 @@ pub async fn update_income_order_handler(
         base_id
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
 
+    // 改了总额, 原分期计划合计对不上则作废 (未收款, 没有核销记录)
+    if let Some(amount_cents) = amount_cents {
+        sqlx::query(
+            r#"
+            DELETE FROM order_installments WHERE order_id = $1
+              AND (SELECT SUM(amount_cents) FROM order_installments WHERE order_id = $1) <> $2
+            "#,
+        )
+        .bind(order_id)
+        .bind(amount_cents)
+        .execute(&state.db_pool)
+        .await
+        .map_err(AppError::db("Failed to clear installments"))?;
+    }
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
@@ -270,8 +329,8 @@ pub async fn cancel_income_order_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(order_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     let result = sqlx::query!(
         "UPDATE orders SET status='cancelled' WHERE id=$1 AND base_id=$2 AND paid_amount_cents=0",
@@ -279,11 +338,10 @@ pub async fn cancel_income_order_handler(
         base_id
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(AppError::BadRequest("request.invalid"));
     }
     Ok(Json(serde_json::json!({ "success": true })))
 }
@@ -297,8 +355,9 @@ pub async fn submit_payment_proof_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<SubmitPaymentProofPayload>, // ★ 修复：使用正确的 Struct
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    TenantScope::from_claims(&claims).ensure_owned(&state.db_pool, Owned::Order(payload.order_id)).await?;
     let amount_cents = (payload.amount * 100.0) as i32;
 
     sqlx::query!(
@@ -317,89 +376,88 @@ pub async fn submit_payment_proof_handler(
     )
     .execute(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Submit payment failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Submit payment failed"))?;
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
 
 // GET /api/v1/finance/payments
-// 获取待确认流水 (关联订单、客户、销售信息)
-pub async fn get_payment_records_handler(
-    State(state): State<AppState>,
-    claims: Claims,
-    Query(params): Query<PaymentQuery>,
-) -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
-    // ✅ Allow HQ admins and HQ finance roles
-    let is_hq_admin = claims.roles.iter().any(|r| r == "role.hq.admin" || r == "role.hq.finance");
-    
-    // Determine base filter
-    let base_filter = if is_hq_admin {
-        None // HQ admin/finance can see all payments
-    } else {
-        claims.base_id // Base user can only see their own base's payments
-    };
-    
-    if !is_hq_admin && base_filter.is_none() {
-        return Err(StatusCode::FORBIDDEN);
-    }
+// 获取收款流水 (关联订单、客户、销售信息)
+// 分页; 筛选: status / channel / order_id / base_id / created_from / created_to; q 搜付款人、订单号和客户名
+const PAYMENT_RECORD_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "r.created_at"), ("amount", "r.amount_cents")],
+    default_sort: "-created_at",
+    tiebreak: "r.id",
+    filters: &[
+        Filter::text("status", "r.status"),
+        Filter::text("channel", "r.channel"),
+        Filter::uuid("order_id", "r.order_id"),
+        Filter::uuid("base_id", "r.base_id"),
+        Filter::date_from("created_from", "r.created_at"),
+        Filter::date_to("created_to", "r.created_at"),
+    ],
+    search: &["r.payer_name", "o.order_no", "c.name"],
+};
 
-    let status_filter = params.status;
+#[derive(sqlx::FromRow)]
+struct PaymentRecordRow {
+    id: Uuid,
+    amount_cents: i32,
+    transaction_type: String,
+    channel: String,
+    status: String,
+    created_at: Option<chrono::DateTime<chrono::Utc>>,
+    payer_name: Option<String>,
+    proof_image_url: Option<String>,
+    order_no: Option<String>,
+    order_customer_name: Option<String>,
+    order_contact_name: Option<String>,
+    sales_name: Option<String>,
+}
 
-    // Unified query with optional base_id filter
-    let records = sqlx::query!(
-        r#"
-        SELECT 
-            r.id,
-            r.amount_cents,
-            r.transaction_type,
-            r.channel,
-            r.status,
-            r.created_at,
-            r.payer_name as "payer_name?",           
-            r.proof_image_url as "proof_image_url?", 
-            o.order_no as "order_no?",
-            c.name as "order_customer_name?",
-            o.contact_name as "order_contact_name?",
-            u.full_name as "sales_name?"
-        FROM finance_payment_records r
-        LEFT JOIN orders o ON r.order_id = o.id
-        LEFT JOIN customers c ON o.customer_id = c.id
-        LEFT JOIN users u ON o.sales_id = u.id
-        WHERE ($1::uuid IS NULL OR r.base_id = $1::uuid)
-        AND ($2::text IS NULL OR r.status = $2::text)
-        ORDER BY r.created_at DESC
-        LIMIT 100
-        "#,
-        base_filter,
-        status_filter
-    )
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Fetch audit records failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+pub async fn get_payment_records_handler(
+    State(state): State<AppState>,
+    scope: TenantScope, // 总部账号看全部, 基地账号只看本基地 (访问权限由路由声明)
+    list: ListQuery,
+) -> Result<Json<Page<serde_json::Value>>, AppError> {
+    let page = list
+        .fetch_page::<PaymentRecordRow>(&state.db_pool, &PAYMENT_RECORD_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    r.id, r.amount_cents, r.transaction_type, r.channel, r.status, r.created_at,
+                    r.payer_name, r.proof_image_url,
+                    o.order_no,
+                    c.name as order_customer_name,
+                    o.contact_name as order_contact_name,
+                    u.full_name as sales_name
+                FROM finance_payment_records r
+                LEFT JOIN orders o ON r.order_id = o.id
+                LEFT JOIN customers c ON o.customer_id = c.id
+                LEFT JOIN users u ON o.sales_id = u.id
+                WHERE 1 = 1
+                "#,
+            );
+            scope.push_filter(qb, "r");
+        })
+        .await?;
 
     // 转换 JSON
-    let response = records.into_iter().map(|r| {
+    let response = page.map(|r| {
         serde_json::json!({
             "id": r.id,
             "amount_cents": r.amount_cents,
             "type": r.transaction_type,
             "channel": r.channel,
             "status": r.status,
-            // 格式化时间 - add explicit type annotation
-            "created_at": r.created_at.map(|dt: chrono::DateTime<chrono::Utc>| dt.to_rfc3339()).unwrap_or_default(),
-            
+            "created_at": r.created_at.map(|dt| dt.to_rfc3339()).unwrap_or_default(),
+
             // 基础信息
             "payer_name": r.payer_name.unwrap_or_default(),
             "proof_url": r.proof_image_url, // 确保字段名与前端一致
             "sales_name": r.sales_name.unwrap_or("未知销售".to_string()), // ★ 返回销售名
-            
+
             // 嵌套订单信息 (用于前端显示关联订单/客户)
             "order": {
                 "order_no": r.order_no.unwrap_or_default(),
@@ -408,7 +466,7 @@ pub async fn get_payment_records_handler(
                     .unwrap_or("未知客户".to_string())
             }
         })
-    }).collect();
+    });
 
     Ok(Json(response))
 }
@@ -418,14 +476,27 @@ pub async fn verify_payment_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(record_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, AppError> {
     // 开启事务
-    let mut tx = state.db_pool.begin().await.map_err(|e| {
-        tracing::error!("Failed to begin transaction: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let mut tx = state.db_pool.begin().await.map_err(AppError::db("Failed to begin transaction"))?;
+
+    TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::PaymentRecord(record_id)).await?;
+    verify_payment_record(&mut tx, record_id, Uuid::parse_str(&claims.sub).ok()).await?;
+
+    // 提交事务
+    tx.commit().await.map_err(AppError::db("Transaction commit failed"))?;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
 
-    // 1. 更新流水状态 (PENDING -> VERIFIED)
+// 审核收款流水 (人工审核 / 在线支付成功共用): 流水置为已审核, 累加订单已付金额, 核销分期, 记账,
+// 订单刚付清时执行交付; verified_by 为空表示系统自动审核 (在线支付)
+pub async fn verify_payment_record(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    record_id: Uuid,
+    user_id: Option<Uuid>,
+) -> Result<(), AppError> {
+    // 1. 更新流水状态 (PENDING -> VERIFIED), 已审核的不能重复审核 (否则会重复记账)
     // 记录谁审核的 (verified_by) 和审核时间
     let record = sqlx::query!(
         r#"
@@ -434,54 +505,95 @@ pub async fn verify_payment_handler(
             status = 'VERIFIED', 
             verified_at = NOW(), 
             verified_by = $1 
-        WHERE id = $2 
-        RETURNING order_id, amount_cents
+        WHERE id = $2 AND status = 'PENDING'
+        RETURNING order_id, amount_cents, channel
         "#,
-        Uuid::parse_str(&claims.sub).unwrap_or_default(),
+        user_id,
         record_id
     )
-    .fetch_one(&mut *tx)
+    .fetch_optional(&mut **tx)
     .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
+    .map_err(AppError::db("Failed to verify payment record"))?
+    .ok_or(AppError::Conflict("payment.not_pending"))?;
 
     // 2. 更新订单已付金额 & 状态
-    // 使用 RETURNING status 获取更新后的最新状态
+    // 使用 RETURNING status 获取更新后的最新状态, was_paid 为更新前是否已付清
     let updated_order = sqlx::query!(
         r#"
-        UPDATE orders 
+        UPDATE orders o
         SET 
-            paid_amount_cents = paid_amount_cents + $1,
+            paid_amount_cents = o.paid_amount_cents + $1,
             status = CASE 
-                WHEN (paid_amount_cents + $1) >= total_amount_cents THEN 'paid'::order_status 
-                ELSE status 
+                WHEN (o.paid_amount_cents + $1) >= o.total_amount_cents THEN 'paid'::order_status 
+                ELSE o.status 
             END,
             updated_at = NOW()
-        WHERE id = $2
-        RETURNING id, status::TEXT as status
+        FROM (SELECT id, status FROM orders WHERE id = $2 FOR UPDATE) prev
+        WHERE o.id = prev.id
+        RETURNING o.id, o.hq_id, o.base_id, o.order_no, o.paid_amount_cents,
+                  o.status::TEXT as status, (prev.status = 'paid'::order_status) as "was_paid!"
         "#,
         record.amount_cents,
         record.order_id
     )
-    .fetch_one(&mut *tx)
+    .fetch_one(&mut **tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to update order status: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    // 3. 核心业务闭环：如果订单刚刚变为 'paid'，触发交付逻辑
-    if updated_order.status == Some("paid".to_string()) {
+    .map_err(AppError::db("Failed to update order status"))?;
+    let just_paid = updated_order.status.as_deref() == Some("paid") && !updated_order.was_paid;
+
+    // 按到期日先后核销到订单分期
+    match_payment_to_installments(tx, updated_order.id, record_id, record.amount_cents).await?;
+
+    // 3. 记账: 收款 借 现金/银行存款, 贷 合同负债; 订单付清时 借 合同负债, 贷 主营业务收入 (已付清订单的追加收款直接确认)
+    post_transfer(tx, LedgerTransfer {
+        hq_id: updated_order.hq_id,
+        base_id: Some(updated_order.base_id),
+        entry_date: None,
+        source_type: SOURCE_ORDER_PAYMENT,
+        source_id: Some(record_id),
+        description: format!("订单收款: {}", updated_order.order_no),
+        created_by: user_id,
+        debit: funds_account(&record.channel),
+        credit: ACCOUNT_CONTRACT_LIABILITY,
+        amount_cents: record.amount_cents as i64,
+    })
+    .await?;
+
+    // 会员卡部分留在合同负债, 随消课 / 摊销确认收入
+    let revenue_cents = if just_paid {
+        let deferred = sqlx::query_scalar!(
+            r#"SELECT COALESCE(SUM(total_price_cents), 0)::int AS "deferred!" FROM order_items WHERE order_id = $1 AND tier_id IS NOT NULL"#,
+            updated_order.id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        (updated_order.paid_amount_cents - deferred).max(0)
+    } else if updated_order.was_paid {
+        record.amount_cents
+    } else {
+        0
+    };
+    post_transfer(tx, LedgerTransfer {
+        hq_id: updated_order.hq_id,
+        base_id: Some(updated_order.base_id),
+        entry_date: None,
+        source_type: SOURCE_ORDER_REVENUE,
+        source_id: Some(updated_order.id),
+        description: format!("订单收入确认: {}", updated_order.order_no),
+        created_by: user_id,
+        debit: ACCOUNT_CONTRACT_LIABILITY,
+        credit: ACCOUNT_REVENUE,
+        amount_cents: revenue_cents as i64,
+    })
+    .await?;
+
+    // 4. 核心业务闭环：如果订单刚刚变为 'paid'，触发交付逻辑
+    if just_paid {
         tracing::info!(">>> 订单 {} 已付清，开始执行自动交付...", updated_order.id);
-        fulfill_order(&mut tx, updated_order.id).await?;
+        fulfill_order(tx, updated_order.id).await?;
     }
 
-    // 提交事务
-    tx.commit().await.map_err(|e| {
-        tracing::error!("Transaction commit failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    Ok(Json(serde_json::json!({ "success": true })))
+    Ok(())
 }
 
 // POST /api/v1/finance/expenses
@@ -489,8 +601,8 @@ pub async fn create_expense_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateExpensePayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
     let amount_cents = (payload.amount * 100.0) as i32;
 
     sqlx::query(
@@ -508,26 +620,39 @@ pub async fn create_expense_handler(
     .bind(Uuid::parse_str(&claims.sub).unwrap_or_default())
     .bind(payload.proof_url)
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
 // GET /api/v1/finance/expenses
+// 分页; 筛选: category / status / date_from / date_to (按支出日期); q 搜说明
+const EXPENSE_LIST: ListSpec = ListSpec {
+    sorts: &[("expense_date", "expense_date"), ("created_at", "created_at"), ("amount", "amount_cents")],
+    default_sort: "-expense_date",
+    tiebreak: "id",
+    filters: &[
+        Filter::text("category", "category"),
+        Filter::text("status", "status"),
+        Filter::date_from("date_from", "expense_date"),
+        Filter::date_to("date_to", "expense_date"),
+    ],
+    search: &["description"],
+};
+
 pub async fn get_expenses_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<Expense>>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
-    let expenses = sqlx::query_as::<_, Expense>(
-        "SELECT id, base_id, category, amount_cents, description, expense_date, created_at, proof_image_url, status FROM expenses WHERE base_id = $1 ORDER BY expense_date DESC LIMIT 100"
-    )
-    .bind(base_id)
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(expenses))
+    list: ListQuery,
+) -> Result<Json<Page<Expense>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    let page = list
+        .fetch_page(&state.db_pool, &EXPENSE_LIST, |qb| {
+            qb.push("SELECT id, base_id, category, amount_cents, description, expense_date, created_at, proof_image_url, status FROM expenses WHERE base_id = ")
+                .push_bind(base_id);
+        })
+        .await?;
+    Ok(Json(page))
 }
 
 // ✅ Finance Dashboard Query Parameters
@@ -546,15 +671,7 @@ pub async fn get_hq_finance_dashboard_handler(
     State(state): State<AppState>,
     claims: Claims,
     Query(params): Query<FinanceDashboardQuery>, // ✅ 使用新的查询结构
-) -> Result<Json<HqFinanceDashboardData>, StatusCode> {
-    let is_hq = claims
-        .roles
-        .iter()
-        .any(|r| r == "role.hq.admin" || r == "role.hq.finance");
-    if !is_hq {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
+) -> Result<Json<HqFinanceDashboardData>, AppError> {
     // ✅ 动态构建时间条件
     let time_condition = match params.mode.as_deref() {
         Some("year") => {
@@ -578,20 +695,28 @@ pub async fn get_hq_finance_dashboard_handler(
             )
         },
         Some("custom") => {
-            let start = params.start.as_deref().unwrap_or("2026-01-01");
-            let end = params.end.as_deref().unwrap_or("2026-12-31");
+            // 日期拼进 SQL 前先解析, 非法输入回落到默认值
+            let parse = |v: Option<&str>, default: &str| {
+                v.and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+                    .map(|d| d.to_string())
+                    .unwrap_or_else(|| default.to_string())
+            };
+            let start = parse(params.start.as_deref(), "2026-01-01");
+            let end = parse(params.end.as_deref(), "2026-12-31");
             format!("created_at >= '{}' AND created_at < '{} 23:59:59'", start, end)
         },
         _ => "created_at >= date_trunc('month', CURRENT_DATE)".to_string(),
     };
 
     let month_cash_in = sqlx::query_scalar!(
-        "SELECT COALESCE(SUM(amount_cents), 0) FROM finance_payment_records WHERE transaction_type = 'INCOME' AND status = 'VERIFIED' AND created_at >= date_trunc('month', CURRENT_DATE)"
+        "SELECT COALESCE(SUM(amount_cents), 0) FROM finance_payment_records WHERE hq_id = $1 AND transaction_type = 'INCOME' AND status = 'VERIFIED' AND created_at >= date_trunc('month', CURRENT_DATE)",
+        claims.hq_id
     ).fetch_one(&state.db_pool).await.unwrap_or(Some(0)).unwrap_or(0) as i64;
 
     // ✅ 新增：昨日实收 (用于小程序昨日快报)
     let yesterday_cash_in = sqlx::query_scalar!(
-        "SELECT COALESCE(SUM(amount_cents), 0) FROM finance_payment_records WHERE transaction_type = 'INCOME' AND status = 'VERIFIED' AND created_at >= CURRENT_DATE - INTERVAL '1 day' AND created_at < CURRENT_DATE"
+        "SELECT COALESCE(SUM(amount_cents), 0) FROM finance_payment_records WHERE hq_id = $1 AND transaction_type = 'INCOME' AND status = 'VERIFIED' AND created_at >= CURRENT_DATE - INTERVAL '1 day' AND created_at < CURRENT_DATE",
+        claims.hq_id
     ).fetch_one(&state.db_pool).await.unwrap_or(Some(0)).unwrap_or(0) as i64;
 
     let month_cost = 0; 
@@ -612,8 +737,8 @@ pub async fn get_hq_finance_dashboard_handler(
     };
 
     let rankings = sqlx::query_as::<_, BaseRankingItem>(
-        "SELECT b.id as base_id, b.name as base_name, COALESCE(SUM(r.amount_cents), 0) as total_income FROM bases b LEFT JOIN finance_payment_records r ON b.id = r.base_id AND r.status = 'VERIFIED' GROUP BY b.id, b.name ORDER BY total_income DESC LIMIT 5"
-    ).fetch_all(&state.db_pool).await.unwrap_or(vec![]);
+        "SELECT b.id as base_id, b.name as base_name, COALESCE(SUM(CASE WHEN r.transaction_type = 'REFUND' THEN -r.amount_cents ELSE r.amount_cents END), 0) as total_income FROM bases b LEFT JOIN finance_payment_records r ON b.id = r.base_id AND r.status = 'VERIFIED' WHERE b.hq_id = $1 GROUP BY b.id, b.name ORDER BY total_income DESC LIMIT 5"
+    ).bind(claims.hq_id).fetch_all(&state.db_pool).await.unwrap_or(vec![]);
 
     let rankings_with_margin = rankings
         .into_iter()
@@ -627,13 +752,14 @@ pub async fn get_hq_finance_dashboard_handler(
     let query_str = format!(
         "SELECT type as order_type, SUM(total_amount_cents) as total 
          FROM orders 
-         WHERE status IN ('paid', 'completed') AND {} 
+         WHERE hq_id = $1 AND status IN ('paid', 'completed') AND {} 
          GROUP BY type 
          ORDER BY 2 DESC",
         time_condition
     );
 
     let income_rows = sqlx::query(&query_str)
+        .bind(claims.hq_id)
         .fetch_all(&state.db_pool)
         .await
         .unwrap_or_default();
@@ -694,8 +820,8 @@ pub struct BaseFinanceDashboard {
 pub async fn get_base_finance_dashboard_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<BaseFinanceDashboard>, (StatusCode, String)> {
-    let base_id = claims.base_id.ok_or((StatusCode::FORBIDDEN, "No Base ID".to_string()))?;
+) -> Result<Json<BaseFinanceDashboard>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     // 1. Today Income (SUM returns Option<i64>)
     let today_income = sqlx::query_scalar!(
@@ -703,8 +829,7 @@ pub async fn get_base_finance_dashboard_handler(
         base_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .await?
     .unwrap_or(0); 
 
     // 2. Today Expense
@@ -714,8 +839,7 @@ pub async fn get_base_finance_dashboard_handler(
          base_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .await?
     .unwrap_or(0);
 
     // 3. Pending Incomes (COUNT)
@@ -725,8 +849,7 @@ pub async fn get_base_finance_dashboard_handler(
         base_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .await?
     .unwrap_or(0); // Handle Option if inferred, or just 0
 
     // 4. Pending Expenses (COUNT)
@@ -735,8 +858,7 @@ pub async fn get_base_finance_dashboard_handler(
         base_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .await?
     .unwrap_or(0);
 
     Ok(Json(BaseFinanceDashboard {
@@ -748,62 +870,71 @@ pub async fn get_base_finance_dashboard_handler(
 }
 
 // --- 内部辅助函数：订单交付逻辑 (库存扣减/权益发放) ---
+// 按明细交付: 关联商品的从本基地库存出库, 关联卡种的给订单客户开卡 (记入 order_items.membership_id, 退款时停用)
+// 其余明细 (服务 / 活动) 无需交付
 async fn fulfill_order(
     tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     order_id: Uuid,
-) -> Result<(), StatusCode> {
-    
-    // A. 获取订单类型和客户信息
-    // ★★★ 修复 1: 使用 "type_!" 强制非空重命名，避开 Rust 关键字 type
+) -> Result<(), AppError> {
     let order = sqlx::query!(
-        r#"SELECT type::TEXT as "type_!", customer_id FROM orders WHERE id = $1"#,
+        "SELECT hq_id, base_id, order_no, customer_id FROM orders WHERE id = $1",
         order_id
     )
-    .fetch_one(&mut **tx) // ★★★ 修复 2: 使用 &mut **tx (解引用到 PgConnection)
+    .fetch_one(&mut **tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch order type: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch order"))?;
 
-    // B. 获取订单明细
-    // 注意：必须先执行第一步创建 order_items 表，否则这里会编译报错
     let items = sqlx::query!(
-        "SELECT name, quantity, unit_price_cents FROM order_items WHERE order_id = $1",
+        "SELECT id, name, quantity, product_id, tier_id FROM order_items WHERE order_id = $1 AND membership_id IS NULL",
         order_id
     )
-    .fetch_all(&mut **tx) // ★★★ 修复 3: 同样使用 &mut **tx
+    .fetch_all(&mut **tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch items: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    // C. 根据业务类型分发逻辑
-    // ★★★ 修复 4: 这里使用 .type_ (String) 进行匹配
-    match order.type_.as_str() {
-        // --- 场景 1: B2C 个人业务 (如: 购买课包) ---
-        "b2c" => {
-            if let Some(customer_id) = order.customer_id {
-                for item in items {
-                    if item.name.contains("课") {
-                        tracing::info!(">>> [交付-B2C] 给客户 {:?} 增加 {} 课时", customer_id, item.quantity);
-                        // TODO: 实际更新 customer_memberships 表
-                    }
-                }
-            }
-        },
-
-        // --- 场景 2: B2B/B2G 团单业务 (如: 购买教具/耗材) ---
-        "b2b" | "b2g" => {
-            for item in items {
-                tracing::info!(">>> [交付-B2B] 扣减库存: 商品={}, 数量={}", item.name, item.quantity);
-                // TODO: 实际更新 materials 表
+    .map_err(AppError::db("Failed to fetch items"))?;
+
+    for item in items {
+        if let Some(product_id) = item.product_id {
+            // 出库: 库存不足时整单审核失败, 先补货再审核
+            let stock = sqlx::query_scalar!(
+                "SELECT quantity FROM base_inventory WHERE base_id = $1 AND product_id = $2 FOR UPDATE",
+                order.base_id, product_id
+            )
+            .fetch_optional(&mut **tx)
+            .await?
+            .unwrap_or(0);
+            if stock < item.quantity {
+                return Err(AppError::Conflict("stock.insufficient"));
             }
-        },
-        
-        _ => {
-            tracing::warn!(">>> 未知的订单类型，跳过交付逻辑: {}", order.type_);
+            sqlx::query!(
+                "UPDATE base_inventory SET quantity = quantity - $1, last_updated_at = NOW() WHERE base_id = $2 AND product_id = $3",
+                item.quantity, order.base_id, product_id
+            )
+            .execute(&mut **tx)
+            .await?;
+            sqlx::query!(
+                "INSERT INTO inventory_logs (base_id, product_id, change_amount, reason) VALUES ($1, $2, $3, $4)",
+                order.base_id, product_id, -item.quantity, format!("订单出库: {}", order.order_no)
+            )
+            .execute(&mut **tx)
+            .await?;
+            tracing::info!(">>> [交付] 订单 {} 出库: 商品={}, 数量={}", order.order_no, item.name, item.quantity);
+        } else if let (Some(tier_id), Some(customer_id)) = (item.tier_id, order.customer_id) {
+            let membership_id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO customer_memberships (hq_id, customer_id, tier_id, start_date, expiry_date, remaining_uses, is_active)
+                SELECT $1, $2, t.id, NOW(), NOW() + make_interval(days => t.duration_days), t.usage_count, true
+                FROM membership_tiers t WHERE t.id = $3
+                RETURNING id
+                "#,
+                order.hq_id, customer_id, tier_id
+            )
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(AppError::db("Failed to issue membership"))?;
+            sqlx::query!("UPDATE order_items SET membership_id = $1 WHERE id = $2", membership_id, item.id)
+                .execute(&mut **tx)
+                .await?;
+            tracing::info!(">>> [交付] 订单 {} 开卡: 客户={}, 卡种={}", order.order_no, customer_id, item.name);
         }
     }
 
@@ -8,25 +8,23 @@ use serde_json::Value;
 
 use super::AppState;
 use crate::models::Tenant;
+use crate::error::AppError;
 
 pub async fn db_health_handler(
     State(state): State<AppState>,
-) -> Result<Json<Tenant>, StatusCode> {
+) -> Result<Json<Tenant>, AppError> {
     match sqlx::query_as::<_, Tenant>("SELECT id, name FROM hqs LIMIT 1")
         .fetch_one(&state.db_pool)
         .await
     {
         Ok(hq) => Ok(Json(hq)),
-        Err(e) => {
-            tracing::error!("Database query failed: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        Err(e) => Err(AppError::Database { context: "Database query failed", source: e }),
     }
 }
 
 pub async fn ai_health_handler(
     State(state): State<AppState>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, AppError> {
     let url = format!("{}/health", state.ai_api_url);
     tracing::info!("Calling AI service at: {}", url);
     match state.http_client.get(&url).send().await {
@@ -36,12 +34,12 @@ pub async fn ai_health_handler(
                 Ok(Json(ai_response))
             } else {
                 tracing::error!("AI service returned error: {}", response.status());
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(StatusCode::INTERNAL_SERVER_ERROR.into())
             }
         }
         Err(e) => {
             tracing::error!("Failed to call AI service: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into())
         }
     }
 }
\ No newline at end of file
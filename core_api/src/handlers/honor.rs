@@ -5,19 +5,19 @@
 
 use axum::{
     extract::{State, Path}, 
-    http::StatusCode, 
     Json
 };
 use uuid::Uuid; 
 
 use super::AppState;
 use crate::models::{Claims, HonorRank, CreateHonorRankPayload, UpdateHonorRankPayload};
+use crate::error::AppError;
 
 // (GET /api/v1/honor-ranks)
 pub async fn get_honor_ranks(
     State(state): State<AppState>,
     claims: Claims, 
-) -> Result<Json<Vec<HonorRank>>, StatusCode> {
+) -> Result<Json<Vec<HonorRank>>, AppError> {
     
     let hq_id = claims.hq_id; 
 
@@ -33,10 +33,7 @@ pub async fn get_honor_ranks(
     .await
     {
         Ok(ranks) => ranks,
-        Err(e) => {
-            tracing::error!("Failed to fetch honor ranks: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to fetch honor ranks", source: e }),
     };
 
     Ok(Json(ranks))
@@ -47,26 +44,13 @@ pub async fn create_honor_rank(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateHonorRankPayload>,
-) -> Result<Json<HonorRank>, StatusCode> {
-    
-    // 1. 权限检查
-    let is_authorized = claims.roles.iter().any(|role| 
-        role == "role.hq.admin"
-    );
-
-    if !is_authorized {
-        tracing::warn!(
-            "Unauthorized attempt to create honor rank by user {} (roles: {:?})",
-            claims.sub,
-            claims.roles
-        );
-        return Err(StatusCode::FORBIDDEN); 
-    }
+) -> Result<Json<HonorRank>, AppError> {
     
     let hq_id = claims.hq_id; 
 
-    // 2. 插入数据
-    let new_rank = match sqlx::query_as::<_, HonorRank>(
+    // 插入数据
+    // 等级或名称重复时返回 409 honor_rank.duplicate
+    let new_rank = sqlx::query_as::<_, HonorRank>(
         r#"
         INSERT INTO honor_ranks (hq_id, name_key, rank_level, points_required, badge_icon_url)
         VALUES ($1, $2, $3, $4, $5)
@@ -80,20 +64,7 @@ pub async fn create_honor_rank(
     .bind(payload.badge_icon_url)
     .fetch_one(&state.db_pool)
     .await
-    {
-        Ok(rank) => rank,
-        Err(e) => {
-            // 捕获重复错误
-            if let Some(db_err) = e.as_database_error() {
-                if db_err.is_unique_violation() {
-                    tracing::warn!("Failed to create honor rank: duplicate level {} or name {}", payload.rank_level, payload.name_key);
-                    return Err(StatusCode::CONFLICT); // 409
-                }
-            }
-            tracing::error!("Failed to create honor rank: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
+    .map_err(AppError::db("Failed to create honor rank"))?;
 
     Ok(Json(new_rank))
 }
@@ -104,13 +75,9 @@ pub async fn update_honor_rank(
     claims: Claims,
     Path(rank_id): Path<Uuid>, 
     Json(payload): Json<UpdateHonorRankPayload>,
-) -> Result<Json<HonorRank>, StatusCode> {
+) -> Result<Json<HonorRank>, AppError> {
     
-    // 1. 权限检查
-    let is_authorized = claims.roles.iter().any(|role| role == "role.hq.admin");
-    if !is_authorized { return Err(StatusCode::FORBIDDEN); }
-
-    // 2. 执行更新
+    // 执行更新
     let updated_rank = match sqlx::query_as::<_, HonorRank>(
         r#"
         UPDATE honor_ranks 
@@ -126,11 +93,8 @@ pub async fn update_honor_rank(
     .await
     {
         Ok(Some(rank)) => rank,
-        Ok(None) => return Err(StatusCode::NOT_FOUND), // 找不到记录
-        Err(e) => {
-            tracing::error!("Failed to update honor rank: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Ok(None) => return Err(AppError::NotFound("resource.not_found")), // 找不到记录
+        Err(e) => return Err(AppError::Database { context: "Failed to update honor rank", source: e }),
     };
 
     Ok(Json(updated_rank))
@@ -2,64 +2,55 @@
  * src/handlers/hq.rs
  * 职责: 租户级总览 (V16.2 - 增加基地信息查询)
  */
-use axum::{extract::State, http::StatusCode, Json};
-use super::AppState;
+use axum::{extract::State, Json};
+use super::{AppState, PARTICIPANT_LIST};
 use crate::models::{Claims, ParticipantDetail, DashboardStats, AdvancedDashboardStats, PendingStaff, BaseRankingItem};
+use crate::error::AppError;
+use crate::pagination::{ListQuery, Page};
 
-// (GET /api/v1/hq/participants)
+// (GET /api/v1/hq/participants) 全部基地的学员, 分页 / 筛选同 PARTICIPANT_LIST
 // (★ V16.3 - 增加 last_class_time)
 pub async fn get_all_hq_participants(
     State(state): State<AppState>,
-    claims: Claims, 
-) -> Result<Json<Vec<ParticipantDetail>>, StatusCode> {
-    
-    if !claims.roles.iter().any(|r| r == "role.hq.admin") {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
+    claims: Claims,
+    list: ListQuery,
+) -> Result<Json<Page<ParticipantDetail>>, AppError> {
     let hq_id = claims.hq_id;
 
-    let participants = match sqlx::query_as::<_, ParticipantDetail>(
-        r#"
-        SELECT 
-            p.id, p.name, p.date_of_birth, p.gender,
-            c.name AS customer_name,
-            c.phone_number AS customer_phone,
-            pp.current_total_points,
-            hr.name_key AS rank_name_key,
-            b.id as base_id,
-            b.name as base_name,
-            
-            -- (★ V16.3 新增: 最近一次实到上课时间)
-            (
-                SELECT MAX(cl.start_time)
-                FROM class_enrollments ce
-                JOIN classes cl ON ce.class_id = cl.id
-                WHERE ce.participant_id = p.id 
-                  AND ce.status = 'completed'
-            ) as last_class_time
+    let page = list
+        .fetch_page(&state.db_pool, &PARTICIPANT_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    p.id, p.name, p.date_of_birth, p.gender,
+                    c.name AS customer_name,
+                    c.phone_number AS customer_phone,
+                    pp.current_total_points,
+                    hr.name_key AS rank_name_key,
+                    b.id as base_id,
+                    b.name as base_name,
 
-        FROM participants p
-        JOIN customers c ON p.customer_id = c.id
-        LEFT JOIN bases b ON c.base_id = b.id 
-        LEFT JOIN participant_profiles pp ON p.id = pp.participant_id
-        LEFT JOIN honor_ranks hr ON pp.current_honor_rank_id = hr.id
-        WHERE p.hq_id = $1
-        ORDER BY p.created_at DESC
-        "#,
-    )
-    .bind(hq_id) 
-    .fetch_all(&state.db_pool)
-    .await
-    {
-        Ok(list) => list,
-        Err(e) => {
-            tracing::error!("Failed to fetch participants: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
+                    -- (★ V16.3 新增: 最近一次实到上课时间)
+                    (
+                        SELECT MAX(cl.start_time)
+                        FROM class_enrollments ce
+                        JOIN classes cl ON ce.class_id = cl.id
+                        WHERE ce.participant_id = p.id
+                          AND ce.status = 'completed'
+                    ) as last_class_time
+
+                FROM participants p
+                JOIN customers c ON p.customer_id = c.id
+                LEFT JOIN bases b ON c.base_id = b.id
+                LEFT JOIN participant_profiles pp ON p.id = pp.participant_id
+                LEFT JOIN honor_ranks hr ON pp.current_honor_rank_id = hr.id
+                WHERE p.hq_id = "#,
+            );
+            qb.push_bind(hq_id);
+        })
+        .await?;
 
-    Ok(Json(participants))
+    Ok(Json(page))
 }
 
 // (★ V16.2 Step 2: 新增统计接口)
@@ -67,11 +58,8 @@ pub async fn get_all_hq_participants(
 pub async fn get_hq_participant_stats(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<crate::models::TenantParticipantStats>, StatusCode> {
+) -> Result<Json<crate::models::TenantParticipantStats>, AppError> {
     
-    if !claims.roles.iter().any(|r| r == "role.hq.admin") {
-        return Err(StatusCode::FORBIDDEN);
-    }
 
     let hq_id = claims.hq_id;
 
@@ -97,10 +85,7 @@ pub async fn get_hq_participant_stats(
     .await
     {
         Ok(s) => s,
-        Err(e) => {
-            tracing::error!("Failed to fetch participant stats: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to fetch participant stats", source: e }),
     };
 
     Ok(Json(stats))
@@ -110,11 +95,8 @@ pub async fn get_hq_participant_stats(
 pub async fn get_hq_dashboard_stats_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<DashboardStats>, StatusCode> {
+) -> Result<Json<DashboardStats>, AppError> {
     // ✅ Allow both HQ admin and finance roles
-    if !claims.roles.iter().any(|r| r == "role.hq.admin" || r == "role.hq.finance") {
-        return Err(StatusCode::FORBIDDEN);
-    }
 
     let hq_id = claims.hq_id;
 
@@ -136,10 +118,7 @@ pub async fn get_hq_dashboard_stats_handler(
     )
     .fetch_one(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch HQ dashboard stats: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch HQ dashboard stats"))?;
 
     // 计算增长率
     let revenue_growth = if stats_row.yesterday_revenue.unwrap_or(0) > 0 {
@@ -280,11 +259,8 @@ pub async fn get_hq_dashboard_stats_handler(
 pub async fn get_hq_dashboard_analytics_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<AdvancedDashboardStats>, StatusCode> {
+) -> Result<Json<AdvancedDashboardStats>, AppError> {
     // ✅ Allow admin, finance, and operation roles for analytics
-    if !claims.roles.iter().any(|r| r == "role.hq.admin" || r == "role.hq.finance" || r == "role.hq.operation") {
-        return Err(StatusCode::FORBIDDEN);
-    }
 
     let hq_id = claims.hq_id;
 
@@ -303,10 +279,7 @@ pub async fn get_hq_dashboard_analytics_handler(
     )
     .fetch_one(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch HQ dashboard analytics: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch HQ dashboard analytics"))?;
 
     let conversion_rate = if stats.leads_count.unwrap_or(0) > 0 {
         (stats.members_count.unwrap_or(0) as f64 / stats.leads_count.unwrap_or(0) as f64) * 100.0
@@ -335,10 +308,7 @@ pub async fn get_hq_dashboard_analytics_handler(
 pub async fn get_hq_dashboard_pending_staff_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<PendingStaff>>, StatusCode> {
-    if !claims.roles.iter().any(|r| r == "role.hq.admin" || r == "role.hq.hr") {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<Vec<PendingStaff>>, AppError> {
 
     let hq_id = claims.hq_id;
 
@@ -359,10 +329,7 @@ pub async fn get_hq_dashboard_pending_staff_handler(
     .bind(hq_id)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch pending staff: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to fetch pending staff"))?;
 
     Ok(Json(staff))
 }
\ No newline at end of file
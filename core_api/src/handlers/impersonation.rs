@@ -0,0 +1,159 @@
+/*
+ * src/handlers/impersonation.rs
+ * 职责: 开发调试用的模拟登录 (替代原 dev_token_ 后门)
+ * 1. 仅当环境变量 DEV_IMPERSONATION=true 时注册签发接口, 生产环境不开启
+ * 2. 总部管理员可为本总部的任意员工 / 家长签发短时 Token (不可续期), 每次签发写入 impersonation_logs
+ */
+
+use axum::{extract::State, Json};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::{open_impersonation_session, AppState, SUBJECT_CUSTOMER, SUBJECT_USER};
+use crate::models::Claims;
+use crate::tenant::{Owned, TenantScope};
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+
+const TOKEN_TTL_MINUTES: i64 = 30;
+
+pub const TARGET_USER: &str = "user";
+pub const TARGET_CUSTOMER: &str = "customer";
+
+// 启动时读取一次: DEV_IMPERSONATION=true / 1
+pub fn impersonation_enabled() -> bool {
+    std::env::var("DEV_IMPERSONATION")
+        .map(|v| matches!(v.trim(), "1" | "true"))
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImpersonatePayload {
+    pub target_type: String, // user | customer
+    pub target_id: Uuid,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpersonateResponse {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub audit_id: Uuid,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ImpersonationLog {
+    pub id: Uuid,
+    pub actor_id: Uuid,
+    pub actor_name: Option<String>,
+    pub target_type: String,
+    pub target_id: Uuid,
+    pub target_name: Option<String>,
+    pub reason: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+// (POST /api/v1/hq/impersonations) 为本总部的员工 / 家长签发短时 Token
+pub async fn create_impersonation_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    scope: TenantScope,
+    Json(payload): Json<ImpersonatePayload>,
+) -> Result<Json<ImpersonateResponse>, AppError> {
+    let actor_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::Unauthorized("auth.unauthorized"))?;
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let expires_at = Utc::now() + Duration::minutes(TOKEN_TTL_MINUTES);
+    let subject_type = match payload.target_type.as_str() {
+        TARGET_USER => {
+            scope.ensure_owned(&state.db_pool, Owned::User(payload.target_id)).await?;
+            SUBJECT_USER
+        }
+        TARGET_CUSTOMER => {
+            scope.ensure_owned(&state.db_pool, Owned::Customer(payload.target_id)).await?;
+            SUBJECT_CUSTOMER
+        }
+        _ => return Err(AppError::BadRequest("request.invalid")),
+    };
+
+    // 会话与审计日志同一事务写入, 审计写入失败则不签发
+    let mut tx = state.db_pool.begin().await?;
+    let session = open_impersonation_session(&mut tx, &state.jwt_secret, subject_type, payload.target_id, actor_id, expires_at)
+        .await?
+        .ok_or(AppError::NotFound("resource.not_found"))?; // 已停用的账号
+
+    let audit_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO impersonation_logs (hq_id, actor_id, target_type, target_id, target_name, reason, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+        "#,
+    )
+    .bind(claims.hq_id)
+    .bind(actor_id)
+    .bind(&payload.target_type)
+    .bind(payload.target_id)
+    .bind(&session.claims.full_name)
+    .bind(reason)
+    .bind(expires_at)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to write impersonation log"))?;
+
+    tx.commit().await?;
+
+    tracing::warn!(
+        "Impersonation: user {} issued a token for {} {} (audit {})",
+        actor_id,
+        payload.target_type,
+        payload.target_id,
+        audit_id
+    );
+
+    Ok(Json(ImpersonateResponse { token: session.tokens.token, expires_at, audit_id }))
+}
+
+// (GET /api/v1/hq/impersonations) 本总部的模拟登录记录
+// 分页; 筛选: actor_id / target_type / target_id / created_from / created_to
+const IMPERSONATION_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "l.created_at")],
+    default_sort: "-created_at",
+    tiebreak: "l.id",
+    filters: &[
+        Filter::uuid("actor_id", "l.actor_id"),
+        Filter::text("target_type", "l.target_type"),
+        Filter::uuid("target_id", "l.target_id"),
+        Filter::date_from("created_from", "l.created_at"),
+        Filter::date_to("created_to", "l.created_at"),
+    ],
+    search: &["l.target_name", "l.reason", "u.full_name"],
+};
+
+pub async fn list_impersonations_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    list: ListQuery,
+) -> Result<Json<Page<ImpersonationLog>>, AppError> {
+    let hq_id = claims.hq_id;
+    let page = list
+        .fetch_page(&state.db_pool, &IMPERSONATION_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT l.id, l.actor_id, u.full_name AS actor_name, l.target_type, l.target_id, l.target_name,
+                       l.reason, l.expires_at, l.created_at
+                FROM impersonation_logs l
+                LEFT JOIN users u ON l.actor_id = u.id
+                WHERE l.hq_id = "#,
+            );
+            qb.push_bind(hq_id);
+        })
+        .await?;
+
+    Ok(Json(page))
+}
@@ -0,0 +1,871 @@
+/*
+ * src/handlers/invoicing.rs
+ * 职责: 电子发票 (开具通过 crate::invoice::InvoiceProvider)
+ * 1. 开票申请: 购方税务信息 + 明细 (取自订单明细, 可只开部分数量; 无明细的订单按金额开一行服务费)
+ *    可开票金额 = 订单总额 - 已退款 - 已申请开票 (不含已撤销); 申请后立即开具, 失败的可重试或撤销
+ * 2. 红字发票: 对已开具的蓝字发票整张冲红; 退款审批通过时按退款金额从最近的蓝字发票开始冲红
+ *    (与审批同一事务生成红字申请, 提交后再调用开票服务, 开票失败不影响审批)
+ * 3. 下载 PDF / XML 版式文件
+ * 4. 开票与收款核对: 每个订单 已开票 (蓝 + 红) 与 已收款 的差额
+ * 开具后同步订单 invoice_status / invoice_no / invoice_url
+ */
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use super::AppState;
+use crate::error::AppError;
+use crate::invoice::{InvoiceBuyer, InvoiceDraft, InvoiceLineDraft};
+use crate::models::Claims;
+use crate::tenant::{Owned, TenantScope};
+
+const DEFAULT_TAX_RATE_BP: i32 = 600; // 教育服务 6%
+const DEFAULT_LINE_NAME: &str = "培训服务费";
+
+// 含税金额反算税额, 四舍五入 (红字为负)
+fn tax_of(amount_cents: i64, tax_rate_bp: i32) -> i64 {
+    let numerator = amount_cents.abs() * tax_rate_bp as i64;
+    let denominator = 10_000 + tax_rate_bp as i64;
+    let tax = (numerator + denominator / 2) / denominator;
+    if amount_cents < 0 { -tax } else { tax }
+}
+
+// ---------------------------------------------------------
+// 数据结构
+// ---------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct InvoiceItemPayload {
+    pub order_item_id: Uuid,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInvoicePayload {
+    pub invoice_type: Option<String>, // normal (默认) / special
+    pub buyer_name: String,
+    pub buyer_tax_id: Option<String>,
+    pub buyer_address_phone: Option<String>,
+    pub buyer_bank_account: Option<String>,
+    pub buyer_email: Option<String>,
+    pub tax_rate: Option<f64>,        // 百分比, 默认 6
+    pub remark: Option<String>,
+    pub items: Option<Vec<InvoiceItemPayload>>, // 不传则开全部可开明细
+    pub amount: Option<f64>,          // 元, 仅用于没有明细的订单, 不传则开全部可开金额
+    pub item_name: Option<String>,    // 没有明细的订单的开票项目名称
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReverseInvoicePayload {
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Invoice {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub kind: String,
+    pub invoice_type: String,
+    pub status: String,
+    pub original_invoice_id: Option<Uuid>,
+    pub refund_request_id: Option<Uuid>,
+    pub buyer_name: String,
+    pub buyer_tax_id: Option<String>,
+    pub buyer_address_phone: Option<String>,
+    pub buyer_bank_account: Option<String>,
+    pub buyer_email: Option<String>,
+    pub amount_cents: i32,
+    pub tax_cents: i32,
+    pub remark: Option<String>,
+    pub invoice_no: Option<String>,
+    pub issued_at: Option<DateTime<Utc>>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub lines: serde_json::Value, // [{line_no, order_item_id, name, quantity, unit_price_cents, amount_cents, tax_rate_bp, tax_cents}]
+}
+
+const INVOICE_SELECT: &str = r#"
+    SELECT i.id, i.order_id, i.kind, i.invoice_type, i.status, i.original_invoice_id, i.refund_request_id,
+           i.buyer_name, i.buyer_tax_id, i.buyer_address_phone, i.buyer_bank_account, i.buyer_email,
+           i.amount_cents, i.tax_cents, i.remark, i.invoice_no, i.issued_at, i.attempts, i.last_error, i.created_at,
+           COALESCE(
+               (SELECT jsonb_agg(jsonb_build_object(
+                    'line_no', l.line_no, 'order_item_id', l.order_item_id, 'name', l.name,
+                    'quantity', l.quantity, 'unit_price_cents', l.unit_price_cents, 'amount_cents', l.amount_cents,
+                    'tax_rate_bp', l.tax_rate_bp, 'tax_cents', l.tax_cents) ORDER BY l.line_no)
+                FROM invoice_lines l WHERE l.invoice_id = i.id),
+               '[]'::jsonb
+           ) AS lines
+    FROM invoices i
+"#;
+
+async fn fetch_invoice(pool: &PgPool, invoice_id: Uuid) -> Result<Invoice, AppError> {
+    sqlx::query_as::<_, Invoice>(&format!("{} WHERE i.id = $1", INVOICE_SELECT))
+        .bind(invoice_id)
+        .fetch_one(pool)
+        .await
+        .map_err(AppError::db("Failed to fetch invoice"))
+}
+
+#[derive(FromRow)]
+struct LineDraft {
+    order_item_id: Option<Uuid>,
+    name: String,
+    quantity: Option<i32>,
+    unit_price_cents: Option<i32>,
+    amount_cents: i64,
+    tax_rate_bp: i32,
+}
+
+struct InvoiceHeader {
+    hq_id: Uuid,
+    base_id: Uuid,
+    order_id: Uuid,
+    kind: &'static str,
+    invoice_type: String,
+    original_invoice_id: Option<Uuid>,
+    refund_request_id: Option<Uuid>,
+    buyer: InvoiceBuyer,
+    remark: Option<String>,
+    requested_by: Option<Uuid>,
+}
+
+// 写入开票申请 (待开具) 及明细, 税额按行计算
+async fn insert_invoice(conn: &mut PgConnection, header: &InvoiceHeader, lines: &[LineDraft]) -> Result<Uuid, AppError> {
+    let amount_cents: i64 = lines.iter().map(|l| l.amount_cents).sum();
+    let tax_cents: i64 = lines.iter().map(|l| tax_of(l.amount_cents, l.tax_rate_bp)).sum();
+    let invoice_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO invoices
+        (hq_id, base_id, order_id, kind, invoice_type, original_invoice_id, refund_request_id,
+         buyer_name, buyer_tax_id, buyer_address_phone, buyer_bank_account, buyer_email,
+         amount_cents, tax_cents, remark, requested_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+        RETURNING id
+        "#,
+    )
+    .bind(header.hq_id)
+    .bind(header.base_id)
+    .bind(header.order_id)
+    .bind(header.kind)
+    .bind(&header.invoice_type)
+    .bind(header.original_invoice_id)
+    .bind(header.refund_request_id)
+    .bind(&header.buyer.name)
+    .bind(&header.buyer.tax_id)
+    .bind(&header.buyer.address_phone)
+    .bind(&header.buyer.bank_account)
+    .bind(&header.buyer.email)
+    .bind(amount_cents as i32)
+    .bind(tax_cents as i32)
+    .bind(&header.remark)
+    .bind(header.requested_by)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to create invoice"))?;
+
+    for (idx, line) in lines.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO invoice_lines
+            (invoice_id, line_no, order_item_id, name, quantity, unit_price_cents, amount_cents, tax_rate_bp, tax_cents)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(invoice_id)
+        .bind(idx as i32 + 1)
+        .bind(line.order_item_id)
+        .bind(&line.name)
+        .bind(line.quantity)
+        .bind(line.unit_price_cents)
+        .bind(line.amount_cents as i32)
+        .bind(line.tax_rate_bp)
+        .bind(tax_of(line.amount_cents, line.tax_rate_bp) as i32)
+        .execute(&mut *conn)
+        .await
+        .map_err(AppError::db("Failed to create invoice line"))?;
+    }
+    Ok(invoice_id)
+}
+
+// ---------------------------------------------------------
+// 开具
+// ---------------------------------------------------------
+
+#[derive(FromRow)]
+struct IssueRow {
+    order_id: Uuid,
+    kind: String,
+    invoice_type: String,
+    buyer_name: String,
+    buyer_tax_id: Option<String>,
+    buyer_address_phone: Option<String>,
+    buyer_bank_account: Option<String>,
+    buyer_email: Option<String>,
+    amount_cents: i32,
+    tax_cents: i32,
+    remark: Option<String>,
+    seller_name: String,
+    original_invoice_no: Option<String>,
+}
+
+#[derive(FromRow)]
+struct IssueLineRow {
+    name: String,
+    quantity: Option<i32>,
+    unit_price_cents: Option<i32>,
+    amount_cents: i32,
+    tax_rate_bp: i32,
+    tax_cents: i32,
+}
+
+// 调用开票服务开具一张待开具 / 开具失败的发票; 开具中 (issuing) 的不会被重复提交
+async fn issue_invoice(state: &AppState, invoice_id: Uuid) -> Result<Invoice, AppError> {
+    let row = sqlx::query_as::<_, IssueRow>(
+        r#"
+        WITH claimed AS (
+            UPDATE invoices SET status = 'issuing', attempts = attempts + 1, updated_at = NOW()
+            WHERE id = $1 AND status IN ('pending', 'failed')
+            RETURNING *
+        )
+        SELECT c.order_id, c.kind, c.invoice_type, c.buyer_name, c.buyer_tax_id, c.buyer_address_phone,
+               c.buyer_bank_account, c.buyer_email, c.amount_cents, c.tax_cents, c.remark,
+               h.name AS seller_name, o.invoice_no AS original_invoice_no
+        FROM claimed c
+        JOIN hqs h ON h.id = c.hq_id
+        LEFT JOIN invoices o ON o.id = c.original_invoice_id
+        "#,
+    )
+    .bind(invoice_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to claim invoice"))?
+    .ok_or(AppError::Conflict("invoice.not_pending"))?;
+
+    let lines = sqlx::query_as::<_, IssueLineRow>(
+        "SELECT name, quantity, unit_price_cents, amount_cents, tax_rate_bp, tax_cents FROM invoice_lines WHERE invoice_id = $1 ORDER BY line_no",
+    )
+    .bind(invoice_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    let draft = InvoiceDraft {
+        request_id: invoice_id,
+        kind: row.kind,
+        invoice_type: row.invoice_type,
+        seller_name: row.seller_name,
+        buyer: InvoiceBuyer {
+            name: row.buyer_name,
+            tax_id: row.buyer_tax_id,
+            address_phone: row.buyer_address_phone,
+            bank_account: row.buyer_bank_account,
+            email: row.buyer_email,
+        },
+        lines: lines
+            .into_iter()
+            .map(|l| InvoiceLineDraft {
+                name: l.name,
+                quantity: l.quantity,
+                unit_price_cents: l.unit_price_cents,
+                amount_cents: l.amount_cents as i64,
+                tax_rate_bp: l.tax_rate_bp,
+                tax_cents: l.tax_cents as i64,
+            })
+            .collect(),
+        total_cents: row.amount_cents as i64,
+        tax_cents: row.tax_cents as i64,
+        original_invoice_no: row.original_invoice_no,
+        remark: row.remark,
+    };
+
+    match state.invoice.issue(&draft).await {
+        Ok(issued) => {
+            sqlx::query(
+                r#"
+                UPDATE invoices SET status = 'issued', invoice_no = $1, issued_at = $2, pdf = $3, xml = $4,
+                    last_error = NULL, updated_at = NOW()
+                WHERE id = $5
+                "#,
+            )
+            .bind(&issued.invoice_no)
+            .bind(issued.issued_at)
+            .bind(&issued.pdf)
+            .bind(&issued.xml)
+            .bind(invoice_id)
+            .execute(&state.db_pool)
+            .await
+            .map_err(AppError::db("Failed to save issued invoice"))?;
+            sync_order_invoice_status(&state.db_pool, row.order_id).await?;
+            fetch_invoice(&state.db_pool, invoice_id).await
+        }
+        Err(e) => {
+            sqlx::query("UPDATE invoices SET status = 'failed', last_error = $1, updated_at = NOW() WHERE id = $2")
+                .bind(e.to_string())
+                .bind(invoice_id)
+                .execute(&state.db_pool)
+                .await
+                .map_err(AppError::db("Failed to record invoice failure"))?;
+            sync_order_invoice_status(&state.db_pool, row.order_id).await?;
+            Err(AppError::from(e).with_details(serde_json::json!({ "invoice_id": invoice_id })))
+        }
+    }
+}
+
+// 订单开票状态: 有已开具净额为 billed, 只有待开具 / 失败的为 billing; 发票号码 / 链接取最近一张未全部冲红的蓝字发票
+async fn sync_order_invoice_status(pool: &PgPool, order_id: Uuid) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE orders o SET
+            invoice_status = CASE
+                WHEN s.issued_cents > 0 THEN 'billed'
+                WHEN s.open_count > 0 THEN 'billing'
+                ELSE 'unbilled'
+            END,
+            invoice_no = latest.invoice_no,
+            invoice_url = CASE WHEN latest.id IS NULL THEN NULL ELSE '/api/v1/finance/invoices/' || latest.id || '/pdf' END
+        FROM (
+            SELECT COALESCE(SUM(amount_cents) FILTER (WHERE status = 'issued'), 0) AS issued_cents,
+                   COUNT(*) FILTER (WHERE status IN ('pending', 'issuing', 'failed')) AS open_count
+            FROM invoices WHERE order_id = $1
+        ) s
+        LEFT JOIN LATERAL (
+            SELECT b.id, b.invoice_no FROM invoices b
+            WHERE b.order_id = $1 AND b.kind = 'blue' AND b.status = 'issued'
+              AND b.amount_cents + COALESCE((SELECT SUM(r.amount_cents) FROM invoices r
+                                             WHERE r.original_invoice_id = b.id AND r.status = 'issued'), 0) > 0
+            ORDER BY b.issued_at DESC LIMIT 1
+        ) latest ON TRUE
+        WHERE o.id = $1
+        "#,
+    )
+    .bind(order_id)
+    .execute(pool)
+    .await
+    .map_err(AppError::db("Failed to sync order invoice status"))?;
+    Ok(())
+}
+
+// ---------------------------------------------------------
+// 开票申请
+// ---------------------------------------------------------
+
+fn trimmed(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// (GET /api/v1/finance/orders/:id/invoices)
+pub async fn get_order_invoices_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Vec<Invoice>>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::Order(order_id)).await?;
+    let invoices = sqlx::query_as::<_, Invoice>(&format!("{} WHERE i.order_id = $1 ORDER BY i.created_at, i.kind", INVOICE_SELECT))
+        .bind(order_id)
+        .fetch_all(&state.db_pool)
+        .await
+        .map_err(AppError::db("Failed to fetch invoices"))?;
+    Ok(Json(invoices))
+}
+
+// (POST /api/v1/finance/orders/:id/invoices) 申请并开具蓝字发票
+pub async fn create_order_invoice_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(order_id): Path<Uuid>,
+    Json(payload): Json<CreateInvoicePayload>,
+) -> Result<(StatusCode, Json<Invoice>), AppError> {
+    let buyer = InvoiceBuyer {
+        name: payload.buyer_name.trim().to_string(),
+        tax_id: trimmed(payload.buyer_tax_id),
+        address_phone: trimmed(payload.buyer_address_phone),
+        bank_account: trimmed(payload.buyer_bank_account),
+        email: trimmed(payload.buyer_email),
+    };
+    if buyer.name.is_empty() {
+        return Err(AppError::BadRequest("invoice.buyer_required"));
+    }
+    let invoice_type = payload.invoice_type.unwrap_or_else(|| "normal".to_string());
+    if !matches!(invoice_type.as_str(), "normal" | "special") {
+        return Err(AppError::BadRequest("invoice.type_invalid"));
+    }
+    if invoice_type == "special" && buyer.tax_id.is_none() {
+        return Err(AppError::BadRequest("invoice.tax_id_required"));
+    }
+    let tax_rate_bp = match payload.tax_rate {
+        Some(rate) if (0.0..=17.0).contains(&rate) => (rate * 100.0).round() as i32,
+        Some(_) => return Err(AppError::BadRequest("invoice.tax_rate_invalid")),
+        None => DEFAULT_TAX_RATE_BP,
+    };
+
+    let scope = TenantScope::from_claims(&claims);
+    let mut tx = state.db_pool.begin().await.map_err(AppError::db("Failed to begin transaction"))?;
+    scope.ensure_owned(&mut *tx, Owned::Order(order_id)).await?;
+
+    // 锁订单, 同一订单的开票申请串行校验
+    let (hq_id, base_id, total_amount_cents, status): (Uuid, Uuid, i32, Option<String>) = sqlx::query_as(
+        "SELECT hq_id, base_id, total_amount_cents, status::TEXT FROM orders WHERE id = $1 FOR UPDATE",
+    )
+    .bind(order_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if status.as_deref() == Some("cancelled") {
+        return Err(AppError::Conflict("invoice.order_cancelled"));
+    }
+    let (refunded_cents, requested_cents): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT
+            (SELECT COALESCE(SUM(amount_cents), 0)::bigint FROM finance_payment_records
+             WHERE order_id = $1 AND transaction_type = 'REFUND' AND status = 'VERIFIED'),
+            (SELECT COALESCE(SUM(amount_cents), 0)::bigint FROM invoices WHERE order_id = $1 AND status <> 'cancelled')
+        "#,
+    )
+    .bind(order_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let invoiceable_cents = total_amount_cents as i64 - refunded_cents - requested_cents;
+
+    // 可开数量 = 数量 - 已退 - 已申请开票 (红字冲回的数量为负)
+    let order_items: Vec<(Uuid, String, i32, i32)> = sqlx::query_as(
+        r#"
+        SELECT oi.id, oi.name, oi.unit_price_cents,
+               oi.quantity - oi.refunded_quantity - COALESCE((
+                   SELECT SUM(l.quantity) FROM invoice_lines l JOIN invoices i ON i.id = l.invoice_id
+                   WHERE l.order_item_id = oi.id AND i.status <> 'cancelled'
+               ), 0)::int AS remaining
+        FROM order_items oi WHERE oi.order_id = $1
+        ORDER BY oi.id
+        "#,
+    )
+    .bind(order_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut lines = Vec::new();
+    if order_items.is_empty() {
+        let amount_cents = match payload.amount {
+            Some(amount) => (amount * 100.0).round() as i64,
+            None => invoiceable_cents,
+        };
+        if amount_cents > 0 {
+            lines.push(LineDraft {
+                order_item_id: None,
+                name: trimmed(payload.item_name).unwrap_or_else(|| DEFAULT_LINE_NAME.to_string()),
+                quantity: None,
+                unit_price_cents: None,
+                amount_cents,
+                tax_rate_bp,
+            });
+        }
+    } else {
+        let selected: Vec<(Uuid, i32)> = match payload.items {
+            Some(items) => items.into_iter().map(|i| (i.order_item_id, i.quantity)).collect(),
+            None => order_items.iter().filter(|i| i.3 > 0).map(|i| (i.0, i.3)).collect(),
+        };
+        for (order_item_id, quantity) in selected {
+            let (_, name, unit_price_cents, remaining) = order_items
+                .iter()
+                .find(|i| i.0 == order_item_id)
+                .filter(|_| quantity > 0)
+                .ok_or(AppError::BadRequest("invoice.invalid_item"))?;
+            if quantity > *remaining {
+                return Err(AppError::Conflict("invoice.quantity_exceeded"));
+            }
+            lines.push(LineDraft {
+                order_item_id: Some(order_item_id),
+                name: name.clone(),
+                quantity: Some(quantity),
+                unit_price_cents: Some(*unit_price_cents),
+                amount_cents: *unit_price_cents as i64 * quantity as i64,
+                tax_rate_bp,
+            });
+        }
+    }
+
+    let amount_cents: i64 = lines.iter().map(|l| l.amount_cents).sum();
+    if lines.is_empty() || amount_cents <= 0 {
+        return Err(AppError::Conflict("invoice.nothing_to_invoice"));
+    }
+    if amount_cents > invoiceable_cents {
+        return Err(AppError::Conflict("invoice.exceeds_order"));
+    }
+
+    let header = InvoiceHeader {
+        hq_id,
+        base_id,
+        order_id,
+        kind: "blue",
+        invoice_type,
+        original_invoice_id: None,
+        refund_request_id: None,
+        buyer,
+        remark: trimmed(payload.remark),
+        requested_by: Uuid::parse_str(&claims.sub).ok(),
+    };
+    let invoice_id = insert_invoice(&mut tx, &header, &lines).await?;
+    tx.commit().await.map_err(AppError::db("Transaction commit failed"))?;
+
+    let invoice = issue_invoice(&state, invoice_id).await?;
+    Ok((StatusCode::CREATED, Json(invoice)))
+}
+
+// (POST /api/v1/finance/invoices/:id/issue) 重试开具失败 / 待开具的发票
+pub async fn issue_invoice_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(invoice_id): Path<Uuid>,
+) -> Result<Json<Invoice>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::Invoice(invoice_id)).await?;
+    Ok(Json(issue_invoice(&state, invoice_id).await?))
+}
+
+// (POST /api/v1/finance/invoices/:id/cancel) 撤销未开具的申请, 释放可开票金额
+pub async fn cancel_invoice_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(invoice_id): Path<Uuid>,
+) -> Result<Json<Invoice>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::Invoice(invoice_id)).await?;
+    let order_id: Uuid = sqlx::query_scalar(
+        "UPDATE invoices SET status = 'cancelled', updated_at = NOW() WHERE id = $1 AND status IN ('pending', 'failed') RETURNING order_id",
+    )
+    .bind(invoice_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to cancel invoice"))?
+    .ok_or(AppError::Conflict("invoice.not_pending"))?;
+    sync_order_invoice_status(&state.db_pool, order_id).await?;
+    Ok(Json(fetch_invoice(&state.db_pool, invoice_id).await?))
+}
+
+// ---------------------------------------------------------
+// 红字发票
+// ---------------------------------------------------------
+
+#[derive(FromRow)]
+struct BlueInvoice {
+    id: Uuid,
+    hq_id: Uuid,
+    base_id: Uuid,
+    order_id: Uuid,
+    invoice_type: String,
+    buyer_name: String,
+    buyer_tax_id: Option<String>,
+    buyer_address_phone: Option<String>,
+    buyer_bank_account: Option<String>,
+    buyer_email: Option<String>,
+    amount_cents: i32,
+    reversed_cents: i32, // 已申请冲红金额 (正数, 不含已撤销)
+}
+
+// 已开具的蓝字发票及已冲红金额, 加行锁避免并发超额冲红
+const BLUE_SELECT: &str = r#"
+    SELECT i.id, i.hq_id, i.base_id, i.order_id, i.invoice_type, i.buyer_name, i.buyer_tax_id,
+           i.buyer_address_phone, i.buyer_bank_account, i.buyer_email, i.amount_cents,
+           (-COALESCE((SELECT SUM(r.amount_cents) FROM invoices r
+                       WHERE r.original_invoice_id = i.id AND r.status <> 'cancelled'), 0))::int AS reversed_cents
+    FROM invoices i
+"#;
+
+// 冲红 amount_cents (正数); 未冲过且整张冲红时按原明细逐行冲回, 否则按第一行项目冲一行金额
+async fn insert_red_invoice(
+    conn: &mut PgConnection,
+    blue: &BlueInvoice,
+    amount_cents: i64,
+    remark: String,
+    refund_request_id: Option<Uuid>,
+    requested_by: Option<Uuid>,
+) -> Result<Uuid, AppError> {
+    let blue_lines = sqlx::query_as::<_, LineDraft>(
+        r#"
+        SELECT order_item_id, name, quantity, unit_price_cents, amount_cents::bigint AS amount_cents, tax_rate_bp
+        FROM invoice_lines WHERE invoice_id = $1 ORDER BY line_no
+        "#,
+    )
+    .bind(blue.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let lines: Vec<LineDraft> = if blue.reversed_cents == 0 && amount_cents == blue.amount_cents as i64 {
+        blue_lines
+            .into_iter()
+            .map(|line| LineDraft { quantity: line.quantity.map(|q| -q), amount_cents: -line.amount_cents, ..line })
+            .collect()
+    } else {
+        let (name, tax_rate_bp) = blue_lines
+            .first()
+            .map(|l| (l.name.clone(), l.tax_rate_bp))
+            .unwrap_or_else(|| (DEFAULT_LINE_NAME.to_string(), DEFAULT_TAX_RATE_BP));
+        vec![LineDraft { order_item_id: None, name, quantity: None, unit_price_cents: None, amount_cents: -amount_cents, tax_rate_bp }]
+    };
+
+    let header = InvoiceHeader {
+        hq_id: blue.hq_id,
+        base_id: blue.base_id,
+        order_id: blue.order_id,
+        kind: "red",
+        invoice_type: blue.invoice_type.clone(),
+        original_invoice_id: Some(blue.id),
+        refund_request_id,
+        buyer: InvoiceBuyer {
+            name: blue.buyer_name.clone(),
+            tax_id: blue.buyer_tax_id.clone(),
+            address_phone: blue.buyer_address_phone.clone(),
+            bank_account: blue.buyer_bank_account.clone(),
+            email: blue.buyer_email.clone(),
+        },
+        remark: Some(remark),
+        requested_by,
+    };
+    insert_invoice(conn, &header, &lines).await
+}
+
+// (POST /api/v1/finance/invoices/:id/reverse) 蓝字发票剩余未冲红部分整体冲红
+pub async fn reverse_invoice_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(invoice_id): Path<Uuid>,
+    Json(payload): Json<ReverseInvoicePayload>,
+) -> Result<(StatusCode, Json<Invoice>), AppError> {
+    if payload.reason.trim().is_empty() {
+        return Err(AppError::BadRequest("invoice.reason_required"));
+    }
+    let mut tx = state.db_pool.begin().await.map_err(AppError::db("Failed to begin transaction"))?;
+    TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::Invoice(invoice_id)).await?;
+
+    let blue = sqlx::query_as::<_, BlueInvoice>(&format!(
+        "{} WHERE i.id = $1 AND i.kind = 'blue' AND i.status = 'issued' FOR UPDATE",
+        BLUE_SELECT
+    ))
+    .bind(invoice_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::Conflict("invoice.not_reversible"))?;
+    let remaining = blue.amount_cents as i64 - blue.reversed_cents as i64;
+    if remaining <= 0 {
+        return Err(AppError::Conflict("invoice.already_reversed"));
+    }
+
+    let red_id = insert_red_invoice(
+        &mut tx,
+        &blue,
+        remaining,
+        format!("冲红: {}", payload.reason.trim()),
+        None,
+        Uuid::parse_str(&claims.sub).ok(),
+    )
+    .await?;
+    tx.commit().await.map_err(AppError::db("Transaction commit failed"))?;
+
+    let invoice = issue_invoice(&state, red_id).await?;
+    Ok((StatusCode::CREATED, Json(invoice)))
+}
+
+// 退款审批通过时调用 (须在审批的事务内, 订单已加锁):
+// 按退款金额从最近开具的蓝字发票开始生成红字申请, 最多冲到已开票金额为止
+pub async fn create_refund_red_invoices(conn: &mut PgConnection, refund_id: Uuid, approved_by: Uuid) -> Result<(), AppError> {
+    let (order_id, amount_cents, reason): (Uuid, i32, String) =
+        sqlx::query_as("SELECT order_id, amount_cents, reason FROM refund_requests WHERE id = $1")
+            .bind(refund_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+    let blues = sqlx::query_as::<_, BlueInvoice>(&format!(
+        "{} WHERE i.order_id = $1 AND i.kind = 'blue' AND i.status = 'issued' ORDER BY i.issued_at DESC FOR UPDATE",
+        BLUE_SELECT
+    ))
+    .bind(order_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut left = amount_cents as i64;
+    for blue in blues {
+        if left <= 0 {
+            break;
+        }
+        let remaining = blue.amount_cents as i64 - blue.reversed_cents as i64;
+        if remaining <= 0 {
+            continue;
+        }
+        let portion = left.min(remaining);
+        insert_red_invoice(conn, &blue, portion, format!("退款冲红: {}", reason), Some(refund_id), Some(approved_by)).await?;
+        left -= portion;
+    }
+    Ok(())
+}
+
+// 退款审批事务提交后调用: 开具该退款生成的红字发票; 失败只记录, 可在订单发票列表中重试
+pub async fn issue_refund_red_invoices(state: &AppState, refund_id: Uuid) {
+    let pending: Vec<Uuid> = match sqlx::query_scalar(
+        "SELECT id FROM invoices WHERE refund_request_id = $1 AND status = 'pending' ORDER BY created_at",
+    )
+    .bind(refund_id)
+    .fetch_all(&state.db_pool)
+    .await
+    {
+        Ok(ids) => ids,
+        Err(e) => {
+            tracing::error!("Failed to load red invoices for refund {}: {}", refund_id, e);
+            return;
+        }
+    };
+    for invoice_id in pending {
+        if let Err(e) = issue_invoice(state, invoice_id).await {
+            tracing::warn!("Red invoice {} for refund {} not issued: {:?}", invoice_id, refund_id, e);
+        }
+    }
+}
+
+// ---------------------------------------------------------
+// 版式文件下载
+// ---------------------------------------------------------
+
+async fn invoice_file(state: &AppState, scope: &TenantScope, invoice_id: Uuid) -> Result<(String, Vec<u8>, String), AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::Invoice(invoice_id)).await?;
+    let (invoice_no, pdf, xml): (Option<String>, Option<Vec<u8>>, Option<String>) =
+        sqlx::query_as("SELECT invoice_no, pdf, xml FROM invoices WHERE id = $1 AND status = 'issued'")
+            .bind(invoice_id)
+            .fetch_optional(&state.db_pool)
+            .await?
+            .ok_or(AppError::Conflict("invoice.not_issued"))?;
+    Ok((invoice_no.unwrap_or_default(), pdf.unwrap_or_default(), xml.unwrap_or_default()))
+}
+
+// (GET /api/v1/finance/invoices/:id/pdf)
+pub async fn download_invoice_pdf_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(invoice_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let (invoice_no, pdf, _) = invoice_file(&state, &scope, invoice_id).await?;
+    let headers = [
+        (header::CONTENT_TYPE, "application/pdf".to_string()),
+        (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.pdf\"", invoice_no)),
+    ];
+    Ok((headers, pdf))
+}
+
+// (GET /api/v1/finance/invoices/:id/xml)
+pub async fn download_invoice_xml_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(invoice_id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let (invoice_no, _, xml) = invoice_file(&state, &scope, invoice_id).await?;
+    let headers = [
+        (header::CONTENT_TYPE, "application/xml; charset=utf-8".to_string()),
+        (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.xml\"", invoice_no)),
+    ];
+    Ok((headers, xml))
+}
+
+// ---------------------------------------------------------
+// 开票与收款核对
+// ---------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct InvoiceReconciliationQuery {
+    pub base_id: Option<Uuid>,     // 总部账号可指定基地, 基地账号固定本基地
+    pub from: Option<NaiveDate>,   // 按下单日期
+    pub to: Option<NaiveDate>,
+    #[serde(default)]
+    pub mismatch_only: bool,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct InvoiceReconciliationRow {
+    pub order_id: Uuid,
+    pub order_no: String,
+    pub base_id: Uuid,
+    pub customer_name: Option<String>,
+    pub total_amount_cents: i32,
+    pub paid_amount_cents: i32,
+    pub invoiced_cents: i64, // 已开具净额 (蓝 - 红)
+    pub pending_cents: i64,  // 待开具 / 开具中 / 开具失败
+    #[sqlx(default)]
+    pub difference_cents: i64, // 已开票 - 已收款
+    #[sqlx(default)]
+    pub status: String,        // matched / under_invoiced / over_invoiced
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct InvoiceReconciliationTotals {
+    pub paid_amount_cents: i64,
+    pub invoiced_cents: i64,
+    pub pending_cents: i64,
+    pub difference_cents: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceReconciliation {
+    pub rows: Vec<InvoiceReconciliationRow>,
+    pub totals: InvoiceReconciliationTotals,
+}
+
+// (GET /api/v1/finance/invoices/reconciliation?base_id=&from=&to=&mismatch_only=)
+// 已取消的订单不参与; 只列有收款或有发票的订单
+pub async fn get_invoice_reconciliation_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(query): Query<InvoiceReconciliationQuery>,
+) -> Result<Json<InvoiceReconciliation>, AppError> {
+    let base_id = scope.base_id.or(query.base_id);
+    let rows = sqlx::query_as::<_, InvoiceReconciliationRow>(
+        r#"
+        SELECT o.id AS order_id, o.order_no, o.base_id, COALESCE(c.name, o.contact_name) AS customer_name,
+               o.total_amount_cents, o.paid_amount_cents,
+               COALESCE(SUM(i.amount_cents) FILTER (WHERE i.status = 'issued'), 0)::bigint AS invoiced_cents,
+               COALESCE(SUM(i.amount_cents) FILTER (WHERE i.status IN ('pending', 'issuing', 'failed')), 0)::bigint AS pending_cents
+        FROM orders o
+        LEFT JOIN customers c ON c.id = o.customer_id
+        LEFT JOIN invoices i ON i.order_id = o.id
+        WHERE o.hq_id = $1 AND ($2::uuid IS NULL OR o.base_id = $2)
+          AND ($3::date IS NULL OR o.created_at::date >= $3)
+          AND ($4::date IS NULL OR o.created_at::date <= $4)
+          AND o.status IS DISTINCT FROM 'cancelled'
+        GROUP BY o.id, c.name
+        HAVING o.paid_amount_cents <> 0 OR COUNT(i.id) > 0
+        ORDER BY o.created_at DESC
+        "#,
+    )
+    .bind(scope.hq_id)
+    .bind(base_id)
+    .bind(query.from)
+    .bind(query.to)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to reconcile invoices"))?;
+
+    let mut totals = InvoiceReconciliationTotals::default();
+    let rows: Vec<InvoiceReconciliationRow> = rows
+        .into_iter()
+        .map(|mut row| {
+            row.difference_cents = row.invoiced_cents - row.paid_amount_cents as i64;
+            row.status = match row.difference_cents {
+                0 => "matched",
+                d if d < 0 => "under_invoiced",
+                _ => "over_invoiced",
+            }
+            .to_string();
+            row
+        })
+        .filter(|row| !query.mismatch_only || row.difference_cents != 0)
+        .inspect(|row| {
+            totals.paid_amount_cents += row.paid_amount_cents as i64;
+            totals.invoiced_cents += row.invoiced_cents;
+            totals.pending_cents += row.pending_cents;
+            totals.difference_cents += row.difference_cents;
+        })
+        .collect();
+
+    Ok(Json(InvoiceReconciliation { rows, totals }))
+}
@@ -3,19 +3,22 @@
  * 职责: 销售线索管理 - Lead Management for Base Principals
  */
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Path, State},
     http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use super::AppState;
 use crate::models::Claims;
+use crate::tenant::{Owned, TenantScope};
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
 
 // ==========================================
 // 1. Data Models
 // ==========================================
 
-#[derive(Serialize)]
+#[derive(Serialize, sqlx::FromRow)]
 pub struct LeadItem {
     pub id: uuid::Uuid,
     pub contact_name: String,
@@ -94,86 +97,56 @@ pub struct AddFollowUpPayload {
     pub next_follow_up_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Deserialize)]
-pub struct LeadQuery {
-    pub status: Option<String>,
-    pub assigned_to: Option<uuid::Uuid>,
-    pub page: Option<i32>,
-    pub limit: Option<i32>,
-}
-
 // ==========================================
 // 2. API Handlers
 // ==========================================
 
 // GET /api/v1/base/leads - 获取线索列表
+// 分页; 默认待跟进的排在前面; 筛选: status / source / assigned_to / created_from / created_to
+// q 搜联系人、手机号和孩子姓名
+const LEAD_LIST: ListSpec = ListSpec {
+    sorts: &[
+        ("next_follow_up_at", "l.next_follow_up_at"),
+        ("created_at", "l.created_at"),
+        ("last_contact_at", "l.last_contact_at"),
+        ("quality_score", "l.quality_score"),
+    ],
+    default_sort: "next_follow_up_at,-created_at",
+    tiebreak: "l.id",
+    filters: &[
+        Filter::text("status", "l.status"),
+        Filter::text("source", "l.source"),
+        Filter::uuid("assigned_to", "l.assigned_to"),
+        Filter::date_from("created_from", "l.created_at"),
+        Filter::date_to("created_to", "l.created_at"),
+    ],
+    search: &["l.contact_name", "l.phone_number", "l.child_name"],
+};
+
 pub async fn get_leads_handler(
     State(state): State<AppState>,
     claims: Claims,
-    Query(params): Query<LeadQuery>,
-) -> Result<Json<Vec<LeadItem>>, StatusCode> {
-    // Check if user is base principal/admin
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
-    
-    let page = params.page.unwrap_or(1);
-    let limit = params.limit.unwrap_or(20);
-    let offset = (page - 1) * limit;
-
-    let leads = sqlx::query_as::<_, (
-        uuid::Uuid, String, String, Option<String>, Option<i32>, 
-        Option<String>, String, Option<i32>, Option<String>,
-        Option<chrono::DateTime<chrono::Utc>>, Option<chrono::DateTime<chrono::Utc>>,
-        chrono::DateTime<chrono::Utc>
-    )>(
-        r#"
-        SELECT 
-            l.id, l.contact_name, l.phone_number, l.child_name, l.child_age,
-            l.source, l.status, l.quality_score, u.full_name as assigned_to_name,
-            l.last_contact_at, l.next_follow_up_at, l.created_at
-        FROM leads l
-        LEFT JOIN users u ON l.assigned_to = u.id
-        WHERE l.base_id = $1
-        AND ($2::text IS NULL OR l.status = $2::text)
-        AND ($3::uuid IS NULL OR l.assigned_to = $3::uuid)
-        ORDER BY 
-            CASE WHEN l.next_follow_up_at IS NOT NULL THEN 0 ELSE 1 END,
-            l.next_follow_up_at ASC,
-            l.created_at DESC
-        LIMIT $4 OFFSET $5
-        "#
-    )
-    .bind(base_id)
-    .bind(params.status)
-    .bind(params.assigned_to)
-    .bind(limit as i64)
-    .bind(offset as i64)
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch leads: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    list: ListQuery,
+) -> Result<Json<Page<LeadItem>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
-    let result = leads.into_iter().map(|(
-        id, contact_name, phone_number, child_name, child_age,
-        source, status, quality_score, assigned_to_name,
-        last_contact_at, next_follow_up_at, created_at
-    )| LeadItem {
-        id,
-        contact_name,
-        phone_number,
-        child_name,
-        child_age,
-        source,
-        status,
-        quality_score,
-        assigned_to_name,
-        last_contact_at,
-        next_follow_up_at,
-        created_at,
-    }).collect();
+    let page = list
+        .fetch_page(&state.db_pool, &LEAD_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    l.id, l.contact_name, l.phone_number, l.child_name, l.child_age,
+                    l.source, l.status, l.quality_score, u.full_name as assigned_to_name,
+                    l.last_contact_at, l.next_follow_up_at, l.created_at
+                FROM leads l
+                LEFT JOIN users u ON l.assigned_to = u.id
+                WHERE l.base_id = "#,
+            );
+            qb.push_bind(base_id);
+        })
+        .await?;
 
-    Ok(Json(result))
+    Ok(Json(page))
 }
 
 // POST /api/v1/base/leads - 创建线索
@@ -181,8 +154,8 @@ pub async fn create_lead_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateLeadPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
     let hq_id = claims.hq_id;
     let user_id = uuid::Uuid::parse_str(&claims.sub).unwrap_or_default();
 
@@ -210,10 +183,7 @@ pub async fn create_lead_handler(
     .bind(user_id)
     .fetch_one(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to create lead: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to create lead"))?;
 
     Ok(Json(serde_json::json!({
         "id": lead_id,
@@ -226,8 +196,8 @@ pub async fn get_lead_detail_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(lead_id): Path<uuid::Uuid>,
-) -> Result<Json<LeadDetail>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<LeadDetail>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     // Get basic lead details (first query - 12 fields max)
     let lead_row = sqlx::query_as::<_, (
@@ -249,11 +219,8 @@ pub async fn get_lead_detail_handler(
     .bind(base_id)
     .fetch_optional(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch lead detail: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .map_err(AppError::db("Failed to fetch lead detail"))?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
 
     // Get additional fields (second query)
     let lead_extra = sqlx::query_as::<_, (
@@ -272,8 +239,7 @@ pub async fn get_lead_detail_handler(
     )
     .bind(lead_id)
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     // Get follow-up records
     let follow_ups = sqlx::query_as::<_, (
@@ -331,12 +297,18 @@ pub async fn get_lead_detail_handler(
 pub async fn update_lead_handler(
     State(state): State<AppState>,
     claims: Claims,
+    scope: TenantScope,
     Path(lead_id): Path<uuid::Uuid>,
     Json(payload): Json<UpdateLeadPayload>,
-) -> Result<StatusCode, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<StatusCode, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
-    sqlx::query(
+    // 只能分配给本基地员工
+    if let Some(assignee) = payload.assigned_to {
+        scope.ensure_owned(&state.db_pool, Owned::User(assignee)).await?;
+    }
+
+    let result = sqlx::query(
         r#"
         UPDATE leads SET
             status = COALESCE($1, status),
@@ -358,11 +330,11 @@ pub async fn update_lead_handler(
     .bind(base_id)
     .execute(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to update lead: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to update lead"))?;
 
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("resource.not_found"));
+    }
     Ok(StatusCode::OK)
 }
 
@@ -372,8 +344,8 @@ pub async fn add_follow_up_handler(
     claims: Claims,
     Path(lead_id): Path<uuid::Uuid>,
     Json(payload): Json<AddFollowUpPayload>,
-) -> Result<StatusCode, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<StatusCode, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
     let user_id = uuid::Uuid::parse_str(&claims.sub).unwrap_or_default();
 
     // Verify lead belongs to this base
@@ -383,11 +355,10 @@ pub async fn add_follow_up_handler(
     .bind(lead_id)
     .bind(base_id)
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     if !lead_exists {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(AppError::NotFound("resource.not_found"));
     }
 
     // Insert follow-up record
@@ -407,10 +378,7 @@ pub async fn add_follow_up_handler(
     .bind(user_id)
     .execute(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to add follow-up: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to add follow-up"))?;
 
     // Update lead's last_contact_at and next_follow_up_at
     sqlx::query(
@@ -424,8 +392,7 @@ pub async fn add_follow_up_handler(
     .bind(payload.next_follow_up_at)
     .bind(lead_id)
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     Ok(StatusCode::CREATED)
 }
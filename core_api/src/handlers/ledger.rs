@@ -0,0 +1,597 @@
+/*
+ * src/handlers/ledger.rs
+ * 职责: 总账 (复式记账)
+ * 1. 会计科目: 每个总部一套; 系统科目 (system_key) 由迁移 / 新建总部时带出, 业务流程按 system_key 记账, 总部可另建科目
+ * 2. 记账: post_journal_entry 写一张凭证 (至少两行, 借贷相等, 科目属于本总部且启用, 所属月份未结账)
+ *    post_transfer 是业务流程用的 "借 A 贷 B" 两行凭证, 金额为 0 时不记账
+ * 3. 结账: 按月锁定整个总部的账, 须按月份顺序结账 / 反结账; 已结账月份不能再记账
+ * 报表 (试算平衡 / 利润表 / 资产负债表) 见 ledger_report.rs
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use super::AppState;
+use crate::error::AppError;
+use crate::models::Claims;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+use crate::tenant::{Owned, TenantScope};
+use crate::timezone::business_date;
+
+// --- 系统科目 (ledger_account_defaults.system_key) ---
+pub const ACCOUNT_CASH: &str = "cash";
+pub const ACCOUNT_BANK: &str = "bank";
+pub const ACCOUNT_CONTRACT_LIABILITY: &str = "contract_liability";
+pub const ACCOUNT_REVENUE: &str = "revenue";
+pub const ACCOUNT_SELLING_EXPENSE: &str = "selling_expense";
+pub const ACCOUNT_ADMIN_EXPENSE: &str = "admin_expense";
+
+// --- 凭证来源 (journal_entries.source_type), 与历史流水的 category 一致 ---
+pub const SOURCE_MEMBERSHIP_SALE: &str = "membership_sale";
+pub const SOURCE_MEMBERSHIP_REFUND: &str = "membership_refund";
+pub const SOURCE_COURSE_REVENUE: &str = "course_revenue";
+pub const SOURCE_MEMBERSHIP_REVENUE: &str = "membership_revenue";
+pub const SOURCE_ORDER_PAYMENT: &str = "order_payment";
+pub const SOURCE_ORDER_REVENUE: &str = "order_revenue";
+pub const SOURCE_ORDER_REFUND: &str = "order_refund";
+pub const SOURCE_EXPENSE: &str = "expense";
+pub const SOURCE_MANUAL: &str = "manual";
+
+pub const ACCOUNT_TYPES: [&str; 5] = ["asset", "liability", "equity", "revenue", "expense"];
+
+// 收款渠道 -> 资金科目: 现金入库存现金, 其余 (转账 / 微信 / 支付宝) 入银行存款
+pub fn funds_account(channel: &str) -> &'static str {
+    if channel.eq_ignore_ascii_case("cash") {
+        ACCOUNT_CASH
+    } else {
+        ACCOUNT_BANK
+    }
+}
+
+// 报销类别 -> 费用科目: 市场推广计销售费用, 其余 (房租 / 工资 / 水电 ...) 计管理费用
+pub fn expense_account(category: &str) -> &'static str {
+    match category {
+        "marketing" => ACCOUNT_SELLING_EXPENSE,
+        _ => ACCOUNT_ADMIN_EXPENSE,
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct LedgerAccount {
+    pub id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub account_type: String,
+    pub system_key: Option<String>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JournalLineInput {
+    pub account_id: Uuid,
+    #[serde(default)]
+    pub debit_cents: i64,
+    #[serde(default)]
+    pub credit_cents: i64,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct NewJournalEntry {
+    pub hq_id: Uuid,
+    pub base_id: Option<Uuid>,
+    pub entry_date: Option<NaiveDate>, // 为空记当天
+    pub source_type: &'static str,
+    pub source_id: Option<Uuid>,
+    pub description: String,
+    pub created_by: Option<Uuid>,
+    pub lines: Vec<JournalLineInput>,
+}
+
+// 业务流程的两行凭证: 借 debit 贷 credit (system_key)
+#[derive(Debug)]
+pub struct LedgerTransfer {
+    pub hq_id: Uuid,
+    pub base_id: Option<Uuid>,
+    pub entry_date: Option<NaiveDate>,
+    pub source_type: &'static str,
+    pub source_id: Option<Uuid>,
+    pub description: String,
+    pub created_by: Option<Uuid>,
+    pub debit: &'static str,
+    pub credit: &'static str,
+    pub amount_cents: i64,
+}
+
+// ---------------------------------------------------------
+// 记账
+// ---------------------------------------------------------
+
+fn validate_lines(lines: &[JournalLineInput]) -> Result<(), AppError> {
+    let one_side = |l: &JournalLineInput| l.debit_cents >= 0 && l.credit_cents >= 0 && (l.debit_cents == 0) != (l.credit_cents == 0);
+    if lines.len() < 2 || !lines.iter().all(one_side) {
+        return Err(AppError::BadRequest("ledger.line_invalid"));
+    }
+    let debit: i64 = lines.iter().map(|l| l.debit_cents).sum();
+    let credit: i64 = lines.iter().map(|l| l.credit_cents).sum();
+    if debit != credit {
+        return Err(AppError::Rejected("ledger.unbalanced"));
+    }
+    Ok(())
+}
+
+// 结账持排他锁, 记账持共享锁: 结账与记账互斥, 记账之间互不阻塞
+async fn lock_ledger(conn: &mut PgConnection, hq_id: Uuid, exclusive: bool) -> Result<(), AppError> {
+    let sql = if exclusive {
+        "SELECT pg_advisory_xact_lock(hashtext('ledger:' || $1::text))"
+    } else {
+        "SELECT pg_advisory_xact_lock_shared(hashtext('ledger:' || $1::text))"
+    };
+    sqlx::query(sql).bind(hq_id).execute(&mut *conn).await.map_err(AppError::db("Failed to lock ledger"))?;
+    Ok(())
+}
+
+pub async fn system_account(conn: &mut PgConnection, hq_id: Uuid, key: &str) -> Result<Uuid, AppError> {
+    sqlx::query_scalar("SELECT id FROM ledger_accounts WHERE hq_id = $1 AND system_key = $2 AND is_active")
+        .bind(hq_id)
+        .bind(key)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(AppError::db("Failed to load ledger account"))?
+        .ok_or(AppError::Rejected("ledger.account_invalid"))
+}
+
+// 写一张凭证; 须在调用方的事务内, 与业务单据一起提交
+pub async fn post_journal_entry(conn: &mut PgConnection, entry: NewJournalEntry) -> Result<Uuid, AppError> {
+    validate_lines(&entry.lines)?;
+    lock_ledger(conn, entry.hq_id, false).await?;
+
+    // 未指定日期时记在业务时区的今天, 不取数据库会话时区的 CURRENT_DATE
+    let entry_date = entry.entry_date.unwrap_or_else(|| business_date(Utc::now()));
+    if period_closed(conn, entry.hq_id, entry_date).await? {
+        return Err(AppError::Conflict("ledger.period_closed"));
+    }
+
+    let mut account_ids: Vec<Uuid> = entry.lines.iter().map(|l| l.account_id).collect();
+    account_ids.sort();
+    account_ids.dedup();
+    let valid: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ledger_accounts WHERE id = ANY($1) AND hq_id = $2 AND is_active")
+        .bind(&account_ids)
+        .bind(entry.hq_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(AppError::db("Failed to check ledger accounts"))?;
+    if valid != account_ids.len() as i64 {
+        return Err(AppError::Rejected("ledger.account_invalid"));
+    }
+
+    let entry_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO journal_entries (hq_id, base_id, entry_date, source_type, source_id, description, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+        "#,
+    )
+    .bind(entry.hq_id)
+    .bind(entry.base_id)
+    .bind(entry_date)
+    .bind(entry.source_type)
+    .bind(entry.source_id)
+    .bind(&entry.description)
+    .bind(entry.created_by)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to insert journal entry"))?;
+
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("INSERT INTO journal_lines (entry_id, account_id, debit_cents, credit_cents, memo) ");
+    qb.push_values(entry.lines, |mut row, line| {
+        row.push_bind(entry_id)
+            .push_bind(line.account_id)
+            .push_bind(line.debit_cents)
+            .push_bind(line.credit_cents)
+            .push_bind(line.memo);
+    });
+    qb.build().execute(&mut *conn).await.map_err(AppError::db("Failed to insert journal lines"))?;
+
+    Ok(entry_id)
+}
+
+pub async fn post_transfer(conn: &mut PgConnection, t: LedgerTransfer) -> Result<Option<Uuid>, AppError> {
+    if t.amount_cents <= 0 {
+        return Ok(None);
+    }
+    let debit = system_account(conn, t.hq_id, t.debit).await?;
+    let credit = system_account(conn, t.hq_id, t.credit).await?;
+    let line = |account_id, debit_cents, credit_cents| JournalLineInput { account_id, debit_cents, credit_cents, memo: None };
+
+    post_journal_entry(
+        conn,
+        NewJournalEntry {
+            hq_id: t.hq_id,
+            base_id: t.base_id,
+            entry_date: t.entry_date,
+            source_type: t.source_type,
+            source_id: t.source_id,
+            description: t.description,
+            created_by: t.created_by,
+            lines: vec![line(debit, t.amount_cents, 0), line(credit, 0, t.amount_cents)],
+        },
+    )
+    .await
+    .map(Some)
+}
+
+async fn period_closed(conn: &mut PgConnection, hq_id: Uuid, date: NaiveDate) -> Result<bool, AppError> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM accounting_periods WHERE hq_id = $1 AND period_start = date_trunc('month', $2::date)::date)",
+    )
+    .bind(hq_id)
+    .bind(date)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to check accounting period"))
+}
+
+// ---------------------------------------------------------
+// 会计科目
+// ---------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLedgerAccountPayload {
+    pub code: String,
+    pub name: String,
+    pub account_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateLedgerAccountPayload {
+    pub code: Option<String>,
+    pub name: Option<String>,
+    pub is_active: Option<bool>,
+}
+
+// (GET /api/v1/ledger/accounts) 本总部科目表
+pub async fn get_ledger_accounts_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<LedgerAccount>>, AppError> {
+    let accounts = sqlx::query_as::<_, LedgerAccount>(
+        "SELECT id, code, name, account_type, system_key, is_active, created_at FROM ledger_accounts WHERE hq_id = $1 ORDER BY code",
+    )
+    .bind(claims.hq_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to fetch ledger accounts"))?;
+    Ok(Json(accounts))
+}
+
+// (POST /api/v1/hq/ledger/accounts) 新增自建科目
+pub async fn create_ledger_account_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<CreateLedgerAccountPayload>,
+) -> Result<Json<LedgerAccount>, AppError> {
+    let (code, name) = (payload.code.trim(), payload.name.trim());
+    if code.is_empty() || name.is_empty() {
+        return Err(AppError::BadRequest("request.missing_field"));
+    }
+    if !ACCOUNT_TYPES.contains(&payload.account_type.as_str()) {
+        return Err(AppError::BadRequest("ledger.account_type_invalid"));
+    }
+
+    let account = sqlx::query_as::<_, LedgerAccount>(
+        r#"
+        INSERT INTO ledger_accounts (hq_id, code, name, account_type)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, code, name, account_type, system_key, is_active, created_at
+        "#,
+    )
+    .bind(claims.hq_id)
+    .bind(code)
+    .bind(name)
+    .bind(&payload.account_type)
+    .fetch_one(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to create ledger account"))?;
+    Ok(Json(account))
+}
+
+// (PUT /api/v1/hq/ledger/accounts/:id) 改编号 / 名称 / 停用; 系统科目不能停用
+pub async fn update_ledger_account_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(account_id): Path<Uuid>,
+    Json(payload): Json<UpdateLedgerAccountPayload>,
+) -> Result<Json<LedgerAccount>, AppError> {
+    let code = payload.code.as_deref().map(str::trim);
+    let name = payload.name.as_deref().map(str::trim);
+    if code == Some("") || name == Some("") {
+        return Err(AppError::BadRequest("request.missing_field"));
+    }
+
+    let mut tx = state.db_pool.begin().await?;
+    let system_key: Option<String> = sqlx::query_scalar("SELECT system_key FROM ledger_accounts WHERE id = $1 AND hq_id = $2 FOR UPDATE")
+        .bind(account_id)
+        .bind(claims.hq_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to load ledger account"))?
+        .ok_or(AppError::NotFound("resource.not_found"))?;
+    if system_key.is_some() && payload.is_active == Some(false) {
+        return Err(AppError::Conflict("ledger.system_account"));
+    }
+
+    let account = sqlx::query_as::<_, LedgerAccount>(
+        r#"
+        UPDATE ledger_accounts
+        SET code = COALESCE($2, code), name = COALESCE($3, name), is_active = COALESCE($4, is_active)
+        WHERE id = $1
+        RETURNING id, code, name, account_type, system_key, is_active, created_at
+        "#,
+    )
+    .bind(account_id)
+    .bind(code)
+    .bind(name)
+    .bind(payload.is_active)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to update ledger account"))?;
+    tx.commit().await?;
+    Ok(Json(account))
+}
+
+// ---------------------------------------------------------
+// 凭证
+// ---------------------------------------------------------
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct JournalEntryItem {
+    pub id: Uuid,
+    pub base_id: Option<Uuid>,
+    pub base_name: Option<String>,
+    pub entry_date: NaiveDate,
+    pub source_type: String,
+    pub source_id: Option<Uuid>,
+    pub description: Option<String>,
+    pub created_by_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub amount_cents: i64,         // 借方合计 (= 贷方合计)
+    pub lines: serde_json::Value,  // [{ account_id, code, name, debit_cents, credit_cents, memo }]
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateJournalEntryPayload {
+    pub entry_date: Option<NaiveDate>,
+    pub base_id: Option<Uuid>, // 总部账号可指定基地, 为空记总部; 基地账号固定为本基地
+    pub description: String,
+    pub lines: Vec<JournalLineInput>,
+}
+
+// (GET /api/v1/ledger/entries) 凭证列表 (序时账)
+// 分页; 筛选: base_id / source_type / source_id / date_from / date_to; q 搜摘要
+const JOURNAL_ENTRY_LIST: ListSpec = ListSpec {
+    sorts: &[("entry_date", "e.entry_date"), ("created_at", "e.created_at")],
+    default_sort: "-entry_date,-created_at",
+    tiebreak: "e.id",
+    filters: &[
+        Filter::uuid("base_id", "e.base_id"),
+        Filter::text("source_type", "e.source_type"),
+        Filter::uuid("source_id", "e.source_id"),
+        Filter::date_from("date_from", "e.entry_date"),
+        Filter::date_to("date_to", "e.entry_date"),
+    ],
+    search: &["e.description"],
+};
+
+pub async fn get_journal_entries_handler(
+    State(state): State<AppState>,
+    scope: TenantScope, // 基地账号只看本基地凭证, 总部看全部 (含总部级凭证)
+    list: ListQuery,
+) -> Result<Json<Page<JournalEntryItem>>, AppError> {
+    let page = list
+        .fetch_page(&state.db_pool, &JOURNAL_ENTRY_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT e.id, e.base_id, b.name AS base_name, e.entry_date, e.source_type, e.source_id, e.description,
+                       u.full_name AS created_by_name, e.created_at,
+                       (SELECT COALESCE(SUM(l.debit_cents), 0)::bigint FROM journal_lines l WHERE l.entry_id = e.id) AS amount_cents,
+                       (SELECT COALESCE(jsonb_agg(jsonb_build_object(
+                                   'account_id', a.id, 'code', a.code, 'name', a.name,
+                                   'debit_cents', l.debit_cents, 'credit_cents', l.credit_cents, 'memo', l.memo)
+                               ORDER BY l.credit_cents, a.code), '[]'::jsonb)
+                        FROM journal_lines l JOIN ledger_accounts a ON l.account_id = a.id
+                        WHERE l.entry_id = e.id) AS lines
+                FROM journal_entries e
+                LEFT JOIN bases b ON e.base_id = b.id
+                LEFT JOIN users u ON e.created_by = u.id
+                WHERE 1 = 1
+                "#,
+            );
+            scope.push_filter(qb, "e");
+        })
+        .await?;
+    Ok(Json(page))
+}
+
+// (POST /api/v1/ledger/entries) 手工凭证 (调账 / 期初余额 / 非业务流程的收支)
+pub async fn create_journal_entry_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<CreateJournalEntryPayload>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let description = payload.description.trim();
+    if description.is_empty() {
+        return Err(AppError::BadRequest("request.missing_field"));
+    }
+
+    let scope = TenantScope::from_claims(&claims);
+    let mut tx = state.db_pool.begin().await?;
+    let base_id = match scope.base_id {
+        Some(own) => Some(own),
+        None => {
+            if let Some(base_id) = payload.base_id {
+                scope.ensure_owned(&mut *tx, Owned::Base(base_id)).await?;
+            }
+            payload.base_id
+        }
+    };
+
+    let entry_id = post_journal_entry(
+        &mut tx,
+        NewJournalEntry {
+            hq_id: claims.hq_id,
+            base_id,
+            entry_date: payload.entry_date,
+            source_type: SOURCE_MANUAL,
+            source_id: None,
+            description: description.to_string(),
+            created_by: Uuid::parse_str(&claims.sub).ok(),
+            lines: payload.lines,
+        },
+    )
+    .await?;
+    tx.commit().await.map_err(AppError::db("Failed to commit journal entry"))?;
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "id": entry_id }))))
+}
+
+// ---------------------------------------------------------
+// 结账
+// ---------------------------------------------------------
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AccountingPeriodItem {
+    pub period_start: NaiveDate,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub closed_by_name: Option<String>,
+    pub entry_count: i64,
+}
+
+// 路径参数 "2026-09" -> 月初
+fn parse_period(period: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(&format!("{}-01", period), "%Y-%m-%d").map_err(|_| AppError::BadRequest("ledger.period_invalid"))
+}
+
+// (GET /api/v1/hq/ledger/periods) 有凭证或已结账的月份 (含当月), 倒序
+pub async fn get_accounting_periods_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<AccountingPeriodItem>>, AppError> {
+    let periods = sqlx::query_as::<_, AccountingPeriodItem>(
+        r#"
+        WITH months AS (
+            SELECT date_trunc('month', entry_date)::date AS period_start FROM journal_entries WHERE hq_id = $1
+            UNION SELECT period_start FROM accounting_periods WHERE hq_id = $1
+            UNION SELECT $2::date
+        )
+        SELECT m.period_start, p.closed_at, u.full_name AS closed_by_name,
+               (SELECT COUNT(*) FROM journal_entries e
+                WHERE e.hq_id = $1 AND e.entry_date >= m.period_start AND e.entry_date < m.period_start + INTERVAL '1 month') AS entry_count
+        FROM months m
+        LEFT JOIN accounting_periods p ON p.hq_id = $1 AND p.period_start = m.period_start
+        LEFT JOIN users u ON p.closed_by = u.id
+        ORDER BY m.period_start DESC
+        "#,
+    )
+    .bind(claims.hq_id)
+    .bind(business_date(Utc::now()).with_day(1))
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to fetch accounting periods"))?;
+    Ok(Json(periods))
+}
+
+// (POST /api/v1/hq/ledger/periods/:period/close) 结账: 月份已结束, 且之前有凭证的月份都已结账
+pub async fn close_accounting_period_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(period): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let period_start = parse_period(&period)?;
+    let next_start = period_start + Months::new(1);
+
+    let mut tx = state.db_pool.begin().await?;
+    lock_ledger(&mut tx, claims.hq_id, true).await?;
+
+    if next_start > business_date(Utc::now()) {
+        return Err(AppError::Conflict("ledger.period_not_ended"));
+    }
+    if period_closed(&mut tx, claims.hq_id, period_start).await? {
+        return Err(AppError::Conflict("ledger.period_closed"));
+    }
+
+    let prior_open: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM journal_entries e
+            WHERE e.hq_id = $1 AND e.entry_date < $2
+              AND NOT EXISTS (SELECT 1 FROM accounting_periods p
+                              WHERE p.hq_id = e.hq_id AND p.period_start = date_trunc('month', e.entry_date)::date)
+        )
+        "#,
+    )
+    .bind(claims.hq_id)
+    .bind(period_start)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to check prior periods"))?;
+    if prior_open {
+        return Err(AppError::Conflict("ledger.period_prior_open"));
+    }
+
+    sqlx::query("INSERT INTO accounting_periods (hq_id, period_start, closed_by) VALUES ($1, $2, $3)")
+        .bind(claims.hq_id)
+        .bind(period_start)
+        .bind(Uuid::parse_str(&claims.sub).ok())
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to close accounting period"))?;
+    tx.commit().await?;
+
+    tracing::info!("Accounting period {} closed for hq {} by {}", period, claims.hq_id, claims.sub);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// (POST /api/v1/hq/ledger/periods/:period/reopen) 反结账: 只能从最后一个已结账月份倒着反结
+pub async fn reopen_accounting_period_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(period): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let period_start = parse_period(&period)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    lock_ledger(&mut tx, claims.hq_id, true).await?;
+
+    if !period_closed(&mut tx, claims.hq_id, period_start).await? {
+        return Err(AppError::Conflict("ledger.period_open"));
+    }
+    let later_closed: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM accounting_periods WHERE hq_id = $1 AND period_start > $2)")
+        .bind(claims.hq_id)
+        .bind(period_start)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to check later periods"))?;
+    if later_closed {
+        return Err(AppError::Conflict("ledger.period_later_closed"));
+    }
+
+    sqlx::query("DELETE FROM accounting_periods WHERE hq_id = $1 AND period_start = $2")
+        .bind(claims.hq_id)
+        .bind(period_start)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to reopen accounting period"))?;
+    tx.commit().await?;
+
+    tracing::info!("Accounting period {} reopened for hq {} by {}", period, claims.hq_id, claims.sub);
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,305 @@
+/*
+ * src/handlers/ledger_report.rs
+ * 职责: 总账报表 (数据来自 journal_entries / journal_lines)
+ * 1. 试算平衡表: 各科目 期初余额 / 本期借贷发生额 / 期末余额, 借贷合计应相等
+ * 2. 利润表: 期间内收入类科目贷方净额、费用类科目借方净额, 净利润 = 收入 - 费用
+ * 3. 资产负债表: 截止日各科目余额; 损益不做结转, 累计净利润以 "未结转损益" 列在所有者权益下
+ * 基地账号只看本基地; 总部账号不传 base_id 为全总部汇总 (含总部级凭证), 传 base_id 看单个基地
+ */
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use super::AppState;
+use crate::error::AppError;
+use crate::tenant::TenantScope;
+use crate::timezone::business_date;
+
+#[derive(Debug, Deserialize)]
+pub struct LedgerReportQuery {
+    pub from: Option<NaiveDate>,  // 默认当月 1 日
+    pub to: Option<NaiveDate>,    // 默认今天
+    pub as_of: Option<NaiveDate>, // 资产负债表截止日, 默认今天
+    pub base_id: Option<Uuid>,
+}
+
+#[derive(Debug, FromRow)]
+struct AccountBalance {
+    account_id: Uuid,
+    code: String,
+    name: String,
+    account_type: String,
+    opening_debit: i64,
+    opening_credit: i64,
+    period_debit: i64,
+    period_credit: i64,
+}
+
+impl AccountBalance {
+    // 资产 / 费用类借方为正, 其余贷方为正
+    fn debit_normal(&self) -> bool {
+        matches!(self.account_type.as_str(), "asset" | "expense")
+    }
+
+    fn closing_net(&self) -> i64 {
+        (self.opening_debit + self.period_debit) - (self.opening_credit + self.period_credit)
+    }
+
+    // 按正常方向的期末余额
+    fn closing_balance(&self) -> i64 {
+        if self.debit_normal() { self.closing_net() } else { -self.closing_net() }
+    }
+
+    // 按正常方向的本期发生净额
+    fn period_amount(&self) -> i64 {
+        let net = self.period_debit - self.period_credit;
+        if self.debit_normal() { net } else { -net }
+    }
+}
+
+// 基地账号固定本基地, 总部账号按参数
+fn report_base(scope: &TenantScope, query: &LedgerReportQuery) -> Option<Uuid> {
+    scope.base_id.or(query.base_id)
+}
+
+fn report_range(query: &LedgerReportQuery) -> Result<(NaiveDate, NaiveDate), AppError> {
+    let today = business_date(Utc::now());
+    let to = query.to.unwrap_or(today);
+    let from = query.from.unwrap_or_else(|| to.with_day(1).unwrap_or(to));
+    if from > to {
+        return Err(AppError::BadRequest("ledger.range_invalid"));
+    }
+    Ok((from, to))
+}
+
+// 各科目截至 to 的发生额, from 之前的计入期初 (from 为空则全部计入本期)
+async fn account_balances(
+    pool: &PgPool,
+    hq_id: Uuid,
+    base_id: Option<Uuid>,
+    from: Option<NaiveDate>,
+    to: NaiveDate,
+) -> Result<Vec<AccountBalance>, AppError> {
+    sqlx::query_as::<_, AccountBalance>(
+        r#"
+        SELECT a.id AS account_id, a.code, a.name, a.account_type,
+               COALESCE(SUM(x.debit_cents) FILTER (WHERE x.entry_date < $3), 0)::bigint AS opening_debit,
+               COALESCE(SUM(x.credit_cents) FILTER (WHERE x.entry_date < $3), 0)::bigint AS opening_credit,
+               COALESCE(SUM(x.debit_cents) FILTER (WHERE $3::date IS NULL OR x.entry_date >= $3), 0)::bigint AS period_debit,
+               COALESCE(SUM(x.credit_cents) FILTER (WHERE $3::date IS NULL OR x.entry_date >= $3), 0)::bigint AS period_credit
+        FROM ledger_accounts a
+        LEFT JOIN (
+            SELECT l.account_id, l.debit_cents, l.credit_cents, e.entry_date
+            FROM journal_lines l
+            JOIN journal_entries e ON l.entry_id = e.id
+            WHERE e.hq_id = $1 AND ($2::uuid IS NULL OR e.base_id = $2) AND e.entry_date <= $4
+        ) x ON x.account_id = a.id
+        WHERE a.hq_id = $1
+        GROUP BY a.id, a.code, a.name, a.account_type
+        ORDER BY a.code
+        "#,
+    )
+    .bind(hq_id)
+    .bind(base_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::db("Failed to aggregate ledger balances"))
+}
+
+// ---------------------------------------------------------
+// 试算平衡表
+// ---------------------------------------------------------
+
+#[derive(Debug, Default, Serialize)]
+pub struct TrialBalanceRow {
+    pub account_id: Option<Uuid>, // 合计行为空
+    pub code: String,
+    pub name: String,
+    pub account_type: String,
+    pub opening_debit_cents: i64,
+    pub opening_credit_cents: i64,
+    pub period_debit_cents: i64,
+    pub period_credit_cents: i64,
+    pub closing_debit_cents: i64,
+    pub closing_credit_cents: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrialBalance {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub base_id: Option<Uuid>,
+    pub rows: Vec<TrialBalanceRow>, // 只列有余额或发生额的科目
+    pub totals: TrialBalanceRow,
+    pub balanced: bool,
+}
+
+// 余额净额按方向拆到借方 / 贷方
+fn split(net: i64) -> (i64, i64) {
+    if net >= 0 { (net, 0) } else { (0, -net) }
+}
+
+// (GET /api/v1/ledger/trial-balance?from=&to=&base_id=)
+pub async fn get_trial_balance_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(query): Query<LedgerReportQuery>,
+) -> Result<Json<TrialBalance>, AppError> {
+    let (from, to) = report_range(&query)?;
+    let base_id = report_base(&scope, &query);
+    let balances = account_balances(&state.db_pool, scope.hq_id, base_id, Some(from), to).await?;
+
+    let mut totals = TrialBalanceRow { name: "合计".to_string(), ..Default::default() };
+    let mut rows = Vec::new();
+    for b in balances {
+        let (opening_debit, opening_credit) = split(b.opening_debit - b.opening_credit);
+        let (closing_debit, closing_credit) = split(b.closing_net());
+        if opening_debit + opening_credit + b.period_debit + b.period_credit + closing_debit + closing_credit == 0 {
+            continue;
+        }
+        let row = TrialBalanceRow {
+            account_id: Some(b.account_id),
+            code: b.code,
+            name: b.name,
+            account_type: b.account_type,
+            opening_debit_cents: opening_debit,
+            opening_credit_cents: opening_credit,
+            period_debit_cents: b.period_debit,
+            period_credit_cents: b.period_credit,
+            closing_debit_cents: closing_debit,
+            closing_credit_cents: closing_credit,
+        };
+        totals.opening_debit_cents += row.opening_debit_cents;
+        totals.opening_credit_cents += row.opening_credit_cents;
+        totals.period_debit_cents += row.period_debit_cents;
+        totals.period_credit_cents += row.period_credit_cents;
+        totals.closing_debit_cents += row.closing_debit_cents;
+        totals.closing_credit_cents += row.closing_credit_cents;
+        rows.push(row);
+    }
+
+    let balanced = totals.opening_debit_cents == totals.opening_credit_cents
+        && totals.period_debit_cents == totals.period_credit_cents
+        && totals.closing_debit_cents == totals.closing_credit_cents;
+    Ok(Json(TrialBalance { from, to, base_id, rows, totals, balanced }))
+}
+
+// ---------------------------------------------------------
+// 利润表 / 资产负债表
+// ---------------------------------------------------------
+
+#[derive(Debug, Serialize)]
+pub struct StatementLine {
+    pub account_id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub amount_cents: i64,
+}
+
+impl StatementLine {
+    fn new(b: &AccountBalance, amount_cents: i64) -> Self {
+        StatementLine { account_id: b.account_id, code: b.code.clone(), name: b.name.clone(), amount_cents }
+    }
+}
+
+// 某类科目的非零行及合计
+fn section(balances: &[AccountBalance], account_type: &str, amount: impl Fn(&AccountBalance) -> i64) -> (Vec<StatementLine>, i64) {
+    let lines: Vec<StatementLine> = balances
+        .iter()
+        .filter(|b| b.account_type == account_type)
+        .map(|b| StatementLine::new(b, amount(b)))
+        .filter(|l| l.amount_cents != 0)
+        .collect();
+    let total = lines.iter().map(|l| l.amount_cents).sum();
+    (lines, total)
+}
+
+#[derive(Debug, Serialize)]
+pub struct IncomeStatement {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub base_id: Option<Uuid>,
+    pub revenue: Vec<StatementLine>,
+    pub expenses: Vec<StatementLine>,
+    pub total_revenue_cents: i64,
+    pub total_expense_cents: i64,
+    pub net_income_cents: i64,
+}
+
+// (GET /api/v1/ledger/income-statement?from=&to=&base_id=)
+pub async fn get_income_statement_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(query): Query<LedgerReportQuery>,
+) -> Result<Json<IncomeStatement>, AppError> {
+    let (from, to) = report_range(&query)?;
+    let base_id = report_base(&scope, &query);
+    let balances = account_balances(&state.db_pool, scope.hq_id, base_id, Some(from), to).await?;
+
+    let (revenue, total_revenue_cents) = section(&balances, "revenue", AccountBalance::period_amount);
+    let (expenses, total_expense_cents) = section(&balances, "expense", AccountBalance::period_amount);
+    Ok(Json(IncomeStatement {
+        from,
+        to,
+        base_id,
+        revenue,
+        expenses,
+        total_revenue_cents,
+        total_expense_cents,
+        net_income_cents: total_revenue_cents - total_expense_cents,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceSheet {
+    pub as_of: NaiveDate,
+    pub base_id: Option<Uuid>,
+    pub assets: Vec<StatementLine>,
+    pub liabilities: Vec<StatementLine>,
+    pub equity: Vec<StatementLine>,
+    pub unclosed_earnings_cents: i64, // 累计 收入 - 费用
+    pub total_assets_cents: i64,
+    pub total_liabilities_cents: i64,
+    pub total_equity_cents: i64, // 含未结转损益
+    pub balanced: bool,          // 资产 = 负债 + 所有者权益
+}
+
+// (GET /api/v1/ledger/balance-sheet?as_of=&base_id=)
+pub async fn get_balance_sheet_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(query): Query<LedgerReportQuery>,
+) -> Result<Json<BalanceSheet>, AppError> {
+    let as_of = query.as_of.unwrap_or_else(|| business_date(Utc::now()));
+    let base_id = report_base(&scope, &query);
+    let balances = account_balances(&state.db_pool, scope.hq_id, base_id, None, as_of).await?;
+
+    let (assets, total_assets_cents) = section(&balances, "asset", AccountBalance::closing_balance);
+    let (liabilities, total_liabilities_cents) = section(&balances, "liability", AccountBalance::closing_balance);
+    let (equity, equity_accounts_cents) = section(&balances, "equity", AccountBalance::closing_balance);
+    let (_, revenue_cents) = section(&balances, "revenue", AccountBalance::closing_balance);
+    let (_, expense_cents) = section(&balances, "expense", AccountBalance::closing_balance);
+    let unclosed_earnings_cents = revenue_cents - expense_cents;
+    let total_equity_cents = equity_accounts_cents + unclosed_earnings_cents;
+
+    Ok(Json(BalanceSheet {
+        as_of,
+        base_id,
+        assets,
+        liabilities,
+        equity,
+        unclosed_earnings_cents,
+        total_assets_cents,
+        total_liabilities_cents,
+        total_equity_cents,
+        balanced: total_assets_cents == total_liabilities_cents + total_equity_cents,
+    }))
+}
@@ -0,0 +1,168 @@
+/*
+ * src/handlers/login_guard.rs
+ * 职责: 员工登录防爆破 (数据来自 user_login_history)
+ * 1. 同一邮箱连续密码错误: 从第 2 次起逐次加长等待时间, 达到 5 次锁定 15 分钟
+ *    计数窗口从 "15 分钟前 / 最近一次成功登录 / 管理员解锁" 中最晚的时间算起
+ * 2. 同一 IP 15 分钟内密码错误 20 次: 暂停该 IP 的登录
+ * 3. 被拦截的请求也记入历史 (不计入错误次数), 供风控页查看
+ * 4. 管理员解锁 / 员工改密后, 之前的错误次数清零
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::AppState;
+use crate::models::Claims;
+use crate::tenant::{Owned, TenantScope};
+use crate::error::AppError;
+
+pub const LOGIN_MAX_FAILURES: i64 = 5;
+pub const LOGIN_LOCK_MINUTES: i64 = 15;
+const IP_MAX_FAILURES: i64 = 20;
+const DELAY_BASE_SECS: i64 = 2; // 第 2 次错误后等 2 秒, 之后每次翻倍
+const DELAY_MAX_SECS: i64 = 30;
+
+// user_login_history.failure_reason_key
+pub const LOGIN_FAILED_CREDENTIALS: &str = "auth.invalid_credentials"; // 只有这一种计入锁定
+pub const LOGIN_FAILED_DISABLED: &str = "auth.account_disabled";
+pub const LOGIN_FAILED_LOCKED: &str = "auth.locked";
+pub const LOGIN_FAILED_THROTTLED: &str = "auth.throttled";
+
+// 当前窗口内某账号 (acct.email / acct.login_unlocked_at) 的密码错误次数、最近一次错误时间和 IP
+// 调用方提供名为 acct 的行, 以 CROSS JOIN LATERAL 方式使用
+pub(crate) fn failures_lateral_sql() -> String {
+    format!(
+        r#"
+        SELECT COUNT(*) AS failures, MAX(h.login_timestamp) AS last_failure,
+               (ARRAY_AGG(h.ip_address ORDER BY h.login_timestamp DESC))[1] AS last_ip
+        FROM user_login_history h
+        WHERE lower(h.email_attempted) = lower(acct.email)
+          AND h.status = 'failed' AND h.failure_reason_key = '{reason}'
+          AND h.login_timestamp > GREATEST(
+              NOW() - make_interval(mins => {window}),
+              COALESCE(acct.login_unlocked_at, '-infinity'),
+              COALESCE((SELECT MAX(s.login_timestamp) FROM user_login_history s
+                        WHERE lower(s.email_attempted) = lower(acct.email) AND s.status = 'success'), '-infinity'))
+        "#,
+        reason = LOGIN_FAILED_CREDENTIALS,
+        window = LOGIN_LOCK_MINUTES,
+    )
+}
+
+#[derive(Debug)]
+pub enum LoginBlock {
+    Throttled,                // 等待时间未到 / IP 错误过多
+    Locked(DateTime<Utc>),    // 账号锁定到该时间
+}
+
+impl From<LoginBlock> for AppError {
+    fn from(b: LoginBlock) -> Self {
+        match b {
+            LoginBlock::Throttled => AppError::Custom(StatusCode::TOO_MANY_REQUESTS, LOGIN_FAILED_THROTTLED),
+            LoginBlock::Locked(until) => AppError::Custom(StatusCode::LOCKED, LOGIN_FAILED_LOCKED)
+                .with_details(serde_json::json!({ "locked_until": until })),
+        }
+    }
+}
+
+// 校验密码之前调用: 被限制时不再校验密码
+pub async fn check_login_allowed(pool: &PgPool, email: &str, ip: Option<&str>) -> Result<Option<LoginBlock>, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        r#"
+        SELECT f.failures, f.last_failure,
+            (SELECT COUNT(*) FROM user_login_history
+             WHERE $2::text IS NOT NULL AND ip_address = $2 AND status = 'failed' AND failure_reason_key = '{reason}'
+               AND login_timestamp > NOW() - make_interval(mins => {window})) AS ip_failures
+        FROM (
+            SELECT $1::text AS email,
+                   (SELECT login_unlocked_at FROM users WHERE lower(email) = lower($1) LIMIT 1) AS login_unlocked_at
+        ) acct
+        CROSS JOIN LATERAL ({failures}) f
+        "#,
+        reason = LOGIN_FAILED_CREDENTIALS,
+        window = LOGIN_LOCK_MINUTES,
+        failures = failures_lateral_sql(),
+    ))
+    .bind(email)
+    .bind(ip)
+    .fetch_one(pool)
+    .await?;
+
+    let failures: i64 = row.get("failures");
+    let last_failure: Option<DateTime<Utc>> = row.get("last_failure");
+    let ip_failures: i64 = row.get("ip_failures");
+
+    if ip_failures >= IP_MAX_FAILURES {
+        return Ok(Some(LoginBlock::Throttled));
+    }
+    let Some(last_failure) = last_failure else {
+        return Ok(None);
+    };
+    if failures >= LOGIN_MAX_FAILURES {
+        return Ok(Some(LoginBlock::Locked(last_failure + Duration::minutes(LOGIN_LOCK_MINUTES))));
+    }
+    if failures >= 2 {
+        let delay = (DELAY_BASE_SECS << (failures - 2)).min(DELAY_MAX_SECS);
+        if Utc::now() < last_failure + Duration::seconds(delay) {
+            return Ok(Some(LoginBlock::Throttled));
+        }
+    }
+    Ok(None)
+}
+
+// 写入登录历史; 失败时不影响登录结果
+pub async fn record_login_attempt(
+    pool: &PgPool,
+    email: &str,
+    user: Option<(Uuid, Uuid)>, // (user_id, hq_id)
+    ip: Option<&str>,
+    user_agent: Option<&str>,
+    failure_reason: Option<&str>,
+) {
+    let status = if failure_reason.is_none() { "success" } else { "failed" };
+    let result = sqlx::query(
+        r#"
+        INSERT INTO user_login_history (email_attempted, user_id, hq_id, ip_address, user_agent, status, failure_reason_key)
+        VALUES ($1, $2, $3, $4, $5, $6::login_status, $7)
+        "#,
+    )
+    .bind(email)
+    .bind(user.map(|(id, _)| id))
+    .bind(user.map(|(_, hq)| hq))
+    .bind(ip)
+    .bind(user_agent)
+    .bind(status)
+    .bind(failure_reason)
+    .execute(pool)
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!("Failed to record login attempt: {}", e);
+    }
+}
+
+// (POST /api/v1/hq/users/:id/unlock) 管理员解除员工的登录锁定
+pub async fn unlock_user_login_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(user_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = state.db_pool.begin().await?;
+    // 基地账号只能解锁本基地员工
+    TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::User(user_id)).await?;
+
+    sqlx::query("UPDATE users SET login_unlocked_at = NOW(), updated_at = NOW() WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to unlock user login"))?;
+    tx.commit().await?;
+
+    tracing::info!("Login unlocked for user {} by {}", user_id, claims.sub);
+    Ok(StatusCode::NO_CONTENT)
+}
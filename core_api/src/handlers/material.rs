@@ -4,13 +4,14 @@
  * (★ V3 - 角色安全加固版 ★)
  */
 
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{extract::State, Json};
 
 
 // 【修改】导入 AppState 和 Claims
 use super::AppState;
 // 导入 models
 use crate::models::{Claims, Material, CreateMaterialPayload};
+use crate::error::AppError;
 
 
 // (GET /api/v1/materials - 获取所有物料定义)
@@ -18,7 +19,7 @@ use crate::models::{Claims, Material, CreateMaterialPayload};
 pub async fn get_materials_handler(
     State(state): State<AppState>,
     claims: Claims, // <-- 【修改】必须出示“钥匙”
-) -> Result<Json<Vec<Material>>, StatusCode> {
+) -> Result<Json<Vec<Material>>, AppError> {
     
     // (HACK 已移除!)
     let hq_id = claims.hq_id; // <-- 【修改】使用“钥匙”中的租户ID
@@ -36,10 +37,7 @@ pub async fn get_materials_handler(
     .await
     {
         Ok(materials) => materials,
-        Err(e) => {
-            tracing::error!("Failed to fetch materials: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to fetch materials", source: e }),
     };
 
     Ok(Json(materials))
@@ -51,24 +49,9 @@ pub async fn create_material_handler(
     State(state): State<AppState>,
     claims: Claims, // <-- 【修改】必须出示“钥匙”
     Json(payload): Json<CreateMaterialPayload>,
-) -> Result<Json<Material>, StatusCode> {
+) -> Result<Json<Material>, AppError> {
     
-    // --- (★ 新增：角色安全守卫 ★) ---
-    // 检查“钥匙”中的角色列表是否包含“租户管理员”
-    let is_authorized = claims.roles.iter().any(|role| 
-        role == "role.hq.admin"
-    );
-
-    if !is_authorized {
-        tracing::warn!(
-            "Unauthorized attempt to create material by user {} (roles: {:?})",
-            claims.sub,
-            claims.roles
-        );
-        // 403 Forbidden: "我认识你(Token有效)，但你没有权限(Role不对)"
-        return Err(StatusCode::FORBIDDEN);
-    }
-    // --- (守卫结束) ---
+    // (权限: 路由声明 hq(perm::MATERIALS_MANAGE))
 
     // (HACK 已移除!)
     let hq_id = claims.hq_id; // <-- 【修改】使用“钥匙”中的租户ID
@@ -89,10 +72,7 @@ pub async fn create_material_handler(
     .await
     {
         Ok(material) => material,
-        Err(e) => {
-            tracing::error!("Failed to create material: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to create material", source: e }),
     };
 
     Ok(Json(new_material))
@@ -9,25 +9,34 @@ use axum::{
     http::StatusCode,
     Json,
 };
-use chrono::{Utc};
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-use super::{toggle_status_common, AppState};
+use super::ledger::{post_transfer, LedgerTransfer, ACCOUNT_CASH, ACCOUNT_CONTRACT_LIABILITY, SOURCE_MEMBERSHIP_REFUND, SOURCE_MEMBERSHIP_SALE};
+use super::{is_valid_recognition, post_membership_revenue, toggle_status_common, AppState};
+use crate::tenant::{Owned, TenantScope};
 use crate::models::{
     Claims,
     CreateCustomerMembershipPayload,
     CreateMembershipTierPayload,
     CustomerMembership,
+    ExtendMembershipPayload,
+    MembershipActionPayload,
+    MembershipAdjustment,
     MembershipTier,
+    MembershipTierType,
+    TransferMembershipPayload,
     UpdateStatusPayload, // (★ 修复: 添加 UpdateStatusPayload)
 };
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
 
 // (GET Tiers)
 pub async fn get_membership_tiers_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<MembershipTier>>, StatusCode> {
-    let tiers = sqlx::query_as::<_, MembershipTier>("SELECT * FROM membership_tiers WHERE hq_id = $1 AND is_active = true ORDER BY price_in_cents ASC").bind(claims.hq_id).fetch_all(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<Vec<MembershipTier>>, AppError> {
+    let tiers = sqlx::query_as::<_, MembershipTier>("SELECT * FROM membership_tiers WHERE hq_id = $1 AND is_active = true ORDER BY price_in_cents ASC").bind(claims.hq_id).fetch_all(&state.db_pool).await?;
     Ok(Json(tiers))
 }
 
@@ -36,14 +45,15 @@ pub async fn create_membership_tier_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateMembershipTierPayload>,
-) -> Result<Json<MembershipTier>, StatusCode> {
-    if !claims.roles.contains(&"role.hq.admin".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<MembershipTier>, AppError> {
     let price = (payload.price * 100.0).round() as i32;
-    let new_tier = sqlx::query_as::<_, MembershipTier>(r#"INSERT INTO membership_tiers (hq_id, name_key, description_key, tier_type, price_in_cents, duration_days, usage_count, is_active) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *"#)
-    .bind(claims.hq_id).bind(&payload.name_key).bind(payload.description_key).bind(payload.tier_type).bind(price).bind(payload.duration_days).bind(payload.usage_count).bind(payload.is_active.unwrap_or(true))
-    .fetch_one(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let recognition_method = payload.recognition_method.as_deref().unwrap_or("daily");
+    if !is_valid_recognition(recognition_method, payload.expected_class_count) {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+    let new_tier = sqlx::query_as::<_, MembershipTier>(r#"INSERT INTO membership_tiers (hq_id, name_key, description_key, tier_type, price_in_cents, duration_days, usage_count, is_active, recognition_method, expected_class_count) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING *"#)
+    .bind(claims.hq_id).bind(&payload.name_key).bind(payload.description_key).bind(payload.tier_type).bind(price).bind(payload.duration_days).bind(payload.usage_count).bind(payload.is_active.unwrap_or(true)).bind(recognition_method).bind(payload.expected_class_count)
+    .fetch_one(&state.db_pool).await?;
     Ok(Json(new_tier))
 }
 
@@ -52,15 +62,15 @@ pub async fn assign_membership_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateCustomerMembershipPayload>,
-) -> Result<Json<CustomerMembership>, StatusCode> {
+) -> Result<Json<CustomerMembership>, AppError> {
     let hq_id = claims.hq_id;
     let _base_id = match claims.base_id {
         Some(id) => id,
-        None => return Err(StatusCode::FORBIDDEN),
+        None => return Err(AppError::Forbidden("auth.forbidden")),
     };
     let mut tx = match state.db_pool.begin().await {
         Ok(tx) => tx,
-        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e) => return Err(e.into()),
     };
     let tier = match sqlx::query_as::<_, MembershipTier>(
         "SELECT * FROM membership_tiers WHERE id = $1 AND hq_id = $2 AND is_active = true",
@@ -73,21 +83,18 @@ pub async fn assign_membership_handler(
         Ok(Some(t)) => t,
         Ok(None) => {
             tx.rollback().await.ok();
-            return Err(StatusCode::NOT_FOUND);
+            return Err(AppError::NotFound("resource.not_found"));
         }
         Err(_) => {
             tx.rollback().await.ok();
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
         }
     };
-    let customer_check = sqlx::query("SELECT id FROM customers WHERE id = $1 AND hq_id = $2")
-        .bind(payload.customer_id)
-        .bind(hq_id)
-        .fetch_optional(&mut *tx)
-        .await;
-    if customer_check.is_err() || customer_check.unwrap().is_none() {
-        tx.rollback().await.ok();
-        return Err(StatusCode::NOT_FOUND);
+    // 客户 / 学员必须属于本基地
+    let scope = TenantScope::from_claims(&claims);
+    scope.ensure_owned(&mut *tx, Owned::Customer(payload.customer_id)).await?;
+    if let Some(participant_id) = payload.participant_id {
+        scope.ensure_owned(&mut *tx, Owned::Participant(participant_id)).await?;
     }
 
     let start_date = Utc::now();
@@ -97,29 +104,62 @@ pub async fn assign_membership_handler(
     let new_membership = match sqlx::query_as::<_, CustomerMembership>(
         r#"INSERT INTO customer_memberships (hq_id, customer_id, participant_id, tier_id, start_date, expiry_date, remaining_uses, is_active) VALUES ($1, $2, $3, $4, $5, $6, $7, true) RETURNING *"#
     ).bind(hq_id).bind(payload.customer_id).bind(payload.participant_id).bind(payload.tier_id).bind(start_date).bind(expiry_date).bind(tier.usage_count).fetch_one(&mut *tx).await {
-        Ok(m) => m, Err(_) => { tx.rollback().await.ok(); return Err(StatusCode::INTERNAL_SERVER_ERROR); }
+        Ok(m) => m, Err(_) => { tx.rollback().await.ok(); return Err(StatusCode::INTERNAL_SERVER_ERROR.into()); }
     };
 
-    let price_in_cents = tier.price_in_cents;
-    let user_id_uuid = Uuid::parse_str(&claims.sub).unwrap_or_default();
-    sqlx::query(
-        r#"INSERT INTO financial_transactions (hq_id, base_id, amount_in_cents, transaction_type, category, related_entity_id, description, created_by, debit_subject, credit_subject) VALUES ($1, $2, $3, 'income', 'membership_sale', $4, $5, $6, 'cash', 'contract_liability')"#
-    ).bind(hq_id).bind(claims.base_id).bind(price_in_cents).bind(new_membership.id).bind(format!("销售会员卡: {}", tier.name_key)).bind(user_id_uuid).execute(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // 售卡分录: 借 现金, 贷 合同负债
+    post_transfer(&mut tx, LedgerTransfer {
+        hq_id,
+        base_id: claims.base_id,
+        entry_date: None,
+        source_type: SOURCE_MEMBERSHIP_SALE,
+        source_id: Some(new_membership.id),
+        description: format!("销售会员卡: {}", tier.name_key),
+        created_by: Uuid::parse_str(&claims.sub).ok(),
+        debit: ACCOUNT_CASH,
+        credit: ACCOUNT_CONTRACT_LIABILITY,
+        amount_cents: tier.price_in_cents as i64,
+    })
+    .await?;
 
     if let Err(_) = tx.commit().await {
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
     }
     Ok(Json(new_membership))
 }
 
-// (GET Base Memberships)
+// (GET Base Memberships) 本基地的有效会员卡
+// 分页; 筛选: customer_id / participant_id / tier_id / expiry_from / expiry_to; q 搜家长姓名和手机号
+const BASE_MEMBERSHIP_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "cm.created_at"), ("expiry_date", "cm.expiry_date"), ("remaining_uses", "cm.remaining_uses")],
+    default_sort: "-created_at",
+    tiebreak: "cm.id",
+    filters: &[
+        Filter::uuid("customer_id", "cm.customer_id"),
+        Filter::uuid("participant_id", "cm.participant_id"),
+        Filter::uuid("tier_id", "cm.tier_id"),
+        Filter::date_from("expiry_from", "cm.expiry_date"),
+        Filter::date_to("expiry_to", "cm.expiry_date"),
+    ],
+    search: &["c.name", "c.phone_number"],
+};
+
 pub async fn get_base_memberships_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<CustomerMembership>>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
-    let list = sqlx::query_as::<_, CustomerMembership>("SELECT cm.* FROM customer_memberships cm JOIN customers c ON cm.customer_id = c.id WHERE cm.hq_id = $1 AND c.base_id = $2 AND cm.is_active = true ORDER BY cm.created_at DESC").bind(claims.hq_id).bind(base_id).fetch_all(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(list))
+    list: ListQuery,
+) -> Result<Json<Page<CustomerMembership>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    let hq_id = claims.hq_id;
+    let page = list
+        .fetch_page(&state.db_pool, &BASE_MEMBERSHIP_LIST, |qb| {
+            qb.push("SELECT cm.* FROM customer_memberships cm JOIN customers c ON cm.customer_id = c.id WHERE cm.is_active = true AND cm.hq_id = ")
+                .push_bind(hq_id)
+                .push(" AND c.base_id = ")
+                .push_bind(base_id);
+        })
+        .await?;
+    Ok(Json(page))
 }
 
 // (GET Customer Memberships)
@@ -127,8 +167,8 @@ pub async fn get_customer_memberships_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(customer_id): Path<Uuid>,
-) -> Result<Json<Vec<CustomerMembership>>, StatusCode> {
-    let list = sqlx::query_as::<_, CustomerMembership>("SELECT * FROM customer_memberships WHERE customer_id = $1 AND hq_id = $2 AND is_active = true").bind(customer_id).bind(claims.hq_id).fetch_all(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<Vec<CustomerMembership>>, AppError> {
+    let list = sqlx::query_as::<_, CustomerMembership>("SELECT * FROM customer_memberships WHERE customer_id = $1 AND hq_id = $2 AND is_active = true").bind(customer_id).bind(claims.hq_id).fetch_all(&state.db_pool).await?;
     Ok(Json(list))
 }
 
@@ -138,10 +178,7 @@ pub async fn toggle_tier_status_handler(
     claims: Claims,
     Path(id): Path<Uuid>,
     Json(payload): Json<UpdateStatusPayload>,
-) -> Result<StatusCode, StatusCode> {
-    if !claims.roles.contains(&"role.hq.admin".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<StatusCode, AppError> {
     toggle_status_common(
         &state.db_pool,
         "membership_tiers",
@@ -151,3 +188,684 @@ pub async fn toggle_tier_status_handler(
     )
     .await
 }
+
+#[derive(sqlx::FromRow)]
+struct MembershipCheckRow {
+    is_owner: bool,
+    is_active: bool,
+    is_frozen: bool,
+    has_started: bool,
+    not_expired: bool,
+    remaining_uses: Option<i32>, // 仅次卡有值
+    reserved_uses: i64,
+}
+
+// --- 会员卡可用性校验 (报名 / 消课时调用) ---
+// 返回 None 表示可用, Some(原因 key) 表示不可用
+// 次卡的"已预约次数" = 该卡下状态为 enrolled 的报名数 (报名即占用一次, 消课 / 请假 / 退课后释放)
+pub async fn find_membership_problem(
+    conn: &mut sqlx::PgConnection,
+    membership_id: Uuid,
+    participant_id: Uuid,
+    class_id: Uuid,
+    enrollment_id: Option<Uuid>, // 消课时传入, 该报名本身的预约不重复计算
+) -> Result<Option<&'static str>, sqlx::Error> {
+    let row = sqlx::query_as::<_, MembershipCheckRow>(
+        r#"
+        SELECT
+            (cm.customer_id = p.customer_id AND (cm.participant_id IS NULL OR cm.participant_id = p.id)) AS is_owner,
+            COALESCE(cm.is_active, true) AS is_active,
+            cm.frozen_at IS NOT NULL AS is_frozen,
+            cm.start_date <= c.start_time AS has_started,
+            (cm.expiry_date IS NULL OR cm.expiry_date >= c.start_time) AS not_expired,
+            CASE WHEN mt.tier_type = 'usage_based' THEN COALESCE(cm.remaining_uses, 0) END AS remaining_uses,
+            (SELECT COUNT(*) FROM class_enrollments e
+             WHERE e.customer_membership_id = cm.id
+               AND COALESCE(e.status, 'enrolled') = 'enrolled'
+               AND ($4::uuid IS NULL OR e.id <> $4)) AS reserved_uses
+        FROM customer_memberships cm
+        JOIN membership_tiers mt ON cm.tier_id = mt.id
+        JOIN participants p ON p.id = $2
+        JOIN classes c ON c.id = $3
+        WHERE cm.id = $1 AND cm.hq_id = c.hq_id
+        FOR UPDATE OF cm
+        "#,
+    )
+    .bind(membership_id)
+    .bind(participant_id)
+    .bind(class_id)
+    .bind(enrollment_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(match row {
+        Some(row) => membership_problem(&row),
+        None => Some("membership.not_found"),
+    })
+}
+
+// 按校验顺序给出第一个不可用原因; 次卡剩余次数需扣掉已预约 (未消课) 的次数
+fn membership_problem(row: &MembershipCheckRow) -> Option<&'static str> {
+    if !row.is_owner {
+        Some("membership.not_owner")
+    } else if !row.is_active {
+        Some("membership.inactive")
+    } else if row.is_frozen {
+        Some("membership.frozen")
+    } else if !row.has_started {
+        Some("membership.not_started")
+    } else if !row.not_expired {
+        Some("membership.expired")
+    } else if row.remaining_uses.is_some_and(|left| (left as i64) - row.reserved_uses <= 0) {
+        Some("membership.no_remaining_uses")
+    } else {
+        None
+    }
+}
+
+// ==========================================
+// 会员卡生命周期: 冻结 / 解冻 / 延期 / 转让 / 退卡
+// ==========================================
+
+// 变更记录 (写入 membership_adjustments)
+#[derive(Default)]
+struct NewAdjustment<'a> {
+    action: &'a str,
+    days: Option<i32>,
+    uses: Option<i32>,
+    amount_in_cents: Option<i32>,
+    from_participant_id: Option<Uuid>,
+    to_participant_id: Option<Uuid>,
+    target_membership_id: Option<Uuid>,
+    reason: Option<&'a str>,
+}
+
+async fn record_adjustment(
+    conn: &mut sqlx::PgConnection,
+    claims: &Claims,
+    membership: &CustomerMembership,
+    adj: NewAdjustment<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO membership_adjustments
+        (hq_id, membership_id, action, days, uses, amount_in_cents, from_participant_id, to_participant_id, target_membership_id, reason, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#,
+    )
+    .bind(membership.hq_id)
+    .bind(membership.id)
+    .bind(adj.action)
+    .bind(adj.days)
+    .bind(adj.uses)
+    .bind(adj.amount_in_cents)
+    .bind(adj.from_participant_id)
+    .bind(adj.to_participant_id)
+    .bind(adj.target_membership_id)
+    .bind(adj.reason)
+    .bind(Uuid::parse_str(&claims.sub).ok())
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+// 锁定本基地的会员卡 (连同卡种), 不存在或不属于本基地返回 404
+async fn lock_base_membership(
+    conn: &mut sqlx::PgConnection,
+    claims: &Claims,
+    membership_id: Uuid,
+) -> Result<(CustomerMembership, MembershipTier), AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let membership = sqlx::query_as::<_, CustomerMembership>(
+        r#"
+        SELECT cm.* FROM customer_memberships cm
+        JOIN customers c ON cm.customer_id = c.id
+        WHERE cm.id = $1 AND cm.hq_id = $2 AND c.base_id = $3
+        FOR UPDATE OF cm
+        "#,
+    )
+    .bind(membership_id)
+    .bind(claims.hq_id)
+    .bind(base_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to lock membership"))?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    let tier = sqlx::query_as::<_, MembershipTier>("SELECT * FROM membership_tiers WHERE id = $1")
+        .bind(membership.tier_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(AppError::db("Failed to fetch membership tier"))?;
+
+    Ok((membership, tier))
+}
+
+// 已预约 (enrolled) 但尚未消课的次数
+async fn count_reserved_uses(conn: &mut sqlx::PgConnection, membership_id: Uuid) -> Result<i64, AppError> {
+    sqlx::query_scalar(
+        "SELECT COUNT(*) FROM class_enrollments WHERE customer_membership_id = $1 AND COALESCE(status, 'enrolled') = 'enrolled'",
+    )
+    .bind(membership_id)
+    .fetch_one(conn)
+    .await
+    .map_err(AppError::db("Failed to count reserved uses"))
+}
+
+fn db_error(e: sqlx::Error) -> AppError {
+    AppError::Database { context: "Membership lifecycle update failed", source: e }
+}
+
+// 整天数 (不足一天按一天算)
+fn elapsed_days(from: DateTime<Utc>, to: DateTime<Utc>) -> i32 {
+    let seconds = (to - from).num_seconds().max(0);
+    ((seconds + 86_399) / 86_400) as i32
+}
+
+// (POST /api/v1/customer-memberships/:id/freeze) 冻结 (如孩子生病停课)
+pub async fn freeze_membership_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(membership_id): Path<Uuid>,
+    Json(payload): Json<MembershipActionPayload>,
+) -> Result<Json<CustomerMembership>, AppError> {
+    let mut tx = state.db_pool.begin().await.map_err(db_error)?;
+    let (membership, _) = lock_base_membership(&mut tx, &claims, membership_id).await?;
+
+    if !membership.is_active || membership.frozen_at.is_some() {
+        return Err(AppError::Conflict(if membership.is_active { "membership.frozen" } else { "membership.inactive" }));
+    }
+    // 没有到期日的卡冻结无意义
+    if membership.expiry_date.is_none() {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let updated = sqlx::query_as::<_, CustomerMembership>(
+        "UPDATE customer_memberships SET frozen_at = NOW() WHERE id = $1 RETURNING *",
+    )
+    .bind(membership_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    record_adjustment(&mut tx, &claims, &membership, NewAdjustment {
+        action: "freeze",
+        reason: payload.reason.as_deref(),
+        ..Default::default()
+    })
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+    Ok(Json(updated))
+}
+
+// (POST /api/v1/customer-memberships/:id/unfreeze) 解冻, 到期日按冻结天数顺延
+pub async fn unfreeze_membership_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(membership_id): Path<Uuid>,
+    Json(payload): Json<MembershipActionPayload>,
+) -> Result<Json<CustomerMembership>, AppError> {
+    let mut tx = state.db_pool.begin().await.map_err(db_error)?;
+    let (membership, _) = lock_base_membership(&mut tx, &claims, membership_id).await?;
+
+    let frozen_at = membership.frozen_at.ok_or(AppError::Conflict("membership.not_frozen"))?;
+    let frozen_days = elapsed_days(frozen_at, Utc::now());
+
+    let updated = sqlx::query_as::<_, CustomerMembership>(
+        r#"
+        UPDATE customer_memberships
+        SET frozen_at = NULL,
+            expiry_date = expiry_date + make_interval(days => $2)
+        WHERE id = $1
+        RETURNING *
+        "#,
+    )
+    .bind(membership_id)
+    .bind(frozen_days)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    record_adjustment(&mut tx, &claims, &membership, NewAdjustment {
+        action: "unfreeze",
+        days: Some(frozen_days),
+        reason: payload.reason.as_deref(),
+        ..Default::default()
+    })
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+    Ok(Json(updated))
+}
+
+// (POST /api/v1/customer-memberships/:id/extend) 手动延期
+pub async fn extend_membership_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(membership_id): Path<Uuid>,
+    Json(payload): Json<ExtendMembershipPayload>,
+) -> Result<Json<CustomerMembership>, AppError> {
+    let reason = payload.reason.trim();
+    if reason.is_empty() || payload.days <= 0 || payload.days > 3650 {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let mut tx = state.db_pool.begin().await.map_err(db_error)?;
+    let (membership, _) = lock_base_membership(&mut tx, &claims, membership_id).await?;
+
+    if !membership.is_active {
+        return Err(AppError::Conflict("membership.inactive"));
+    }
+    if membership.expiry_date.is_none() {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let updated = sqlx::query_as::<_, CustomerMembership>(
+        "UPDATE customer_memberships SET expiry_date = expiry_date + make_interval(days => $2) WHERE id = $1 RETURNING *",
+    )
+    .bind(membership_id)
+    .bind(payload.days)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(db_error)?;
+
+    record_adjustment(&mut tx, &claims, &membership, NewAdjustment {
+        action: "extend",
+        days: Some(payload.days),
+        reason: Some(reason),
+        ..Default::default()
+    })
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+    Ok(Json(updated))
+}
+
+// (POST /api/v1/customer-memberships/:id/transfer) 转让给同一家长的其他孩子
+// 不填 uses: 整卡转让 (要求无未消课预约); 填 uses: 次卡拆分出一张新卡给目标学员
+pub async fn transfer_membership_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(membership_id): Path<Uuid>,
+    Json(payload): Json<TransferMembershipPayload>,
+) -> Result<Json<CustomerMembership>, AppError> {
+    let mut tx = state.db_pool.begin().await.map_err(db_error)?;
+    let (membership, tier) = lock_base_membership(&mut tx, &claims, membership_id).await?;
+
+    if !membership.is_active || membership.frozen_at.is_some() {
+        return Err(AppError::Conflict(if membership.is_active { "membership.frozen" } else { "membership.inactive" }));
+    }
+
+    let target_customer: Option<Uuid> = sqlx::query_scalar("SELECT customer_id FROM participants WHERE id = $1 AND hq_id = $2")
+        .bind(payload.to_participant_id)
+        .bind(claims.hq_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    if target_customer != Some(membership.customer_id) || membership.participant_id == Some(payload.to_participant_id) {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let reserved = count_reserved_uses(&mut tx, membership_id).await?;
+
+    let (result, transferred_uses) = match payload.uses {
+        None => {
+            if reserved > 0 {
+                return Err(AppError::Conflict("membership.has_reservations"));
+            }
+            let moved = sqlx::query_as::<_, CustomerMembership>(
+                "UPDATE customer_memberships SET participant_id = $2 WHERE id = $1 RETURNING *",
+            )
+            .bind(membership_id)
+            .bind(payload.to_participant_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            (moved, membership.remaining_uses)
+        }
+        Some(uses) => {
+            if tier.tier_type != MembershipTierType::UsageBased || uses <= 0 {
+                return Err(AppError::BadRequest("request.invalid"));
+            }
+            let available = membership.remaining_uses.unwrap_or(0) as i64 - reserved;
+            if uses as i64 > available {
+                return Err(AppError::Conflict("membership.no_remaining_uses"));
+            }
+
+            // 转出次数对应的合同负债随之转到新卡: 原卡按已结转处理, 新卡只持有这部分负债
+            let moved_liability = per_use_price_in_cents(&tier) * uses;
+            sqlx::query(
+                "UPDATE customer_memberships SET remaining_uses = remaining_uses - $2, revenue_recognized_in_cents = revenue_recognized_in_cents + $3 WHERE id = $1",
+            )
+            .bind(membership_id)
+            .bind(uses)
+            .bind(moved_liability)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+
+            let split = sqlx::query_as::<_, CustomerMembership>(
+                r#"
+                INSERT INTO customer_memberships (hq_id, customer_id, participant_id, tier_id, start_date, expiry_date, remaining_uses, is_active, revenue_recognized_in_cents)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, true, $8)
+                RETURNING *
+                "#,
+            )
+            .bind(membership.hq_id)
+            .bind(membership.customer_id)
+            .bind(payload.to_participant_id)
+            .bind(membership.tier_id)
+            .bind(membership.start_date)
+            .bind(membership.expiry_date)
+            .bind(uses)
+            .bind((tier.price_in_cents - moved_liability).max(0))
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            (split, Some(uses))
+        }
+    };
+
+    record_adjustment(&mut tx, &claims, &membership, NewAdjustment {
+        action: "transfer",
+        uses: transferred_uses,
+        from_participant_id: membership.participant_id,
+        to_participant_id: Some(payload.to_participant_id),
+        target_membership_id: Some(result.id),
+        reason: payload.reason.as_deref(),
+        ..Default::default()
+    })
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+    Ok(Json(result))
+}
+
+// 次卡单次价格 (分): 与消课确认收入的均摊口径一致, 除不尽的零头留在合同负债里, 退卡时结转
+fn per_use_price_in_cents(tier: &MembershipTier) -> i32 {
+    match tier.usage_count {
+        Some(count) if count > 0 => tier.price_in_cents.max(0) / count,
+        _ => 0,
+    }
+}
+
+// 按未使用部分折算退款金额 (分)
+// 次卡: 单次价格 × 剩余次数; 期限卡: 按剩余天数占比, 冻结中从冻结日起算
+// 都不超过尚未确认为收入的部分 (售价 - 已确认收入), 已转收入的金额不再退
+fn calc_refund_in_cents(membership: &CustomerMembership, tier: &MembershipTier, now: DateTime<Utc>) -> i32 {
+    let price = tier.price_in_cents.max(0) as i64;
+    let unrecognized = (price - membership.revenue_recognized_in_cents as i64).max(0);
+    let amount = match tier.tier_type {
+        MembershipTierType::UsageBased => {
+            let left = membership.remaining_uses.unwrap_or(0).max(0) as i64;
+            per_use_price_in_cents(tier) as i64 * left
+        }
+        MembershipTierType::TimeBased => {
+            let Some(expiry) = membership.expiry_date else { return 0 };
+            let total = tier
+                .duration_days
+                .map(|d| d as i64)
+                .unwrap_or_else(|| (expiry - membership.start_date).num_days());
+            let reference = membership.frozen_at.unwrap_or(now).max(membership.start_date);
+            let left = (expiry - reference).num_days().clamp(0, total.max(0));
+            if total > 0 { price * left / total } else { 0 }
+        }
+    };
+    amount.min(unrecognized) as i32
+}
+
+// (POST /api/v1/customer-memberships/:id/refund) 退卡: 按比例退款并冲销合同负债
+pub async fn refund_membership_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(membership_id): Path<Uuid>,
+    Json(payload): Json<MembershipActionPayload>,
+) -> Result<Json<serde_json::Value>, AppError> {
+
+    let mut tx = state.db_pool.begin().await.map_err(db_error)?;
+    let (membership, tier) = lock_base_membership(&mut tx, &claims, membership_id).await?;
+
+    if !membership.is_active {
+        return Err(AppError::Conflict("membership.inactive"));
+    }
+    // 还有未消课的预约时, 需先取消预约再退卡
+    if count_reserved_uses(&mut tx, membership_id).await? > 0 {
+        return Err(AppError::Conflict("membership.has_reservations"));
+    }
+
+    let refund_amount = calc_refund_in_cents(&membership, &tier, Utc::now());
+
+    sqlx::query("UPDATE customer_memberships SET is_active = false, frozen_at = NULL WHERE id = $1")
+        .bind(membership_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    // 冲销分录: 借 合同负债, 贷 现金 (与售卡分录方向相反)
+    post_transfer(&mut tx, LedgerTransfer {
+        hq_id: claims.hq_id,
+        base_id: claims.base_id,
+        entry_date: None,
+        source_type: SOURCE_MEMBERSHIP_REFUND,
+        source_id: Some(membership_id),
+        description: format!("会员卡退款: {}", tier.name_key),
+        created_by: Uuid::parse_str(&claims.sub).ok(),
+        debit: ACCOUNT_CONTRACT_LIABILITY,
+        credit: ACCOUNT_CASH,
+        amount_cents: refund_amount as i64,
+    })
+    .await?;
+
+    // 退款之外剩余的合同负债一次性转收入, 退卡后该卡负债清零
+    // 期限卡: 已使用但尚未摊销的部分; 次卡: 单次价格除不尽的零头
+    let leftover = tier.price_in_cents - membership.revenue_recognized_in_cents - refund_amount;
+    if leftover > 0 {
+        let label = if tier.tier_type == MembershipTierType::TimeBased { "期限卡" } else { "次卡" };
+        post_membership_revenue(
+            &mut tx,
+            claims.hq_id,
+            claims.base_id,
+            membership_id,
+            leftover,
+            &format!("{}收入确认 (退卡结转): {}", label, tier.name_key),
+        )
+        .await?;
+    }
+
+    record_adjustment(&mut tx, &claims, &membership, NewAdjustment {
+        action: "refund",
+        amount_in_cents: Some(refund_amount),
+        reason: payload.reason.as_deref(),
+        ..Default::default()
+    })
+    .await
+    .map_err(db_error)?;
+
+    tx.commit().await.map_err(db_error)?;
+    Ok(Json(serde_json::json!({
+        "membership_id": membership_id,
+        "refund_amount_in_cents": refund_amount,
+    })))
+}
+
+// (GET /api/v1/customer-memberships/:id/adjustments) 会员卡变更记录
+pub async fn get_membership_adjustments_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(membership_id): Path<Uuid>,
+) -> Result<Json<Vec<MembershipAdjustment>>, AppError> {
+    let list = sqlx::query_as::<_, MembershipAdjustment>(
+        r#"
+        SELECT id, membership_id, action, days, uses, amount_in_cents, from_participant_id,
+               to_participant_id, target_membership_id, reason, created_by, created_at
+        FROM membership_adjustments
+        WHERE membership_id = $1 AND hq_id = $2
+        ORDER BY created_at DESC
+        "#,
+    )
+    .bind(membership_id)
+    .bind(claims.hq_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(db_error)?;
+
+    Ok(Json(list))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::{RECOGNITION_DAILY, RECOGNITION_PER_CLASS};
+    use chrono::{Duration, TimeZone};
+
+    fn at(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap() + Duration::days(d as i64 - 1)
+    }
+
+    fn tier(tier_type: MembershipTierType, recognition_method: &str) -> MembershipTier {
+        MembershipTier {
+            id: Uuid::nil(),
+            hq_id: Uuid::nil(),
+            name_key: String::new(),
+            description_key: None,
+            tier_type,
+            price_in_cents: 100_000,
+            duration_days: Some(100),
+            usage_count: Some(30),
+            is_active: true,
+            recognition_method: recognition_method.to_string(),
+            expected_class_count: Some(20),
+        }
+    }
+
+    fn membership(expiry: Option<DateTime<Utc>>, remaining_uses: Option<i32>) -> CustomerMembership {
+        CustomerMembership {
+            id: Uuid::nil(),
+            customer_id: Uuid::nil(),
+            participant_id: None,
+            tier_id: Uuid::nil(),
+            hq_id: Uuid::nil(),
+            start_date: at(1),
+            expiry_date: expiry,
+            remaining_uses,
+            is_active: true,
+            frozen_at: None,
+            revenue_recognized_in_cents: 0,
+        }
+    }
+
+    fn usable(remaining_uses: Option<i32>, reserved_uses: i64) -> MembershipCheckRow {
+        MembershipCheckRow {
+            is_owner: true,
+            is_active: true,
+            is_frozen: false,
+            has_started: true,
+            not_expired: true,
+            remaining_uses,
+            reserved_uses,
+        }
+    }
+
+    #[test]
+    fn reserved_uses_count_against_remaining_uses() {
+        assert_eq!(membership_problem(&usable(Some(3), 0)), None);
+        assert_eq!(membership_problem(&usable(Some(3), 2)), None);
+        assert_eq!(membership_problem(&usable(Some(3), 3)), Some("membership.no_remaining_uses"));
+        assert_eq!(membership_problem(&usable(Some(0), 0)), Some("membership.no_remaining_uses"));
+        // 已透支 (历史数据) 同样不可用
+        assert_eq!(membership_problem(&usable(Some(-1), 0)), Some("membership.no_remaining_uses"));
+        // 期限卡不按次数限制
+        assert_eq!(membership_problem(&usable(None, 50)), None);
+    }
+
+    #[test]
+    fn membership_problems_are_reported_in_order() {
+        let mut row = usable(Some(0), 0);
+        row.not_expired = false;
+        row.is_frozen = true;
+        assert_eq!(membership_problem(&row), Some("membership.frozen"));
+        row.is_owner = false;
+        assert_eq!(membership_problem(&row), Some("membership.not_owner"));
+        row.is_owner = true;
+        row.is_frozen = false;
+        assert_eq!(membership_problem(&row), Some("membership.expired"));
+        row.has_started = false;
+        assert_eq!(membership_problem(&row), Some("membership.not_started"));
+        row.is_active = false;
+        assert_eq!(membership_problem(&row), Some("membership.inactive"));
+    }
+
+    #[test]
+    fn elapsed_days_rounds_partial_days_up() {
+        assert_eq!(elapsed_days(at(1), at(1)), 0);
+        assert_eq!(elapsed_days(at(1), at(1) + Duration::seconds(1)), 1);
+        assert_eq!(elapsed_days(at(1), at(11)), 10);
+        assert_eq!(elapsed_days(at(1), at(11) + Duration::hours(1)), 11);
+        assert_eq!(elapsed_days(at(11), at(1)), 0);
+    }
+
+    #[test]
+    fn usage_based_refund_uses_per_class_price() {
+        let t = tier(MembershipTierType::UsageBased, RECOGNITION_DAILY);
+        // 100000 / 30 = 3333 (向下取整), 剩 10 次
+        assert_eq!(calc_refund_in_cents(&membership(None, Some(10)), &t, at(50)), 33_330);
+        assert_eq!(calc_refund_in_cents(&membership(None, Some(30)), &t, at(50)), 99_990);
+        assert_eq!(calc_refund_in_cents(&membership(None, Some(-1)), &t, at(50)), 0);
+    }
+
+    #[test]
+    fn usage_based_refund_leaves_rounding_remainder_for_settlement() {
+        let t = tier(MembershipTierType::UsageBased, RECOGNITION_DAILY);
+        // 用了 20 次, 已确认 20 × 3333; 退 10 × 3333, 剩 10 分零头由退卡时结转收入
+        let mut m = membership(None, Some(10));
+        m.revenue_recognized_in_cents = 66_660;
+        let refund = calc_refund_in_cents(&m, &t, at(50));
+        assert_eq!(refund, 33_330);
+        assert_eq!(t.price_in_cents - m.revenue_recognized_in_cents - refund, 10);
+        // 拆分转出的次数已计入已确认部分, 退款不会超过本卡持有的负债
+        m.revenue_recognized_in_cents = 90_000;
+        assert_eq!(calc_refund_in_cents(&m, &t, at(50)), 10_000);
+    }
+
+    #[test]
+    fn time_based_refund_is_proportional_to_days_left() {
+        let t = tier(MembershipTierType::TimeBased, RECOGNITION_DAILY);
+        let m = membership(Some(at(101)), None);
+        assert_eq!(calc_refund_in_cents(&m, &t, at(1)), 100_000);
+        assert_eq!(calc_refund_in_cents(&m, &t, at(41)), 60_000);
+        assert_eq!(calc_refund_in_cents(&m, &t, at(41) + Duration::hours(12)), 59_000); // 剩余不足整天的部分不退
+        assert_eq!(calc_refund_in_cents(&m, &t, at(200)), 0);
+    }
+
+    #[test]
+    fn frozen_card_refund_counts_from_freeze_date() {
+        let t = tier(MembershipTierType::TimeBased, RECOGNITION_DAILY);
+        let mut m = membership(Some(at(101)), None);
+        m.frozen_at = Some(at(31));
+        assert_eq!(calc_refund_in_cents(&m, &t, at(81)), 70_000);
+    }
+
+    #[test]
+    fn refund_never_exceeds_unrecognized_revenue() {
+        // 按天摊销已确认到第 41 天: 比例与负债一致
+        let daily = tier(MembershipTierType::TimeBased, RECOGNITION_DAILY);
+        let mut m = membership(Some(at(101)), None);
+        m.revenue_recognized_in_cents = 40_000;
+        assert_eq!(calc_refund_in_cents(&m, &daily, at(41)), 60_000);
+
+        // 按次确认: 前 40 天上了 15 节 (已确认 75000), 只能退剩余负债 25000
+        let per_class = tier(MembershipTierType::TimeBased, RECOGNITION_PER_CLASS);
+        m.revenue_recognized_in_cents = 75_000;
+        assert_eq!(calc_refund_in_cents(&m, &per_class, at(41)), 25_000);
+
+        // 已全部确认 (到期结转后) 不再退款
+        m.revenue_recognized_in_cents = 100_000;
+        assert_eq!(calc_refund_in_cents(&m, &per_class, at(41)), 0);
+    }
+}
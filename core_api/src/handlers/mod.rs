@@ -6,10 +6,18 @@
  * 2. 声明并导出所有子模块 (auth.rs, customer.rs, ...)。
  */
 
+use std::sync::Arc;
+
 use sqlx::PgPool;
 use uuid::Uuid;
 use reqwest::StatusCode;
 
+use crate::error::AppError;
+use crate::invoice::InvoiceProvider;
+use crate::payment::PaymentProvider;
+use crate::sms::SmsProvider;
+use crate::wechat::WechatApi;
+
 // --- 1. 共享的 AppState ---
 // (AppState 留在父模块中, 以便子模块可以通过 super::AppState 访问)
 #[derive(Clone)]
@@ -18,6 +26,10 @@ pub struct AppState {
     pub ai_api_url: String,
     pub http_client: reqwest::Client,
     pub jwt_secret: String,
+    pub wechat: Arc<dyn WechatApi>, // 微信登录 (jscode2session), 见 src/wechat.rs
+    pub sms: Arc<dyn SmsProvider>,  // 短信验证码, 见 src/sms.rs
+    pub invoice: Arc<dyn InvoiceProvider>, // 电子发票开具, 见 src/invoice.rs
+    pub payment: Arc<dyn PaymentProvider>, // 在线支付 (微信支付), 见 src/payment.rs
 }
 
 // --- 2. 声明并导出所有子模块 ---
@@ -28,6 +40,18 @@ pub use health::*;
 pub mod auth;
 pub use auth::*;
 
+// --- 登录会话: Refresh Token / 退出登录 / 吊销 ---
+pub mod session;
+pub use session::*;
+
+// --- 员工密码: 密码策略 / 修改 / 重置 ---
+pub mod password;
+pub use password::*;
+
+// --- 登录防爆破: 逐次延迟 / 临时锁定 / 管理员解锁 ---
+pub mod login_guard;
+pub use login_guard::*;
+
 pub mod base;
 pub use base::*;
 
@@ -44,6 +68,10 @@ pub use customer_honor::*;
 pub mod customer_auth;
 pub use customer_auth::*;
 
+// --- 短信验证码 ---
+pub mod sms_code;
+pub use sms_code::*;
+
 pub mod participant;
 pub use participant::*;
 
@@ -77,9 +105,17 @@ pub use teacher::*;
 pub mod class;
 pub use class::*;
 
+// --- 【新增】循环排课 ---
+pub mod class_series;
+pub use class_series::*;
+
 pub mod enrollment;
 pub use enrollment::*;
 
+// --- 【新增】报名候补队列 ---
+pub mod waitlist;
+pub use waitlist::*;
+
 // --- 【新增】基地库存模块 ---
 pub mod stock;
 pub use stock::*;
@@ -94,9 +130,32 @@ pub use procurement::*;
 pub mod schedule_ai;
 pub use schedule_ai::*;
 
+// --- 【新增】排课冲突校验 ---
+pub mod schedule_conflict;
+pub use schedule_conflict::*;
+
+// --- 【新增】本地排课求解器 (AI 兜底) ---
+pub mod schedule_solver;
+
 pub mod finance; // 新增
 pub use finance::*;
 
+// --- 订单退款: 申请 / 审批通过后退款入账 ---
+pub mod order_refund;
+pub use order_refund::*;
+
+// --- 应收账款: 订单分期 / 收款核销 / 账龄分析 ---
+pub mod receivable;
+pub use receivable::*;
+
+// --- 电子发票: 开票申请 / 红字冲销 / 开票与收款核对 ---
+pub mod invoicing;
+pub use invoicing::*;
+
+// --- 在线支付: 小程序下单 / 支付回调 / 每日对账 ---
+pub mod online_payment;
+pub use online_payment::*;
+
 pub mod supply;
 pub use supply::*;
 
@@ -121,6 +180,32 @@ pub use trial_class::*;
 pub mod workspace;
 pub use workspace::*;
 
+// --- 【新增】C端个人消息 & 老师请假代课 ---
+pub mod notification;
+pub use notification::*;
+
+pub mod substitution;
+pub use substitution::*;
+
+// --- 【新增】期限卡收入确认 ---
+pub mod revenue;
+pub use revenue::*;
+
+// --- 总账: 会计科目 / 凭证 / 结账 / 报表 ---
+pub mod ledger;
+pub use ledger::*;
+
+pub mod ledger_report;
+pub use ledger_report::*;
+
+// --- 【新增】会员卡续费提醒 ---
+pub mod reminder;
+pub use reminder::*;
+
+// --- 【新增】开发调试: 模拟登录 ---
+pub mod impersonation;
+pub use impersonation::*;
+
 // --- (★ V16.0 新增: 通用状态切换逻辑) ---
 // 这是一个辅助函数，不是 Handler，供具体 Handler 调用
 pub async fn toggle_status_common(
@@ -129,7 +214,7 @@ pub async fn toggle_status_common(
     id: Uuid,
     hq_id: Uuid,
     is_active: bool,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AppError> {
     
     // 注意: 表名必须是硬编码传入的，防止 SQL 注入风险
     let query = format!(
@@ -149,7 +234,7 @@ pub async fn toggle_status_common(
         })?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(AppError::NotFound("resource.not_found"));
     }
 
     Ok(StatusCode::OK)
@@ -0,0 +1,140 @@
+/*
+ * src/handlers/notification.rs
+ * 职责: C 端个人消息 (customer_notifications)
+ * 供调课、候补、会员卡提醒等业务在事务内写入; 家长在小程序 "我的消息" 查看
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use super::AppState;
+use crate::models::Claims;
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CustomerNotification {
+    pub id: Uuid,
+    pub category: String,
+    pub title: String,
+    pub content: String,
+    pub related_entity_id: Option<Uuid>,
+    pub is_read: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+// 给单个家长发消息
+pub async fn notify_customer(
+    conn: &mut PgConnection,
+    hq_id: Uuid,
+    customer_id: Uuid,
+    category: &str,
+    title: &str,
+    content: &str,
+    related_entity_id: Option<Uuid>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO customer_notifications (hq_id, customer_id, category, title, content, related_entity_id)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(hq_id)
+    .bind(customer_id)
+    .bind(category)
+    .bind(title)
+    .bind(content)
+    .bind(related_entity_id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+// 给某节课所有有效报名的家长发消息, 返回通知人数
+pub async fn notify_class_customers(
+    conn: &mut PgConnection,
+    class_id: Uuid,
+    category: &str,
+    title: &str,
+    content: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO customer_notifications (hq_id, customer_id, category, title, content, related_entity_id)
+        SELECT DISTINCT e.hq_id, e.customer_id, $2, $3, $4, e.class_id
+        FROM class_enrollments e
+        WHERE e.class_id = $1
+          AND COALESCE(e.status, 'enrolled') NOT IN ('leave', 'cancelled')
+        "#,
+    )
+    .bind(class_id)
+    .bind(category)
+    .bind(title)
+    .bind(content)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// (GET /api/v1/customer/notifications) 我的消息
+// 分页; 筛选: category / is_read
+const NOTIFICATION_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "created_at")],
+    default_sort: "-created_at",
+    tiebreak: "id",
+    filters: &[Filter::text("category", "category"), Filter::bool("is_read", "is_read")],
+    search: &[],
+};
+
+pub async fn get_customer_notifications_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    list: ListQuery,
+) -> Result<Json<Page<CustomerNotification>>, AppError> {
+    let customer_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::BadRequest("request.invalid"))?;
+    let hq_id = claims.hq_id;
+
+    let page = list
+        .fetch_page(&state.db_pool, &NOTIFICATION_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT id, category, title, content, related_entity_id, is_read, created_at
+                FROM customer_notifications
+                WHERE customer_id = "#,
+            );
+            qb.push_bind(customer_id).push(" AND hq_id = ").push_bind(hq_id);
+        })
+        .await?;
+
+    Ok(Json(page))
+}
+
+// (PATCH /api/v1/customer/notifications/:id/read) 标记已读
+pub async fn mark_customer_notification_read_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(notification_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let customer_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::BadRequest("request.invalid"))?;
+
+    let result = sqlx::query(
+        "UPDATE customer_notifications SET is_read = true WHERE id = $1 AND customer_id = $2",
+    )
+    .bind(notification_id)
+    .bind(customer_id)
+    .execute(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to mark notification read"))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("resource.not_found"));
+    }
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,945 @@
+/*
+ * src/handlers/online_payment.rs
+ * 职责: 在线支付 (支付渠道通过 crate::payment::PaymentProvider, 目前为微信支付小程序 JSAPI)
+ * 1. 小程序下单: 为自己的订单付清未付金额, 或直接购买会员卡 (先建一张卡种订单再下单)
+ *    同一订单再次下单时关闭之前未支付的交易 (已关闭的交易若仍被支付, 回调照常入账)
+ * 2. 支付结果: 微信回调 (验签解密) / 小程序查询时主动查单 / 对账补单, 三个入口共用 settle_transaction,
+ *    幂等地生成已审核的收款流水 (channel = wechat_pay), 之后记账、核销分期、付清交付与人工审核一致;
+ *    订单已取消或交付失败 (如库存不足) 时流水保持待审核, 由财务处理
+ * 3. 每日对账: 下载前一天 (北京时间) 的交易账单逐笔比对本地交易; 漏单 (已付款未入账) 自动补单,
+ *    其余差异 (账单缺失 / 金额不符 / 非本系统交易) 记入明细; 同时关闭过期未支付的交易,
+ *    作废小程序购卡产生的未付款订单
+ * 4. 模拟付款 (仅 WECHAT_PAY_PROVIDER=simulator): 生成签名回调并走回调同一处理流程
+ */
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, FromRow, PgPool};
+use uuid::Uuid;
+
+use super::{new_order_no, verify_payment_record, AppState};
+use crate::error::AppError;
+use crate::models::Claims;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+use crate::payment::{NotificationHeaders, PrepayRequest, TradeResult, TRADE_SUCCESS};
+use crate::tenant::{Owned, TenantScope};
+use crate::timezone::business_date;
+
+const PAYMENT_EXPIRE_MINUTES: i64 = 30;
+const DEFAULT_JOB_INTERVAL_SECS: u64 = 3600; // 账单次日 10 点后才能下载, 每小时检查一次前一天是否已对账
+
+// ---------------------------------------------------------
+// 数据结构
+// ---------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct PurchaseMembershipPayload {
+    pub tier_id: Uuid,
+    pub base_id: Option<Uuid>, // 不传则用家长所属基地
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PaymentTransaction {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub base_id: Uuid,
+    pub customer_id: Uuid,
+    pub purpose: String,
+    pub out_trade_no: String,
+    pub description: String,
+    pub amount_cents: i32,
+    pub status: String, // pending / paid / closed
+    pub provider_transaction_id: Option<String>,
+    pub payment_record_id: Option<Uuid>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub notify_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatePaymentResponse {
+    pub transaction: PaymentTransaction,
+    pub pay_params: serde_json::Value, // 直接传给 wx.requestPayment
+}
+
+const TRANSACTION_SELECT: &str = r#"
+    SELECT id, order_id, base_id, customer_id, purpose, out_trade_no, description, amount_cents, status,
+           provider_transaction_id, payment_record_id, paid_at, expires_at, notify_count, created_at
+    FROM payment_transactions
+"#;
+
+async fn fetch_transaction(pool: &PgPool, id: Uuid) -> Result<PaymentTransaction, AppError> {
+    sqlx::query_as::<_, PaymentTransaction>(&format!("{} WHERE id = $1", TRANSACTION_SELECT))
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .map_err(AppError::db("Failed to fetch payment transaction"))?
+        .ok_or(AppError::NotFound("resource.not_found"))
+}
+
+// 家长只能看自己的交易
+async fn fetch_customer_transaction(pool: &PgPool, claims: &Claims, id: Uuid) -> Result<PaymentTransaction, AppError> {
+    let customer_id = customer_of(claims)?;
+    let txn = fetch_transaction(pool, id).await?;
+    if txn.customer_id != customer_id {
+        return Err(AppError::NotFound("resource.not_found"));
+    }
+    Ok(txn)
+}
+
+fn customer_of(claims: &Claims) -> Result<Uuid, AppError> {
+    Uuid::parse_str(&claims.sub).map_err(|_| AppError::BadRequest("request.invalid"))
+}
+
+// ---------------------------------------------------------
+// 下单
+// ---------------------------------------------------------
+
+// 本小程序下的 openid (JSAPI 支付必须)
+async fn payer_openid(state: &AppState, customer_id: Uuid) -> Result<String, AppError> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT openid FROM customer_wechat_accounts WHERE customer_id = $1 AND app_id = $2 ORDER BY created_at DESC LIMIT 1",
+    )
+    .bind(customer_id)
+    .bind(state.wechat.app_id())
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to load openid"))?
+    .ok_or(AppError::Custom(StatusCode::PRECONDITION_FAILED, "customer.wechat_required"))
+}
+
+// 为订单的未付金额下单: 锁订单建交易 (关闭旧的待支付交易), 提交后再调用支付平台, 下单失败时关闭交易
+async fn start_payment(
+    state: &AppState,
+    customer_id: Uuid,
+    order_id: Uuid,
+    purpose: &str,
+    description: &str,
+) -> Result<CreatePaymentResponse, AppError> {
+    let openid = payer_openid(state, customer_id).await?;
+
+    let mut tx = state.db_pool.begin().await.map_err(AppError::db("Begin tx failed"))?;
+    let order = sqlx::query!(
+        r#"
+        SELECT hq_id, base_id, customer_id, status::TEXT AS "status!", approval_status,
+               total_amount_cents, paid_amount_cents
+        FROM orders WHERE id = $1 FOR UPDATE
+        "#,
+        order_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .filter(|o| o.customer_id == Some(customer_id))
+    .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    let amount_cents = order.total_amount_cents - order.paid_amount_cents;
+    let payable = matches!(order.status.as_str(), "pending" | "partial_paid")
+        && order.approval_status.as_deref().unwrap_or("approved") == "approved"
+        && amount_cents > 0;
+    if !payable {
+        return Err(AppError::Conflict("payment.order_not_payable"));
+    }
+
+    sqlx::query!(
+        "UPDATE payment_transactions SET status = 'closed', updated_at = NOW() WHERE order_id = $1 AND status = 'pending'",
+        order_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let id = Uuid::new_v4();
+    let out_trade_no = id.simple().to_string();
+    let description: String = description.chars().take(40).collect();
+    let expires_at = Utc::now() + Duration::minutes(PAYMENT_EXPIRE_MINUTES);
+    sqlx::query!(
+        r#"
+        INSERT INTO payment_transactions
+            (id, hq_id, base_id, order_id, customer_id, provider, purpose, out_trade_no, description, amount_cents, payer_openid, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        "#,
+        id,
+        order.hq_id,
+        order.base_id,
+        order_id,
+        customer_id,
+        state.payment.name(),
+        purpose,
+        out_trade_no,
+        description,
+        amount_cents,
+        openid,
+        expires_at
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to create payment transaction"))?;
+    tx.commit().await.map_err(AppError::db("Commit tx failed"))?;
+
+    let prepay = state
+        .payment
+        .create_prepay(&PrepayRequest { out_trade_no, description, amount_cents, payer_openid: openid, expires_at })
+        .await;
+    let prepay = match prepay {
+        Ok(prepay) => prepay,
+        Err(e) => {
+            sqlx::query!("UPDATE payment_transactions SET status = 'closed', updated_at = NOW() WHERE id = $1", id)
+                .execute(&state.db_pool)
+                .await?;
+            return Err(e.into());
+        }
+    };
+    sqlx::query!("UPDATE payment_transactions SET prepay_id = $2, updated_at = NOW() WHERE id = $1", id, prepay.prepay_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    Ok(CreatePaymentResponse { transaction: fetch_transaction(&state.db_pool, id).await?, pay_params: prepay.pay_params })
+}
+
+// (POST /api/v1/customer/orders/:id/pay) 小程序支付自己的订单 (付清未付金额)
+pub async fn create_order_payment_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(order_id): Path<Uuid>,
+) -> Result<(StatusCode, Json<CreatePaymentResponse>), AppError> {
+    let customer_id = customer_of(&claims)?;
+    let order_no = sqlx::query_scalar!("SELECT order_no FROM orders WHERE id = $1", order_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    let resp = start_payment(&state, customer_id, order_id, "order", &format!("订单 {}", order_no)).await?;
+    Ok((StatusCode::CREATED, Json(resp)))
+}
+
+// (POST /api/v1/customer/memberships/purchase) 小程序购买会员卡: 建一张卡种订单后下单, 付清后自动开卡
+pub async fn purchase_membership_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<PurchaseMembershipPayload>,
+) -> Result<(StatusCode, Json<CreatePaymentResponse>), AppError> {
+    let customer_id = customer_of(&claims)?;
+
+    let tier = sqlx::query!(
+        "SELECT name_key, price_in_cents FROM membership_tiers WHERE id = $1 AND hq_id = $2 AND is_active = true AND price_in_cents > 0",
+        payload.tier_id,
+        claims.hq_id
+    )
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or(AppError::NotFound("payment.tier_unavailable"))?;
+
+    let customer = sqlx::query!("SELECT name, base_id, assigned_sales FROM customers WHERE id = $1", customer_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or(AppError::NotFound("resource.not_found"))?;
+    let base_id = payload.base_id.or(customer.base_id).ok_or(AppError::BadRequest("payment.base_required"))?;
+    let base_code = sqlx::query_scalar!("SELECT code FROM bases WHERE id = $1 AND hq_id = $2", base_id, claims.hq_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or(AppError::BadRequest("payment.base_required"))?
+        .unwrap_or_else(|| "XXX".to_string());
+
+    // 订单号与后台建单同一生成规则
+    let order_no = new_order_no("RET", &base_code);
+
+    let mut tx = state.db_pool.begin().await.map_err(AppError::db("Begin tx failed"))?;
+    let order_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO orders (hq_id, base_id, order_no, type, total_amount_cents, customer_id, contact_name, event_date, sales_id, status, invoice_status)
+        VALUES ($1, $2, $3, 'b2c', $4, $5, $6, CURRENT_DATE, $7, 'pending', 'unbilled')
+        RETURNING id
+        "#,
+        claims.hq_id,
+        base_id,
+        order_no,
+        tier.price_in_cents,
+        customer_id,
+        customer.name,
+        customer.assigned_sales
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Insert order failed"))?;
+    sqlx::query!(
+        r#"
+        INSERT INTO order_items (order_id, name, quantity, unit_price_cents, total_price_cents, tier_id)
+        VALUES ($1, $2, 1, $3, $3, $4)
+        "#,
+        order_id,
+        tier.name_key,
+        tier.price_in_cents,
+        payload.tier_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::db("Insert item failed"))?;
+    tx.commit().await.map_err(AppError::db("Commit tx failed"))?;
+
+    let resp = start_payment(&state, customer_id, order_id, "membership", &tier.name_key).await?;
+    Ok((StatusCode::CREATED, Json(resp)))
+}
+
+// ---------------------------------------------------------
+// 支付结果
+// ---------------------------------------------------------
+
+// 支付成功入账 (幂等): 交易置为已支付并生成收款流水, 按人工审核同一流程审核入账;
+// 审核失败 (订单已取消 / 交付失败) 时流水保持待审核, 钱已收到, 不能让回调失败
+// 返回 false 表示此前已入账 (重复回调)
+pub async fn settle_transaction(pool: &PgPool, transaction_id: Uuid, trade: &TradeResult, channel: &str) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await.map_err(AppError::db("Begin tx failed"))?;
+    let txn = sqlx::query!(
+        r#"
+        SELECT t.hq_id, t.base_id, t.order_id, t.out_trade_no, t.amount_cents, t.status, c.name AS "payer_name?"
+        FROM payment_transactions t
+        JOIN customers c ON t.customer_id = c.id
+        WHERE t.id = $1 FOR UPDATE OF t
+        "#,
+        transaction_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to lock payment transaction"))?;
+    if txn.status == "paid" {
+        return Ok(false);
+    }
+    if trade.amount_cents != txn.amount_cents {
+        tracing::error!(
+            "Payment {} amount mismatch: provider {} cents, local {} cents",
+            txn.out_trade_no,
+            trade.amount_cents,
+            txn.amount_cents
+        );
+        return Err(AppError::Conflict("payment.amount_mismatch"));
+    }
+
+    let record_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO finance_payment_records
+            (hq_id, base_id, order_id, transaction_type, channel, amount_cents, payer_name, channel_transaction_id, status, created_at)
+        VALUES ($1, $2, $3, 'INCOME', $4, $5, $6, $7, 'PENDING', NOW())
+        RETURNING id
+        "#,
+        txn.hq_id,
+        txn.base_id,
+        txn.order_id,
+        channel,
+        txn.amount_cents,
+        txn.payer_name,
+        trade.transaction_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to create payment record"))?;
+
+    sqlx::query!(
+        r#"
+        UPDATE payment_transactions
+        SET status = 'paid', provider_transaction_id = $2, paid_at = COALESCE($3, NOW()), payment_record_id = $4, updated_at = NOW()
+        WHERE id = $1
+        "#,
+        transaction_id,
+        trade.transaction_id,
+        trade.success_time,
+        record_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to update payment transaction"))?;
+
+    let order_status = sqlx::query_scalar!(r#"SELECT status::TEXT AS "status!" FROM orders WHERE id = $1"#, txn.order_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if order_status == "cancelled" {
+        tracing::warn!("Online payment {} received for cancelled order {}, left for manual review", txn.out_trade_no, txn.order_id);
+    } else {
+        // 审核放在保存点里, 失败只回滚审核部分
+        let mut savepoint = tx.begin().await.map_err(AppError::db("Begin savepoint failed"))?;
+        match verify_payment_record(&mut savepoint, record_id, None).await {
+            Ok(()) => savepoint.commit().await.map_err(AppError::db("Commit savepoint failed"))?,
+            Err(e) => {
+                tracing::error!("Online payment {} could not be verified automatically: {:?}", txn.out_trade_no, e);
+                savepoint.rollback().await.map_err(AppError::db("Rollback savepoint failed"))?;
+            }
+        }
+    }
+
+    tx.commit().await.map_err(AppError::db("Commit tx failed"))?;
+    tracing::info!("Online payment {} settled: {} cents for order {}", txn.out_trade_no, txn.amount_cents, txn.order_id);
+    Ok(true)
+}
+
+// 回调处理 (微信回调与模拟付款共用): 验签解密 -> 记回调次数 -> 支付成功则入账
+// 不是本系统发起的交易只记日志, 正常应答以免微信重发
+async fn handle_notification(state: &AppState, headers: &NotificationHeaders, body: &str) -> Result<(), AppError> {
+    let Some(trade) = state.payment.parse_notification(headers, body)? else {
+        return Ok(());
+    };
+    let transaction_id = sqlx::query_scalar!(
+        "UPDATE payment_transactions SET notify_count = notify_count + 1 WHERE out_trade_no = $1 RETURNING id",
+        trade.out_trade_no
+    )
+    .fetch_optional(&state.db_pool)
+    .await?;
+    let Some(transaction_id) = transaction_id else {
+        tracing::warn!("Payment notification for unknown out_trade_no {}", trade.out_trade_no);
+        return Ok(());
+    };
+    if trade.is_success() {
+        settle_transaction(&state.db_pool, transaction_id, &trade, state.payment.name()).await?;
+    }
+    Ok(())
+}
+
+// (POST /api/v1/payments/wechat/notify) 微信支付结果回调, 无登录态, 靠签名鉴别
+// 成功返回 204; 验签失败 401; 处理失败 5xx, 微信会按退避策略重发
+pub async fn wechat_pay_notify_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<StatusCode, AppError> {
+    let headers = NotificationHeaders::from_headers(&headers).ok_or(AppError::Unauthorized("payment.invalid_signature"))?;
+    handle_notification(&state, &headers, &body).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// 主动查单: 待支付 / 已关闭的交易在支付平台已成功时补入账; 返回是否新入账
+async fn sync_transaction(state: &AppState, txn: &PaymentTransaction) -> Result<bool, AppError> {
+    if txn.status == "paid" {
+        return Ok(false);
+    }
+    let trade = state.payment.query_trade(&txn.out_trade_no).await?;
+    if !trade.is_success() {
+        return Ok(false);
+    }
+    settle_transaction(&state.db_pool, txn.id, &trade, state.payment.name()).await
+}
+
+// (GET /api/v1/customer/payments/:id) 小程序支付完成后轮询; 仍待支付时主动查单, 回调延迟也能及时到账
+pub async fn get_customer_payment_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<Uuid>,
+) -> Result<Json<PaymentTransaction>, AppError> {
+    let txn = fetch_customer_transaction(&state.db_pool, &claims, id).await?;
+    if txn.status != "pending" {
+        return Ok(Json(txn));
+    }
+    match sync_transaction(&state, &txn).await {
+        Ok(true) => Ok(Json(fetch_transaction(&state.db_pool, id).await?)),
+        Ok(false) => Ok(Json(txn)),
+        Err(e) => {
+            tracing::warn!("Failed to query payment {}: {:?}", txn.out_trade_no, e);
+            Ok(Json(txn))
+        }
+    }
+}
+
+// (POST /api/v1/customer/payments/:id/simulate) 模拟器: 模拟家长完成付款并投递签名回调
+pub async fn simulate_customer_payment_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(id): Path<Uuid>,
+) -> Result<Json<PaymentTransaction>, AppError> {
+    let simulator = state.payment.simulator().ok_or(AppError::NotFound("payment.simulator_disabled"))?;
+    let txn = fetch_customer_transaction(&state.db_pool, &claims, id).await?;
+    let (headers, body) = simulator.pay(&txn.out_trade_no)?;
+    handle_notification(&state, &headers, &body).await?;
+    Ok(Json(fetch_transaction(&state.db_pool, id).await?))
+}
+
+// ---------------------------------------------------------
+// 后台: 交易列表 / 手动查单
+// ---------------------------------------------------------
+
+// GET /api/v1/finance/online-payments
+// 分页; 筛选: status / purpose / order_id / base_id / created_from / created_to; q 搜商户订单号、微信支付单号、订单号和客户名
+const ONLINE_PAYMENT_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "t.created_at"), ("paid_at", "t.paid_at"), ("amount", "t.amount_cents")],
+    default_sort: "-created_at",
+    tiebreak: "t.id",
+    filters: &[
+        Filter::text("status", "t.status"),
+        Filter::text("purpose", "t.purpose"),
+        Filter::uuid("order_id", "t.order_id"),
+        Filter::uuid("base_id", "t.base_id"),
+        Filter::date_from("created_from", "t.created_at"),
+        Filter::date_to("created_to", "t.created_at"),
+    ],
+    search: &["t.out_trade_no", "t.provider_transaction_id", "o.order_no", "c.name"],
+};
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OnlinePaymentItem {
+    pub id: Uuid,
+    pub order_id: Uuid,
+    pub order_no: String,
+    pub base_id: Uuid,
+    pub customer_id: Uuid,
+    pub customer_name: Option<String>,
+    pub purpose: String,
+    pub out_trade_no: String,
+    pub description: String,
+    pub amount_cents: i32,
+    pub status: String,
+    pub provider_transaction_id: Option<String>,
+    pub payment_record_id: Option<Uuid>,
+    pub payment_status: Option<String>, // 收款流水状态, PENDING 表示需人工审核
+    pub paid_at: Option<DateTime<Utc>>,
+    pub notify_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn get_online_payments_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    list: ListQuery,
+) -> Result<Json<Page<OnlinePaymentItem>>, AppError> {
+    let page = list
+        .fetch_page(&state.db_pool, &ONLINE_PAYMENT_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT t.id, t.order_id, o.order_no, t.base_id, t.customer_id, c.name AS customer_name,
+                       t.purpose, t.out_trade_no, t.description, t.amount_cents, t.status, t.provider_transaction_id,
+                       t.payment_record_id, r.status AS payment_status, t.paid_at, t.notify_count, t.created_at
+                FROM payment_transactions t
+                JOIN orders o ON t.order_id = o.id
+                LEFT JOIN customers c ON t.customer_id = c.id
+                LEFT JOIN finance_payment_records r ON t.payment_record_id = r.id
+                WHERE 1 = 1
+                "#,
+            );
+            scope.push_filter(qb, "t");
+        })
+        .await?;
+    Ok(Json(page))
+}
+
+// (POST /api/v1/finance/online-payments/:id/sync) 手动查单补入账 (家长已付款但回调未到)
+pub async fn sync_online_payment_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(id): Path<Uuid>,
+) -> Result<Json<PaymentTransaction>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::OnlinePayment(id)).await?;
+    let txn = fetch_transaction(&state.db_pool, id).await?;
+    sync_transaction(&state, &txn).await?;
+    Ok(Json(fetch_transaction(&state.db_pool, id).await?))
+}
+
+// ---------------------------------------------------------
+// 每日对账
+// ---------------------------------------------------------
+
+#[derive(Debug, FromRow)]
+struct LocalTrade {
+    id: Uuid,
+    hq_id: Uuid,
+    base_id: Uuid,
+    out_trade_no: String,
+    provider_transaction_id: Option<String>,
+    amount_cents: i32,
+    status: String,
+    paid_on: Option<NaiveDate>,
+}
+
+struct ItemDraft {
+    hq_id: Option<Uuid>,
+    base_id: Option<Uuid>,
+    transaction_id: Option<Uuid>,
+    out_trade_no: String,
+    provider_transaction_id: Option<String>,
+    kind: &'static str,
+    provider_amount_cents: Option<i32>,
+    local_amount_cents: Option<i32>,
+    resolved: bool,
+    note: Option<String>,
+}
+
+impl ItemDraft {
+    fn new(kind: &'static str, out_trade_no: &str, local: Option<&LocalTrade>) -> Self {
+        Self {
+            hq_id: local.map(|l| l.hq_id),
+            base_id: local.map(|l| l.base_id),
+            transaction_id: local.map(|l| l.id),
+            out_trade_no: out_trade_no.to_string(),
+            provider_transaction_id: local.and_then(|l| l.provider_transaction_id.clone()),
+            kind,
+            provider_amount_cents: None,
+            local_amount_cents: local.map(|l| l.amount_cents),
+            resolved: false,
+            note: None,
+        }
+    }
+}
+
+const LOCAL_TRADE_SELECT: &str = r#"
+    SELECT id, hq_id, base_id, out_trade_no, provider_transaction_id, amount_cents, status,
+           (paid_at AT TIME ZONE 'Asia/Shanghai')::date AS paid_on
+    FROM payment_transactions
+"#;
+
+async fn save_reconciliation(
+    pool: &PgPool,
+    provider: &str,
+    date: NaiveDate,
+    provider_totals: Option<(i32, i64)>,
+    error: Option<String>,
+    items: &[ItemDraft],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await.map_err(AppError::db("Begin tx failed"))?;
+    let (local_count, local_amount) = sqlx::query_as::<_, (i64, i64)>(
+        r#"
+        SELECT COUNT(*), COALESCE(SUM(amount_cents), 0)::bigint FROM payment_transactions
+        WHERE provider = $1 AND status = 'paid' AND (paid_at AT TIME ZONE 'Asia/Shanghai')::date = $2
+        "#,
+    )
+    .bind(provider)
+    .bind(date)
+    .fetch_one(&mut *tx)
+    .await?;
+    let (provider_count, provider_amount) = provider_totals.unwrap_or((0, 0));
+    let reconciliation_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO payment_reconciliations
+            (provider, bill_date, status, provider_count, provider_amount_cents, local_count, local_amount_cents, discrepancy_count, error, run_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW())
+        ON CONFLICT (provider, bill_date) DO UPDATE SET
+            status = EXCLUDED.status, provider_count = EXCLUDED.provider_count, provider_amount_cents = EXCLUDED.provider_amount_cents,
+            local_count = EXCLUDED.local_count, local_amount_cents = EXCLUDED.local_amount_cents,
+            discrepancy_count = EXCLUDED.discrepancy_count, error = EXCLUDED.error, run_at = NOW()
+        RETURNING id
+        "#,
+        provider,
+        date,
+        if error.is_some() { "failed" } else { "completed" },
+        provider_count,
+        provider_amount,
+        local_count as i32,
+        local_amount,
+        items.len() as i32,
+        error
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query!("DELETE FROM payment_reconciliation_items WHERE reconciliation_id = $1", reconciliation_id)
+        .execute(&mut *tx)
+        .await?;
+    for item in items {
+        sqlx::query!(
+            r#"
+            INSERT INTO payment_reconciliation_items
+                (reconciliation_id, hq_id, base_id, transaction_id, out_trade_no, provider_transaction_id, kind,
+                 provider_amount_cents, local_amount_cents, resolved, note)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            reconciliation_id,
+            item.hq_id,
+            item.base_id,
+            item.transaction_id,
+            item.out_trade_no,
+            item.provider_transaction_id,
+            item.kind,
+            item.provider_amount_cents,
+            item.local_amount_cents,
+            item.resolved,
+            item.note
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await.map_err(AppError::db("Commit tx failed"))?;
+    Ok(())
+}
+
+// 对账某天 (北京时间) 的交易, 重跑覆盖之前的结果; 账单下载失败时记为 failed 并返回错误
+pub async fn reconcile_payments(state: &AppState, date: NaiveDate) -> Result<(), AppError> {
+    let provider = state.payment.name();
+    let entries = match state.payment.trade_statement(date).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            save_reconciliation(&state.db_pool, provider, date, None, Some(e.to_string()), &[]).await?;
+            return Err(e.into());
+        }
+    };
+    let provider_totals = (entries.len() as i32, entries.iter().map(|e| e.amount_cents as i64).sum::<i64>());
+
+    // 账单里的交易按商户订单号找本地交易 (不限状态和日期); 本地当天已支付的逐笔勾销
+    let out_trade_nos: Vec<String> = entries.iter().map(|e| e.out_trade_no.clone()).collect();
+    let mut known: HashMap<String, LocalTrade> = sqlx::query_as::<_, LocalTrade>(&format!(
+        "{} WHERE provider = $1 AND (out_trade_no = ANY($2) OR (status = 'paid' AND (paid_at AT TIME ZONE 'Asia/Shanghai')::date = $3))",
+        LOCAL_TRADE_SELECT
+    ))
+    .bind(provider)
+    .bind(&out_trade_nos)
+    .bind(date)
+    .fetch_all(&state.db_pool)
+    .await?
+    .into_iter()
+    .map(|t| (t.out_trade_no.clone(), t))
+    .collect();
+
+    let mut items = Vec::new();
+    for entry in &entries {
+        let Some(local) = known.remove(&entry.out_trade_no) else {
+            let mut item = ItemDraft::new("unknown_trade", &entry.out_trade_no, None);
+            item.provider_transaction_id = Some(entry.transaction_id.clone());
+            item.provider_amount_cents = Some(entry.amount_cents);
+            items.push(item);
+            continue;
+        };
+        if local.amount_cents != entry.amount_cents {
+            let mut item = ItemDraft::new("amount_mismatch", &entry.out_trade_no, Some(&local));
+            item.provider_transaction_id = Some(entry.transaction_id.clone());
+            item.provider_amount_cents = Some(entry.amount_cents);
+            items.push(item);
+            continue;
+        }
+        if local.status == "paid" {
+            continue;
+        }
+
+        // 漏单: 支付平台已成功, 本地未入账 (回调丢失), 按账单补单
+        let mut item = ItemDraft::new("missing_local", &entry.out_trade_no, Some(&local));
+        item.provider_transaction_id = Some(entry.transaction_id.clone());
+        item.provider_amount_cents = Some(entry.amount_cents);
+        let trade = TradeResult {
+            out_trade_no: entry.out_trade_no.clone(),
+            transaction_id: Some(entry.transaction_id.clone()),
+            trade_state: TRADE_SUCCESS.to_string(),
+            amount_cents: entry.amount_cents,
+            success_time: Some(entry.trade_time),
+        };
+        match settle_transaction(&state.db_pool, local.id, &trade, provider).await {
+            Ok(_) => {
+                item.resolved = true;
+                item.note = Some("已按账单补单".to_string());
+            }
+            Err(e) => item.note = Some(format!("补单失败: {:?}", e)),
+        }
+        items.push(item);
+    }
+
+    // 本地当天已支付但账单里没有
+    for local in known.into_values().filter(|l| l.status == "paid" && l.paid_on == Some(date)) {
+        items.push(ItemDraft::new("missing_provider", &local.out_trade_no.clone(), Some(&local)));
+    }
+
+    save_reconciliation(&state.db_pool, provider, date, Some(provider_totals), None, &items).await?;
+    let unresolved = items.iter().filter(|i| !i.resolved).count();
+    if unresolved > 0 {
+        tracing::warn!("Payment reconciliation {}: {} unresolved discrepancies", date, unresolved);
+    }
+    Ok(())
+}
+
+// 关闭过期未支付的交易; 小程序购卡建的订单没收到任何款项且已无待支付交易时作废
+pub async fn close_expired_payments(pool: &PgPool) -> Result<(u64, u64), AppError> {
+    let closed = sqlx::query!(
+        "UPDATE payment_transactions SET status = 'closed', updated_at = NOW() WHERE status = 'pending' AND expires_at < NOW()"
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    let cancelled = sqlx::query!(
+        r#"
+        UPDATE orders o SET status = 'cancelled', updated_at = NOW()
+        WHERE o.status = 'pending' AND o.paid_amount_cents = 0
+          AND EXISTS (SELECT 1 FROM payment_transactions t WHERE t.order_id = o.id AND t.purpose = 'membership')
+          AND NOT EXISTS (SELECT 1 FROM payment_transactions t WHERE t.order_id = o.id AND t.status <> 'closed')
+          AND NOT EXISTS (SELECT 1 FROM finance_payment_records r WHERE r.order_id = o.id)
+        "#
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok((closed, cancelled))
+}
+
+// 启动后台对账任务 (间隔可用 PAYMENT_RECONCILIATION_INTERVAL_SECS 配置):
+// 每次关闭过期交易, 前一天还没有成功对账时对账 (账单未出时记为 failed, 下次重试)
+pub fn spawn_payment_reconciliation_job(state: AppState) {
+    let interval_secs = std::env::var("PAYMENT_RECONCILIATION_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_JOB_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            match close_expired_payments(&state.db_pool).await {
+                Ok((closed, cancelled)) if closed + cancelled > 0 => {
+                    tracing::info!("Online payments: {} expired transactions closed, {} orders cancelled", closed, cancelled)
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Closing expired payments failed: {:?}", e),
+            }
+
+            let yesterday = business_date(Utc::now()) - Duration::days(1);
+            let done = sqlx::query_scalar!(
+                "SELECT EXISTS (SELECT 1 FROM payment_reconciliations WHERE provider = $1 AND bill_date = $2 AND status = 'completed') AS \"done!\"",
+                state.payment.name(),
+                yesterday
+            )
+            .fetch_one(&state.db_pool)
+            .await
+            .unwrap_or(false);
+            if !done {
+                if let Err(e) = reconcile_payments(&state, yesterday).await {
+                    tracing::warn!("Payment reconciliation for {} failed: {:?}", yesterday, e);
+                }
+            }
+        }
+    });
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentReconciliationQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RunPaymentReconciliationPayload {
+    pub date: NaiveDate,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PaymentReconciliationItem {
+    pub id: Uuid,
+    pub base_id: Option<Uuid>,
+    pub transaction_id: Option<Uuid>,
+    pub order_id: Option<Uuid>,
+    pub out_trade_no: String,
+    pub provider_transaction_id: Option<String>,
+    pub kind: String,
+    pub provider_amount_cents: Option<i32>,
+    pub local_amount_cents: Option<i32>,
+    pub resolved: bool,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PaymentReconciliationDay {
+    pub bill_date: NaiveDate,
+    pub status: String,
+    pub error: Option<String>,
+    pub run_at: DateTime<Utc>,
+    pub paid_count: i64,        // 本租户当天已支付笔数 / 金额
+    pub paid_amount_cents: i64,
+    pub discrepancy_count: i64, // 本租户的差异 (含已补单)
+    pub unresolved_count: i64,
+    #[sqlx(skip)]
+    pub items: Vec<PaymentReconciliationItem>,
+}
+
+// 对账批次按租户展示: 账单是整个商户号的, 笔数金额和差异只统计本租户 (基地账号只看本基地)
+async fn reconciliation_days(
+    pool: &PgPool,
+    scope: &TenantScope,
+    provider: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<PaymentReconciliationDay>, AppError> {
+    let days = sqlx::query_as::<_, PaymentReconciliationDay>(
+        r#"
+        SELECT r.bill_date, r.status, r.error, r.run_at,
+               (SELECT COUNT(*) FROM payment_transactions t
+                 WHERE t.provider = r.provider AND t.status = 'paid' AND t.hq_id = $2 AND ($3::uuid IS NULL OR t.base_id = $3)
+                   AND (t.paid_at AT TIME ZONE 'Asia/Shanghai')::date = r.bill_date) AS paid_count,
+               (SELECT COALESCE(SUM(t.amount_cents), 0)::bigint FROM payment_transactions t
+                 WHERE t.provider = r.provider AND t.status = 'paid' AND t.hq_id = $2 AND ($3::uuid IS NULL OR t.base_id = $3)
+                   AND (t.paid_at AT TIME ZONE 'Asia/Shanghai')::date = r.bill_date) AS paid_amount_cents,
+               (SELECT COUNT(*) FROM payment_reconciliation_items i
+                 WHERE i.reconciliation_id = r.id AND i.hq_id = $2 AND ($3::uuid IS NULL OR i.base_id = $3)) AS discrepancy_count,
+               (SELECT COUNT(*) FROM payment_reconciliation_items i
+                 WHERE i.reconciliation_id = r.id AND i.hq_id = $2 AND ($3::uuid IS NULL OR i.base_id = $3)
+                   AND NOT i.resolved) AS unresolved_count
+        FROM payment_reconciliations r
+        WHERE r.provider = $1 AND r.bill_date BETWEEN $4 AND $5
+        ORDER BY r.bill_date DESC
+        "#,
+    )
+    .bind(provider)
+    .bind(scope.hq_id)
+    .bind(scope.base_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await
+    .map_err(AppError::db("Failed to fetch payment reconciliations"))?;
+    Ok(days)
+}
+
+// GET /api/v1/finance/payment-reconciliations?from=&to= (默认最近 30 天)
+pub async fn get_payment_reconciliations_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(query): Query<PaymentReconciliationQuery>,
+) -> Result<Json<Vec<PaymentReconciliationDay>>, AppError> {
+    let to = query.to.unwrap_or_else(|| business_date(Utc::now()));
+    let from = query.from.unwrap_or(to - Duration::days(30));
+    if from > to {
+        return Err(AppError::BadRequest("ledger.range_invalid"));
+    }
+    Ok(Json(reconciliation_days(&state.db_pool, &scope, state.payment.name(), from, to).await?))
+}
+
+async fn reconciliation_detail(state: &AppState, scope: &TenantScope, date: NaiveDate) -> Result<PaymentReconciliationDay, AppError> {
+    let mut day = reconciliation_days(&state.db_pool, scope, state.payment.name(), date, date)
+        .await?
+        .pop()
+        .ok_or(AppError::NotFound("resource.not_found"))?;
+    day.items = sqlx::query_as::<_, PaymentReconciliationItem>(
+        r#"
+        SELECT i.id, i.base_id, i.transaction_id, t.order_id, i.out_trade_no, i.provider_transaction_id, i.kind,
+               i.provider_amount_cents, i.local_amount_cents, i.resolved, i.note
+        FROM payment_reconciliation_items i
+        JOIN payment_reconciliations r ON i.reconciliation_id = r.id
+        LEFT JOIN payment_transactions t ON i.transaction_id = t.id
+        WHERE r.provider = $1 AND r.bill_date = $2 AND i.hq_id = $3 AND ($4::uuid IS NULL OR i.base_id = $4)
+        ORDER BY i.resolved, i.kind, i.out_trade_no
+        "#,
+    )
+    .bind(state.payment.name())
+    .bind(date)
+    .bind(scope.hq_id)
+    .bind(scope.base_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to fetch reconciliation items"))?;
+    Ok(day)
+}
+
+// GET /api/v1/finance/payment-reconciliations/:date
+pub async fn get_payment_reconciliation_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(date): Path<NaiveDate>,
+) -> Result<Json<PaymentReconciliationDay>, AppError> {
+    Ok(Json(reconciliation_detail(&state, &scope, date).await?))
+}
+
+// (POST /api/v1/finance/payment-reconciliations/run) 手动重跑某天的对账 (账单是整个商户号的, 结果对所有租户生效)
+pub async fn run_payment_reconciliation_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Json(payload): Json<RunPaymentReconciliationPayload>,
+) -> Result<Json<PaymentReconciliationDay>, AppError> {
+    if payload.date >= business_date(Utc::now()) {
+        return Err(AppError::BadRequest("payment.bill_not_ready"));
+    }
+    reconcile_payments(&state, payload.date).await?;
+    Ok(Json(reconciliation_detail(&state, &scope, payload.date).await?))
+}
@@ -0,0 +1,343 @@
+/*
+ * src/handlers/order_refund.rs
+ * 职责: 订单退款
+ * 1. 基地对已收款的订单发起退款申请: 金额 (部分 / 全额) + 可选的退回明细, 进入工作台 "退费审批"
+ * 2. 审批通过 (apply_order_refund, 与审批状态在同一事务):
+ *    - 生成退款流水 (finance_payment_records, transaction_type = 'REFUND')
+ *    - 冲减订单已付金额, 全部退完时订单置为 refunded; 分期从最晚一期冲回已收
+ *    - 冲销凭证: 贷 现金/银行存款; 借方先冲退回会员卡的未确认部分 (合同负债), 其余订单已付清的冲收入, 未付清的冲合同负债
+ *    - 退回明细: 会员卡停用, 库存商品退回本基地库存
+ *    - 已开票的订单按退款金额生成红字发票申请 (审批提交后由 workspace 调用开票服务)
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use super::ledger::{funds_account, post_transfer, LedgerTransfer, ACCOUNT_CONTRACT_LIABILITY, ACCOUNT_REVENUE, SOURCE_ORDER_REFUND};
+use super::{create_refund_red_invoices, release_installments, AppState};
+use crate::error::AppError;
+use crate::models::Claims;
+use crate::tenant::{Owned, TenantScope};
+
+#[derive(Debug, Deserialize)]
+pub struct RefundItemPayload {
+    pub order_item_id: Uuid,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrderRefundPayload {
+    pub amount: f64, // 元
+    pub reason: String,
+    pub channel: String, // 退款去向: cash / bank / wechat ...
+    pub participant_id: Option<Uuid>,
+    #[serde(default)]
+    pub items: Vec<RefundItemPayload>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OrderRefund {
+    pub id: Uuid,
+    pub amount_cents: i32,
+    pub reason: String,
+    pub channel: String,
+    pub status: Option<String>,
+    pub rejection_reason: Option<String>,
+    pub payment_record_id: Option<Uuid>,
+    pub created_by_name: Option<String>,
+    pub approved_by_name: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub approved_at: Option<DateTime<Utc>>,
+    pub items: serde_json::Value, // [{ order_item_id, name, quantity }]
+}
+
+// ---------------------------------------------------------
+// 发起 / 查询
+// ---------------------------------------------------------
+
+// (POST /api/v1/finance/orders/:id/refunds) 发起退款申请
+// 可退金额 = 已付 - 审批中的退款; 可退数量 = 数量 - 已退 - 审批中的退回
+pub async fn create_order_refund_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(order_id): Path<Uuid>,
+    Json(payload): Json<CreateOrderRefundPayload>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let amount_cents = (payload.amount * 100.0).round() as i32;
+    if amount_cents <= 0 {
+        return Err(AppError::BadRequest("refund.invalid_amount"));
+    }
+    if payload.reason.trim().is_empty() {
+        return Err(AppError::BadRequest("refund.reason_required"));
+    }
+    if payload.channel.trim().is_empty() || payload.items.iter().any(|i| i.quantity <= 0) {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let scope = TenantScope::from_claims(&claims);
+    let mut tx = state.db_pool.begin().await?;
+    scope.ensure_owned(&mut *tx, Owned::Order(order_id)).await?;
+    if let Some(participant_id) = payload.participant_id {
+        scope.ensure_owned(&mut *tx, Owned::Participant(participant_id)).await?;
+    }
+
+    // 锁订单, 同一订单的退款申请串行校验
+    let (hq_id, base_id, paid_amount_cents, status): (Uuid, Uuid, i32, Option<String>) = sqlx::query_as(
+        "SELECT hq_id, base_id, paid_amount_cents, status::TEXT FROM orders WHERE id = $1 FOR UPDATE",
+    )
+    .bind(order_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if paid_amount_cents <= 0 || matches!(status.as_deref(), Some("cancelled") | Some("refunded")) {
+        return Err(AppError::Conflict("refund.order_not_paid"));
+    }
+    let pending_cents: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(amount_cents), 0)::bigint FROM refund_requests WHERE order_id = $1 AND status = 'pending'",
+    )
+    .bind(order_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if amount_cents as i64 > paid_amount_cents as i64 - pending_cents {
+        return Err(AppError::Conflict("refund.exceeds_paid"));
+    }
+
+    for item in &payload.items {
+        let refundable: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT (oi.quantity - oi.refunded_quantity - COALESCE((
+                SELECT SUM(ri.quantity) FROM refund_request_items ri
+                JOIN refund_requests rr ON ri.refund_id = rr.id
+                WHERE ri.order_item_id = oi.id AND rr.status = 'pending'
+            ), 0))::bigint
+            FROM order_items oi WHERE oi.id = $1 AND oi.order_id = $2
+            "#,
+        )
+        .bind(item.order_item_id)
+        .bind(order_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        match refundable {
+            None => return Err(AppError::BadRequest("refund.invalid_item")),
+            Some(left) if (item.quantity as i64) > left => return Err(AppError::Conflict("refund.quantity_exceeded")),
+            _ => {}
+        }
+    }
+
+    let refund_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO refund_requests (hq_id, base_id, order_id, participant_id, amount_cents, reason, channel, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id
+        "#,
+    )
+    .bind(hq_id)
+    .bind(base_id)
+    .bind(order_id)
+    .bind(payload.participant_id)
+    .bind(amount_cents)
+    .bind(payload.reason.trim())
+    .bind(payload.channel.trim())
+    .bind(Uuid::parse_str(&claims.sub).map_err(|_| AppError::Unauthorized("auth.unauthorized"))?)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to create refund request"))?;
+
+    for item in &payload.items {
+        sqlx::query("INSERT INTO refund_request_items (refund_id, order_item_id, quantity) VALUES ($1, $2, $3)")
+            .bind(refund_id)
+            .bind(item.order_item_id)
+            .bind(item.quantity)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::db("Failed to save refund items"))?;
+    }
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "id": refund_id }))))
+}
+
+// (GET /api/v1/finance/orders/:id/refunds) 订单的退款记录 (含审批中 / 已驳回)
+pub async fn get_order_refunds_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Vec<OrderRefund>>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::Order(order_id)).await?;
+
+    let refunds = sqlx::query_as::<_, OrderRefund>(
+        r#"
+        SELECT rr.id, rr.amount_cents, rr.reason, rr.channel, rr.status, rr.rejection_reason, rr.payment_record_id,
+               cu.full_name AS created_by_name, au.full_name AS approved_by_name, rr.created_at, rr.approved_at,
+               COALESCE((
+                   SELECT jsonb_agg(jsonb_build_object('order_item_id', oi.id, 'name', oi.name, 'quantity', ri.quantity) ORDER BY oi.name)
+                   FROM refund_request_items ri JOIN order_items oi ON ri.order_item_id = oi.id
+                   WHERE ri.refund_id = rr.id
+               ), '[]'::jsonb) AS items
+        FROM refund_requests rr
+        LEFT JOIN users cu ON rr.created_by = cu.id
+        LEFT JOIN users au ON rr.approved_by = au.id
+        WHERE rr.order_id = $1
+        ORDER BY rr.created_at DESC
+        "#,
+    )
+    .bind(order_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to fetch order refunds"))?;
+
+    Ok(Json(refunds))
+}
+
+// ---------------------------------------------------------
+// 审批通过
+// ---------------------------------------------------------
+
+// 退款审批通过后调用, 须在审批的事务内; 申请须已置为 approved
+pub async fn apply_order_refund(conn: &mut PgConnection, refund_id: Uuid, approved_by: Uuid) -> Result<(), AppError> {
+    let (hq_id, base_id, order_id, amount_cents, channel): (Uuid, Uuid, Uuid, i32, String) = sqlx::query_as(
+        "SELECT hq_id, base_id, order_id, amount_cents, channel FROM refund_requests WHERE id = $1",
+    )
+    .bind(refund_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let (order_no, paid_amount_cents, status): (String, i32, Option<String>) = sqlx::query_as(
+        "SELECT order_no, paid_amount_cents, status::TEXT FROM orders WHERE id = $1 FOR UPDATE",
+    )
+    .bind(order_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    // 申请后订单可能已被其他退款退过
+    if amount_cents > paid_amount_cents {
+        return Err(AppError::Conflict("refund.exceeds_paid"));
+    }
+
+    // 1. 退回明细: 累计已退数量 (超出时 CHECK 约束报 refund.quantity_exceeded), 停卡 / 回库存
+    let items: Vec<(i32, i32, Option<Uuid>, Option<Uuid>)> = sqlx::query_as(
+        r#"
+        UPDATE order_items oi SET refunded_quantity = oi.refunded_quantity + ri.quantity
+        FROM refund_request_items ri
+        WHERE ri.refund_id = $1 AND ri.order_item_id = oi.id
+        RETURNING ri.quantity, oi.total_price_cents, oi.product_id, oi.membership_id
+        "#,
+    )
+    .bind(refund_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to update refunded quantity"))?;
+
+    let mut unearned_cents: i64 = 0; // 退回会员卡中尚未确认收入的部分 (仍在合同负债)
+    for (quantity, total_price_cents, product_id, membership_id) in items {
+        if let Some(product_id) = product_id {
+            sqlx::query(
+                r#"
+                INSERT INTO base_inventory (base_id, product_id, quantity) VALUES ($1, $2, $3)
+                ON CONFLICT (base_id, product_id)
+                DO UPDATE SET quantity = base_inventory.quantity + EXCLUDED.quantity, last_updated_at = NOW()
+                "#,
+            )
+            .bind(base_id)
+            .bind(product_id)
+            .bind(quantity)
+            .execute(&mut *conn)
+            .await
+            .map_err(AppError::db("Failed to restock inventory"))?;
+            sqlx::query("INSERT INTO inventory_logs (base_id, product_id, change_amount, reason) VALUES ($1, $2, $3, $4)")
+                .bind(base_id)
+                .bind(product_id)
+                .bind(quantity)
+                .bind(format!("订单退货入库: {}", order_no))
+                .execute(&mut *conn)
+                .await
+                .map_err(AppError::db("Failed to write inventory log"))?;
+        }
+        if let Some(membership_id) = membership_id {
+            let recognized: Option<i32> = sqlx::query_scalar(
+                "UPDATE customer_memberships SET is_active = false, frozen_at = NULL WHERE id = $1 RETURNING revenue_recognized_in_cents",
+            )
+            .bind(membership_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(AppError::db("Failed to deactivate membership"))?;
+            unearned_cents += (total_price_cents - recognized.unwrap_or(0)).max(0) as i64;
+        }
+    }
+
+    // 2. 退款流水 (直接为已核实)
+    let record_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO finance_payment_records
+        (hq_id, base_id, order_id, transaction_type, channel, amount_cents, status, verified_at, verified_by)
+        VALUES ($1, $2, $3, 'REFUND', $4, $5, 'VERIFIED', NOW(), $6)
+        RETURNING id
+        "#,
+    )
+    .bind(hq_id)
+    .bind(base_id)
+    .bind(order_id)
+    .bind(&channel)
+    .bind(amount_cents)
+    .bind(approved_by)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to create refund record"))?;
+    sqlx::query("UPDATE refund_requests SET payment_record_id = $1, updated_at = NOW() WHERE id = $2")
+        .bind(record_id)
+        .bind(refund_id)
+        .execute(&mut *conn)
+        .await?;
+
+    // 3. 冲减订单已付, 全部退完置为 refunded
+    sqlx::query(
+        r#"
+        UPDATE orders SET
+            paid_amount_cents = paid_amount_cents - $1,
+            status = CASE WHEN paid_amount_cents - $1 = 0 THEN 'refunded'::order_status ELSE status END,
+            updated_at = NOW()
+        WHERE id = $2
+        "#,
+    )
+    .bind(amount_cents)
+    .bind(order_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to update order after refund"))?;
+    release_installments(conn, order_id, amount_cents).await?;
+    // 已开票的按退款金额生成红字发票申请 (事务提交后开具)
+    create_refund_red_invoices(conn, refund_id, approved_by).await?;
+
+    // 4. 冲销凭证: 已付清的订单收入已确认, 冲收入; 未付清的仍在合同负债
+    let amount = amount_cents as i64;
+    let (liability_cents, revenue_cents) = if status.as_deref() == Some("paid") {
+        let liability = unearned_cents.min(amount);
+        (liability, amount - liability)
+    } else {
+        (amount, 0)
+    };
+    let description = format!("订单退款: {}", order_no);
+    for (debit, cents) in [(ACCOUNT_CONTRACT_LIABILITY, liability_cents), (ACCOUNT_REVENUE, revenue_cents)] {
+        post_transfer(conn, LedgerTransfer {
+            hq_id,
+            base_id: Some(base_id),
+            entry_date: None,
+            source_type: SOURCE_ORDER_REFUND,
+            source_id: Some(refund_id),
+            description: description.clone(),
+            created_by: Some(approved_by),
+            debit,
+            credit: funds_account(&channel),
+            amount_cents: cents,
+        })
+        .await?;
+    }
+
+    tracing::info!("Refund {} applied to order {}: {} cents", refund_id, order_no, amount_cents);
+    Ok(())
+}
@@ -9,12 +9,16 @@ use axum::{
     http::StatusCode,
     Json,
 };
+use sqlx::{Postgres, QueryBuilder};
 use uuid::Uuid;
 
 // 导入在 mod.rs 中定义的 AppState
 use super::AppState;
 // 导入 models
 use crate::models::{Claims, Participant, CreateParticipantPayload, ParticipantDetail};
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+use crate::tenant::TenantScope;
 
 
 // (POST /api/v1/participants - 创建一个新学员并关联到家长)
@@ -23,32 +27,30 @@ pub async fn create_participant_handler(
     State(state): State<AppState>,
     claims: Claims, // <-- 自动验证 Token, 获取 Claims
     Json(payload): Json<CreateParticipantPayload>,
-) -> Result<Json<Participant>, StatusCode> {
+) -> Result<Json<Participant>, AppError> {
     
     let hq_id = claims.hq_id;
 
     // --- (★ 关键: 启动数据库事务 ★) ---
     let mut tx = match state.db_pool.begin().await {
         Ok(tx) => tx,
-        Err(e) => {
-            tracing::error!("Failed to start transaction: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to start transaction", source: e }),
     };
 
     // --- (★ 事务内: 安全校验 ★) ---
+    // 基地账号只能给本基地的家长添加学员
     let parent_check = sqlx::query(
-        "SELECT id FROM customers WHERE id = $1 AND hq_id = $2"
+        "SELECT id FROM customers WHERE id = $1 AND hq_id = $2 AND ($3::uuid IS NULL OR base_id = $3)"
     )
     .bind(payload.customer_id)
     .bind(hq_id)
+    .bind(claims.base_id)
     .fetch_optional(&mut *tx) // (★ 关键: 使用事务 'tx')
     .await;
 
     if let Err(e) = parent_check {
-        tracing::error!("Failed to check parent customer: {}", e);
         tx.rollback().await.ok(); // (回滚)
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(AppError::Database { context: "Failed to check parent customer", source: e });
     }
     
     if parent_check.unwrap().is_none() {
@@ -58,7 +60,7 @@ pub async fn create_participant_handler(
             payload.customer_id
         );
         tx.rollback().await.ok(); // (回滚)
-        return Err(StatusCode::NOT_FOUND); 
+        return Err(AppError::NotFound("resource.not_found")); 
     }
 
     // --- (★ 事务内: 核心操作 1: 创建 Participant) ---
@@ -82,9 +84,8 @@ pub async fn create_participant_handler(
     {
         Ok(participant) => participant,
         Err(e) => {
-            tracing::error!("Failed to create participant: {}", e);
             tx.rollback().await.ok(); // (回滚)
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(AppError::Database { context: "Failed to create participant", source: e });
         }
     };
 
@@ -103,15 +104,13 @@ pub async fn create_participant_handler(
     .await;
 
     if let Err(e) = profile_result {
-        tracing::error!("Failed to create participant profile: {}", e);
         tx.rollback().await.ok(); // (回滚)
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(AppError::Database { context: "Failed to create participant profile", source: e });
     }
     
     // --- (★ 关键: 提交事务 ★) ---
     if let Err(e) = tx.commit().await {
-        tracing::error!("Failed to commit transaction: {}", e);
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        return Err(AppError::Database { context: "Failed to commit transaction", source: e });
     }
 
     // (事务成功)
@@ -125,7 +124,7 @@ pub async fn get_participants_for_customer_handler(
     State(state): State<AppState>,
     claims: Claims,        // <-- 自动验证 Token, 获取 Claims
     Path(customer_id): Path<Uuid>, // <-- 从 URL 路径中提取 'customer_id'
-) -> Result<Json<Vec<Participant>>, StatusCode> {
+) -> Result<Json<Vec<Participant>>, AppError> {
     
     let hq_id = claims.hq_id;
     
@@ -144,7 +143,7 @@ pub async fn get_participants_for_customer_handler(
         Ok(list) => list,
         Err(e) => {
             tracing::error!("Failed to fetch participants for customer {}: {}", customer_id, e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
         }
     };
 
@@ -152,115 +151,95 @@ pub async fn get_participants_for_customer_handler(
 }
 
 
-// (GET /api/v1/participants)
-// (★ V1 - 无修改 ★)
-pub async fn get_participants_handler(
-    State(state): State<AppState>,
-    claims: Claims, // <-- 自动验证 Token
-) -> Result<Json<Vec<Participant>>, StatusCode> {
-    
-    let participants: Vec<Participant>;
-    let hq_id = claims.hq_id;
-    
-    if let Some(base_id) = claims.base_id {
-        // --- 场景 A: 基地员工 (Base Employee) ---
-        tracing::debug!("Fetching participants for base_id: {}", base_id);
-        
-        participants = sqlx::query_as::<_, Participant>(
-            r#"
-            SELECT p.*
-            FROM participants p
-            JOIN customers c ON p.customer_id = c.id
-            WHERE p.hq_id = $1 AND c.base_id = $2
-            ORDER BY p.name ASC
-            "#,
-        )
-        .bind(hq_id)
-        .bind(base_id)
-        .fetch_all(&state.db_pool) // (★ 'GET' 操作不需要事务)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to fetch base participants: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+// --- 学员列表 (分页) ---
+// 筛选: base_id / customer_id / is_active; q 搜学员姓名、家长姓名和手机号
+pub(crate) const PARTICIPANT_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "p.created_at"), ("name", "p.name")],
+    default_sort: "-created_at",
+    tiebreak: "p.id",
+    filters: &[
+        Filter::uuid("base_id", "c.base_id"),
+        Filter::uuid("customer_id", "p.customer_id"),
+        Filter::bool("is_active", "p.is_active"),
+    ],
+    search: &["p.name", "c.name", "c.phone_number"],
+};
 
-    } else {
-        // --- 场景 B: 租户管理员 (Tenant Admin) ---
-        tracing::debug!("Fetching all participants for hq_id: {}", hq_id);
-        
-        participants = sqlx::query_as::<_, Participant>(
-            r#"
-            SELECT * FROM participants
-            WHERE hq_id = $1
-            ORDER BY name ASC
-            "#,
-        )
-        .bind(hq_id)
-        .fetch_all(&state.db_pool) // (★ 'GET' 操作不需要事务)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to fetch all hq participants: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+// 学员挂在家长名下, 基地归属看家长的 base_id
+fn push_participant_scope(qb: &mut QueryBuilder<'static, Postgres>, scope: &TenantScope) {
+    qb.push(" AND p.hq_id = ").push_bind(scope.hq_id);
+    if let Some(base_id) = scope.base_id {
+        qb.push(" AND c.base_id = ").push_bind(base_id);
     }
+}
 
-    Ok(Json(participants))
+// (GET /api/v1/participants)
+pub async fn get_participants_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    list: ListQuery,
+) -> Result<Json<Page<Participant>>, AppError> {
+    let page = list
+        .fetch_page(&state.db_pool, &PARTICIPANT_LIST, |qb| {
+            qb.push("SELECT p.* FROM participants p JOIN customers c ON p.customer_id = c.id WHERE 1 = 1");
+            push_participant_scope(qb, &scope);
+        })
+        .await?;
+
+    Ok(Json(page))
 }
 
+// (GET /api/v1/base/participants) 基地学员列表, 带积分 / 等级 / 最近上课 / 剩余课次
 pub async fn get_base_participants_handler(
     State(state): State<AppState>,
-    claims: Claims,
-) -> Result<Json<Vec<ParticipantDetail>>, StatusCode> {
-    
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
-
-    let participants = match sqlx::query_as::<_, ParticipantDetail>(
-        r#"
-        SELECT 
-            p.id, p.name, p.date_of_birth, p.gender,
-            c.name AS customer_name,
-            c.phone_number AS customer_phone,
-            pp.current_total_points,
-            hr.name_key AS rank_name_key,
-            b.id as base_id,
-            b.name as base_name,
-            
-            -- 最近上课时间
-            (
-                SELECT MAX(cl.start_time)
-                FROM class_enrollments ce
-                JOIN classes cl ON ce.class_id = cl.id
-                WHERE ce.participant_id = p.id AND ce.status = 'completed'
-            ) as last_class_time,
-
-            -- (★ 新增: 剩余总课次 - 统计该学员名下所有有效次卡的剩余次数之和)
-            (
-                SELECT COALESCE(SUM(remaining_uses), 0)
-                FROM customer_memberships cm
-                WHERE cm.participant_id = p.id 
-                  AND cm.is_active = true 
-                  AND (cm.expiry_date IS NULL OR cm.expiry_date > NOW())
-            ) as remaining_counts
-
-        FROM participants p
-        JOIN customers c ON p.customer_id = c.id
-        LEFT JOIN bases b ON c.base_id = b.id
-        LEFT JOIN participant_profiles pp ON p.id = pp.participant_id
-        LEFT JOIN honor_ranks hr ON pp.current_honor_rank_id = hr.id
-        WHERE c.base_id = $1
-        ORDER BY p.created_at DESC
-        "#,
-    )
-    .bind(base_id)
-    .fetch_all(&state.db_pool)
-    .await
-    {
-        Ok(list) => list,
-        Err(e) => {
-            tracing::error!("Failed: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
+    scope: TenantScope,
+    list: ListQuery,
+) -> Result<Json<Page<ParticipantDetail>>, AppError> {
+    if scope.base_id.is_none() {
+        return Err(AppError::Forbidden("auth.base_required"));
+    }
 
-    Ok(Json(participants))
-}
\ No newline at end of file
+    let page = list
+        .fetch_page(&state.db_pool, &PARTICIPANT_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    p.id, p.name, p.date_of_birth, p.gender,
+                    c.name AS customer_name,
+                    c.phone_number AS customer_phone,
+                    pp.current_total_points,
+                    hr.name_key AS rank_name_key,
+                    b.id as base_id,
+                    b.name as base_name,
+
+                    -- 最近上课时间
+                    (
+                        SELECT MAX(cl.start_time)
+                        FROM class_enrollments ce
+                        JOIN classes cl ON ce.class_id = cl.id
+                        WHERE ce.participant_id = p.id AND ce.status = 'completed'
+                    ) as last_class_time,
+
+                    -- 剩余总课次: 该学员名下所有有效次卡的剩余次数之和
+                    (
+                        SELECT COALESCE(SUM(remaining_uses), 0)
+                        FROM customer_memberships cm
+                        WHERE cm.participant_id = p.id
+                          AND cm.is_active = true
+                          AND (cm.expiry_date IS NULL OR cm.expiry_date > NOW())
+                    ) as remaining_counts
+
+                FROM participants p
+                JOIN customers c ON p.customer_id = c.id
+                LEFT JOIN bases b ON c.base_id = b.id
+                LEFT JOIN participant_profiles pp ON p.id = pp.participant_id
+                LEFT JOIN honor_ranks hr ON pp.current_honor_rank_id = hr.id
+                WHERE 1 = 1
+                "#,
+            );
+            push_participant_scope(qb, &scope);
+        })
+        .await?;
+
+    Ok(Json(page))
+}
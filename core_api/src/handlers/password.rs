@@ -0,0 +1,286 @@
+/*
+ * src/handlers/password.rs
+ * 职责: 员工密码管理
+ * 1. 密码策略: 注册 / 创建员工 / 改密 / 重置共用
+ * 2. 自助修改密码 (需要旧密码), 改密后吊销全部会话并签发新会话
+ * 3. 管理员重置: 签发一次性令牌, 员工凭令牌设置新密码 (首次登录强制改密也走同一流程)
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use bcrypt::{hash, verify, DEFAULT_COST};
+use chrono::{DateTime, Duration, Utc};
+use rand::{distributions::Alphanumeric, seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Row};
+use tokio::task;
+use uuid::Uuid;
+
+use super::{open_session, revoke_subject_sessions, AppState, REVOKE_PASSWORD_CHANGED, SUBJECT_USER};
+use crate::models::{AuthResponse, Claims};
+use crate::permissions::is_customer;
+use crate::tenant::{Owned, TenantScope};
+use crate::error::AppError;
+
+// 令牌用途 (password_reset_tokens.purpose)
+pub const RESET_PURPOSE_ADMIN: &str = "admin_reset";
+pub const RESET_PURPOSE_FIRST_LOGIN: &str = "first_login";
+
+const PASSWORD_MIN_CHARS: usize = 8;
+const PASSWORD_MAX_BYTES: usize = 72; // bcrypt 只取前 72 字节
+const ADMIN_RESET_TTL_HOURS: i64 = 24;
+const FIRST_LOGIN_TTL_MINUTES: i64 = 15;
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordPayload {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompleteResetPayload {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasswordResetToken {
+    pub reset_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+// --- 密码策略 ---
+
+// 至少 8 位, 同时包含字母和数字, 不能与邮箱相同
+pub fn check_password_policy(password: &str, email: &str) -> Result<(), AppError> {
+    let ok = password.chars().count() >= PASSWORD_MIN_CHARS
+        && password.len() <= PASSWORD_MAX_BYTES
+        && password.chars().any(|c| c.is_ascii_alphabetic())
+        && password.chars().any(|c| c.is_ascii_digit())
+        && !password.eq_ignore_ascii_case(email.trim());
+    if ok {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest("auth.weak_password"))
+    }
+}
+
+// 管理员创建员工时的随机初始密码 (满足密码策略)
+pub fn generate_strong_password() -> String {
+    let mut rng = rand::thread_rng();
+    let upper: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().collect();
+    let lower: Vec<char> = "abcdefghijklmnopqrstuvwxyz".chars().collect();
+    let numbers: Vec<char> = "0123456789".chars().collect();
+    let special: Vec<char> = "!@#$%^&*".chars().collect();
+    let mut password: Vec<char> = Vec::new();
+    password.push(*upper.choose(&mut rng).unwrap());
+    password.push(*lower.choose(&mut rng).unwrap());
+    password.push(*numbers.choose(&mut rng).unwrap());
+    password.push(*special.choose(&mut rng).unwrap());
+    let all_chars = [upper, lower, numbers, special].concat();
+    for _ in 0..4 { password.push(*all_chars.choose(&mut rng).unwrap()); }
+    password.shuffle(&mut rng);
+    password.into_iter().collect()
+}
+
+// bcrypt 计算较慢, 放到阻塞线程池
+pub async fn hash_password(password: String) -> Result<String, AppError> {
+    task::spawn_blocking(move || hash(password, DEFAULT_COST))
+        .await
+        .map_err(AppError::internal("Password hashing task failed"))?
+        .map_err(AppError::internal("Failed to hash password"))
+}
+
+pub async fn verify_password(password: String, password_hash: String) -> Result<bool, AppError> {
+    task::spawn_blocking(move || verify(password, &password_hash).unwrap_or(false))
+        .await
+        .map_err(AppError::internal("Password verification task failed"))
+}
+
+// --- 一次性令牌 ---
+
+// 签发新令牌时作废该员工之前未使用的令牌
+pub async fn issue_password_reset_token(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    purpose: &str,
+    created_by: Option<Uuid>,
+) -> Result<PasswordResetToken, sqlx::Error> {
+    let ttl = if purpose == RESET_PURPOSE_FIRST_LOGIN {
+        Duration::minutes(FIRST_LOGIN_TTL_MINUTES)
+    } else {
+        Duration::hours(ADMIN_RESET_TTL_HOURS)
+    };
+    let expires_at = Utc::now() + ttl;
+    let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(48).map(char::from).collect();
+
+    sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO password_reset_tokens (user_id, token_hash, purpose, created_by, expires_at)
+        VALUES ($1, encode(sha256(convert_to($2, 'UTF8')), 'hex'), $3, $4, $5)
+        "#,
+    )
+    .bind(user_id)
+    .bind(&token)
+    .bind(purpose)
+    .bind(created_by)
+    .bind(expires_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(PasswordResetToken { reset_token: token, expires_at })
+}
+
+// 写入新密码, 清除强制改密标记和登录锁定, 作废未使用的重置令牌, 吊销全部会话
+async fn set_password(conn: &mut PgConnection, user_id: Uuid, password_hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE users SET password_hash = $2, password_changed_at = NOW(), must_change_password = false,
+               login_unlocked_at = NOW(), updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(password_hash)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    revoke_subject_sessions(conn, SUBJECT_USER, user_id, REVOKE_PASSWORD_CHANGED).await?;
+    Ok(())
+}
+
+// (POST /api/v1/auth/password) 员工修改自己的密码; 其他设备全部下线, 当前设备换发新 Token
+pub async fn change_password_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(payload): Json<ChangePasswordPayload>,
+) -> Result<Json<AuthResponse>, AppError> {
+    if is_customer(&claims) {
+        return Err(AppError::Forbidden("auth.forbidden"));
+    }
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::BadRequest("request.invalid"))?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT u.email, u.password_hash, s.impersonated_by
+        FROM users u
+        JOIN auth_sessions s ON s.id = $2
+        WHERE u.id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(claims.sid)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to load user for password change"))?
+    .ok_or(AppError::Unauthorized("auth.unauthorized"))?;
+
+    // 模拟登录的会话不能替员工改密码
+    if row.get::<Option<Uuid>, _>("impersonated_by").is_some() {
+        return Err(AppError::Forbidden("auth.impersonation_forbidden"));
+    }
+    let email: String = row.get("email");
+    let current_hash: String = row.get("password_hash");
+
+    if !verify_password(payload.current_password.clone(), current_hash).await? {
+        return Err(AppError::Unauthorized("auth.invalid_credentials"));
+    }
+    check_password_policy(&payload.new_password, &email)?;
+    if payload.new_password == payload.current_password {
+        return Err(AppError::BadRequest("auth.same_password"));
+    }
+    let new_hash = hash_password(payload.new_password).await?;
+
+    let mut tx = state.db_pool.begin().await?;
+    set_password(&mut tx, user_id, &new_hash).await.map_err(AppError::db("Failed to change password"))?;
+    let session = open_session(&mut tx, &state.jwt_secret, SUBJECT_USER, user_id)
+        .await?
+        .ok_or(AppError::Unauthorized("auth.unauthorized"))?;
+    tx.commit().await?;
+
+    Ok(Json(session.tokens))
+}
+
+// (POST /api/v1/hq/users/:id/password-reset) 管理员为员工签发一次性重置令牌 (忘记密码时使用)
+// 原密码立即失效, 员工凭令牌设置新密码
+pub async fn reset_user_password_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<PasswordResetToken>, AppError> {
+    let actor_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::BadRequest("request.invalid"))?;
+
+    let mut tx = state.db_pool.begin().await?;
+    // 基地账号只能重置本基地员工
+    TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::User(user_id)).await?;
+
+    // 原密码替换为随机值并下线全部设备 (账号可能已泄露), 之后只能凭令牌设置新密码
+    let scrambled = hash_password(generate_strong_password()).await?;
+    sqlx::query("UPDATE users SET password_hash = $2, must_change_password = true, updated_at = NOW() WHERE id = $1")
+        .bind(user_id)
+        .bind(scrambled)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to reset password"))?;
+    revoke_subject_sessions(&mut tx, SUBJECT_USER, user_id, REVOKE_PASSWORD_CHANGED)
+        .await
+        .map_err(AppError::db("Failed to revoke sessions on password reset"))?;
+    let token = issue_password_reset_token(&mut tx, user_id, RESET_PURPOSE_ADMIN, Some(actor_id))
+        .await
+        .map_err(AppError::db("Failed to issue password reset token"))?;
+    tx.commit().await?;
+
+    tracing::info!("Password reset issued for user {} by {}", user_id, actor_id);
+    Ok(Json(token))
+}
+
+// (POST /api/v1/auth/password/reset) 凭一次性令牌设置新密码, 成功后需重新登录
+pub async fn complete_password_reset_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<CompleteResetPayload>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = state.db_pool.begin().await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT t.id, t.user_id, u.email
+        FROM password_reset_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_hash = encode(sha256(convert_to($1, 'UTF8')), 'hex')
+          AND t.used_at IS NULL AND t.expires_at > NOW() AND u.is_active = true
+        FOR UPDATE OF t
+        "#,
+    )
+    .bind(payload.token.trim())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to look up password reset token"))?
+    .ok_or(AppError::Unauthorized("auth.reset_token_invalid"))?;
+
+    let token_id: Uuid = row.get("id");
+    let user_id: Uuid = row.get("user_id");
+    let email: String = row.get("email");
+
+    check_password_policy(&payload.new_password, &email)?;
+    let new_hash = hash_password(payload.new_password).await?;
+
+    sqlx::query("UPDATE password_reset_tokens SET used_at = NOW() WHERE id = $1")
+        .bind(token_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to consume password reset token"))?;
+    set_password(&mut tx, user_id, &new_hash).await.map_err(AppError::db("Failed to reset password"))?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
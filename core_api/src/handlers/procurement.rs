@@ -11,31 +11,40 @@ use sqlx::Row;
 use uuid::Uuid;
 
 use super::AppState;
+use crate::permissions::{base, hq, perm, Grants};
+use crate::tenant::{Owned, TenantScope};
 use crate::models::{
     Claims,
     CreateProcurementPayload, ProcurementItem, ProcurementOrder, ProcurementStatus,
     UpdateProcurementStatusPayload,
 };
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
 
 // (POST) 提交采购申请
 pub async fn create_procurement_order(
     State(state): State<AppState>,
     claims: Claims,
+    scope: TenantScope,
     Json(payload): Json<CreateProcurementPayload>,
-) -> Result<Json<ProcurementOrder>, StatusCode> {
+) -> Result<Json<ProcurementOrder>, AppError> {
     let hq_id = claims.hq_id;
 
     // 1. 必须是基地用户
     let base_id = match claims.base_id {
         Some(id) => id,
-        None => return Err(StatusCode::FORBIDDEN),
+        None => return Err(AppError::Forbidden("auth.forbidden")),
     };
 
     let mut tx = state
         .db_pool
         .begin()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
+
+    // 物料必须是本总部的
+    for item in &payload.items {
+        scope.ensure_visible(&mut *tx, Owned::Material(item.material_id)).await?;
+    }
 
     let submit_note_clone = payload.submit_note.clone();
 
@@ -53,10 +62,7 @@ pub async fn create_procurement_order(
     .bind(submit_note_clone)
     .fetch_one(&mut *tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to create order: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to create order"))?;
 
     // 3. 插入明细
     for item in payload.items {
@@ -68,15 +74,11 @@ pub async fn create_procurement_order(
         .bind(item.quantity)
         .execute(&mut *tx)
         .await
-        .map_err(|e| {
-            tracing::error!("Failed to insert item: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(AppError::db("Failed to insert item"))?;
     }
 
     tx.commit()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
     // 4. 返回
     Ok(Json(ProcurementOrder {
@@ -97,51 +99,54 @@ pub async fn create_procurement_order(
 }
 
 // (GET) 获取采购单列表
+// 分页; 筛选: status / base_id / created_from / created_to
+const PROCUREMENT_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "o.created_at")],
+    default_sort: "-created_at",
+    tiebreak: "o.id",
+    filters: &[
+        Filter::text("status", "o.status"),
+        Filter::uuid("base_id", "o.base_id"),
+        Filter::date_from("created_from", "o.created_at"),
+        Filter::date_to("created_to", "o.created_at"),
+    ],
+    search: &["b.name", "u.full_name"],
+};
+
 pub async fn get_procurement_orders(
     State(state): State<AppState>,
-    claims: Claims,
-) -> Result<Json<Vec<ProcurementOrder>>, StatusCode> {
-    let hq_id = claims.hq_id;
-
-    let sql = r#"
-        SELECT 
-            o.*, 
-            b.name as base_name,
-            u.full_name as applicant_name
-        FROM procurement_orders o
-        LEFT JOIN bases b ON o.base_id = b.id
-        LEFT JOIN users u ON o.applicant_id = u.id
-        WHERE o.hq_id = $1
-    "#;
-
-    let orders = if let Some(base_id) = claims.base_id {
-        sqlx::query_as::<_, ProcurementOrder>(&format!(
-            "{} AND o.base_id = $2 ORDER BY o.created_at DESC",
-            sql
-        ))
-        .bind(hq_id)
-        .bind(base_id)
-        .fetch_all(&state.db_pool)
-        .await
-    } else {
-        sqlx::query_as::<_, ProcurementOrder>(&format!("{} ORDER BY o.created_at DESC", sql))
-            .bind(hq_id)
-            .fetch_all(&state.db_pool)
-            .await
-    };
+    scope: TenantScope,
+    list: ListQuery,
+) -> Result<Json<Page<ProcurementOrder>>, AppError> {
+    let page = list
+        .fetch_page(&state.db_pool, &PROCUREMENT_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    o.*,
+                    b.name as base_name,
+                    u.full_name as applicant_name
+                FROM procurement_orders o
+                LEFT JOIN bases b ON o.base_id = b.id
+                LEFT JOIN users u ON o.applicant_id = u.id
+                WHERE 1 = 1
+                "#,
+            );
+            scope.push_filter(qb, "o");
+        })
+        .await?;
 
-    orders.map(Json).map_err(|e| {
-        tracing::error!("Fetch orders failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })
+    Ok(Json(page))
 }
 
 // (GET) 获取单条订单详情
 pub async fn get_procurement_details(
     State(state): State<AppState>,
-    _claims: Claims,
+    scope: TenantScope,
     Path(order_id): Path<Uuid>,
-) -> Result<Json<Vec<ProcurementItem>>, StatusCode> {
+) -> Result<Json<Vec<ProcurementItem>>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::ProcurementOrder(order_id)).await?;
+
     let items = sqlx::query_as::<_, ProcurementItem>(
         r#"
         SELECT 
@@ -156,10 +161,7 @@ pub async fn get_procurement_details(
     .bind(order_id)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Fetch items failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Fetch items failed"))?;
 
     Ok(Json(items))
 }
@@ -168,42 +170,42 @@ pub async fn get_procurement_details(
 pub async fn update_procurement_status(
     State(state): State<AppState>,
     claims: Claims,
+    grants: Grants,
     Path(order_id): Path<Uuid>,
     Json(payload): Json<UpdateProcurementStatusPayload>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AppError> {
     let mut tx = state
         .db_pool
         .begin()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
+    TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::ProcurementOrder(order_id)).await?;
 
     let order =
         sqlx::query("SELECT base_id, status FROM procurement_orders WHERE id = $1 FOR UPDATE")
             .bind(order_id)
             .fetch_optional(&mut *tx)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .await?;
 
     let row = match order {
         Some(r) => r,
-        None => return Err(StatusCode::NOT_FOUND),
+        None => return Err(AppError::NotFound("resource.not_found")),
     };
 
     let base_id: Uuid = row.get("base_id");
-    let is_hq = claims.base_id.is_none();
 
+    // 审批 / 发货归总部, 收货归基地
     match payload.status {
         ProcurementStatus::Approved | ProcurementStatus::Rejected | ProcurementStatus::Shipped => {
-            if !is_hq {
-                return Err(StatusCode::FORBIDDEN);
+            if !hq(perm::PROCUREMENT_APPROVE).check(&claims, &grants) {
+                return Err(AppError::Forbidden("auth.forbidden"));
             }
         }
         ProcurementStatus::Received => {
-            if is_hq {
-                return Err(StatusCode::FORBIDDEN);
+            if !base(perm::PROCUREMENT_MANAGE).check(&claims, &grants) {
+                return Err(AppError::Forbidden("auth.forbidden"));
             }
         }
-        _ => return Err(StatusCode::BAD_REQUEST),
+        _ => return Err(AppError::BadRequest("request.invalid")),
     }
 
     sqlx::query(
@@ -221,8 +223,7 @@ pub async fn update_procurement_status(
     .bind(payload.tracking_number)
     .bind(order_id)
     .execute(&mut *tx)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     if payload.status == ProcurementStatus::Received {
         let items = sqlx::query_as::<_, ProcurementItem>(
@@ -233,8 +234,7 @@ pub async fn update_procurement_status(
         )
         .bind(order_id)
         .fetch_all(&mut *tx)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
         for item in items {
             sqlx::query(
@@ -248,15 +248,11 @@ pub async fn update_procurement_status(
             .bind(item.quantity)
             .execute(&mut *tx)
             .await
-            .map_err(|e| {
-                tracing::error!("Stock update failed: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+            .map_err(AppError::db("Stock update failed"))?;
         }
     }
 
     tx.commit()
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
     Ok(StatusCode::OK)
 }
@@ -1,6 +1,6 @@
 use axum::{
     extract::{Path, State, Json, Query}, // ★ 引入 Query 提取器
-    http::{StatusCode, header},
+    http::{header},
     response::{IntoResponse},
     Json as AxumJson,
 };
@@ -8,6 +8,7 @@ use rand::{distributions::Alphanumeric, Rng};
 use uuid::Uuid;
 use sqlx::Postgres;
 use super::AppState;
+use crate::tenant::{Owned, TenantScope};
 use serde::Deserialize;
 use crate::models::{
     Claims, 
@@ -18,6 +19,8 @@ use crate::models::{
     DbVerifyItem, 
     BatchSummary
 };
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec};
 
 // ==========================================
 // DTOs (本地定义)
@@ -42,22 +45,23 @@ pub async fn generate_qrcodes_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<GenerateQrcodePayload>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, AppError> {
     
-    if payload.quantity > 50000 { return Err(StatusCode::BAD_REQUEST); }
+    if payload.quantity > 50000 { return Err(AppError::BadRequest("request.invalid")); }
 
     let batch_no = format!("B{}-{}", chrono::Utc::now().format("%Y%m%d"), generate_secure_string(4).to_uppercase());
 
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut tx = state.db_pool.begin().await?;
 
     let batch_id: Uuid = sqlx::query_scalar::<Postgres, Uuid>(
-        "INSERT INTO qrcode_batches (batch_no, name, quantity, created_by) VALUES ($1, $2, $3, $4) RETURNING id"
+        "INSERT INTO qrcode_batches (batch_no, name, quantity, created_by, hq_id) VALUES ($1, $2, $3, $4, $5) RETURNING id"
     )
     .bind(&batch_no)
     .bind(&payload.batch_name)
     .bind(payload.quantity as i32)
     .bind(Uuid::parse_str(&claims.sub).unwrap_or_default())
-    .fetch_one(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .bind(claims.hq_id)
+    .fetch_one(&mut *tx).await?;
 
     let mut all_items = Vec::with_capacity(payload.quantity);
     for _ in 0..payload.quantity {
@@ -69,10 +73,10 @@ pub async fn generate_qrcodes_handler(
         query_builder.push_values(chunk, |mut b, item| {
             b.push_bind(batch_id).push_bind(&item.0).push_bind(&item.1);
         });
-        query_builder.build().execute(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        query_builder.build().execute(&mut *tx).await?;
     }
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
 
     Ok(AxumJson(GenerateQRResponse {
         batch_id,
@@ -87,10 +91,11 @@ pub async fn generate_qrcodes_handler(
 // ==========================================
 pub async fn export_batch_csv_handler(
     State(state): State<AppState>,
-    _claims: Claims,
+    scope: TenantScope,
     Path(batch_id): Path<Uuid>,
-) -> Result<impl IntoResponse, StatusCode> {
-    
+) -> Result<impl IntoResponse, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::QrcodeBatch(batch_id)).await?;
+
     // ★ 修改：查询时带上 secret_salt
     let items = sqlx::query_as::<Postgres, DbExportItem>(
         "SELECT short_code, secret_salt FROM qrcode_items WHERE batch_id = $1 ORDER BY id ASC"
@@ -98,10 +103,7 @@ pub async fn export_batch_csv_handler(
     .bind(batch_id)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Export failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Export failed"))?;
 
     // 假设前端验证页地址 (请替换为真实域名)
     let base_url = "http://192.168.10.59:3000/verify/"; 
@@ -132,7 +134,7 @@ pub async fn verify_qrcode_handler(
     State(state): State<AppState>,
     Path(code): Path<String>,
     Query(query): Query<VerifyQuery>, // ★ 获取 URL 参数
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<impl IntoResponse, AppError> {
     
     // ★ 修改：查询时带上 secret_salt
     let item = sqlx::query_as::<Postgres, DbVerifyItem>(
@@ -145,10 +147,7 @@ pub async fn verify_qrcode_handler(
     .bind(code)
     .fetch_optional(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Verify db error: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Verify db error"))?;
 
     let res = match item {
         None => VerifyQRResponse {
@@ -218,37 +217,51 @@ pub async fn verify_qrcode_handler(
 }
 
 // ==========================================
-// 4. 获取批次列表接口 (管理员) - 保持不变
+// 4. 获取批次列表接口 (管理员)
 // ==========================================
+// 分页; 筛选: created_from / created_to; q 搜批次号和名称
+const BATCH_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "b.created_at"), ("batch_no", "b.batch_no")],
+    default_sort: "-created_at",
+    tiebreak: "b.id",
+    filters: &[
+        Filter::date_from("created_from", "b.created_at"),
+        Filter::date_to("created_to", "b.created_at"),
+    ],
+    search: &["b.batch_no", "b.name"],
+};
+
 pub async fn list_batches_handler(
     State(state): State<AppState>,
-    _claims: Claims,
-) -> Result<impl IntoResponse, StatusCode> {
-    
-    let sql = r#"
-        SELECT 
-            b.id, 
-            b.batch_no, 
-            b.name, 
-            b.quantity, 
-            b.created_at,
-            COUNT(CASE WHEN i.status = 'ACTIVE' THEN 1 END) as active_count,
-            COUNT(CASE WHEN i.status = 'SCANNED' THEN 1 END) as scan_count
-        FROM qrcode_batches b
-        LEFT JOIN qrcode_items i ON b.id = i.batch_id
-        GROUP BY b.id
-        ORDER BY b.created_at DESC
-    "#;
-
-    let batches = sqlx::query_as::<Postgres, BatchSummary>(sql)
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("List batches failed: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    scope: TenantScope,
+    list: ListQuery,
+) -> Result<impl IntoResponse, AppError> {
+    let page = list
+        .fetch_page::<BatchSummary>(&state.db_pool, &BATCH_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    b.id,
+                    b.batch_no,
+                    b.name,
+                    b.quantity,
+                    b.created_at,
+                    counts.active_count,
+                    counts.scan_count
+                FROM qrcode_batches b
+                CROSS JOIN LATERAL (
+                    SELECT
+                        COUNT(CASE WHEN i.status = 'ACTIVE' THEN 1 END) as active_count,
+                        COUNT(CASE WHEN i.status = 'SCANNED' THEN 1 END) as scan_count
+                    FROM qrcode_items i WHERE i.batch_id = b.id
+                ) counts
+                WHERE b.hq_id = "#,
+            );
+            qb.push_bind(scope.hq_id);
+        })
+        .await?;
 
-    Ok(AxumJson(batches))
+    Ok(AxumJson(page))
 }
 
 // ==========================================
@@ -256,14 +269,12 @@ pub async fn list_batches_handler(
 // ==========================================
 pub async fn activate_batch_handler(
     State(state): State<AppState>,
-    _claims: Claims,
+    scope: TenantScope,
     Path(batch_id): Path<Uuid>,
-) -> Result<impl IntoResponse, StatusCode> {
-    
-    let mut tx = state.db_pool.begin().await.map_err(|e| {
-        tracing::error!("Begin tx failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+) -> Result<impl IntoResponse, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::QrcodeBatch(batch_id)).await?;
+
+    let mut tx = state.db_pool.begin().await.map_err(AppError::db("Begin tx failed"))?;
 
     let result = sqlx::query(
         "UPDATE qrcode_items SET status = 'ACTIVE' WHERE batch_id = $1::uuid AND status = 'DORMANT'"
@@ -271,15 +282,9 @@ pub async fn activate_batch_handler(
     .bind(batch_id)
     .execute(&mut *tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Activate items failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Activate items failed"))?;
 
-    tx.commit().await.map_err(|e| {
-        tracing::error!("Commit tx failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    tx.commit().await.map_err(AppError::db("Commit tx failed"))?;
 
     let rows_affected = result.rows_affected();
     
@@ -0,0 +1,377 @@
+/*
+ * src/handlers/receivable.rs
+ * 职责: 应收账款
+ * 1. 订单分期: 为订单设置多个付款节点 (到期日 + 金额, 合计须等于订单总额), 重设时已收款项重新核销
+ * 2. 收款核销: 收款核实 (verify_payment_handler) 时按到期日先后核销到未收齐的各期; 退款从最晚一期冲回
+ *    订单 due_date 同步为最早一笔未收齐分期的到期日
+ * 3. 账龄分析: 按基地 / 客户汇总未收金额, 分 未到期 / 逾期 1-30 / 31-60 / 61-90 / 90 天以上
+ *    数据来自视图 order_receivables (有分期按每期, 无分期按订单 due_date)
+ */
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use super::AppState;
+use crate::error::AppError;
+use crate::tenant::{Owned, TenantScope};
+use crate::timezone::business_date;
+
+// ---------------------------------------------------------
+// 订单分期
+// ---------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct InstallmentPayload {
+    pub due_date: NaiveDate,
+    pub amount: f64, // 元
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetInstallmentsPayload {
+    pub installments: Vec<InstallmentPayload>, // 为空则取消分期
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OrderInstallment {
+    pub id: Uuid,
+    pub seq: i32,
+    pub due_date: NaiveDate,
+    pub amount_cents: i32,
+    pub paid_cents: i32,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub note: Option<String>,
+    pub status: String,            // paid / partial / overdue / due
+    pub payments: serde_json::Value, // 核销到该期的收款流水 [{payment_record_id, amount_cents, created_at}]
+}
+
+async fn fetch_installments(conn: &mut PgConnection, order_id: Uuid) -> Result<Vec<OrderInstallment>, AppError> {
+    sqlx::query_as::<_, OrderInstallment>(
+        r#"
+        SELECT i.id, i.seq, i.due_date, i.amount_cents, i.paid_cents, i.paid_at, i.note,
+               CASE
+                   WHEN i.paid_cents >= i.amount_cents THEN 'paid'
+                   WHEN i.due_date < $2 THEN 'overdue'
+                   WHEN i.paid_cents > 0 THEN 'partial'
+                   ELSE 'due'
+               END AS status,
+               COALESCE(
+                   (SELECT jsonb_agg(jsonb_build_object(
+                        'payment_record_id', p.payment_record_id,
+                        'amount_cents', p.amount_cents,
+                        'created_at', p.created_at) ORDER BY p.created_at)
+                    FROM order_installment_payments p WHERE p.installment_id = i.id),
+                   '[]'::jsonb
+               ) AS payments
+        FROM order_installments i
+        WHERE i.order_id = $1
+        ORDER BY i.seq
+        "#,
+    )
+    .bind(order_id)
+    .bind(business_date(Utc::now()))
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to fetch installments"))
+}
+
+// (GET /api/v1/finance/orders/:id/installments)
+pub async fn get_order_installments_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(order_id): Path<Uuid>,
+) -> Result<Json<Vec<OrderInstallment>>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::Order(order_id)).await?;
+    let mut conn = state.db_pool.acquire().await.map_err(AppError::db("Failed to acquire connection"))?;
+    Ok(Json(fetch_installments(&mut conn, order_id).await?))
+}
+
+// (PUT /api/v1/finance/orders/:id/installments)
+// 整体替换分期计划; 订单已有的收款 / 退款按时间顺序重新核销
+pub async fn set_order_installments_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Path(order_id): Path<Uuid>,
+    Json(payload): Json<SetInstallmentsPayload>,
+) -> Result<Json<Vec<OrderInstallment>>, AppError> {
+    let mut plan: Vec<(NaiveDate, i32, Option<String>)> = Vec::with_capacity(payload.installments.len());
+    for item in payload.installments {
+        let amount_cents = (item.amount * 100.0).round() as i32;
+        if amount_cents <= 0 {
+            return Err(AppError::BadRequest("installment.invalid_amount"));
+        }
+        plan.push((item.due_date, amount_cents, item.note.filter(|n| !n.trim().is_empty())));
+    }
+    plan.sort_by_key(|(due_date, _, _)| *due_date);
+
+    let mut tx = state.db_pool.begin().await.map_err(AppError::db("Failed to begin transaction"))?;
+    scope.ensure_owned(&mut *tx, Owned::Order(order_id)).await?;
+
+    let (hq_id, base_id, total_amount_cents, status): (Uuid, Uuid, i32, Option<String>) = sqlx::query_as(
+        "SELECT hq_id, base_id, total_amount_cents, status::TEXT FROM orders WHERE id = $1 FOR UPDATE",
+    )
+    .bind(order_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if matches!(status.as_deref(), Some("cancelled") | Some("refunded")) {
+        return Err(AppError::Conflict("installment.order_closed"));
+    }
+    if !plan.is_empty() && plan.iter().map(|(_, cents, _)| *cents as i64).sum::<i64>() != total_amount_cents as i64 {
+        return Err(AppError::Rejected("installment.sum_mismatch"));
+    }
+
+    sqlx::query("DELETE FROM order_installments WHERE order_id = $1")
+        .bind(order_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to clear installments"))?;
+
+    for (idx, (due_date, amount_cents, note)) in plan.iter().enumerate() {
+        sqlx::query(
+            r#"
+            INSERT INTO order_installments (order_id, hq_id, base_id, seq, due_date, amount_cents, note)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(order_id)
+        .bind(hq_id)
+        .bind(base_id)
+        .bind(idx as i32 + 1)
+        .bind(due_date)
+        .bind(amount_cents)
+        .bind(note)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to create installment"))?;
+    }
+
+    if !plan.is_empty() {
+        // 已核实的收款按核实先后重新核销, 再冲回已退款金额
+        let records: Vec<(Uuid, String, i32)> = sqlx::query_as(
+            r#"
+            SELECT id, transaction_type, amount_cents FROM finance_payment_records
+            WHERE order_id = $1 AND status = 'VERIFIED'
+            ORDER BY verified_at, created_at
+            "#,
+        )
+        .bind(order_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut refunded_cents = 0;
+        for (record_id, transaction_type, amount_cents) in records {
+            if transaction_type == "REFUND" {
+                refunded_cents += amount_cents;
+            } else {
+                match_payment_to_installments(&mut tx, order_id, record_id, amount_cents).await?;
+            }
+        }
+        release_installments(&mut tx, order_id, refunded_cents).await?;
+    }
+
+    let installments = fetch_installments(&mut tx, order_id).await?;
+    tx.commit().await.map_err(AppError::db("Transaction commit failed"))?;
+    Ok(Json(installments))
+}
+
+// ---------------------------------------------------------
+// 收款核销 (在收款 / 退款的事务内调用)
+// ---------------------------------------------------------
+
+// 收款按到期日先后核销到未收齐的分期, 超出分期的部分不核销; 订单没有分期时不做任何事
+pub async fn match_payment_to_installments(
+    conn: &mut PgConnection,
+    order_id: Uuid,
+    payment_record_id: Uuid,
+    amount_cents: i32,
+) -> Result<(), AppError> {
+    let open: Vec<(Uuid, i32)> = sqlx::query_as(
+        r#"
+        SELECT id, amount_cents - paid_cents FROM order_installments
+        WHERE order_id = $1 AND paid_cents < amount_cents
+        ORDER BY seq
+        FOR UPDATE
+        "#,
+    )
+    .bind(order_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut left = amount_cents;
+    for (installment_id, open_cents) in open {
+        if left <= 0 {
+            break;
+        }
+        let take = left.min(open_cents);
+        sqlx::query(
+            r#"
+            UPDATE order_installments SET
+                paid_cents = paid_cents + $1,
+                paid_at = CASE WHEN paid_cents + $1 = amount_cents THEN NOW() ELSE NULL END
+            WHERE id = $2
+            "#,
+        )
+        .bind(take)
+        .bind(installment_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(AppError::db("Failed to apply payment to installment"))?;
+        sqlx::query(
+            r#"
+            INSERT INTO order_installment_payments (installment_id, payment_record_id, amount_cents)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (installment_id, payment_record_id)
+            DO UPDATE SET amount_cents = order_installment_payments.amount_cents + EXCLUDED.amount_cents
+            "#,
+        )
+        .bind(installment_id)
+        .bind(payment_record_id)
+        .bind(take)
+        .execute(&mut *conn)
+        .await
+        .map_err(AppError::db("Failed to record installment payment"))?;
+        left -= take;
+    }
+
+    sync_order_due_date(conn, order_id).await
+}
+
+// 退款从最晚一期往前冲回已收金额 (核销明细保留, 作为历史记录)
+pub async fn release_installments(conn: &mut PgConnection, order_id: Uuid, amount_cents: i32) -> Result<(), AppError> {
+    if amount_cents <= 0 {
+        return Ok(());
+    }
+    let paid: Vec<(Uuid, i32)> = sqlx::query_as(
+        "SELECT id, paid_cents FROM order_installments WHERE order_id = $1 AND paid_cents > 0 ORDER BY seq DESC FOR UPDATE",
+    )
+    .bind(order_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut left = amount_cents;
+    for (installment_id, paid_cents) in paid {
+        if left <= 0 {
+            break;
+        }
+        let take = left.min(paid_cents);
+        sqlx::query("UPDATE order_installments SET paid_cents = paid_cents - $1, paid_at = NULL WHERE id = $2")
+            .bind(take)
+            .bind(installment_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(AppError::db("Failed to release installment"))?;
+        left -= take;
+    }
+
+    sync_order_due_date(conn, order_id).await
+}
+
+// 订单 due_date 取最早一笔未收齐分期的到期日 (全部收齐时保持不变)
+async fn sync_order_due_date(conn: &mut PgConnection, order_id: Uuid) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE orders SET due_date = next.due_date
+        FROM (SELECT MIN(due_date) AS due_date FROM order_installments WHERE order_id = $1 AND paid_cents < amount_cents) next
+        WHERE orders.id = $1 AND next.due_date IS NOT NULL
+        "#,
+    )
+    .bind(order_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to sync order due date"))?;
+    Ok(())
+}
+
+// ---------------------------------------------------------
+// 账龄分析
+// ---------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct AgingQuery {
+    pub as_of: Option<NaiveDate>,     // 账龄计算日, 默认今天 (未收金额始终为当前余额)
+    pub group_by: Option<String>,     // base (默认) / customer
+    pub base_id: Option<Uuid>,        // 总部账号可指定基地, 基地账号固定本基地
+}
+
+#[derive(Debug, Default, Serialize, FromRow)]
+pub struct AgingRow {
+    pub id: Option<Uuid>, // 基地 / 客户 id; 合计行及未关联客户的订单为空
+    pub name: String,
+    pub current_cents: i64,       // 未到期
+    pub days_1_30_cents: i64,
+    pub days_31_60_cents: i64,
+    pub days_61_90_cents: i64,
+    pub days_over_90_cents: i64,
+    pub total_cents: i64,
+    pub order_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgingReport {
+    pub as_of: NaiveDate,
+    pub group_by: String,
+    pub base_id: Option<Uuid>,
+    pub rows: Vec<AgingRow>, // 按未收合计从大到小
+    pub totals: AgingRow,
+}
+
+// (GET /api/v1/finance/receivables/aging?as_of=&group_by=&base_id=)
+pub async fn get_receivables_aging_handler(
+    State(state): State<AppState>,
+    scope: TenantScope,
+    Query(query): Query<AgingQuery>,
+) -> Result<Json<AgingReport>, AppError> {
+    let as_of = query.as_of.unwrap_or_else(|| business_date(Utc::now()));
+    let base_id = scope.base_id.or(query.base_id);
+    let group_by = query.group_by.unwrap_or_else(|| "base".to_string());
+    let (key, name) = match group_by.as_str() {
+        "base" => ("r.base_id", "b.name"),
+        "customer" => ("r.customer_id", "COALESCE(c.name, c.phone_number, '未关联客户')"),
+        _ => return Err(AppError::BadRequest("request.invalid")),
+    };
+
+    // key / name 为上面固定的列表达式, 不含用户输入
+    let sql = format!(
+        r#"
+        SELECT {key} AS id, {name} AS name,
+               COALESCE(SUM(r.outstanding_cents) FILTER (WHERE r.due_date >= $3), 0)::bigint AS current_cents,
+               COALESCE(SUM(r.outstanding_cents) FILTER (WHERE $3 - r.due_date BETWEEN 1 AND 30), 0)::bigint AS days_1_30_cents,
+               COALESCE(SUM(r.outstanding_cents) FILTER (WHERE $3 - r.due_date BETWEEN 31 AND 60), 0)::bigint AS days_31_60_cents,
+               COALESCE(SUM(r.outstanding_cents) FILTER (WHERE $3 - r.due_date BETWEEN 61 AND 90), 0)::bigint AS days_61_90_cents,
+               COALESCE(SUM(r.outstanding_cents) FILTER (WHERE $3 - r.due_date > 90), 0)::bigint AS days_over_90_cents,
+               COALESCE(SUM(r.outstanding_cents), 0)::bigint AS total_cents,
+               COUNT(DISTINCT r.order_id) AS order_count
+        FROM order_receivables r
+        JOIN bases b ON b.id = r.base_id
+        LEFT JOIN customers c ON c.id = r.customer_id
+        WHERE r.hq_id = $1 AND ($2::uuid IS NULL OR r.base_id = $2)
+        GROUP BY 1, 2
+        ORDER BY total_cents DESC, name
+        "#
+    );
+    let rows = sqlx::query_as::<_, AgingRow>(&sql)
+        .bind(scope.hq_id)
+        .bind(base_id)
+        .bind(as_of)
+        .fetch_all(&state.db_pool)
+        .await
+        .map_err(AppError::db("Failed to aggregate receivables aging"))?;
+
+    let mut totals = AgingRow { name: "合计".to_string(), ..Default::default() };
+    for row in &rows {
+        totals.current_cents += row.current_cents;
+        totals.days_1_30_cents += row.days_1_30_cents;
+        totals.days_31_60_cents += row.days_31_60_cents;
+        totals.days_61_90_cents += row.days_61_90_cents;
+        totals.days_over_90_cents += row.days_over_90_cents;
+        totals.total_cents += row.total_cents;
+        totals.order_count += row.order_count;
+    }
+
+    Ok(Json(AgingReport { as_of, group_by, base_id, rows, totals }))
+}
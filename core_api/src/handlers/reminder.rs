@@ -0,0 +1,251 @@
+/*
+ * src/handlers/reminder.rs
+ * 职责: 会员卡续费提醒 (即将到期 / 剩余次数不足)
+ * 后台任务每天扫描 customer_memberships, 给家长发消息, 同时在基地工作台生成跟进清单
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use super::{notify_customer, AppState};
+use crate::models::Claims;
+use crate::timezone::business_tz;
+use crate::error::AppError;
+
+const DEFAULT_EXPIRY_WITHIN_DAYS: i32 = 7;
+const DEFAULT_LOW_BALANCE_USES: i32 = 2;
+const DEFAULT_JOB_INTERVAL_SECS: u64 = 24 * 3600;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReminderSettings {
+    pub expiry_within_days: i32, // 到期前 N 天提醒
+    pub low_balance_uses: i32,   // 剩余次数 <= 该值时提醒
+}
+
+impl ReminderSettings {
+    // 从环境变量读取: REMINDER_EXPIRY_DAYS / REMINDER_LOW_BALANCE_USES
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: i32| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<i32>().ok())
+                .filter(|v| *v >= 0)
+                .unwrap_or(default)
+        };
+        Self {
+            expiry_within_days: read("REMINDER_EXPIRY_DAYS", DEFAULT_EXPIRY_WITHIN_DAYS),
+            low_balance_uses: read("REMINDER_LOW_BALANCE_USES", DEFAULT_LOW_BALANCE_USES),
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct NewReminder {
+    hq_id: Uuid,
+    customer_id: Uuid,
+    membership_id: Uuid,
+    kind: String,
+    expiry_date: Option<DateTime<Utc>>,
+    remaining_uses: Option<i32>,
+    participant_name: Option<String>,
+    tier_name: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct MembershipReminderItem {
+    pub id: Uuid,
+    pub kind: String,
+    pub membership_id: Uuid,
+    pub customer_id: Uuid,
+    pub customer_name: Option<String>,
+    pub phone_number: String,
+    pub participant_name: Option<String>,
+    pub tier_name: String,
+    pub expiry_date: Option<DateTime<Utc>>,
+    pub remaining_uses: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FollowUpReminderPayload {
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReminderScanSummary {
+    pub expiring: i64,
+    pub low_balance: i64,
+}
+
+// 扫描一次并生成提醒 (已生成过的同周期提醒自动跳过)
+// low_balance 的周期 = 到期日 + 最近一次延期/转让, 续期或转卡后余额再次不足会重新提醒
+pub async fn scan_membership_reminders(pool: &PgPool, settings: ReminderSettings) -> Result<ReminderScanSummary, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let created = sqlx::query_as::<_, NewReminder>(
+        r#"
+        WITH due AS (
+            SELECT cm.id AS membership_id, cm.hq_id, c.base_id, cm.customer_id, 'expiring' AS kind,
+                   to_char(cm.expiry_date, 'YYYY-MM-DD') AS cycle_key, cm.expiry_date, cm.remaining_uses
+            FROM customer_memberships cm
+            JOIN customers c ON cm.customer_id = c.id
+            WHERE COALESCE(cm.is_active, true) = true
+              AND cm.frozen_at IS NULL
+              AND cm.expiry_date IS NOT NULL
+              AND cm.expiry_date > NOW()
+              AND cm.expiry_date <= NOW() + make_interval(days => $1)
+            UNION ALL
+            SELECT cm.id, cm.hq_id, c.base_id, cm.customer_id, 'low_balance',
+                   'low:' || COALESCE(to_char(cm.expiry_date, 'YYYY-MM-DD'), '-') || ':' || COALESCE(adj.last_at::text, '0'),
+                   cm.expiry_date, cm.remaining_uses
+            FROM customer_memberships cm
+            JOIN customers c ON cm.customer_id = c.id
+            JOIN membership_tiers mt ON cm.tier_id = mt.id
+            LEFT JOIN LATERAL (
+                SELECT extract(epoch FROM max(ma.created_at))::bigint AS last_at
+                FROM membership_adjustments ma
+                WHERE ma.membership_id = cm.id AND ma.action IN ('extend', 'transfer')
+            ) adj ON true
+            WHERE COALESCE(cm.is_active, true) = true
+              AND mt.tier_type = 'usage_based'
+              AND cm.remaining_uses IS NOT NULL
+              AND cm.remaining_uses <= $2
+              AND (cm.expiry_date IS NULL OR cm.expiry_date > NOW())
+        ),
+        inserted AS (
+            INSERT INTO membership_reminders (hq_id, base_id, customer_id, membership_id, kind, cycle_key, expiry_date, remaining_uses)
+            SELECT hq_id, base_id, customer_id, membership_id, kind, cycle_key, expiry_date, remaining_uses FROM due
+            ON CONFLICT (membership_id, kind, cycle_key) DO NOTHING
+            RETURNING hq_id, customer_id, membership_id, kind, expiry_date, remaining_uses
+        )
+        SELECT i.hq_id, i.customer_id, i.membership_id, i.kind, i.expiry_date, i.remaining_uses,
+               p.name AS participant_name, mt.name_key AS tier_name
+        FROM inserted i
+        JOIN customer_memberships cm ON i.membership_id = cm.id
+        JOIN membership_tiers mt ON cm.tier_id = mt.id
+        LEFT JOIN participants p ON cm.participant_id = p.id
+        "#,
+    )
+    .bind(settings.expiry_within_days)
+    .bind(settings.low_balance_uses)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut summary = ReminderScanSummary::default();
+    for r in &created {
+        let holder = r.participant_name.as_deref().unwrap_or("您");
+        let (title, content) = if r.kind == "expiring" {
+            summary.expiring += 1;
+            let expiry = r
+                .expiry_date
+                .map(|d| d.with_timezone(&business_tz()).format("%Y-%m-%d").to_string())
+                .unwrap_or_default();
+            ("会员卡即将到期", format!("{} 的会员卡 ({}) 将于 {} 到期, 请及时续费。", holder, r.tier_name, expiry))
+        } else {
+            summary.low_balance += 1;
+            (
+                "会员卡次数不足",
+                format!("{} 的会员卡 ({}) 仅剩 {} 次, 请及时续费。", holder, r.tier_name, r.remaining_uses.unwrap_or(0)),
+            )
+        };
+        let category = format!("membership_{}", r.kind);
+        notify_customer(&mut tx, r.hq_id, r.customer_id, &category, title, &content, Some(r.membership_id)).await?;
+    }
+
+    tx.commit().await?;
+    Ok(summary)
+}
+
+// 启动后台提醒任务 (间隔可用 REMINDER_JOB_INTERVAL_SECS 配置, 默认每天一次)
+pub fn spawn_membership_reminder_job(pool: PgPool) {
+    let settings = ReminderSettings::from_env();
+    let interval_secs = std::env::var("REMINDER_JOB_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_JOB_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            match scan_membership_reminders(&pool, settings).await {
+                Ok(summary) if summary.expiring + summary.low_balance > 0 => tracing::info!(
+                    "Membership reminders: {} expiring, {} low balance",
+                    summary.expiring,
+                    summary.low_balance
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::error!("Membership reminder job failed: {}", e),
+            }
+        }
+    });
+}
+
+// (GET /api/v1/base/workspace/renewal-reminders) 待跟进的续费提醒
+pub async fn get_renewal_reminders_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<MembershipReminderItem>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let list = sqlx::query_as::<_, MembershipReminderItem>(
+        r#"
+        SELECT r.id, r.kind, r.membership_id, r.customer_id, c.name AS customer_name, c.phone_number,
+               p.name AS participant_name, mt.name_key AS tier_name,
+               cm.expiry_date, cm.remaining_uses, r.created_at
+        FROM membership_reminders r
+        JOIN customers c ON r.customer_id = c.id
+        JOIN customer_memberships cm ON r.membership_id = cm.id
+        JOIN membership_tiers mt ON cm.tier_id = mt.id
+        LEFT JOIN participants p ON cm.participant_id = p.id
+        WHERE r.hq_id = $1 AND r.base_id = $2 AND r.status = 'open'
+        ORDER BY r.created_at ASC
+        "#,
+    )
+    .bind(claims.hq_id)
+    .bind(base_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to fetch renewal reminders"))?;
+
+    Ok(Json(list))
+}
+
+// (POST /api/v1/base/workspace/renewal-reminders/:id/follow-up) 标记已跟进
+pub async fn follow_up_reminder_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(reminder_id): Path<Uuid>,
+    Json(payload): Json<FollowUpReminderPayload>,
+) -> Result<StatusCode, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE membership_reminders
+        SET status = 'followed_up', follow_up_note = $1, followed_up_by = $2, followed_up_at = NOW()
+        WHERE id = $3 AND hq_id = $4 AND base_id = $5 AND status = 'open'
+        "#,
+    )
+    .bind(payload.note)
+    .bind(Uuid::parse_str(&claims.sub).ok())
+    .bind(reminder_id)
+    .bind(claims.hq_id)
+    .bind(base_id)
+    .execute(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to follow up reminder"))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("resource.not_found"));
+    }
+    Ok(StatusCode::OK)
+}
@@ -2,9 +2,10 @@
  * src/handlers/report.rs
  * 职责: 数据报表 - Data Reports & Analytics
  */
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{extract::State, Json};
 use super::AppState;
 use crate::models::Claims;
+use crate::error::AppError;
 
 // ==========================================
 // 1. Top Products Report
@@ -25,10 +26,7 @@ pub struct TopProductItem {
 pub async fn get_top_products_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<TopProductItem>>, StatusCode> {
-    if !claims.roles.iter().any(|r| r == "role.hq.admin") {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<Vec<TopProductItem>>, AppError> {
 
     let hq_id = claims.hq_id;
 
@@ -95,10 +93,7 @@ pub struct OrderTrendData {
 pub async fn get_order_trend_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<OrderTrendData>, StatusCode> {
-    if !claims.roles.iter().any(|r| r == "role.hq.admin") {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<OrderTrendData>, AppError> {
 
     let hq_id = claims.hq_id;
 
@@ -155,10 +150,7 @@ pub struct FunnelData {
 pub async fn get_funnel_data_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<FunnelData>, StatusCode> {
-    if !claims.roles.iter().any(|r| r == "role.hq.admin") {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<FunnelData>, AppError> {
 
     let hq_id = claims.hq_id;
 
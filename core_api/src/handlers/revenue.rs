@@ -0,0 +1,374 @@
+/*
+ * src/handlers/revenue.rs
+ * 职责: 期限卡收入确认 (合同负债 → 收入)
+ * daily: 按有效期直线摊销, 由后台定时任务按天推进; per_class: 消课时按 售价 / 预计次数 确认, 到期后余额一次性确认
+ */
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use uuid::Uuid;
+
+use super::AppState;
+use super::ledger::{post_transfer, LedgerTransfer, ACCOUNT_CONTRACT_LIABILITY, ACCOUNT_REVENUE, SOURCE_MEMBERSHIP_REVENUE};
+use crate::models::{Claims, MembershipTier};
+use crate::error::AppError;
+
+pub const RECOGNITION_DAILY: &str = "daily";
+pub const RECOGNITION_PER_CLASS: &str = "per_class";
+
+// 默认每小时跑一次; 按整天推进, 同一天内重复运行不会重复记账
+const DEFAULT_JOB_INTERVAL_SECS: u64 = 3600;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateTierRecognitionPayload {
+    pub recognition_method: String,
+    pub expected_class_count: Option<i32>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RecognitionSummary {
+    pub memberships_processed: i64,
+    pub entries_posted: i64,
+    pub recognized_in_cents: i64,
+    pub failed: i64, // 单张卡失败只记日志并计数, 不影响其他卡
+}
+
+#[derive(Debug, FromRow)]
+struct RecognitionCandidate {
+    id: Uuid,
+    hq_id: Uuid,
+    base_id: Option<Uuid>,
+    start_date: DateTime<Utc>,
+    expiry_date: DateTime<Utc>,
+    frozen_at: Option<DateTime<Utc>>,
+    revenue_recognized_in_cents: i32,
+    revenue_recognized_until: Option<DateTime<Utc>>,
+    price_in_cents: i32,
+    recognition_method: String,
+    name_key: String,
+}
+
+pub fn is_valid_recognition(method: &str, expected_class_count: Option<i32>) -> bool {
+    match method {
+        RECOGNITION_DAILY => true,
+        RECOGNITION_PER_CLASS => expected_class_count.is_some_and(|n| n > 0),
+        _ => false,
+    }
+}
+
+// 计算本次应确认的金额与新的摊销进度
+fn plan_recognition(c: &RecognitionCandidate, now: DateTime<Utc>) -> (i32, DateTime<Utc>) {
+    let cursor = c.revenue_recognized_until.unwrap_or(c.start_date);
+    let remaining = (c.price_in_cents - c.revenue_recognized_in_cents).max(0);
+
+    // per_class: 平时由消课确认, 到期后 (未冻结) 把剩余负债一次性转收入
+    if c.recognition_method == RECOGNITION_PER_CLASS {
+        if c.frozen_at.is_none() && now >= c.expiry_date {
+            return (remaining, c.expiry_date);
+        }
+        return (0, cursor);
+    }
+
+    // daily: 冻结期间不摊销, 只摊到冻结时刻, 之后进度直接跳到当前 (解冻后到期日会顺延)
+    let end = c.frozen_at.unwrap_or(now).min(now).min(c.expiry_date);
+    if end >= c.expiry_date {
+        return (remaining, c.expiry_date);
+    }
+
+    let days = (end - cursor).num_days();
+    let span_seconds = (c.expiry_date - cursor).num_seconds();
+    let (amount, next_cursor) = if days > 0 && span_seconds > 0 {
+        let amount = remaining as i64 * (days * 86_400) / span_seconds;
+        (amount as i32, cursor + Duration::days(days))
+    } else {
+        (0, cursor)
+    };
+
+    match c.frozen_at {
+        Some(_) => (amount, next_cursor.max(now)),
+        None => (amount, next_cursor),
+    }
+}
+
+// 记一张 借 合同负债 / 贷 主营业务收入 凭证, 并累加该卡已确认金额
+pub async fn post_membership_revenue(
+    conn: &mut PgConnection,
+    hq_id: Uuid,
+    base_id: Option<Uuid>,
+    membership_id: Uuid,
+    amount_in_cents: i32,
+    description: &str,
+) -> Result<(), AppError> {
+    post_transfer(
+        conn,
+        LedgerTransfer {
+            hq_id,
+            base_id,
+            entry_date: None,
+            source_type: SOURCE_MEMBERSHIP_REVENUE,
+            source_id: Some(membership_id),
+            description: description.to_string(),
+            created_by: None,
+            debit: ACCOUNT_CONTRACT_LIABILITY,
+            credit: ACCOUNT_REVENUE,
+            amount_cents: amount_in_cents as i64,
+        },
+    )
+    .await?;
+
+    sqlx::query("UPDATE customer_memberships SET revenue_recognized_in_cents = revenue_recognized_in_cents + $2 WHERE id = $1")
+        .bind(membership_id)
+        .bind(amount_in_cents)
+        .execute(&mut *conn)
+        .await
+        .map_err(AppError::db("Failed to update recognized revenue"))?;
+    Ok(())
+}
+
+// 待确认收入的卡 ($1 = 当前时间, $3 = per_class); 扫描和逐张加锁时共用, 加锁后会重新判断一次
+const CANDIDATE_FILTER: &str = r#"
+    mt.tier_type = 'time_based'
+    AND COALESCE(cm.is_active, true) = true
+    AND cm.expiry_date IS NOT NULL
+    AND cm.revenue_recognized_in_cents < mt.price_in_cents
+    AND COALESCE(cm.revenue_recognized_until, cm.start_date) < LEAST($1, cm.expiry_date)
+    AND (mt.recognition_method <> $3 OR (cm.frozen_at IS NULL AND cm.expiry_date <= $1))
+"#;
+
+// 单张卡: 锁定后计算并入账 (每张卡一个事务, 避免长事务)
+async fn recognize_membership(pool: &PgPool, membership_id: Uuid, now: DateTime<Utc>) -> Result<i32, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let candidate = sqlx::query_as::<_, RecognitionCandidate>(&format!(
+        r#"
+        SELECT cm.id, cm.hq_id, c.base_id, cm.start_date, cm.expiry_date, cm.frozen_at,
+               cm.revenue_recognized_in_cents, cm.revenue_recognized_until,
+               mt.price_in_cents, mt.recognition_method, mt.name_key
+        FROM customer_memberships cm
+        JOIN membership_tiers mt ON cm.tier_id = mt.id
+        JOIN customers c ON cm.customer_id = c.id
+        WHERE cm.id = $2 AND {filter}
+        FOR UPDATE OF cm
+        "#,
+        filter = CANDIDATE_FILTER,
+    ))
+    .bind(now)
+    .bind(membership_id)
+    .bind(RECOGNITION_PER_CLASS)
+    .fetch_optional(&mut *tx)
+    .await?;
+    // 扫描之后、加锁之前卡可能已退款 / 冻结 / 结清: 不再符合条件就跳过
+    let Some(candidate) = candidate else {
+        return Ok(0);
+    };
+
+    let (amount, next_cursor) = plan_recognition(&candidate, now);
+
+    if amount > 0 {
+        let label = if candidate.recognition_method == RECOGNITION_PER_CLASS { "到期结转" } else { "按天摊销" };
+        post_membership_revenue(
+            &mut tx,
+            candidate.hq_id,
+            candidate.base_id,
+            candidate.id,
+            amount,
+            &format!("期限卡收入确认 ({}): {}", label, candidate.name_key),
+        )
+        .await?;
+    }
+
+    sqlx::query("UPDATE customer_memberships SET revenue_recognized_until = $2 WHERE id = $1")
+        .bind(candidate.id)
+        .bind(next_cursor)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(amount)
+}
+
+// 跑一轮收入确认 (定时任务 & 手动触发共用)
+// per_class 的卡平时在消课时已确认收入, 这里只挑已到期 (且未冻结) 待结转余额的卡
+pub async fn run_revenue_recognition(pool: &PgPool, hq_id: Option<Uuid>) -> Result<RecognitionSummary, AppError> {
+    let now = Utc::now();
+    let ids: Vec<Uuid> = sqlx::query_scalar(&format!(
+        r#"
+        SELECT cm.id
+        FROM customer_memberships cm
+        JOIN membership_tiers mt ON cm.tier_id = mt.id
+        WHERE {filter}
+          AND ($2::uuid IS NULL OR cm.hq_id = $2)
+        "#,
+        filter = CANDIDATE_FILTER,
+    ))
+    .bind(now)
+    .bind(hq_id)
+    .bind(RECOGNITION_PER_CLASS)
+    .fetch_all(pool)
+    .await?;
+
+    let mut summary = RecognitionSummary::default();
+    for id in ids {
+        summary.memberships_processed += 1;
+        match recognize_membership(pool, id, now).await {
+            Ok(amount) if amount > 0 => {
+                summary.entries_posted += 1;
+                summary.recognized_in_cents += amount as i64;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::error!("Revenue recognition failed for membership {}: {:?}", id, e);
+                summary.failed += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+// 启动后台定时任务 (间隔可用 REVENUE_RECOGNITION_INTERVAL_SECS 配置)
+pub fn spawn_revenue_recognition_job(pool: PgPool) {
+    let interval_secs = std::env::var("REVENUE_RECOGNITION_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_JOB_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            match run_revenue_recognition(&pool, None).await {
+                Ok(summary) if summary.entries_posted > 0 || summary.failed > 0 => tracing::info!(
+                    "Revenue recognition: {} entries, {} cents, {} failed",
+                    summary.entries_posted,
+                    summary.recognized_in_cents,
+                    summary.failed
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::error!("Revenue recognition job failed: {:?}", e),
+            }
+        }
+    });
+}
+
+// (POST /api/v1/finance/revenue-recognition/run) 手动触发本总部的收入确认
+pub async fn run_revenue_recognition_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<RecognitionSummary>, AppError> {
+
+    let summary = run_revenue_recognition(&state.db_pool, Some(claims.hq_id)).await?;
+    Ok(Json(summary))
+}
+
+// (PATCH /api/v1/membership-tiers/:id/recognition) 配置卡种的收入确认方式
+pub async fn update_tier_recognition_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(tier_id): Path<Uuid>,
+    Json(payload): Json<UpdateTierRecognitionPayload>,
+) -> Result<Json<MembershipTier>, AppError> {
+    if !is_valid_recognition(&payload.recognition_method, payload.expected_class_count) {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    let tier = sqlx::query_as::<_, MembershipTier>(
+        "UPDATE membership_tiers SET recognition_method = $1, expected_class_count = $2 WHERE id = $3 AND hq_id = $4 RETURNING *",
+    )
+    .bind(&payload.recognition_method)
+    .bind(payload.expected_class_count)
+    .bind(tier_id)
+    .bind(claims.hq_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to update tier recognition"))?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    Ok(Json(tier))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap() + Duration::days(d as i64 - 1)
+    }
+
+    // 1 月 1 日开卡, 100 天有效期, 售价 1000 元
+    fn card(method: &str) -> RecognitionCandidate {
+        RecognitionCandidate {
+            id: Uuid::nil(),
+            hq_id: Uuid::nil(),
+            base_id: None,
+            start_date: at(1),
+            expiry_date: at(101),
+            frozen_at: None,
+            revenue_recognized_in_cents: 0,
+            revenue_recognized_until: None,
+            price_in_cents: 100_000,
+            recognition_method: method.to_string(),
+            name_key: String::new(),
+        }
+    }
+
+    #[test]
+    fn daily_recognizes_whole_days_only() {
+        let c = card(RECOGNITION_DAILY);
+        assert_eq!(plan_recognition(&c, at(11)), (10_000, at(11)));
+        // 不足一天不推进
+        assert_eq!(plan_recognition(&c, at(1) + Duration::hours(23)), (0, at(1)));
+        assert_eq!(plan_recognition(&c, at(11) + Duration::hours(5)), (10_000, at(11)));
+    }
+
+    #[test]
+    fn daily_continues_from_cursor_over_the_remaining_balance() {
+        let mut c = card(RECOGNITION_DAILY);
+        c.revenue_recognized_in_cents = 10_000;
+        c.revenue_recognized_until = Some(at(11));
+        assert_eq!(plan_recognition(&c, at(21)), (10_000, at(21)));
+
+        // 余额按剩余天数摊: 每天向下取整, 尾差留到到期一次性确认
+        c.price_in_cents = 100_001;
+        assert_eq!(plan_recognition(&c, at(12)), (1_000, at(12)));
+        assert_eq!(plan_recognition(&c, at(101)), (90_001, at(101)));
+    }
+
+    #[test]
+    fn daily_stops_at_freeze_and_skips_frozen_period() {
+        let mut c = card(RECOGNITION_DAILY);
+        c.frozen_at = Some(at(31));
+        // 冻结前的 30 天照常确认, 进度跳到当前, 冻结期间不再确认
+        assert_eq!(plan_recognition(&c, at(41)), (30_000, at(41)));
+
+        c.revenue_recognized_in_cents = 30_000;
+        c.revenue_recognized_until = Some(at(41));
+        assert_eq!(plan_recognition(&c, at(51)), (0, at(51)));
+    }
+
+    #[test]
+    fn daily_recognizes_remaining_balance_at_expiry() {
+        let mut c = card(RECOGNITION_DAILY);
+        c.revenue_recognized_in_cents = 99_000;
+        c.revenue_recognized_until = Some(at(100));
+        assert_eq!(plan_recognition(&c, at(200)), (1_000, at(101)));
+    }
+
+    #[test]
+    fn per_class_only_settles_balance_after_expiry() {
+        let mut c = card(RECOGNITION_PER_CLASS);
+        c.revenue_recognized_in_cents = 40_000; // 消课时已确认
+        assert_eq!(plan_recognition(&c, at(50)), (0, at(1)));
+        assert_eq!(plan_recognition(&c, at(101)), (60_000, at(101)));
+
+        // 冻结中的卡到期日会顺延, 不结转
+        c.frozen_at = Some(at(90));
+        assert_eq!(plan_recognition(&c, at(120)), (0, at(1)));
+    }
+}
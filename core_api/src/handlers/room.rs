@@ -12,35 +12,31 @@ use axum::{
 use uuid::Uuid; // (★ 修复: 添加 Uuid)
 
 use super::AppState;
+use crate::tenant::{Owned, TenantScope};
 // (★ 修复: 添加 UpdateRoomPayload)
 use crate::models::{Claims, Room, CreateRoomPayload, UpdateRoomPayload};
+use crate::error::AppError;
 
 // (GET) 获取教室
 pub async fn get_rooms_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<Room>>, StatusCode> {
+) -> Result<Json<Vec<Room>>, AppError> {
     let hq_id = claims.hq_id;
-    let is_hq = claims.roles.iter().any(|r| r == "role.hq.admin");
-    let is_base = claims.roles.iter().any(|r| r == "role.base.admin");
-
-    if !is_hq && !is_base { return Err(StatusCode::FORBIDDEN); }
+    let is_hq = claims.base_id.is_none();
 
     let rooms = if is_hq {
         sqlx::query_as::<_, Room>("SELECT * FROM rooms WHERE hq_id = $1 ORDER BY base_id, name ASC")
             .bind(hq_id).fetch_all(&state.db_pool).await
     } else {
-        let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+        let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
         sqlx::query_as::<_, Room>("SELECT * FROM rooms WHERE hq_id = $1 AND base_id = $2 ORDER BY name ASC")
             .bind(hq_id).bind(base_id).fetch_all(&state.db_pool).await
     };
 
     match rooms {
         Ok(r) => Ok(Json(r)),
-        Err(e) => {
-            tracing::error!("Failed to fetch rooms: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        Err(e) => Err(AppError::Database { context: "Failed to fetch rooms", source: e }),
     }
 }
 
@@ -49,22 +45,19 @@ pub async fn create_room_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateRoomPayload>,
-) -> Result<Json<Room>, StatusCode> {
-    let is_hq = claims.roles.iter().any(|r| r == "role.hq.admin");
-    let is_base = claims.roles.iter().any(|r| r == "role.base.admin");
-    if !is_hq && !is_base { return Err(StatusCode::FORBIDDEN); }
+) -> Result<Json<Room>, AppError> {
+    let is_hq = claims.base_id.is_none();
 
-    let final_base_id = if is_hq { payload.base_id } else { claims.base_id.ok_or(StatusCode::FORBIDDEN)? };
+    let final_base_id = if is_hq { payload.base_id } else { claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))? };
+    // 请求里指定的基地也要校验, 基地账号不能借别的基地 id 建教室
+    TenantScope::from_claims(&claims).ensure_owned(&state.db_pool, Owned::Base(payload.base_id)).await?;
 
     let new_room = sqlx::query_as::<_, Room>(
         r#"INSERT INTO rooms (hq_id, base_id, name, capacity, layout_rows, layout_columns, is_schedulable) VALUES ($1, $2, $3, $4, $5, $6, true) RETURNING *"#
     )
     .bind(claims.hq_id).bind(final_base_id).bind(&payload.name).bind(payload.capacity)
     .bind(payload.layout_rows.unwrap_or(5)).bind(payload.layout_columns.unwrap_or(6))
-    .fetch_one(&state.db_pool).await.map_err(|e| {
-        tracing::error!("Failed to create room: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .fetch_one(&state.db_pool).await.map_err(AppError::db("Failed to create room"))?;
 
     Ok(Json(new_room))
 }
@@ -75,20 +68,13 @@ pub async fn update_room_handler(
     claims: Claims,
     Path(room_id): Path<Uuid>,
     Json(payload): Json<UpdateRoomPayload>,
-) -> Result<Json<Room>, StatusCode> {
-    if claims.base_id.is_none() && !claims.roles.contains(&"role.hq.admin".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
+) -> Result<Json<Room>, AppError> {
     let updated = sqlx::query_as::<_, Room>(
         r#"UPDATE rooms SET name = $1, capacity = $2, layout_rows = $3, layout_columns = $4 WHERE id = $5 AND hq_id = $6 RETURNING *"#
     )
     .bind(&payload.name).bind(payload.capacity).bind(payload.layout_rows).bind(payload.layout_columns)
     .bind(room_id).bind(claims.hq_id)
-    .fetch_one(&state.db_pool).await.map_err(|e| {
-        tracing::error!("Failed to update room: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .fetch_one(&state.db_pool).await.map_err(AppError::db("Failed to update room"))?;
 
     Ok(Json(updated))
 }
@@ -98,20 +84,16 @@ pub async fn delete_room_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(room_id): Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
-    if claims.base_id.is_none() && !claims.roles.contains(&"role.hq.admin".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
+) -> Result<StatusCode, AppError> {
     let active_classes = sqlx::query_scalar::<_, i64>(
         "SELECT COUNT(*) FROM classes WHERE room_id = $1 AND status = 'scheduled' AND start_time > NOW()"
     ).bind(room_id).fetch_one(&state.db_pool).await.unwrap_or(0);
 
-    if active_classes > 0 { return Err(StatusCode::CONFLICT); }
+    if active_classes > 0 { return Err(AppError::Conflict("room.in_use")); }
 
     let res = sqlx::query("DELETE FROM rooms WHERE id = $1 AND hq_id = $2")
-        .bind(room_id).bind(claims.hq_id).execute(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .bind(room_id).bind(claims.hq_id).execute(&state.db_pool).await?;
 
-    if res.rows_affected() == 0 { return Err(StatusCode::NOT_FOUND); }
+    if res.rows_affected() == 0 { return Err(AppError::NotFound("resource.not_found")); }
     Ok(StatusCode::NO_CONTENT)
 }
\ No newline at end of file
@@ -4,36 +4,43 @@
  * (★ V13.5 - 修复 DateTime 导入缺失 ★)
  */
 use axum::{extract::{State, Path}, http::StatusCode, Json};
+use sqlx::FromRow;
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 // (★ 修复: 添加 DateTime)
-use chrono::{Datelike, Utc, DateTime}; 
+use chrono::{Datelike, Utc, DateTime, NaiveDate}; 
 
 
-use super::AppState;
+use super::schedule_solver::{solve_schedule, BusySlot, SolverInput};
+use super::{find_schedule_conflicts, preview_schedule_conflicts, AppState, ProposedSlot, ScheduleConflict, schedule_conflict_error};
+use crate::tenant::{Owned, TenantScope};
 use crate::models::{
     Claims,
     TeacherSkill, TeacherAvailability, 
     UpdateTeacherSkillsPayload, CreateAvailabilityPayload
 };
+use crate::error::AppError;
+use crate::timezone::business_date;
 
 // --- AI 通信使用的临时结构体 ---
+// (同时也是本地求解器 schedule_solver.rs 的输入)
 #[derive(Serialize)]
-struct AiRequest {
+struct AiRequest<'a> {
     base_id: Uuid,
     start_date: String,
-    teachers: Vec<AiTeacher>,
-    courses: Vec<AiCourse>,
-    rooms: Vec<AiRoom>,
+    teachers: &'a [AiTeacher],
+    courses: &'a [AiCourse],
+    rooms: &'a [AiRoom],
     density: i32,
+    end_date: String,
 }
 #[derive(Serialize)]
-struct AiTeacher { id: Uuid, name: String, skills: Vec<Uuid>, availability: Vec<TeacherAvailability> }
+pub(crate) struct AiTeacher { pub id: Uuid, pub name: String, pub skills: Vec<Uuid>, pub availability: Vec<TeacherAvailability> }
 #[derive(Serialize)]
-struct AiCourse { id: Uuid, name: String, duration: i32 }
+pub(crate) struct AiCourse { pub id: Uuid, pub name: String, pub duration: i32 }
 #[derive(Serialize)]
-struct AiRoom { id: Uuid, name: String, capacity: i32 }
+pub(crate) struct AiRoom { pub id: Uuid, pub name: String, pub capacity: i32 }
 
 #[derive(Deserialize)]
 struct AiResponse {
@@ -48,21 +55,100 @@ struct AiResultClass {
     end_time: String,   
 }
 
+// --- 排课参数 (替代原先写死的 density=3 / 本周一 / 容量 10) ---
+#[derive(Debug, Default, Deserialize)]
+pub struct AutoScheduleParams {
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub density: Option<i32>,
+    pub max_capacity: Option<i32>,
+    pub engine: Option<String>, // auto (默认: AI 优先, 不可用时本地兜底) / ai / local
+}
+
+struct ResolvedScheduleParams {
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    density: i32,
+    max_capacity: i32,
+    engine: String,
+}
+
+// 解析后的排课建议 (时间已转为 UTC)
+pub(crate) struct ProposedClass {
+    pub course_id: Uuid,
+    pub teacher_id: Uuid,
+    pub room_id: Uuid,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+}
+
+// --- 草稿 DTO ---
+#[derive(Debug, Serialize, FromRow)]
+pub struct ScheduleDraft {
+    pub id: Uuid,
+    pub hq_id: Uuid,
+    pub base_id: Uuid,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub density: i32,
+    pub max_capacity: i32,
+    pub engine: String,
+    pub status: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub committed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ScheduleDraftItem {
+    pub id: Uuid,
+    pub draft_id: Uuid,
+    pub course_id: Uuid,
+    pub teacher_id: Uuid,
+    pub room_id: Uuid,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub max_capacity: i32,
+    pub class_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleDraftItemView {
+    #[serde(flatten)]
+    pub item: ScheduleDraftItem,
+    pub conflicts: Vec<ScheduleConflict>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleDraftDetail {
+    pub draft: ScheduleDraft,
+    pub items: Vec<ScheduleDraftItemView>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateDraftItemPayload {
+    pub course_id: Option<Uuid>,
+    pub teacher_id: Option<Uuid>,
+    pub room_id: Option<Uuid>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub max_capacity: Option<i32>,
+}
+
 // 1. (GET) 获取老师配置
 pub async fn get_teacher_config_handler(
     State(state): State<AppState>,
-    claims: Claims,
+    scope: TenantScope,
     Path(teacher_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let _ = claims.hq_id;
+) -> Result<Json<serde_json::Value>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::User(teacher_id)).await?;
 
     let skills = sqlx::query_as::<_, TeacherSkill>(
         r#"SELECT tqc.course_id, c.name_key as course_name FROM teacher_qualified_courses tqc JOIN courses c ON tqc.course_id = c.id WHERE tqc.teacher_id = $1"#
-    ).bind(teacher_id).fetch_all(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    ).bind(teacher_id).fetch_all(&state.db_pool).await?;
 
     let availability = sqlx::query_as::<_, TeacherAvailability>(
         r#"SELECT * FROM teacher_availability WHERE teacher_id = $1 ORDER BY day_of_week ASC, start_time ASC"#
-    ).bind(teacher_id).fetch_all(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    ).bind(teacher_id).fetch_all(&state.db_pool).await?;
 
     Ok(Json(json!({ "skills": skills, "availability": availability })))
 }
@@ -70,54 +156,81 @@ pub async fn get_teacher_config_handler(
 // 2. (PUT) 更新技能
 pub async fn update_teacher_skills_handler(
     State(state): State<AppState>,
-    claims: Claims,
+    scope: TenantScope,
     Path(teacher_id): Path<Uuid>,
     Json(payload): Json<UpdateTeacherSkillsPayload>,
-) -> Result<StatusCode, StatusCode> {
-    if !claims.roles.iter().any(|r| r.contains("admin")) { return Err(StatusCode::FORBIDDEN); }
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<StatusCode, AppError> {
+    let mut tx = state.db_pool.begin().await?;
+    scope.ensure_owned(&mut *tx, Owned::User(teacher_id)).await?;
     
-    sqlx::query("DELETE FROM teacher_qualified_courses WHERE teacher_id = $1").bind(teacher_id).execute(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sqlx::query("DELETE FROM teacher_qualified_courses WHERE teacher_id = $1").bind(teacher_id).execute(&mut *tx).await?;
     for course_id in payload.course_ids {
-        sqlx::query("INSERT INTO teacher_qualified_courses (teacher_id, course_id) VALUES ($1, $2)").bind(teacher_id).bind(course_id).execute(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        scope.ensure_visible(&mut *tx, Owned::Course(course_id)).await?;
+        sqlx::query("INSERT INTO teacher_qualified_courses (teacher_id, course_id) VALUES ($1, $2)").bind(teacher_id).bind(course_id).execute(&mut *tx).await?;
     }
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
     Ok(StatusCode::OK)
 }
 
 // 3. (POST) 新增时间
 pub async fn add_teacher_availability_handler(
     State(state): State<AppState>,
-    _claims: Claims,
+    scope: TenantScope,
     Path(teacher_id): Path<Uuid>,
     Json(payload): Json<CreateAvailabilityPayload>,
-) -> Result<StatusCode, StatusCode> {
-    let start = chrono::NaiveTime::parse_from_str(&payload.start_time, "%H:%M").map_err(|_| StatusCode::BAD_REQUEST)?;
-    let end = chrono::NaiveTime::parse_from_str(&payload.end_time, "%H:%M").map_err(|_| StatusCode::BAD_REQUEST)?;
+) -> Result<StatusCode, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::User(teacher_id)).await?;
+    let start = chrono::NaiveTime::parse_from_str(&payload.start_time, "%H:%M").map_err(|_| AppError::BadRequest("request.invalid"))?;
+    let end = chrono::NaiveTime::parse_from_str(&payload.end_time, "%H:%M").map_err(|_| AppError::BadRequest("request.invalid"))?;
     sqlx::query("INSERT INTO teacher_availability (teacher_id, day_of_week, start_time, end_time) VALUES ($1, $2, $3, $4)")
-        .bind(teacher_id).bind(payload.day_of_week).bind(start).bind(end).execute(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .bind(teacher_id).bind(payload.day_of_week).bind(start).bind(end).execute(&state.db_pool).await?;
     Ok(StatusCode::CREATED)
 }
 
 // 4. (DELETE) 删除时间
 pub async fn delete_teacher_availability_handler(
     State(state): State<AppState>,
-    _claims: Claims,
+    scope: TenantScope,
     Path(availability_id): Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
-    sqlx::query("DELETE FROM teacher_availability WHERE id = $1").bind(availability_id).execute(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<StatusCode, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::TeacherAvailability(availability_id)).await?;
+    sqlx::query("DELETE FROM teacher_availability WHERE id = $1").bind(availability_id).execute(&state.db_pool).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
-// 5. (POST) ★ 核心: 触发 AI 自动排课
-pub async fn trigger_auto_schedule_handler(
-    State(state): State<AppState>,
-    claims: Claims,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
-    let hq_id = claims.hq_id;
+// --- 排课工具函数 ---
+
+// 默认: 本周一起排 7 天, 密度 3, 每节 10 人; 单次最多排 31 天
+fn resolve_schedule_params(params: AutoScheduleParams) -> Result<ResolvedScheduleParams, AppError> {
+    let start_date = params.start_date.unwrap_or_else(|| {
+        let today = business_date(Utc::now());
+        today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64)
+    });
+    let end_date = params.end_date.unwrap_or(start_date + chrono::Duration::days(6));
+    let density = params.density.unwrap_or(3);
+    let max_capacity = params.max_capacity.unwrap_or(10);
+    let engine = params.engine.unwrap_or_else(|| "auto".to_string());
 
+    if end_date < start_date
+        || (end_date - start_date).num_days() > 30
+        || !(1..=10).contains(&density)
+        || max_capacity <= 0
+        || !matches!(engine.as_str(), "auto" | "ai" | "local")
+    {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+
+    Ok(ResolvedScheduleParams { start_date, end_date, density, max_capacity, engine })
+}
+
+// 收集基地的课程/教室/老师, 按 engine 调用 AI 服务或本地求解器生成排课建议
+// 返回 (排课建议, 实际使用的引擎)
+async fn generate_schedule(
+    state: &AppState,
+    hq_id: Uuid,
+    base_id: Uuid,
+    params: &ResolvedScheduleParams,
+) -> Result<(Vec<ProposedClass>, &'static str), AppError> {
     // --- A. 数据准备 ---
     
     // 1. 课程
@@ -125,20 +238,20 @@ pub async fn trigger_auto_schedule_handler(
         AiCourse,
         "SELECT id, name_key as name, default_duration_minutes as duration FROM courses WHERE hq_id = $1 AND is_active = true",
         hq_id
-    ).fetch_all(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    ).fetch_all(&state.db_pool).await?;
 
     // 2. 教室 (使用 COALESCE 和 as "capacity!" 强制非空)
     let rooms = sqlx::query_as!(
         AiRoom,
         r#"SELECT id, name, COALESCE(capacity, 10) as "capacity!" FROM rooms WHERE base_id = $1"#,
         base_id
-    ).fetch_all(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    ).fetch_all(&state.db_pool).await?;
 
     // 3. 老师
     let raw_teachers = sqlx::query!(
         "SELECT t.user_id as id, u.full_name as name FROM teachers t JOIN users u ON t.user_id = u.id WHERE t.base_id = $1 AND t.is_active = true",
         base_id
-    ).fetch_all(&state.db_pool).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    ).fetch_all(&state.db_pool).await?;
 
     let mut ai_teachers = Vec::new();
     for t in raw_teachers {
@@ -158,22 +271,91 @@ pub async fn trigger_auto_schedule_handler(
         });
     }
 
-    let today = Utc::now().date_naive();
-    let days_from_monday = today.weekday().num_days_from_monday();
-    let monday = today - chrono::Duration::days(days_from_monday as i64);
+    // --- B. 选择引擎 ---
+    let ai_result = match params.engine.as_str() {
+        "local" => None,
+        _ => Some(request_ai_schedule(state, base_id, params, &ai_teachers, &courses, &rooms).await),
+    };
 
-    // --- B. 调用 AI ---
+    match ai_result {
+        Some(Ok(results)) => Ok((results, "ai")),
+        Some(Err(e)) if params.engine == "ai" => Err(e),
+        _ => {
+            if params.engine == "auto" {
+                tracing::warn!("AI schedule failed, falling back to local solver for base {}", base_id);
+            }
+            let busy = load_busy_slots(state, base_id, params, &ai_teachers).await?;
+            let results = solve_schedule(SolverInput {
+                teachers: &ai_teachers,
+                courses: &courses,
+                rooms: &rooms,
+                busy,
+                start_date: params.start_date,
+                end_date: params.end_date,
+                density: params.density,
+                max_capacity: params.max_capacity,
+            });
+            Ok((results, "local"))
+        }
+    }
+}
+
+// 本地求解器需要知道的已有占用: 本基地教室 + 本基地老师 (含其在其他基地的课)
+async fn load_busy_slots(
+    state: &AppState,
+    base_id: Uuid,
+    params: &ResolvedScheduleParams,
+    teachers: &[AiTeacher],
+) -> Result<Vec<BusySlot>, AppError> {
+    let teacher_ids: Vec<Uuid> = teachers.iter().map(|t| t.id).collect();
+    // 时区差异下多取一天余量
+    let from = (params.start_date - chrono::Duration::days(1)).and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let to = (params.end_date + chrono::Duration::days(2)).and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+
+    let rows: Vec<(Uuid, DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+        r#"
+        SELECT c.room_id, c.start_time, c.end_time
+        FROM classes c
+        WHERE c.base_id = $1 AND COALESCE(c.status, 'scheduled') <> 'cancelled'
+          AND c.start_time < $3 AND c.end_time > $2
+        UNION ALL
+        SELECT ct.teacher_id, c.start_time, c.end_time
+        FROM class_teachers ct
+        JOIN classes c ON ct.class_id = c.id
+        WHERE ct.teacher_id = ANY($4) AND COALESCE(c.status, 'scheduled') <> 'cancelled'
+          AND c.start_time < $3 AND c.end_time > $2
+        "#
+    )
+    .bind(base_id)
+    .bind(from)
+    .bind(to)
+    .bind(&teacher_ids)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to load busy slots"))?;
+
+    Ok(rows.into_iter().map(|(resource_id, start_time, end_time)| BusySlot { resource_id, start_time, end_time }).collect())
+}
+
+async fn request_ai_schedule(
+    state: &AppState,
+    base_id: Uuid,
+    params: &ResolvedScheduleParams,
+    teachers: &[AiTeacher],
+    courses: &[AiCourse],
+    rooms: &[AiRoom],
+) -> Result<Vec<ProposedClass>, AppError> {
     let payload = AiRequest {
         base_id,
-        start_date: monday.to_string(),
-        teachers: ai_teachers,
+        start_date: params.start_date.to_string(),
+        teachers,
         courses,
         rooms,
-        density: 3,
+        density: params.density,
+        end_date: params.end_date.to_string(),
     };
 
-    let client = reqwest::Client::new();
-    let ai_res = client.post(format!("{}/schedule/generate", state.ai_api_url))
+    let ai_res = state.http_client.post(format!("{}/schedule/generate", state.ai_api_url))
         .json(&payload)
         .send()
         .await
@@ -183,51 +365,527 @@ pub async fn trigger_auto_schedule_handler(
         })?;
 
     if !ai_res.status().is_success() {
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        tracing::error!("AI Service returned {}", ai_res.status());
+        return Err(StatusCode::BAD_GATEWAY.into());
     }
 
-    let ai_data: AiResponse = ai_res.json().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let ai_data: AiResponse = ai_res.json().await.map_err(|_| StatusCode::BAD_GATEWAY)?;
 
-    // --- C. 写入结果 ---
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let mut count = 0;
+    ai_data.results.into_iter().map(|cls| {
+        let start = DateTime::parse_from_rfc3339(&cls.start_time).map_err(|_| StatusCode::BAD_GATEWAY)?.with_timezone(&Utc);
+        let end = DateTime::parse_from_rfc3339(&cls.end_time).map_err(|_| StatusCode::BAD_GATEWAY)?.with_timezone(&Utc);
+        Ok(ProposedClass {
+            course_id: cls.course_id,
+            teacher_id: cls.teacher_id,
+            room_id: cls.room_id,
+            start_time: start,
+            end_time: end,
+        })
+    }).collect()
+}
 
-    for cls in ai_data.results {
-        let start = DateTime::parse_from_rfc3339(&cls.start_time).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?.with_timezone(&Utc);
-        let end = DateTime::parse_from_rfc3339(&cls.end_time).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?.with_timezone(&Utc);
+async fn is_teacher_qualified(
+    conn: &mut sqlx::PgConnection,
+    teacher_id: Uuid,
+    course_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM teacher_qualified_courses WHERE teacher_id = $1 AND course_id = $2)")
+        .bind(teacher_id)
+        .bind(course_id)
+        .fetch_one(&mut *conn)
+        .await
+}
+
+// 草稿内部的教室 / 老师重叠: 只与排在前面且本身可排的条目比较 (与逐条写入的结果一致)
+fn draft_overlaps(item: &ScheduleDraftItem, accepted: &[&ScheduleDraftItem]) -> Vec<ScheduleConflict> {
+    let mut conflicts = Vec::new();
+    for other in accepted.iter().filter(|o| o.start_time < item.end_time && o.end_time > item.start_time) {
+        if other.room_id == item.room_id {
+            conflicts.push(ScheduleConflict {
+                kind: "room_booked".to_string(),
+                resource_id: item.room_id,
+                conflicting_class_id: None,
+                start_time: item.start_time,
+                end_time: item.end_time,
+                message: "教室在该时段已被草稿中的其他排课占用".to_string(),
+            });
+        }
+        if other.teacher_id == item.teacher_id {
+            conflicts.push(ScheduleConflict {
+                kind: "teacher_booked".to_string(),
+                resource_id: item.teacher_id,
+                conflicting_class_id: None,
+                start_time: item.start_time,
+                end_time: item.end_time,
+                message: "老师在该时段已有草稿中的其他排课".to_string(),
+            });
+        }
+    }
+    conflicts
+}
+
+// 逐条校验草稿 (不写库): 老师资质、与已有排课的冲突、草稿内部互相冲突
+// 预览和提交共用: 预览 (lock = false) 只读不加锁; 写入前 (lock = true) 锁住教室 / 老师, 须在同一事务内校验后再写入
+async fn check_draft_items(
+    conn: &mut sqlx::PgConnection,
+    items: Vec<ScheduleDraftItem>,
+    lock: bool,
+) -> Result<Vec<ScheduleDraftItemView>, sqlx::Error> {
+    let mut views: Vec<ScheduleDraftItemView> = Vec::with_capacity(items.len());
+
+    for item in items {
+        let mut conflicts = Vec::new();
+        if !is_teacher_qualified(&mut *conn, item.teacher_id, item.course_id).await? {
+            conflicts.push(ScheduleConflict {
+                kind: "teacher_unqualified".to_string(),
+                resource_id: item.teacher_id,
+                conflicting_class_id: None,
+                start_time: item.start_time,
+                end_time: item.end_time,
+                message: "老师不具备该课程的授课资质".to_string(),
+            });
+        }
+
+        let teacher_ids = [item.teacher_id];
+        let slot = ProposedSlot {
+            class_id: None,
+            room_id: item.room_id,
+            teacher_ids: &teacher_ids,
+            start_time: item.start_time,
+            end_time: item.end_time,
+        };
+        conflicts.extend(if lock {
+            find_schedule_conflicts(&mut *conn, &slot).await?
+        } else {
+            preview_schedule_conflicts(&mut *conn, &slot).await?
+        });
+
+        let accepted: Vec<&ScheduleDraftItem> =
+            views.iter().filter(|v| v.conflicts.is_empty()).map(|v| &v.item).collect();
+        conflicts.extend(draft_overlaps(&item, &accepted));
 
+        views.push(ScheduleDraftItemView { item, conflicts });
+    }
+
+    Ok(views)
+}
+
+// 把校验通过的草稿条目写入 classes, 回填 class_id
+async fn insert_draft_classes(
+    conn: &mut sqlx::PgConnection,
+    draft: &ScheduleDraft,
+    views: &mut [ScheduleDraftItemView],
+) -> Result<(), sqlx::Error> {
+    for view in views.iter_mut() {
+        let item = &mut view.item;
         let class_id: Uuid = sqlx::query_scalar(
             r#"
             INSERT INTO classes (hq_id, base_id, course_id, room_id, start_time, end_time, max_capacity, status)
-            VALUES ($1, $2, $3, $4, $5, $6, 10, 'scheduled')
+            VALUES ($1, $2, $3, $4, $5, $6, $7, 'scheduled')
             RETURNING id
             "#
         )
-        .bind(hq_id)
-        .bind(base_id)
-        .bind(cls.course_id)
-        .bind(cls.room_id)
-        .bind(start)
-        .bind(end)
-        .fetch_one(&mut *tx)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .bind(draft.hq_id)
+        .bind(draft.base_id)
+        .bind(item.course_id)
+        .bind(item.room_id)
+        .bind(item.start_time)
+        .bind(item.end_time)
+        .bind(item.max_capacity)
+        .fetch_one(&mut *conn)
+        .await?;
 
         sqlx::query("INSERT INTO class_teachers (class_id, teacher_id) VALUES ($1, $2)")
             .bind(class_id)
-            .bind(cls.teacher_id)
-            .execute(&mut *tx)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            
-        count += 1;
+            .bind(item.teacher_id)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query("UPDATE schedule_draft_items SET class_id = $1 WHERE id = $2")
+            .bind(class_id)
+            .bind(item.id)
+            .execute(&mut *conn)
+            .await?;
+
+        item.class_id = Some(class_id);
     }
+    Ok(())
+}
+
+// for_update: 修改 / 提交草稿时锁住草稿行; 只读预览不加锁
+async fn fetch_draft(
+    conn: &mut sqlx::PgConnection,
+    draft_id: Uuid,
+    hq_id: Uuid,
+    base_id: Uuid,
+    for_update: bool,
+) -> Result<(ScheduleDraft, Vec<ScheduleDraftItem>), AppError> {
+    let draft = sqlx::query_as::<_, ScheduleDraft>(&format!(
+        "SELECT id, hq_id, base_id, start_date, end_date, density, max_capacity, engine, status, created_at, committed_at FROM schedule_drafts WHERE id = $1 AND hq_id = $2 AND base_id = $3{}",
+        if for_update { " FOR UPDATE" } else { "" }
+    ))
+    .bind(draft_id).bind(hq_id).bind(base_id)
+    .fetch_optional(&mut *conn).await
+    .map_err(AppError::db("Failed to fetch schedule draft"))?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let items = sqlx::query_as::<_, ScheduleDraftItem>(
+        "SELECT * FROM schedule_draft_items WHERE draft_id = $1 ORDER BY start_time ASC"
+    )
+    .bind(draft_id)
+    .fetch_all(&mut *conn).await
+    .map_err(AppError::db("Failed to fetch schedule draft items"))?;
+
+    Ok((draft, items))
+}
+
+// 把排课建议存为草稿 (status = draft), 返回草稿 id
+async fn save_draft(
+    conn: &mut sqlx::PgConnection,
+    claims: &Claims,
+    base_id: Uuid,
+    params: &ResolvedScheduleParams,
+    engine: &str,
+    proposals: Vec<ProposedClass>,
+) -> Result<Uuid, AppError> {
+    let draft_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO schedule_drafts (hq_id, base_id, start_date, end_date, density, max_capacity, engine, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id
+        "#
+    )
+    .bind(claims.hq_id)
+    .bind(base_id)
+    .bind(params.start_date)
+    .bind(params.end_date)
+    .bind(params.density)
+    .bind(params.max_capacity)
+    .bind(engine)
+    .bind(Uuid::parse_str(&claims.sub).ok())
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to create schedule draft"))?;
+
+    for cls in proposals {
+        sqlx::query(
+            r#"
+            INSERT INTO schedule_draft_items (draft_id, course_id, teacher_id, room_id, start_time, end_time, max_capacity)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#
+        )
+        .bind(draft_id)
+        .bind(cls.course_id)
+        .bind(cls.teacher_id)
+        .bind(cls.room_id)
+        .bind(cls.start_time)
+        .bind(cls.end_time)
+        .bind(params.max_capacity)
+        .execute(&mut *conn)
+        .await
+        .map_err(AppError::db("Failed to save draft item"))?;
+    }
+
+    Ok(draft_id)
+}
+
+// 5. (POST) ★ 核心: 触发自动排课 (直接写入, 冲突条目跳过)
+pub async fn trigger_auto_schedule_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    params: Option<Json<AutoScheduleParams>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    let hq_id = claims.hq_id;
+
+    let params = resolve_schedule_params(params.map(|Json(p)| p).unwrap_or_default())?;
+    let (proposals, engine) = generate_schedule(&state, hq_id, base_id, &params).await?;
+
+    // --- C. 写入结果 ---
+    // (★ AI 结果不可信: 先存为草稿, 与提交草稿走同一校验 (老师资质 + 冲突, 加锁), 冲突的条目跳过并返回明细)
+    let mut tx = state.db_pool.begin().await?;
+    let draft_id = save_draft(&mut tx, &claims, base_id, &params, engine, proposals).await?;
+    let (draft, items) = fetch_draft(&mut tx, draft_id, hq_id, base_id, true).await?;
+    let views = check_draft_items(&mut tx, items, true).await
+        .map_err(AppError::db("Failed to check schedule conflicts"))?;
+
+    let (mut accepted, skipped): (Vec<_>, Vec<_>) = views.into_iter().partition(|v| v.conflicts.is_empty());
+    insert_draft_classes(&mut tx, &draft, &mut accepted).await
+        .map_err(AppError::db("Failed to create scheduled classes"))?;
+
+    // 草稿直接记为已提交, 跳过的条目 class_id 为空
+    sqlx::query("UPDATE schedule_drafts SET status = 'committed', committed_at = NOW() WHERE id = $1")
+        .bind(draft_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let count = accepted.len();
+    let skipped_count = skipped.len();
+    let conflicts: Vec<ScheduleConflict> = skipped.into_iter().flat_map(|v| v.conflicts).collect();
 
     Ok(Json(json!({
         "status": "success",
         "classes_created": count,
-        "message": format!("AI 成功生成了 {} 节排课", count)
+        "engine": engine,
+        "draft_id": draft_id,
+        "conflicts": conflicts,
+        "message": format!("成功生成了 {} 节排课, 因冲突跳过 {} 条", count, skipped_count)
     })))
-}
\ No newline at end of file
+}
+
+// 6. (POST) 生成排课草稿 (只保存, 不写入 classes)
+pub async fn create_schedule_draft_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(params): Json<AutoScheduleParams>,
+) -> Result<Json<ScheduleDraftDetail>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    let hq_id = claims.hq_id;
+
+    let params = resolve_schedule_params(params)?;
+    let (proposals, engine) = generate_schedule(&state, hq_id, base_id, &params).await?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let draft_id = save_draft(&mut tx, &claims, base_id, &params, engine, proposals).await?;
+
+    // 预览: 逐条校验得到每条的冲突 (不写入 classes)
+    let (draft, items) = fetch_draft(&mut tx, draft_id, hq_id, base_id, false).await?;
+    let views = check_draft_items(&mut tx, items, false).await
+        .map_err(AppError::db("Failed to preview schedule draft"))?;
+    tx.commit().await?;
+
+    Ok(Json(ScheduleDraftDetail { draft, items: views }))
+}
+
+// 7. (GET) 草稿列表
+pub async fn get_schedule_drafts_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<ScheduleDraft>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let drafts = sqlx::query_as::<_, ScheduleDraft>(
+        "SELECT id, hq_id, base_id, start_date, end_date, density, max_capacity, engine, status, created_at, committed_at FROM schedule_drafts WHERE hq_id = $1 AND base_id = $2 ORDER BY created_at DESC LIMIT 50"
+    )
+    .bind(claims.hq_id)
+    .bind(base_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to fetch schedule drafts"))?;
+
+    Ok(Json(drafts))
+}
+
+// 8. (GET) 草稿详情 (含每条的实时冲突)
+pub async fn get_schedule_draft_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(draft_id): Path<Uuid>,
+) -> Result<Json<ScheduleDraftDetail>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    // 只读预览: 不锁草稿, 也不拿排课咨询锁, 不会阻塞同时进行的排课写入
+    let mut conn = state.db_pool.acquire().await?;
+    let (draft, items) = fetch_draft(&mut conn, draft_id, claims.hq_id, base_id, false).await?;
+
+    let views = if draft.status == "draft" {
+        check_draft_items(&mut conn, items, false).await
+            .map_err(AppError::db("Failed to preview schedule draft"))?
+    } else {
+        items.into_iter().map(|item| ScheduleDraftItemView { item, conflicts: vec![] }).collect()
+    };
+
+    Ok(Json(ScheduleDraftDetail { draft, items: views }))
+}
+
+// 9. (PATCH) 修改草稿中的某一条
+pub async fn update_schedule_draft_item_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((draft_id, item_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<UpdateDraftItemPayload>,
+) -> Result<Json<ScheduleDraftItem>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let (draft, _) = fetch_draft(&mut tx, draft_id, claims.hq_id, base_id, true).await?;
+    if draft.status != "draft" { return Err(AppError::Conflict("schedule.draft_not_editable")); }
+
+    // 课程须为本总部课程, 教室 / 老师必须属于本基地
+    if let Some(course_id) = payload.course_id {
+        TenantScope::from_claims(&claims).ensure_visible(&mut *tx, Owned::Course(course_id)).await?;
+    }
+    if let Some(room_id) = payload.room_id {
+        let ok: Option<Uuid> = sqlx::query_scalar("SELECT id FROM rooms WHERE id = $1 AND base_id = $2")
+            .bind(room_id).bind(base_id).fetch_optional(&mut *tx).await?;
+        if ok.is_none() { return Err(AppError::BadRequest("request.invalid")); }
+    }
+    if let Some(teacher_id) = payload.teacher_id {
+        let ok: Option<Uuid> = sqlx::query_scalar("SELECT user_id FROM teachers WHERE user_id = $1 AND base_id = $2")
+            .bind(teacher_id).bind(base_id).fetch_optional(&mut *tx).await?;
+        if ok.is_none() { return Err(AppError::BadRequest("request.invalid")); }
+    }
+
+    let item = sqlx::query_as::<_, ScheduleDraftItem>(
+        r#"
+        UPDATE schedule_draft_items
+        SET course_id = COALESCE($1, course_id),
+            teacher_id = COALESCE($2, teacher_id),
+            room_id = COALESCE($3, room_id),
+            start_time = COALESCE($4, start_time),
+            end_time = COALESCE($5, end_time),
+            max_capacity = COALESCE($6, max_capacity)
+        WHERE id = $7 AND draft_id = $8
+        RETURNING *
+        "#
+    )
+    .bind(payload.course_id)
+    .bind(payload.teacher_id)
+    .bind(payload.room_id)
+    .bind(payload.start_time)
+    .bind(payload.end_time)
+    .bind(payload.max_capacity)
+    .bind(item_id)
+    .bind(draft_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to update draft item"))?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    if item.end_time <= item.start_time || item.max_capacity <= 0 {
+        return Err(AppError::BadRequest("request.invalid"));
+    }
+    // 换课程或换老师后, 老师须具备该课程资质
+    if !is_teacher_qualified(&mut tx, item.teacher_id, item.course_id).await? {
+        return Err(AppError::BadRequest("schedule.teacher_unqualified"));
+    }
+
+    tx.commit().await?;
+    Ok(Json(item))
+}
+
+// 10. (DELETE) 删除草稿中的某一条
+pub async fn delete_schedule_draft_item_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((draft_id, item_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let (draft, _) = fetch_draft(&mut tx, draft_id, claims.hq_id, base_id, true).await?;
+    if draft.status != "draft" { return Err(AppError::Conflict("schedule.draft_not_editable")); }
+
+    let result = sqlx::query("DELETE FROM schedule_draft_items WHERE id = $1 AND draft_id = $2")
+        .bind(item_id).bind(draft_id)
+        .execute(&mut *tx).await?;
+    if result.rows_affected() == 0 { return Err(AppError::NotFound("resource.not_found")); }
+
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// 11. (POST) 提交草稿: 全部无冲突才整体写入, 否则 409 返回冲突明细
+pub async fn commit_schedule_draft_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(draft_id): Path<Uuid>,
+) -> Result<Json<ScheduleDraftDetail>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let (draft, items) = fetch_draft(&mut tx, draft_id, claims.hq_id, base_id, true).await?;
+    if draft.status != "draft" { return Err(AppError::Conflict("schedule.draft_not_editable")); }
+
+    let mut views = check_draft_items(&mut tx, items, true).await
+        .map_err(AppError::db("Failed to check schedule draft"))?;
+
+    let conflicts: Vec<ScheduleConflict> = views.iter().flat_map(|v| v.conflicts.clone()).collect();
+    if !conflicts.is_empty() {
+        return Err(schedule_conflict_error(conflicts));
+    }
+
+    insert_draft_classes(&mut tx, &draft, &mut views).await
+        .map_err(AppError::db("Failed to commit schedule draft"))?;
+
+    let draft = sqlx::query_as::<_, ScheduleDraft>(
+        "UPDATE schedule_drafts SET status = 'committed', committed_at = NOW() WHERE id = $1 RETURNING id, hq_id, base_id, start_date, end_date, density, max_capacity, engine, status, created_at, committed_at"
+    )
+    .bind(draft_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(Json(ScheduleDraftDetail { draft, items: views }))
+}
+
+// 12. (DELETE) 放弃草稿
+pub async fn discard_schedule_draft_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(draft_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let result = sqlx::query(
+        "UPDATE schedule_drafts SET status = 'discarded' WHERE id = $1 AND hq_id = $2 AND base_id = $3 AND status = 'draft'"
+    )
+    .bind(draft_id).bind(claims.hq_id).bind(base_id)
+    .execute(&state.db_pool).await?;
+
+    if result.rows_affected() == 0 { return Err(AppError::NotFound("resource.not_found")); }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    const ROOM_A: Uuid = Uuid::from_u128(1);
+    const ROOM_B: Uuid = Uuid::from_u128(2);
+    const TEACHER_A: Uuid = Uuid::from_u128(10);
+    const TEACHER_B: Uuid = Uuid::from_u128(11);
+
+    fn item(room_id: Uuid, teacher_id: Uuid, start_hour: i64, hours: i64) -> ScheduleDraftItem {
+        let start = Utc.with_ymd_and_hms(2026, 10, 17, 0, 0, 0).unwrap() + Duration::hours(start_hour);
+        ScheduleDraftItem {
+            id: Uuid::new_v4(),
+            draft_id: Uuid::nil(),
+            course_id: Uuid::nil(),
+            teacher_id,
+            room_id,
+            start_time: start,
+            end_time: start + Duration::hours(hours),
+            max_capacity: 10,
+            class_id: None,
+        }
+    }
+
+    fn kinds(conflicts: &[ScheduleConflict]) -> Vec<(&str, Uuid)> {
+        conflicts.iter().map(|c| (c.kind.as_str(), c.resource_id)).collect()
+    }
+
+    #[test]
+    fn overlapping_draft_items_share_room_or_teacher() {
+        let first = item(ROOM_A, TEACHER_A, 1, 2);
+        assert_eq!(kinds(&draft_overlaps(&item(ROOM_A, TEACHER_B, 2, 1), &[&first])), [("room_booked", ROOM_A)]);
+        assert_eq!(kinds(&draft_overlaps(&item(ROOM_B, TEACHER_A, 2, 1), &[&first])), [("teacher_booked", TEACHER_A)]);
+        assert_eq!(
+            kinds(&draft_overlaps(&item(ROOM_A, TEACHER_A, 0, 2), &[&first])),
+            [("room_booked", ROOM_A), ("teacher_booked", TEACHER_A)]
+        );
+    }
+
+    #[test]
+    fn back_to_back_or_unrelated_items_do_not_conflict() {
+        let first = item(ROOM_A, TEACHER_A, 1, 2);
+        assert!(draft_overlaps(&item(ROOM_A, TEACHER_A, 3, 1), &[&first]).is_empty());
+        assert!(draft_overlaps(&item(ROOM_A, TEACHER_A, 0, 1), &[&first]).is_empty());
+        assert!(draft_overlaps(&item(ROOM_B, TEACHER_B, 1, 2), &[&first]).is_empty());
+        assert!(draft_overlaps(&first, &[]).is_empty());
+    }
+}
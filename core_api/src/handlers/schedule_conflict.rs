@@ -0,0 +1,339 @@
+/*
+ * src/handlers/schedule_conflict.rs
+ * 职责: 排课冲突校验 (教室 / 老师 / 学员 撞课检测)
+ * 供 class.rs、schedule_ai.rs、enrollment.rs 在写入 classes / class_enrollments 前调用
+ */
+
+use chrono::{DateTime, Datelike, Utc};
+use serde::Serialize;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::models::TeacherAvailability;
+use crate::tenant::{Owned, TenantScope};
+use crate::timezone::business_tz;
+use crate::error::AppError;
+
+// --- 冲突明细 ---
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleConflict {
+    // room_booked | teacher_booked | teacher_unavailable | participant_overlap
+    pub kind: String,
+    pub resource_id: Uuid,
+    pub conflicting_class_id: Option<Uuid>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub message: String,
+}
+
+// --- 待校验的排课时段 ---
+pub struct ProposedSlot<'a> {
+    pub class_id: Option<Uuid>, // 修改已有排课时传入, 校验时排除自身
+    pub room_id: Uuid,
+    pub teacher_ids: &'a [Uuid],
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+}
+
+// --- 排课冲突: 返回 409 + 冲突列表 (details.conflicts) ---
+pub fn schedule_conflict_error(conflicts: Vec<ScheduleConflict>) -> AppError {
+    AppError::Conflict("schedule.conflict").with_details(serde_json::json!({ "conflicts": conflicts }))
+}
+
+// 排课引用的课程 / 教室 / 老师必须属于当前租户 (课程可以是总部课程)
+pub async fn ensure_slot_in_scope(
+    conn: &mut PgConnection,
+    scope: &TenantScope,
+    course_id: Option<Uuid>,
+    room_id: Option<Uuid>,
+    teacher_ids: &[Uuid],
+) -> Result<(), AppError> {
+    if let Some(course_id) = course_id {
+        scope.ensure_visible(&mut *conn, Owned::Course(course_id)).await?;
+    }
+    if let Some(room_id) = room_id {
+        scope.ensure_owned(&mut *conn, Owned::Room(room_id)).await?;
+    }
+    for teacher_id in teacher_ids {
+        scope.ensure_owned(&mut *conn, Owned::User(*teacher_id)).await?;
+    }
+    Ok(())
+}
+
+// 老师可上课时段判断 (排课校验与本地求解器共用)
+// 按业务时区换算星期与时刻, 整节课须落在当天的某个窗口内; 未配置任何窗口视为不限时段
+pub fn fits_availability<'a>(
+    availability: impl IntoIterator<Item = &'a TeacherAvailability>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> bool {
+    let mut windows = availability.into_iter().peekable();
+    if windows.peek().is_none() {
+        return true;
+    }
+    let (start, end) = (start.with_timezone(&business_tz()), end.with_timezone(&business_tz()));
+    if start.date_naive() != end.date_naive() {
+        return false;
+    }
+    let weekday = start.weekday().number_from_monday() as i32;
+    windows.any(|a| a.day_of_week == weekday && a.start_time <= start.time() && a.end_time >= end.time())
+}
+
+// 同一教室 / 老师的 "校验 + 写入" 串行化: 事务级咨询锁, 提交或回滚时释放
+// 按 id 排序加锁, 避免两个事务交叉等待
+async fn lock_schedule_resources(conn: &mut PgConnection, slot: &ProposedSlot<'_>) -> Result<(), sqlx::Error> {
+    let mut ids = slot.teacher_ids.to_vec();
+    ids.push(slot.room_id);
+    ids.sort();
+    ids.dedup();
+    for id in ids {
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('schedule:' || $1::text))")
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+// 校验一个排课时段, 返回所有冲突 (空列表表示可排)
+// 校验前先锁住涉及的教室和老师, 调用方须在同一事务内校验并写入, 否则并发请求仍可能同时通过校验
+pub async fn find_schedule_conflicts(
+    conn: &mut PgConnection,
+    slot: &ProposedSlot<'_>,
+) -> Result<Vec<ScheduleConflict>, sqlx::Error> {
+    lock_schedule_resources(&mut *conn, slot).await?;
+    preview_schedule_conflicts(conn, slot).await
+}
+
+// 只读校验, 不加锁: 供预览展示, 结果不能作为写入依据 (写入走 find_schedule_conflicts)
+// 注意: 老师未配置任何 teacher_availability 时视为不限时段
+pub async fn preview_schedule_conflicts(
+    conn: &mut PgConnection,
+    slot: &ProposedSlot<'_>,
+) -> Result<Vec<ScheduleConflict>, sqlx::Error> {
+    let mut conflicts = Vec::new();
+
+    // 1. 教室占用
+    let room_clashes: Vec<(Uuid, String)> = sqlx::query_as(
+        r#"
+        SELECT c.id, r.name
+        FROM classes c
+        JOIN rooms r ON c.room_id = r.id
+        WHERE c.room_id = $1
+          AND ($2::uuid IS NULL OR c.id <> $2)
+          AND COALESCE(c.status, 'scheduled') <> 'cancelled'
+          AND c.start_time < $4 AND c.end_time > $3
+        "#,
+    )
+    .bind(slot.room_id)
+    .bind(slot.class_id)
+    .bind(slot.start_time)
+    .bind(slot.end_time)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for (class_id, room_name) in room_clashes {
+        conflicts.push(ScheduleConflict {
+            kind: "room_booked".to_string(),
+            resource_id: slot.room_id,
+            conflicting_class_id: Some(class_id),
+            start_time: slot.start_time,
+            end_time: slot.end_time,
+            message: format!("教室 {} 在该时段已被占用", room_name),
+        });
+    }
+
+    if !slot.teacher_ids.is_empty() {
+        // 2. 老师撞课
+        let teacher_clashes: Vec<(Uuid, Uuid, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT ct.teacher_id, c.id, u.full_name
+            FROM class_teachers ct
+            JOIN classes c ON ct.class_id = c.id
+            JOIN users u ON ct.teacher_id = u.id
+            WHERE ct.teacher_id = ANY($1)
+              AND ($2::uuid IS NULL OR c.id <> $2)
+              AND COALESCE(c.status, 'scheduled') <> 'cancelled'
+              AND c.start_time < $4 AND c.end_time > $3
+            "#,
+        )
+        .bind(slot.teacher_ids)
+        .bind(slot.class_id)
+        .bind(slot.start_time)
+        .bind(slot.end_time)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for (teacher_id, class_id, name) in teacher_clashes {
+            conflicts.push(ScheduleConflict {
+                kind: "teacher_booked".to_string(),
+                resource_id: teacher_id,
+                conflicting_class_id: Some(class_id),
+                start_time: slot.start_time,
+                end_time: slot.end_time,
+                message: format!("老师 {} 在该时段已有课程", name.unwrap_or_default()),
+            });
+        }
+
+        // 3. 超出老师可上课时段 (按业务时区换算星期与时间, 见 fits_availability)
+        let teachers: Vec<(Uuid, Option<String>)> = sqlx::query_as(
+            "SELECT t.user_id, u.full_name FROM teachers t JOIN users u ON t.user_id = u.id WHERE t.user_id = ANY($1)",
+        )
+        .bind(slot.teacher_ids)
+        .fetch_all(&mut *conn)
+        .await?;
+        let availability = sqlx::query_as::<_, TeacherAvailability>(
+            "SELECT * FROM teacher_availability WHERE teacher_id = ANY($1)",
+        )
+        .bind(slot.teacher_ids)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let unavailable = teachers.into_iter().filter(|(teacher_id, _)| {
+            let windows = availability.iter().filter(|a| a.teacher_id == *teacher_id);
+            !fits_availability(windows, slot.start_time, slot.end_time)
+        });
+
+        for (teacher_id, name) in unavailable {
+            conflicts.push(ScheduleConflict {
+                kind: "teacher_unavailable".to_string(),
+                resource_id: teacher_id,
+                conflicting_class_id: None,
+                start_time: slot.start_time,
+                end_time: slot.end_time,
+                message: format!("该时段不在老师 {} 的可上课时间内", name.unwrap_or_default()),
+            });
+        }
+    }
+
+    // 4. 已报名学员撞课 (仅修改已有排课时间时存在)
+    if let Some(class_id) = slot.class_id {
+        let participant_clashes: Vec<(Uuid, Uuid, String)> = sqlx::query_as(
+            r#"
+            SELECT e.participant_id, other.id, p.name
+            FROM class_enrollments e
+            JOIN participants p ON e.participant_id = p.id
+            JOIN class_enrollments oe ON oe.participant_id = e.participant_id AND oe.class_id <> e.class_id
+            JOIN classes other ON oe.class_id = other.id
+            WHERE e.class_id = $1
+              AND COALESCE(e.status, 'enrolled') NOT IN ('leave', 'cancelled')
+              AND COALESCE(oe.status, 'enrolled') NOT IN ('leave', 'cancelled')
+              AND COALESCE(other.status, 'scheduled') <> 'cancelled'
+              AND other.start_time < $3 AND other.end_time > $2
+            "#,
+        )
+        .bind(class_id)
+        .bind(slot.start_time)
+        .bind(slot.end_time)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for (participant_id, other_id, name) in participant_clashes {
+            conflicts.push(ScheduleConflict {
+                kind: "participant_overlap".to_string(),
+                resource_id: participant_id,
+                conflicting_class_id: Some(other_id),
+                start_time: slot.start_time,
+                end_time: slot.end_time,
+                message: format!("学员 {} 在该时段已报名其他课程", name),
+            });
+        }
+    }
+
+    Ok(conflicts)
+}
+
+// 报名前校验: 学员在目标课程时段内是否已有其他课程
+// 与教室 / 老师一样先对学员加事务级咨询锁, 调用方须在同一事务内校验并写入报名,
+// 否则同一学员并发报名两节重叠的课程仍可能同时通过校验
+pub async fn find_participant_conflicts(
+    conn: &mut PgConnection,
+    participant_id: Uuid,
+    class_id: Uuid,
+) -> Result<Vec<ScheduleConflict>, sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('participant:' || $1::text))")
+        .bind(participant_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let rows: Vec<(Uuid, DateTime<Utc>, DateTime<Utc>, String)> = sqlx::query_as(
+        r#"
+        SELECT other.id, target.start_time, target.end_time, p.name
+        FROM classes target
+        JOIN classes other ON other.id <> target.id
+            AND other.start_time < target.end_time AND other.end_time > target.start_time
+            AND COALESCE(other.status, 'scheduled') <> 'cancelled'
+        JOIN class_enrollments oe ON oe.class_id = other.id
+            AND oe.participant_id = $1
+            AND COALESCE(oe.status, 'enrolled') NOT IN ('leave', 'cancelled')
+        JOIN participants p ON p.id = $1
+        WHERE target.id = $2
+        "#,
+    )
+    .bind(participant_id)
+    .bind(class_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(other_id, start_time, end_time, name)| ScheduleConflict {
+            kind: "participant_overlap".to_string(),
+            resource_id: participant_id,
+            conflicting_class_id: Some(other_id),
+            start_time,
+            end_time,
+            message: format!("学员 {} 在该时段已报名其他课程", name),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveTime, TimeZone};
+
+    fn window(day_of_week: i32, from: u32, to: u32) -> TeacherAvailability {
+        TeacherAvailability {
+            id: Uuid::new_v4(),
+            teacher_id: Uuid::nil(),
+            day_of_week,
+            start_time: NaiveTime::from_hms_opt(from, 0, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(to, 0, 0).unwrap(),
+        }
+    }
+
+    fn utc(d: u32, h: u32, m: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, d, h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn no_windows_means_unrestricted() {
+        assert!(fits_availability(&[], utc(17, 18, 0), utc(17, 19, 0)));
+    }
+
+    #[test]
+    fn slot_must_lie_inside_a_window_in_business_timezone() {
+        // 周六 (6) 北京时间 09:00-12:00 = UTC 01:00-04:00
+        let windows = [window(6, 9, 12)];
+        assert!(fits_availability(&windows, utc(17, 1, 0), utc(17, 4, 0)));
+        assert!(!fits_availability(&windows, utc(17, 3, 30), utc(17, 4, 30)));
+        assert!(!fits_availability(&windows, utc(16, 1, 0), utc(16, 2, 0))); // 周五
+    }
+
+    #[test]
+    fn weekday_follows_business_date_not_utc_date() {
+        // UTC 周五 16:00 = 北京时间周六 00:00
+        let windows = [window(6, 0, 2)];
+        assert!(fits_availability(&windows, utc(16, 16, 0), utc(16, 17, 0)));
+        let windows = [window(5, 16, 18)];
+        assert!(!fits_availability(&windows, utc(16, 16, 0), utc(16, 17, 0)));
+    }
+
+    #[test]
+    fn slot_crossing_midnight_never_fits() {
+        let windows = [window(5, 0, 23), window(6, 0, 23)];
+        // 北京时间周五 23:30 - 周六 00:30
+        assert!(!fits_availability(&windows, utc(16, 15, 30), utc(16, 16, 30)));
+    }
+}
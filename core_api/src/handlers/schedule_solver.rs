@@ -0,0 +1,289 @@
+/*
+ * src/handlers/schedule_solver.rs
+ * 职责: 本地排课求解器 (AI 服务不可用时的兜底, 也可通过 engine=local 直接选用)
+ * 输入与 AI 服务相同 (AiTeacher / AiCourse / AiRoom), 结果确定、可复现, 可作为 AI 结果的对照基线
+ *
+ * 规则 (贪心):
+ * 1. 每门课程每 7 天最多排 density 节, 同一课程每天最多 1 节, 尽量分散到不同日期
+ * 2. 老师必须具备该课程资质 (teacher_qualified_courses), 且整节课落在其 teacher_availability 窗口内
+ *    (与排课校验共用 fits_availability, 按业务时区判断; 未配置窗口的老师不限时段, 求解器只在营业时间内为其找空档)
+ * 3. 教室容量必须 >= 目标人数, 优先选容量最接近的教室
+ * 4. 老师 / 教室不可与已有排课 (busy) 或本次已排课程重叠
+ */
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use uuid::Uuid;
+
+use super::schedule_ai::{AiCourse, AiRoom, AiTeacher, ProposedClass};
+use super::schedule_conflict::fits_availability;
+use crate::timezone::business_tz;
+
+// 候选开始时间的步长 (分钟)
+const SLOT_STEP_MINUTES: i64 = 30;
+
+// 老师未配置 teacher_availability 时的候选范围 (营业时间); 只决定在哪里找空档, 不是额外的约束
+const DEFAULT_OPEN_HOUR: u32 = 9;
+const DEFAULT_CLOSE_HOUR: u32 = 21;
+
+// 已占用的时段 (老师或教室)
+pub(crate) struct BusySlot {
+    pub resource_id: Uuid,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+}
+
+pub(crate) struct SolverInput<'a> {
+    pub teachers: &'a [AiTeacher],
+    pub courses: &'a [AiCourse],
+    pub rooms: &'a [AiRoom],
+    pub busy: Vec<BusySlot>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub density: i32,
+    pub max_capacity: i32,
+}
+
+fn overlaps(busy: &[BusySlot], resource_id: Uuid, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+    busy.iter()
+        .any(|b| b.resource_id == resource_id && b.start_time < end && b.end_time > start)
+}
+
+// 业务时区的日期 + 时刻 -> UTC
+fn to_utc(date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+    business_tz()
+        .from_local_datetime(&date.and_time(time))
+        .single()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+pub(crate) fn solve_schedule(input: SolverInput<'_>) -> Vec<ProposedClass> {
+    let SolverInput { teachers, courses, rooms, mut busy, start_date, end_date, density, max_capacity } = input;
+
+    // 排序保证结果确定
+    let mut courses: Vec<&AiCourse> = courses.iter().collect();
+    courses.sort_by_key(|c| c.id);
+    let mut teachers: Vec<&AiTeacher> = teachers.iter().collect();
+    teachers.sort_by_key(|t| t.id);
+    let mut rooms: Vec<&AiRoom> = rooms.iter().filter(|r| r.capacity >= max_capacity).collect();
+    rooms.sort_by_key(|r| (r.capacity, r.id));
+
+    let mut results = Vec::new();
+    if rooms.is_empty() || density <= 0 {
+        return results;
+    }
+
+    let days = (end_date - start_date).num_days();
+    for week_start in (0..=days).step_by(7) {
+        for course in &courses {
+            if course.duration <= 0 {
+                continue;
+            }
+            let length = Duration::minutes(course.duration as i64);
+            let mut placed_this_week = 0;
+
+            for offset in week_start..(week_start + 7).min(days + 1) {
+                if placed_this_week >= density {
+                    break;
+                }
+                let date = start_date + Duration::days(offset);
+                let weekday = date.weekday().number_from_monday() as i32;
+
+                if let Some(class) = place_on_day(&teachers, &rooms, &busy, course.id, length, date, weekday) {
+                    busy.push(BusySlot { resource_id: class.teacher_id, start_time: class.start_time, end_time: class.end_time });
+                    busy.push(BusySlot { resource_id: class.room_id, start_time: class.start_time, end_time: class.end_time });
+                    results.push(class);
+                    placed_this_week += 1;
+                }
+            }
+        }
+    }
+
+    results
+}
+
+// 在指定日期为课程找到第一个可行的 (老师, 时段, 教室)
+fn place_on_day(
+    teachers: &[&AiTeacher],
+    rooms: &[&AiRoom],
+    busy: &[BusySlot],
+    course_id: Uuid,
+    length: Duration,
+    date: NaiveDate,
+    weekday: i32,
+) -> Option<ProposedClass> {
+    for teacher in teachers.iter().filter(|t| t.skills.contains(&course_id)) {
+        let mut windows: Vec<(NaiveTime, NaiveTime)> = if teacher.availability.is_empty() {
+            vec![(
+                NaiveTime::from_hms_opt(DEFAULT_OPEN_HOUR, 0, 0)?,
+                NaiveTime::from_hms_opt(DEFAULT_CLOSE_HOUR, 0, 0)?,
+            )]
+        } else {
+            teacher.availability.iter()
+                .filter(|a| a.day_of_week == weekday)
+                .map(|a| (a.start_time, a.end_time))
+                .collect()
+        };
+        windows.sort();
+
+        for (window_start, window_end) in windows {
+            let mut slot_start = window_start;
+            while slot_start + length <= window_end && slot_start + length > slot_start {
+                let (Some(start), Some(end)) = (to_utc(date, slot_start), to_utc(date, slot_start + length)) else {
+                    break;
+                };
+
+                if fits_availability(&teacher.availability, start, end) && !overlaps(busy, teacher.id, start, end) {
+                    if let Some(room) = rooms.iter().find(|r| !overlaps(busy, r.id, start, end)) {
+                        return Some(ProposedClass {
+                            course_id,
+                            teacher_id: teacher.id,
+                            room_id: room.id,
+                            start_time: start,
+                            end_time: end,
+                        });
+                    }
+                }
+                let next = slot_start + Duration::minutes(SLOT_STEP_MINUTES);
+                if next <= slot_start {
+                    break; // 跨过午夜
+                }
+                slot_start = next;
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TeacherAvailability;
+
+    const COURSE: Uuid = Uuid::from_u128(1);
+    const TEACHER_A: Uuid = Uuid::from_u128(10);
+    const TEACHER_B: Uuid = Uuid::from_u128(11);
+    const ROOM_SMALL: Uuid = Uuid::from_u128(20);
+    const ROOM_BIG: Uuid = Uuid::from_u128(21);
+
+    // 2026-10-17 是周六
+    fn saturday() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 17).unwrap()
+    }
+
+    fn at(date: NaiveDate, h: u32, m: u32) -> DateTime<Utc> {
+        to_utc(date, NaiveTime::from_hms_opt(h, m, 0).unwrap()).unwrap()
+    }
+
+    fn teacher(id: Uuid, skills: Vec<Uuid>, availability: Vec<(i32, u32, u32)>) -> AiTeacher {
+        AiTeacher {
+            id,
+            name: String::new(),
+            skills,
+            availability: availability
+                .into_iter()
+                .map(|(day_of_week, from, to)| TeacherAvailability {
+                    id: Uuid::new_v4(),
+                    teacher_id: id,
+                    day_of_week,
+                    start_time: NaiveTime::from_hms_opt(from, 0, 0).unwrap(),
+                    end_time: NaiveTime::from_hms_opt(to, 0, 0).unwrap(),
+                })
+                .collect(),
+        }
+    }
+
+    fn course() -> Vec<AiCourse> {
+        vec![AiCourse { id: COURSE, name: String::new(), duration: 60 }]
+    }
+
+    fn room(id: Uuid, capacity: i32) -> AiRoom {
+        AiRoom { id, name: String::new(), capacity }
+    }
+
+    fn solve_one_day(teachers: &[AiTeacher], rooms: &[AiRoom], busy: Vec<BusySlot>, max_capacity: i32) -> Vec<ProposedClass> {
+        solve_schedule(SolverInput {
+            teachers,
+            courses: &course(),
+            rooms,
+            busy,
+            start_date: saturday(),
+            end_date: saturday(),
+            density: 1,
+            max_capacity,
+        })
+    }
+
+    #[test]
+    fn places_in_business_hours_of_business_timezone() {
+        let teachers = [teacher(TEACHER_A, vec![COURSE], vec![])];
+        let got = solve_one_day(&teachers, &[room(ROOM_BIG, 20)], vec![], 10);
+        assert_eq!(got.len(), 1);
+        // 北京时间 09:00 = UTC 01:00
+        assert_eq!(got[0].start_time, Utc.with_ymd_and_hms(2026, 10, 17, 1, 0, 0).unwrap());
+        assert_eq!(got[0].end_time - got[0].start_time, Duration::minutes(60));
+    }
+
+    #[test]
+    fn skips_busy_room_and_busy_teacher() {
+        let teachers = [teacher(TEACHER_A, vec![COURSE], vec![])];
+        let rooms = [room(ROOM_BIG, 20)];
+        let day = saturday();
+
+        let busy_room = vec![BusySlot { resource_id: ROOM_BIG, start_time: at(day, 9, 0), end_time: at(day, 10, 30) }];
+        let got = solve_one_day(&teachers, &rooms, busy_room, 10);
+        assert_eq!(got[0].start_time, at(day, 10, 30));
+
+        let busy_teacher = vec![BusySlot { resource_id: TEACHER_A, start_time: at(day, 8, 0), end_time: at(day, 12, 0) }];
+        let got = solve_one_day(&teachers, &rooms, busy_teacher, 10);
+        assert_eq!(got[0].start_time, at(day, 12, 0));
+    }
+
+    #[test]
+    fn does_not_double_book_within_one_run() {
+        let courses = [
+            AiCourse { id: Uuid::from_u128(1), name: String::new(), duration: 60 },
+            AiCourse { id: Uuid::from_u128(2), name: String::new(), duration: 60 },
+        ];
+        let teachers = [teacher(TEACHER_A, vec![courses[0].id, courses[1].id], vec![])];
+        let got = solve_schedule(SolverInput {
+            teachers: &teachers,
+            courses: &courses,
+            rooms: &[room(ROOM_BIG, 20)],
+            busy: vec![],
+            start_date: saturday(),
+            end_date: saturday(),
+            density: 1,
+            max_capacity: 10,
+        });
+        assert_eq!(got.len(), 2);
+        assert!(got[0].end_time <= got[1].start_time || got[1].end_time <= got[0].start_time);
+    }
+
+    #[test]
+    fn respects_teacher_availability_and_qualification() {
+        let day = saturday();
+        let teachers = [
+            teacher(TEACHER_A, vec![], vec![]),                  // 无资质
+            teacher(TEACHER_B, vec![COURSE], vec![(6, 14, 16)]), // 仅周六 14:00-16:00
+        ];
+        let got = solve_one_day(&teachers, &[room(ROOM_BIG, 20)], vec![], 10);
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].teacher_id, TEACHER_B);
+        assert_eq!(got[0].start_time, at(day, 14, 0));
+        assert!(fits_availability(&teachers[1].availability, got[0].start_time, got[0].end_time));
+
+        // 窗口放不下整节课 / 只在其他星期可上课: 不排
+        let teachers = [teacher(TEACHER_B, vec![COURSE], vec![(6, 14, 14), (1, 9, 18)])];
+        assert!(solve_one_day(&teachers, &[room(ROOM_BIG, 20)], vec![], 10).is_empty());
+    }
+
+    #[test]
+    fn room_capacity_must_fit_and_closest_fit_wins() {
+        let teachers = [teacher(TEACHER_A, vec![COURSE], vec![])];
+        let rooms = [room(ROOM_BIG, 30), room(ROOM_SMALL, 12)];
+        let got = solve_one_day(&teachers, &rooms, vec![], 10);
+        assert_eq!(got[0].room_id, ROOM_SMALL);
+
+        assert!(solve_one_day(&teachers, &rooms, vec![], 31).is_empty());
+    }
+}
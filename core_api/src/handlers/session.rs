@@ -0,0 +1,326 @@
+/*
+ * src/handlers/session.rs
+ * 职责: 登录会话 (短时 Access Token + 服务端保存的轮换 Refresh Token)
+ * 1. Access Token 15 分钟有效, 携带会话 id (sid), auth_middleware 每次校验会话未被吊销
+ * 2. Refresh Token 只存 SHA-256 摘要, 每次刷新作废旧的并签发新的;
+ *    已使用过的 Refresh Token 再次出现视为泄露, 吊销整个会话
+ * 3. 退出登录 / 员工停用 / 改密码时调用 revoke_subject_sessions
+ */
+
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Deserialize;
+use sqlx::{PgConnection, PgPool, Row};
+use uuid::Uuid;
+
+use super::{load_customer_claims, load_user_claims, AppState};
+use crate::models::{AuthResponse, Claims};
+use crate::error::AppError;
+
+pub const SUBJECT_USER: &str = "user";
+pub const SUBJECT_CUSTOMER: &str = "customer";
+
+// 吊销原因 (auth_sessions.revoked_reason)
+pub const REVOKE_LOGOUT: &str = "logout";
+pub const REVOKE_DEACTIVATED: &str = "deactivated";
+pub const REVOKE_MERGED: &str = "merged"; // 客户档案合并到已有档案
+pub const REVOKE_PASSWORD_CHANGED: &str = "password_changed";
+const REVOKE_REFRESH_REUSE: &str = "refresh_reuse";
+
+const ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+
+// 会话空闲有效期: 每次刷新顺延. 家长端沿用原来的 30 天免登录
+fn session_ttl(subject_type: &str) -> Duration {
+    match subject_type {
+        SUBJECT_CUSTOMER => Duration::days(30),
+        _ => Duration::days(7),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshPayload {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LogoutPayload {
+    #[serde(default)]
+    pub all: bool, // true = 退出该账号的所有设备
+}
+
+// 新签发的会话: tokens 返回给客户端, claims 供调用方读取姓名等信息
+pub struct IssuedSession {
+    pub claims: Claims,
+    pub tokens: AuthResponse,
+}
+
+async fn load_subject_claims(
+    conn: &mut PgConnection,
+    subject_type: &str,
+    subject_id: Uuid,
+    sid: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<Option<Claims>, sqlx::Error> {
+    match subject_type {
+        SUBJECT_USER => load_user_claims(conn, subject_id, sid, expires_at).await,
+        SUBJECT_CUSTOMER => load_customer_claims(conn, subject_id, sid, expires_at).await,
+        _ => Ok(None),
+    }
+}
+
+fn encode_claims(jwt_secret: &str, claims: &Claims) -> Result<String, AppError> {
+    encode(&Header::default(), claims, &EncodingKey::from_secret(jwt_secret.as_bytes())).map_err(AppError::internal("Failed to generate token"))
+}
+
+// 生成随机 Refresh Token, 数据库只保存摘要
+async fn issue_refresh_token(
+    conn: &mut PgConnection,
+    session_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<String, sqlx::Error> {
+    let token: String = rand::thread_rng().sample_iter(&Alphanumeric).take(48).map(char::from).collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (session_id, token_hash, expires_at)
+        VALUES ($1, encode(sha256(convert_to($2, 'UTF8')), 'hex'), $3)
+        "#,
+    )
+    .bind(session_id)
+    .bind(&token)
+    .bind(expires_at)
+    .execute(conn)
+    .await?;
+
+    Ok(token)
+}
+
+async fn create_session(
+    conn: &mut PgConnection,
+    jwt_secret: &str,
+    subject_type: &str,
+    subject_id: Uuid,
+    impersonated_by: Option<Uuid>,
+    expires_at: DateTime<Utc>,
+) -> Result<Option<IssuedSession>, AppError> {
+    let sid = Uuid::new_v4();
+    // 模拟登录的会话不可续期, Access Token 直接用满整个会话
+    let access_expires_at = match impersonated_by {
+        Some(_) => expires_at,
+        None => Utc::now() + Duration::minutes(ACCESS_TOKEN_TTL_MINUTES),
+    };
+
+    let claims = load_subject_claims(&mut *conn, subject_type, subject_id, sid, access_expires_at)
+        .await
+        .map_err(AppError::db("Failed to load session subject"))?;
+    let Some(claims) = claims else {
+        return Ok(None); // 账号不存在或已停用
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO auth_sessions (id, subject_type, subject_id, hq_id, impersonated_by, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(sid)
+    .bind(subject_type)
+    .bind(subject_id)
+    .bind(claims.hq_id)
+    .bind(impersonated_by)
+    .bind(expires_at)
+    .execute(&mut *conn)
+    .await
+    .map_err(AppError::db("Failed to create session"))?;
+
+    let refresh_token = match impersonated_by {
+        Some(_) => None,
+        None => Some(issue_refresh_token(&mut *conn, sid, expires_at).await.map_err(AppError::db("Failed to issue refresh token"))?),
+    };
+
+    let token = encode_claims(jwt_secret, &claims)?;
+
+    Ok(Some(IssuedSession {
+        claims,
+        tokens: AuthResponse { token, refresh_token, expires_at: access_expires_at },
+    }))
+}
+
+// 登录成功后新建会话, 签发 Access Token + Refresh Token
+pub async fn open_session(
+    conn: &mut PgConnection,
+    jwt_secret: &str,
+    subject_type: &str,
+    subject_id: Uuid,
+) -> Result<Option<IssuedSession>, AppError> {
+    let expires_at = Utc::now() + session_ttl(subject_type);
+    create_session(conn, jwt_secret, subject_type, subject_id, None, expires_at).await
+}
+
+// 模拟登录: 到期即失效, 不签发 Refresh Token
+pub async fn open_impersonation_session(
+    conn: &mut PgConnection,
+    jwt_secret: &str,
+    subject_type: &str,
+    subject_id: Uuid,
+    actor_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<Option<IssuedSession>, AppError> {
+    create_session(conn, jwt_secret, subject_type, subject_id, Some(actor_id), expires_at).await
+}
+
+// auth_middleware 调用: 会话存在、未吊销且未过期
+pub async fn session_is_active(pool: &PgPool, sid: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM auth_sessions WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW())",
+    )
+    .bind(sid)
+    .fetch_one(pool)
+    .await
+}
+
+async fn revoke_session(conn: &mut PgConnection, sid: Uuid, reason: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE auth_sessions SET revoked_at = NOW(), revoked_reason = $2 WHERE id = $1 AND revoked_at IS NULL",
+    )
+    .bind(sid)
+    .bind(reason)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// 吊销某个账号的全部会话 (员工停用 / 改密码 / 退出所有设备), 返回吊销数量
+pub async fn revoke_subject_sessions(
+    conn: &mut PgConnection,
+    subject_type: &str,
+    subject_id: Uuid,
+    reason: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE auth_sessions SET revoked_at = NOW(), revoked_reason = $3
+        WHERE subject_type = $1 AND subject_id = $2 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(subject_type)
+    .bind(subject_id)
+    .bind(reason)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// (POST /api/v1/auth/refresh) 用 Refresh Token 换取新的 Access Token + Refresh Token
+pub async fn refresh_token_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshPayload>,
+) -> Result<Json<AuthResponse>, AppError> {
+    let mut tx = state.db_pool.begin().await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT rt.id, rt.used_at, rt.expires_at,
+               s.id AS session_id, s.subject_type, s.subject_id, s.revoked_at, s.expires_at AS session_expires_at
+        FROM refresh_tokens rt
+        JOIN auth_sessions s ON rt.session_id = s.id
+        WHERE rt.token_hash = encode(sha256(convert_to($1, 'UTF8')), 'hex')
+        FOR UPDATE OF rt, s
+        "#,
+    )
+    .bind(payload.refresh_token.trim())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to look up refresh token"))?
+    .ok_or(AppError::Unauthorized("auth.unauthorized"))?;
+
+    let token_id: Uuid = row.get("id");
+    let used_at: Option<DateTime<Utc>> = row.get("used_at");
+    let token_expires_at: DateTime<Utc> = row.get("expires_at");
+    let session_id: Uuid = row.get("session_id");
+    let subject_type: String = row.get("subject_type");
+    let subject_id: Uuid = row.get("subject_id");
+    let revoked_at: Option<DateTime<Utc>> = row.get("revoked_at");
+    let session_expires_at: DateTime<Utc> = row.get("session_expires_at");
+
+    let now = Utc::now();
+    if revoked_at.is_some() || session_expires_at <= now || token_expires_at <= now {
+        return Err(AppError::Unauthorized("auth.unauthorized"));
+    }
+
+    if used_at.is_some() {
+        // 旧 Token 被重复使用: 可能已泄露, 吊销整个会话, 双方都需要重新登录
+        revoke_session(&mut tx, session_id, REVOKE_REFRESH_REUSE).await.map_err(AppError::db("Failed to revoke session"))?;
+        tx.commit().await?;
+        tracing::warn!("Refresh token reuse detected, session {} revoked", session_id);
+        return Err(AppError::Unauthorized("auth.unauthorized"));
+    }
+
+    sqlx::query("UPDATE refresh_tokens SET used_at = NOW() WHERE id = $1")
+        .bind(token_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to consume refresh token"))?;
+
+    // 重新读取角色 / 基地等信息, 调岗或改角色在下一次刷新时生效
+    let access_expires_at = now + Duration::minutes(ACCESS_TOKEN_TTL_MINUTES);
+    let claims = load_subject_claims(&mut tx, &subject_type, subject_id, session_id, access_expires_at)
+        .await
+        .map_err(AppError::db("Failed to load session subject"))?;
+
+    let Some(claims) = claims else {
+        revoke_session(&mut tx, session_id, REVOKE_DEACTIVATED).await.map_err(AppError::db("Failed to revoke session"))?;
+        tx.commit().await?;
+        return Err(AppError::Unauthorized("auth.unauthorized"));
+    };
+
+    let expires_at = now + session_ttl(&subject_type);
+    sqlx::query("UPDATE auth_sessions SET expires_at = $2, last_refreshed_at = NOW() WHERE id = $1")
+        .bind(session_id)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to extend session"))?;
+
+    let refresh_token = issue_refresh_token(&mut tx, session_id, expires_at).await.map_err(AppError::db("Failed to issue refresh token"))?;
+    let token = encode_claims(&state.jwt_secret, &claims)?;
+
+    tx.commit().await?;
+
+    Ok(Json(AuthResponse { token, refresh_token: Some(refresh_token), expires_at: access_expires_at }))
+}
+
+// (POST /api/v1/auth/logout) 吊销当前会话; {"all": true} 时吊销该账号的全部会话
+pub async fn logout_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    payload: Option<Json<LogoutPayload>>,
+) -> Result<StatusCode, AppError> {
+    let Json(payload) = payload.unwrap_or_default();
+    let mut conn = state.db_pool.acquire().await?;
+
+    let result = if payload.all {
+        sqlx::query(
+            r#"
+            UPDATE auth_sessions s SET revoked_at = NOW(), revoked_reason = $2
+            FROM auth_sessions cur
+            WHERE cur.id = $1
+              AND s.subject_type = cur.subject_type AND s.subject_id = cur.subject_id
+              AND s.revoked_at IS NULL
+            "#,
+        )
+        .bind(claims.sid)
+        .bind(REVOKE_LOGOUT)
+        .execute(&mut *conn)
+        .await
+        .map(|r| r.rows_affected())
+    } else {
+        revoke_session(&mut conn, claims.sid, REVOKE_LOGOUT).await
+    };
+
+    result.map_err(AppError::db("Failed to revoke session"))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
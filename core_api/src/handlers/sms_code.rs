@@ -0,0 +1,186 @@
+/*
+ * src/handlers/sms_code.rs
+ * 职责: 短信验证码
+ * 1. 下发: 6 位数字, 5 分钟有效, 库里只存哈希; 按手机号和 IP 限流
+ * 2. 校验: 只认最近一条, 错误次数达到上限后作废, 校验通过即核销
+ */
+
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use super::AppState;
+use crate::middleware::client_ip;
+use crate::error::AppError;
+
+// 验证码用途, 同一手机号不同用途的验证码互不影响
+pub const SMS_PURPOSE_BIND_PHONE: &str = "bind_phone";
+
+const CODE_TTL_MINUTES: i64 = 5;
+const MAX_ATTEMPTS: i32 = 5;
+const RESEND_INTERVAL_SECS: i64 = 60; // 同一手机号两次下发的最小间隔
+const PHONE_DAILY_LIMIT: i64 = 10;    // 同一手机号 24 小时内最多下发次数
+const IP_HOURLY_LIMIT: i64 = 20;      // 同一 IP 1 小时内最多下发次数
+
+#[derive(Debug, Deserialize)]
+pub struct SmsCodePayload {
+    pub phone_number: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SmsCodeResponse {
+    pub expires_in: i64,   // 验证码有效期 (秒)
+    pub resend_after: i64, // 多少秒后可重新获取
+}
+
+// 大陆手机号: 1 开头的 11 位数字
+pub fn is_valid_phone(phone: &str) -> bool {
+    phone.len() == 11 && phone.starts_with('1') && phone.chars().all(|c| c.is_ascii_digit())
+}
+
+// 哈希里带上 jwt_secret, 拿到库也不能离线穷举 6 位验证码
+const CODE_HASH_SQL: &str = "encode(sha256(convert_to($1 || ':' || $2 || ':' || $3, 'UTF8')), 'hex')";
+
+// (POST /api/v1/customer/sms-code) 下发短信验证码
+pub async fn send_sms_code_handler(
+    State(state): State<AppState>,
+    connect: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(payload): Json<SmsCodePayload>,
+) -> Result<Json<SmsCodeResponse>, AppError> {
+    let phone = payload.phone_number.trim();
+    if !is_valid_phone(phone) {
+        return Err(AppError::BadRequest("customer.phone_invalid"));
+    }
+    let ip = client_ip(connect, &headers);
+
+    let mut tx = state.db_pool.begin().await?;
+
+    // 同一手机号并发请求串行化, 避免同时通过限流检查
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('sms:' || $1))")
+        .bind(phone)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::db("Failed to lock sms phone"))?;
+
+    let limits = sqlx::query(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM sms_verification_codes
+             WHERE phone_number = $1 AND created_at > NOW() - make_interval(secs => $3)) AS recent,
+            (SELECT COUNT(*) FROM sms_verification_codes
+             WHERE phone_number = $1 AND created_at > NOW() - INTERVAL '24 hours') AS daily,
+            (SELECT COUNT(*) FROM sms_verification_codes
+             WHERE $2::text IS NOT NULL AND request_ip = $2 AND created_at > NOW() - INTERVAL '1 hour') AS by_ip
+        "#,
+    )
+    .bind(phone)
+    .bind(ip.as_deref())
+    .bind(RESEND_INTERVAL_SECS as f64)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to check sms rate limit"))?;
+
+    let recent: i64 = limits.get("recent");
+    let daily: i64 = limits.get("daily");
+    let by_ip: i64 = limits.get("by_ip");
+    if recent > 0 || daily >= PHONE_DAILY_LIMIT || by_ip >= IP_HOURLY_LIMIT {
+        tracing::warn!("SMS code rate limited: phone={} ip={:?}", phone, ip);
+        return Err(AppError::Custom(StatusCode::TOO_MANY_REQUESTS, "sms.rate_limited"));
+    }
+
+    let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+
+    sqlx::query(&format!(
+        r#"
+        INSERT INTO sms_verification_codes (phone_number, purpose, code_hash, request_ip, expires_at)
+        VALUES ($1, $4, {}, $5, NOW() + make_interval(mins => $6))
+        "#,
+        CODE_HASH_SQL
+    ))
+    .bind(phone)
+    .bind(&code)
+    .bind(&state.jwt_secret)
+    .bind(SMS_PURPOSE_BIND_PHONE)
+    .bind(ip.as_deref())
+    .bind(CODE_TTL_MINUTES as i32)
+    .execute(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to save sms code"))?;
+
+    // 发送失败时回滚, 不占用限流次数
+    state.sms.send_code(phone, &code).await.map_err(|e| {
+        tracing::error!("Failed to send sms code: {:?}", e);
+        AppError::from(e)
+    })?;
+
+    tx.commit().await?;
+
+    Ok(Json(SmsCodeResponse {
+        expires_in: CODE_TTL_MINUTES * 60,
+        resend_after: RESEND_INTERVAL_SECS,
+    }))
+}
+
+// 校验并核销验证码; 单独提交, 调用方后续事务失败也不会回滚错误次数
+// 验证码错误 / 过期 / 已使用 / 错误次数过多 统一返回 400
+pub async fn verify_sms_code(
+    pool: &PgPool,
+    jwt_secret: &str,
+    phone: &str,
+    purpose: &str,
+    code: &str,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    let row = sqlx::query(&format!(
+        r#"
+        SELECT id, code_hash = {} AS matched,
+               consumed_at IS NULL AND expires_at > NOW() AND attempts < $5 AS usable
+        FROM sms_verification_codes
+        WHERE phone_number = $1 AND purpose = $4
+        ORDER BY created_at DESC
+        LIMIT 1
+        FOR UPDATE
+        "#,
+        CODE_HASH_SQL
+    ))
+    .bind(phone)
+    .bind(code.trim())
+    .bind(jwt_secret)
+    .bind(purpose)
+    .bind(MAX_ATTEMPTS)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(AppError::db("Failed to load sms code"))?
+    .ok_or(AppError::BadRequest("sms.code_invalid"))?;
+
+    let id: Uuid = row.get("id");
+    let matched: bool = row.get("matched");
+    let usable: bool = row.get("usable");
+    if !usable {
+        return Err(AppError::BadRequest("sms.code_invalid"));
+    }
+
+    let sql = if matched {
+        "UPDATE sms_verification_codes SET consumed_at = NOW() WHERE id = $1"
+    } else {
+        "UPDATE sms_verification_codes SET attempts = attempts + 1 WHERE id = $1"
+    };
+    sqlx::query(sql).bind(id).execute(&mut *tx).await.map_err(AppError::db("Failed to update sms code"))?;
+    tx.commit().await?;
+
+    if matched {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest("sms.code_invalid"))
+    }
+}
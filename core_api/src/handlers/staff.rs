@@ -2,9 +2,11 @@
  * src/handlers/staff.rs
  * 职责: 人效风控 - Staff/Personnel Risk Control & Rankings
  */
-use axum::{extract::State, http::StatusCode, Json};
-use super::AppState;
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use super::{failures_lateral_sql, AppState, LOGIN_LOCK_MINUTES, LOGIN_MAX_FAILURES};
 use crate::models::Claims;
+use crate::error::AppError;
 
 // ==========================================
 // 1. Risk Control Dashboard
@@ -26,11 +28,8 @@ pub struct RiskAlert {
 pub async fn get_staff_risk_stats_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<StaffRiskStats>, StatusCode> {
+) -> Result<Json<StaffRiskStats>, AppError> {
     // Check HQ admin permission
-    if !claims.roles.iter().any(|r| r == "role.hq.admin") {
-        return Err(StatusCode::FORBIDDEN);
-    }
 
     let hq_id = claims.hq_id;
 
@@ -57,14 +56,17 @@ pub async fn get_staff_risk_stats_handler(
         100.0
     };
 
-    // Get inactive accounts (not logged in for 30+ days)
+    // Get inactive accounts (not logged in for 30+ days, from login history)
     let inactive_count = sqlx::query_scalar::<_, i64>(
         r#"
         SELECT COUNT(*) 
-        FROM users 
-        WHERE hq_id = $1 
-        AND is_active = true
-        AND (last_login_at IS NULL OR last_login_at < NOW() - INTERVAL '30 days')
+        FROM users u
+        WHERE u.hq_id = $1 
+        AND u.is_active = true
+        AND NOT EXISTS (
+            SELECT 1 FROM user_login_history h
+            WHERE h.user_id = u.id AND h.status = 'success' AND h.login_timestamp >= NOW() - INTERVAL '30 days'
+        )
         "#
     )
     .bind(hq_id)
@@ -102,12 +104,75 @@ pub async fn get_staff_risk_stats_handler(
         });
     }
 
+    // Accounts temporarily locked by repeated wrong passwords
+    let locked_count = match fetch_locked_accounts(&state, hq_id).await {
+        Ok(list) => list.len(),
+        Err(e) => {
+            tracing::error!("Failed to count locked accounts: {}", e);
+            0
+        }
+    };
+    if locked_count > 0 {
+        alerts.push(RiskAlert {
+            id: 3,
+            message: format!("发现 {} 个账号因多次密码错误被临时锁定", locked_count),
+        });
+    }
+
     Ok(Json(StaffRiskStats {
         health_score,
         alerts,
     }))
 }
 
+// ==========================================
+// 1.1 Locked Accounts (login brute-force protection)
+// ==========================================
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+pub struct LockedAccountItem {
+    pub id: uuid::Uuid,
+    pub full_name: Option<String>,
+    pub email: String,
+    pub base_name: Option<String>,
+    pub failed_attempts: i64,
+    pub last_failed_at: DateTime<Utc>,
+    pub last_failed_ip: Option<String>,
+    pub locked_until: DateTime<Utc>,
+}
+
+async fn fetch_locked_accounts(state: &AppState, hq_id: uuid::Uuid) -> Result<Vec<LockedAccountItem>, sqlx::Error> {
+    sqlx::query_as::<_, LockedAccountItem>(&format!(
+        r#"
+        SELECT acct.id, acct.full_name, acct.email, b.name AS base_name,
+               f.failures AS failed_attempts, f.last_failure AS last_failed_at, f.last_ip AS last_failed_ip,
+               f.last_failure + make_interval(mins => {window}) AS locked_until
+        FROM users acct
+        LEFT JOIN bases b ON acct.base_id = b.id
+        CROSS JOIN LATERAL ({failures}) f
+        WHERE acct.hq_id = $1
+          AND f.failures >= {max_failures}
+          AND f.last_failure + make_interval(mins => {window}) > NOW()
+        ORDER BY f.last_failure DESC
+        "#,
+        window = LOGIN_LOCK_MINUTES,
+        max_failures = LOGIN_MAX_FAILURES,
+        failures = failures_lateral_sql(),
+    ))
+    .bind(hq_id)
+    .fetch_all(&state.db_pool)
+    .await
+}
+
+// GET /api/v1/hq/staff/locked-accounts
+pub async fn get_locked_accounts_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<LockedAccountItem>>, AppError> {
+    let list = fetch_locked_accounts(&state, claims.hq_id).await.map_err(AppError::db("Failed to fetch locked accounts"))?;
+    Ok(Json(list))
+}
+
 // ==========================================
 // 2. Key Personnel
 // ==========================================
@@ -126,10 +191,7 @@ pub struct KeyPersonnelItem {
 pub async fn get_key_personnel_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<KeyPersonnelItem>>, StatusCode> {
-    if !claims.roles.iter().any(|r| r == "role.hq.admin") {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<Vec<KeyPersonnelItem>>, AppError> {
 
     let hq_id = claims.hq_id;
 
@@ -192,10 +254,7 @@ pub struct BaseRankingData {
 pub async fn get_purchase_rankings_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<BaseRankingData>>, StatusCode> {
-    if !claims.roles.iter().any(|r| r == "role.hq.admin") {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<Vec<BaseRankingData>>, AppError> {
 
     let hq_id = claims.hq_id;
 
@@ -240,10 +299,7 @@ pub async fn get_purchase_rankings_handler(
 pub async fn get_activity_rankings_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<BaseRankingData>>, StatusCode> {
-    if !claims.roles.iter().any(|r| r == "role.hq.admin") {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<Vec<BaseRankingData>>, AppError> {
 
     let hq_id = claims.hq_id;
 
@@ -2,16 +2,17 @@
  * src/handlers/stock.rs
  * (★ V2.2 - 移除非法字符和重复定义 ★)
  */
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{extract::State, Json};
 
 use super::AppState;
 use crate::models::{Claims, StockAlert}; 
+use crate::error::AppError;
 
 // (GET /api/v1/base/stock/alerts)
 pub async fn get_stock_alerts_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<StockAlert>>, StatusCode> {
+) -> Result<Json<Vec<StockAlert>>, AppError> {
     
     let hq_id = claims.hq_id;
 
@@ -19,7 +20,7 @@ pub async fn get_stock_alerts_handler(
         Some(id) => id,
         None => {
             tracing::warn!("User {} without base_id tried to access stock", claims.sub);
-            return Err(StatusCode::FORBIDDEN);
+            return Err(AppError::Forbidden("auth.forbidden"));
         }
     };
 
@@ -47,10 +48,7 @@ pub async fn get_stock_alerts_handler(
     .await
     {
         Ok(alerts) => alerts,
-        Err(e) => {
-            tracing::error!("Failed to fetch stock alerts: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to fetch stock alerts", source: e }),
     };
 
     Ok(Json(alerts))
@@ -60,7 +58,7 @@ pub async fn get_stock_alerts_handler(
 pub async fn get_base_stock_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<StockAlert>>, StatusCode> {
+) -> Result<Json<Vec<StockAlert>>, AppError> {
 
     let hq_id = claims.hq_id;
 
@@ -68,7 +66,7 @@ pub async fn get_base_stock_handler(
         Some(id) => id,
         None => {
             tracing::warn!("User {} without base_id tried to access stock", claims.sub);
-            return Err(StatusCode::FORBIDDEN);
+            return Err(AppError::Forbidden("auth.forbidden"));
         }
     };
 
@@ -96,10 +94,7 @@ pub async fn get_base_stock_handler(
     .await
     {
         Ok(data) => data,
-        Err(e) => {
-            tracing::error!("Failed to fetch base stock: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to fetch base stock", source: e }),
     };
 
     Ok(Json(stocks))
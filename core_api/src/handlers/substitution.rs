@@ -0,0 +1,387 @@
+/*
+ * src/handlers/substitution.rs
+ * 职责: 老师请假代课 (Substitution)
+ * 请假审批通过后: 找出受影响的课程 -> 推荐有资质且有空的代课老师 -> 自动换人或标记"待安排代课" -> 通知已报名家长
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use super::{find_schedule_conflicts, notify_class_customers, AppState, ProposedSlot};
+use crate::models::Claims;
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SubstituteCandidate {
+    pub teacher_id: Uuid,
+    pub full_name: Option<String>,
+    pub nearby_class_count: i64, // 前后 3 天内已有课时数, 越少越优先
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubstitutionOutcome {
+    pub substitution_id: Uuid,
+    pub class_id: Uuid,
+    pub start_time: DateTime<Utc>,
+    pub status: String, // reassigned / needs_cover
+    pub substitute_teacher_id: Option<Uuid>,
+    pub candidate_teacher_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ClassSubstitution {
+    pub id: Uuid,
+    pub class_id: Uuid,
+    pub leave_request_id: Option<Uuid>,
+    pub original_teacher_id: Uuid,
+    pub original_teacher_name: Option<String>,
+    pub substitute_teacher_id: Option<Uuid>,
+    pub substitute_teacher_name: Option<String>,
+    pub candidate_teacher_ids: Vec<Uuid>,
+    pub status: String,
+    pub course_name_key: Option<String>,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssignSubstitutePayload {
+    pub teacher_id: Uuid,
+}
+
+#[derive(FromRow)]
+struct ClassSlot {
+    room_id: Uuid,
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+}
+
+// 为某节课找可代课老师: 同基地、在职、有该课程资质、未请假、该时段无课且在可上课时间内
+pub async fn find_substitute_candidates(
+    conn: &mut PgConnection,
+    class_id: Uuid,
+    absent_teacher_id: Uuid,
+) -> Result<Vec<SubstituteCandidate>, sqlx::Error> {
+    let class = sqlx::query_as::<_, ClassSlot>(
+        "SELECT room_id, start_time, end_time FROM classes WHERE id = $1",
+    )
+    .bind(class_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let pool = sqlx::query_as::<_, SubstituteCandidate>(
+        r#"
+        SELECT t.user_id AS teacher_id, u.full_name,
+               (SELECT COUNT(*) FROM class_teachers ct2
+                JOIN classes c2 ON ct2.class_id = c2.id
+                WHERE ct2.teacher_id = t.user_id
+                  AND c2.start_time BETWEEN c.start_time - INTERVAL '3 days' AND c.start_time + INTERVAL '3 days'
+               ) AS nearby_class_count
+        FROM classes c
+        JOIN teachers t ON t.base_id = c.base_id AND COALESCE(t.is_active, true)
+        JOIN users u ON t.user_id = u.id AND u.is_active = true
+        JOIN teacher_qualified_courses tqc ON tqc.teacher_id = t.user_id AND tqc.course_id = c.course_id
+        WHERE c.id = $1
+          AND t.user_id <> $2
+          AND NOT EXISTS (SELECT 1 FROM class_teachers ct WHERE ct.class_id = c.id AND ct.teacher_id = t.user_id)
+          AND NOT EXISTS (
+              SELECT 1 FROM leave_requests lr
+              WHERE lr.user_id = t.user_id AND lr.status = 'approved'
+                AND lr.start_time < c.end_time AND lr.end_time > c.start_time
+          )
+        ORDER BY nearby_class_count ASC, u.full_name ASC
+        "#,
+    )
+    .bind(class_id)
+    .bind(absent_teacher_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut candidates = Vec::new();
+    for candidate in pool {
+        let teacher_ids = [candidate.teacher_id];
+        let slot = ProposedSlot {
+            class_id: Some(class_id),
+            room_id: class.room_id,
+            teacher_ids: &teacher_ids,
+            start_time: class.start_time,
+            end_time: class.end_time,
+        };
+        // 只关心老师本人的冲突 (教室 / 学员冲突与换老师无关)
+        let busy = find_schedule_conflicts(&mut *conn, &slot)
+            .await?
+            .iter()
+            .any(|c| c.resource_id == candidate.teacher_id);
+        if !busy {
+            candidates.push(candidate);
+        }
+    }
+
+    Ok(candidates)
+}
+
+// 把某节课的老师从 from 换成 to, 并通知家长
+async fn reassign_class_teacher(
+    conn: &mut PgConnection,
+    class_id: Uuid,
+    from: Uuid,
+    to: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM class_teachers WHERE class_id = $1 AND teacher_id = $2")
+        .bind(class_id)
+        .bind(from)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("INSERT INTO class_teachers (class_id, teacher_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(class_id)
+        .bind(to)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("UPDATE classes SET needs_cover = false WHERE id = $1")
+        .bind(class_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let name: Option<String> = sqlx::query_scalar("SELECT full_name FROM users WHERE id = $1")
+        .bind(to)
+        .fetch_one(&mut *conn)
+        .await?;
+    notify_class_customers(
+        conn,
+        class_id,
+        "class_teacher_changed",
+        "上课老师变更",
+        &format!("原任课老师请假, 本节课将由 {} 老师代课, 上课时间与教室不变。", name.unwrap_or_default()),
+    )
+    .await?;
+    Ok(())
+}
+
+// 请假审批通过后调用: 处理请假时段内该老师所有未开始的课程
+pub async fn apply_teacher_leave(
+    conn: &mut PgConnection,
+    leave_request_id: Uuid,
+) -> Result<Vec<SubstitutionOutcome>, sqlx::Error> {
+    let (teacher_id, leave_start, leave_end): (Uuid, DateTime<Utc>, DateTime<Utc>) = sqlx::query_as(
+        "SELECT user_id, start_time, end_time FROM leave_requests WHERE id = $1",
+    )
+    .bind(leave_request_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let affected: Vec<(Uuid, Uuid, Uuid, DateTime<Utc>)> = sqlx::query_as(
+        r#"
+        SELECT c.id, c.hq_id, c.base_id, c.start_time
+        FROM classes c
+        JOIN class_teachers ct ON ct.class_id = c.id
+        WHERE ct.teacher_id = $1
+          AND COALESCE(c.status, 'scheduled') = 'scheduled'
+          AND c.start_time > NOW()
+          AND c.start_time < $3 AND c.end_time > $2
+        ORDER BY c.start_time ASC
+        FOR UPDATE OF c
+        "#,
+    )
+    .bind(teacher_id)
+    .bind(leave_start)
+    .bind(leave_end)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut outcomes = Vec::new();
+    for (class_id, hq_id, base_id, start_time) in affected {
+        let candidates = find_substitute_candidates(&mut *conn, class_id, teacher_id).await?;
+        let candidate_ids: Vec<Uuid> = candidates.iter().map(|c| c.teacher_id).collect();
+
+        let (status, substitute) = match candidate_ids.first() {
+            Some(&best) => {
+                reassign_class_teacher(&mut *conn, class_id, teacher_id, best).await?;
+                ("reassigned", Some(best))
+            }
+            None => {
+                sqlx::query("UPDATE classes SET needs_cover = true WHERE id = $1")
+                    .bind(class_id)
+                    .execute(&mut *conn)
+                    .await?;
+                notify_class_customers(
+                    &mut *conn,
+                    class_id,
+                    "class_needs_cover",
+                    "上课老师请假",
+                    "本节课任课老师请假, 基地正在安排代课老师, 确认后会再次通知您。",
+                )
+                .await?;
+                ("needs_cover", None)
+            }
+        };
+
+        let substitution_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO class_substitutions (
+                hq_id, base_id, class_id, leave_request_id, original_teacher_id,
+                substitute_teacher_id, candidate_teacher_ids, status, resolved_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, CASE WHEN $8 = 'reassigned' THEN NOW() END)
+            RETURNING id
+            "#,
+        )
+        .bind(hq_id)
+        .bind(base_id)
+        .bind(class_id)
+        .bind(leave_request_id)
+        .bind(teacher_id)
+        .bind(substitute)
+        .bind(&candidate_ids)
+        .bind(status)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        outcomes.push(SubstitutionOutcome {
+            substitution_id,
+            class_id,
+            start_time,
+            status: status.to_string(),
+            substitute_teacher_id: substitute,
+            candidate_teacher_ids: candidate_ids,
+        });
+    }
+
+    Ok(outcomes)
+}
+
+// (GET /api/v1/base/substitutions) 代课记录 (默认 needs_cover 排在前面)
+// 分页; 筛选: status / original_teacher_id / substitute_teacher_id / start_from / start_to
+const SUBSTITUTION_LIST: ListSpec = ListSpec {
+    sorts: &[
+        ("needs_cover", "(s.status = 'needs_cover')"),
+        ("start_time", "c.start_time"),
+        ("created_at", "s.created_at"),
+    ],
+    default_sort: "-needs_cover,start_time",
+    tiebreak: "s.id",
+    filters: &[
+        Filter::text("status", "s.status"),
+        Filter::uuid("original_teacher_id", "s.original_teacher_id"),
+        Filter::uuid("substitute_teacher_id", "s.substitute_teacher_id"),
+        Filter::date_from("start_from", "c.start_time"),
+        Filter::date_to("start_to", "c.start_time"),
+    ],
+    search: &[],
+};
+
+pub async fn get_substitutions_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    list: ListQuery,
+) -> Result<Json<Page<ClassSubstitution>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    let hq_id = claims.hq_id;
+
+    let page = list
+        .fetch_page(&state.db_pool, &SUBSTITUTION_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT s.id, s.class_id, s.leave_request_id,
+                       s.original_teacher_id, ou.full_name AS original_teacher_name,
+                       s.substitute_teacher_id, su.full_name AS substitute_teacher_name,
+                       s.candidate_teacher_ids, s.status,
+                       co.name_key AS course_name_key, c.start_time, c.end_time, s.created_at
+                FROM class_substitutions s
+                JOIN classes c ON s.class_id = c.id
+                LEFT JOIN courses co ON c.course_id = co.id
+                LEFT JOIN users ou ON s.original_teacher_id = ou.id
+                LEFT JOIN users su ON s.substitute_teacher_id = su.id
+                WHERE s.hq_id = "#,
+            );
+            qb.push_bind(hq_id).push(" AND s.base_id = ").push_bind(base_id);
+        })
+        .await?;
+
+    Ok(Json(page))
+}
+
+// (GET /api/v1/base/substitutions/:id/candidates) 实时推荐代课老师
+pub async fn get_substitute_candidates_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(substitution_id): Path<Uuid>,
+) -> Result<Json<Vec<SubstituteCandidate>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let mut conn = state.db_pool.acquire().await?;
+
+    let (class_id, original_teacher_id): (Uuid, Uuid) = sqlx::query_as(
+        "SELECT class_id, original_teacher_id FROM class_substitutions WHERE id = $1 AND hq_id = $2 AND base_id = $3",
+    )
+    .bind(substitution_id)
+    .bind(claims.hq_id)
+    .bind(base_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    let candidates = find_substitute_candidates(&mut conn, class_id, original_teacher_id)
+        .await
+        .map_err(AppError::db("Failed to find substitutes"))?;
+
+    Ok(Json(candidates))
+}
+
+// (POST /api/v1/base/substitutions/:id/assign) 手动指定代课老师
+pub async fn assign_substitute_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(substitution_id): Path<Uuid>,
+    Json(payload): Json<AssignSubstitutePayload>,
+) -> Result<StatusCode, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let mut tx = state.db_pool.begin().await?;
+
+    let (class_id, current_teacher_id): (Uuid, Uuid) = sqlx::query_as(
+        r#"
+        SELECT class_id, COALESCE(substitute_teacher_id, original_teacher_id)
+        FROM class_substitutions
+        WHERE id = $1 AND hq_id = $2 AND base_id = $3
+        FOR UPDATE
+        "#,
+    )
+    .bind(substitution_id)
+    .bind(claims.hq_id)
+    .bind(base_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
+
+    // 只能从推荐范围内选 (资质 / 空闲 / 未请假)
+    let candidates = find_substitute_candidates(&mut tx, class_id, current_teacher_id)
+        .await
+        .map_err(AppError::db("Failed to find substitutes"))?;
+    if !candidates.iter().any(|c| c.teacher_id == payload.teacher_id) {
+        return Err(AppError::Conflict("substitution.teacher_unavailable"));
+    }
+
+    reassign_class_teacher(&mut tx, class_id, current_teacher_id, payload.teacher_id)
+        .await
+        .map_err(AppError::db("Failed to assign substitute"))?;
+
+    sqlx::query(
+        "UPDATE class_substitutions SET substitute_teacher_id = $1, status = 'reassigned', resolved_at = NOW() WHERE id = $2",
+    )
+    .bind(payload.teacher_id)
+    .bind(substitution_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(StatusCode::OK)
+}
@@ -4,9 +4,9 @@
  */
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
     Json,
 };
+use sqlx::{Postgres, QueryBuilder};
 use uuid::Uuid;
 use chrono::Utc;
 use rand::Rng; // 需要: cargo add rand
@@ -17,6 +17,44 @@ use crate::models::{
     UploadPaymentProofPayload, ShipOrderPayload,
     CreateProductPayload, UpdateProductPayload
 };
+use crate::tenant::{Owned, TenantScope};
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+
+// --- 采购单列表 (总部 / 基地共用) ---
+// 分页; 筛选: status / base_id / created_from / created_to; q 搜单号和商品名
+const SUPPLY_ORDER_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "o.created_at"), ("total_amount", "o.total_amount_cents")],
+    default_sort: "-created_at",
+    tiebreak: "o.id",
+    filters: &[
+        Filter::text("status", "o.status"),
+        Filter::uuid("base_id", "o.base_id"),
+        Filter::date_from("created_from", "o.created_at"),
+        Filter::date_to("created_to", "o.created_at"),
+    ],
+    search: &["o.order_no", "items_summary.summary"],
+};
+
+fn push_supply_order_select(qb: &mut QueryBuilder<'static, Postgres>) {
+    qb.push(
+        r#"
+        SELECT
+            o.id, o.order_no, o.base_id, o.total_amount_cents, o.status,
+            o.payment_proof_url, o.logistics_info, o.created_at,
+            b.name as base_name,
+            -- 拼接商品名用于列表展示
+            items_summary.summary as items_summary
+        FROM supply_orders o
+        LEFT JOIN bases b ON o.base_id = b.id
+        LEFT JOIN LATERAL (
+            SELECT STRING_AGG(product_name || ' x' || quantity, ', ') AS summary
+            FROM supply_order_items WHERE supply_order_id = o.id
+        ) items_summary ON true
+        WHERE 1 = 1
+        "#,
+    );
+}
 
 // ==========================================
 // 1. 基地端：浏览商城 & 采购
@@ -26,7 +64,7 @@ use crate::models::{
 pub async fn get_hq_products_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<HqProduct>>, StatusCode> {
+) -> Result<Json<Vec<HqProduct>>, AppError> {
     
     // 场景 1: 基地用户 (Base ID 存在) -> 只看上架的
     if let Some(_base_id) = claims.base_id {
@@ -42,10 +80,7 @@ pub async fn get_hq_products_handler(
         .bind(claims.hq_id) 
         .fetch_all(&state.db_pool)
         .await
-        .map_err(|e| {
-            tracing::error!("Fetch base products failed: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(AppError::db("Fetch base products failed"))?;
         
         return Ok(Json(products));
     }
@@ -63,10 +98,7 @@ pub async fn get_hq_products_handler(
     .bind(claims.hq_id)
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Fetch hq products failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Fetch hq products failed"))?;
 
     Ok(Json(products))
 }
@@ -76,16 +108,16 @@ pub async fn create_supply_order_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateSupplyOrderPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, AppError> {
     // 1. 权限校验
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     if payload.items.is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(AppError::BadRequest("request.invalid"));
     }
 
     // 2. 开启事务
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut tx = state.db_pool.begin().await?;
 
     // 3. 计算总价并验证库存
     let mut total_amount_cents = 0;
@@ -94,21 +126,21 @@ pub async fn create_supply_order_handler(
     for item in &payload.items {
         // 查商品信息
         let product = sqlx::query!(
-            "SELECT name, price_cents, stock_quantity FROM hq_products WHERE id = $1 AND is_active = true FOR UPDATE",
-            item.product_id
+            "SELECT name, price_cents, stock_quantity FROM hq_products WHERE id = $1 AND hq_id = $2 AND is_active = true FOR UPDATE",
+            item.product_id,
+            claims.hq_id
         )
         .fetch_optional(&mut *tx)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
         let product = match product {
             Some(p) => p,
-            None => return Err(StatusCode::BAD_REQUEST), 
+            None => return Err(AppError::BadRequest("request.invalid")), 
         };
 
         // ★★★ 修复点：处理 Option 类型，如果库存为 NULL 则视为 0 ★★★
         if product.stock_quantity.unwrap_or(0) < item.quantity {
-             return Err(StatusCode::CONFLICT); // 库存不足
+             return Err(AppError::Conflict("stock.insufficient"));
         }
 
         let item_total = product.price_cents * item.quantity;
@@ -116,7 +148,7 @@ pub async fn create_supply_order_handler(
 
         // 扣减库存 (SQL 会自动处理 NULL 问题，但逻辑上我们已经保证了安全)
         sqlx::query!("UPDATE hq_products SET stock_quantity = stock_quantity - $1 WHERE id = $2", item.quantity, item.product_id)
-            .execute(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .execute(&mut *tx).await?;
 
         order_items_data.push((item.product_id, product.name, item.quantity, product.price_cents));
     }
@@ -147,10 +179,7 @@ pub async fn create_supply_order_handler(
     .bind(total_amount_cents)
     .fetch_one(&mut *tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Create order failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Create order failed"))?;
 
     // 6. 插入订单明细
     for (p_id, p_name, qty, price) in order_items_data {
@@ -162,11 +191,10 @@ pub async fn create_supply_order_handler(
             order_id, p_id, p_name, qty, price
         )
         .execute(&mut *tx)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
     }
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
 
     Ok(Json(serde_json::json!({ "success": true, "order_id": order_id })))
 }
@@ -178,7 +206,7 @@ pub async fn upload_payment_proof_handler(
     claims: Claims,
     Path(order_id): Path<Uuid>,
     Json(payload): Json<UploadPaymentProofPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, AppError> {
     // 只能更新自己基地的、未支付的订单
     let result = sqlx::query(
         r#"
@@ -191,11 +219,10 @@ pub async fn upload_payment_proof_handler(
     .bind(order_id)
     .bind(claims.base_id) // 安全限制
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(AppError::NotFound("resource.not_found"));
     }
 
     Ok(Json(serde_json::json!({ "success": true })))
@@ -206,38 +233,20 @@ pub async fn upload_payment_proof_handler(
 // ==========================================
 
 // GET /api/v1/hq/supply/orders
-// 总部查看所有采购单
+// 总部查看所有采购单, 分页 / 筛选见 SUPPLY_ORDER_LIST
 pub async fn get_all_supply_orders_handler(
     State(state): State<AppState>,
-    claims: Claims,
-) -> Result<Json<Vec<SupplyOrder>>, StatusCode> {
-    // 只有总部管理员能看
-    let is_hq = claims.roles.iter().any(|r| r == "role.hq.admin" || r == "role.hq.finance");
-    if !is_hq { return Err(StatusCode::FORBIDDEN); }
-
-    let orders = sqlx::query_as::<_, SupplyOrder>(
-        r#"
-        SELECT 
-            o.id, o.order_no, o.base_id, o.total_amount_cents, o.status, 
-            o.payment_proof_url, o.logistics_info, o.created_at,
-            b.name as base_name,
-            -- 简单拼接商品名用于列表展示
-            (SELECT STRING_AGG(product_name || ' x' || quantity, ', ') FROM supply_order_items WHERE supply_order_id = o.id) as items_summary
-        FROM supply_orders o
-        LEFT JOIN bases b ON o.base_id = b.id
-        WHERE o.hq_id = $1
-        ORDER BY o.created_at DESC
-        "#
-    )
-    .bind(claims.hq_id)
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Fetch orders failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    scope: TenantScope,
+    list: ListQuery,
+) -> Result<Json<Page<SupplyOrder>>, AppError> {
+    let page = list
+        .fetch_page(&state.db_pool, &SUPPLY_ORDER_LIST, |qb| {
+            push_supply_order_select(qb);
+            scope.push_filter(qb, "o");
+        })
+        .await?;
 
-    Ok(Json(orders))
+    Ok(Json(page))
 }
 
 // PUT /api/v1/hq/supply/orders/:id/confirm
@@ -246,18 +255,18 @@ pub async fn confirm_supply_payment_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(order_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let is_hq_finance = claims.roles.iter().any(|r| r == "role.hq.admin" || r == "role.hq.finance");
-    if !is_hq_finance { return Err(StatusCode::FORBIDDEN); }
-
-    sqlx::query(
+) -> Result<Json<serde_json::Value>, AppError> {
+    let result = sqlx::query(
         "UPDATE supply_orders SET status = 'paid', updated_at = NOW() WHERE id = $1 AND hq_id = $2"
     )
     .bind(order_id)
     .bind(claims.hq_id)
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("resource.not_found"));
+    }
 
     Ok(Json(serde_json::json!({ "success": true, "status": "paid" })))
 }
@@ -269,13 +278,10 @@ pub async fn ship_supply_order_handler(
     claims: Claims,
     Path(order_id): Path<Uuid>,
     Json(payload): Json<ShipOrderPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let is_hq_admin = claims.roles.iter().any(|r| r == "role.hq.admin");
-    if !is_hq_admin { return Err(StatusCode::FORBIDDEN); }
-
+) -> Result<Json<serde_json::Value>, AppError> {
     // 这里未来可以增加逻辑：如果是“SaaS服务”商品，自动延长基地的有效期
     
-    sqlx::query(
+    let result = sqlx::query(
         r#"
         UPDATE supply_orders 
         SET status = 'shipped', logistics_info = $1, updated_at = NOW() 
@@ -286,8 +292,11 @@ pub async fn ship_supply_order_handler(
     .bind(order_id)
     .bind(claims.hq_id)
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("resource.not_found"));
+    }
 
     Ok(Json(serde_json::json!({ "success": true, "status": "shipped" })))
 }
@@ -296,9 +305,9 @@ pub async fn create_product_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateProductPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, AppError> {
     // 只有总部管理员能操作 (简单的权限检查)
-    // if claims.base_id.is_some() { return Err(StatusCode::FORBIDDEN); }
+    // if claims.base_id.is_some() { return Err(AppError::Forbidden("auth.forbidden")); }
 
     sqlx::query(
         r#"
@@ -316,10 +325,7 @@ pub async fn create_product_handler(
     .bind(payload.is_active)
     .execute(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Create product failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Create product failed"))?;
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
@@ -331,12 +337,12 @@ pub async fn update_product_handler(
     claims: Claims,
     Path(id): Path<Uuid>,
     Json(payload): Json<UpdateProductPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, AppError> {
     // 只有总部管理员能操作
-    // if claims.base_id.is_some() { return Err(StatusCode::FORBIDDEN); }
+    // if claims.base_id.is_some() { return Err(AppError::Forbidden("auth.forbidden")); }
 
     // 使用 COALESCE 动态更新：如果前端传了 null，就保持原值
-    sqlx::query(
+    let result = sqlx::query(
         r#"
         UPDATE hq_products 
         SET 
@@ -361,53 +367,42 @@ pub async fn update_product_handler(
     .bind(claims.hq_id)
     .execute(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Update product failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Update product failed"))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("resource.not_found"));
+    }
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
+
+// GET /api/v1/supply/orders
+// 基地查看自己的采购单
 pub async fn get_base_supply_orders_handler(
     State(state): State<AppState>,
-    claims: Claims,
-) -> Result<Json<Vec<SupplyOrder>>, StatusCode> {
-    // 1. 必须是基地用户
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+    scope: TenantScope,
+    list: ListQuery,
+) -> Result<Json<Page<SupplyOrder>>, AppError> {
+    // 1. 必须是基地用户, 只查自己的订单
+    if scope.base_id.is_none() {
+        return Err(AppError::Forbidden("auth.base_required"));
+    }
 
-    // 2. 只查 base_id = 自己的订单
-    let orders = sqlx::query_as::<_, SupplyOrder>(
-        r#"
-        SELECT 
-            o.id, o.order_no, o.base_id, o.total_amount_cents, o.status, 
-            o.payment_proof_url, o.logistics_info, o.created_at,
-            b.name as base_name,
-            -- 子查询拼接商品详情字符串
-            (SELECT STRING_AGG(product_name || ' x' || quantity, ', ') 
-             FROM supply_order_items 
-             WHERE supply_order_id = o.id) as items_summary
-        FROM supply_orders o
-        LEFT JOIN bases b ON o.base_id = b.id
-        WHERE o.base_id = $1
-        ORDER BY o.created_at DESC
-        "#
-    )
-    .bind(base_id)
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Fetch base supply orders failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let page = list
+        .fetch_page(&state.db_pool, &SUPPLY_ORDER_LIST, |qb| {
+            push_supply_order_select(qb);
+            scope.push_filter(qb, "o");
+        })
+        .await?;
 
-    Ok(Json(orders))
+    Ok(Json(page))
 }
 
 pub async fn get_base_inventory_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<Vec<serde_json::Value>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     let items = sqlx::query!(
         r#"
@@ -423,10 +418,7 @@ pub async fn get_base_inventory_handler(
     )
     .fetch_all(&state.db_pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Fetch inventory failed: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Fetch inventory failed"))?;
 
     let result = items.into_iter().map(|item| {
         serde_json::json!({
@@ -450,12 +442,12 @@ pub async fn consume_inventory_handler(
     claims: Claims,
     Path(product_id): Path<Uuid>,
     Json(payload): Json<crate::models::ConsumeInventoryPayload>, // 使用 models 里的结构体
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
-    if payload.quantity <= 0 { return Err(StatusCode::BAD_REQUEST); }
+    if payload.quantity <= 0 { return Err(AppError::BadRequest("request.invalid")); }
 
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut tx = state.db_pool.begin().await?;
 
     // 1. 检查库存
     let current_qty = sqlx::query_scalar!(
@@ -463,12 +455,11 @@ pub async fn consume_inventory_handler(
         base_id, product_id
     )
     .fetch_optional(&mut *tx)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    .await?
+    .ok_or(AppError::NotFound("resource.not_found"))?;
 
     if current_qty < payload.quantity {
-        return Err(StatusCode::CONFLICT); // 库存不足
+        return Err(AppError::Conflict("stock.insufficient"));
     }
 
     // 2. 扣减库存
@@ -477,8 +468,7 @@ pub async fn consume_inventory_handler(
         payload.quantity, base_id, product_id
     )
     .execute(&mut *tx)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     // 3. 写入日志
     let change_amount = -payload.quantity;
@@ -487,52 +477,61 @@ pub async fn consume_inventory_handler(
         base_id, product_id, change_amount, payload.reason
     )
     .execute(&mut *tx)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
 // GET /api/v1/base/inventory/logs
-// 查询库存变动日志
+// 查询库存变动日志; 分页; 筛选: product_id / created_from / created_to; q 搜商品名、SKU 和原因
+const INVENTORY_LOG_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "l.created_at")],
+    default_sort: "-created_at",
+    tiebreak: "l.id",
+    filters: &[
+        Filter::uuid("product_id", "l.product_id"),
+        Filter::date_from("created_from", "l.created_at"),
+        Filter::date_to("created_to", "l.created_at"),
+    ],
+    search: &["p.name", "p.sku", "l.reason"],
+};
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+pub struct InventoryLogItem {
+    pub id: Uuid,
+    pub product_name: String,
+    pub sku: Option<String>,
+    pub image_url: Option<String>,
+    pub change_amount: i32,
+    pub reason: String,
+    pub created_at: Option<chrono::DateTime<Utc>>,
+}
+
 pub async fn get_inventory_logs_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<serde_json::Value>>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
-
-    let logs = sqlx::query!(
-        r#"
-        SELECT 
-            l.id, l.change_amount, l.reason, l.created_at,
-            p.name as product_name, p.sku, p.image_url
-        FROM inventory_logs l
-        JOIN hq_products p ON l.product_id = p.id
-        WHERE l.base_id = $1
-        ORDER BY l.created_at DESC
-        LIMIT 100
-        "#,
-        base_id
-    )
-    .fetch_all(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let result = logs.into_iter().map(|log| {
-        serde_json::json!({
-            "id": log.id,
-            "product_name": log.product_name,
-            "sku": log.sku,
-            "image_url": log.image_url,
-            "change_amount": log.change_amount,
-            "reason": log.reason,
-            "created_at": log.created_at
+    list: ListQuery,
+) -> Result<Json<Page<InventoryLogItem>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+
+    let page = list
+        .fetch_page(&state.db_pool, &INVENTORY_LOG_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    l.id, l.change_amount, l.reason, l.created_at,
+                    p.name as product_name, p.sku, p.image_url
+                FROM inventory_logs l
+                JOIN hq_products p ON l.product_id = p.id
+                WHERE l.base_id = "#,
+            );
+            qb.push_bind(base_id);
         })
-    }).collect();
+        .await?;
 
-    Ok(Json(result))
+    Ok(Json(page))
 }
 
 // PUT /api/v1/supply/orders/:id/receive
@@ -541,27 +540,27 @@ pub async fn receive_supply_order_handler(
     State(state): State<AppState>,
     claims: Claims,
     Path(order_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    let mut tx = state.db_pool.begin().await?;
 
     let order = sqlx::query!(
         "SELECT status FROM supply_orders WHERE id = $1 AND base_id = $2 FOR UPDATE",
         order_id, base_id
     )
     .fetch_optional(&mut *tx)
-    .await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     match order {
         Some(o) if o.status.as_deref() == Some("shipped") => {},
-        _ => return Err(StatusCode::BAD_REQUEST), 
+        _ => return Err(AppError::BadRequest("request.invalid")), 
     }
 
     let items = sqlx::query!(
         "SELECT product_id, quantity FROM supply_order_items WHERE supply_order_id = $1",
         order_id
     )
-    .fetch_all(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .fetch_all(&mut *tx).await?;
 
     for item in items {
         sqlx::query!(
@@ -573,26 +572,30 @@ pub async fn receive_supply_order_handler(
             "#,
             base_id, item.product_id, item.quantity
         )
-        .execute(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .execute(&mut *tx).await?;
     }
 
     sqlx::query!("UPDATE supply_orders SET status = 'completed', updated_at = NOW() WHERE id = $1", order_id)
-        .execute(&mut *tx).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .execute(&mut *tx).await?;
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
 pub async fn restock_inventory_handler(
     State(state): State<AppState>,
     claims: Claims,
+    scope: TenantScope,
     Path(product_id): Path<Uuid>,
     Json(payload): Json<crate::models::ConsumeInventoryPayload>, 
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
-    if payload.quantity <= 0 { return Err(StatusCode::BAD_REQUEST); }
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    if payload.quantity <= 0 { return Err(AppError::BadRequest("request.invalid")); }
+
+    let mut tx = state.db_pool.begin().await?;
 
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // 只能补本总部的商品
+    scope.ensure_visible(&mut *tx, Owned::Product(product_id)).await?;
 
     // 1. 增加库存 (如果不存在则插入)
     sqlx::query!(
@@ -605,8 +608,7 @@ pub async fn restock_inventory_handler(
         base_id, product_id, payload.quantity
     )
     .execute(&mut *tx)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     // 2. 写入日志 (change_amount 为正数)
     let change_amount = payload.quantity;
@@ -615,10 +617,9 @@ pub async fn restock_inventory_handler(
         base_id, product_id, change_amount, payload.reason
     )
     .execute(&mut *tx)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
\ No newline at end of file
@@ -12,13 +12,14 @@ use uuid::Uuid;
 use super::AppState;
 // 导入 models
 use crate::models::{Claims, Teacher};
+use crate::error::AppError;
 
 // (GET /api/v1/base/teachers - 获取 "本基地" 可用的教师列表)
 // (★ V2 - 基地安全加固 ★)
 pub async fn get_base_teachers_handler(
     State(state): State<AppState>,
     claims: Claims, // <-- 【修改】必须出示“钥匙”
-) -> Result<Json<Vec<Teacher>>, StatusCode> {
+) -> Result<Json<Vec<Teacher>>, AppError> {
 
     let hq_id = claims.hq_id;
 
@@ -28,7 +29,7 @@ pub async fn get_base_teachers_handler(
         Some(id) => id,
         None => {
             tracing::warn!("User {} without base_id tried to access base-specific teachers", claims.sub);
-            return Err(StatusCode::FORBIDDEN); // 403 Forbidden
+            return Err(AppError::Forbidden("auth.forbidden")); // 403 Forbidden
         }
     };
 
@@ -46,10 +47,7 @@ pub async fn get_base_teachers_handler(
     .await
     {
         Ok(teachers) => teachers,
-        Err(e) => {
-            tracing::error!("Failed to fetch base teachers: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+        Err(e) => return Err(AppError::Database { context: "Failed to fetch base teachers", source: e }),
     };
     Ok(Json(teachers))
 }
@@ -58,9 +56,9 @@ pub async fn get_base_teachers_handler(
 pub async fn get_teacher_dashboard_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<crate::models::TeacherDashboardResponse>, StatusCode> {
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| StatusCode::UNAUTHORIZED)?;
-    let base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
+) -> Result<Json<crate::models::TeacherDashboardResponse>, AppError> {
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::Unauthorized("auth.unauthorized"))?;
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     // 1. 获取教师 ID (可选，非教务岗可能没有此记录)
     let teacher_id: Option<Uuid> = sqlx::query_scalar!(
@@ -91,8 +89,7 @@ pub async fn get_teacher_dashboard_handler(
             tid
         )
         .fetch_one(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .await?
         .unwrap_or(0);
 
         // 本月消课数
@@ -106,8 +103,7 @@ pub async fn get_teacher_dashboard_handler(
             tid
         )
         .fetch_one(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .await?
         .unwrap_or(0);
 
         // 即将开始的课程
@@ -132,8 +128,7 @@ pub async fn get_teacher_dashboard_handler(
         )
         .bind(tid)
         .fetch_all(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
     }
 
     // 待跟进线索 (这属于 User 职能，不依赖老师档案)
@@ -142,8 +137,7 @@ pub async fn get_teacher_dashboard_handler(
         user_id
     )
     .fetch_one(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .await?
     .unwrap_or(0);
 
     Ok(Json(crate::models::TeacherDashboardResponse {
@@ -3,13 +3,16 @@
  * 职责: 试听课管理 - Trial Class Management for Base Admin
  */
 use axum::{
-    extract::{Path, Query, State},
+    extract::{Path, State},
     http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use super::AppState;
 use crate::models::Claims;
+use crate::tenant::{Owned, TenantScope};
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
 
 // ==========================================
 // 1. Data Models
@@ -76,30 +79,35 @@ pub struct TrialClassFeedback {
     pub conversion_intent: String,
 }
 
-#[derive(Deserialize)]
-pub struct TrialClassQuery {
-    pub status: Option<String>,
-}
-
 // ==========================================
 // 2. API Handlers
 // ==========================================
 
 // GET /api/v1/base/trial-classes - 获取试听课列表
+// 分页; 筛选: status / teacher_id / lead_id / scheduled_from / scheduled_to; q 搜学员、家长姓名和手机号
+const TRIAL_CLASS_LIST: ListSpec = ListSpec {
+    sorts: &[("scheduled_at", "tc.scheduled_at"), ("created_at", "tc.created_at")],
+    default_sort: "-scheduled_at",
+    tiebreak: "tc.id",
+    filters: &[
+        Filter::text("status", "tc.status"),
+        Filter::uuid("teacher_id", "tc.teacher_id"),
+        Filter::uuid("lead_id", "tc.lead_id"),
+        Filter::date_from("scheduled_from", "tc.scheduled_at"),
+        Filter::date_to("scheduled_to", "tc.scheduled_at"),
+    ],
+    search: &["tc.student_name", "tc.parent_name", "tc.parent_phone"],
+};
+
 pub async fn get_trial_classes_handler(
     State(state): State<AppState>,
     claims: Claims,
-    Query(params): Query<TrialClassQuery>,
-) -> Result<Json<Vec<TrialClassItem>>, StatusCode> {
-    // 检查权限：必须是基地角色
-    if !claims.roles.iter().any(|r| r.starts_with("role.base.")) {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
+    list: ListQuery,
+) -> Result<Json<Page<TrialClassItem>>, AppError> {
     // 获取 base_id
     let base_id = match claims.base_id {
         Some(id) => id,
-        None => return Err(StatusCode::FORBIDDEN),
+        None => return Err(AppError::Forbidden("auth.forbidden")),
     };
 
     #[derive(sqlx::FromRow)]
@@ -127,56 +135,28 @@ pub async fn get_trial_classes_handler(
         created_at: chrono::DateTime<chrono::Utc>,
     }
 
-    let rows = if let Some(ref status_val) = params.status {
-       sqlx::query_as::<_, TrialClassRow>(
-            r#"
-            SELECT 
-                tc.id, tc.base_id, tc.lead_id,
-                tc.student_name, tc.student_age, tc.student_grade,
-                tc.parent_name, tc.parent_phone, tc.parent_wechat,
-                tc.scheduled_at, tc.duration, tc.teacher_id,
-                u.full_name as teacher_name,
-                tc.classroom, tc.course_type, tc.status,
-                tc.student_performance, tc.parent_satisfaction,
-                tc.conversion_intent, tc.notes, tc.created_at
-            FROM trial_classes tc
-            LEFT JOIN users u ON tc.teacher_id = u.id
-            WHERE tc.base_id = $1 AND tc.status = $2
-            ORDER BY tc.scheduled_at DESC
-            "#
-        )
-        .bind(base_id)
-        .bind(status_val)
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    } else {
-        sqlx::query_as::<_, TrialClassRow>(
-            r#"
-            SELECT 
-                tc.id, tc.base_id, tc.lead_id,
-                tc.student_name, tc.student_age, tc.student_grade,
-                tc.parent_name, tc.parent_phone, tc.parent_wechat,
-                tc.scheduled_at, tc.duration, tc.teacher_id,
-                u.full_name as teacher_name,
-                tc.classroom, tc.course_type, tc.status,
-                tc.student_performance, tc.parent_satisfaction,
-                tc.conversion_intent, tc.notes, tc.created_at
-            FROM trial_classes tc
-            LEFT JOIN users u ON tc.teacher_id = u.id
-            WHERE tc.base_id = $1
-            ORDER BY tc.scheduled_at DESC
-            "#
-        )
-        .bind(base_id)
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    };
+    let page = list
+        .fetch_page::<TrialClassRow>(&state.db_pool, &TRIAL_CLASS_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    tc.id, tc.base_id, tc.lead_id,
+                    tc.student_name, tc.student_age, tc.student_grade,
+                    tc.parent_name, tc.parent_phone, tc.parent_wechat,
+                    tc.scheduled_at, tc.duration, tc.teacher_id,
+                    u.full_name as teacher_name,
+                    tc.classroom, tc.course_type, tc.status,
+                    tc.student_performance, tc.parent_satisfaction,
+                    tc.conversion_intent, tc.notes, tc.created_at
+                FROM trial_classes tc
+                LEFT JOIN users u ON tc.teacher_id = u.id
+                WHERE tc.base_id = "#,
+            );
+            qb.push_bind(base_id);
+        })
+        .await?;
 
-    let trial_classes: Vec<TrialClassItem> = rows
-        .into_iter()
-        .map(|row| TrialClassItem {
+    let trial_classes = page.map(|row| TrialClassItem {
             id: row.id,
             base_id: row.base_id,
             lead_id: row.lead_id,
@@ -198,8 +178,7 @@ pub async fn get_trial_classes_handler(
             conversion_intent: row.conversion_intent,
             notes: row.notes,
             created_at: row.created_at.to_rfc3339(),
-        })
-        .collect();
+        });
 
     Ok(Json(trial_classes))
 }
@@ -209,21 +188,26 @@ pub async fn create_trial_class_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateTrialClassPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    if !claims.roles.iter().any(|r| r.starts_with("role.base.")) {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<serde_json::Value>, AppError> {
 
     let base_id = match claims.base_id {
         Some(id) => id,
-        None => return Err(StatusCode::FORBIDDEN),
+        None => return Err(AppError::Forbidden("auth.forbidden")),
     };
 
     // 解析 scheduled_at
     let scheduled_at = chrono::DateTime::parse_from_rfc3339(&payload.scheduled_at)
-        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .map_err(|_| AppError::BadRequest("request.invalid"))?
         .with_timezone(&chrono::Utc);
 
+    let scope = TenantScope::from_claims(&claims);
+    if let Some(lead_id) = payload.lead_id {
+        scope.ensure_owned(&state.db_pool, Owned::Lead(lead_id)).await?;
+    }
+    if let Some(teacher_id) = payload.teacher_id {
+        scope.ensure_owned(&state.db_pool, Owned::User(teacher_id)).await?;
+    }
+
     let trial_class_id = uuid::Uuid::new_v4();
 
     // Create trial class
@@ -256,8 +240,7 @@ pub async fn create_trial_class_handler(
         uuid::Uuid::parse_str(&claims.sub).unwrap_or_default()
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     // Auto-create customer if not exists
     let existing_customer = sqlx::query!(
@@ -301,9 +284,11 @@ pub async fn create_trial_class_handler(
 // GET /api/v1/base/trial-classes/:id - 获取试听课详情
 pub async fn get_trial_class_handler(
     State(state): State<AppState>,
-    _claims: Claims,
+    scope: TenantScope,
     Path(trial_class_id): Path<uuid::Uuid>,
-) -> Result<Json<TrialClassItem>, StatusCode> {
+) -> Result<Json<TrialClassItem>, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::TrialClass(trial_class_id)).await?;
+
     let row = sqlx::query!(
         r#"
         SELECT 
@@ -336,7 +321,7 @@ pub async fn get_trial_class_handler(
     )
     .fetch_one(&state.db_pool)
     .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
+    .map_err(|_| AppError::NotFound("resource.not_found"))?;
 
     Ok(Json(TrialClassItem {
         id: row.id,
@@ -366,14 +351,19 @@ pub async fn get_trial_class_handler(
 // PUT /api/v1/base/trial-classes/:id - 更新试听课
 pub async fn update_trial_class_handler(
     State(state): State<AppState>,
-    _claims: Claims,
+    scope: TenantScope,
     Path(trial_class_id): Path<uuid::Uuid>,
     Json(payload): Json<UpdateTrialClassPayload>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AppError> {
+    scope.ensure_owned(&state.db_pool, Owned::TrialClass(trial_class_id)).await?;
+    if let Some(teacher_id) = payload.teacher_id {
+        scope.ensure_owned(&state.db_pool, Owned::User(teacher_id)).await?;
+    }
+
     let scheduled_at = if let Some(ref dt_str) = payload.scheduled_at {
         Some(
             chrono::DateTime::parse_from_rfc3339(dt_str)
-                .map_err(|_| StatusCode::BAD_REQUEST)?
+                .map_err(|_| AppError::BadRequest("request.invalid"))?
                 .with_timezone(&chrono::Utc),
         )
     } else {
@@ -403,8 +393,7 @@ pub async fn update_trial_class_handler(
         trial_class_id
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     Ok(StatusCode::OK)
 }
@@ -415,7 +404,9 @@ pub async fn add_trial_class_feedback_handler(
     claims: Claims,
     Path(trial_class_id): Path<uuid::Uuid>,
     Json(payload): Json<TrialClassFeedback>,
-) -> Result<StatusCode, StatusCode> {
+) -> Result<StatusCode, AppError> {
+    TenantScope::from_claims(&claims).ensure_owned(&state.db_pool, Owned::TrialClass(trial_class_id)).await?;
+
     // 1. Update trial class feedback and status
     sqlx::query!(
         r#"
@@ -435,8 +426,7 @@ pub async fn add_trial_class_feedback_handler(
         trial_class_id
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .await?;
 
     // 2. If conversion intent is high or medium, try to convert lead to customer
     if payload.conversion_intent == "high" || payload.conversion_intent == "medium" {
@@ -450,8 +440,7 @@ pub async fn add_trial_class_feedback_handler(
             trial_class_id
         )
         .fetch_optional(&state.db_pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
 
         if let Some(tc) = trial_class {
             // Check if customer already exists by phone
@@ -461,8 +450,7 @@ pub async fn add_trial_class_feedback_handler(
             .bind(&tc.parent_phone)
             .bind(tc.base_id)
             .fetch_optional(&state.db_pool)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .await?;
 
             let customer_id = if let Some(id) = existing_customer_id {
                 id
@@ -482,8 +470,7 @@ pub async fn add_trial_class_feedback_handler(
                     tc.parent_wechat
                 )
                 .execute(&state.db_pool)
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                .await?;
                 
                 new_customer_id
             };
@@ -503,8 +490,7 @@ pub async fn add_trial_class_feedback_handler(
                     lead_id
                 )
                 .execute(&state.db_pool)
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                .await?;
             }
         }
     }
@@ -7,6 +7,7 @@ use axum::{
 use std::path::Path;
 use tokio::fs;
 use uuid::Uuid;
+use crate::error::AppError;
 
 #[derive(serde::Serialize)]
 pub struct UploadResponse {
@@ -16,7 +17,7 @@ pub struct UploadResponse {
 // POST /api/v1/upload
 pub async fn upload_file_handler(
     mut multipart: Multipart,
-) -> Result<Json<UploadResponse>, StatusCode> {
+) -> Result<Json<UploadResponse>, AppError> {
     
     // 使用 loop 循环替代 while let，配合显式 match，解决所有类型推断问题
     loop {
@@ -29,7 +30,7 @@ pub async fn upload_file_handler(
             Ok(None) => break,     // 结束循环
             Err(e) => {
                 tracing::error!("Multipart error: {}", e);
-                return Err(StatusCode::BAD_REQUEST);
+                return Err(AppError::BadRequest("request.invalid"));
             }
         };
 
@@ -43,8 +44,8 @@ pub async fn upload_file_handler(
             let data: Bytes = match bytes_result {
                 Ok(d) => d,
                 Err(e) => {
-                    tracing::error!("Read bytes error: {}", e);
-                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                    tracing::warn!("Read bytes error: {}", e);
+                    return Err(AppError::BadRequest("upload.invalid_file"));
                 }
             };
 
@@ -57,7 +58,7 @@ pub async fn upload_file_handler(
             if !Path::new(upload_dir).exists() {
                 if let Err(e) = fs::create_dir_all(upload_dir).await {
                     tracing::error!("Create dir error: {}", e);
-                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
                 }
             }
 
@@ -65,7 +66,7 @@ pub async fn upload_file_handler(
             let file_path = Path::new(upload_dir).join(&new_filename);
             if let Err(e) = fs::write(&file_path, &data).await {
                 tracing::error!("Write file error: {}", e);
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
             }
 
             // 6. 返回 URL (生产环境请改为配置项)
@@ -75,5 +76,5 @@ pub async fn upload_file_handler(
     }
 
     // 如果没传 file 字段
-    Err(StatusCode::BAD_REQUEST)
+    Err(AppError::BadRequest("request.invalid"))
 }
\ No newline at end of file
@@ -4,103 +4,82 @@
  * 1. get_hq_users: SQL 查询增加 u.staff_status::text
  * 2. create_hq_user: 返回的 UserDetail JSON 中增加 staff_status
  */
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{extract::State, Json};
 use uuid::Uuid;
-use bcrypt::{hash, DEFAULT_COST};
 use crate::models::{Claims, UserDetail, CreateUserPayload, UpdateStatusPayload, UpdateUserPayload};
-use super::{AppState};
-
-use sqlx::{QueryBuilder};
-use rand::{seq::SliceRandom};
-
-fn generate_strong_password() -> String {
-    let mut rng = rand::thread_rng();
-    let upper: Vec<char> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().collect();
-    let lower: Vec<char> = "abcdefghijklmnopqrstuvwxyz".chars().collect();
-    let numbers: Vec<char> = "0123456789".chars().collect();
-    let special: Vec<char> = "!@#$%^&*".chars().collect();
-    let mut password: Vec<char> = Vec::new();
-    password.push(*upper.choose(&mut rng).unwrap());
-    password.push(*lower.choose(&mut rng).unwrap());
-    password.push(*numbers.choose(&mut rng).unwrap());
-    password.push(*special.choose(&mut rng).unwrap());
-    let all_chars = [upper, lower, numbers, special].concat();
-    for _ in 0..4 { password.push(*all_chars.choose(&mut rng).unwrap()); }
-    password.shuffle(&mut rng);
-    password.into_iter().collect()
-}
+use super::{
+    check_password_policy, generate_strong_password, hash_password, revoke_subject_sessions, AppState,
+    REVOKE_DEACTIVATED, SUBJECT_USER,
+};
+use crate::tenant::{Owned, TenantScope};
+
+use crate::error::AppError;
+use crate::pagination::{Filter, ListQuery, ListSpec, Page};
+
+// 基地账号只能创建 / 调整为这些角色
+const BASE_ASSIGNABLE_ROLES: [&str; 4] = ["role.base.academic", "role.base.finance", "role.teacher", "role.base.hr"];
 
 // (GET) 获取员工列表
+// 分页; 筛选: base_id / staff_status / is_active; q 搜姓名、邮箱和手机号
+const USER_LIST: ListSpec = ListSpec {
+    sorts: &[("created_at", "u.created_at"), ("full_name", "u.full_name"), ("email", "u.email")],
+    default_sort: "-created_at",
+    tiebreak: "u.id",
+    filters: &[
+        Filter::uuid("base_id", "u.base_id"),
+        Filter::text("staff_status", "u.staff_status"),
+        Filter::bool("is_active", "u.is_active"),
+    ],
+    search: &["u.full_name", "u.email", "u.phone_number"],
+};
+
 pub async fn get_hq_users(
     State(state): State<AppState>,
-    claims: Claims,
-) -> Result<Json<Vec<UserDetail>>, StatusCode> {
+    scope: TenantScope,
+    list: ListQuery,
+) -> Result<Json<Page<UserDetail>>, AppError> {
     
-    let hq_id = claims.hq_id;
-    let is_hq = claims.roles.iter().any(|r| r == "role.hq.admin");
-    let is_base = claims.roles.iter().any(|r| r == "role.base.admin");
-
-    if !is_hq && !is_base {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
     // ★★★ [修复 1] SQL 查询增加 u.staff_status::text ★★★
     // 同时也补上了 skills 和 is_teaching_now 的查询逻辑
-    let mut query_builder: QueryBuilder<sqlx::Postgres> = QueryBuilder::new(
-        r#"
-        SELECT 
-            u.id, u.email, u.full_name, u.is_active, u.created_at,
-            u.phone_number, u.gender, u.blood_type, u.date_of_birth, u.address,
-            
-            u.staff_status::text, -- <--- 关键修复: 查出状态
-
-            u.base_id,
-            b.name as base_name,
-            (SELECT r.name_key FROM roles r 
-             JOIN user_roles ur ON r.id = ur.role_id 
-             WHERE ur.user_id = u.id LIMIT 1) as role_name,
-             
-            (SELECT STRING_AGG(c.name_key, ', ')
-             FROM teacher_qualified_courses tqc
-             JOIN courses c ON tqc.course_id = c.id
-             WHERE tqc.teacher_id = u.id) as skills,
-             
-            EXISTS (
-                SELECT 1 FROM classes cl
-                JOIN class_teachers ct ON cl.id = ct.class_id
-                WHERE ct.teacher_id = u.id
-                AND cl.status = 'scheduled'
-                AND CURRENT_TIMESTAMP BETWEEN cl.start_time AND cl.end_time
-            ) as is_teaching_now
-
-        FROM users u
-        LEFT JOIN bases b ON u.base_id = b.id
-        WHERE u.hq_id = 
-        "#
-    );
-
-    query_builder.push_bind(hq_id);
-
-    if !is_hq && is_base {
-        if let Some(base_id) = claims.base_id {
-            query_builder.push(" AND u.base_id = ");
-            query_builder.push_bind(base_id);
-        } else {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    }
-
-    query_builder.push(" ORDER BY u.created_at DESC ");
-
-    let users = query_builder.build_query_as::<UserDetail>()
-        .fetch_all(&state.db_pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to fetch users: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    Ok(Json(users))
+    let page = list
+        .fetch_page(&state.db_pool, &USER_LIST, |qb| {
+            qb.push(
+                r#"
+                SELECT
+                    u.id, u.email, u.full_name, u.is_active, u.created_at,
+                    u.phone_number, u.gender, u.blood_type, u.date_of_birth, u.address,
+
+                    u.staff_status::text, -- <--- 关键修复: 查出状态
+
+                    u.base_id,
+                    b.name as base_name,
+                    (SELECT r.name_key FROM roles r
+                     JOIN user_roles ur ON r.id = ur.role_id
+                     WHERE ur.user_id = u.id LIMIT 1) as role_name,
+
+                    (SELECT STRING_AGG(c.name_key, ', ')
+                     FROM teacher_qualified_courses tqc
+                     JOIN courses c ON tqc.course_id = c.id
+                     WHERE tqc.teacher_id = u.id) as skills,
+
+                    EXISTS (
+                        SELECT 1 FROM classes cl
+                        JOIN class_teachers ct ON cl.id = ct.class_id
+                        WHERE ct.teacher_id = u.id
+                        AND cl.status = 'scheduled'
+                        AND CURRENT_TIMESTAMP BETWEEN cl.start_time AND cl.end_time
+                    ) as is_teaching_now
+
+                FROM users u
+                LEFT JOIN bases b ON u.base_id = b.id
+                WHERE 1 = 1
+                "#,
+            );
+            scope.push_filter(qb, "u");
+        })
+        .await?;
+
+    Ok(Json(page))
 }
 
 // (POST) 创建新员工
@@ -108,30 +87,32 @@ pub async fn create_hq_user(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateUserPayload>,
-) -> Result<Json<UserDetail>, StatusCode> {
+) -> Result<Json<UserDetail>, AppError> {
     
-    let is_hq = claims.roles.iter().any(|r| r == "role.hq.admin");
-    let is_base = claims.roles.iter().any(|r| r == "role.base.admin");
-
-    if !is_hq && !is_base {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    let is_hq = claims.base_id.is_none();
 
     let (final_base_id, final_role_key) = if is_hq {
         (payload.base_id, payload.role_key.clone())
     } else {
-        let my_base_id = claims.base_id.ok_or(StatusCode::FORBIDDEN)?;
-        let allowed_roles = vec!["role.base.academic", "role.base.finance", "role.teacher", "role.base.hr"];
-        if !allowed_roles.contains(&payload.role_key.as_str()) {
-            return Err(StatusCode::FORBIDDEN);
+        let my_base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+        if !BASE_ASSIGNABLE_ROLES.contains(&payload.role_key.as_str()) {
+            return Err(AppError::Forbidden("auth.forbidden"));
         }
         (Some(my_base_id), payload.role_key.clone())
     };
 
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut tx = state.db_pool.begin().await?;
+    // 请求里显式指定的基地同样要校验 (基地账号只能填本基地)
+    if let Some(base_id) = payload.base_id.or(final_base_id) {
+        TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::Base(base_id)).await?;
+    }
 
+    // 管理员知道初始密码, 员工首次登录时必须改密
+    if let Some(password) = &payload.password {
+        check_password_policy(password, &payload.email)?;
+    }
     let plain_password = payload.password.clone().unwrap_or_else(generate_strong_password);
-    let password_hash = hash(&plain_password, DEFAULT_COST).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let password_hash = hash_password(plain_password.clone()).await?;
 
     // 这里 INSERT 默认用数据库的 staff_status='active'，所以不用改 SQL
     let user_id: Uuid = sqlx::query_scalar(
@@ -139,9 +120,9 @@ pub async fn create_hq_user(
         INSERT INTO users (
             hq_id, base_id, email, password_hash, full_name, is_active,
             phone_number, gender, blood_type, date_of_birth, address,
-            password_changed_at
+            password_changed_at, must_change_password
         )
-        VALUES ($1, $2, $3, $4, $5, true, $6, $7, $8, $9, $10, CURRENT_TIMESTAMP)
+        VALUES ($1, $2, $3, $4, $5, true, $6, $7, $8, $9, $10, CURRENT_TIMESTAMP, true)
         RETURNING id
         "#
     )
@@ -157,10 +138,7 @@ pub async fn create_hq_user(
     .bind(&payload.address)
     .fetch_one(&mut *tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to create user: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to create user"))?;
 
     let role_id: Option<Uuid> = sqlx::query_scalar(
         "SELECT id FROM roles WHERE name_key = $1 AND hq_id = $2"
@@ -176,11 +154,10 @@ pub async fn create_hq_user(
             .bind(user_id)
             .bind(rid)
             .execute(&mut *tx)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .await?;
     } else {
         tx.rollback().await.ok();
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(AppError::BadRequest("request.invalid"));
     }
 
     if final_role_key == "role.teacher" || final_role_key == "role.base.academic" || final_role_key == "role.base.admin" {
@@ -193,14 +170,11 @@ pub async fn create_hq_user(
             .bind(bid)
             .execute(&mut *tx)
             .await
-            .map_err(|e| {
-                tracing::error!("Failed to create teacher profile: {}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+            .map_err(AppError::db("Failed to create teacher profile"))?;
         }
     }
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
 
     // ★★★ [修复 2] 这里构造 JSON 时补上 staff_status ★★★
     Ok(Json(UserDetail {
@@ -226,20 +200,16 @@ pub async fn create_hq_user(
     }))
 }
 
-// ... Update 相关的函数已经是对的，保持原样即可 ...
+// (PATCH /api/v1/hq/users/:id/status) 封禁 / 解封账号, 封禁时吊销该员工的全部登录会话
 pub async fn update_user_status_handler(
     State(state): State<AppState>,
     claims: Claims,
     axum::extract::Path(user_id): axum::extract::Path<Uuid>,
     Json(payload): Json<UpdateStatusPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    
-    let is_hq_admin = claims.roles.contains(&"role.hq.admin".to_string());
-    let is_base_admin = claims.roles.contains(&"role.base.admin".to_string());
-
-    if !is_hq_admin && !is_base_admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
+) -> Result<Json<serde_json::Value>, AppError> {
+    let mut tx = state.db_pool.begin().await?;
+    // 基地账号只能操作本基地员工
+    TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::User(user_id)).await?;
 
     let result = sqlx::query(
         "UPDATE users SET is_active = $1 WHERE id = $2 AND hq_id = $3"
@@ -247,17 +217,20 @@ pub async fn update_user_status_handler(
     .bind(payload.is_active)
     .bind(user_id)
     .bind(claims.hq_id)
-    .execute(&state.db_pool)
+    .execute(&mut *tx)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to update user status: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    .map_err(AppError::db("Failed to update user status"))?;
 
     if result.rows_affected() == 0 {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(AppError::NotFound("resource.not_found"));
     }
 
+    if !payload.is_active {
+        revoke_subject_sessions(&mut tx, SUBJECT_USER, user_id, REVOKE_DEACTIVATED).await.map_err(AppError::db("Failed to revoke sessions"))?;
+    }
+
+    tx.commit().await?;
+
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
@@ -266,9 +239,11 @@ pub async fn update_user_handler(
     claims: Claims,
     axum::extract::Path(user_id): axum::extract::Path<Uuid>,
     Json(payload): Json<UpdateUserPayload>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+) -> Result<Json<serde_json::Value>, AppError> {
     
-    let mut tx = state.db_pool.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut tx = state.db_pool.begin().await?;
+    // 基地账号只能修改本基地员工
+    TenantScope::from_claims(&claims).ensure_owned(&mut *tx, Owned::User(user_id)).await?;
 
     let new_is_active = if let Some(status) = &payload.staff_status {
         match status.as_str() {
@@ -300,38 +275,41 @@ pub async fn update_user_handler(
         .bind(claims.hq_id)
         .execute(&mut *tx)
         .await
-        .map_err(|e| {
-            tracing::error!("Update user failed: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        .map_err(AppError::db("Update user failed"))?;
+    }
+
+    // 离职: 已签发的 Token 立即失效
+    if new_is_active == Some(false) {
+        revoke_subject_sessions(&mut tx, SUBJECT_USER, user_id, REVOKE_DEACTIVATED).await.map_err(AppError::db("Failed to revoke sessions"))?;
     }
 
     if let Some(new_role_key) = &payload.role_key {
-        let role_id = sqlx::query_scalar::<_, Uuid>("SELECT id FROM roles WHERE name_key = $1")
+        if claims.base_id.is_some() && !BASE_ASSIGNABLE_ROLES.contains(&new_role_key.as_str()) {
+            return Err(AppError::Forbidden("auth.forbidden"));
+        }
+        let role_id = sqlx::query_scalar::<_, Uuid>("SELECT id FROM roles WHERE name_key = $1 AND hq_id = $2")
             .bind(new_role_key)
+            .bind(claims.hq_id)
             .fetch_optional(&mut *tx)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .ok_or(StatusCode::BAD_REQUEST)?;
+            .await?
+            .ok_or(AppError::BadRequest("request.invalid"))?;
 
         let result = sqlx::query("UPDATE user_roles SET role_id = $1 WHERE user_id = $2")
             .bind(role_id)
             .bind(user_id)
             .execute(&mut *tx)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            .await?;
             
         if result.rows_affected() == 0 {
              let _ = sqlx::query("INSERT INTO user_roles (user_id, role_id) VALUES ($1, $2)")
                 .bind(user_id)
                 .bind(role_id)
                 .execute(&mut *tx)
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                .await?;
         }
     }
 
-    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tx.commit().await?;
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
\ No newline at end of file
@@ -0,0 +1,264 @@
+/*
+ * src/handlers/waitlist.rs
+ * 职责: 报名容量控制 & 候补队列 (Waitlist)
+ * 报名人数达到 classes.max_capacity 后进入候补; 有人退课 / 请假时按报名先后自动转正并通知家长
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use super::{find_membership_problem, find_participant_conflicts, notify_customer, AppState};
+use crate::models::{Claims, ClassEnrollment};
+use crate::timezone::business_tz;
+use crate::error::AppError;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WaitlistEntry {
+    pub id: Uuid,
+    pub hq_id: Uuid,
+    pub class_id: Uuid,
+    pub participant_id: Uuid,
+    pub customer_id: Uuid,
+    pub customer_membership_id: Option<Uuid>,
+    pub status: String,
+    pub enrollment_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub promoted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WaitlistDetail {
+    pub id: Uuid,
+    pub participant_id: Uuid,
+    pub participant_name: String,
+    pub customer_membership_id: Option<Uuid>,
+    pub position: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+pub enum EnrollOutcome {
+    Enrolled(ClassEnrollment),
+    Waitlisted(WaitlistEntry),
+    Rejected(&'static str), // 会员卡不可用
+}
+
+// 占用名额的报名 (请假 / 取消的不占)
+const ACTIVE_ENROLLMENT_FILTER: &str = "COALESCE(status, 'enrolled') NOT IN ('leave', 'cancelled')";
+
+// 锁定课程并返回剩余名额
+async fn lock_free_seats(conn: &mut PgConnection, class_id: Uuid) -> Result<i64, sqlx::Error> {
+    let max_capacity: i32 = sqlx::query_scalar("SELECT max_capacity FROM classes WHERE id = $1 FOR UPDATE")
+        .bind(class_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let active: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM class_enrollments WHERE class_id = $1 AND {}",
+        ACTIVE_ENROLLMENT_FILTER
+    ))
+    .bind(class_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(max_capacity as i64 - active)
+}
+
+// 报名: 先校验会员卡, 有名额直接报名, 满员则进入候补 (调用方需先排除重复报名)
+pub async fn enroll_or_waitlist(
+    conn: &mut PgConnection,
+    hq_id: Uuid,
+    class_id: Uuid,
+    participant_id: Uuid,
+    customer_id: Uuid,
+    customer_membership_id: Option<Uuid>,
+) -> Result<EnrollOutcome, sqlx::Error> {
+    if let Some(membership_id) = customer_membership_id {
+        if let Some(problem) = find_membership_problem(&mut *conn, membership_id, participant_id, class_id, None).await? {
+            return Ok(EnrollOutcome::Rejected(problem));
+        }
+    }
+
+    if lock_free_seats(&mut *conn, class_id).await? > 0 {
+        let enrollment = sqlx::query_as::<_, ClassEnrollment>(
+            "INSERT INTO class_enrollments (hq_id, class_id, participant_id, customer_id, customer_membership_id, status) VALUES ($1, $2, $3, $4, $5, 'enrolled') RETURNING *"
+        )
+        .bind(hq_id)
+        .bind(class_id)
+        .bind(participant_id)
+        .bind(customer_id)
+        .bind(customer_membership_id)
+        .fetch_one(&mut *conn)
+        .await?;
+        return Ok(EnrollOutcome::Enrolled(enrollment));
+    }
+
+    let entry = sqlx::query_as::<_, WaitlistEntry>(
+        r#"
+        INSERT INTO class_waitlist (hq_id, class_id, participant_id, customer_id, customer_membership_id)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (class_id, participant_id) WHERE status = 'waiting'
+        DO UPDATE SET customer_membership_id = EXCLUDED.customer_membership_id
+        RETURNING *
+        "#,
+    )
+    .bind(hq_id)
+    .bind(class_id)
+    .bind(participant_id)
+    .bind(customer_id)
+    .bind(customer_membership_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(EnrollOutcome::Waitlisted(entry))
+}
+
+// 名额释放后调用: 按候补先后转正, 返回新报名 id
+// 候补期间学员已报了同时段的其他课程时, 保留其候补并顺延给下一位
+pub async fn promote_waitlist(conn: &mut PgConnection, class_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    let free = lock_free_seats(&mut *conn, class_id).await?;
+    if free <= 0 {
+        return Ok(vec![]);
+    }
+
+    let waiting = sqlx::query_as::<_, WaitlistEntry>(
+        "SELECT * FROM class_waitlist WHERE class_id = $1 AND status = 'waiting' ORDER BY created_at ASC FOR UPDATE",
+    )
+    .bind(class_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut promoted = Vec::new();
+    for entry in waiting {
+        if promoted.len() as i64 >= free {
+            break;
+        }
+        // 候补期间会员卡可能已过期 / 用完: 取消该候补并通知家长
+        if let Some(membership_id) = entry.customer_membership_id {
+            if find_membership_problem(&mut *conn, membership_id, entry.participant_id, entry.class_id, None).await?.is_some() {
+                sqlx::query("UPDATE class_waitlist SET status = 'cancelled' WHERE id = $1")
+                    .bind(entry.id)
+                    .execute(&mut *conn)
+                    .await?;
+                notify_customer(
+                    &mut *conn,
+                    entry.hq_id,
+                    entry.customer_id,
+                    "waitlist_cancelled",
+                    "候补已取消",
+                    "课程有空位, 但所选会员卡已不可用 (过期或次数不足), 候补已自动取消, 请联系基地。",
+                    Some(entry.class_id),
+                )
+                .await?;
+                continue;
+            }
+        }
+
+        if !find_participant_conflicts(&mut *conn, entry.participant_id, entry.class_id).await?.is_empty() {
+            continue;
+        }
+
+        // 之前请过假的学员会有旧报名记录, 直接恢复
+        let enrollment_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO class_enrollments (hq_id, class_id, participant_id, customer_id, customer_membership_id, status)
+            VALUES ($1, $2, $3, $4, $5, 'enrolled')
+            ON CONFLICT (class_id, participant_id)
+            DO UPDATE SET status = 'enrolled', customer_membership_id = EXCLUDED.customer_membership_id
+            RETURNING id
+            "#,
+        )
+        .bind(entry.hq_id)
+        .bind(entry.class_id)
+        .bind(entry.participant_id)
+        .bind(entry.customer_id)
+        .bind(entry.customer_membership_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        sqlx::query("UPDATE class_waitlist SET status = 'promoted', enrollment_id = $1, promoted_at = NOW() WHERE id = $2")
+            .bind(enrollment_id)
+            .bind(entry.id)
+            .execute(&mut *conn)
+            .await?;
+
+        let (participant_name, start_time): (String, DateTime<Utc>) = sqlx::query_as(
+            "SELECT p.name, c.start_time FROM participants p, classes c WHERE p.id = $1 AND c.id = $2",
+        )
+        .bind(entry.participant_id)
+        .bind(entry.class_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        notify_customer(
+            &mut *conn,
+            entry.hq_id,
+            entry.customer_id,
+            "waitlist_promoted",
+            "候补成功",
+            &format!(
+                "{} 已从候补转为正式报名, 上课时间: {}。",
+                participant_name,
+                start_time.with_timezone(&business_tz()).format("%Y-%m-%d %H:%M")
+            ),
+            Some(entry.class_id),
+        )
+        .await?;
+
+        promoted.push(enrollment_id);
+    }
+
+    Ok(promoted)
+}
+
+// (GET /api/v1/classes/:id/waitlist) 候补名单
+pub async fn get_class_waitlist_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(class_id): Path<Uuid>,
+) -> Result<Json<Vec<WaitlistDetail>>, AppError> {
+    let list = sqlx::query_as::<_, WaitlistDetail>(
+        r#"
+        SELECT w.id, w.participant_id, p.name AS participant_name, w.customer_membership_id,
+               ROW_NUMBER() OVER (ORDER BY w.created_at ASC) AS position,
+               w.created_at
+        FROM class_waitlist w
+        JOIN participants p ON w.participant_id = p.id
+        WHERE w.class_id = $1 AND w.hq_id = $2 AND w.status = 'waiting'
+        ORDER BY w.created_at ASC
+        "#,
+    )
+    .bind(class_id)
+    .bind(claims.hq_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to fetch waitlist"))?;
+
+    Ok(Json(list))
+}
+
+// (DELETE /api/v1/waitlist/:id) 取消候补
+pub async fn cancel_waitlist_entry_handler(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(entry_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let result = sqlx::query(
+        "UPDATE class_waitlist SET status = 'cancelled' WHERE id = $1 AND hq_id = $2 AND status = 'waiting'",
+    )
+    .bind(entry_id)
+    .bind(claims.hq_id)
+    .execute(&state.db_pool)
+    .await
+    .map_err(AppError::db("Failed to cancel waitlist entry"))?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("resource.not_found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,12 +1,15 @@
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use crate::{AppState, models::Claims};
+use super::{apply_order_refund, apply_teacher_leave, issue_refund_red_invoices};
+use super::ledger::{expense_account, post_transfer, LedgerTransfer, ACCOUNT_CASH, SOURCE_EXPENSE};
+use crate::error::AppError;
+use crate::tenant::{Owned, TenantScope};
 
 // --- Models ---
 
@@ -64,33 +67,33 @@ pub struct ApprovalActionPayload {
 pub async fn get_workspace_overview_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<WorkspaceOverview>, (StatusCode, String)> {
-    let base_id = claims.base_id.ok_or((StatusCode::FORBIDDEN, "Base ID required".to_string()))?;
+) -> Result<Json<WorkspaceOverview>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     // 1. Approvals
     let discount_count: i64 = sqlx::query_scalar!(
         "SELECT COUNT(*) FROM orders WHERE base_id = $1 AND approval_status = 'pending' AND discount_amount_cents > 0",
         base_id
     )
-    .fetch_one(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?.unwrap_or(0);
+    .fetch_one(&state.db_pool).await?.unwrap_or(0);
 
     let refund_count: i64 = sqlx::query_scalar!(
         "SELECT COUNT(*) FROM refund_requests WHERE base_id = $1 AND status = 'pending'",
         base_id
     )
-    .fetch_one(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?.unwrap_or(0);
+    .fetch_one(&state.db_pool).await?.unwrap_or(0);
 
     let expense_count: i64 = sqlx::query_scalar!(
         "SELECT COUNT(*) FROM expenses WHERE base_id = $1 AND status = 'pending'",
         base_id
     )
-    .fetch_one(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?.unwrap_or(0);
+    .fetch_one(&state.db_pool).await?.unwrap_or(0);
 
     let leave_count: i64 = sqlx::query_scalar!(
         "SELECT COUNT(*) FROM leave_requests WHERE base_id = $1 AND status = 'pending'",
         base_id
     )
-    .fetch_one(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?.unwrap_or(0);
+    .fetch_one(&state.db_pool).await?.unwrap_or(0);
 
     // 2. Risks
     let overdue_count: i64 = sqlx::query_scalar!(
@@ -108,6 +111,20 @@ pub async fn get_workspace_overview_handler(
         });
     }
 
+    let renewal_count: i64 = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM membership_reminders WHERE base_id = $1 AND status = 'open'",
+        base_id
+    )
+    .fetch_one(&state.db_pool).await.unwrap_or(Some(0)).unwrap_or(0);
+
+    if renewal_count > 0 {
+        risks.push(RiskItem {
+            id: "MEMBERSHIP_RENEWAL".to_string(),
+            text: "会员续费跟进".to_string(),
+            desc: format!("有{}张会员卡即将到期或次数不足, 待跟进续费", renewal_count),
+        });
+    }
+
     Ok(Json(WorkspaceOverview {
         approvals: ApprovalCounts { discount: discount_count, refund: refund_count, expense: expense_count, leave: leave_count },
         risks,
@@ -118,8 +135,8 @@ pub async fn get_approval_list_handler(
     State(state): State<AppState>,
     claims: Claims,
     Query(query): Query<ApprovalListQuery>,
-) -> Result<Json<Vec<ApprovalItem>>, (StatusCode, String)> {
-    let base_id = claims.base_id.ok_or((StatusCode::FORBIDDEN, "Base ID required".to_string()))?;
+) -> Result<Json<Vec<ApprovalItem>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
     let mut items = Vec::new();
 
     match query.type_.as_str() {
@@ -135,8 +152,7 @@ pub async fn get_approval_list_handler(
                 base_id
             )
             .fetch_all(&state.db_pool)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .await?;
 
             for r in rows {
                 items.push(ApprovalItem {
@@ -164,8 +180,7 @@ pub async fn get_approval_list_handler(
                 base_id
             )
             .fetch_all(&state.db_pool)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .await?;
 
             for r in rows {
                 items.push(ApprovalItem {
@@ -193,8 +208,7 @@ pub async fn get_approval_list_handler(
                 base_id
             )
             .fetch_all(&state.db_pool)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .await?;
 
             for r in rows {
                 items.push(ApprovalItem {
@@ -222,8 +236,7 @@ pub async fn get_approval_list_handler(
                 base_id
             )
             .fetch_all(&state.db_pool)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .await?;
 
             for r in rows {
                 let leave_type = match r.type_.as_str() {
@@ -245,7 +258,7 @@ pub async fn get_approval_list_handler(
                 });
             }
         },
-        _ => return Err((StatusCode::BAD_REQUEST, "Invalid approval type".to_string())),
+        _ => return Err(AppError::BadRequest("approval.invalid_type")),
     }
 
     Ok(Json(items))
@@ -255,46 +268,105 @@ pub async fn handle_approval_action_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<ApprovalActionPayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, AppError> {
     let user_id = Uuid::parse_str(&claims.sub)
-        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid User ID in token".to_string()))?;
+        .map_err(|_| AppError::Unauthorized("auth.unauthorized"))?;
 
     let new_status = match payload.action.as_str() {
         "approve" => "approved",
         "reject" => "rejected",
-        _ => return Err((StatusCode::BAD_REQUEST, "Invalid action".to_string())),
+        _ => return Err(AppError::BadRequest("approval.invalid_action")),
     };
 
+    let scope = TenantScope::from_claims(&claims);
     match payload.type_.as_str() {
         "discount" => {
+            scope.ensure_owned(&state.db_pool, Owned::Order(payload.id)).await?;
             sqlx::query!(
                 "UPDATE orders SET approval_status = $1, approved_by = $2, approved_at = NOW() WHERE id = $3",
                 new_status, user_id, payload.id
             )
-            .execute(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .execute(&state.db_pool).await?;
         },
         "refund" => {
+            let mut tx = state.db_pool.begin().await?;
             sqlx::query!(
-                "UPDATE refund_requests SET status = $1, approved_by = $2, approved_at = NOW(), rejection_reason = $3 WHERE id = $4",
-                new_status, user_id, payload.reason, payload.id
+                r#"
+                UPDATE refund_requests SET status = $1, approved_by = $2, approved_at = NOW(), rejection_reason = $3, updated_at = NOW()
+                WHERE id = $4 AND hq_id = $5 AND status = 'pending'
+                RETURNING id
+                "#,
+                new_status, user_id, payload.reason, payload.id, claims.hq_id
             )
-            .execute(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .fetch_optional(&mut *tx).await?
+            .ok_or(AppError::Conflict("resource.conflict"))?;
+
+            // 审批通过: 退款流水 / 冲减订单 / 冲销凭证 / 停卡 / 回库存
+            if new_status == "approved" {
+                apply_order_refund(&mut tx, payload.id, user_id).await?;
+            }
+            tx.commit().await?;
+            // 红字发票在提交后开具, 开票失败不影响审批
+            if new_status == "approved" {
+                issue_refund_red_invoices(&state, payload.id).await;
+            }
         },
         "expense" => {
-            sqlx::query!(
-                "UPDATE expenses SET status = $1, approved_by = $2, approved_at = NOW(), rejection_reason = $3 WHERE id = $4",
-                new_status, user_id, payload.reason, payload.id
+            let mut tx = state.db_pool.begin().await?;
+            let expense = sqlx::query!(
+                r#"
+                UPDATE expenses SET status = $1, approved_by = $2, approved_at = NOW(), rejection_reason = $3
+                WHERE id = $4 AND hq_id = $5 AND status = 'pending'
+                RETURNING hq_id, base_id, category, amount_cents, expense_date, description
+                "#,
+                new_status, user_id, payload.reason, payload.id, claims.hq_id
             )
-            .execute(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .fetch_optional(&mut *tx).await?
+            .ok_or(AppError::Conflict("resource.conflict"))?;
+
+            // 审批通过记账: 借 销售费用/管理费用, 贷 库存现金
+            if new_status == "approved" {
+                post_transfer(&mut tx, LedgerTransfer {
+                    hq_id: expense.hq_id,
+                    base_id: Some(expense.base_id),
+                    entry_date: expense.expense_date,
+                    source_type: SOURCE_EXPENSE,
+                    source_id: Some(payload.id),
+                    description: format!("费用报销: {}", expense.description.as_deref().unwrap_or(&expense.category)),
+                    created_by: Some(user_id),
+                    debit: expense_account(&expense.category),
+                    credit: ACCOUNT_CASH,
+                    amount_cents: expense.amount_cents as i64,
+                })
+                .await?;
+            }
+            tx.commit().await?;
         },
         "leave" => {
+            let mut tx = state.db_pool.begin().await?;
+            // 只能审批本租户待审批的请假; 重复审批会重复安排代课和通知家长
+            scope.ensure_owned(&mut *tx, Owned::LeaveRequest(payload.id)).await?;
             sqlx::query!(
-                "UPDATE leave_requests SET status = $1, approved_by = $2, approved_at = NOW(), rejection_reason = $3 WHERE id = $4",
-                new_status, user_id, payload.reason, payload.id
+                r#"
+                UPDATE leave_requests SET status = $1, approved_by = $2, approved_at = NOW(), rejection_reason = $3, updated_at = NOW()
+                WHERE id = $4 AND hq_id = $5 AND status = 'pending'
+                RETURNING id
+                "#,
+                new_status, user_id, payload.reason, payload.id, claims.hq_id
             )
-            .execute(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .fetch_optional(&mut *tx).await?
+            .ok_or(AppError::Conflict("resource.conflict"))?;
+
+            // 审批通过: 自动为受影响课程安排代课 / 标记待代课, 并通知家长
+            if new_status == "approved" {
+                let substitutions = apply_teacher_leave(&mut tx, payload.id)
+                    .await?;
+                tx.commit().await?;
+                return Ok(Json(serde_json::json!({ "success": true, "substitutions": substitutions })));
+            }
+            tx.commit().await?;
         },
-        _ => return Err((StatusCode::BAD_REQUEST, "Invalid approval type".to_string())),
+        _ => return Err(AppError::BadRequest("approval.invalid_type")),
     }
 
     Ok(Json(serde_json::json!({ "success": true })))
@@ -320,28 +392,28 @@ pub struct TransactionItem {
 pub async fn get_finance_summary_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<FinanceSummary>, (StatusCode, String)> {
-    let base_id = claims.base_id.ok_or((StatusCode::FORBIDDEN, "Base ID required".to_string()))?;
+) -> Result<Json<FinanceSummary>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     // 1. Incomes (Orders)
     let today_income: i64 = sqlx::query_scalar!(
         "SELECT SUM(total_amount_cents) FROM orders WHERE base_id = $1 AND status = 'paid' AND created_at >= CURRENT_DATE",
         base_id
     )
-    .fetch_one(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?.unwrap_or(0);
+    .fetch_one(&state.db_pool).await?.unwrap_or(0);
 
     let month_income: i64 = sqlx::query_scalar!(
         "SELECT SUM(total_amount_cents) FROM orders WHERE base_id = $1 AND status = 'paid' AND created_at >= date_trunc('month', CURRENT_DATE)",
         base_id
     )
-    .fetch_one(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?.unwrap_or(0);
+    .fetch_one(&state.db_pool).await?.unwrap_or(0);
 
     // 2. Expenses
     let month_expense: i64 = sqlx::query_scalar!(
         "SELECT SUM(amount_cents) FROM expenses WHERE base_id = $1 AND status = 'approved' AND created_at >= date_trunc('month', CURRENT_DATE)",
         base_id
     )
-    .fetch_one(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?.unwrap_or(0);
+    .fetch_one(&state.db_pool).await?.unwrap_or(0);
 
     // 3. Recent Transactions (Orders + Expenses mixed)
     // Union query is complex with sqlx macro types, so we query separately and merge in memory for simplicity (or use a view)
@@ -355,7 +427,7 @@ pub async fn get_finance_summary_handler(
         "#,
         base_id
     )
-    .fetch_all(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .fetch_all(&state.db_pool).await?;
 
     let recent_expenses = sqlx::query!(
         r#"
@@ -366,7 +438,7 @@ pub async fn get_finance_summary_handler(
         "#,
         base_id
     )
-    .fetch_all(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .fetch_all(&state.db_pool).await?;
 
     let mut transactions = Vec::new();
     for o in recent_orders {
@@ -415,8 +487,8 @@ pub struct StaffItem {
 pub async fn get_base_staff_list_handler(
     State(state): State<AppState>,
     claims: Claims,
-) -> Result<Json<Vec<StaffItem>>, (StatusCode, String)> {
-    let base_id = claims.base_id.ok_or((StatusCode::FORBIDDEN, "Base ID required".to_string()))?;
+) -> Result<Json<Vec<StaffItem>>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     let rows = sqlx::query!(
         r#"
@@ -442,8 +514,7 @@ pub async fn get_base_staff_list_handler(
         base_id
     )
     .fetch_all(&state.db_pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .await?;
 
     let mut items = Vec::new();
     for r in rows {
@@ -488,8 +559,8 @@ pub async fn get_report_stats_handler(
     State(state): State<AppState>,
     claims: Claims,
     Query(query): Query<ReportQuery>,
-) -> Result<Json<ChartData>, (StatusCode, String)> {
-    let base_id = claims.base_id.ok_or((StatusCode::FORBIDDEN, "Base ID required".to_string()))?;
+) -> Result<Json<ChartData>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
 
     match query.dimension.as_str() {
         "revenue" => {
@@ -509,7 +580,7 @@ pub async fn get_report_stats_handler(
                 "#,
                 base_id
             )
-            .fetch_all(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .fetch_all(&state.db_pool).await?;
 
             let mut labels = Vec::new();
             let mut data = Vec::new();
@@ -545,7 +616,7 @@ pub async fn get_report_stats_handler(
                 "#,
                 base_id
             )
-            .fetch_all(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            .fetch_all(&state.db_pool).await?;
 
             let mut labels = Vec::new();
             let mut data = Vec::new();
@@ -573,7 +644,7 @@ pub async fn get_report_stats_handler(
             let lead_count = sqlx::query_scalar!(
                 "SELECT COUNT(*) FROM customers WHERE base_id = $1 AND created_at >= date_trunc('month', CURRENT_DATE)",
                 base_id
-            ).fetch_one(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?.unwrap_or(0);
+            ).fetch_one(&state.db_pool).await?.unwrap_or(0);
 
             // 2. Trials: All trial classes created (or attended)
             // Checking if trial_classes table exists and has base_id. Assuming yes from previous context.
@@ -586,7 +657,7 @@ pub async fn get_report_stats_handler(
             let deal_count = sqlx::query_scalar!(
                 "SELECT COUNT(*) FROM orders WHERE base_id = $1 AND status = 'paid' AND created_at >= date_trunc('month', CURRENT_DATE)",
                 base_id
-            ).fetch_one(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?.unwrap_or(0);
+            ).fetch_one(&state.db_pool).await?.unwrap_or(0);
 
             Ok(Json(ChartData {
                 labels: vec!["新增线索".to_string(), "试听排课".to_string(), "成交报名".to_string()],
@@ -613,7 +684,7 @@ pub async fn get_report_stats_handler(
                 "#,
                 base_id
              )
-             .fetch_all(&state.db_pool).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+             .fetch_all(&state.db_pool).await?;
 
              let mut labels = Vec::new();
              let mut data = Vec::new();
@@ -632,7 +703,7 @@ pub async fn get_report_stats_handler(
                 summary: None
              }))
         },
-        _ => Err((StatusCode::BAD_REQUEST, "Invalid dimension".to_string()))
+        _ => Err(AppError::BadRequest("request.invalid"))
     }
 }
 
@@ -647,9 +718,9 @@ pub async fn create_notice_handler(
     State(state): State<AppState>,
     claims: Claims,
     Json(payload): Json<CreateNoticePayload>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let base_id = claims.base_id.ok_or((StatusCode::FORBIDDEN, "Base ID required".to_string()))?;
-    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid User ID".to_string()))?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let base_id = claims.base_id.ok_or(AppError::Forbidden("auth.base_required"))?;
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::Unauthorized("auth.unauthorized"))?;
 
     sqlx::query!(
         "INSERT INTO notices (base_id, title, content, priority, created_by) VALUES ($1, $2, $3, $4, $5)",
@@ -660,8 +731,7 @@ pub async fn create_notice_handler(
         user_id
     )
     .execute(&state.db_pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .await?;
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
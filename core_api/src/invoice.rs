@@ -0,0 +1,269 @@
+/*
+ * src/invoice.rs
+ * 职责: 电子发票开具
+ * 1. InvoiceProvider: 向开票平台提交蓝字 / 红字发票, 返回发票号码及版式文件 (PDF / XML)
+ *    接入税务数字账户或第三方开票服务商时实现该 trait 并在 invoice_from_env 中注册
+ * 2. MockInvoice: 本地开发用 (INVOICE_PROVIDER=mock), 本地生成发票号码和简易 PDF / XML
+ *    INVOICE_MOCK_UNAVAILABLE=true 模拟开票平台不可用, 用于调试开具失败 / 重试
+ * 金额均为含税金额 (分), 红字发票金额为负数
+ */
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+use axum::{async_trait, http::StatusCode};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+#[derive(Debug)]
+pub enum InvoiceError {
+    NotConfigured,    // 未配置开票服务商
+    Rejected(String), // 开票平台拒绝 (购方信息有误 / 额度不足 ...)
+    Provider(String), // 网络 / 平台异常, 可重试
+}
+
+impl From<InvoiceError> for AppError {
+    fn from(e: InvoiceError) -> Self {
+        match e {
+            InvoiceError::NotConfigured => AppError::Custom(StatusCode::SERVICE_UNAVAILABLE, "invoice.not_configured"),
+            InvoiceError::Rejected(msg) => {
+                tracing::warn!("Invoice rejected by provider: {}", msg);
+                AppError::Rejected("invoice.provider_rejected")
+            }
+            InvoiceError::Provider(msg) => {
+                tracing::error!("Invoice provider error: {}", msg);
+                AppError::Custom(StatusCode::BAD_GATEWAY, "invoice.issue_failed")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for InvoiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvoiceError::NotConfigured => write!(f, "invoice provider not configured"),
+            InvoiceError::Rejected(msg) => write!(f, "rejected: {}", msg),
+            InvoiceError::Provider(msg) => write!(f, "provider error: {}", msg),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InvoiceBuyer {
+    pub name: String,
+    pub tax_id: Option<String>,       // 纳税人识别号, 个人可为空
+    pub address_phone: Option<String>,
+    pub bank_account: Option<String>,
+    pub email: Option<String>,        // 交付邮箱
+}
+
+#[derive(Debug, Clone)]
+pub struct InvoiceLineDraft {
+    pub name: String,
+    pub quantity: Option<i32>,         // 折让 / 部分冲红行没有数量
+    pub unit_price_cents: Option<i32>,
+    pub amount_cents: i64,             // 含税
+    pub tax_rate_bp: i32,              // 税率 (万分比), 600 = 6%
+    pub tax_cents: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct InvoiceDraft {
+    pub request_id: Uuid,                    // invoices.id, 服务商侧用作幂等键
+    pub kind: String,                        // blue / red
+    pub invoice_type: String,                // normal 普票 / special 专票
+    pub seller_name: String,
+    pub buyer: InvoiceBuyer,
+    pub lines: Vec<InvoiceLineDraft>,
+    pub total_cents: i64,
+    pub tax_cents: i64,
+    pub original_invoice_no: Option<String>, // 红字发票对应的蓝字发票号码
+    pub remark: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IssuedInvoice {
+    pub invoice_no: String,
+    pub issued_at: DateTime<Utc>,
+    pub pdf: Vec<u8>,
+    pub xml: String,
+}
+
+#[async_trait]
+pub trait InvoiceProvider: Send + Sync {
+    async fn issue(&self, draft: &InvoiceDraft) -> Result<IssuedInvoice, InvoiceError>;
+}
+
+// --- 本地开发: 本地生成发票号码和版式文件 ---
+
+pub struct MockInvoice {
+    seller_tax_id: String,
+    seq: AtomicU32,
+    unavailable: AtomicBool,
+}
+
+impl MockInvoice {
+    pub fn new(seller_tax_id: &str) -> Self {
+        Self { seller_tax_id: seller_tax_id.to_string(), seq: AtomicU32::new(0), unavailable: AtomicBool::new(false) }
+    }
+
+    // 模拟开票平台不可用: 开具返回 Provider 错误
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.unavailable.store(unavailable, Ordering::Relaxed);
+    }
+}
+
+impl Default for MockInvoice {
+    fn default() -> Self {
+        Self::new("91110000MOCK00000X")
+    }
+}
+
+#[async_trait]
+impl InvoiceProvider for MockInvoice {
+    async fn issue(&self, draft: &InvoiceDraft) -> Result<IssuedInvoice, InvoiceError> {
+        if self.unavailable.load(Ordering::Relaxed) {
+            return Err(InvoiceError::Provider("mock invoice platform unavailable".to_string()));
+        }
+        if draft.buyer.name.trim().is_empty() {
+            return Err(InvoiceError::Rejected("buyer name is empty".to_string()));
+        }
+        let issued_at = Utc::now();
+        // 数电票号码 20 位: 年份 2 位 + 申请 id 取 10 位 + 序号 8 位
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed) % 100_000_000;
+        let invoice_no = format!(
+            "{}{:010}{:08}",
+            issued_at.format("%y"),
+            draft.request_id.as_u128() % 10_000_000_000,
+            seq
+        );
+        let xml = mock_xml(draft, &self.seller_tax_id, &invoice_no, issued_at);
+        let pdf = mock_pdf(draft, &self.seller_tax_id, &invoice_no, issued_at);
+        Ok(IssuedInvoice { invoice_no, issued_at, pdf, xml })
+    }
+}
+
+// --- 未配置: 拒绝开票, 开票申请保持待开具 ---
+
+pub struct UnconfiguredInvoice;
+
+#[async_trait]
+impl InvoiceProvider for UnconfiguredInvoice {
+    async fn issue(&self, _draft: &InvoiceDraft) -> Result<IssuedInvoice, InvoiceError> {
+        Err(InvoiceError::NotConfigured)
+    }
+}
+
+// 启动时读取: INVOICE_PROVIDER=mock 用本地模拟, 销方税号取 INVOICE_SELLER_TAX_ID
+pub fn invoice_from_env() -> Arc<dyn InvoiceProvider> {
+    match std::env::var("INVOICE_PROVIDER").unwrap_or_default().trim() {
+        "mock" => {
+            tracing::warn!("INVOICE_PROVIDER=mock: invoices are generated locally and are not legally valid");
+            let mock = match std::env::var("INVOICE_SELLER_TAX_ID") {
+                Ok(tax_id) if !tax_id.trim().is_empty() => MockInvoice::new(tax_id.trim()),
+                _ => MockInvoice::default(),
+            };
+            mock.set_unavailable(std::env::var("INVOICE_MOCK_UNAVAILABLE").map(|v| v == "true").unwrap_or(false));
+            Arc::new(mock)
+        }
+        _ => {
+            tracing::warn!("INVOICE_PROVIDER is not set, electronic invoicing is disabled");
+            Arc::new(UnconfiguredInvoice)
+        }
+    }
+}
+
+// ---------------------------------------------------------
+// 模拟版式文件
+// ---------------------------------------------------------
+
+fn yuan(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn mock_xml(draft: &InvoiceDraft, seller_tax_id: &str, invoice_no: &str, issued_at: DateTime<Utc>) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<EInvoice>\n");
+    let _ = writeln!(xml, "  <InvoiceNumber>{}</InvoiceNumber>", invoice_no);
+    let _ = writeln!(xml, "  <IssueTime>{}</IssueTime>", issued_at.format("%Y-%m-%d %H:%M:%S"));
+    let _ = writeln!(xml, "  <Kind>{}</Kind>", draft.kind);
+    let _ = writeln!(xml, "  <InvoiceType>{}</InvoiceType>", draft.invoice_type);
+    if let Some(original) = &draft.original_invoice_no {
+        let _ = writeln!(xml, "  <OriginalInvoiceNumber>{}</OriginalInvoiceNumber>", original);
+    }
+    let _ = writeln!(xml, "  <Seller><Name>{}</Name><TaxID>{}</TaxID></Seller>", xml_escape(&draft.seller_name), seller_tax_id);
+    let _ = writeln!(
+        xml,
+        "  <Buyer><Name>{}</Name><TaxID>{}</TaxID></Buyer>",
+        xml_escape(&draft.buyer.name),
+        xml_escape(draft.buyer.tax_id.as_deref().unwrap_or(""))
+    );
+    xml.push_str("  <Items>\n");
+    for line in &draft.lines {
+        let _ = writeln!(
+            xml,
+            "    <Item><Name>{}</Name><Quantity>{}</Quantity><UnitPrice>{}</UnitPrice><Amount>{}</Amount><TaxRate>{}</TaxRate><Tax>{}</Tax></Item>",
+            xml_escape(&line.name),
+            line.quantity.map(|q| q.to_string()).unwrap_or_default(),
+            line.unit_price_cents.map(|p| yuan(p as i64)).unwrap_or_default(),
+            yuan(line.amount_cents),
+            line.tax_rate_bp as f64 / 10_000.0,
+            yuan(line.tax_cents)
+        );
+    }
+    xml.push_str("  </Items>\n");
+    let _ = writeln!(xml, "  <Total>{}</Total>", yuan(draft.total_cents));
+    let _ = writeln!(xml, "  <TotalTax>{}</TotalTax>", yuan(draft.tax_cents));
+    if let Some(remark) = &draft.remark {
+        let _ = writeln!(xml, "  <Remark>{}</Remark>", xml_escape(remark));
+    }
+    xml.push_str("</EInvoice>\n");
+    xml
+}
+
+// 单页 PDF, 内置字体只能显示 ASCII, 只写号码 / 税号 / 金额
+fn mock_pdf(draft: &InvoiceDraft, seller_tax_id: &str, invoice_no: &str, issued_at: DateTime<Utc>) -> Vec<u8> {
+    let ascii = |s: &str| s.chars().filter(|c| c.is_ascii() && !matches!(c, '(' | ')' | '\\')).collect::<String>();
+    let lines = [
+        format!("MOCK E-INVOICE ({})", if draft.kind == "red" { "RED" } else { "BLUE" }),
+        format!("No. {}", invoice_no),
+        format!("Date {}", issued_at.format("%Y-%m-%d")),
+        format!("Seller tax id {}", seller_tax_id),
+        format!("Buyer tax id {}", ascii(draft.buyer.tax_id.as_deref().unwrap_or("-"))),
+        format!("Total {} (tax {})", yuan(draft.total_cents), yuan(draft.tax_cents)),
+        draft.original_invoice_no.as_ref().map(|n| format!("Reverses {}", n)).unwrap_or_default(),
+    ];
+    let mut content = String::from("BT /F1 12 Tf 50 780 Td 16 TL\n");
+    for line in lines.iter().filter(|l| !l.is_empty()) {
+        let _ = writeln!(content, "({}) Tj T*", line);
+    }
+    content.push_str("ET\n");
+
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Contents 4 0 R /Resources << /Font << /F1 5 0 R >> >> >>".to_string(),
+        format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+    ];
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, obj) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        let _ = write!(pdf, "{} 0 obj\n{}\nendobj\n", i + 1, obj);
+    }
+    let xref = pdf.len();
+    let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(pdf, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(pdf, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref);
+    pdf.into_bytes()
+}
@@ -0,0 +1,234 @@
+/*
+ * src/invoice_tests.rs
+ * 职责: 电子发票集成测试 (MockInvoice 开具)
+ * 1. 开票申请校验: 专票税号 / 可开数量 / 可开金额
+ * 2. 按订单明细开具蓝字发票, 下载 PDF / XML, 同步订单开票状态
+ * 3. 退款审批通过后自动冲红; 手工整张冲红; 开票平台不可用时记为失败, 可重试 / 撤销
+ * 4. 开票与收款核对
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::handlers::AppState;
+use crate::invoice::MockInvoice;
+use crate::test_support::{cleanup_tenant, login, seed_tenant, spawn_app, test_pool, test_state};
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn invoices_issue_reverse_and_reconcile() {
+    let pool = test_pool(5).await;
+
+    let provider = Arc::new(MockInvoice::default());
+    let addr = spawn_app(AppState { invoice: provider.clone(), ..test_state(&pool) }).await;
+
+    let tenant = seed_tenant(&pool, "invoice-test").await;
+    let hq = tenant.hq;
+    let (_, admin_email) = tenant.add_staff(&pool, "role.base.admin").await;
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+    let token = login(addr, &admin_email).await;
+    let send = |method: reqwest::Method, path: &str, body: Value| {
+        let req = client.request(method, url(path)).bearer_auth(&token).json(&body);
+        async move { req.send().await.unwrap() }
+    };
+    let get = |path: &str| {
+        let req = client.get(url(path)).bearer_auth(&token);
+        async move { req.send().await.unwrap() }
+    };
+    let reconciliation = |order: Uuid| {
+        let req = client.get(url("/api/v1/finance/invoices/reconciliation")).bearer_auth(&token);
+        async move {
+            let body: Value = req.send().await.unwrap().json().await.unwrap();
+            body["rows"].as_array().unwrap().iter().find(|r| r["order_id"] == order.to_string()).cloned().unwrap()
+        }
+    };
+
+    // 订单: 教材 4 件 x 50 元 + 营地 1 x 800 元, 全额收款
+    let created: Value = send(reqwest::Method::POST, "/api/v1/finance/orders", json!({
+        "type_": "b2c",
+        "contact_name": "invoice parent",
+        "items": [
+            {"name": "kit", "quantity": 4, "unit_price": 50.0},
+            {"name": "camp", "quantity": 1, "unit_price": 800.0},
+        ],
+    }))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let order: Uuid = created["order_id"].as_str().unwrap().parse().unwrap();
+    send(reqwest::Method::POST, "/api/v1/finance/payments", json!({
+        "order_id": order, "amount": 1000.0, "channel": "bank_transfer", "payer_name": "parent",
+    }))
+    .await;
+    let payment: Uuid = sqlx::query_scalar("SELECT id FROM finance_payment_records WHERE order_id = $1")
+        .bind(order)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let verified = send(reqwest::Method::PUT, &format!("/api/v1/finance/payments/{}/verify", payment), json!({})).await;
+    assert_eq!(verified.status(), reqwest::StatusCode::OK);
+    let (kit, camp): (Uuid, Uuid) = sqlx::query_as(
+        "SELECT (SELECT id FROM order_items WHERE order_id = $1 AND name = 'kit'), (SELECT id FROM order_items WHERE order_id = $1 AND name = 'camp')",
+    )
+    .bind(order)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    // 1. 校验
+    let path = format!("/api/v1/finance/orders/{}/invoices", order);
+    let special = send(reqwest::Method::POST, &path, json!({"invoice_type": "special", "buyer_name": "ACME"})).await;
+    assert_eq!(special.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(special.json::<Value>().await.unwrap()["code"], "invoice.tax_id_required");
+    let too_many = send(reqwest::Method::POST, &path, json!({
+        "buyer_name": "ACME", "items": [{"order_item_id": kit, "quantity": 5}],
+    }))
+    .await;
+    assert_eq!(too_many.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(too_many.json::<Value>().await.unwrap()["code"], "invoice.quantity_exceeded");
+
+    // 2. 先开 2 件教材, 再开其余全部明细
+    let first = send(reqwest::Method::POST, &path, json!({
+        "buyer_name": "ACME Ltd", "buyer_tax_id": "91310000ACME00001X", "items": [{"order_item_id": kit, "quantity": 2}],
+    }))
+    .await;
+    assert_eq!(first.status(), reqwest::StatusCode::CREATED);
+    let first: Value = first.json().await.unwrap();
+    assert_eq!(first["status"], "issued");
+    assert_eq!(first["kind"], "blue");
+    assert_eq!(first["amount_cents"], 10_000);
+    assert_eq!(first["tax_cents"], 566);
+    assert_eq!(first["invoice_no"].as_str().unwrap().len(), 20);
+    let first_id = first["id"].as_str().unwrap().to_string();
+
+    let second: Value = send(reqwest::Method::POST, &path, json!({"buyer_name": "ACME Ltd", "buyer_tax_id": "91310000ACME00001X"}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(second["amount_cents"], 90_000);
+    let lines = second["lines"].as_array().unwrap();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.iter().any(|l| l["order_item_id"] == kit.to_string() && l["quantity"] == 2));
+    assert!(lines.iter().any(|l| l["order_item_id"] == camp.to_string() && l["quantity"] == 1));
+    let nothing = send(reqwest::Method::POST, &path, json!({"buyer_name": "ACME Ltd"})).await;
+    assert_eq!(nothing.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(nothing.json::<Value>().await.unwrap()["code"], "invoice.nothing_to_invoice");
+
+    let pdf = get(&format!("/api/v1/finance/invoices/{}/pdf", first_id)).await;
+    assert_eq!(pdf.headers()["content-type"], "application/pdf");
+    assert!(pdf.bytes().await.unwrap().starts_with(b"%PDF-"));
+    let xml = get(&format!("/api/v1/finance/invoices/{}/xml", first_id)).await.text().await.unwrap();
+    assert!(xml.contains(first["invoice_no"].as_str().unwrap()));
+    assert!(xml.contains("91310000ACME00001X"));
+
+    let (invoice_status, invoice_no): (Option<String>, Option<String>) =
+        sqlx::query_as("SELECT invoice_status, invoice_no FROM orders WHERE id = $1")
+            .bind(order)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(invoice_status.as_deref(), Some("billed"));
+    assert_eq!(invoice_no.as_deref(), second["invoice_no"].as_str());
+    let row = reconciliation(order).await;
+    assert_eq!(row["invoiced_cents"], 100_000);
+    assert_eq!(row["status"], "matched");
+
+    // 3. 退款 300 元审批通过: 从最近的蓝字发票部分冲红
+    let refund: Value = send(reqwest::Method::POST, &format!("/api/v1/finance/orders/{}/refunds", order), json!({
+        "amount": 300.0, "reason": "camp shortened", "channel": "bank",
+    }))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let approved = send(reqwest::Method::POST, "/api/v1/base/approval/action", json!({
+        "id": refund["id"], "type": "refund", "action": "approve",
+    }))
+    .await;
+    assert_eq!(approved.status(), reqwest::StatusCode::OK);
+    let invoices: Value = get(&path).await.json().await.unwrap();
+    let red = invoices.as_array().unwrap().iter().find(|i| i["kind"] == "red").unwrap();
+    assert_eq!(red["status"], "issued");
+    assert_eq!(red["amount_cents"], -30_000);
+    assert_eq!(red["original_invoice_id"], second["id"]);
+    assert_eq!(red["refund_request_id"], refund["id"]);
+    assert_eq!(red["lines"].as_array().unwrap().len(), 1);
+    assert_eq!(reconciliation(order).await["status"], "matched");
+
+    // 4. 开票平台不可用时整张冲红第一张: 记为失败, 恢复后重试
+    provider.set_unavailable(true);
+    let failed = send(reqwest::Method::POST, &format!("/api/v1/finance/invoices/{}/reverse", first_id), json!({"reason": "wrong buyer"})).await;
+    assert_eq!(failed.status(), reqwest::StatusCode::BAD_GATEWAY);
+    let failed: Value = failed.json().await.unwrap();
+    assert_eq!(failed["code"], "invoice.issue_failed");
+    let red_id = failed["details"]["invoice_id"].as_str().unwrap().to_string();
+    assert_eq!(reconciliation(order).await["pending_cents"], -10_000);
+    provider.set_unavailable(false);
+
+    let retried = send(reqwest::Method::POST, &format!("/api/v1/finance/invoices/{}/issue", red_id), json!({})).await;
+    assert_eq!(retried.status(), reqwest::StatusCode::OK);
+    let retried: Value = retried.json().await.unwrap();
+    assert_eq!(retried["status"], "issued");
+    assert_eq!(retried["attempts"], 2);
+    assert_eq!(retried["lines"][0]["quantity"], -2);
+    let again = send(reqwest::Method::POST, &format!("/api/v1/finance/invoices/{}/reverse", first_id), json!({"reason": "again"})).await;
+    assert_eq!(again.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(again.json::<Value>().await.unwrap()["code"], "invoice.already_reversed");
+
+    let row = reconciliation(order).await;
+    assert_eq!(row["invoiced_cents"], 60_000);
+    assert_eq!(row["paid_amount_cents"], 70_000);
+    assert_eq!(row["difference_cents"], -10_000);
+    assert_eq!(row["status"], "under_invoiced");
+    let mismatched: Value = get("/api/v1/finance/invoices/reconciliation?mismatch_only=true").await.json().await.unwrap();
+    assert_eq!(mismatched["rows"].as_array().unwrap().len(), 1);
+
+    // 冲红退回的 2 件教材可以重新开票
+    let reissued = send(reqwest::Method::POST, &path, json!({
+        "buyer_name": "ACME Group", "items": [{"order_item_id": kit, "quantity": 2}],
+    }))
+    .await;
+    assert_eq!(reissued.status(), reqwest::StatusCode::CREATED);
+    assert_eq!(reconciliation(order).await["status"], "matched");
+
+    // 5. 无明细订单: 开票失败后撤销, 订单回到未开票
+    let b2b: Value = send(reqwest::Method::POST, "/api/v1/finance/orders", json!({
+        "type_": "b2b", "contact_name": "district", "total_amount": 500.0,
+    }))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let b2b: Uuid = b2b["order_id"].as_str().unwrap().parse().unwrap();
+    provider.set_unavailable(true);
+    let failed: Value = send(reqwest::Method::POST, &format!("/api/v1/finance/orders/{}/invoices", b2b), json!({"buyer_name": "District"}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    provider.set_unavailable(false);
+    let pending_id = failed["details"]["invoice_id"].as_str().unwrap().to_string();
+    let listed: Value = get(&format!("/api/v1/finance/orders/{}/invoices", b2b)).await.json().await.unwrap();
+    assert_eq!(listed[0]["status"], "failed");
+    assert_eq!(listed[0]["lines"][0]["name"], "培训服务费");
+    assert_eq!(listed[0]["amount_cents"], 50_000);
+    let cancelled = send(reqwest::Method::POST, &format!("/api/v1/finance/invoices/{}/cancel", pending_id), json!({})).await;
+    assert_eq!(cancelled.status(), reqwest::StatusCode::OK);
+    let not_issued = get(&format!("/api/v1/finance/invoices/{}/pdf", pending_id)).await;
+    assert_eq!(not_issued.status(), reqwest::StatusCode::CONFLICT);
+    let status: Option<String> = sqlx::query_scalar("SELECT invoice_status FROM orders WHERE id = $1")
+        .bind(b2b)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status.as_deref(), Some("unbilled"));
+
+    cleanup_tenant(&pool, hq).await;
+}
@@ -0,0 +1,177 @@
+/*
+ * src/ledger_tests.rs
+ * 职责: 总账集成测试
+ * 1. 手工凭证: 借贷不平衡返回 422 ledger.unbalanced
+ * 2. 业务记账: 订单收款审核 / 报销审批自动生成凭证, 不能重复审核
+ * 3. 报表: 试算平衡 / 利润表 / 资产负债表 与凭证一致
+ * 4. 结账: 当月不能结账, 已结账月份不能记账, 反结账后恢复
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use std::collections::HashMap;
+
+use chrono::{Datelike, Months, Utc};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::test_support::{cleanup_tenant, login, seed_tenant, spawn_app, test_pool, test_state};
+use crate::timezone::business_date;
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn ledger_posts_business_flows_and_closes_periods() {
+    let pool = test_pool(5).await;
+
+    let addr = spawn_app(test_state(&pool)).await;
+
+    let tenant = seed_tenant(&pool, "ledger-test").await;
+    let (hq, base) = (tenant.hq, tenant.base);
+    let tag = hq.simple().to_string();
+    let (_, hq_email) = tenant.add_staff(&pool, "role.hq.admin").await;
+    let (_, base_email) = tenant.add_staff(&pool, "role.base.admin").await;
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+    let hq_token = login(addr, &hq_email).await;
+    let base_token = login(addr, &base_email).await;
+    let get = |path: &str, token: &str| {
+        let req = client.get(url(path)).bearer_auth(token);
+        async move { req.send().await.unwrap().json::<Value>().await.unwrap() }
+    };
+    let send = |method: reqwest::Method, path: &str, token: &str, body: Value| {
+        let req = client.request(method, url(path)).bearer_auth(token).json(&body);
+        async move { req.send().await.unwrap() }
+    };
+
+    // 新总部自动带出默认科目
+    let accounts: HashMap<String, String> = get("/api/v1/ledger/accounts", &base_token)
+        .await
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|a| Some((a["system_key"].as_str()?.to_string(), a["id"].as_str()?.to_string())))
+        .collect();
+    assert!(accounts.contains_key("contract_liability"));
+    let line = |key: &str, debit: i64, credit: i64| json!({"account_id": accounts[key], "debit_cents": debit, "credit_cents": credit});
+
+    // 1. 手工凭证: 投入资本 1000 元; 借贷不平衡被拒绝
+    let unbalanced = send(reqwest::Method::POST, "/api/v1/ledger/entries", &base_token, json!({
+        "description": "unbalanced",
+        "lines": [line("bank", 100_000, 0), line("paid_in_capital", 0, 90_000)],
+    }))
+    .await;
+    assert_eq!(unbalanced.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(unbalanced.json::<Value>().await.unwrap()["code"], "ledger.unbalanced");
+    let capital = send(reqwest::Method::POST, "/api/v1/ledger/entries", &base_token, json!({
+        "description": "capital",
+        "lines": [line("bank", 100_000, 0), line("paid_in_capital", 0, 100_000)],
+    }))
+    .await;
+    assert_eq!(capital.status(), reqwest::StatusCode::CREATED);
+
+    // 2. 订单收款审核: 收款 + 付清后确认收入; 不能重复审核
+    let order = Uuid::new_v4();
+    let payment = Uuid::new_v4();
+    sqlx::query("INSERT INTO orders (id, hq_id, base_id, order_no, type, total_amount_cents) VALUES ($1, $2, $3, $4, 'b2b', 20000)")
+        .bind(order)
+        .bind(hq)
+        .bind(base)
+        .bind(format!("LEDGER-{}", tag))
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO finance_payment_records (id, hq_id, base_id, order_id, channel, amount_cents) VALUES ($1, $2, $3, $4, 'bank', 20000)")
+        .bind(payment)
+        .bind(hq)
+        .bind(base)
+        .bind(order)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let verify_path = format!("/api/v1/finance/payments/{}/verify", payment);
+    let verified = send(reqwest::Method::PUT, &verify_path, &base_token, json!({})).await;
+    assert_eq!(verified.status(), reqwest::StatusCode::OK);
+    let again = send(reqwest::Method::PUT, &verify_path, &base_token, json!({})).await;
+    assert_eq!(again.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(again.json::<Value>().await.unwrap()["code"], "payment.not_pending");
+
+    // 3. 报销审批通过: 借 销售费用, 贷 库存现金
+    let expense = Uuid::new_v4();
+    sqlx::query("INSERT INTO expenses (id, hq_id, base_id, category, amount_cents, description) VALUES ($1, $2, $3, 'marketing', 3000, 'flyers')")
+        .bind(expense)
+        .bind(hq)
+        .bind(base)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let approval = json!({"id": expense, "type": "expense", "action": "approve"});
+    let approved = send(reqwest::Method::POST, "/api/v1/base/approval/action", &base_token, approval.clone()).await;
+    assert_eq!(approved.status(), reqwest::StatusCode::OK);
+    let approved_again = send(reqwest::Method::POST, "/api/v1/base/approval/action", &base_token, approval).await;
+    assert_eq!(approved_again.status(), reqwest::StatusCode::CONFLICT);
+
+    let entries = get(&format!("/api/v1/ledger/entries?base_id={}", base), &base_token).await;
+    let mut sources: Vec<&str> = entries["items"].as_array().unwrap().iter().map(|e| e["source_type"].as_str().unwrap()).collect();
+    sources.sort();
+    assert_eq!(sources, ["expense", "manual", "order_payment", "order_revenue"]);
+
+    // 4. 报表
+    let trial = get("/api/v1/ledger/trial-balance", &base_token).await;
+    assert_eq!(trial["balanced"], true);
+    assert_eq!(trial["totals"]["period_debit_cents"], 100_000 + 20_000 * 2 + 3000);
+
+    let income = get("/api/v1/ledger/income-statement", &base_token).await;
+    assert_eq!(income["total_revenue_cents"], 20_000);
+    assert_eq!(income["total_expense_cents"], 3000);
+    assert_eq!(income["net_income_cents"], 17_000);
+
+    let sheet = get("/api/v1/ledger/balance-sheet", &base_token).await;
+    assert_eq!(sheet["balanced"], true);
+    assert_eq!(sheet["total_assets_cents"], 100_000 + 20_000 - 3000);
+    assert_eq!(sheet["total_liabilities_cents"], 0);
+    assert_eq!(sheet["unclosed_earnings_cents"], 17_000);
+
+    // 5. 结账: 上月有一张总部级凭证
+    let today = business_date(Utc::now());
+    let last_month = today.with_day(1).unwrap() - Months::new(1);
+    let last_period = last_month.format("%Y-%m").to_string();
+    let backdated = |token: &str| {
+        send(reqwest::Method::POST, "/api/v1/ledger/entries", token, json!({
+            "entry_date": last_month,
+            "description": "backdated",
+            "lines": [line("cash", 500, 0), line("other_payable", 0, 500)],
+        }))
+    };
+    assert_eq!(backdated(&hq_token).await.status(), reqwest::StatusCode::CREATED);
+
+    let period_action = |period: &str, action: &str| {
+        send(reqwest::Method::POST, &format!("/api/v1/hq/ledger/periods/{}/{}", period, action), &hq_token, json!({}))
+    };
+    let current = period_action(&today.format("%Y-%m").to_string(), "close").await;
+    assert_eq!(current.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(current.json::<Value>().await.unwrap()["code"], "ledger.period_not_ended");
+    assert_eq!(period_action("2026-13", "close").await.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(period_action(&last_period, "close").await.status(), reqwest::StatusCode::NO_CONTENT);
+
+    let closed = backdated(&hq_token).await;
+    assert_eq!(closed.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(closed.json::<Value>().await.unwrap()["code"], "ledger.period_closed");
+    let periods = get("/api/v1/hq/ledger/periods", &hq_token).await;
+    let periods = periods.as_array().unwrap();
+    assert_eq!(periods.len(), 2); // 当月 + 上月
+    assert!(periods[0]["closed_at"].is_null());
+    assert_eq!(periods[1]["entry_count"], 1);
+    assert!(periods[1]["closed_at"].is_string());
+
+    assert_eq!(period_action(&last_period, "reopen").await.status(), reqwest::StatusCode::NO_CONTENT);
+    let reopened = period_action(&last_period, "reopen").await;
+    assert_eq!(reopened.json::<Value>().await.unwrap()["code"], "ledger.period_open");
+    assert_eq!(backdated(&hq_token).await.status(), reqwest::StatusCode::CREATED);
+
+    // 总部汇总含总部级凭证
+    let hq_trial = get("/api/v1/ledger/trial-balance", &hq_token).await;
+    assert_eq!(hq_trial["balanced"], true);
+    assert_eq!(hq_trial["totals"]["opening_debit_cents"], 1000);
+
+    cleanup_tenant(&pool, hq).await;
+}
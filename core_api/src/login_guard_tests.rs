@@ -0,0 +1,120 @@
+/*
+ * src/login_guard_tests.rs
+ * 职责: 登录防爆破集成测试
+ * 1. 同一邮箱连续输错: 逐次延迟 (429), 达到上限后锁定 (423), 锁定期间正确密码也不能登录
+ * 2. 总部风控页能看到被锁账号, 管理员解锁后恢复登录
+ * 3. 同一 IP 错误次数过多时暂停该 IP 的登录
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::handlers::LOGIN_FAILED_CREDENTIALS;
+use crate::test_support::{cleanup_tenant, login, seed_tenant, spawn_app, test_pool, test_state, TEST_PASSWORD};
+
+// 尝试登录不存在的邮箱时, 登录记录没有 hq_id, 按邮箱里的标记单独清理
+async fn cleanup(pool: &PgPool, hq: Uuid, tag: &str) {
+    sqlx::query("DELETE FROM user_login_history WHERE email_attempted LIKE '%' || $1 || '%'")
+        .bind(tag)
+        .execute(pool)
+        .await
+        .unwrap();
+    cleanup_tenant(pool, hq).await;
+}
+
+// 直接写入较早的错误记录, 避免测试里等待逐次延迟
+async fn seed_failures(pool: &PgPool, email: &str, ip: &str, count: i32) {
+    sqlx::query(
+        r#"
+        INSERT INTO user_login_history (email_attempted, ip_address, status, failure_reason_key, login_timestamp)
+        SELECT $1, $2, 'failed', $3, NOW() - INTERVAL '5 minutes'
+        FROM generate_series(1, $4)
+        "#,
+    )
+    .bind(email)
+    .bind(ip)
+    .bind(LOGIN_FAILED_CREDENTIALS)
+    .bind(count)
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn repeated_failures_throttle_lock_and_admin_unlock() {
+    let pool = test_pool(5).await;
+
+    // 按来源 IP 限流, spawn_app 与 main.rs 一样带上了 ConnectInfo
+    let addr = spawn_app(test_state(&pool)).await;
+    let tenant = seed_tenant(&pool, "guard-test").await;
+    let hq = tenant.hq;
+    let tag = hq.simple().to_string();
+    let (_, admin_email) = tenant.add_staff(&pool, "role.hq.admin").await;
+    let (teacher, teacher_email) = tenant.add_staff(&pool, "role.teacher").await;
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+    let attempt = |email: &str, password: &str| {
+        let req = client.post(url("/api/v1/auth/login")).json(&json!({"email": email, "password": password}));
+        async move { req.send().await.unwrap().status() }
+    };
+
+    let admin_token = login(addr, &admin_email).await;
+
+    // 1. 逐次延迟: 连续两次错误后, 马上再试被拒绝 (不校验密码)
+    assert_eq!(attempt(&teacher_email, "wrong-1").await, reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(attempt(&teacher_email, "wrong-2").await, reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(attempt(&teacher_email, TEST_PASSWORD).await, reqwest::StatusCode::TOO_MANY_REQUESTS);
+
+    // 2. 达到上限后锁定, 正确密码也返回 423
+    seed_failures(&pool, &teacher_email, "10.0.0.9", 3).await;
+    assert_eq!(attempt(&teacher_email, TEST_PASSWORD).await, reqwest::StatusCode::LOCKED);
+
+    // 3. 风控页: 被锁账号列表和告警
+    let locked: Value = client
+        .get(url("/api/v1/hq/staff/locked-accounts"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let locked = locked.as_array().unwrap();
+    assert_eq!(locked.len(), 1);
+    assert_eq!(locked[0]["id"], teacher.to_string());
+    assert_eq!(locked[0]["failed_attempts"], 5);
+    let risk: Value = client
+        .get(url("/api/v1/hq/staff/risk-stats"))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(risk["alerts"].as_array().unwrap().iter().any(|a| a["id"] == 3));
+
+    // 4. 管理员解锁后恢复登录, 成功登录后错误次数重新计算
+    let unlock = client
+        .post(url(&format!("/api/v1/hq/users/{}/unlock", teacher)))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unlock.status(), reqwest::StatusCode::NO_CONTENT);
+    assert_eq!(attempt(&teacher_email, TEST_PASSWORD).await, reqwest::StatusCode::OK);
+    assert_eq!(attempt(&teacher_email, "wrong-3").await, reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(attempt(&teacher_email, TEST_PASSWORD).await, reqwest::StatusCode::OK);
+    // 邮箱不区分大小写, 与错误计数口径一致
+    assert_eq!(attempt(&teacher_email.to_uppercase(), TEST_PASSWORD).await, reqwest::StatusCode::OK);
+
+    // 5. 同一 IP 错误过多: 换任何邮箱都被暂停
+    seed_failures(&pool, &format!("spray-{}@guard.test", tag), "127.0.0.1", 20).await;
+    assert_eq!(attempt(&admin_email, TEST_PASSWORD).await, reqwest::StatusCode::TOO_MANY_REQUESTS);
+
+    cleanup(&pool, hq, &tag).await;
+}
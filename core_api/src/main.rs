@@ -2,7 +2,7 @@
  * src/main.rs (修复版)
  */
 use axum::{
-    routing::{get, post, patch, put},
+    routing::{delete, get, post, patch, put},
     Router,
     http::{Method},
 };
@@ -14,17 +14,60 @@ use tower_http::trace::TraceLayer;
 use tower_http::cors::CorsLayer;
 use axum::http::header; 
 
+mod error;
 mod handlers;
 mod models;
+mod invoice;
 mod middleware; // 这里的 middleware 指的是 src/middleware.rs
+mod pagination;
+mod payment;
+mod permissions;
+mod sms;
+mod tenant;
+mod timezone;
+mod wechat;
+
+#[cfg(test)]
+mod api_error_tests;
+#[cfg(test)]
+mod invoice_tests;
+#[cfg(test)]
+mod ledger_tests;
+#[cfg(test)]
+mod login_guard_tests;
+#[cfg(test)]
+mod online_payment_tests;
+#[cfg(test)]
+mod order_refund_tests;
+#[cfg(test)]
+mod pagination_tests;
+#[cfg(test)]
+mod receivable_tests;
+#[cfg(test)]
+mod schedule_conflict_tests;
+#[cfg(test)]
+mod password_tests;
+#[cfg(test)]
+mod sms_tests;
+#[cfg(test)]
+mod tenant_tests;
+#[cfg(test)]
+mod test_support;
+#[cfg(test)]
+mod waitlist_tests;
+#[cfg(test)]
+mod wechat_tests;
 
 use middleware::auth_middleware; // 引入我们自己写的鉴权函数
+use permissions::{authenticated, base, customer, hq, perm, staff, RequirePermission};
 
 use tower_http::services::ServeDir;
 
 use handlers::{
     AppState,
-    db_health_handler, ai_health_handler, register_handler, login_handler,
+    db_health_handler, ai_health_handler, register_handler, login_handler, refresh_token_handler, logout_handler,
+    change_password_handler, reset_user_password_handler, complete_password_reset_handler,
+    unlock_user_login_handler,
     get_hq_bases_handler, create_hq_base_handler, update_hq_base_handler,
     create_asset_type_handler, get_asset_types_handler, get_all_assets_handler,
     create_asset_handler, transfer_asset_handler, delete_asset_handler,
@@ -38,17 +81,43 @@ use handlers::{
     get_report_stats_handler, create_notice_handler,
     create_membership_tier_handler, get_membership_tiers_handler, assign_membership_handler,
     get_customer_memberships_handler, get_base_memberships_handler, toggle_tier_status_handler,
+    freeze_membership_handler, unfreeze_membership_handler, extend_membership_handler,
+    transfer_membership_handler, refund_membership_handler, get_membership_adjustments_handler,
+    run_revenue_recognition_handler, update_tier_recognition_handler, spawn_revenue_recognition_job,
+    get_ledger_accounts_handler, create_ledger_account_handler, update_ledger_account_handler,
+    get_journal_entries_handler, create_journal_entry_handler,
+    get_accounting_periods_handler, close_accounting_period_handler, reopen_accounting_period_handler,
+    get_trial_balance_handler, get_income_statement_handler, get_balance_sheet_handler,
+    get_renewal_reminders_handler, follow_up_reminder_handler, spawn_membership_reminder_job,
     create_course_handler, update_course_handler, get_courses_handler, toggle_course_status_handler,
     create_room_handler, get_rooms_handler, update_room_handler, delete_room_handler,
     get_base_teachers_handler, get_teacher_dashboard_handler, create_base_class_handler, get_base_classes_handler, 
     update_class_handler, create_enrollment_handler, get_enrollments_for_class_handler,
     complete_enrollment_handler, delete_enrollment_handler, delete_class_handler,
+    create_class_series_handler, extend_class_series_handler, get_class_series_handler, update_series_occurrence_handler,
+    add_series_exclusion_handler, enroll_series_handler,
+    get_substitutions_handler, get_substitute_candidates_handler, assign_substitute_handler,
+    get_customer_notifications_handler, mark_customer_notification_read_handler,
+    get_class_waitlist_handler, cancel_waitlist_entry_handler,
     create_honor_rank, get_honor_ranks, update_honor_rank, get_hq_users,
-    create_hq_user, update_user_handler, get_stock_alerts_handler, get_base_stock_handler,
+    create_hq_user, update_user_handler, update_user_status_handler, get_stock_alerts_handler, get_base_stock_handler,
     create_procurement_order, get_procurement_orders, get_procurement_details, update_procurement_status,
     get_teacher_config_handler, update_teacher_skills_handler, add_teacher_availability_handler,
     delete_teacher_availability_handler, trigger_auto_schedule_handler,
+    create_schedule_draft_handler, get_schedule_drafts_handler, get_schedule_draft_handler,
+    update_schedule_draft_item_handler, delete_schedule_draft_item_handler,
+    commit_schedule_draft_handler, discard_schedule_draft_handler,
     create_income_order_handler, get_income_orders_handler,update_income_order_handler, cancel_income_order_handler,
+    create_order_refund_handler, get_order_refunds_handler,
+    get_order_installments_handler, set_order_installments_handler, get_receivables_aging_handler,
+    get_order_invoices_handler, create_order_invoice_handler, issue_invoice_handler, cancel_invoice_handler,
+    reverse_invoice_handler, download_invoice_pdf_handler, download_invoice_xml_handler,
+    get_invoice_reconciliation_handler,
+    // 在线支付
+    create_order_payment_handler, purchase_membership_handler, get_customer_payment_handler,
+    simulate_customer_payment_handler, wechat_pay_notify_handler, get_online_payments_handler,
+    sync_online_payment_handler, get_payment_reconciliations_handler, get_payment_reconciliation_handler,
+    run_payment_reconciliation_handler, spawn_payment_reconciliation_job,
     create_expense_handler, get_expenses_handler, get_payment_records_handler, verify_payment_handler, 
     get_hq_products_handler, create_supply_order_handler, upload_payment_proof_handler,
     get_all_supply_orders_handler, confirm_supply_payment_handler, ship_supply_order_handler,
@@ -59,7 +128,7 @@ use handlers::{
     update_invoice_status_handler,upload_file_handler,
     get_base_finance_dashboard_handler,
     generate_qrcodes_handler, verify_qrcode_handler, export_batch_csv_handler,list_batches_handler, activate_batch_handler,
-    get_staff_risk_stats_handler, get_key_personnel_handler, get_purchase_rankings_handler, get_activity_rankings_handler,
+    get_staff_risk_stats_handler, get_key_personnel_handler, get_locked_accounts_handler, get_purchase_rankings_handler, get_activity_rankings_handler,
     get_hq_dashboard_stats_handler, get_hq_dashboard_analytics_handler, get_hq_dashboard_pending_staff_handler,
     get_top_products_handler, get_order_trend_handler, get_funnel_data_handler,
     get_leads_handler, create_lead_handler, get_lead_detail_handler, update_lead_handler, add_follow_up_handler,
@@ -70,7 +139,8 @@ use handlers::{
     get_customer_orders_handler, get_customer_membership_tiers_handler, get_customer_notices_handler,
     get_customer_participant_report_handler,
     // C端认证handlers
-    wechat_login_handler, bind_phone_handler, generate_miniprogram_code_handler,
+    wechat_login_handler, bind_phone_handler, bind_wechat_phone_handler, send_sms_code_handler, generate_miniprogram_code_handler,
+    impersonation_enabled, create_impersonation_handler, list_impersonations_handler,
 };
 
 
@@ -104,13 +174,24 @@ async fn main() {
         .expect("Failed to run database migrations");
     println!("✅ Migrations success!");
 
+    // 后台任务: 期限卡收入确认 & 会员卡续费提醒
+    spawn_revenue_recognition_job(pool.clone());
+    spawn_membership_reminder_job(pool.clone());
+
     let app_state = AppState {
         db_pool: pool,
         jwt_secret,
         ai_api_url,
+        wechat: wechat::wechat_from_env(http_client.clone()),
+        sms: sms::sms_from_env(),
+        invoice: invoice::invoice_from_env(),
+        payment: payment::payment_from_env(http_client.clone()),
         http_client,
     };
 
+    // 后台任务: 关闭过期支付交易 & 每日支付对账
+    spawn_payment_reconciliation_job(app_state.clone());
+
     let cors = CorsLayer::new()
         .allow_origin(allowed_origins)
         .allow_methods([Method::GET, Method::POST, Method::OPTIONS, Method::PUT, Method::DELETE, Method::PATCH])
@@ -122,166 +203,279 @@ async fn main() {
         .allow_credentials(true)
         .max_age(std::time::Duration::from_secs(86400));
 
+    let app = Router::new()
+        .merge(api_routes(app_state.clone()))
+        .nest_service("/uploads", ServeDir::new("uploads"))
+        .layer(cors)
+        .layer(TraceLayer::new_for_http())
+        .with_state(app_state);
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], 8000));
+    tracing::info!("Listening on {}", addr);
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    // 带上对端地址, 短信验证码按 IP 限流时用到
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+}
+
+// 全部业务路由 (不含 CORS / 静态文件), 集成测试直接挂载同一套路由
+fn api_routes(app_state: AppState) -> Router<AppState> {
     let public_routes = Router::new()
         .route("/health/db", get(db_health_handler))
         .route("/health/ai", get(ai_health_handler))
         .route("/api/v1/auth/register", post(register_handler))
         .route("/api/v1/auth/login", post(login_handler))
         .route("/api/v1/auth/wechat-login", post(wechat_login_handler))  // C端微信登录
+        .route("/api/v1/auth/refresh", post(refresh_token_handler))
+        .route("/api/v1/auth/password/reset", post(complete_password_reset_handler)) // 凭一次性令牌设置新密码
         .route("/api/v1/base/generate-miniprogram-code", post(generate_miniprogram_code_handler))
-        .route("/api/v1/verify/:code", get(verify_qrcode_handler));
+        .route("/api/v1/verify/:code", get(verify_qrcode_handler))
+        .route("/api/v1/payments/wechat/notify", post(wechat_pay_notify_handler)); // 微信支付回调, 验签鉴别
 
+    // 每条路由声明所需权限与作用域 (见 src/permissions.rs): hq = 仅总部, base = 仅基地, staff = 总部 / 基地均可
     let protected_routes = Router::new()
-        .route("/api/v1/bases", get(get_hq_bases_handler).post(create_hq_base_handler))
-        .route("/api/v1/bases/:id", axum::routing::put(update_hq_base_handler))
-        .route("/api/v1/asset-types", post(create_asset_type_handler).get(get_asset_types_handler))
-        .route("/api/v1/hq/assets", get(get_all_assets_handler).post(create_asset_handler))
-        .route("/api/v1/hq/assets/:id", axum::routing::delete(delete_asset_handler))
-        .route("/api/v1/hq/assets/:id/transfer", axum::routing::put(transfer_asset_handler))
-        .route("/api/v1/materials", post(create_material_handler).get(get_materials_handler))
-        .route("/api/v1/customers", post(create_customer_handler).get(get_customers_handler))
-        .route("/api/v1/participants", post(create_participant_handler).get(get_participants_handler)) 
-        .route("/api/v1/customers/:id/participants", get(get_participants_for_customer_handler))
-        .route("/api/v1/hq/participants/stats", get(get_hq_participant_stats))
-        .route("/api/v1/base/participants", get(get_base_participants_handler))
-        .route("/api/v1/base/dashboard/overview", get(get_dashboard_overview_handler))
-        .route("/api/v1/base/workspace/overview", get(get_workspace_overview_handler))
-        .route("/api/v1/base/approval/list", get(get_approval_list_handler))
-        .route("/api/v1/base/approval/action", post(handle_approval_action_handler))
-        .route("/api/v1/base/finance/summary", get(get_finance_summary_handler))
-        .route("/api/v1/base/staff/list", get(get_base_staff_list_handler))
-        .route("/api/v1/base/report/stats", get(get_report_stats_handler))
-        .route("/api/v1/base/notice/create", post(create_notice_handler))
-        .route("/api/v1/base/finance/dashboard_data", get(get_base_finance_dashboard_handler))
-        .route("/api/v1/hq/participants", get(get_all_hq_participants))
-        .route("/api/v1/hq/users", get(get_hq_users).post(create_hq_user))
-        .route("/api/v1/hq/users/:id", axum::routing::put(update_user_handler))
-        .route("/api/v1/membership-tiers", post(create_membership_tier_handler).get(get_membership_tiers_handler))
-        .route("/api/v1/customer-memberships", post(assign_membership_handler)) 
-        .route("/api/v1/customers/:id/memberships", get(get_customer_memberships_handler))
-        .route("/api/v1/base/customer-memberships", get(get_base_memberships_handler))
-        .route("/api/v1/membership-tiers/:id/status", axum::routing::patch(toggle_tier_status_handler))
-        .route("/api/v1/courses", post(create_course_handler).get(get_courses_handler))
-        .route("/api/v1/courses/:id", axum::routing::put(update_course_handler))
-        .route("/api/v1/courses/:id/status", axum::routing::patch(toggle_course_status_handler))
-        .route("/api/v1/rooms", get(get_rooms_handler).post(create_room_handler))
-        .route("/api/v1/rooms/:id", axum::routing::put(update_room_handler).delete(delete_room_handler))
-        .route("/api/v1/hq/rooms", get(get_rooms_handler).post(create_room_handler))
-        .route("/api/v1/base/rooms", get(get_rooms_handler))
-        .route("/api/v1/base/teachers", get(get_base_teachers_handler))
-        .route("/api/v1/base/classes", post(create_base_class_handler).get(get_base_classes_handler))
-        .route("/api/v1/base/classes/:id", patch(update_class_handler).delete(delete_class_handler))
-        .route("/api/v1/enrollments", post(create_enrollment_handler))
-        .route("/api/v1/classes/:id/enrollments", get(get_enrollments_for_class_handler))
-        .route("/api/v1/enrollments/:id/complete", patch(complete_enrollment_handler))
-        .route("/api/v1/enrollments/:id", axum::routing::delete(delete_enrollment_handler))
-        .route("/api/v1/honor-ranks", post(create_honor_rank).get(get_honor_ranks))
-        .route("/api/v1/honor-ranks/:id", axum::routing::put(update_honor_rank))
-        .route("/api/v1/base/stock/alerts", get(get_stock_alerts_handler))
-        .route("/api/v1/base/stock", get(get_base_stock_handler))
-        .route("/api/v1/procurements", post(create_procurement_order).get(get_procurement_orders))
-        .route("/api/v1/procurements/:id/items", get(get_procurement_details))
-        .route("/api/v1/procurements/:id/status", axum::routing::put(update_procurement_status))
-        .route("/api/v1/teachers/:id/config", get(get_teacher_config_handler))
-        .route("/api/v1/teachers/:id/skills", axum::routing::put(update_teacher_skills_handler))
-        .route("/api/v1/teachers/:id/availability", post(add_teacher_availability_handler))
-        .route("/api/v1/teachers/availability/:id", axum::routing::delete(delete_teacher_availability_handler))
-        .route("/api/v1/teacher/dashboard", get(get_teacher_dashboard_handler))
-        .route("/api/v1/base/schedule/auto-generate", post(trigger_auto_schedule_handler))
-        .route("/api/v1/finance/payments/verify", post(verify_payment_handler))
+        .route("/api/v1/bases", get(get_hq_bases_handler).require(staff(perm::BASES_READ)))
+        .route("/api/v1/bases", post(create_hq_base_handler).require(hq(perm::BASES_MANAGE)))
+        .route("/api/v1/bases/:id", put(update_hq_base_handler).require(hq(perm::BASES_MANAGE)))
+        .route("/api/v1/asset-types", get(get_asset_types_handler).require(staff(perm::ASSETS_READ)))
+        .route("/api/v1/asset-types", post(create_asset_type_handler).require(hq(perm::ASSETS_MANAGE)))
+        .route("/api/v1/hq/assets", get(get_all_assets_handler).require(hq(perm::ASSETS_READ)))
+        .route("/api/v1/hq/assets", post(create_asset_handler).require(hq(perm::ASSETS_MANAGE)))
+        .route("/api/v1/hq/assets/:id", delete(delete_asset_handler).require(hq(perm::ASSETS_MANAGE)))
+        .route("/api/v1/hq/assets/:id/transfer", put(transfer_asset_handler).require(hq(perm::ASSETS_MANAGE)))
+        .route("/api/v1/materials", get(get_materials_handler).require(staff(perm::MATERIALS_READ)))
+        .route("/api/v1/materials", post(create_material_handler).require(hq(perm::MATERIALS_MANAGE)))
+        .route("/api/v1/customers", get(get_customers_handler).require(staff(perm::CUSTOMERS_READ)))
+        .route("/api/v1/customers", post(create_customer_handler).require(staff(perm::CUSTOMERS_MANAGE)))
+        .route("/api/v1/participants", get(get_participants_handler).require(staff(perm::CUSTOMERS_READ)))
+        .route("/api/v1/participants", post(create_participant_handler).require(staff(perm::CUSTOMERS_MANAGE)))
+        .route("/api/v1/customers/:id/participants", get(get_participants_for_customer_handler).require(staff(perm::CUSTOMERS_READ)))
+        .route("/api/v1/hq/participants/stats", get(get_hq_participant_stats).require(hq(perm::REPORTS_HQ)))
+        .route("/api/v1/base/participants", get(get_base_participants_handler).require(base(perm::CUSTOMERS_READ)))
+        .route("/api/v1/base/dashboard/overview", get(get_dashboard_overview_handler).require(base(perm::WORKSPACE_READ)))
+        .route("/api/v1/base/workspace/overview", get(get_workspace_overview_handler).require(base(perm::WORKSPACE_READ)))
+        .route("/api/v1/base/workspace/renewal-reminders", get(get_renewal_reminders_handler).require(base(perm::MEMBERSHIPS_READ)))
+        .route("/api/v1/base/workspace/renewal-reminders/:id/follow-up", post(follow_up_reminder_handler).require(base(perm::MEMBERSHIPS_MANAGE)))
+        .route("/api/v1/base/approval/list", get(get_approval_list_handler).require(base(perm::APPROVALS_MANAGE)))
+        .route("/api/v1/base/approval/action", post(handle_approval_action_handler).require(base(perm::APPROVALS_MANAGE)))
+        .route("/api/v1/base/finance/summary", get(get_finance_summary_handler).require(base(perm::FINANCE_READ)))
+        .route("/api/v1/base/staff/list", get(get_base_staff_list_handler).require(base(perm::STAFF_READ)))
+        .route("/api/v1/base/report/stats", get(get_report_stats_handler).require(base(perm::WORKSPACE_READ)))
+        .route("/api/v1/base/notice/create", post(create_notice_handler).require(base(perm::NOTICES_MANAGE)))
+        .route("/api/v1/base/finance/dashboard_data", get(get_base_finance_dashboard_handler).require(base(perm::FINANCE_READ)))
+        .route("/api/v1/hq/participants", get(get_all_hq_participants).require(hq(perm::CUSTOMERS_READ)))
+        .route("/api/v1/hq/users", get(get_hq_users).require(staff(perm::STAFF_READ)))
+        .route("/api/v1/hq/users", post(create_hq_user).require(staff(perm::STAFF_MANAGE)))
+        .route("/api/v1/hq/users/:id", put(update_user_handler).require(staff(perm::STAFF_MANAGE)))
+        .route("/api/v1/hq/users/:id/status", patch(update_user_status_handler).require(staff(perm::STAFF_MANAGE)))
+        .route("/api/v1/hq/users/:id/password-reset", post(reset_user_password_handler).require(staff(perm::STAFF_MANAGE)))
+        .route("/api/v1/hq/users/:id/unlock", post(unlock_user_login_handler).require(staff(perm::STAFF_MANAGE)))
+        .route("/api/v1/membership-tiers", get(get_membership_tiers_handler).require(staff(perm::MEMBERSHIPS_READ)))
+        .route("/api/v1/membership-tiers", post(create_membership_tier_handler).require(hq(perm::TIERS_MANAGE)))
+        .route("/api/v1/customer-memberships", post(assign_membership_handler).require(base(perm::MEMBERSHIPS_MANAGE)))
+        // --- 会员卡生命周期 ---
+        .route("/api/v1/customer-memberships/:id/freeze", post(freeze_membership_handler).require(base(perm::MEMBERSHIPS_MANAGE)))
+        .route("/api/v1/customer-memberships/:id/unfreeze", post(unfreeze_membership_handler).require(base(perm::MEMBERSHIPS_MANAGE)))
+        .route("/api/v1/customer-memberships/:id/extend", post(extend_membership_handler).require(base(perm::MEMBERSHIPS_MANAGE)))
+        .route("/api/v1/customer-memberships/:id/transfer", post(transfer_membership_handler).require(base(perm::MEMBERSHIPS_MANAGE)))
+        .route("/api/v1/customer-memberships/:id/refund", post(refund_membership_handler).require(base(perm::MEMBERSHIPS_REFUND)))
+        .route("/api/v1/customer-memberships/:id/adjustments", get(get_membership_adjustments_handler).require(staff(perm::MEMBERSHIPS_READ)))
+        .route("/api/v1/customers/:id/memberships", get(get_customer_memberships_handler).require(staff(perm::MEMBERSHIPS_READ)))
+        .route("/api/v1/base/customer-memberships", get(get_base_memberships_handler).require(base(perm::MEMBERSHIPS_READ)))
+        .route("/api/v1/membership-tiers/:id/status", patch(toggle_tier_status_handler).require(hq(perm::TIERS_MANAGE)))
+        .route("/api/v1/membership-tiers/:id/recognition", patch(update_tier_recognition_handler).require(hq(perm::TIERS_MANAGE)))
+        .route("/api/v1/finance/revenue-recognition/run", post(run_revenue_recognition_handler).require(hq(perm::FINANCE_MANAGE)))
+        .route("/api/v1/courses", get(get_courses_handler).require(staff(perm::COURSES_READ)))
+        .route("/api/v1/courses", post(create_course_handler).require(hq(perm::COURSES_MANAGE)))
+        .route("/api/v1/courses/:id", put(update_course_handler).require(hq(perm::COURSES_MANAGE)))
+        .route("/api/v1/courses/:id/status", patch(toggle_course_status_handler).require(hq(perm::COURSES_MANAGE)))
+        .route("/api/v1/rooms", get(get_rooms_handler).require(staff(perm::ROOMS_READ)))
+        .route("/api/v1/rooms", post(create_room_handler).require(staff(perm::ROOMS_MANAGE)))
+        .route("/api/v1/rooms/:id", put(update_room_handler).require(staff(perm::ROOMS_MANAGE)))
+        .route("/api/v1/rooms/:id", delete(delete_room_handler).require(staff(perm::ROOMS_MANAGE)))
+        .route("/api/v1/hq/rooms", get(get_rooms_handler).require(hq(perm::ROOMS_READ)))
+        .route("/api/v1/hq/rooms", post(create_room_handler).require(hq(perm::ROOMS_MANAGE)))
+        .route("/api/v1/base/rooms", get(get_rooms_handler).require(base(perm::ROOMS_READ)))
+        .route("/api/v1/base/teachers", get(get_base_teachers_handler).require(base(perm::SCHEDULE_READ)))
+        .route("/api/v1/base/classes", get(get_base_classes_handler).require(base(perm::SCHEDULE_READ)))
+        .route("/api/v1/base/classes", post(create_base_class_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/classes/:id", patch(update_class_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/classes/:id", delete(delete_class_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/class-series", get(get_class_series_handler).require(base(perm::SCHEDULE_READ)))
+        .route("/api/v1/base/class-series", post(create_class_series_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/class-series/:id/occurrences/:class_id", patch(update_series_occurrence_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/class-series/:id/exclusions", post(add_series_exclusion_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/class-series/:id/extend", post(extend_class_series_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/class-series/:id/enrollments", post(enroll_series_handler).require(base(perm::ENROLLMENTS_MANAGE)))
+        .route("/api/v1/base/substitutions", get(get_substitutions_handler).require(base(perm::SCHEDULE_READ)))
+        .route("/api/v1/base/substitutions/:id/candidates", get(get_substitute_candidates_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/substitutions/:id/assign", post(assign_substitute_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/enrollments", post(create_enrollment_handler).require(base(perm::ENROLLMENTS_MANAGE)))
+        .route("/api/v1/classes/:id/enrollments", get(get_enrollments_for_class_handler).require(base(perm::SCHEDULE_READ)))
+        .route("/api/v1/classes/:id/waitlist", get(get_class_waitlist_handler).require(base(perm::SCHEDULE_READ)))
+        .route("/api/v1/waitlist/:id", delete(cancel_waitlist_entry_handler).require(base(perm::ENROLLMENTS_MANAGE)))
+        .route("/api/v1/enrollments/:id/complete", patch(complete_enrollment_handler).require(base(perm::ATTENDANCE_RECORD)))
+        .route("/api/v1/enrollments/:id", delete(delete_enrollment_handler).require(base(perm::ENROLLMENTS_MANAGE)))
+        .route("/api/v1/honor-ranks", get(get_honor_ranks).require(staff(perm::HONOR_READ)))
+        .route("/api/v1/honor-ranks", post(create_honor_rank).require(hq(perm::HONOR_MANAGE)))
+        .route("/api/v1/honor-ranks/:id", put(update_honor_rank).require(hq(perm::HONOR_MANAGE)))
+        .route("/api/v1/base/stock/alerts", get(get_stock_alerts_handler).require(base(perm::STOCK_READ)))
+        .route("/api/v1/base/stock", get(get_base_stock_handler).require(base(perm::STOCK_READ)))
+        .route("/api/v1/procurements", get(get_procurement_orders).require(staff(perm::PROCUREMENT_MANAGE)))
+        .route("/api/v1/procurements", post(create_procurement_order).require(base(perm::PROCUREMENT_MANAGE)))
+        .route("/api/v1/procurements/:id/items", get(get_procurement_details).require(staff(perm::PROCUREMENT_MANAGE)))
+        .route("/api/v1/procurements/:id/status", put(update_procurement_status).require(staff(perm::PROCUREMENT_MANAGE)))
+        .route("/api/v1/teachers/:id/config", get(get_teacher_config_handler).require(base(perm::SCHEDULE_READ)))
+        .route("/api/v1/teachers/:id/skills", put(update_teacher_skills_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/teachers/:id/availability", post(add_teacher_availability_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/teachers/availability/:id", delete(delete_teacher_availability_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/teacher/dashboard", get(get_teacher_dashboard_handler).require(base(perm::TEACHING_SELF)))
+        .route("/api/v1/base/schedule/auto-generate", post(trigger_auto_schedule_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/schedule/drafts", get(get_schedule_drafts_handler).require(base(perm::SCHEDULE_READ)))
+        .route("/api/v1/base/schedule/drafts", post(create_schedule_draft_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/schedule/drafts/:id", get(get_schedule_draft_handler).require(base(perm::SCHEDULE_READ)))
+        .route("/api/v1/base/schedule/drafts/:id", delete(discard_schedule_draft_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/schedule/drafts/:id/items/:item_id", patch(update_schedule_draft_item_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/schedule/drafts/:id/items/:item_id", delete(delete_schedule_draft_item_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/base/schedule/drafts/:id/commit", post(commit_schedule_draft_handler).require(base(perm::SCHEDULE_MANAGE)))
+        .route("/api/v1/finance/payments/verify", post(verify_payment_handler).require(staff(perm::FINANCE_MANAGE)))
         // 1. 收入订单
-        .route("/api/v1/finance/orders", post(create_income_order_handler).get(get_income_orders_handler))
-        .route("/api/v1/finance/orders/:id", put(update_income_order_handler))
-        .route("/api/v1/finance/orders/:id/cancel", put(cancel_income_order_handler))
+        .route("/api/v1/finance/orders", get(get_income_orders_handler).require(base(perm::FINANCE_READ)))
+        .route("/api/v1/finance/orders", post(create_income_order_handler).require(base(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/orders/:id", put(update_income_order_handler).require(base(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/orders/:id/cancel", put(cancel_income_order_handler).require(base(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/orders/:id/refunds", get(get_order_refunds_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/orders/:id/refunds", post(create_order_refund_handler).require(base(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/orders/:id/installments", get(get_order_installments_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/orders/:id/installments", put(set_order_installments_handler).require(base(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/receivables/aging", get(get_receivables_aging_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/orders/:id/invoices", get(get_order_invoices_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/orders/:id/invoices", post(create_order_invoice_handler).require(staff(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/invoices/reconciliation", get(get_invoice_reconciliation_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/invoices/:id/issue", post(issue_invoice_handler).require(staff(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/invoices/:id/cancel", post(cancel_invoice_handler).require(staff(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/invoices/:id/reverse", post(reverse_invoice_handler).require(staff(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/invoices/:id/pdf", get(download_invoice_pdf_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/invoices/:id/xml", get(download_invoice_xml_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/online-payments", get(get_online_payments_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/online-payments/:id/sync", post(sync_online_payment_handler).require(staff(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/payment-reconciliations", get(get_payment_reconciliations_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/payment-reconciliations/run", post(run_payment_reconciliation_handler).require(hq(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/payment-reconciliations/:date", get(get_payment_reconciliation_handler).require(staff(perm::FINANCE_READ)))
         // 2. 运营支出 (房租/工资)
-        .route("/api/v1/finance/expenses", post(create_expense_handler).get(get_expenses_handler))
+        .route("/api/v1/finance/expenses", get(get_expenses_handler).require(base(perm::FINANCE_READ)))
+        .route("/api/v1/finance/expenses", post(create_expense_handler).require(base(perm::FINANCE_MANAGE)))
         
-        .route("/api/v1/finance/orders/:id/items", get(get_order_items_handler))
-        .route("/api/v1/finance/orders/:id/invoice", put(update_invoice_status_handler))
+        .route("/api/v1/finance/orders/:id/items", get(get_order_items_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/orders/:id/invoice", put(update_invoice_status_handler).require(staff(perm::FINANCE_MANAGE)))
 
         // 3. 资金确认
-        .route("/api/v1/finance/payments", get(get_payment_records_handler).post(submit_payment_proof_handler))
-        .route("/api/v1/finance/payments/:id/verify", put(verify_payment_handler))
+        .route("/api/v1/finance/payments", get(get_payment_records_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/finance/payments", post(submit_payment_proof_handler).require(base(perm::FINANCE_MANAGE)))
+        .route("/api/v1/finance/payments/:id/verify", put(verify_payment_handler).require(staff(perm::FINANCE_MANAGE)))
+        // 4. 总账: 科目 / 凭证 / 结账 / 报表
+        .route("/api/v1/ledger/accounts", get(get_ledger_accounts_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/hq/ledger/accounts", post(create_ledger_account_handler).require(hq(perm::FINANCE_MANAGE)))
+        .route("/api/v1/hq/ledger/accounts/:id", put(update_ledger_account_handler).require(hq(perm::FINANCE_MANAGE)))
+        .route("/api/v1/ledger/entries", get(get_journal_entries_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/ledger/entries", post(create_journal_entry_handler).require(staff(perm::FINANCE_MANAGE)))
+        .route("/api/v1/hq/ledger/periods", get(get_accounting_periods_handler).require(hq(perm::FINANCE_READ)))
+        .route("/api/v1/hq/ledger/periods/:period/close", post(close_accounting_period_handler).require(hq(perm::LEDGER_CLOSE)))
+        .route("/api/v1/hq/ledger/periods/:period/reopen", post(reopen_accounting_period_handler).require(hq(perm::LEDGER_CLOSE)))
+        .route("/api/v1/ledger/trial-balance", get(get_trial_balance_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/ledger/income-statement", get(get_income_statement_handler).require(staff(perm::FINANCE_READ)))
+        .route("/api/v1/ledger/balance-sheet", get(get_balance_sheet_handler).require(staff(perm::FINANCE_READ)))
         // --- 供应链: 基地端 ---
-        .route("/api/v1/supply/products", get(get_hq_products_handler).post(create_product_handler))
-        .route("/api/v1/supply/products/:id", put(update_product_handler))
-        .route("/api/v1/supply/orders",  post(create_supply_order_handler).get(get_base_supply_orders_handler))
-        .route("/api/v1/supply/orders/:id/payment", post(upload_payment_proof_handler))
-        .route("/api/v1/base/inventory", get(get_base_inventory_handler))
-        .route("/api/v1/base/inventory/:id/consume", post(consume_inventory_handler))
-        .route("/api/v1/base/inventory/logs", get(get_inventory_logs_handler))
-        .route("/api/v1/supply/orders/:id/receive", put(receive_supply_order_handler))
-        .route("/api/v1/base/inventory/:id/restock", post(restock_inventory_handler))
+        .route("/api/v1/supply/products", get(get_hq_products_handler).require(staff(perm::SUPPLY_READ)))
+        .route("/api/v1/supply/products", post(create_product_handler).require(hq(perm::SUPPLY_MANAGE)))
+        .route("/api/v1/supply/products/:id", put(update_product_handler).require(hq(perm::SUPPLY_MANAGE)))
+        .route("/api/v1/supply/orders", get(get_base_supply_orders_handler).require(base(perm::SUPPLY_READ)))
+        .route("/api/v1/supply/orders", post(create_supply_order_handler).require(base(perm::SUPPLY_ORDER)))
+        .route("/api/v1/supply/orders/:id/payment", post(upload_payment_proof_handler).require(base(perm::SUPPLY_ORDER)))
+        .route("/api/v1/base/inventory", get(get_base_inventory_handler).require(base(perm::STOCK_READ)))
+        .route("/api/v1/base/inventory/:id/consume", post(consume_inventory_handler).require(base(perm::INVENTORY_MANAGE)))
+        .route("/api/v1/base/inventory/logs", get(get_inventory_logs_handler).require(base(perm::STOCK_READ)))
+        .route("/api/v1/supply/orders/:id/receive", put(receive_supply_order_handler).require(base(perm::SUPPLY_ORDER)))
+        .route("/api/v1/base/inventory/:id/restock", post(restock_inventory_handler).require(base(perm::INVENTORY_MANAGE)))
         
         // --- 供应链: 总部端 (HQ) ---
-        .route("/api/v1/hq/dashboard/stats", get(get_hq_dashboard_stats_handler))
-        .route("/api/v1/hq/dashboard/analytics", get(get_hq_dashboard_analytics_handler))
-        .route("/api/v1/hq/dashboard/pending-staff", get(get_hq_dashboard_pending_staff_handler))
-        .route("/api/v1/hq/supply/orders", get(get_all_supply_orders_handler))
-        .route("/api/v1/hq/supply/orders/:id/confirm", put(confirm_supply_payment_handler))
-        .route("/api/v1/hq/supply/orders/:id/ship", put(ship_supply_order_handler))
+        .route("/api/v1/hq/dashboard/stats", get(get_hq_dashboard_stats_handler).require(hq(perm::REPORTS_HQ)))
+        .route("/api/v1/hq/dashboard/analytics", get(get_hq_dashboard_analytics_handler).require(hq(perm::REPORTS_HQ)))
+        .route("/api/v1/hq/dashboard/pending-staff", get(get_hq_dashboard_pending_staff_handler).require(hq(perm::STAFF_READ)))
+        .route("/api/v1/hq/supply/orders", get(get_all_supply_orders_handler).require(hq(perm::SUPPLY_MANAGE)))
+        .route("/api/v1/hq/supply/orders/:id/confirm", put(confirm_supply_payment_handler).require(hq(perm::SUPPLY_MANAGE)))
+        .route("/api/v1/hq/supply/orders/:id/ship", put(ship_supply_order_handler).require(hq(perm::SUPPLY_MANAGE)))
 
-        .route("/api/v1/hq/finance/dashboard", get(get_hq_finance_dashboard_handler))
+        .route("/api/v1/hq/finance/dashboard", get(get_hq_finance_dashboard_handler).require(hq(perm::FINANCE_READ)))
 
-        .route("/api/v1/upload", post(upload_file_handler))
+        .route("/api/v1/upload", post(upload_file_handler).require(authenticated()))
+        .route("/api/v1/auth/logout", post(logout_handler).require(authenticated()))
+        .route("/api/v1/auth/password", post(change_password_handler).require(authenticated()))
         
         // --- Staff/Personnel Risk Control ---
-        .route("/api/v1/hq/staff/risk-stats", get(get_staff_risk_stats_handler))
-        .route("/api/v1/hq/staff/key-personnel", get(get_key_personnel_handler))
-        .route("/api/v1/hq/staff/rankings/purchase", get(get_purchase_rankings_handler))
-        .route("/api/v1/hq/staff/rankings/activity", get(get_activity_rankings_handler))
+        .route("/api/v1/hq/staff/risk-stats", get(get_staff_risk_stats_handler).require(hq(perm::REPORTS_HQ)))
+        .route("/api/v1/hq/staff/key-personnel", get(get_key_personnel_handler).require(hq(perm::REPORTS_HQ)))
+        .route("/api/v1/hq/staff/locked-accounts", get(get_locked_accounts_handler).require(hq(perm::REPORTS_HQ)))
+        .route("/api/v1/hq/staff/rankings/purchase", get(get_purchase_rankings_handler).require(hq(perm::REPORTS_HQ)))
+        .route("/api/v1/hq/staff/rankings/activity", get(get_activity_rankings_handler).require(hq(perm::REPORTS_HQ)))
         
         // --- Data Reports ---
-        .route("/api/v1/hq/reports/top-products", get(get_top_products_handler))
-        .route("/api/v1/hq/reports/order-trend", get(get_order_trend_handler))
-        .route("/api/v1/hq/reports/funnel", get(get_funnel_data_handler))
+        .route("/api/v1/hq/reports/top-products", get(get_top_products_handler).require(hq(perm::REPORTS_HQ)))
+        .route("/api/v1/hq/reports/order-trend", get(get_order_trend_handler).require(hq(perm::REPORTS_HQ)))
+        .route("/api/v1/hq/reports/funnel", get(get_funnel_data_handler).require(hq(perm::REPORTS_HQ)))
         
         // --- Base: Lead Management ---
-        .route("/api/v1/base/leads", get(get_leads_handler))
-        .route("/api/v1/base/leads", post(create_lead_handler))
-        .route("/api/v1/base/leads/:id", get(get_lead_detail_handler))
-        .route("/api/v1/base/leads/:id", put(update_lead_handler))
-        .route("/api/v1/base/leads/:id/follow-up", post(add_follow_up_handler))
+        .route("/api/v1/base/leads", get(get_leads_handler).require(base(perm::LEADS_MANAGE)))
+        .route("/api/v1/base/leads", post(create_lead_handler).require(base(perm::LEADS_MANAGE)))
+        .route("/api/v1/base/leads/:id", get(get_lead_detail_handler).require(base(perm::LEADS_MANAGE)))
+        .route("/api/v1/base/leads/:id", put(update_lead_handler).require(base(perm::LEADS_MANAGE)))
+        .route("/api/v1/base/leads/:id/follow-up", post(add_follow_up_handler).require(base(perm::LEADS_MANAGE)))
         
         // --- Base: Trial Class Management ---
-        .route("/api/v1/base/trial-classes", get(get_trial_classes_handler))
-        .route("/api/v1/base/trial-classes", post(create_trial_class_handler))
-        .route("/api/v1/base/trial-classes/:id", get(get_trial_class_handler))
-        .route("/api/v1/base/trial-classes/:id", put(update_trial_class_handler))
-        .route("/api/v1/base/trial-classes/:id/feedback", post(add_trial_class_feedback_handler))
+        .route("/api/v1/base/trial-classes", get(get_trial_classes_handler).require(base(perm::TRIALS_MANAGE)))
+        .route("/api/v1/base/trial-classes", post(create_trial_class_handler).require(base(perm::TRIALS_MANAGE)))
+        .route("/api/v1/base/trial-classes/:id", get(get_trial_class_handler).require(base(perm::TRIALS_MANAGE)))
+        .route("/api/v1/base/trial-classes/:id", put(update_trial_class_handler).require(base(perm::TRIALS_MANAGE)))
+        .route("/api/v1/base/trial-classes/:id/feedback", post(add_trial_class_feedback_handler).require(base(perm::TRIALS_MANAGE)))
         
         // --- C-End Customer APIs ---
-        .route("/api/v1/customer/profile", get(get_customer_profile_handler))
-        .route("/api/v1/customer/schedule", get(get_customer_schedule_handler))
-        .route("/api/v1/customer/course-balance", get(get_course_balance_handler))
-        .route("/api/v1/customer/honor", get(get_customer_honor_handler))
-        .route("/api/v1/customer/points-history", get(get_points_history_handler))
-        .route("/api/v1/customer/bind-phone", post(bind_phone_handler))
-        .route("/api/v1/customer/orders", get(get_customer_orders_handler))
-        .route("/api/v1/customer/membership-tiers", get(get_customer_membership_tiers_handler))
-        .route("/api/v1/customer/notices", get(get_customer_notices_handler))
-        .route("/api/v1/customer/report", get(get_customer_participant_report_handler))
+        .route("/api/v1/customer/profile", get(get_customer_profile_handler).require(customer()))
+        .route("/api/v1/customer/schedule", get(get_customer_schedule_handler).require(customer()))
+        .route("/api/v1/customer/course-balance", get(get_course_balance_handler).require(customer()))
+        .route("/api/v1/customer/honor", get(get_customer_honor_handler).require(customer()))
+        .route("/api/v1/customer/points-history", get(get_points_history_handler).require(customer()))
+        .route("/api/v1/customer/sms-code", post(send_sms_code_handler).require(customer()))
+        .route("/api/v1/customer/bind-phone", post(bind_phone_handler).require(customer()))
+        .route("/api/v1/customer/wechat-phone", post(bind_wechat_phone_handler).require(customer()))
+        .route("/api/v1/customer/orders", get(get_customer_orders_handler).require(customer()))
+        .route("/api/v1/customer/orders/:id/pay", post(create_order_payment_handler).require(customer()))
+        .route("/api/v1/customer/memberships/purchase", post(purchase_membership_handler).require(customer()))
+        .route("/api/v1/customer/payments/:id", get(get_customer_payment_handler).require(customer()))
+        .route("/api/v1/customer/payments/:id/simulate", post(simulate_customer_payment_handler).require(customer()))
+        .route("/api/v1/customer/membership-tiers", get(get_customer_membership_tiers_handler).require(customer()))
+        .route("/api/v1/customer/notices", get(get_customer_notices_handler).require(customer()))
+        .route("/api/v1/customer/notifications", get(get_customer_notifications_handler).require(customer()))
+        .route("/api/v1/customer/notifications/:id/read", patch(mark_customer_notification_read_handler).require(customer()))
+        .route("/api/v1/customer/report", get(get_customer_participant_report_handler).require(customer()))
         
-        .route("/api/v1/hq/qrcodes/generate", post(generate_qrcodes_handler))
+        .route("/api/v1/hq/qrcodes/generate", post(generate_qrcodes_handler).require(hq(perm::QRCODES_MANAGE)))
 
-        .route("/api/v1/admin/qrcodes/:batch_id/export", get(export_batch_csv_handler))
-        .route("/api/v1/admin/qrcodes/batches", get(list_batches_handler))
-        .route("/api/v1/admin/qrcodes/:batch_id/activate", post(activate_batch_handler))
+        .route("/api/v1/admin/qrcodes/:batch_id/export", get(export_batch_csv_handler).require(hq(perm::QRCODES_MANAGE)))
+        .route("/api/v1/admin/qrcodes/batches", get(list_batches_handler).require(hq(perm::QRCODES_MANAGE)))
+        .route("/api/v1/admin/qrcodes/:batch_id/activate", post(activate_batch_handler).require(hq(perm::QRCODES_MANAGE)))
 
-        
-        // ★ 修复: 使用 axum::middleware::from_fn 调用，而不是 middleware::from_fn
-        .route_layer(axum::middleware::from_fn(auth_middleware));
+        // --- 开发调试: 模拟登录 (审计记录始终可查, 签发接口仅在 DEV_IMPERSONATION=true 时注册) ---
+        .route("/api/v1/hq/impersonations", get(list_impersonations_handler).require(hq(perm::USERS_IMPERSONATE)));
 
-    let app = Router::new()
-        .merge(public_routes)
-        .merge(protected_routes)
-        .nest_service("/uploads", ServeDir::new("uploads"))
-        .layer(cors)
-        .layer(TraceLayer::new_for_http())
-        .with_state(app_state);
+    let protected_routes = if impersonation_enabled() {
+        tracing::warn!("DEV_IMPERSONATION is enabled: HQ admins can mint tokens for other accounts");
+        protected_routes.route("/api/v1/hq/impersonations", post(create_impersonation_handler).require(hq(perm::USERS_IMPERSONATE)))
+    } else {
+        protected_routes
+    };
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8000));
-    tracing::info!("Listening on {}", addr);
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
-}
\ No newline at end of file
+    // ★ 修复: 使用 axum::middleware::from_fn 调用，而不是 middleware::from_fn
+    let protected_routes =
+        protected_routes.route_layer(axum::middleware::from_fn_with_state(app_state, auth_middleware));
+
+    public_routes.merge(protected_routes)
+}
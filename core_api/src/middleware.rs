@@ -1,21 +1,29 @@
 /*
  * src/middleware.rs
  * 职责: 拦截所有请求，校验 JWT Token，并将用户信息注入到请求上下文中
+ * 另: client_ip 取请求来源 IP, 供短信验证码 / 登录限流使用
+ *     来自受信任代理 (TRUSTED_PROXIES) 的请求才采信 X-Forwarded-For
  */
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap},
     middleware::Next,
     response::Response,
 };
 use jsonwebtoken::{decode, DecodingKey, Validation};
-use uuid::Uuid;
+use crate::error::AppError;
+use crate::handlers::{session_is_active, AppState};
 use crate::models::Claims;
+use crate::permissions::load_grants;
 
 pub async fn auth_middleware(
+    State(state): State<AppState>,
     mut req: Request,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, AppError> {
     // 1. 获取 Authorization Header
     let auth_header = req.headers()
         .get(header::AUTHORIZATION)
@@ -24,32 +32,16 @@ pub async fn auth_middleware(
     let auth_header = if let Some(auth_header) = auth_header {
         auth_header
     } else {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err(AppError::Unauthorized("auth.unauthorized"));
     };
 
     // 2. 检查 "Bearer " 前缀
     if !auth_header.starts_with("Bearer ") {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err(AppError::Unauthorized("auth.unauthorized"));
     }
 
     let token = &auth_header[7..];
 
-    // 2.5 开发环境"后门"：支持 dev_token_ 直接绕过 JWT 校检
-    if token.starts_with("dev_token_") {
-        let claims = Claims {
-            sub: "02352317-d905-4429-9bc7-577e4907660c".to_string(), // 李希圣的ID
-            roles: vec!["CONSUMER".to_string()],
-            hq_id: Uuid::parse_str("dc53fe5d-1212-4259-8350-bb443df1717e").unwrap(),
-            base_id: Some(Uuid::parse_str("841e6e10-4507-467e-af42-ebbcff2dbb6e").unwrap()),
-            base_name: Some("哑巴湖基地".to_string()),
-            base_logo: None,
-            full_name: "测试用户(李希圣)".to_string(),
-            exp: 9999999999,
-        };
-        req.extensions_mut().insert(claims);
-        return Ok(next.run(req).await);
-    }
-
     // 3. 解析 Token
     let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "secret".to_string());
     
@@ -61,12 +53,164 @@ pub async fn auth_middleware(
         token,
         &DecodingKey::from_secret(secret.as_bytes()),
         &validation,
-    ).map_err(|_| StatusCode::UNAUTHORIZED)?; // Token 过期或无效
+    ).map_err(|_| AppError::Unauthorized("auth.token_invalid"))?; // Token 过期或无效
 
-    // 4. 将 Claims 放入请求扩展中 (Extensions)
+    // 4. 会话已退出 / 被吊销 (员工停用、改密码) 时, 未过期的 Token 也立即失效
+    let active = session_is_active(&state.db_pool, token_data.claims.sid)
+        .await
+        .map_err(AppError::db("Failed to check session"))?;
+    if !active {
+        return Err(AppError::Unauthorized("auth.session_revoked"));
+    }
+
+    // 5. 加载该用户角色对应的权限集, 供路由上的 .require(...) 校验
+    let grants = load_grants(&state.db_pool, &token_data.claims)
+        .await
+        .map_err(AppError::db("Failed to load permissions"))?;
+
+    // 6. 将 Claims 放入请求扩展中 (Extensions)
     // 这一步至关重要，后续的 Handler 通过 Claims::from_request_parts 获取数据
     req.extensions_mut().insert(token_data.claims);
+    req.extensions_mut().insert(grants);
 
-    // 5. 放行
+    // 7. 放行
     Ok(next.run(req).await)
-}
\ No newline at end of file
+}
+
+// --- 来源 IP ---
+
+// 受信任的代理网段, 如 "10.0.0.5,172.16.0.0/12"
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ProxyNet {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl ProxyNet {
+    fn parse(s: &str) -> Option<Self> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u32>().ok()?)),
+            None => (s.parse::<IpAddr>().ok()?, None),
+        };
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(bits);
+        (prefix <= bits).then_some(ProxyNet { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let (net, ip, bits) = match (self.addr, ip) {
+            (IpAddr::V4(n), IpAddr::V4(i)) => (u32::from(n) as u128, u32::from(i) as u128, 32),
+            (IpAddr::V6(n), IpAddr::V6(i)) => (u128::from(n), u128::from(i), 128),
+            _ => return false,
+        };
+        let shift = bits - self.prefix;
+        shift >= bits || (net >> shift) == (ip >> shift)
+    }
+}
+
+// 启动后首次使用时读取一次; 无法解析的条目记日志后忽略
+fn trusted_proxies() -> &'static [ProxyNet] {
+    static PROXIES: OnceLock<Vec<ProxyNet>> = OnceLock::new();
+    PROXIES.get_or_init(|| {
+        std::env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| {
+                let net = ProxyNet::parse(s);
+                if net.is_none() {
+                    tracing::warn!("Ignoring invalid TRUSTED_PROXIES entry: {}", s);
+                }
+                net
+            })
+            .collect()
+    })
+}
+
+// X-Forwarded-For 从右往左跳过受信任代理, 第一个其他地址即客户端
+// 代理没有转发 / 转发的地址无法解析时返回 None
+fn forwarded_client_ip(headers: &HeaderMap, trusted: &[ProxyNet]) -> Option<IpAddr> {
+    let hops: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .collect();
+    for hop in hops.into_iter().rev() {
+        let ip = hop.parse::<IpAddr>().ok()?;
+        if !trusted.iter().any(|net| net.contains(ip)) {
+            return Some(ip);
+        }
+    }
+    None
+}
+
+// 直连时用对端地址; 对端是受信任代理 (如 web_admin 的 NextAuth 服务端) 时取其转发的客户端地址,
+// 代理没带客户端地址就返回 None, 避免所有员工共用代理的 IP 被一起限流
+// 没有连接信息 (如集成测试) 时也返回 None, 调用方跳过按 IP 的限制
+pub fn client_ip(connect: Option<ConnectInfo<SocketAddr>>, headers: &HeaderMap) -> Option<String> {
+    let ConnectInfo(addr) = connect?;
+    resolve_client_ip(addr.ip(), headers, trusted_proxies()).map(|ip| ip.to_string())
+}
+
+fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[ProxyNet]) -> Option<IpAddr> {
+    if !trusted.iter().any(|net| net.contains(peer)) {
+        return Some(peer);
+    }
+    forwarded_client_ip(headers, trusted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nets(list: &[&str]) -> Vec<ProxyNet> {
+        list.iter().map(|s| ProxyNet::parse(s).unwrap()).collect()
+    }
+
+    fn xff(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn proxy_net_matches_single_address_and_cidr() {
+        assert!(ProxyNet::parse("10.0.0.5").unwrap().contains(ip("10.0.0.5")));
+        assert!(!ProxyNet::parse("10.0.0.5").unwrap().contains(ip("10.0.0.6")));
+        assert!(ProxyNet::parse("172.16.0.0/12").unwrap().contains(ip("172.31.255.1")));
+        assert!(!ProxyNet::parse("172.16.0.0/12").unwrap().contains(ip("172.32.0.1")));
+        assert!(ProxyNet::parse("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+        assert!(!ProxyNet::parse("10.0.0.0/8").unwrap().contains(ip("::1")));
+        assert!(ProxyNet::parse("10.0.0.0/33").is_none());
+        assert!(ProxyNet::parse("web_admin").is_none());
+    }
+
+    #[test]
+    fn direct_requests_ignore_forwarded_header() {
+        let trusted = nets(&["10.0.0.5"]);
+        assert_eq!(resolve_client_ip(ip("203.0.113.9"), &xff("1.2.3.4"), &trusted), Some(ip("203.0.113.9")));
+    }
+
+    #[test]
+    fn trusted_proxy_uses_rightmost_untrusted_hop() {
+        let trusted = nets(&["10.0.0.0/8"]);
+        // 客户端自己伪造的最左一项不被采信
+        let headers = xff("6.6.6.6, 198.51.100.7, 10.0.0.9");
+        assert_eq!(resolve_client_ip(ip("10.0.0.5"), &headers, &trusted), Some(ip("198.51.100.7")));
+    }
+
+    #[test]
+    fn trusted_proxy_without_client_address_skips_ip_limit() {
+        let trusted = nets(&["10.0.0.5"]);
+        assert_eq!(resolve_client_ip(ip("10.0.0.5"), &HeaderMap::new(), &trusted), None);
+        assert_eq!(resolve_client_ip(ip("10.0.0.5"), &xff("10.0.0.5"), &trusted), None);
+        assert_eq!(resolve_client_ip(ip("10.0.0.5"), &xff("garbage"), &trusted), None);
+    }
+}
@@ -14,9 +14,11 @@ use chrono::{DateTime, NaiveDate, Utc};
 use axum::{
     async_trait,
     extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
+    http::request::Parts,
 };
 
+use crate::error::AppError;
+
 // ==========================================
 // 1. 认证与令牌 (Auth & Token)
 // ==========================================
@@ -30,6 +32,7 @@ pub struct Claims {
     pub base_name: Option<String>,
     pub base_logo: Option<String>,
     pub full_name: String,
+    pub sid: Uuid, // 登录会话 id (auth_sessions), 会话吊销后 Token 立即失效
     pub exp: usize,
 }
 
@@ -38,14 +41,14 @@ impl<S> FromRequestParts<S> for Claims
 where
     S: Send + Sync,
 {
-    type Rejection = StatusCode;
+    type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         parts
             .extensions
             .get::<Claims>()
             .cloned()
-            .ok_or(StatusCode::UNAUTHORIZED)
+            .ok_or(AppError::Unauthorized("auth.unauthorized"))
     }
 }
 
@@ -58,6 +61,9 @@ pub struct AuthBody {
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub expires_at: DateTime<Utc>, // Access Token 过期时间
 }
 
 // ==========================================
@@ -293,13 +299,6 @@ pub struct AssetDetail {
     pub base_id: Option<Uuid>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct AssetQuery {
-    pub base_id: Option<Uuid>,
-    pub status: Option<String>,
-    pub keyword: Option<String>, 
-}
-
 #[derive(Debug, Serialize, FromRow)]
 pub struct AssetType {
     pub id: Uuid,
@@ -444,6 +443,8 @@ pub struct MembershipTier {
     pub duration_days: Option<i32>,
     pub usage_count: Option<i32>,
     pub is_active: bool,
+    pub recognition_method: String, // 期限卡收入确认方式: "daily" | "per_class"
+    pub expected_class_count: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -455,6 +456,8 @@ pub struct CreateMembershipTierPayload {
     pub duration_days: Option<i32>,
     pub usage_count: Option<i32>,
     pub is_active: Option<bool>,
+    pub recognition_method: Option<String>,
+    pub expected_class_count: Option<i32>,
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -468,6 +471,10 @@ pub struct CustomerMembership {
     pub expiry_date: Option<DateTime<Utc>>, 
     pub remaining_uses: Option<i32>,    
     pub is_active: bool,
+    #[sqlx(default)]
+    pub frozen_at: Option<DateTime<Utc>>, // 非空 = 冻结中
+    #[sqlx(default)]
+    pub revenue_recognized_in_cents: i32, // 已确认收入 (合同负债已转收入的部分); 次卡拆分转出的次数也计入, 即 售价 - 本卡持有的负债
 }
 
 #[derive(Debug, Deserialize)]
@@ -477,6 +484,43 @@ pub struct CreateCustomerMembershipPayload {
     pub participant_id: Option<Uuid>, 
 }
 
+// 冻结 / 解冻 / 退卡 (原因可选)
+#[derive(Debug, Deserialize)]
+pub struct MembershipActionPayload {
+    pub reason: Option<String>,
+}
+
+// 手动延期 (必须填写原因)
+#[derive(Debug, Deserialize)]
+pub struct ExtendMembershipPayload {
+    pub days: i32,
+    pub reason: String,
+}
+
+// 转让给同一家长名下的其他学员
+#[derive(Debug, Deserialize)]
+pub struct TransferMembershipPayload {
+    pub to_participant_id: Uuid,
+    pub uses: Option<i32>, // 仅次卡: 转出次数, 不填则整卡转让
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct MembershipAdjustment {
+    pub id: Uuid,
+    pub membership_id: Uuid,
+    pub action: String,
+    pub days: Option<i32>,
+    pub uses: Option<i32>,
+    pub amount_in_cents: Option<i32>,
+    pub from_participant_id: Option<Uuid>,
+    pub to_participant_id: Option<Uuid>,
+    pub target_membership_id: Option<Uuid>,
+    pub reason: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
 // ==========================================
 // 7. 教务 (Course, Class)
 // ==========================================
@@ -764,6 +808,10 @@ pub struct OrderItem {
     pub quantity: i32,
     pub unit_price_cents: i32,
     pub total_price_cents: i32,
+    pub product_id: Option<Uuid>,    // 基地库存商品, 交付时出库
+    pub tier_id: Option<Uuid>,       // 会员卡种, 交付时开卡
+    pub membership_id: Option<Uuid>, // 已开出的会员卡
+    pub refunded_quantity: i32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -787,6 +835,8 @@ pub struct CreateOrderItemPayload {
     pub name: String,
     pub quantity: i32,
     pub unit_price: f64, 
+    pub product_id: Option<Uuid>, // 二选一: 库存商品 / 会员卡种 (卡种数量须为 1)
+    pub tier_id: Option<Uuid>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -854,11 +904,6 @@ pub struct SubmitPaymentProofPayload {
     pub proof_url: Option<String>,
 }
 
-#[derive(Deserialize)]
-pub struct PaymentQuery {
-    pub status: Option<String>,
-}
-
 // ==========================================
 // 11. 看板统计 (Dashboard)
 // ==========================================
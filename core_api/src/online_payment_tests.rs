@@ -0,0 +1,316 @@
+/*
+ * src/online_payment_tests.rs
+ * 职责: 在线支付集成测试 (支付渠道用 SimulatedPay, 回调走真实的签名 / 加密格式)
+ * 1. 小程序为订单下单: 重复下单关闭旧交易; 模拟付款后生成已审核的微信支付流水, 订单付清
+ * 2. 回调: 重复回调幂等, 伪造签名 401
+ * 3. 小程序购卡: 付款后自动开卡; 过期未付的购卡订单被作废
+ * 4. 每日对账: 漏单自动补单, 金额不符 / 账单缺失 / 非本系统交易记入差异明细
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::handlers::{close_expired_payments, reconcile_payments, AppState};
+use crate::payment::{PaymentProvider, PrepayRequest, SimulatedPay};
+use crate::test_support::{cleanup_tenant, login, seed_tenant, spawn_app, test_pool, test_state};
+use crate::timezone::business_date;
+
+// 对账单里对不上订单的流水没有 hq_id, 按交易号单独清理
+async fn cleanup(pool: &PgPool, hq: Uuid, unknown_trade_no: &str) {
+    sqlx::query("DELETE FROM payment_reconciliation_items WHERE out_trade_no = $1")
+        .bind(unknown_trade_no)
+        .execute(pool)
+        .await
+        .unwrap();
+    cleanup_tenant(pool, hq).await;
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn online_payments_settle_notify_and_reconcile() {
+    let pool = test_pool(5).await;
+
+    let provider = Arc::new(SimulatedPay::default());
+    let state = AppState { payment: provider.clone(), ..test_state(&pool) };
+    let addr = spawn_app(state.clone()).await;
+
+    let tenant = seed_tenant(&pool, "payment-test").await;
+    let (hq, base) = (tenant.hq, tenant.base);
+    let tier = Uuid::new_v4();
+    let tag = hq.simple().to_string();
+    let unknown_trade_no = format!("ext{}", &tag[..24]);
+    let (_, hq_email) = tenant.add_staff(&pool, "role.hq.admin").await;
+    let (_, base_email) = tenant.add_staff(&pool, "role.base.admin").await;
+    sqlx::query("INSERT INTO membership_tiers (id, hq_id, name_key, tier_type, price_in_cents, usage_count) VALUES ($1, $2, '10 classes', 'usage_based', 100000, 10)")
+        .bind(tier)
+        .bind(hq)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+    let hq_token = login(addr, &hq_email).await;
+    let base_token = login(addr, &base_email).await;
+    let wechat_login: Value = client
+        .post(url("/api/v1/auth/wechat-login"))
+        .json(&json!({"code": tag, "base_id": base}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let parent_token = wechat_login["token"].as_str().unwrap().to_string();
+    let customer: Uuid = wechat_login["customer"]["id"].as_str().unwrap().parse().unwrap();
+
+    let get = |path: &str, token: &str| {
+        let req = client.get(url(path)).bearer_auth(token);
+        async move { req.send().await.unwrap().json::<Value>().await.unwrap() }
+    };
+    let send = |method: reqwest::Method, path: &str, token: &str, body: Value| {
+        let req = client.request(method, url(path)).bearer_auth(token).json(&body);
+        async move { req.send().await.unwrap() }
+    };
+    // 直接建单: 后台建单的订单号精确到分钟, 同一分钟建多张会重号
+    let create_order = |amount_cents: i32| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_scalar::<_, Uuid>(
+                r#"
+                INSERT INTO orders (hq_id, base_id, order_no, type, total_amount_cents, customer_id, contact_name, event_date, status)
+                VALUES ($1, $2, $3, 'b2c', $4, $5, 'payment parent', CURRENT_DATE, 'pending')
+                RETURNING id
+                "#,
+            )
+            .bind(hq)
+            .bind(base)
+            .bind(format!("PAY-{}", Uuid::new_v4().simple()))
+            .bind(amount_cents)
+            .bind(customer)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        }
+    };
+    let pay_order = |order: Uuid| {
+        let req = client.post(url(&format!("/api/v1/customer/orders/{}/pay", order))).bearer_auth(&parent_token);
+        async move {
+            let resp = req.send().await.unwrap();
+            assert_eq!(resp.status(), reqwest::StatusCode::CREATED);
+            resp.json::<Value>().await.unwrap()
+        }
+    };
+    let order_state = |order: Uuid| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_as::<_, (i32, String)>("SELECT paid_amount_cents, status::TEXT FROM orders WHERE id = $1")
+                .bind(order)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        }
+    };
+
+    // 1. 订单下单: 再次下单关闭上一笔交易, 模拟付款后订单付清, 生成系统审核的微信支付流水
+    let order = create_order(30000).await;
+    let first = pay_order(order).await;
+    assert_eq!(first["transaction"]["amount_cents"], 30000);
+    assert!(first["pay_params"]["package"].as_str().unwrap().starts_with("prepay_id="));
+    let second = pay_order(order).await;
+    let first_id = first["transaction"]["id"].as_str().unwrap();
+    let second_id = second["transaction"]["id"].as_str().unwrap().to_string();
+    assert_eq!(get(&format!("/api/v1/customer/payments/{}", first_id), &parent_token).await["status"], "closed");
+    let pending = get(&format!("/api/v1/customer/payments/{}", second_id), &parent_token).await;
+    assert_eq!(pending["status"], "pending");
+
+    let paid: Value = send(reqwest::Method::POST, &format!("/api/v1/customer/payments/{}/simulate", second_id), &parent_token, json!({}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(paid["status"], "paid");
+    assert!(paid["provider_transaction_id"].as_str().unwrap().starts_with("4200"));
+    assert_eq!(order_state(order).await, (30000, "paid".to_string()));
+    let record: (String, String, i32, Option<Uuid>) = sqlx::query_as(
+        "SELECT channel, status, amount_cents, verified_by FROM finance_payment_records WHERE order_id = $1",
+    )
+    .bind(order)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(record, ("wechat_pay".to_string(), "VERIFIED".to_string(), 30000, None));
+
+    // 付清的订单不能再下单
+    let again = send(reqwest::Method::POST, &format!("/api/v1/customer/orders/{}/pay", order), &parent_token, json!({})).await;
+    assert_eq!(again.status(), reqwest::StatusCode::CONFLICT);
+
+    // 2. 微信重发回调: 幂等; 篡改签名: 401
+    let out_trade_no = paid["out_trade_no"].as_str().unwrap();
+    let (headers, body) = provider.pay(out_trade_no).unwrap();
+    let notify = |signature: &str| {
+        client
+            .post(url("/api/v1/payments/wechat/notify"))
+            .header("Wechatpay-Timestamp", &headers.timestamp)
+            .header("Wechatpay-Nonce", &headers.nonce)
+            .header("Wechatpay-Signature", signature)
+            .header("Wechatpay-Serial", &headers.serial)
+            .body(body.clone())
+            .send()
+    };
+    assert_eq!(notify(&headers.signature).await.unwrap().status(), reqwest::StatusCode::NO_CONTENT);
+    assert_eq!(notify("Zm9yZ2Vk").await.unwrap().status(), reqwest::StatusCode::UNAUTHORIZED);
+    let unsigned = client.post(url("/api/v1/payments/wechat/notify")).body(body.clone()).send().await.unwrap();
+    assert_eq!(unsigned.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let (records, notify_count): (i64, i32) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM finance_payment_records WHERE order_id = $1), notify_count FROM payment_transactions WHERE id = $2",
+    )
+    .bind(order)
+    .bind(second_id.parse::<Uuid>().unwrap())
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!((records, notify_count), (1, 2));
+
+    // 别人的交易看不到
+    let staff_view = send(reqwest::Method::GET, &format!("/api/v1/customer/payments/{}", second_id), &base_token, json!({})).await;
+    assert_eq!(staff_view.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // 3. 小程序购卡: 付款后开卡
+    let purchase = send(reqwest::Method::POST, "/api/v1/customer/memberships/purchase", &parent_token, json!({"tier_id": tier})).await;
+    assert_eq!(purchase.status(), reqwest::StatusCode::CREATED);
+    let purchase: Value = purchase.json().await.unwrap();
+    assert_eq!(purchase["transaction"]["purpose"], "membership");
+    assert_eq!(purchase["transaction"]["amount_cents"], 100000);
+    let purchase_id = purchase["transaction"]["id"].as_str().unwrap();
+    send(reqwest::Method::POST, &format!("/api/v1/customer/payments/{}/simulate", purchase_id), &parent_token, json!({})).await;
+    let memberships: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM customer_memberships WHERE customer_id = $1 AND tier_id = $2 AND is_active")
+        .bind(customer)
+        .bind(tier)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(memberships, 1);
+    let unavailable = send(reqwest::Method::POST, "/api/v1/customer/memberships/purchase", &parent_token, json!({"tier_id": Uuid::new_v4()})).await;
+    assert_eq!(unavailable.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // 过期未付的购卡订单: 交易关闭, 订单作废
+    let abandoned: Value = send(reqwest::Method::POST, "/api/v1/customer/memberships/purchase", &parent_token, json!({"tier_id": tier}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let abandoned_order: Uuid = abandoned["transaction"]["order_id"].as_str().unwrap().parse().unwrap();
+    sqlx::query("UPDATE payment_transactions SET expires_at = NOW() - INTERVAL '1 minute' WHERE order_id = $1")
+        .bind(abandoned_order)
+        .execute(&pool)
+        .await
+        .unwrap();
+    close_expired_payments(&pool).await.unwrap();
+    assert_eq!(order_state(abandoned_order).await.1, "cancelled");
+
+    // 4. 对账: 漏单 (已付款, 回调丢失) / 金额不符 / 本地已付账单缺失 / 非本系统交易
+    let lost_order = create_order(20000).await;
+    let lost = pay_order(lost_order).await;
+    provider.pay(lost["transaction"]["out_trade_no"].as_str().unwrap()).unwrap();
+
+    let mismatch_order = create_order(10000).await;
+    let mismatch = pay_order(mismatch_order).await;
+    let mismatch_no = mismatch["transaction"]["out_trade_no"].as_str().unwrap().to_string();
+    let expires_at = Utc::now() + chrono::Duration::minutes(30);
+    provider
+        .create_prepay(&PrepayRequest {
+            out_trade_no: mismatch_no.clone(),
+            description: "mismatch".to_string(),
+            amount_cents: 5000,
+            payer_openid: "openid".to_string(),
+            expires_at,
+        })
+        .await
+        .unwrap();
+    provider.pay(&mismatch_no).unwrap();
+
+    provider
+        .create_prepay(&PrepayRequest {
+            out_trade_no: unknown_trade_no.clone(),
+            description: "external".to_string(),
+            amount_cents: 1234,
+            payer_openid: "openid".to_string(),
+            expires_at,
+        })
+        .await
+        .unwrap();
+    provider.pay(&unknown_trade_no).unwrap();
+
+    let missing_no = format!("local{}", &tag[..20]);
+    sqlx::query(
+        r#"
+        INSERT INTO payment_transactions
+            (hq_id, base_id, order_id, customer_id, provider, out_trade_no, description, amount_cents, payer_openid, status, paid_at, expires_at)
+        VALUES ($1, $2, $3, $4, 'wechat_pay', $5, 'missing', 999, 'openid', 'paid', NOW(), NOW())
+        "#,
+    )
+    .bind(hq)
+    .bind(base)
+    .bind(mismatch_order)
+    .bind(customer)
+    .bind(&missing_no)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let today = business_date(Utc::now());
+    reconcile_payments(&state, today).await.unwrap();
+    assert_eq!(order_state(lost_order).await, (20000, "paid".to_string()));
+    assert_eq!(order_state(mismatch_order).await, (0, "pending".to_string()));
+
+    let day = get(&format!("/api/v1/finance/payment-reconciliations/{}", today), &hq_token).await;
+    assert_eq!(day["status"], "completed");
+    let kinds: Vec<(String, bool)> = day["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| (i["kind"].as_str().unwrap().to_string(), i["resolved"].as_bool().unwrap()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("amount_mismatch".to_string(), false),
+            ("missing_provider".to_string(), false),
+            ("missing_local".to_string(), true),
+        ]
+    );
+    assert_eq!(day["unresolved_count"], 2);
+    let unknown: (String, Option<Uuid>, Option<i32>) = sqlx::query_as(
+        "SELECT kind, hq_id, provider_amount_cents FROM payment_reconciliation_items WHERE out_trade_no = $1",
+    )
+    .bind(&unknown_trade_no)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(unknown, ("unknown_trade".to_string(), None, Some(1234)));
+
+    // 重跑结果一致 (补过的单不再是差异); 当天账单未出, 接口不能对账
+    reconcile_payments(&state, today).await.unwrap();
+    let rerun = get(&format!("/api/v1/finance/payment-reconciliations/{}", today), &hq_token).await;
+    assert_eq!(rerun["items"].as_array().unwrap().len(), 2);
+    let too_early = send(reqwest::Method::POST, "/api/v1/finance/payment-reconciliations/run", &hq_token, json!({"date": today})).await;
+    assert_eq!(too_early.status(), reqwest::StatusCode::BAD_REQUEST);
+    let base_run = send(reqwest::Method::POST, "/api/v1/finance/payment-reconciliations/run", &base_token, json!({"date": today})).await;
+    assert_eq!(base_run.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // 后台交易列表 (基地可见本基地)
+    let list = get("/api/v1/finance/online-payments?status=paid&sort=amount", &base_token).await;
+    let amounts: Vec<i64> = list["items"].as_array().unwrap().iter().map(|t| t["amount_cents"].as_i64().unwrap()).collect();
+    assert_eq!(amounts, vec![999, 20000, 30000, 100000]);
+    let searched = get(&format!("/api/v1/finance/online-payments?q={}", out_trade_no), &base_token).await;
+    assert_eq!(searched["total"], 1);
+    assert_eq!(searched["items"][0]["payment_status"], "VERIFIED");
+
+    cleanup(&pool, hq, &unknown_trade_no).await;
+}
@@ -0,0 +1,191 @@
+/*
+ * src/order_refund_tests.rs
+ * 职责: 订单退款集成测试
+ * 1. 订单付清交付: 商品出库, 卡种开卡; 会员卡部分留在合同负债
+ * 2. 退款申请校验: 金额 / 可退金额 (含审批中) / 可退数量
+ * 3. 审批通过: 退款流水、订单已付冲减、会员卡停用、库存退回、冲销凭证; 全部退完订单置为 refunded
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::test_support::{cleanup_tenant, login, seed_tenant, spawn_app, test_pool, test_state};
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn order_refund_reverses_money_memberships_and_stock() {
+    let pool = test_pool(5).await;
+
+    let addr = spawn_app(test_state(&pool)).await;
+
+    let tenant = seed_tenant(&pool, "refund-test").await;
+    let (hq, base) = (tenant.hq, tenant.base);
+    let customer = Uuid::new_v4();
+    let product = Uuid::new_v4();
+    let tier = Uuid::new_v4();
+    let (_, admin_email) = tenant.add_staff(&pool, "role.base.admin").await;
+    sqlx::query("INSERT INTO customers (id, hq_id, base_id, name, phone_number) VALUES ($1, $2, $3, 'refund parent', $4)")
+        .bind(customer)
+        .bind(hq)
+        .bind(base)
+        .bind(format!("16{}", &hq.as_u128().to_string()[..9]))
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO hq_products (id, hq_id, name, type, price_cents) VALUES ($1, $2, 'kit', 'material', 5000)")
+        .bind(product)
+        .bind(hq)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO base_inventory (base_id, product_id, quantity) VALUES ($1, $2, 10)")
+        .bind(base)
+        .bind(product)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO membership_tiers (id, hq_id, name_key, tier_type, price_in_cents, usage_count) VALUES ($1, $2, '10 classes', 'usage_based', 100000, 10)")
+        .bind(tier)
+        .bind(hq)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+    let token = login(addr, &admin_email).await;
+    let send = |method: reqwest::Method, path: &str, body: Value| {
+        let req = client.request(method, url(path)).bearer_auth(&token).json(&body);
+        async move { req.send().await.unwrap() }
+    };
+    let get = |path: &str| {
+        let req = client.get(url(path)).bearer_auth(&token);
+        async move { req.send().await.unwrap().json::<Value>().await.unwrap() }
+    };
+    let stock = || async {
+        sqlx::query_scalar::<_, i32>("SELECT quantity FROM base_inventory WHERE base_id = $1 AND product_id = $2")
+            .bind(base)
+            .bind(product)
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+    };
+    let order_state = |order: Uuid| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query_as::<_, (i32, String)>("SELECT paid_amount_cents, status::TEXT FROM orders WHERE id = $1")
+                .bind(order)
+                .fetch_one(&pool)
+                .await
+                .unwrap()
+        }
+    };
+
+    // 1. 下单 (3 件商品 + 1 张卡), 收款审核后交付
+    let created: Value = send(reqwest::Method::POST, "/api/v1/finance/orders", json!({
+        "type_": "b2c",
+        "customer_id": customer,
+        "contact_name": "refund parent",
+        "items": [
+            {"name": "kit", "quantity": 3, "unit_price": 50.0, "product_id": product},
+            {"name": "10 classes", "quantity": 1, "unit_price": 1000.0, "tier_id": tier},
+        ],
+    }))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let order: Uuid = created["order_id"].as_str().unwrap().parse().unwrap();
+    let paid = send(reqwest::Method::POST, "/api/v1/finance/payments", json!({
+        "order_id": order, "amount": 1150.0, "channel": "bank_transfer", "payer_name": "parent",
+    }))
+    .await;
+    assert_eq!(paid.status(), reqwest::StatusCode::OK);
+    let payment: Uuid = sqlx::query_scalar("SELECT id FROM finance_payment_records WHERE order_id = $1")
+        .bind(order)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let verified = send(reqwest::Method::PUT, &format!("/api/v1/finance/payments/{}/verify", payment), json!({})).await;
+    assert_eq!(verified.status(), reqwest::StatusCode::OK);
+    assert_eq!(order_state(order).await, (115_000, "paid".to_string()));
+    assert_eq!(stock().await, 7);
+
+    let items = get(&format!("/api/v1/finance/orders/{}/items", order)).await;
+    let items = items.as_array().unwrap();
+    let kit = items.iter().find(|i| i["name"] == "kit").unwrap();
+    let card = items.iter().find(|i| i["name"] == "10 classes").unwrap();
+    let membership: Uuid = card["membership_id"].as_str().unwrap().parse().unwrap();
+    let income = get("/api/v1/ledger/income-statement").await;
+    assert_eq!(income["total_revenue_cents"], 15_000); // 卡费 1000 元留在合同负债
+
+    // 2. 申请校验
+    let refunds_path = format!("/api/v1/finance/orders/{}/refunds", order);
+    let request = |amount: f64, items: Value| {
+        send(reqwest::Method::POST, &refunds_path, json!({"amount": amount, "reason": "moving away", "channel": "bank_transfer", "items": items}))
+    };
+    for (amount, items, status, code) in [
+        (0.0, json!([]), reqwest::StatusCode::BAD_REQUEST, "refund.invalid_amount"),
+        (1150.01, json!([]), reqwest::StatusCode::CONFLICT, "refund.exceeds_paid"),
+        (10.0, json!([{"order_item_id": kit["id"], "quantity": 4}]), reqwest::StatusCode::CONFLICT, "refund.quantity_exceeded"),
+        (10.0, json!([{"order_item_id": Uuid::new_v4(), "quantity": 1}]), reqwest::StatusCode::BAD_REQUEST, "refund.invalid_item"),
+    ] {
+        let res = request(amount, items).await;
+        assert_eq!(res.status(), status, "amount {}", amount);
+        assert_eq!(res.json::<Value>().await.unwrap()["code"], code);
+    }
+
+    // 3. 部分退款: 退卡 + 退 1 件商品; 审批中的金额占用可退额度
+    let first = request(1050.0, json!([{"order_item_id": card["id"], "quantity": 1}, {"order_item_id": kit["id"], "quantity": 1}])).await;
+    assert_eq!(first.status(), reqwest::StatusCode::CREATED);
+    let first: Uuid = first.json::<Value>().await.unwrap()["id"].as_str().unwrap().parse().unwrap();
+    let over = request(200.0, json!([])).await;
+    assert_eq!(over.json::<Value>().await.unwrap()["code"], "refund.exceeds_paid");
+
+    let approve = |id: Uuid| send(reqwest::Method::POST, "/api/v1/base/approval/action", json!({"id": id, "type": "refund", "action": "approve"}));
+    assert_eq!(approve(first).await.status(), reqwest::StatusCode::OK);
+    assert_eq!(approve(first).await.status(), reqwest::StatusCode::CONFLICT);
+
+    assert_eq!(order_state(order).await, (10_000, "paid".to_string()));
+    assert_eq!(stock().await, 8);
+    let active: bool = sqlx::query_scalar("SELECT is_active FROM customer_memberships WHERE id = $1")
+        .bind(membership)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(!active);
+
+    let refunds = get(&refunds_path).await;
+    assert_eq!(refunds[0]["status"], "approved");
+    assert_eq!(refunds[0]["items"].as_array().unwrap().len(), 2);
+    let record_type: String = sqlx::query_scalar("SELECT transaction_type FROM finance_payment_records WHERE id = $1")
+        .bind(refunds[0]["payment_record_id"].as_str().unwrap().parse::<Uuid>().unwrap())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(record_type, "REFUND");
+
+    // 卡费冲合同负债, 其余 50 元冲收入
+    let income = get("/api/v1/ledger/income-statement").await;
+    assert_eq!(income["total_revenue_cents"], 10_000);
+    let sheet = get("/api/v1/ledger/balance-sheet").await;
+    assert_eq!(sheet["balanced"], true);
+    assert_eq!(sheet["total_liabilities_cents"], 0);
+    assert_eq!(sheet["total_assets_cents"], 10_000);
+
+    // 4. 退完剩余金额: 订单置为 refunded, 之后不能再申请
+    let rest = request(100.0, json!([{"order_item_id": kit["id"], "quantity": 2}])).await;
+    let rest: Uuid = rest.json::<Value>().await.unwrap()["id"].as_str().unwrap().parse().unwrap();
+    assert_eq!(approve(rest).await.status(), reqwest::StatusCode::OK);
+    assert_eq!(order_state(order).await, (0, "refunded".to_string()));
+    assert_eq!(stock().await, 10);
+    let closed = request(1.0, json!([])).await;
+    assert_eq!(closed.status(), reqwest::StatusCode::CONFLICT);
+    assert_eq!(closed.json::<Value>().await.unwrap()["code"], "refund.order_not_paid");
+
+    let trial = get("/api/v1/ledger/trial-balance").await;
+    assert_eq!(trial["balanced"], true);
+
+    cleanup_tenant(&pool, hq).await;
+}
@@ -0,0 +1,270 @@
+/*
+ * src/pagination.rs
+ * 职责: 列表接口统一的分页 / 筛选 / 排序
+ * 1. ListQuery 从查询参数中取出 page / page_size / sort / q, 其余参数留作筛选条件
+ *    - page 从 1 开始; page_size 默认 20, 最大 500
+ *    - sort=-created_at,name: 逗号分隔, "-" 前缀为倒序
+ *    - q: 关键字, 在接口声明的列上做模糊匹配
+ * 2. ListSpec 由各接口声明可排序字段 / 可筛选字段 / 搜索列; 参数名映射到写死的 SQL 表达式, 不拼接用户输入
+ *    未声明的排序字段返回 400, 未声明的筛选参数忽略 (可能属于接口自己的 Query)
+ * 3. fetch_page 用同一段 SQL 分别查总数和当前页, 返回 Page { items, total, page, page_size, has_more }
+ * 下拉选项类 (基地 / 课程 / 教室 / 会员卡种类 ...) 和统计排行接口数据量有上限, 仍直接返回数组
+ */
+
+use std::collections::HashMap;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+use sqlx::{postgres::PgRow, FromRow, PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Clone)]
+pub struct ListQuery {
+    pub page: i64,
+    pub page_size: i64,
+    pub sort: Option<String>,
+    pub q: Option<String>,
+    pub params: HashMap<String, String>, // 其余查询参数, 按 ListSpec.filters 取用
+}
+
+impl ListQuery {
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.page_size
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ListQuery
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Query(mut params) = Query::<HashMap<String, String>>::try_from_uri(&parts.uri)
+            .map_err(|_| AppError::BadRequest("request.invalid"))?;
+
+        let mut number = |key: &str, default: i64| -> Result<i64, AppError> {
+            match params.remove(key).filter(|v| !v.is_empty()) {
+                Some(v) => v.parse::<i64>().map_err(|_| AppError::BadRequest("list.invalid_page")),
+                None => Ok(default),
+            }
+        };
+        let page = number("page", 1)?;
+        let page_size = number("page_size", DEFAULT_PAGE_SIZE)?;
+        if page < 1 || !(1..=MAX_PAGE_SIZE).contains(&page_size) {
+            return Err(AppError::BadRequest("list.invalid_page"));
+        }
+
+        let non_empty = |v: Option<String>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let sort = non_empty(params.remove("sort"));
+        let q = non_empty(params.remove("q"));
+        Ok(ListQuery { page, page_size, sort, q, params })
+    }
+}
+
+// --- 接口声明 ---
+
+#[derive(Debug, Clone, Copy)]
+pub enum FilterKind {
+    Text,     // column::text = 值 (状态 / 枚举)
+    Uuid,     // column = 值
+    Bool,     // column = true / false
+    DateFrom, // column >= 值 (YYYY-MM-DD)
+    DateTo,   // column < 值 + 1 天 (含当天)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+    pub param: &'static str,
+    pub column: &'static str,
+    pub kind: FilterKind,
+}
+
+impl Filter {
+    pub const fn text(param: &'static str, column: &'static str) -> Self {
+        Filter { param, column, kind: FilterKind::Text }
+    }
+    pub const fn uuid(param: &'static str, column: &'static str) -> Self {
+        Filter { param, column, kind: FilterKind::Uuid }
+    }
+    pub const fn bool(param: &'static str, column: &'static str) -> Self {
+        Filter { param, column, kind: FilterKind::Bool }
+    }
+    // 同一列的 起始日期 / 截止日期 两个参数
+    pub const fn date_from(param: &'static str, column: &'static str) -> Self {
+        Filter { param, column, kind: FilterKind::DateFrom }
+    }
+    pub const fn date_to(param: &'static str, column: &'static str) -> Self {
+        Filter { param, column, kind: FilterKind::DateTo }
+    }
+}
+
+#[derive(Debug)]
+pub struct ListSpec {
+    pub sorts: &'static [(&'static str, &'static str)], // 排序参数名 -> SQL 表达式
+    pub default_sort: &'static str,                     // 如 "-created_at"
+    pub tiebreak: &'static str,                         // 唯一列 (通常是主键), 保证翻页顺序稳定
+    pub filters: &'static [Filter],
+    pub search: &'static [&'static str],                // q 模糊匹配的列
+}
+
+// 已校验的筛选值
+#[derive(Debug, Clone)]
+enum Bound {
+    Text(String),
+    Uuid(Uuid),
+    Bool(bool),
+    Date(NaiveDate),
+}
+
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub page: i64,
+    pub page_size: i64,
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            page: self.page,
+            page_size: self.page_size,
+            has_more: self.has_more,
+        }
+    }
+}
+
+impl ListQuery {
+    fn bound_filters(&self, spec: &ListSpec) -> Result<Vec<(Filter, Bound)>, AppError> {
+        let mut bound = Vec::new();
+        for filter in spec.filters {
+            let Some(raw) = self.params.get(filter.param).map(|v| v.trim()).filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            let value = match filter.kind {
+                FilterKind::Text => Some(Bound::Text(raw.to_string())),
+                FilterKind::Uuid => raw.parse().ok().map(Bound::Uuid),
+                FilterKind::Bool => raw.parse().ok().map(Bound::Bool),
+                FilterKind::DateFrom | FilterKind::DateTo => {
+                    NaiveDate::parse_from_str(raw, "%Y-%m-%d").ok().map(Bound::Date)
+                }
+            };
+            bound.push((*filter, value.ok_or(AppError::BadRequest("list.invalid_filter"))?));
+        }
+        Ok(bound)
+    }
+
+    fn order_by(&self, spec: &ListSpec) -> Result<String, AppError> {
+        let sort = self.sort.as_deref().unwrap_or(spec.default_sort);
+        let mut terms = Vec::new();
+        for field in sort.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let (name, dir) = match field.strip_prefix('-') {
+                Some(name) => (name, "DESC"),
+                None => (field, "ASC"),
+            };
+            let (_, column) = spec
+                .sorts
+                .iter()
+                .find(|(key, _)| *key == name)
+                .ok_or(AppError::BadRequest("list.invalid_sort"))?;
+            terms.push(format!("{} {} NULLS LAST", column, dir));
+        }
+        terms.push(format!("{} DESC", spec.tiebreak));
+        Ok(terms.join(", "))
+    }
+
+    // 基础 SQL 须以 WHERE 条件结尾, 这里追加 " AND ..."
+    fn push_conditions(&self, qb: &mut QueryBuilder<'static, Postgres>, spec: &ListSpec, filters: &[(Filter, Bound)]) {
+        for (filter, value) in filters {
+            match (filter.kind, value) {
+                (FilterKind::Text, Bound::Text(v)) => {
+                    qb.push(format!(" AND {}::text = ", filter.column)).push_bind(v.clone());
+                }
+                (FilterKind::DateFrom, Bound::Date(d)) => {
+                    qb.push(format!(" AND {} >= ", filter.column)).push_bind(*d);
+                }
+                (FilterKind::DateTo, Bound::Date(d)) => {
+                    qb.push(format!(" AND {} < ", filter.column)).push_bind(*d).push(" + 1");
+                }
+                (_, Bound::Uuid(v)) => {
+                    qb.push(format!(" AND {} = ", filter.column)).push_bind(*v);
+                }
+                (_, Bound::Bool(v)) => {
+                    qb.push(format!(" AND {} = ", filter.column)).push_bind(*v);
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(q), false) = (&self.q, spec.search.is_empty()) {
+            let pattern = format!("%{}%", q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+            qb.push(" AND (");
+            for (i, column) in spec.search.iter().enumerate() {
+                if i > 0 {
+                    qb.push(" OR ");
+                }
+                qb.push(format!("{}::text ILIKE ", column)).push_bind(pattern.clone());
+            }
+            qb.push(")");
+        }
+    }
+
+    // base 写入 "SELECT ... FROM ... WHERE <租户条件>", 会被调用两次 (总数 / 当前页)
+    pub async fn fetch_page<T>(
+        &self,
+        pool: &PgPool,
+        spec: &ListSpec,
+        base: impl Fn(&mut QueryBuilder<'static, Postgres>),
+    ) -> Result<Page<T>, AppError>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let filters = self.bound_filters(spec)?;
+        let order_by = self.order_by(spec)?;
+
+        let mut count_qb = QueryBuilder::new("SELECT COUNT(*) FROM (");
+        base(&mut count_qb);
+        self.push_conditions(&mut count_qb, spec, &filters);
+        count_qb.push(") AS list_total");
+        let total: i64 = count_qb
+            .build_query_scalar()
+            .fetch_one(pool)
+            .await
+            .map_err(AppError::db("Failed to count list"))?;
+
+        let mut qb = QueryBuilder::new("");
+        base(&mut qb);
+        self.push_conditions(&mut qb, spec, &filters);
+        qb.push(format!(" ORDER BY {}", order_by));
+        qb.push(" LIMIT ").push_bind(self.page_size);
+        qb.push(" OFFSET ").push_bind(self.offset());
+        let items: Vec<T> = qb
+            .build_query_as()
+            .fetch_all(pool)
+            .await
+            .map_err(AppError::db("Failed to fetch list page"))?;
+
+        Ok(Page {
+            has_more: self.offset() + (items.len() as i64) < total,
+            items,
+            total,
+            page: self.page,
+            page_size: self.page_size,
+        })
+    }
+}
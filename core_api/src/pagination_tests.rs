@@ -0,0 +1,98 @@
+/*
+ * src/pagination_tests.rs
+ * 职责: 列表接口分页 / 筛选 / 排序集成测试 (以 GET /api/v1/customers 为例)
+ * 1. 返回 { items, total, page, page_size, has_more }, 翻页不重不漏
+ * 2. sort 可选字段与方向, 未声明的字段返回 400 list.invalid_sort
+ * 3. 字段筛选与 q 关键字搜索同时作用于总数和当前页
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::test_support::{cleanup_tenant, login, seed_tenant, spawn_app, test_pool, test_state};
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn list_endpoints_page_filter_and_sort() {
+    let pool = test_pool(5).await;
+
+    let addr = spawn_app(test_state(&pool)).await;
+
+    let tenant = seed_tenant(&pool, "pagination-test").await;
+    let hq = tenant.hq;
+    let (_, admin_email) = tenant.add_staff(&pool, "role.hq.admin").await;
+
+    // 25 个客户: 名字 c00..c24, 越靠后越新; 每 5 个有 1 个正式会员
+    sqlx::query(
+        r#"
+        INSERT INTO customers (hq_id, name, phone_number, customer_type, created_at)
+        SELECT $1, 'c' || lpad(i::text, 2, '0'), '139' || lpad(i::text, 8, '0'),
+               CASE WHEN i % 5 = 0 THEN 'member' ELSE 'prospect' END,
+               NOW() - make_interval(mins => 100 - i)
+        FROM generate_series(0, 24) AS i
+        "#,
+    )
+    .bind(hq)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let client = reqwest::Client::new();
+    let token = login(addr, &admin_email).await;
+
+    let list = |query: &str| {
+        client
+            .get(format!("http://{}/api/v1/customers?{}", addr, query))
+            .bearer_auth(&token)
+            .send()
+    };
+    let names = |body: &Value| -> Vec<String> {
+        body["items"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap().to_string()).collect()
+    };
+
+    // 1. 默认按创建时间倒序, 每页 20
+    let first: Value = list("").await.unwrap().json().await.unwrap();
+    assert_eq!(first["total"], 25);
+    assert_eq!(first["page"], 1);
+    assert_eq!(first["page_size"], 20);
+    assert_eq!(first["has_more"], true);
+    assert_eq!(names(&first)[0], "c24");
+
+    // 2. 翻页不重不漏
+    let mut seen = HashSet::new();
+    for page in 1..=3 {
+        let body: Value = list(&format!("page={}&page_size=10&sort=name", page)).await.unwrap().json().await.unwrap();
+        assert_eq!(body["has_more"], page < 3);
+        seen.extend(names(&body));
+    }
+    assert_eq!(seen.len(), 25);
+
+    let asc: Value = list("page_size=3&sort=name").await.unwrap().json().await.unwrap();
+    assert_eq!(names(&asc), ["c00", "c01", "c02"]);
+
+    // 3. 筛选和搜索同时作用于总数
+    let members: Value = list("customer_type=member&sort=-name").await.unwrap().json().await.unwrap();
+    assert_eq!(members["total"], 5);
+    assert_eq!(names(&members), ["c20", "c15", "c10", "c05", "c00"]);
+
+    let searched: Value = list("q=c1&customer_type=prospect").await.unwrap().json().await.unwrap();
+    assert_eq!(searched["total"], 8); // c10..c19 中 c10 / c15 为会员
+    assert_eq!(searched["has_more"], false);
+
+    // 4. 非法参数
+    for (query, code) in [
+        ("sort=password_hash", "list.invalid_sort"),
+        ("page=0", "list.invalid_page"),
+        ("page_size=501", "list.invalid_page"),
+        ("created_from=yesterday", "list.invalid_filter"),
+    ] {
+        let res = list(query).await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST, "query {}", query);
+        let body: Value = res.json().await.unwrap();
+        assert_eq!(body["code"], code, "query {}", query);
+    }
+
+    cleanup_tenant(&pool, hq).await;
+}
@@ -0,0 +1,110 @@
+/*
+ * src/password_tests.rs
+ * 职责: 员工密码管理集成测试
+ * 1. 管理员创建的员工首次登录只拿到一次性改密令牌, 设置新密码后才能登录
+ * 2. 自助改密: 校验旧密码, 其他会话下线; 管理员重置: 原密码和会话立即失效
+ * 3. 密码策略: 注册 / 创建员工 / 改密 / 重置共用
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use serde_json::{json, Value};
+
+use crate::test_support::{cleanup_tenant, login, seed_tenant, spawn_app, test_pool, test_state};
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn staff_password_rotation_change_and_reset() {
+    let pool = test_pool(5).await;
+
+    let addr = spawn_app(test_state(&pool)).await;
+
+    let tenant = seed_tenant(&pool, "password-test").await;
+    let (hq, base) = (tenant.hq, tenant.base);
+    let tag = hq.simple().to_string();
+    let (_, admin_email) = tenant.add_staff(&pool, "role.hq.admin").await;
+    let teacher_email = format!("teacher-{}@password.test", tag);
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+    let post = |path: &str, token: Option<&str>, body: Value| {
+        let mut req = client.post(url(path)).json(&body);
+        if let Some(token) = token {
+            req = req.bearer_auth(token);
+        }
+        async move { req.send().await.unwrap() }
+    };
+    let attempt = |email: String, password: &str| post("/api/v1/auth/login", None, json!({"email": email, "password": password}));
+    // Token 是否仍有效 (会话被吊销时中间件返回 401)
+    let alive = |token: String| {
+        let req = client.get(url("/api/v1/teacher/dashboard")).bearer_auth(token);
+        async move { req.send().await.unwrap().status() != reqwest::StatusCode::UNAUTHORIZED }
+    };
+
+    let admin_token = login(addr, &admin_email).await;
+
+    // 1. 密码策略: 注册 / 创建员工时指定的弱密码被拒绝
+    let weak_register = post("/api/v1/auth/register", None, json!({"email": format!("weak-{}@password.test", tag), "password": "short"})).await;
+    assert_eq!(weak_register.status(), reqwest::StatusCode::BAD_REQUEST);
+    let new_teacher = json!({"email": teacher_email, "full_name": "teacher", "role_key": "role.teacher", "base_id": base});
+    let mut weak = new_teacher.clone();
+    weak["password"] = json!("onlyletters");
+    let weak_create = post("/api/v1/hq/users", Some(&admin_token), weak).await;
+    assert_eq!(weak_create.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 2. 随机初始密码: 首次登录不签发会话, 只返回一次性改密令牌
+    let created: Value = post("/api/v1/hq/users", Some(&admin_token), new_teacher).await.json().await.unwrap();
+    let teacher_id = created["id"].as_str().unwrap().to_string();
+    let initial = created["initial_password"].as_str().unwrap().to_string();
+    let first: Value = attempt(teacher_email.clone(), &initial).await.json().await.unwrap();
+    assert_eq!(first["must_change_password"], true);
+    assert!(first.get("token").is_none());
+    let reset_token = first["reset_token"].as_str().unwrap().to_string();
+
+    let weak_reset = post("/api/v1/auth/password/reset", None, json!({"token": reset_token, "new_password": "12345678"})).await;
+    assert_eq!(weak_reset.status(), reqwest::StatusCode::BAD_REQUEST);
+    let done = post("/api/v1/auth/password/reset", None, json!({"token": reset_token, "new_password": "teacher-pass-1"})).await;
+    assert_eq!(done.status(), reqwest::StatusCode::NO_CONTENT);
+    let reused = post("/api/v1/auth/password/reset", None, json!({"token": reset_token, "new_password": "teacher-pass-2"})).await;
+    assert_eq!(reused.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 3. 自助改密: 旧密码错误拒绝; 成功后原 Token 失效, 返回的新 Token 可用
+    let session: Value = attempt(teacher_email.clone(), "teacher-pass-1").await.json().await.unwrap();
+    let teacher_token = session["token"].as_str().unwrap().to_string();
+    let wrong = post(
+        "/api/v1/auth/password",
+        Some(&teacher_token),
+        json!({"current_password": "nope-1234", "new_password": "teacher-pass-2"}),
+    )
+    .await;
+    assert_eq!(wrong.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let changed: Value = post(
+        "/api/v1/auth/password",
+        Some(&teacher_token),
+        json!({"current_password": "teacher-pass-1", "new_password": "teacher-pass-2"}),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    let teacher_token_2 = changed["token"].as_str().unwrap().to_string();
+    assert!(!alive(teacher_token).await);
+    assert!(alive(teacher_token_2.clone()).await);
+
+    // 4. 管理员重置: 员工已登录的设备和当前密码立即失效, 凭令牌设置新密码
+    let issued: Value = post(&format!("/api/v1/hq/users/{}/password-reset", teacher_id), Some(&admin_token), json!({}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let admin_reset = issued["reset_token"].as_str().unwrap().to_string();
+    assert!(!alive(teacher_token_2).await);
+    let old_password = attempt(teacher_email.clone(), "teacher-pass-2").await;
+    assert_eq!(old_password.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let done = post("/api/v1/auth/password/reset", None, json!({"token": admin_reset, "new_password": "teacher-pass-3"})).await;
+    assert_eq!(done.status(), reqwest::StatusCode::NO_CONTENT);
+    let relogin: Value = attempt(teacher_email.clone(), "teacher-pass-3").await.json().await.unwrap();
+    assert!(relogin["token"].is_string());
+
+    cleanup_tenant(&pool, hq).await;
+}
@@ -0,0 +1,755 @@
+/*
+ * src/payment.rs
+ * 职责: 在线支付 (微信支付 APIv3, 小程序 JSAPI)
+ * 1. PaymentProvider: 下单 (prepay) / 回调验签解密 / 查单 / 下载交易账单
+ *    - WechatPayClient: 调用微信支付; 请求用商户私钥签名 (RSA-SHA256), 回调用微信支付公钥验签,
+ *      回调资源用 APIv3 密钥解密 (AEAD_AES_256_GCM)
+ *    - SimulatedPay: 本地模拟器 (WECHAT_PAY_PROVIDER=simulator), 在内存中记账,
+ *      模拟付款时生成与微信相同格式的加密回调 (签名改用 HMAC-SHA256), 账单也按微信 CSV 格式生成
+ * 2. 金额单位均为分; 账单按业务时区 (北京时间) 出账
+ */
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use axum::{async_trait, http::HeaderMap, http::StatusCode};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng};
+use rsa::pkcs1v15::{Signature, SigningKey, VerifyingKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use rsa::signature::{SignatureEncoding, Signer, Verifier};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use crate::error::AppError;
+use crate::timezone::{business_date, business_tz};
+
+const DEFAULT_API_BASE: &str = "https://api.mch.weixin.qq.com";
+const NOTIFY_MAX_SKEW_SECS: i64 = 300; // 回调时间戳与本机相差超过 5 分钟视为重放
+
+pub const PROVIDER_WECHAT_PAY: &str = "wechat_pay";
+pub const TRADE_SUCCESS: &str = "SUCCESS";
+
+#[derive(Debug)]
+pub enum PaymentError {
+    NotConfigured,     // 未配置商户号 / 证书 / 密钥
+    InvalidSignature,  // 回调验签或解密失败
+    TradeNotFound,     // 查单: 支付平台没有该商户订单号
+    Rejected(String),  // 支付平台拒绝 (参数错误 / 订单已关闭 ...)
+    Transport(String), // 网络 / 响应格式错误, 可重试
+}
+
+// 支付平台的原始错误只记日志, 不透传给前端
+impl From<PaymentError> for AppError {
+    fn from(e: PaymentError) -> Self {
+        match e {
+            PaymentError::NotConfigured => AppError::Custom(StatusCode::SERVICE_UNAVAILABLE, "payment.not_configured"),
+            PaymentError::InvalidSignature => AppError::Unauthorized("payment.invalid_signature"),
+            PaymentError::TradeNotFound => AppError::NotFound("payment.trade_not_found"),
+            PaymentError::Rejected(msg) => {
+                tracing::warn!("Payment rejected by provider: {}", msg);
+                AppError::Rejected("payment.provider_rejected")
+            }
+            PaymentError::Transport(msg) => {
+                tracing::error!("Payment provider error: {}", msg);
+                AppError::Custom(StatusCode::BAD_GATEWAY, "payment.provider_failed")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaymentError::NotConfigured => write!(f, "payment provider not configured"),
+            PaymentError::InvalidSignature => write!(f, "invalid notification signature"),
+            PaymentError::TradeNotFound => write!(f, "trade not found"),
+            PaymentError::Rejected(msg) => write!(f, "rejected: {}", msg),
+            PaymentError::Transport(msg) => write!(f, "transport error: {}", msg),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrepayRequest {
+    pub out_trade_no: String,
+    pub description: String, // 商品描述, 展示在微信账单里
+    pub amount_cents: i32,
+    pub payer_openid: String, // 小程序 openid
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Prepay {
+    pub prepay_id: String,
+    pub pay_params: Value, // 小程序 wx.requestPayment 的参数
+}
+
+// 回调通知的签名头 (Wechatpay-Timestamp / Nonce / Signature / Serial)
+#[derive(Debug, Clone)]
+pub struct NotificationHeaders {
+    pub timestamp: String,
+    pub nonce: String,
+    pub signature: String,
+    pub serial: String,
+}
+
+impl NotificationHeaders {
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let get = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        Some(Self {
+            timestamp: get("Wechatpay-Timestamp")?,
+            nonce: get("Wechatpay-Nonce")?,
+            signature: get("Wechatpay-Signature")?,
+            serial: get("Wechatpay-Serial")?,
+        })
+    }
+}
+
+// 支付结果 (回调解密后 / 查单返回)
+#[derive(Debug, Clone)]
+pub struct TradeResult {
+    pub out_trade_no: String,
+    pub transaction_id: Option<String>, // 支付平台交易号, 未支付时为空
+    pub trade_state: String,            // SUCCESS / NOTPAY / CLOSED / REFUND / PAYERROR ...
+    pub amount_cents: i32,
+    pub success_time: Option<DateTime<Utc>>,
+}
+
+impl TradeResult {
+    pub fn is_success(&self) -> bool {
+        self.trade_state == TRADE_SUCCESS && self.transaction_id.is_some()
+    }
+}
+
+// 交易账单的一行 (只取支付成功的交易)
+#[derive(Debug, Clone)]
+pub struct StatementEntry {
+    pub out_trade_no: String,
+    pub transaction_id: String,
+    pub amount_cents: i32,
+    pub trade_time: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    // payment_transactions.provider
+    fn name(&self) -> &'static str;
+    async fn create_prepay(&self, req: &PrepayRequest) -> Result<Prepay, PaymentError>;
+    // 验签并解密回调; 返回 None 表示不是支付结果通知 (忽略即可)
+    fn parse_notification(&self, headers: &NotificationHeaders, body: &str) -> Result<Option<TradeResult>, PaymentError>;
+    async fn query_trade(&self, out_trade_no: &str) -> Result<TradeResult, PaymentError>;
+    // 某天 (北京时间) 支付成功的交易
+    async fn trade_statement(&self, date: NaiveDate) -> Result<Vec<StatementEntry>, PaymentError>;
+    // 本地模拟器 (用于模拟付款), 真实支付返回 None
+    fn simulator(&self) -> Option<&SimulatedPay> {
+        None
+    }
+}
+
+// ---------------------------------------------------------
+// 回调 / 账单格式 (真实支付与模拟器共用)
+// ---------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+struct NotificationBody {
+    event_type: String,
+    resource: EncryptedResource,
+}
+
+#[derive(Debug, Deserialize)]
+struct EncryptedResource {
+    ciphertext: String,
+    associated_data: Option<String>,
+    nonce: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransactionAmount {
+    total: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Transaction {
+    out_trade_no: String,
+    transaction_id: Option<String>,
+    trade_state: String,
+    amount: Option<TransactionAmount>,
+    success_time: Option<String>,
+}
+
+impl From<Transaction> for TradeResult {
+    fn from(t: Transaction) -> Self {
+        TradeResult {
+            out_trade_no: t.out_trade_no,
+            transaction_id: t.transaction_id.filter(|id| !id.is_empty()),
+            trade_state: t.trade_state,
+            amount_cents: t.amount.map(|a| a.total).unwrap_or(0),
+            success_time: t
+                .success_time
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|d| d.with_timezone(&Utc)),
+        }
+    }
+}
+
+fn random_nonce() -> String {
+    rand::thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect()
+}
+
+fn check_timestamp(timestamp: &str) -> Result<(), PaymentError> {
+    let ts: i64 = timestamp.parse().map_err(|_| PaymentError::InvalidSignature)?;
+    if (Utc::now().timestamp() - ts).abs() > NOTIFY_MAX_SKEW_SECS {
+        return Err(PaymentError::InvalidSignature);
+    }
+    Ok(())
+}
+
+// 回调验签通过后解密 resource; 只处理 TRANSACTION.* 事件
+fn decrypt_notification(api_v3_key: &[u8], body: &str) -> Result<Option<TradeResult>, PaymentError> {
+    let body: NotificationBody = serde_json::from_str(body).map_err(|_| PaymentError::InvalidSignature)?;
+    if !body.event_type.starts_with("TRANSACTION.") {
+        return Ok(None);
+    }
+    let cipher = Aes256Gcm::new_from_slice(api_v3_key).map_err(|_| PaymentError::NotConfigured)?;
+    let nonce: [u8; 12] = body.resource.nonce.as_bytes().try_into().map_err(|_| PaymentError::InvalidSignature)?;
+    let ciphertext = STANDARD.decode(&body.resource.ciphertext).map_err(|_| PaymentError::InvalidSignature)?;
+    let aad = body.resource.associated_data.unwrap_or_default();
+    let plain = cipher
+        .decrypt(&Nonce::from(nonce), Payload { msg: &ciphertext, aad: aad.as_bytes() })
+        .map_err(|_| PaymentError::InvalidSignature)?;
+    let transaction: Transaction = serde_json::from_slice(&plain).map_err(|_| PaymentError::InvalidSignature)?;
+    Ok(Some(transaction.into()))
+}
+
+fn encrypt_notification(api_v3_key: &[u8], event_type: &str, transaction: &Value) -> String {
+    let cipher = Aes256Gcm::new_from_slice(api_v3_key).expect("32-byte key");
+    let nonce: String = random_nonce().chars().take(12).collect();
+    let nonce_bytes: [u8; 12] = nonce.as_bytes().try_into().expect("12-byte nonce");
+    let aad = "transaction";
+    let ciphertext = cipher
+        .encrypt(&Nonce::from(nonce_bytes), Payload { msg: transaction.to_string().as_bytes(), aad: aad.as_bytes() })
+        .expect("aes-gcm encrypt");
+    json!({
+        "id": uuid::Uuid::new_v4(),
+        "create_time": Utc::now().with_timezone(&business_tz()).to_rfc3339_opts(SecondsFormat::Secs, true),
+        "event_type": event_type,
+        "resource_type": "encrypt-resource",
+        "summary": "支付成功",
+        "resource": {
+            "algorithm": "AEAD_AES_256_GCM",
+            "ciphertext": STANDARD.encode(ciphertext),
+            "associated_data": aad,
+            "original_type": "transaction",
+            "nonce": nonce,
+        }
+    })
+    .to_string()
+}
+
+// 账单金额 "100.00" (元) -> 分
+fn parse_yuan(value: &str) -> Option<i32> {
+    let (yuan, fen) = value.split_once('.').unwrap_or((value, "0"));
+    let fen = format!("{:0<2}", fen);
+    if fen.len() != 2 {
+        return None;
+    }
+    let cents = yuan.parse::<i64>().ok()? * 100 + fen.parse::<i64>().ok()?;
+    i32::try_from(cents).ok()
+}
+
+// 交易账单 (CSV, 每个字段前带 ` 防止 Excel 转科学计数):
+// 第一行表头, 之后每行一笔交易, 以 "总交易单数" 开头的汇总表头及其后内容忽略; 按表头名取列
+pub fn parse_trade_bill(text: &str) -> Result<Vec<StatementEntry>, PaymentError> {
+    let mut lines = text.lines().map(|l| l.trim_start_matches('\u{feff}')).filter(|l| !l.trim().is_empty());
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or_else(|| PaymentError::Transport("empty trade bill".to_string()))?
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let col = |name: &str| {
+        header
+            .iter()
+            .position(|h| *h == name)
+            .ok_or_else(|| PaymentError::Transport(format!("trade bill column missing: {}", name)))
+    };
+    let (time_col, txn_col, no_col, state_col, amount_col) =
+        (col("交易时间")?, col("微信订单号")?, col("商户订单号")?, col("交易状态")?, col("订单金额")?);
+
+    let mut entries = Vec::new();
+    for line in lines {
+        if line.starts_with("总交易单数") {
+            break;
+        }
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim().trim_start_matches('`')).collect();
+        let field = |i: usize| fields.get(i).copied().unwrap_or_default();
+        if field(state_col) != TRADE_SUCCESS {
+            continue; // 退款行另行处理, 这里只对支付
+        }
+        let bad_row = || PaymentError::Transport(format!("malformed trade bill row: {}", line));
+        let trade_time = NaiveDateTime::parse_from_str(field(time_col), "%Y-%m-%d %H:%M:%S").map_err(|_| bad_row())?;
+        entries.push(StatementEntry {
+            out_trade_no: field(no_col).to_string(),
+            transaction_id: field(txn_col).to_string(),
+            amount_cents: parse_yuan(field(amount_col)).ok_or_else(bad_row)?,
+            trade_time: business_tz().from_local_datetime(&trade_time).single().ok_or_else(bad_row)?.with_timezone(&Utc),
+        });
+    }
+    Ok(entries)
+}
+
+fn render_trade_bill(entries: &[StatementEntry]) -> String {
+    let mut text = String::from(
+        "交易时间,公众账号ID,商户号,特约商户号,设备号,微信订单号,商户订单号,用户标识,交易类型,交易状态,付款银行,货币种类,应结订单金额,代金券金额,微信退款单号,商户退款单号,退款金额,充值券退款金额,退款类型,退款状态,商品名称,商户数据包,手续费,费率,订单金额,申请退款金额,费率备注\n",
+    );
+    let mut total = 0i64;
+    for e in entries {
+        let amount = format!("{}.{:02}", e.amount_cents / 100, e.amount_cents % 100);
+        total += e.amount_cents as i64;
+        let time = e.trade_time.with_timezone(&business_tz()).format("%Y-%m-%d %H:%M:%S");
+        text.push_str(&format!(
+            "`{},`{},`{},`0,`,`{},`{},`,`JSAPI,`SUCCESS,`OTHERS,`CNY,`{},`0.00,`0,`0,`0.00,`0.00,`,`,`,`,`0.00,`0.60%,`{},`0.00,`\n",
+            time, SIM_APP_ID, SIM_MCH_ID, e.transaction_id, e.out_trade_no, amount, amount
+        ));
+    }
+    text.push_str("总交易单数,应结订单总金额,退款总金额,充值券退款总金额,手续费总金额,订单总金额,申请退款总金额\n");
+    text.push_str(&format!("`{},`{}.{:02},`0.00,`0.00,`0.00,`{}.{:02},`0.00\n", entries.len(), total / 100, total % 100, total / 100, total % 100));
+    text
+}
+
+// ---------------------------------------------------------
+// 微信支付 APIv3
+// ---------------------------------------------------------
+
+pub struct WechatPayConfig {
+    pub api_base: String,
+    pub app_id: String,             // 小程序 appid
+    pub mch_id: String,             // 商户号
+    pub serial_no: String,          // 商户 API 证书序列号
+    pub private_key_pem: String,    // 商户 API 私钥 (apiclient_key.pem, PKCS#8)
+    pub platform_key_pem: String,   // 微信支付公钥
+    pub platform_key_id: String,    // 微信支付公钥 ID (回调头 Wechatpay-Serial)
+    pub api_v3_key: String,         // APIv3 密钥 (32 字节)
+    pub notify_url: String,         // 支付结果回调地址, 指向 /api/v1/payments/wechat/notify
+}
+
+pub struct WechatPayClient {
+    api_base: String,
+    app_id: String,
+    mch_id: String,
+    serial_no: String,
+    signing_key: SigningKey<Sha256>,
+    platform_key: VerifyingKey<Sha256>,
+    platform_key_id: String,
+    api_v3_key: Vec<u8>,
+    notify_url: String,
+    http: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    code: Option<String>,
+    message: Option<String>,
+}
+
+impl WechatPayClient {
+    pub fn new(config: WechatPayConfig, http: reqwest::Client) -> Result<Self, String> {
+        let private_key = RsaPrivateKey::from_pkcs8_pem(&config.private_key_pem).map_err(|e| format!("merchant private key: {}", e))?;
+        let platform_key =
+            RsaPublicKey::from_public_key_pem(&config.platform_key_pem).map_err(|e| format!("platform public key: {}", e))?;
+        if config.api_v3_key.len() != 32 {
+            return Err("APIv3 key must be 32 bytes".to_string());
+        }
+        Ok(Self {
+            api_base: config.api_base.trim_end_matches('/').to_string(),
+            app_id: config.app_id,
+            mch_id: config.mch_id,
+            serial_no: config.serial_no,
+            signing_key: SigningKey::<Sha256>::new(private_key),
+            platform_key: VerifyingKey::<Sha256>::new(platform_key),
+            platform_key_id: config.platform_key_id,
+            api_v3_key: config.api_v3_key.into_bytes(),
+            notify_url: config.notify_url,
+            http,
+        })
+    }
+
+    fn sign(&self, message: &str) -> String {
+        STANDARD.encode(self.signing_key.sign(message.as_bytes()).to_bytes())
+    }
+
+    // 请求签名: HTTP 方法\nURL 路径 (含查询串)\n时间戳\n随机串\n请求体\n
+    fn authorization(&self, method: &str, path: &str, body: &str) -> String {
+        let timestamp = Utc::now().timestamp();
+        let nonce = random_nonce();
+        let signature = self.sign(&format!("{}\n{}\n{}\n{}\n{}\n", method, path, timestamp, nonce, body));
+        format!(
+            r#"WECHATPAY2-SHA256-RSA2048 mchid="{}",nonce_str="{}",signature="{}",timestamp="{}",serial_no="{}""#,
+            self.mch_id, nonce, signature, timestamp, self.serial_no
+        )
+    }
+
+    // 返回 (状态码, 响应体); 4xx 转为 Rejected (ORDER_NOT_EXIST / RESOURCE_NOT_EXISTS 转为 TradeNotFound), 5xx 转为 Transport
+    async fn call(&self, method: reqwest::Method, path: &str, body: Option<&Value>) -> Result<String, PaymentError> {
+        let body_text = body.map(Value::to_string).unwrap_or_default();
+        let mut req = self
+            .http
+            .request(method.clone(), format!("{}{}", self.api_base, path))
+            .header("Authorization", self.authorization(method.as_str(), path, &body_text))
+            .header("Accept", "application/json")
+            .header("User-Agent", "edusaas-core-api");
+        if body.is_some() {
+            req = req.header("Content-Type", "application/json").body(body_text);
+        }
+        let resp = req.send().await.map_err(|e| PaymentError::Transport(e.to_string()))?;
+        let status = resp.status();
+        let text = resp.text().await.map_err(|e| PaymentError::Transport(e.to_string()))?;
+        if status.is_success() {
+            return Ok(text);
+        }
+        let err: ApiErrorBody = serde_json::from_str(&text).unwrap_or(ApiErrorBody { code: None, message: Some(text.clone()) });
+        let code = err.code.unwrap_or_default();
+        match (status.as_u16(), code.as_str()) {
+            (404, _) | (_, "ORDER_NOT_EXIST") | (_, "RESOURCE_NOT_EXISTS") => Err(PaymentError::TradeNotFound),
+            (400..=499, _) => Err(PaymentError::Rejected(format!("{} {}: {}", status, code, err.message.unwrap_or_default()))),
+            _ => Err(PaymentError::Transport(format!("{} {}: {}", status, code, err.message.unwrap_or_default()))),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PrepayResponse {
+    prepay_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BillResponse {
+    download_url: String,
+    hash_type: Option<String>,
+    hash_value: Option<String>,
+}
+
+#[async_trait]
+impl PaymentProvider for WechatPayClient {
+    fn name(&self) -> &'static str {
+        PROVIDER_WECHAT_PAY
+    }
+
+    async fn create_prepay(&self, req: &PrepayRequest) -> Result<Prepay, PaymentError> {
+        let body = json!({
+            "appid": self.app_id,
+            "mchid": self.mch_id,
+            "description": req.description,
+            "out_trade_no": req.out_trade_no,
+            "time_expire": req.expires_at.with_timezone(&business_tz()).to_rfc3339_opts(SecondsFormat::Secs, true),
+            "notify_url": self.notify_url,
+            "amount": { "total": req.amount_cents, "currency": "CNY" },
+            "payer": { "openid": req.payer_openid },
+        });
+        let text = self.call(reqwest::Method::POST, "/v3/pay/transactions/jsapi", Some(&body)).await?;
+        let resp: PrepayResponse = serde_json::from_str(&text).map_err(|e| PaymentError::Transport(format!("{}: {}", e, text)))?;
+
+        // 小程序调起支付的签名: appId\n时间戳\n随机串\nprepay_id=...\n
+        let timestamp = Utc::now().timestamp().to_string();
+        let nonce = random_nonce();
+        let package = format!("prepay_id={}", resp.prepay_id);
+        let pay_sign = self.sign(&format!("{}\n{}\n{}\n{}\n", self.app_id, timestamp, nonce, package));
+        Ok(Prepay {
+            pay_params: json!({
+                "appId": self.app_id,
+                "timeStamp": timestamp,
+                "nonceStr": nonce,
+                "package": package,
+                "signType": "RSA",
+                "paySign": pay_sign,
+            }),
+            prepay_id: resp.prepay_id,
+        })
+    }
+
+    fn parse_notification(&self, headers: &NotificationHeaders, body: &str) -> Result<Option<TradeResult>, PaymentError> {
+        if headers.serial != self.platform_key_id {
+            tracing::warn!("WeChat Pay notification signed by unknown key {}", headers.serial);
+            return Err(PaymentError::InvalidSignature);
+        }
+        check_timestamp(&headers.timestamp)?;
+        let signature = STANDARD.decode(&headers.signature).map_err(|_| PaymentError::InvalidSignature)?;
+        let signature = Signature::try_from(signature.as_slice()).map_err(|_| PaymentError::InvalidSignature)?;
+        let message = format!("{}\n{}\n{}\n", headers.timestamp, headers.nonce, body);
+        self.platform_key.verify(message.as_bytes(), &signature).map_err(|_| PaymentError::InvalidSignature)?;
+        decrypt_notification(&self.api_v3_key, body)
+    }
+
+    async fn query_trade(&self, out_trade_no: &str) -> Result<TradeResult, PaymentError> {
+        let path = format!("/v3/pay/transactions/out-trade-no/{}?mchid={}", out_trade_no, self.mch_id);
+        let text = self.call(reqwest::Method::GET, &path, None).await?;
+        let transaction: Transaction = serde_json::from_str(&text).map_err(|e| PaymentError::Transport(format!("{}: {}", e, text)))?;
+        Ok(transaction.into())
+    }
+
+    async fn trade_statement(&self, date: NaiveDate) -> Result<Vec<StatementEntry>, PaymentError> {
+        let path = format!("/v3/bill/tradebill?bill_date={}&bill_type=ALL", date.format("%Y-%m-%d"));
+        let bill = match self.call(reqwest::Method::GET, &path, None).await {
+            Ok(text) => serde_json::from_str::<BillResponse>(&text).map_err(|e| PaymentError::Transport(format!("{}: {}", e, text)))?,
+            // 当天没有交易时微信返回 NO_STATEMENT_EXIST
+            Err(PaymentError::Rejected(msg)) if msg.contains("NO_STATEMENT_EXIST") => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        // 下载地址同样需要签名, 签名路径为去掉域名的部分
+        let download_path = bill
+            .download_url
+            .split_once("://")
+            .and_then(|(_, rest)| rest.find('/').map(|i| rest[i..].to_string()))
+            .ok_or_else(|| PaymentError::Transport(format!("bad download_url: {}", bill.download_url)))?;
+        let resp = self
+            .http
+            .get(&bill.download_url)
+            .header("Authorization", self.authorization("GET", &download_path, ""))
+            .send()
+            .await
+            .map_err(|e| PaymentError::Transport(e.to_string()))?;
+        if !resp.status().is_success() {
+            return Err(PaymentError::Transport(format!("trade bill download failed: {}", resp.status())));
+        }
+        let bytes = resp.bytes().await.map_err(|e| PaymentError::Transport(e.to_string()))?;
+        if bill.hash_type.as_deref() == Some("SHA1") {
+            let digest: String = Sha1::digest(&bytes).iter().map(|b| format!("{:02x}", b)).collect();
+            if Some(digest.as_str()) != bill.hash_value.as_deref().map(str::to_ascii_lowercase).as_deref() {
+                return Err(PaymentError::Transport("trade bill hash mismatch".to_string()));
+            }
+        }
+        parse_trade_bill(&String::from_utf8_lossy(&bytes))
+    }
+}
+
+// ---------------------------------------------------------
+// 本地模拟器
+// ---------------------------------------------------------
+
+const SIM_APP_ID: &str = "wxsimulator";
+const SIM_MCH_ID: &str = "1900000000";
+const SIM_KEY_ID: &str = "SIMULATOR";
+
+struct SimTrade {
+    amount_cents: i32,
+    payer_openid: String,
+    transaction_id: Option<String>,
+    paid_at: Option<DateTime<Utc>>,
+}
+
+pub struct SimulatedPay {
+    key: [u8; 32], // APIv3 密钥, 同时用作回调签名密钥
+    trades: Mutex<HashMap<String, SimTrade>>,
+    seq: AtomicU32,
+}
+
+impl Default for SimulatedPay {
+    fn default() -> Self {
+        Self { key: rand::random(), trades: Mutex::new(HashMap::new()), seq: AtomicU32::new(0) }
+    }
+}
+
+impl SimulatedPay {
+    fn sign(&self, message: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("hmac key");
+        mac.update(message.as_bytes());
+        STANDARD.encode(mac.finalize().into_bytes())
+    }
+
+    fn transaction_id(&self) -> String {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed) % 100_000_000;
+        format!("4200{}{:08}", Utc::now().with_timezone(&business_tz()).format("%Y%m%d%H%M%S"), seq)
+    }
+
+    // 模拟用户付款成功, 返回微信会推送的回调 (签名头 + 请求体); 重复调用得到同一笔交易的回调 (模拟微信重发)
+    pub fn pay(&self, out_trade_no: &str) -> Result<(NotificationHeaders, String), PaymentError> {
+        let transaction = {
+            let mut trades = self.trades.lock().unwrap();
+            let trade = trades.get_mut(out_trade_no).ok_or(PaymentError::TradeNotFound)?;
+            if trade.transaction_id.is_none() {
+                trade.transaction_id = Some(self.transaction_id());
+                trade.paid_at = Some(Utc::now());
+            }
+            sim_transaction(out_trade_no, trade)
+        };
+        let body = encrypt_notification(&self.key, "TRANSACTION.SUCCESS", &transaction);
+        let timestamp = Utc::now().timestamp().to_string();
+        let nonce = random_nonce();
+        let signature = self.sign(&format!("{}\n{}\n{}\n", timestamp, nonce, body));
+        Ok((NotificationHeaders { timestamp, nonce, signature, serial: SIM_KEY_ID.to_string() }, body))
+    }
+}
+
+fn sim_transaction(out_trade_no: &str, trade: &SimTrade) -> Value {
+    let (state, desc) = if trade.transaction_id.is_some() { ("SUCCESS", "支付成功") } else { ("NOTPAY", "订单未支付") };
+    json!({
+        "appid": SIM_APP_ID,
+        "mchid": SIM_MCH_ID,
+        "out_trade_no": out_trade_no,
+        "transaction_id": trade.transaction_id.clone().unwrap_or_default(),
+        "trade_type": "JSAPI",
+        "trade_state": state,
+        "trade_state_desc": desc,
+        "success_time": trade.paid_at.map(|t| t.with_timezone(&business_tz()).to_rfc3339_opts(SecondsFormat::Secs, true)),
+        "payer": { "openid": trade.payer_openid },
+        "amount": { "total": trade.amount_cents, "payer_total": trade.amount_cents, "currency": "CNY" },
+    })
+}
+
+#[async_trait]
+impl PaymentProvider for SimulatedPay {
+    fn name(&self) -> &'static str {
+        PROVIDER_WECHAT_PAY
+    }
+
+    async fn create_prepay(&self, req: &PrepayRequest) -> Result<Prepay, PaymentError> {
+        if req.amount_cents <= 0 || req.payer_openid.is_empty() {
+            return Err(PaymentError::Rejected("PARAM_ERROR".to_string()));
+        }
+        self.trades.lock().unwrap().insert(
+            req.out_trade_no.clone(),
+            SimTrade { amount_cents: req.amount_cents, payer_openid: req.payer_openid.clone(), transaction_id: None, paid_at: None },
+        );
+        let prepay_id = format!("wx{}{:010}", Utc::now().format("%d%H%M%S"), self.seq.fetch_add(1, Ordering::Relaxed));
+        let timestamp = Utc::now().timestamp().to_string();
+        let nonce = random_nonce();
+        let package = format!("prepay_id={}", prepay_id);
+        let pay_sign = self.sign(&format!("{}\n{}\n{}\n{}\n", SIM_APP_ID, timestamp, nonce, package));
+        Ok(Prepay {
+            pay_params: json!({
+                "appId": SIM_APP_ID,
+                "timeStamp": timestamp,
+                "nonceStr": nonce,
+                "package": package,
+                "signType": "HMAC-SHA256",
+                "paySign": pay_sign,
+            }),
+            prepay_id,
+        })
+    }
+
+    fn parse_notification(&self, headers: &NotificationHeaders, body: &str) -> Result<Option<TradeResult>, PaymentError> {
+        if headers.serial != SIM_KEY_ID {
+            return Err(PaymentError::InvalidSignature);
+        }
+        check_timestamp(&headers.timestamp)?;
+        let signature = STANDARD.decode(&headers.signature).map_err(|_| PaymentError::InvalidSignature)?;
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("hmac key");
+        mac.update(format!("{}\n{}\n{}\n", headers.timestamp, headers.nonce, body).as_bytes());
+        mac.verify_slice(&signature).map_err(|_| PaymentError::InvalidSignature)?;
+        decrypt_notification(&self.key, body)
+    }
+
+    async fn query_trade(&self, out_trade_no: &str) -> Result<TradeResult, PaymentError> {
+        let trades = self.trades.lock().unwrap();
+        let trade = trades.get(out_trade_no).ok_or(PaymentError::TradeNotFound)?;
+        let transaction: Transaction =
+            serde_json::from_value(sim_transaction(out_trade_no, trade)).map_err(|e| PaymentError::Transport(e.to_string()))?;
+        Ok(transaction.into())
+    }
+
+    async fn trade_statement(&self, date: NaiveDate) -> Result<Vec<StatementEntry>, PaymentError> {
+        let mut entries: Vec<StatementEntry> = self
+            .trades
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(no, t)| {
+                let paid_at = t.paid_at?;
+                (business_date(paid_at) == date).then(|| StatementEntry {
+                    out_trade_no: no.clone(),
+                    transaction_id: t.transaction_id.clone().unwrap_or_default(),
+                    amount_cents: t.amount_cents,
+                    trade_time: paid_at,
+                })
+            })
+            .collect();
+        entries.sort_by_key(|e| e.trade_time);
+        // 与真实账单走同一个解析
+        parse_trade_bill(&render_trade_bill(&entries))
+    }
+
+    fn simulator(&self) -> Option<&SimulatedPay> {
+        Some(self)
+    }
+}
+
+// --- 未配置: 拒绝下单, 回调一律验签失败 ---
+
+pub struct UnconfiguredPayment;
+
+#[async_trait]
+impl PaymentProvider for UnconfiguredPayment {
+    fn name(&self) -> &'static str {
+        PROVIDER_WECHAT_PAY
+    }
+
+    async fn create_prepay(&self, _req: &PrepayRequest) -> Result<Prepay, PaymentError> {
+        Err(PaymentError::NotConfigured)
+    }
+
+    fn parse_notification(&self, _headers: &NotificationHeaders, _body: &str) -> Result<Option<TradeResult>, PaymentError> {
+        Err(PaymentError::NotConfigured)
+    }
+
+    async fn query_trade(&self, _out_trade_no: &str) -> Result<TradeResult, PaymentError> {
+        Err(PaymentError::NotConfigured)
+    }
+
+    async fn trade_statement(&self, _date: NaiveDate) -> Result<Vec<StatementEntry>, PaymentError> {
+        Err(PaymentError::NotConfigured)
+    }
+}
+
+// 启动时读取: WECHAT_PAY_PROVIDER=simulator 用本地模拟器; 否则按 WECHAT_PAY_* 配置接入微信支付,
+// 配置不全时在线支付不可用 (下单返回 payment.not_configured)
+pub fn payment_from_env(http: reqwest::Client) -> Arc<dyn PaymentProvider> {
+    if std::env::var("WECHAT_PAY_PROVIDER").map(|v| v.trim() == "simulator").unwrap_or(false) {
+        tracing::warn!("WECHAT_PAY_PROVIDER=simulator: online payments are simulated, do not use in production");
+        return Arc::new(SimulatedPay::default());
+    }
+
+    let env = |key: &str| std::env::var(key).unwrap_or_default().trim().to_string();
+    let read_pem = |key: &str| std::fs::read_to_string(env(key)).unwrap_or_default();
+    let mch_id = env("WECHAT_PAY_MCH_ID");
+    if mch_id.is_empty() {
+        tracing::warn!("WECHAT_PAY_MCH_ID is not set, online payment is disabled");
+        return Arc::new(UnconfiguredPayment);
+    }
+    let app_id = match env("WECHAT_PAY_APP_ID") {
+        id if id.is_empty() => env("WECHAT_APP_ID"),
+        id => id,
+    };
+    let api_base = match env("WECHAT_PAY_API_BASE") {
+        base if base.is_empty() => DEFAULT_API_BASE.to_string(),
+        base => base,
+    };
+    let config = WechatPayConfig {
+        api_base,
+        app_id,
+        mch_id,
+        serial_no: env("WECHAT_PAY_SERIAL_NO"),
+        private_key_pem: read_pem("WECHAT_PAY_PRIVATE_KEY_PATH"),
+        platform_key_pem: read_pem("WECHAT_PAY_PUBLIC_KEY_PATH"),
+        platform_key_id: env("WECHAT_PAY_PUBLIC_KEY_ID"),
+        api_v3_key: env("WECHAT_PAY_API_V3_KEY"),
+        notify_url: env("WECHAT_PAY_NOTIFY_URL"),
+    };
+    match WechatPayClient::new(config, http) {
+        Ok(client) => Arc::new(client),
+        Err(e) => {
+            tracing::error!("WeChat Pay configuration invalid ({}), online payment is disabled", e);
+            Arc::new(UnconfiguredPayment)
+        }
+    }
+}
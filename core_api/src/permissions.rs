@@ -0,0 +1,204 @@
+/*
+ * src/permissions.rs
+ * 职责: 权限模型 (RBAC)
+ * 1. 权限挂在 roles 上 (role_permissions 表), 登录用户的权限集在 auth_middleware 中加载
+ * 2. main.rs 中每条路由通过 .require(Rule) 声明 "需要什么权限 + 在哪个作用域 (总部 / 基地)"
+ */
+
+use std::collections::HashSet;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::Response,
+    routing::MethodRouter,
+};
+use sqlx::PgPool;
+
+use crate::error::AppError;
+use crate::models::Claims;
+
+// --- 权限点 (与 permissions 表的 key 一一对应) ---
+pub mod perm {
+    pub const BASES_READ: &str = "bases.read";
+    pub const BASES_MANAGE: &str = "bases.manage";
+    pub const ASSETS_READ: &str = "assets.read";
+    pub const ASSETS_MANAGE: &str = "assets.manage";
+    pub const MATERIALS_READ: &str = "materials.read";
+    pub const MATERIALS_MANAGE: &str = "materials.manage";
+    pub const CUSTOMERS_READ: &str = "customers.read";
+    pub const CUSTOMERS_MANAGE: &str = "customers.manage";
+    pub const WORKSPACE_READ: &str = "workspace.read";
+    pub const NOTICES_MANAGE: &str = "notices.manage";
+    pub const APPROVALS_MANAGE: &str = "approvals.manage";
+    pub const FINANCE_READ: &str = "finance.read";
+    pub const FINANCE_MANAGE: &str = "finance.manage";
+    pub const LEDGER_CLOSE: &str = "ledger.close";
+    pub const STAFF_READ: &str = "staff.read";
+    pub const STAFF_MANAGE: &str = "staff.manage";
+    pub const TIERS_MANAGE: &str = "tiers.manage";
+    pub const MEMBERSHIPS_READ: &str = "memberships.read";
+    pub const MEMBERSHIPS_MANAGE: &str = "memberships.manage";
+    pub const MEMBERSHIPS_REFUND: &str = "memberships.refund";
+    pub const COURSES_READ: &str = "courses.read";
+    pub const COURSES_MANAGE: &str = "courses.manage";
+    pub const ROOMS_READ: &str = "rooms.read";
+    pub const ROOMS_MANAGE: &str = "rooms.manage";
+    pub const SCHEDULE_READ: &str = "schedule.read";
+    pub const SCHEDULE_MANAGE: &str = "schedule.manage";
+    pub const ENROLLMENTS_MANAGE: &str = "enrollments.manage";
+    pub const ATTENDANCE_RECORD: &str = "attendance.record";
+    pub const TEACHING_SELF: &str = "teaching.self";
+    pub const HONOR_READ: &str = "honor.read";
+    pub const HONOR_MANAGE: &str = "honor.manage";
+    pub const STOCK_READ: &str = "stock.read";
+    pub const PROCUREMENT_MANAGE: &str = "procurement.manage";
+    pub const PROCUREMENT_APPROVE: &str = "procurement.approve";
+    pub const SUPPLY_READ: &str = "supply.read";
+    pub const SUPPLY_ORDER: &str = "supply.order";
+    pub const SUPPLY_MANAGE: &str = "supply.manage";
+    pub const INVENTORY_MANAGE: &str = "inventory.manage";
+    pub const REPORTS_HQ: &str = "reports.hq";
+    pub const LEADS_MANAGE: &str = "leads.manage";
+    pub const TRIALS_MANAGE: &str = "trials.manage";
+    pub const QRCODES_MANAGE: &str = "qrcodes.manage";
+    pub const USERS_IMPERSONATE: &str = "users.impersonate";
+}
+
+// C 端家长的 token 角色
+const CUSTOMER_ROLES: [&str; 2] = ["customer", "role.consumer"];
+
+// --- 资源作用域 ---
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Hq,       // 仅总部账号 (不挂基地)
+    Base,     // 仅基地账号
+    Staff,    // 总部 / 基地均可 (handler 内按 base_id 再区分)
+    Customer, // C 端家长
+    Any,      // 任意已登录用户
+}
+
+// --- 路由声明的访问规则 ---
+#[derive(Debug, Clone, Copy)]
+pub struct Rule {
+    pub permission: Option<&'static str>,
+    pub scope: Scope,
+}
+
+pub fn hq(permission: &'static str) -> Rule {
+    Rule { permission: Some(permission), scope: Scope::Hq }
+}
+
+pub fn base(permission: &'static str) -> Rule {
+    Rule { permission: Some(permission), scope: Scope::Base }
+}
+
+pub fn staff(permission: &'static str) -> Rule {
+    Rule { permission: Some(permission), scope: Scope::Staff }
+}
+
+// C 端接口只校验身份, 数据范围由 handler 按 claims.sub 限定
+pub fn customer() -> Rule {
+    Rule { permission: None, scope: Scope::Customer }
+}
+
+// 仅要求已登录 (如文件上传)
+pub fn authenticated() -> Rule {
+    Rule { permission: None, scope: Scope::Any }
+}
+
+// --- 当前用户的权限集 (由 auth_middleware 放入请求扩展) ---
+#[derive(Debug, Clone, Default)]
+pub struct Grants(HashSet<String>);
+
+impl Grants {
+    pub fn allows(&self, permission: &str) -> bool {
+        self.0.contains(permission)
+    }
+}
+
+// handler 内需要细分权限时可直接提取 (如同一接口总部审批 / 基地收货)
+#[async_trait]
+impl<S> FromRequestParts<S> for Grants
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Grants>().cloned().unwrap_or_default())
+    }
+}
+
+pub fn is_customer(claims: &Claims) -> bool {
+    claims.roles.iter().any(|r| CUSTOMER_ROLES.contains(&r.as_str()))
+}
+
+// 按角色加载权限 (角色按总部隔离)
+pub async fn load_grants(pool: &PgPool, claims: &Claims) -> Result<Grants, sqlx::Error> {
+    if is_customer(claims) {
+        return Ok(Grants::default());
+    }
+
+    let keys: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT rp.permission_key
+        FROM role_permissions rp
+        JOIN roles r ON rp.role_id = r.id
+        WHERE r.hq_id = $1 AND r.name_key = ANY($2)
+        "#,
+    )
+    .bind(claims.hq_id)
+    .bind(&claims.roles)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(Grants(keys.into_iter().collect()))
+}
+
+impl Rule {
+    pub fn check(&self, claims: &Claims, grants: &Grants) -> bool {
+        let scope_ok = match self.scope {
+            Scope::Customer => is_customer(claims),
+            Scope::Hq => !is_customer(claims) && claims.base_id.is_none(),
+            Scope::Base => !is_customer(claims) && claims.base_id.is_some(),
+            Scope::Staff => !is_customer(claims),
+            Scope::Any => true,
+        };
+        scope_ok && self.permission.is_none_or(|p| grants.allows(p))
+    }
+}
+
+// 路由级校验中间件 (需在 auth_middleware 之后执行)
+async fn check_rule(State(rule): State<Rule>, req: Request, next: Next) -> Result<Response, AppError> {
+    let claims = req.extensions().get::<Claims>().ok_or(AppError::Unauthorized("auth.unauthorized"))?;
+    let grants = req.extensions().get::<Grants>().cloned().unwrap_or_default();
+
+    if !rule.check(claims, &grants) {
+        tracing::warn!(
+            "Permission denied: user {} (roles: {:?}) needs {:?} at {:?}",
+            claims.sub,
+            claims.roles,
+            rule.permission,
+            rule.scope
+        );
+        return Err(AppError::Forbidden("auth.forbidden"));
+    }
+    Ok(next.run(req).await)
+}
+
+// 在路由上声明所需权限: get(handler).require(hq(perm::BASES_MANAGE))
+pub trait RequirePermission {
+    fn require(self, rule: Rule) -> Self;
+}
+
+impl<S> RequirePermission for MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn require(self, rule: Rule) -> Self {
+        self.route_layer(axum::middleware::from_fn_with_state(rule, check_rule))
+    }
+}
@@ -0,0 +1,199 @@
+/*
+ * src/receivable_tests.rs
+ * 职责: 应收账款集成测试
+ * 1. 分期计划校验: 每期金额 / 合计须等于订单总额
+ * 2. 收款核实按到期日先后核销到各期, 订单 due_date 跟随最早未收齐的一期
+ * 3. 重设分期时重新核销已收款项, 退款从最晚一期冲回
+ * 4. 账龄分析: 按基地 / 客户分段汇总, 无分期的订单按订单到期日
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::test_support::{cleanup_tenant, login, seed_tenant, spawn_app, test_pool, test_state};
+use crate::timezone::business_date;
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn installments_match_payments_and_feed_aging() {
+    let pool = test_pool(5).await;
+
+    let addr = spawn_app(test_state(&pool)).await;
+
+    let tenant = seed_tenant(&pool, "receivable-test").await;
+    let (hq, base) = (tenant.hq, tenant.base);
+    let customer = Uuid::new_v4();
+    let (_, admin_email) = tenant.add_staff(&pool, "role.base.admin").await;
+    sqlx::query("INSERT INTO customers (id, hq_id, base_id, name, phone_number) VALUES ($1, $2, $3, 'school district', $4)")
+        .bind(customer)
+        .bind(hq)
+        .bind(base)
+        .bind(format!("15{}", &hq.as_u128().to_string()[..9]))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+    let token = login(addr, &admin_email).await;
+    let send = |method: reqwest::Method, path: &str, body: Value| {
+        let req = client.request(method, url(path)).bearer_auth(&token).json(&body);
+        async move { req.send().await.unwrap() }
+    };
+    let get = |path: &str| {
+        let req = client.get(url(path)).bearer_auth(&token);
+        async move { req.send().await.unwrap().json::<Value>().await.unwrap() }
+    };
+    let today = business_date(Utc::now());
+    let day = |offset: i64| (today + Duration::days(offset)).to_string();
+    let paid_cents = |list: &Value| -> Vec<i64> {
+        list.as_array().unwrap().iter().map(|i| i["paid_cents"].as_i64().unwrap()).collect()
+    };
+
+    // B2B 订单 3000 元, 分三期; 另一笔无分期、未关联客户的订单 500 元, 5 天前到期
+    let created: Value = send(reqwest::Method::POST, "/api/v1/finance/orders", json!({
+        "type_": "b2b", "customer_id": customer, "contact_name": "district office", "total_amount": 3000.0,
+    }))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let order: Uuid = created["order_id"].as_str().unwrap().parse().unwrap();
+    let other: Value = send(reqwest::Method::POST, "/api/v1/finance/orders", json!({
+        "type_": "b2g", "contact_name": "walk-in", "total_amount": 500.0,
+    }))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let other: Uuid = other["order_id"].as_str().unwrap().parse().unwrap();
+    sqlx::query("UPDATE orders SET due_date = $1 WHERE id = $2")
+        .bind(today - Duration::days(5))
+        .bind(other)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // 1. 校验
+    let path = format!("/api/v1/finance/orders/{}/installments", order);
+    let mismatch = send(reqwest::Method::PUT, &path, json!({"installments": [
+        {"due_date": day(-10), "amount": 1000.0},
+    ]}))
+    .await;
+    assert_eq!(mismatch.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(mismatch.json::<Value>().await.unwrap()["code"], "installment.sum_mismatch");
+    let zero = send(reqwest::Method::PUT, &path, json!({"installments": [
+        {"due_date": day(-10), "amount": 3000.0}, {"due_date": day(10), "amount": 0.0},
+    ]}))
+    .await;
+    assert_eq!(zero.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(zero.json::<Value>().await.unwrap()["code"], "installment.invalid_amount");
+
+    // 2. 三期 (乱序提交, 按到期日排期)
+    let set = send(reqwest::Method::PUT, &path, json!({"installments": [
+        {"due_date": day(10), "amount": 1000.0, "note": "尾款"},
+        {"due_date": day(-100), "amount": 1000.0, "note": "首付"},
+        {"due_date": day(-40), "amount": 1000.0},
+    ]}))
+    .await;
+    assert_eq!(set.status(), reqwest::StatusCode::OK);
+    let list: Value = set.json().await.unwrap();
+    assert_eq!(list[0]["due_date"], day(-100));
+    assert_eq!(list[0]["note"], "首付");
+    assert_eq!(list[2]["seq"], 3);
+    assert_eq!(list[0]["status"], "overdue");
+    assert_eq!(list[2]["status"], "due");
+
+    let aging = get("/api/v1/finance/receivables/aging").await;
+    let row = &aging["rows"][0];
+    assert_eq!(row["id"], base.to_string());
+    assert_eq!(row["current_cents"], 100_000);
+    assert_eq!(row["days_1_30_cents"], 50_000);
+    assert_eq!(row["days_31_60_cents"], 100_000);
+    assert_eq!(row["days_61_90_cents"], 0);
+    assert_eq!(row["days_over_90_cents"], 100_000);
+    assert_eq!(row["total_cents"], 350_000);
+    assert_eq!(row["order_count"], 2);
+
+    // 3. 收款 1500 元核实后: 第一期收齐, 第二期收 500
+    let paid = send(reqwest::Method::POST, "/api/v1/finance/payments", json!({
+        "order_id": order, "amount": 1500.0, "channel": "bank_transfer", "payer_name": "district",
+    }))
+    .await;
+    assert_eq!(paid.status(), reqwest::StatusCode::OK);
+    let payment: Uuid = sqlx::query_scalar("SELECT id FROM finance_payment_records WHERE order_id = $1")
+        .bind(order)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let verified = send(reqwest::Method::PUT, &format!("/api/v1/finance/payments/{}/verify", payment), json!({})).await;
+    assert_eq!(verified.status(), reqwest::StatusCode::OK);
+
+    let list = get(&path).await;
+    assert_eq!(paid_cents(&list), vec![100_000, 50_000, 0]);
+    assert_eq!(list[0]["status"], "paid");
+    assert!(list[0]["paid_at"].is_string());
+    assert_eq!(list[1]["status"], "overdue");
+    assert_eq!(list[1]["payments"][0]["payment_record_id"], payment.to_string());
+    assert_eq!(list[1]["payments"][0]["amount_cents"], 50_000);
+    let due_date: chrono::NaiveDate = sqlx::query_scalar("SELECT due_date FROM orders WHERE id = $1")
+        .bind(order)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(due_date.to_string(), day(-40));
+
+    let by_customer = get("/api/v1/finance/receivables/aging?group_by=customer").await;
+    let rows = by_customer["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+    let district = rows.iter().find(|r| r["id"] == customer.to_string()).unwrap();
+    assert_eq!(district["name"], "school district");
+    assert_eq!(district["current_cents"], 100_000);
+    assert_eq!(district["days_31_60_cents"], 50_000);
+    assert_eq!(district["days_over_90_cents"], 0);
+    let unlinked = rows.iter().find(|r| r["id"].is_null()).unwrap();
+    assert_eq!(unlinked["days_1_30_cents"], 50_000);
+    assert_eq!(by_customer["totals"]["total_cents"], 200_000);
+
+    // 账龄计算日往后推 60 天: 全部已逾期
+    let later = get(&format!("/api/v1/finance/receivables/aging?as_of={}", day(60))).await;
+    assert_eq!(later["totals"]["current_cents"], 0);
+    assert_eq!(later["totals"]["days_31_60_cents"], 100_000);
+    assert_eq!(later["totals"]["days_61_90_cents"], 50_000);
+    assert_eq!(later["totals"]["days_over_90_cents"], 50_000);
+
+    // 4. 重设为两期, 已收 1500 元重新核销到新的第一期
+    let reset = send(reqwest::Method::PUT, &path, json!({"installments": [
+        {"due_date": day(-10), "amount": 2000.0}, {"due_date": day(30), "amount": 1000.0},
+    ]}))
+    .await;
+    assert_eq!(reset.status(), reqwest::StatusCode::OK);
+    let list: Value = reset.json().await.unwrap();
+    assert_eq!(paid_cents(&list), vec![150_000, 0]);
+    assert_eq!(list[0]["payments"][0]["amount_cents"], 150_000);
+
+    // 5. 退款 500 元审批通过, 从最晚有收款的一期冲回
+    let refund: Value = send(reqwest::Method::POST, &format!("/api/v1/finance/orders/{}/refunds", order), json!({
+        "amount": 500.0, "reason": "scope reduced", "channel": "bank",
+    }))
+    .await
+    .json()
+    .await
+    .unwrap();
+    let approved = send(reqwest::Method::POST, "/api/v1/base/approval/action", json!({
+        "id": refund["id"], "type": "refund", "action": "approve",
+    }))
+    .await;
+    assert_eq!(approved.status(), reqwest::StatusCode::OK);
+    assert_eq!(paid_cents(&get(&path).await), vec![100_000, 0]);
+
+    // 6. 取消分期后按订单到期日统计
+    let cleared = send(reqwest::Method::PUT, &path, json!({"installments": []})).await;
+    assert_eq!(cleared.status(), reqwest::StatusCode::OK);
+    let aging = get("/api/v1/finance/receivables/aging").await;
+    assert_eq!(aging["totals"]["total_cents"], 250_000);
+
+    cleanup_tenant(&pool, hq).await;
+}
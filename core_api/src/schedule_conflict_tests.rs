@@ -0,0 +1,129 @@
+/*
+ * src/schedule_conflict_tests.rs
+ * 职责: 排课冲突校验的并发集成测试
+ * 同一教室 / 同一老师同时提交多条重叠的排课 (POST /api/v1/base/classes), 只能有一条成功,
+ * 其余返回 409 schedule.conflict, 数据库里不会出现重叠的排课
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use chrono::{Duration, DurationRound, Utc};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::test_support::{cleanup_tenant, login, seed_tenant, spawn_app, test_pool, test_state};
+
+const CONCURRENCY: usize = 8;
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn concurrent_bookings_of_one_room_or_teacher_admit_only_one() {
+    let pool = test_pool(CONCURRENCY as u32 + 2).await;
+    let addr = spawn_app(test_state(&pool)).await;
+
+    let tenant = seed_tenant(&pool, "schedule-test").await;
+    let (hq, base) = (tenant.hq, tenant.base);
+    let course = Uuid::new_v4();
+    let teachers: Vec<Uuid> = (0..CONCURRENCY).map(|_| Uuid::new_v4()).collect();
+    let rooms: Vec<Uuid> = (0..CONCURRENCY).map(|_| Uuid::new_v4()).collect();
+
+    let (_, admin_email) = tenant.add_staff(&pool, "role.base.admin").await;
+    sqlx::query(
+        r#"
+        INSERT INTO users (id, hq_id, base_id, email, password_hash, full_name)
+        SELECT id, $2, $3, id::text || '@schedule.test', 'x', 'schedule-test'
+        FROM unnest($1::uuid[]) AS id
+        "#,
+    )
+    .bind(&teachers)
+    .bind(hq)
+    .bind(base)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO teachers (user_id, hq_id, base_id) SELECT id, $2, $3 FROM unnest($1::uuid[]) AS id")
+        .bind(&teachers)
+        .bind(hq)
+        .bind(base)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO courses (id, hq_id, name_key) VALUES ($1, $2, 'schedule-test course')")
+        .bind(course)
+        .bind(hq)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO rooms (id, hq_id, base_id, name, capacity) SELECT id, $2, $3, 'schedule-test room', 20 FROM unnest($1::uuid[]) AS id")
+        .bind(&rooms)
+        .bind(hq)
+        .bind(base)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let token = login(addr, &admin_email).await;
+    let client = reqwest::Client::new();
+    let start = (Utc::now() + Duration::days(3)).duration_trunc(Duration::hours(1)).unwrap();
+
+    // 同时提交; 每条请求的 (教室, 老师) 由 pick 决定, 返回 (成功数, 409 数)
+    let race = |offset_days: i64, pick: fn(usize) -> (usize, usize)| {
+        let (client, token, rooms, teachers) = (client.clone(), token.clone(), rooms.clone(), teachers.clone());
+        async move {
+            let from = start + Duration::days(offset_days);
+            let requests = (0..CONCURRENCY).map(|i| {
+                let (room, teacher) = pick(i);
+                client
+                    .post(format!("http://{}/api/v1/base/classes", addr))
+                    .bearer_auth(&token)
+                    .json(&json!({
+                        "course_id": course,
+                        "teacher_ids": [teachers[teacher]],
+                        "room_id": rooms[room],
+                        "start_time": from + Duration::minutes(i as i64 * 5),
+                        "end_time": from + Duration::minutes(60 + i as i64 * 5),
+                        "max_capacity": 10,
+                    }))
+                    .send()
+            });
+            let mut created = 0;
+            let mut rejected = 0;
+            for res in futures::future::join_all(requests).await {
+                let res = res.unwrap();
+                match res.status() {
+                    StatusCode::OK => created += 1,
+                    StatusCode::CONFLICT => {
+                        let body: Value = res.json().await.unwrap();
+                        assert_eq!(body["code"], "schedule.conflict");
+                        rejected += 1;
+                    }
+                    other => panic!("unexpected status {}: {}", other, res.text().await.unwrap()),
+                }
+            }
+            (created, rejected)
+        }
+    };
+
+    // 1. 同一教室, 不同老师
+    assert_eq!(race(0, |i| (0, i)).await, (1, CONCURRENCY - 1));
+    // 2. 同一老师, 不同教室
+    assert_eq!(race(1, |i| (i, 0)).await, (1, CONCURRENCY - 1));
+
+    let overlapping: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM classes a
+        JOIN classes b ON a.id < b.id AND a.start_time < b.end_time AND a.end_time > b.start_time
+        WHERE a.hq_id = $1 AND b.hq_id = $1
+          AND (a.room_id = b.room_id OR EXISTS (
+              SELECT 1 FROM class_teachers ta JOIN class_teachers tb ON ta.teacher_id = tb.teacher_id
+              WHERE ta.class_id = a.id AND tb.class_id = b.id))
+        "#,
+    )
+    .bind(hq)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(overlapping, 0);
+
+    cleanup_tenant(&pool, hq).await;
+}
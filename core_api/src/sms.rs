@@ -0,0 +1,67 @@
+/*
+ * src/sms.rs
+ * 职责: 短信发送
+ * 1. SmsProvider: 发送验证码, 接入短信服务商时实现该 trait 并在 sms_from_env 中注册
+ * 2. LogSms: 本地开发用 (SMS_PROVIDER=log), 验证码只打印到日志
+ */
+
+use std::sync::Arc;
+
+use axum::{async_trait, http::StatusCode};
+
+use crate::error::AppError;
+
+#[derive(Debug)]
+pub enum SmsError {
+    NotConfigured, // 未配置短信服务商
+}
+
+impl From<SmsError> for AppError {
+    fn from(e: SmsError) -> Self {
+        match e {
+            SmsError::NotConfigured => AppError::Custom(StatusCode::SERVICE_UNAVAILABLE, "sms.not_configured"),
+        }
+    }
+}
+
+#[async_trait]
+pub trait SmsProvider: Send + Sync {
+    async fn send_code(&self, phone_number: &str, code: &str) -> Result<(), SmsError>;
+}
+
+// --- 本地开发: 只写日志 ---
+
+pub struct LogSms;
+
+#[async_trait]
+impl SmsProvider for LogSms {
+    async fn send_code(&self, phone_number: &str, code: &str) -> Result<(), SmsError> {
+        tracing::info!("[SMS] verification code for {}: {}", phone_number, code);
+        Ok(())
+    }
+}
+
+// --- 未配置: 拒绝发送, 避免生产环境静默吞掉验证码 ---
+
+pub struct UnconfiguredSms;
+
+#[async_trait]
+impl SmsProvider for UnconfiguredSms {
+    async fn send_code(&self, _phone_number: &str, _code: &str) -> Result<(), SmsError> {
+        Err(SmsError::NotConfigured)
+    }
+}
+
+// 启动时读取: SMS_PROVIDER=log 用日志模拟
+pub fn sms_from_env() -> Arc<dyn SmsProvider> {
+    match std::env::var("SMS_PROVIDER").unwrap_or_default().trim() {
+        "log" => {
+            tracing::warn!("SMS_PROVIDER=log: verification codes are written to the log, do not use in production");
+            Arc::new(LogSms)
+        }
+        _ => {
+            tracing::warn!("SMS_PROVIDER is not set, SMS verification is disabled");
+            Arc::new(UnconfiguredSms)
+        }
+    }
+}
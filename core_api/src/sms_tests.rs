@@ -0,0 +1,198 @@
+/*
+ * src/sms_tests.rs
+ * 职责: 短信验证码绑定手机号集成测试
+ * 1. 用记录型 SmsProvider 截获下发的验证码
+ * 2. 覆盖: 手机号格式 / 重发间隔 / IP 限流 / 错误验证码 / 错误次数上限 / 直接绑定 / 合并到已有档案
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::async_trait;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::handlers::AppState;
+use crate::sms::{SmsError, SmsProvider};
+use crate::test_support::{cleanup_tenant, seed_tenant, spawn_app, test_pool, test_state};
+
+#[derive(Default)]
+struct CaptureSms(Mutex<HashMap<String, String>>);
+
+#[async_trait]
+impl SmsProvider for CaptureSms {
+    async fn send_code(&self, phone_number: &str, code: &str) -> Result<(), SmsError> {
+        self.0.lock().unwrap().insert(phone_number.to_string(), code.to_string());
+        Ok(())
+    }
+}
+
+// 验证码按手机号存, 不属于任何租户
+async fn cleanup(pool: &PgPool, hq: Uuid, phones: &[&str]) {
+    for phone in phones {
+        sqlx::query("DELETE FROM sms_verification_codes WHERE phone_number = $1").bind(phone).execute(pool).await.unwrap();
+    }
+    cleanup_tenant(pool, hq).await;
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn bind_phone_requires_sms_code_and_merges_existing_customer() {
+    let pool = test_pool(5).await;
+
+    let sms = Arc::new(CaptureSms::default());
+    let addr = spawn_app(AppState { sms: sms.clone(), ..test_state(&pool) }).await;
+
+    let tenant = seed_tenant(&pool, "sms-test").await;
+    let (hq, base) = (tenant.hq, tenant.base);
+    let tag = hq.simple().to_string();
+    let digits = hq.as_u128().to_string();
+    let (phone_new, phone_existing) = (format!("13{}", &digits[..9]), format!("15{}", &digits[..9]));
+    let blocked_ip_phone = format!("17{}", &digits[..9]);
+
+    // 前台录入的家长档案 (有学员), 之后家长用微信登录并验证同一手机号
+    let existing = Uuid::new_v4();
+    sqlx::query("INSERT INTO customers (id, hq_id, base_id, phone_number, name) VALUES ($1, $2, $3, $4, 'front desk parent')")
+        .bind(existing)
+        .bind(hq)
+        .bind(base)
+        .bind(&phone_existing)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO participants (id, hq_id, customer_id, name) VALUES ($1, $2, $3, 'kid')")
+        .bind(Uuid::new_v4())
+        .bind(hq)
+        .bind(existing)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+    let post = |path: &str, token: String, body: Value| {
+        let req = client.post(url(path)).bearer_auth(token).json(&body);
+        async move { req.send().await.unwrap() }
+    };
+    let code_of = |phone: &str| sms.0.lock().unwrap().get(phone).cloned().unwrap();
+    // 跳过重发间隔, 让下一次下发可以立即进行
+    let age_codes = |phone: String| {
+        let pool = pool.clone();
+        async move {
+            sqlx::query("UPDATE sms_verification_codes SET created_at = created_at - INTERVAL '2 minutes' WHERE phone_number = $1")
+                .bind(phone)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+    };
+
+    let login: Value = client
+        .post(url("/api/v1/auth/wechat-login"))
+        .json(&json!({"code": format!("sms-{}", tag), "base_id": base}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let token = login["token"].as_str().unwrap().to_string();
+    let wechat_customer: Uuid = login["customer"]["id"].as_str().unwrap().parse().unwrap();
+
+    // 1. 手机号格式 / 重发间隔
+    let bad = post("/api/v1/customer/sms-code", token.clone(), json!({"phone_number": "12345"})).await;
+    assert_eq!(bad.status(), reqwest::StatusCode::BAD_REQUEST);
+    let sent = post("/api/v1/customer/sms-code", token.clone(), json!({"phone_number": phone_new})).await;
+    assert_eq!(sent.status(), reqwest::StatusCode::OK);
+    let again = post("/api/v1/customer/sms-code", token.clone(), json!({"phone_number": phone_new})).await;
+    assert_eq!(again.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+
+    // 2. 未验证 / 验证码错误不能绑定; 错误 5 次后正确的验证码也作废
+    let code = code_of(&phone_new);
+    let wrong = if code == "000000" { "111111" } else { "000000" };
+    for _ in 0..5 {
+        let r = post("/api/v1/customer/bind-phone", token.clone(), json!({"phone_number": phone_new, "code": wrong})).await;
+        assert_eq!(r.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+    let locked = post("/api/v1/customer/bind-phone", token.clone(), json!({"phone_number": phone_new, "code": code})).await;
+    assert_eq!(locked.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 3. 新验证码绑定未建档的手机号: 直接写入, 验证码只能用一次
+    age_codes(phone_new.clone()).await;
+    post("/api/v1/customer/sms-code", token.clone(), json!({"phone_number": phone_new})).await;
+    let code = code_of(&phone_new);
+    let bound: Value = post("/api/v1/customer/bind-phone", token.clone(), json!({"phone_number": phone_new, "code": code}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(bound["merged"], false);
+    assert!(bound.get("token").is_none());
+    let reuse = post("/api/v1/customer/bind-phone", token.clone(), json!({"phone_number": phone_new, "code": code})).await;
+    assert_eq!(reuse.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // 4. 验证已建档的手机号: 微信客户并入原档案, 旧 Token 失效, 新 Token 属于原档案
+    post("/api/v1/customer/sms-code", token.clone(), json!({"phone_number": phone_existing})).await;
+    let merged: Value = post(
+        "/api/v1/customer/bind-phone",
+        token.clone(),
+        json!({"phone_number": phone_existing, "code": code_of(&phone_existing)}),
+    )
+    .await
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(merged["merged"], true);
+    assert_eq!(merged["customer_id"], existing.to_string());
+    let new_token = merged["token"].as_str().unwrap().to_string();
+
+    let old = client.get(url("/api/v1/customer/profile")).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(old.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let profile = client.get(url("/api/v1/customer/profile")).bearer_auth(&new_token).send().await.unwrap();
+    assert_eq!(profile.status(), reqwest::StatusCode::OK);
+
+    let gone: bool = sqlx::query_scalar("SELECT NOT EXISTS (SELECT 1 FROM customers WHERE id = $1)")
+        .bind(wechat_customer)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(gone);
+    let (openid, account_owner): (Option<String>, Uuid) = sqlx::query_as(
+        "SELECT c.wechat_openid, a.customer_id FROM customer_wechat_accounts a JOIN customers c ON c.id = a.customer_id WHERE a.openid = $1",
+    )
+    .bind(format!("mock_openid_sms-{}", tag))
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(account_owner, existing);
+    assert_eq!(openid, Some(format!("mock_openid_sms-{}", tag)));
+
+    // 再次微信登录直接进入原档案
+    let relogin: Value = client
+        .post(url("/api/v1/auth/wechat-login"))
+        .json(&json!({"code": format!("sms-{}", tag)}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(relogin["customer"]["id"], existing.to_string());
+
+    // 5. 同一 IP 1 小时内下发过多
+    sqlx::query(
+        "INSERT INTO sms_verification_codes (phone_number, purpose, code_hash, request_ip, expires_at) \
+         SELECT $1, 'bind_phone', 'x', '127.0.0.1', NOW() FROM generate_series(1, 20)",
+    )
+    .bind(&blocked_ip_phone)
+    .execute(&pool)
+    .await
+    .unwrap();
+    age_codes(blocked_ip_phone.clone()).await;
+    let by_ip = post("/api/v1/customer/sms-code", new_token.clone(), json!({"phone_number": format!("18{}", &digits[..9])})).await;
+    assert_eq!(by_ip.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+
+    cleanup(&pool, hq, &[&phone_new, &phone_existing, &blocked_ip_phone]).await;
+}
@@ -0,0 +1,163 @@
+/*
+ * src/tenant.rs
+ * 职责: 租户隔离 (总部 hq_id / 基地 base_id)
+ * 1. TenantScope 由 Claims 派生, handler 通过它校验 "路径 / 请求体里的 id 是否属于当前租户"
+ * 2. Owned 列出需要按主键访问的资源, 每种资源的归属查询写死在这里, 不接受外部拼接
+ */
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+};
+use sqlx::{PgExecutor, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::Claims;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TenantScope {
+    pub hq_id: Uuid,
+    pub base_id: Option<Uuid>, // None: 总部账号, 可访问本总部下所有基地
+}
+
+// --- 按主键访问的资源 ---
+#[derive(Debug, Clone, Copy)]
+pub enum Owned {
+    Base(Uuid),
+    User(Uuid),
+    TeacherAvailability(Uuid),
+    Course(Uuid),
+    Room(Uuid),
+    Class(Uuid),
+    Customer(Uuid),
+    Participant(Uuid),
+    Order(Uuid),
+    PaymentRecord(Uuid),
+    ProcurementOrder(Uuid),
+    QrcodeBatch(Uuid),
+    TrialClass(Uuid),
+    Lead(Uuid),
+    AssetType(Uuid),
+    Product(Uuid),
+    Material(Uuid),
+    Enrollment(Uuid),
+    Tier(Uuid),
+    Invoice(Uuid),
+    OnlinePayment(Uuid),
+    LeaveRequest(Uuid),
+}
+
+impl Owned {
+    // 返回 (hq_id, base_id); base_id 为 NULL 表示总部级资源 (如课程、卡种)
+    fn owner_query(&self) -> (&'static str, Uuid) {
+        match *self {
+            Owned::Base(id) => ("SELECT hq_id, id AS base_id FROM bases WHERE id = $1", id),
+            Owned::User(id) => ("SELECT hq_id, base_id FROM users WHERE id = $1", id),
+            Owned::TeacherAvailability(id) => (
+                "SELECT u.hq_id, u.base_id FROM teacher_availability ta JOIN users u ON ta.teacher_id = u.id WHERE ta.id = $1",
+                id,
+            ),
+            Owned::Course(id) => ("SELECT hq_id, NULL::uuid AS base_id FROM courses WHERE id = $1", id),
+            Owned::Room(id) => ("SELECT hq_id, base_id FROM rooms WHERE id = $1", id),
+            Owned::Class(id) => ("SELECT hq_id, base_id FROM classes WHERE id = $1", id),
+            Owned::Customer(id) => ("SELECT hq_id, base_id FROM customers WHERE id = $1", id),
+            Owned::Participant(id) => (
+                "SELECT p.hq_id, c.base_id FROM participants p JOIN customers c ON p.customer_id = c.id WHERE p.id = $1",
+                id,
+            ),
+            Owned::Order(id) => ("SELECT hq_id, base_id FROM orders WHERE id = $1", id),
+            Owned::PaymentRecord(id) => ("SELECT hq_id, base_id FROM finance_payment_records WHERE id = $1", id),
+            Owned::ProcurementOrder(id) => ("SELECT hq_id, base_id FROM procurement_orders WHERE id = $1", id),
+            Owned::QrcodeBatch(id) => ("SELECT hq_id, NULL::uuid AS base_id FROM qrcode_batches WHERE id = $1", id),
+            Owned::TrialClass(id) => (
+                "SELECT b.hq_id, tc.base_id FROM trial_classes tc JOIN bases b ON tc.base_id = b.id WHERE tc.id = $1",
+                id,
+            ),
+            Owned::Lead(id) => ("SELECT hq_id, base_id FROM leads WHERE id = $1", id),
+            Owned::AssetType(id) => ("SELECT hq_id, NULL::uuid AS base_id FROM asset_types WHERE id = $1", id),
+            Owned::Product(id) => ("SELECT hq_id, NULL::uuid AS base_id FROM hq_products WHERE id = $1", id),
+            Owned::Material(id) => ("SELECT hq_id, NULL::uuid AS base_id FROM materials WHERE id = $1", id),
+            Owned::Enrollment(id) => (
+                "SELECT e.hq_id, c.base_id FROM class_enrollments e JOIN classes c ON e.class_id = c.id WHERE e.id = $1",
+                id,
+            ),
+            Owned::Tier(id) => ("SELECT hq_id, NULL::uuid AS base_id FROM membership_tiers WHERE id = $1", id),
+            Owned::Invoice(id) => ("SELECT hq_id, base_id FROM invoices WHERE id = $1", id),
+            Owned::OnlinePayment(id) => ("SELECT hq_id, base_id FROM payment_transactions WHERE id = $1", id),
+            Owned::LeaveRequest(id) => ("SELECT hq_id, base_id FROM leave_requests WHERE id = $1", id),
+        }
+    }
+}
+
+impl TenantScope {
+    pub fn from_claims(claims: &Claims) -> Self {
+        Self { hq_id: claims.hq_id, base_id: claims.base_id }
+    }
+
+    // 资源必须属于本租户; 基地账号只能访问本基地的数据 (总部级资源也不行)
+    pub async fn ensure_owned<'e, E: PgExecutor<'e>>(&self, executor: E, resource: Owned) -> Result<(), AppError> {
+        self.check(executor, resource, false).await
+    }
+
+    // 可引用即可: 基地账号额外允许引用总部级资源 (如排课时选总部课程)
+    pub async fn ensure_visible<'e, E: PgExecutor<'e>>(&self, executor: E, resource: Owned) -> Result<(), AppError> {
+        self.check(executor, resource, true).await
+    }
+
+    async fn check<'e, E: PgExecutor<'e>>(&self, executor: E, resource: Owned, allow_shared: bool) -> Result<(), AppError> {
+        let (sql, id) = resource.owner_query();
+        let owner = sqlx::query_as::<_, (Uuid, Option<Uuid>)>(sql)
+            .bind(id)
+            .fetch_optional(executor)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to resolve owner of {:?}: {}", resource, e);
+                AppError::Status(StatusCode::INTERNAL_SERVER_ERROR)
+            })?;
+
+        // 不属于本租户时统一返回 404, 不暴露资源是否存在
+        match owner {
+            Some((hq_id, base_id)) if self.covers(hq_id, base_id, allow_shared) => Ok(()),
+            _ => Err(AppError::NotFound("resource.not_found")),
+        }
+    }
+
+    fn covers(&self, hq_id: Uuid, base_id: Option<Uuid>, allow_shared: bool) -> bool {
+        if hq_id != self.hq_id {
+            return false;
+        }
+        match (self.base_id, base_id) {
+            (None, _) => true,
+            (Some(mine), Some(theirs)) => mine == theirs,
+            (Some(_), None) => allow_shared,
+        }
+    }
+
+    // 列表查询追加租户条件: " AND {alias}.hq_id = ? [AND {alias}.base_id = ?]"
+    pub fn push_filter(&self, qb: &mut QueryBuilder<'_, Postgres>, alias: &str) {
+        qb.push(format!(" AND {}.hq_id = ", alias));
+        qb.push_bind(self.hq_id);
+        if let Some(base_id) = self.base_id {
+            qb.push(format!(" AND {}.base_id = ", alias));
+            qb.push_bind(base_id);
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for TenantScope
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Claims>()
+            .map(TenantScope::from_claims)
+            .ok_or(AppError::Unauthorized("auth.unauthorized"))
+    }
+}
@@ -0,0 +1,181 @@
+/*
+ * src/test_support.rs
+ * 职责: 集成测试 (src/<模块>_tests.rs) 共用的准备与清理
+ * 1. 连接 DATABASE_URL 指向的库并执行迁移
+ * 2. 默认全部用 mock 的 AppState, 个别测试用结构体更新语法替换其中的依赖
+ * 3. 在随机端口启动 api_routes, 与 main.rs 一样带上 ConnectInfo
+ * 4. 造测试租户 (总部 + 基地 + 角色) 和员工账号, 员工登录取 token
+ * 5. 按 hq_id 清理测试租户名下的全部数据
+ */
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::handlers::{hash_password, AppState};
+
+// --- 数据库 ---
+
+// 数据库测试都标了 #[ignore], 显式运行却没配 DATABASE_URL 时直接失败, 不能悄悄通过
+pub async fn test_pool(max_connections: u32) -> PgPool {
+    let database_url = std::env::var("DATABASE_URL")
+        .expect("DATABASE_URL must point to a migrated database to run the ignored integration tests");
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(max_connections)
+        .connect(&database_url)
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    pool
+}
+
+// auth_middleware 从环境变量读取密钥, 测试签发 token 时与之保持一致
+pub fn jwt_secret() -> String {
+    std::env::var("JWT_SECRET").unwrap_or_else(|_| "secret".to_string())
+}
+
+// --- 服务 ---
+
+pub fn test_state(pool: &PgPool) -> AppState {
+    AppState {
+        db_pool: pool.clone(),
+        jwt_secret: jwt_secret(),
+        ai_api_url: "http://127.0.0.1:9".to_string(),
+        http_client: reqwest::Client::new(),
+        wechat: Arc::new(crate::wechat::MockWechat),
+        sms: Arc::new(crate::sms::LogSms),
+        invoice: Arc::new(crate::invoice::MockInvoice::default()),
+        payment: Arc::new(crate::payment::SimulatedPay::default()),
+    }
+}
+
+// 在随机端口启动 API, 返回监听地址
+pub async fn spawn_app(state: AppState) -> SocketAddr {
+    let app = crate::api_routes(state.clone()).with_state(state);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap()
+    });
+    addr
+}
+
+// --- 造数 ---
+
+// 测试员工账号的统一密码
+pub const TEST_PASSWORD: &str = "test-pass-1";
+
+// 测试租户: 一个总部和一个基地, 预置总部管理员 / 基地管理员 / 老师三个角色
+pub struct TestTenant {
+    pub hq: Uuid,
+    pub base: Uuid,
+}
+
+// name 同时用作总部名和基地名前缀, 便于在库里认出残留数据来自哪个测试
+pub async fn seed_tenant(pool: &PgPool, name: &str) -> TestTenant {
+    let tenant = TestTenant { hq: Uuid::new_v4(), base: Uuid::new_v4() };
+    sqlx::query("INSERT INTO hqs (id, name) VALUES ($1, $2)").bind(tenant.hq).bind(name).execute(pool).await.unwrap();
+    sqlx::query("INSERT INTO bases (id, hq_id, name) VALUES ($1, $2, $3 || ' base')")
+        .bind(tenant.base)
+        .bind(tenant.hq)
+        .bind(name)
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO roles (hq_id, name_key) VALUES ($1, 'role.hq.admin'), ($1, 'role.base.admin'), ($1, 'role.teacher')")
+        .bind(tenant.hq)
+        .execute(pool)
+        .await
+        .unwrap();
+    tenant
+}
+
+impl TestTenant {
+    // 建员工账号 (密码 TEST_PASSWORD) 并授予角色; 总部管理员不挂基地, 其他角色挂在本租户的基地上
+    // 邮箱里带上 hq id, 按邮箱清理登录记录时能定位到本租户; 返回 (user_id, email)
+    pub async fn add_staff(&self, pool: &PgPool, role: &str) -> (Uuid, String) {
+        let id = Uuid::new_v4();
+        let email = format!("{}@{}.test", id.simple(), self.hq.simple());
+        let base = (role != "role.hq.admin").then_some(self.base);
+        sqlx::query("INSERT INTO users (id, hq_id, base_id, email, password_hash, full_name) VALUES ($1, $2, $3, $4, $5, $6)")
+            .bind(id)
+            .bind(self.hq)
+            .bind(base)
+            .bind(&email)
+            .bind(hash_password(TEST_PASSWORD.to_string()).await.unwrap())
+            .bind(role)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO user_roles (user_id, role_id) SELECT $1, id FROM roles WHERE hq_id = $2 AND name_key = $3")
+            .bind(id)
+            .bind(self.hq)
+            .bind(role)
+            .execute(pool)
+            .await
+            .unwrap();
+        (id, email)
+    }
+}
+
+// 员工用 TEST_PASSWORD 登录, 返回 access token
+pub async fn login(addr: SocketAddr, email: &str) -> String {
+    let res = reqwest::Client::new()
+        .post(format!("http://{}/api/v1/auth/login", addr))
+        .json(&json!({"email": email, "password": TEST_PASSWORD}))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.status().is_success(), "login failed for {}: {}", email, res.status());
+    let body: Value = res.json().await.unwrap();
+    body["token"].as_str().unwrap().to_string()
+}
+
+// --- 清理 ---
+
+// 没有 hq_id / base_id 列、需要经由父表定位租户的表 (其余的随父表级联删除)
+pub const INDIRECT_TABLES: [(&str, &str); 9] = [
+    ("user_roles", "user_id IN (SELECT id FROM users WHERE hq_id = $1)"),
+    ("teacher_availability", "teacher_id IN (SELECT id FROM users WHERE hq_id = $1)"),
+    ("schedule_draft_items", "draft_id IN (SELECT id FROM schedule_drafts WHERE hq_id = $1)"),
+    ("base_inventory", "base_id IN (SELECT id FROM bases WHERE hq_id = $1)"),
+    ("trial_classes", "base_id IN (SELECT id FROM bases WHERE hq_id = $1)"),
+    ("supply_order_items", "supply_order_id IN (SELECT id FROM supply_orders WHERE hq_id = $1)"),
+    ("procurement_items", "order_id IN (SELECT id FROM procurement_orders WHERE hq_id = $1)"),
+    ("qrcode_items", "batch_id IN (SELECT id FROM qrcode_batches WHERE hq_id = $1)"),
+    ("follow_up_records", "lead_id IN (SELECT id FROM leads WHERE hq_id = $1)"),
+];
+
+// 部分表对 hqs / bases 没有级联删除: 反复按 hq_id / base_id 清理, 直到总部记录能删掉
+pub async fn cleanup_tenant(pool: &PgPool, hq: Uuid) {
+    let mut filters: Vec<String> = INDIRECT_TABLES.iter().map(|(t, f)| format!("DELETE FROM {} WHERE {}", t, f)).collect();
+    let direct: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT format('DELETE FROM %I WHERE %s', c.table_name,
+                      CASE c.column_name WHEN 'hq_id' THEN 'hq_id = $1'
+                           ELSE 'base_id IN (SELECT id FROM bases WHERE hq_id = $1)' END)
+        FROM information_schema.columns c
+        JOIN information_schema.tables t ON t.table_name = c.table_name AND t.table_schema = c.table_schema
+        WHERE c.table_schema = 'public' AND t.table_type = 'BASE TABLE'
+          AND c.column_name IN ('hq_id', 'base_id') AND c.table_name NOT IN ('hqs', 'bases')
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .unwrap();
+    filters.extend(direct);
+    filters.push("DELETE FROM bases WHERE hq_id = $1".to_string());
+
+    for _ in 0..5 {
+        for sql in &filters {
+            let _ = sqlx::query(sql).bind(hq).execute(pool).await;
+        }
+        if sqlx::query("DELETE FROM hqs WHERE id = $1").bind(hq).execute(pool).await.is_ok() {
+            return;
+        }
+    }
+    panic!("failed to clean up test tenant {}", hq);
+}
@@ -0,0 +1,17 @@
+/*
+ * src/timezone.rs
+ * 职责: 业务时区 (北京时间, UTC+8, 无夏令时)
+ * 数据库统一存 UTC; 凡是按 "哪一天 / 星期几 / 几点" 判断的业务逻辑 (循环排课、老师可上课时段、提醒文案等)
+ * 一律用这里的时区换算, 不依赖服务器或数据库会话的本地时区
+ */
+
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+
+pub fn business_tz() -> FixedOffset {
+    FixedOffset::east_opt(8 * 3600).unwrap()
+}
+
+// UTC 时间在业务时区下的日期
+pub fn business_date(t: DateTime<Utc>) -> NaiveDate {
+    t.with_timezone(&business_tz()).date_naive()
+}
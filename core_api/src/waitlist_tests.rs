@@ -0,0 +1,114 @@
+/*
+ * src/waitlist_tests.rs
+ * 职责: 候补转正集成测试 (promote_waitlist)
+ * 候补期间已报名同时段其他课程的学员不转正 (保留候补), 空位顺延给下一位
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::handlers::promote_waitlist;
+use crate::test_support::{cleanup_tenant, seed_tenant, test_pool};
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn promotion_skips_waitlisters_with_overlapping_classes() {
+    let pool = test_pool(2).await;
+
+    let tenant = seed_tenant(&pool, "waitlist-test").await;
+    let (hq, base) = (tenant.hq, tenant.base);
+    let (course, room, customer) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let (target, other) = (Uuid::new_v4(), Uuid::new_v4());
+    let (busy_kid, free_kid) = (Uuid::new_v4(), Uuid::new_v4());
+    let start = Utc::now() + Duration::days(2);
+
+    sqlx::query("INSERT INTO courses (id, hq_id, name_key) VALUES ($1, $2, 'waitlist-test course')").bind(course).bind(hq).execute(&pool).await.unwrap();
+    sqlx::query("INSERT INTO rooms (id, hq_id, base_id, name) VALUES ($1, $2, $3, 'waitlist-test room')")
+        .bind(room)
+        .bind(hq)
+        .bind(base)
+        .execute(&pool)
+        .await
+        .unwrap();
+    // 目标课程 1 个名额且空着; 另一节课与它有半小时重叠
+    sqlx::query(
+        r#"
+        INSERT INTO classes (id, hq_id, base_id, course_id, room_id, start_time, end_time, max_capacity) VALUES
+            ($1, $3, $4, $5, $6, $7, $7 + INTERVAL '1 hour', 1),
+            ($2, $3, $4, $5, $6, $7 + INTERVAL '30 minutes', $7 + INTERVAL '90 minutes', 10)
+        "#,
+    )
+    .bind(target)
+    .bind(other)
+    .bind(hq)
+    .bind(base)
+    .bind(course)
+    .bind(room)
+    .bind(start)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO customers (id, hq_id, base_id, phone_number, name) VALUES ($1, $2, $3, $4, 'waitlist-test parent')")
+        .bind(customer)
+        .bind(hq)
+        .bind(base)
+        .bind(format!("1{:010}", hq.as_u128() % 10_000_000_000))
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO participants (id, hq_id, customer_id, name) VALUES ($1, $3, $4, 'busy'), ($2, $3, $4, 'free')")
+        .bind(busy_kid)
+        .bind(free_kid)
+        .bind(hq)
+        .bind(customer)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO class_enrollments (hq_id, class_id, participant_id, customer_id) VALUES ($1, $2, $3, $4)")
+        .bind(hq)
+        .bind(other)
+        .bind(busy_kid)
+        .bind(customer)
+        .execute(&pool)
+        .await
+        .unwrap();
+    // busy 先排上候补
+    sqlx::query(
+        r#"
+        INSERT INTO class_waitlist (hq_id, class_id, participant_id, customer_id, created_at) VALUES
+            ($1, $2, $3, $5, NOW() - INTERVAL '1 hour'),
+            ($1, $2, $4, $5, NOW())
+        "#,
+    )
+    .bind(hq)
+    .bind(target)
+    .bind(busy_kid)
+    .bind(free_kid)
+    .bind(customer)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let mut tx = pool.begin().await.unwrap();
+    let promoted = promote_waitlist(&mut tx, target).await.unwrap();
+    tx.commit().await.unwrap();
+    assert_eq!(promoted.len(), 1);
+
+    let enrolled: Vec<Uuid> = sqlx::query_scalar("SELECT participant_id FROM class_enrollments WHERE class_id = $1")
+        .bind(target)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(enrolled, vec![free_kid]);
+
+    let busy_status: String = sqlx::query_scalar("SELECT status FROM class_waitlist WHERE class_id = $1 AND participant_id = $2")
+        .bind(target)
+        .bind(busy_kid)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(busy_status, "waiting");
+
+    cleanup_tenant(&pool, hq).await;
+}
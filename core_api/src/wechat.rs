@@ -0,0 +1,201 @@
+/*
+ * src/wechat.rs
+ * 职责: 微信小程序服务端接口
+ * 1. WechatApi: jscode2session (登录 code 换 openid / session_key / unionid)
+ *    - WechatClient: 调用微信接口, 测试里把 api_base 指向本地模拟服务器
+ *    - MockWechat: 本地开发免配置登录 (WECHAT_PROVIDER=mock), 同一个 code 总是得到同一个 openid
+ * 2. decrypt_data: 用 session_key 解密 wx.getPhoneNumber / wx.getUserInfo 返回的 encryptedData
+ */
+
+use std::sync::Arc;
+
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use axum::{async_trait, http::StatusCode};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::AppError;
+
+const DEFAULT_API_BASE: &str = "https://api.weixin.qq.com";
+
+#[derive(Debug, Clone)]
+pub struct WechatSession {
+    pub openid: String,
+    pub session_key: String,
+    pub unionid: Option<String>, // 小程序绑定到开放平台后才会返回
+}
+
+#[derive(Debug)]
+pub enum WechatError {
+    NotConfigured,                        // 未配置 WECHAT_APP_ID / WECHAT_APP_SECRET
+    InvalidCode,                          // 40029 / 40163: code 无效或已使用
+    RateLimited,                          // 45011: 调用太频繁
+    Api { errcode: i64, errmsg: String }, // 其他微信错误码
+    Transport(String),                    // 网络 / 响应格式错误
+    Decrypt,                              // 加密数据无法解密或不是本小程序的数据
+}
+
+// 微信返回的原始错误只记日志, 不透传给前端
+impl From<WechatError> for AppError {
+    fn from(e: WechatError) -> Self {
+        match e {
+            WechatError::NotConfigured => AppError::Custom(StatusCode::SERVICE_UNAVAILABLE, "wechat.not_configured"),
+            WechatError::InvalidCode => AppError::Unauthorized("wechat.invalid_code"),
+            WechatError::RateLimited => AppError::Custom(StatusCode::TOO_MANY_REQUESTS, "wechat.rate_limited"),
+            WechatError::Api { errcode, errmsg } => {
+                tracing::warn!("WeChat API error {}: {}", errcode, errmsg);
+                AppError::Custom(StatusCode::BAD_GATEWAY, "wechat.api_failed")
+            }
+            WechatError::Transport(msg) => {
+                tracing::warn!("WeChat request failed: {}", msg);
+                AppError::Custom(StatusCode::BAD_GATEWAY, "wechat.api_failed")
+            }
+            WechatError::Decrypt => AppError::BadRequest("wechat.decrypt_failed"),
+        }
+    }
+}
+
+#[async_trait]
+pub trait WechatApi: Send + Sync {
+    // 小程序 appid, 用于校验加密数据的水印, 也是 customer_wechat_accounts.app_id
+    fn app_id(&self) -> &str;
+    async fn code2session(&self, code: &str) -> Result<WechatSession, WechatError>;
+}
+
+// --- 微信接口 ---
+
+pub struct WechatClient {
+    api_base: String,
+    app_id: String,
+    app_secret: String,
+    http: reqwest::Client,
+}
+
+impl WechatClient {
+    pub fn new(api_base: &str, app_id: &str, app_secret: &str, http: reqwest::Client) -> Self {
+        Self {
+            api_base: api_base.trim_end_matches('/').to_string(),
+            app_id: app_id.to_string(),
+            app_secret: app_secret.to_string(),
+            http,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Code2SessionResponse {
+    openid: Option<String>,
+    session_key: Option<String>,
+    unionid: Option<String>,
+    #[serde(default)]
+    errcode: i64,
+    #[serde(default)]
+    errmsg: String,
+}
+
+#[async_trait]
+impl WechatApi for WechatClient {
+    fn app_id(&self) -> &str {
+        &self.app_id
+    }
+
+    async fn code2session(&self, code: &str) -> Result<WechatSession, WechatError> {
+        if self.app_id.is_empty() || self.app_secret.is_empty() {
+            return Err(WechatError::NotConfigured);
+        }
+        if code.trim().is_empty() {
+            return Err(WechatError::InvalidCode);
+        }
+
+        // 微信返回的 Content-Type 是 text/plain, 先取文本再解析
+        let body = self
+            .http
+            .get(format!("{}/sns/jscode2session", self.api_base))
+            .query(&[
+                ("appid", self.app_id.as_str()),
+                ("secret", self.app_secret.as_str()),
+                ("js_code", code.trim()),
+                ("grant_type", "authorization_code"),
+            ])
+            .send()
+            .await
+            .map_err(|e| WechatError::Transport(e.to_string()))?
+            .text()
+            .await
+            .map_err(|e| WechatError::Transport(e.to_string()))?;
+
+        let resp: Code2SessionResponse =
+            serde_json::from_str(&body).map_err(|e| WechatError::Transport(format!("{}: {}", e, body)))?;
+
+        match resp.errcode {
+            0 => {}
+            40029 | 40163 => return Err(WechatError::InvalidCode),
+            45011 => return Err(WechatError::RateLimited),
+            errcode => return Err(WechatError::Api { errcode, errmsg: resp.errmsg }),
+        }
+
+        match (resp.openid, resp.session_key) {
+            (Some(openid), Some(session_key)) => Ok(WechatSession { openid, session_key, unionid: resp.unionid }),
+            _ => Err(WechatError::Transport(format!("missing openid / session_key: {}", body))),
+        }
+    }
+}
+
+// --- 本地开发 ---
+
+pub struct MockWechat;
+
+const MOCK_APP_ID: &str = "mock-appid";
+
+#[async_trait]
+impl WechatApi for MockWechat {
+    fn app_id(&self) -> &str {
+        MOCK_APP_ID
+    }
+
+    async fn code2session(&self, code: &str) -> Result<WechatSession, WechatError> {
+        if code.trim().is_empty() {
+            return Err(WechatError::InvalidCode);
+        }
+        Ok(WechatSession {
+            openid: format!("mock_openid_{}", code.trim()),
+            session_key: STANDARD.encode(b"mock-session-key"),
+            unionid: None,
+        })
+    }
+}
+
+// 启动时读取: WECHAT_PROVIDER=mock 用本地模拟, 否则调用微信 (WECHAT_API_BASE 可覆盖接口地址)
+pub fn wechat_from_env(http: reqwest::Client) -> Arc<dyn WechatApi> {
+    if std::env::var("WECHAT_PROVIDER").map(|v| v.trim() == "mock").unwrap_or(false) {
+        tracing::warn!("WECHAT_PROVIDER=mock: WeChat login accepts any code, do not use in production");
+        return Arc::new(MockWechat);
+    }
+
+    let env = |key: &str| std::env::var(key).unwrap_or_default();
+    let api_base = std::env::var("WECHAT_API_BASE").unwrap_or_else(|_| DEFAULT_API_BASE.to_string());
+    let client = WechatClient::new(&api_base, &env("WECHAT_APP_ID"), &env("WECHAT_APP_SECRET"), http);
+    if client.app_id.is_empty() {
+        tracing::warn!("WECHAT_APP_ID is not set, WeChat login is disabled");
+    }
+    Arc::new(client)
+}
+
+// 解密 encryptedData (AES-128-CBC, PKCS#7), 并校验水印里的 appid 是本小程序
+pub fn decrypt_data(app_id: &str, session_key: &str, encrypted_data: &str, iv: &str) -> Result<Value, WechatError> {
+    let key = STANDARD.decode(session_key).map_err(|_| WechatError::Decrypt)?;
+    let iv = STANDARD.decode(iv).map_err(|_| WechatError::Decrypt)?;
+    let mut buf = STANDARD.decode(encrypted_data).map_err(|_| WechatError::Decrypt)?;
+
+    let plain = cbc::Decryptor::<aes::Aes128>::new_from_slices(&key, &iv)
+        .map_err(|_| WechatError::Decrypt)?
+        .decrypt_padded_mut::<Pkcs7>(&mut buf)
+        .map_err(|_| WechatError::Decrypt)?;
+
+    let value: Value = serde_json::from_slice(plain).map_err(|_| WechatError::Decrypt)?;
+    if value["watermark"]["appid"].as_str() != Some(app_id) {
+        return Err(WechatError::Decrypt);
+    }
+    Ok(value)
+}
@@ -0,0 +1,168 @@
+/*
+ * src/wechat_tests.rs
+ * 职责: 微信登录集成测试
+ * 1. 本地起一个模拟的 jscode2session 服务, WechatClient 指向它, 走真实的 HTTP 调用
+ * 2. 覆盖: 同一微信重复登录不重复建客户 / 无效 code / 按 unionid 关联已有家长 / 解密手机号 / 水印校验
+ * 需要 DATABASE_URL 指向已执行迁移的库, 默认忽略, 用 cargo test -- --ignored 运行
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use aes::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+use axum::{extract::Query, routing::get, Router};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::handlers::AppState;
+use crate::wechat::WechatClient;
+use crate::test_support::{cleanup_tenant, seed_tenant, spawn_app, test_pool, test_state};
+
+const APP_ID: &str = "wx-test-app";
+const APP_SECRET: &str = "wx-test-secret";
+// base64("session-key-0001"), 16 字节
+const SESSION_KEY: &str = "c2Vzc2lvbi1rZXktMDAwMQ==";
+
+// 模拟微信 jscode2session: code-* 正常返回, code-union-* 额外带 unionid, 其他 code 报 40029
+async fn mock_jscode2session(Query(q): Query<HashMap<String, String>>) -> String {
+    if q.get("appid").map(String::as_str) != Some(APP_ID) || q.get("secret").map(String::as_str) != Some(APP_SECRET) {
+        return json!({"errcode": 40013, "errmsg": "invalid appid"}).to_string();
+    }
+    let code = q.get("js_code").cloned().unwrap_or_default();
+    if let Some(rest) = code.strip_prefix("code-union-") {
+        json!({"openid": format!("openid-{}", code), "session_key": SESSION_KEY, "unionid": format!("union-{}", rest)}).to_string()
+    } else if code.starts_with("code-") {
+        json!({"openid": format!("openid-{}", code), "session_key": SESSION_KEY}).to_string()
+    } else {
+        json!({"errcode": 40029, "errmsg": "invalid code"}).to_string()
+    }
+}
+
+// 按微信的方式加密: AES-128-CBC + PKCS#7, base64 输出
+fn encrypt(value: &Value) -> (String, String) {
+    let key = STANDARD.decode(SESSION_KEY).unwrap();
+    let iv = *b"0123456789abcdef";
+    let plain = value.to_string().into_bytes();
+    let mut buf = vec![0u8; plain.len() + 16];
+    let cipher = cbc::Encryptor::<aes::Aes128>::new_from_slices(&key, &iv)
+        .unwrap()
+        .encrypt_padded_b2b_mut::<Pkcs7>(&plain, &mut buf)
+        .unwrap();
+    (STANDARD.encode(cipher), STANDARD.encode(iv))
+}
+
+#[tokio::test]
+#[ignore = "needs DATABASE_URL"]
+async fn wechat_login_uses_code2session_and_links_identities() {
+    let pool = test_pool(5).await;
+
+    let mock = Router::new().route("/sns/jscode2session", get(mock_jscode2session));
+    let mock_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mock_base = format!("http://{}", mock_listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(mock_listener, mock).await.unwrap() });
+
+    let http_client = reqwest::Client::new();
+    let state = AppState {
+        wechat: Arc::new(WechatClient::new(&mock_base, APP_ID, APP_SECRET, http_client.clone())),
+        http_client,
+        ..test_state(&pool)
+    };
+    let addr = spawn_app(state).await;
+
+    let tenant = seed_tenant(&pool, "wechat-test").await;
+    let (hq, base) = (tenant.hq, tenant.base);
+    let tag = hq.simple().to_string();
+
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+    let login = |body: Value| {
+        let client = client.clone();
+        let url = url("/api/v1/auth/wechat-login");
+        async move { client.post(url).json(&body).send().await.unwrap() }
+    };
+
+    // 1. 同一微信两次登录: 第一次建客户, 第二次命中同一个客户 (原实现每次都伪造新 openid)
+    let code = format!("code-{}", tag);
+    let first: Value = login(json!({"code": code, "base_id": base})).await.json().await.unwrap();
+    assert_eq!(first["is_new_user"], true);
+    assert_eq!(first["customer"]["wechat_openid"], format!("openid-{}", code));
+    assert!(first["refresh_token"].is_string());
+    let second: Value = login(json!({"code": code})).await.json().await.unwrap();
+    assert_eq!(second["is_new_user"], false);
+    assert_eq!(second["customer"]["id"], first["customer"]["id"]);
+
+    let stored_key: String = sqlx::query_scalar(
+        "SELECT session_key FROM customer_wechat_accounts WHERE app_id = $1 AND openid = $2",
+    )
+    .bind(APP_ID)
+    .bind(format!("openid-{}", code))
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(stored_key, SESSION_KEY);
+
+    // 2. 无效 code
+    let invalid = login(json!({"code": "bogus", "base_id": base})).await;
+    assert_eq!(invalid.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // 3. 已在其他微信应用 (如公众号) 登录过的家长: 按 unionid 关联, 不新建
+    let linked = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO customers (id, hq_id, base_id, phone_number, name, wechat_unionid) VALUES ($1, $2, $3, '', 'oa parent', $4)",
+    )
+    .bind(linked)
+    .bind(hq)
+    .bind(base)
+    .bind(format!("union-{}", tag))
+    .execute(&pool)
+    .await
+    .unwrap();
+    let by_union: Value = login(json!({"code": format!("code-union-{}", tag), "base_id": base})).await.json().await.unwrap();
+    assert_eq!(by_union["is_new_user"], false);
+    assert_eq!(by_union["customer"]["id"], linked.to_string());
+    assert_eq!(by_union["needs_phone"], true);
+
+    // 4. 解密 getPhoneNumber 数据绑定手机号; 水印不是本小程序的数据拒绝
+    let token = by_union["token"].as_str().unwrap();
+    let phone = format!("139{}", &hq.as_u128().to_string()[..8]);
+    let (data, iv) = encrypt(&json!({"phoneNumber": phone, "purePhoneNumber": phone, "countryCode": "86",
+                                     "watermark": {"appid": "wx-other-app", "timestamp": 0}}));
+    let foreign = client
+        .post(url("/api/v1/customer/wechat-phone"))
+        .bearer_auth(token)
+        .json(&json!({"encrypted_data": data, "iv": iv}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(foreign.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let (data, iv) = encrypt(&json!({"phoneNumber": phone, "purePhoneNumber": phone, "countryCode": "86",
+                                     "watermark": {"appid": APP_ID, "timestamp": 0}}));
+    let bound = client
+        .post(url("/api/v1/customer/wechat-phone"))
+        .bearer_auth(token)
+        .json(&json!({"encrypted_data": data, "iv": iv}))
+        .send()
+        .await
+        .unwrap();
+    assert!(bound.status().is_success(), "bind phone failed: {}", bound.status());
+    let stored_phone: String = sqlx::query_scalar("SELECT phone_number FROM customers WHERE id = $1")
+        .bind(linked)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored_phone, phone);
+
+    // 5. 新微信登录时一并提交手机号: 该手机号已建档则关联到原客户
+    let (data, iv) = encrypt(&json!({"phoneNumber": phone, "purePhoneNumber": phone, "countryCode": "86",
+                                     "watermark": {"appid": APP_ID, "timestamp": 0}}));
+    let by_phone: Value = login(json!({"code": format!("code-phone-{}", tag), "base_id": base, "encrypted_data": data, "iv": iv}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(by_phone["customer"]["id"], linked.to_string());
+
+    cleanup_tenant(&pool, hq).await;
+}
@@ -15,9 +15,12 @@ App({
     // ========================================
     const DEV_MODE_AUTO_LOGIN = true;  // 是否启用自动登录（跳过登录页）
     const DEV_USER_TYPE = 'B_END';     // ✅ 测试用户类型: 'B_END' | 'C_END'
+    // 模拟登录 Token: 后端以 DEV_IMPERSONATION=true 启动后, 由总部管理员调用
+    // POST /api/v1/hq/impersonations 签发 (30 分钟有效), 粘贴到这里; 留空则不自动登录
+    const DEV_IMPERSONATION_TOKEN = '';
     // ========================================
 
-    if (DEV_MODE_AUTO_LOGIN) {
+    if (DEV_MODE_AUTO_LOGIN && DEV_IMPERSONATION_TOKEN) {
       const YABAHU_BASE_ID = '841e6e10-4507-467e-af42-ebbcff2dbb6e';
       const YABAHU_HQ_ID = 'dc53fe5d-1212-4259-8350-bb443df1717e';
 
@@ -26,7 +29,7 @@ App({
       if (DEV_USER_TYPE === 'B_END') {
         // ===== B端测试：内部员工（总部财务/校区管理等） =====
         console.log('🔧 [DEV] B端模式 - 模拟总部财务人员登录');
-        testToken = `dev_token_hq_finance`;
+        testToken = DEV_IMPERSONATION_TOKEN;
         testUserInfo = {
           id: '00000000-0000-0000-0000-000000000001', // 假设的HQ财务ID
           name: '张财务(测试)',
@@ -38,7 +41,7 @@ App({
       } else {
         // ===== C端测试：客户（家长扫码注册） =====
         console.log('🔧 [DEV] C端模式 - 模拟哑巴湖基地家长');
-        testToken = `dev_token_${YABAHU_BASE_ID}`;
+        testToken = DEV_IMPERSONATION_TOKEN;
         testUserInfo = {
           id: '02352317-d905-4429-9bc7-577e4907660c', // 李希圣
           name: '李希圣(家长)',
@@ -28,6 +28,10 @@ Page({
       await AuthService.login(email, password);
       // Login success adds its own route logic in AuthService
     } catch (err) {
+      if (err && err.code === 'MUST_CHANGE_PASSWORD') {
+        this.promptNewPassword(err.resetToken);
+        return;
+      }
       wx.showToast({
         title: err.message || '登录失败',
         icon: 'none'
@@ -37,6 +41,25 @@ Page({
     }
   },
 
+  // 首次登录 / 密码被重置: 设置新密码后自动重新登录
+  promptNewPassword(resetToken) {
+    wx.showModal({
+      title: '请设置新密码',
+      editable: true,
+      placeholderText: '至少 8 位, 包含字母和数字',
+      success: async (res) => {
+        if (!res.confirm || !res.content) return;
+        try {
+          await AuthService.completePasswordReset(resetToken, res.content);
+          this.setData({ password: res.content });
+          this.handleLogin();
+        } catch (e) {
+          wx.showToast({ title: '密码不符合要求, 请重新登录后再试', icon: 'none' });
+        }
+      }
+    });
+  },
+
   clearCache() {
     userStore.logout();
     wx.showToast({ title: '已重置', icon: 'none' });
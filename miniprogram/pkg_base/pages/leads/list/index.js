@@ -39,7 +39,7 @@ Page({
 
             const params = {
                 page: this.data.page,
-                limit: 20
+                page_size: 20
             };
 
             // 根据tab筛选状态
@@ -47,11 +47,12 @@ Page({
                 params.status = this.data.currentTab;
             }
 
-            const newLeads = await LeadService.getLeads(params);
+            const res = await LeadService.getLeads(params);
+            const newLeads = res.items || [];
 
             this.setData({
                 leads: append ? [...this.data.leads, ...newLeads] : newLeads,
-                hasMore: newLeads.length >= 20,
+                hasMore: res.has_more,
                 loading: false
             });
 
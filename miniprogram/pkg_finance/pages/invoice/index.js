@@ -31,8 +31,8 @@ Page({
 
     async fetchData() {
         try {
-            const res = await request.get('/api/v1/finance/orders');
-            const allOrders = (res || []).map(item => ({
+            const res = await request.get('/api/v1/finance/orders', { page_size: 500 });
+            const allOrders = ((res && res.items) || []).map(item => ({
                 ...item,
                 total_amount_fmt: (item.total_amount_cents / 100).toFixed(2),
                 payment_status_text: this.getPayStatusText(item.payment_status),
@@ -29,9 +29,9 @@ Page({
 
     async fetchData() {
         try {
-            const res = await request.get('/api/v1/finance/expenses');
+            const res = await request.get('/api/v1/finance/expenses', { page_size: 500 });
 
-            const list = (res || []).map(item => {
+            const list = ((res && res.items) || []).map(item => {
                 const catName = CAT_MAP[item.category] || item.category || '未知';
                 return {
                     ...item,
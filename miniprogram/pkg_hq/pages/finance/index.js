@@ -59,9 +59,9 @@ Page({
             const res = await FinanceService.getPaymentRecords({
                 status: 'PENDING',
                 page: 1,
-                limit: 5
+                page_size: 5
             });
-            this.setData({ records: res });
+            this.setData({ records: res.items || [] });
         } catch (err) {
             console.error('Fetch records failed', err);
         }
@@ -7,6 +7,7 @@ Page({
             alerts: []
         },
         keyPersonnel: [],
+        lockedAccounts: [],
         currentRankTab: 'purchase', // purchase | activity
         purchaseRankings: [],
         activityRankings: [],
@@ -24,6 +25,9 @@ Page({
             // Load risk stats
             const riskStats = await StaffService.getRiskStats();
 
+            // Load accounts locked by repeated wrong passwords
+            const lockedAccounts = await StaffService.getLockedAccounts();
+
             // Load key personnel
             const keyPersonnel = await StaffService.getKeyPersonnel();
 
@@ -37,6 +41,11 @@ Page({
                     alerts: riskStats.alerts || []
                 },
                 keyPersonnel: keyPersonnel || [],
+                lockedAccounts: (lockedAccounts || []).map(a => {
+                    const until = new Date(a.locked_until);
+                    const pad = n => String(n).padStart(2, '0');
+                    return { ...a, lockedUntilText: `${pad(until.getHours())}:${pad(until.getMinutes())}` };
+                }),
                 purchaseRankings: purchaseRankings || [],
                 activityRankings: activityRankings || [],
                 loading: false
@@ -55,6 +64,25 @@ Page({
     },
 
 
+    onUnlock(e) {
+        const { id, name } = e.currentTarget.dataset;
+        wx.showModal({
+            title: '解除锁定',
+            content: `确认解除 ${name} 的登录锁定?`,
+            success: async (res) => {
+                if (!res.confirm) return;
+                try {
+                    await StaffService.unlockAccount(id);
+                    wx.showToast({ title: '已解锁', icon: 'success' });
+                    this.loadData();
+                } catch (err) {
+                    console.error('解锁失败', err);
+                    wx.showToast({ title: '解锁失败', icon: 'none' });
+                }
+            }
+        });
+    },
+
     onTabChange(e) {
         const tab = e.currentTarget.dataset.tab;
         this.setData({ currentRankTab: tab });
@@ -1,6 +1,7 @@
 // services/AuthService.js
 
 import { userStore } from '../store/userStore';
+import { saveSession } from '../utils/session';
 
 /**
  * 配置角色与首页路径的映射关系
@@ -28,7 +29,16 @@ class AuthService {
     const request = require('./request').default; // Circular dependency handling
     try {
       const res = await request.post('/api/v1/auth/login', { email, password });
-      const { token } = res;
+
+      // 管理员创建 / 重置的账号: 先设置新密码 (凭一次性令牌), 再重新登录
+      if (res.must_change_password) {
+        const err = new Error('首次登录请先修改密码');
+        err.code = 'MUST_CHANGE_PASSWORD';
+        err.resetToken = res.reset_token;
+        throw err;
+      }
+
+      const { token, refresh_token } = res;
 
       if (!token) throw new Error('Token missing');
 
@@ -49,6 +59,7 @@ class AuthService {
 
       // 存入 Store
       userStore.setLoginSuccess(token, userInfo, frontendRole);
+      saveSession({ token, refresh_token });
 
       // 初始化路由
       this.initAppRoute();
@@ -60,6 +71,16 @@ class AuthService {
     }
   }
 
+  /**
+   * 凭一次性令牌设置新密码 (至少 8 位, 包含字母和数字)
+   * @param {String} resetToken - 登录接口返回的 reset_token
+   * @param {String} newPassword
+   */
+  async completePasswordReset(resetToken, newPassword) {
+    const request = require('./request').default;
+    await request.post('/api/v1/auth/password/reset', { token: resetToken, new_password: newPassword });
+  }
+
   /**
    * 解析 JWT Token (Base64Url -> JSON)
    */
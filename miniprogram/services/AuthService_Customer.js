@@ -1,5 +1,6 @@
 // services/AuthService_Customer.js - C端认证服务
 const API_BASE_URL = 'http://localhost:8000/api/v1';
+const { saveSession, endSession } = require('../utils/session');
 
 /**
  * 简单的request封装（C端专用）
@@ -80,7 +81,7 @@ class CustomerAuthService {
                             }
                         });
 
-                        wx.setStorageSync('token', result.token);
+                        saveSession(result);
                         wx.setStorageSync('customer', result.customer);
                         wx.hideLoading();
 
@@ -115,27 +116,62 @@ class CustomerAuthService {
     }
 
     /**
-     * 绑定手机号
+     * 获取短信验证码 (60 秒内不能重复获取)
+     */
+    async sendSmsCode(phoneNumber) {
+        return request({
+            url: '/customer/sms-code',
+            method: 'POST',
+            data: { phone_number: phoneNumber }
+        });
+    }
+
+    /**
+     * 短信验证码绑定手机号
+     * 该手机号已有档案时后端会把当前微信合并过去, 并返回新档案的 Token
      */
     async bindPhone(phoneNumber, code) {
         try {
-            await request({
+            const result = await request({
                 url: '/customer/bind-phone',
                 method: 'POST',
                 data: { phone_number: phoneNumber, code: code }
             });
 
-            const customer = wx.getStorageSync('customer');
-            customer.phone_number = phoneNumber;
+            if (result.merged && result.token) {
+                saveSession(result);
+            }
+
+            const customer = wx.getStorageSync('customer') || {};
+            customer.id = result.customer_id;
+            customer.phone_number = result.phone_number;
             wx.setStorageSync('customer', customer);
 
-            return true;
+            return result;
         } catch (error) {
             console.error('Bind phone failed:', error);
             throw error;
         }
     }
 
+    /**
+     * 微信手机号绑定 (<button open-type="getPhoneNumber"> 回调里的 encryptedData / iv)
+     * 后端用登录时保存的 session_key 解密
+     */
+    async bindWechatPhone(encryptedData, iv) {
+        const result = await request({
+            url: '/customer/wechat-phone',
+            method: 'POST',
+            data: { encrypted_data: encryptedData, iv: iv }
+        });
+
+        const customer = wx.getStorageSync('customer');
+        customer.phone_number = result.phone_number;
+        wx.setStorageSync('customer', customer);
+
+        return result.phone_number;
+    }
+
     /**
      * 检查登录状态
      */
@@ -148,6 +184,7 @@ class CustomerAuthService {
      * 退出登录
      */
     logout() {
+        endSession();
         wx.removeStorageSync('token');
         wx.removeStorageSync('customer');
 
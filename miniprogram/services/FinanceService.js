@@ -10,7 +10,8 @@ class FinanceService {
 
     /**
      * 获取流水记录 (分页/筛选)
-     * @param {Object} params { status, user_id, date, page, limit }
+     * @param {Object} params { status, channel, base_id, created_from, created_to, page, page_size }
+     * @returns {Promise<Object>} { items, total, page, page_size, has_more }
      */
     static getPaymentRecords(params = {}) {
         return request.get('/api/v1/finance/payments', params);
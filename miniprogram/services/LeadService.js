@@ -11,8 +11,8 @@ class LeadService {
      * @param {string} params.status - 状态筛选 (new/contacted/qualified/trial_scheduled/converted/lost)
      * @param {string} params.assigned_to - 销售顾问ID
      * @param {number} params.page - 页码
-     * @param {number} params.limit - 每页数量
-     * @returns {Promise<Array>}
+     * @param {number} params.page_size - 每页数量
+     * @returns {Promise<Object>} { items, total, page, page_size, has_more }
      */
     static getLeads(params = {}) {
         return request.get('/api/v1/base/leads', params);
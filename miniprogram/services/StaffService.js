@@ -13,6 +13,22 @@ class StaffService {
         return request.get('/api/v1/hq/staff/risk-stats');
     }
 
+    /**
+     * 获取因多次密码错误被临时锁定的账号
+     * @returns {Promise} Array of { id, full_name, email, base_name, failed_attempts, last_failed_ip, locked_until }
+     */
+    static getLockedAccounts() {
+        return request.get('/api/v1/hq/staff/locked-accounts');
+    }
+
+    /**
+     * 解除账号的登录锁定
+     * @param {string} userId
+     */
+    static unlockAccount(userId) {
+        return request.post(`/api/v1/hq/users/${userId}/unlock`, {});
+    }
+
     /**
      * 获取关键人员列表 (HQ管理员、基地校长)
      * @returns {Promise} Array of key personnel
@@ -2,14 +2,16 @@
 import { API_BASE_URL } from '../config/index';
 import { userStore } from '../store/userStore';
 import AuthService from './AuthService';
+import { refreshSession } from '../utils/session';
 
 /**
  * 通用请求函数
  * @param {String} url - 接口路径 (如 '/dashboard/stats')
  * @param {String} method - 'GET' | 'POST' | 'PUT' | 'DELETE'
  * @param {Object} data - 请求参数
+ * @param {Boolean} retried - 是否已刷新过 Token 重试
  */
-function request(url, method = 'GET', data = {}) {
+function request(url, method = 'GET', data = {}, retried = false) {
   return new Promise((resolve, reject) => {
     // 1. 获取 Token
     const token = userStore.token;
@@ -40,10 +42,18 @@ function request(url, method = 'GET', data = {}) {
           // 请求成功
           resolve(resData);
         } else if (statusCode === 401) {
-          // Token 过期或无效
-          console.warn('⚠️ [API Auth] Token expired or invalid');
-          AuthService.handleSessionExpired(); // 踢回登录页
-          reject(resData);
+          // Token 过期或无效: 先用 refresh_token 续期, 成功后重试一次
+          const refresh = retried ? Promise.resolve(null) : refreshSession();
+          refresh.then((newToken) => {
+            if (newToken) {
+              userStore.setToken(newToken);
+              resolve(request(url, method, data, true));
+              return;
+            }
+            console.warn('⚠️ [API Auth] Token expired or invalid');
+            AuthService.handleSessionExpired(); // 踢回登录页
+            reject(resData);
+          });
         } else {
           // 其他错误 (404, 500 等)
           console.error(`❌ [API Error] ${statusCode}`, resData);
@@ -1,4 +1,5 @@
 import { observable, action } from 'mobx-miniprogram';
+import { endSession } from '../utils/session';
 
 export const userStore = observable({
   // 数据字段
@@ -18,8 +19,15 @@ export const userStore = observable({
     wx.setStorageSync('role', role);
   }),
 
+  // 动作: Token 续期后更新 (refresh_token 由 utils/session.js 保存)
+  setToken: action(function (token) {
+    this.token = token;
+    wx.setStorageSync('token', token);
+  }),
+
   // 动作: 退出登录
   logout: action(function () {
+    endSession(); // 通知后端吊销会话
     this.token = '';
     this.userInfo = null;
     this.role = 'GUEST';
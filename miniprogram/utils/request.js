@@ -1,5 +1,6 @@
 // utils/request.js - 通用请求工具
 const API_BASE_URL = 'http://localhost:8000/api/v1';
+const { refreshSession } = require('./session');
 
 /**
  * 通用请求函数
@@ -7,8 +8,9 @@ const API_BASE_URL = 'http://localhost:8000/api/v1';
  * @param {string} options.url - 接口路径
  * @param {string} options.method - 方法 (GET/POST/PUT/DELETE)
  * @param {Object} options.data - 数据
+ * @param {boolean} retried - 是否已刷新过 Token 重试
  */
-function request({ url, method = 'GET', data = {} }) {
+function request({ url, method = 'GET', data = {} }, retried = false) {
     return new Promise((resolve, reject) => {
         const token = wx.getStorageSync('token');
 
@@ -35,11 +37,19 @@ function request({ url, method = 'GET', data = {} }) {
                 if (statusCode >= 200 && statusCode < 300) {
                     resolve(resData);
                 } else if (statusCode === 401) {
-                    console.warn('Unauthorized - redirecting to login');
-                    // 处理登录过期
-                    wx.removeStorageSync('token');
-                    wx.reLaunch({ url: '/pages/launch/index' });
-                    reject(new Error('Unauthorized'));
+                    // Access Token 过期: 先尝试续期, 成功后重试一次
+                    const refresh = retried ? Promise.resolve(null) : refreshSession();
+                    refresh.then((newToken) => {
+                        if (newToken) {
+                            resolve(request({ url, method, data }, true));
+                            return;
+                        }
+                        console.warn('Unauthorized - redirecting to login');
+                        // 处理登录过期
+                        wx.removeStorageSync('token');
+                        wx.reLaunch({ url: '/pages/launch/index' });
+                        reject(new Error('Unauthorized'));
+                    });
                 } else {
                     wx.showToast({
                         title: resData.message || `请求失败 (${statusCode})`,
@@ -0,0 +1,64 @@
+// utils/session.js - 登录会话续期
+// Access Token 只有 15 分钟有效, 接口返回 401 时先用 refresh_token 换一对新 Token, 成功后重试原请求
+import { API_BASE_URL } from '../config/index';
+
+let refreshing = null; // 同时返回 401 的多个请求共用一次刷新
+
+/**
+ * 保存登录 / 刷新接口返回的 Token
+ * @param {Object} result - { token, refresh_token }
+ */
+export function saveSession(result) {
+  wx.setStorageSync('token', result.token);
+  if (result.refresh_token) {
+    wx.setStorageSync('refresh_token', result.refresh_token);
+  }
+}
+
+/**
+ * 用 refresh_token 换取新 Token
+ * @returns {Promise<String|null>} 新的 Access Token, 失败返回 null (需要重新登录)
+ */
+export function refreshSession() {
+  const refreshToken = wx.getStorageSync('refresh_token');
+  if (!refreshToken) return Promise.resolve(null);
+
+  if (!refreshing) {
+    refreshing = new Promise((resolve) => {
+      wx.request({
+        url: `${API_BASE_URL}/api/v1/auth/refresh`,
+        method: 'POST',
+        data: { refresh_token: refreshToken },
+        header: { 'Content-Type': 'application/json' },
+        success: (res) => {
+          if (res.statusCode === 200 && res.data && res.data.token) {
+            saveSession(res.data);
+            resolve(res.data.token);
+          } else {
+            // 会话已退出 / 被吊销, refresh_token 作废
+            wx.removeStorageSync('refresh_token');
+            resolve(null);
+          }
+        },
+        fail: () => resolve(null),
+        complete: () => { refreshing = null; }
+      });
+    });
+  }
+  return refreshing;
+}
+
+/**
+ * 退出登录: 通知后端吊销当前会话 (失败不影响本地退出)
+ */
+export function endSession() {
+  const token = wx.getStorageSync('token');
+  if (token) {
+    wx.request({
+      url: `${API_BASE_URL}/api/v1/auth/logout`,
+      method: 'POST',
+      header: { 'Authorization': `Bearer ${token}` }
+    });
+  }
+  wx.removeStorageSync('refresh_token');
+}
@@ -3,6 +3,7 @@
 import { useState, useEffect } from 'react';
 import { useSession } from 'next-auth/react';
 import { API_BASE_URL } from '@/lib/config';
+import { MAX_PAGE_SIZE, readApiError } from '@/lib/utils';
 import { 
     CheckCircle, XCircle, FileText, Loader2, 
     ExternalLink, User, Building2, AlertCircle 
@@ -36,12 +37,12 @@ export default function FinanceAuditPage() {
     const fetchRecords = async () => {
         try {
             setLoading(true);
-            const res = await fetch(`${API_BASE_URL}/finance/payments?status=PENDING`, {
+            const res = await fetch(`${API_BASE_URL}/finance/payments?status=PENDING&page_size=${MAX_PAGE_SIZE}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
             if (res.ok) {
                 const data = await res.json();
-                setRecords(data);
+                setRecords(data.items);
             }
         } catch (error) {
             console.error(error);
@@ -70,8 +71,8 @@ export default function FinanceAuditPage() {
                 alert('审核通过，订单已更新！');
                 fetchRecords(); // 刷新列表
             } else {
-                const txt = await res.text();
-                alert('操作失败: ' + txt);
+                const err = await readApiError(res);
+                alert('操作失败: ' + err.message);
             }
         } catch (e) {
             console.error(e);
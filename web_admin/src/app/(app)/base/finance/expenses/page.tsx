@@ -4,6 +4,7 @@ import { useState, useEffect } from 'react';
 import { useSession } from 'next-auth/react';
 import { API_BASE_URL } from '@/lib/config';
 import { Plus, Wallet, Loader2, TrendingDown } from 'lucide-react';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 // Soft UI Evolution colors
 const SOFT_COLORS = {
@@ -39,10 +40,10 @@ export default function BaseExpensesPage() {
 
     const fetchExpenses = async () => {
         try {
-            const res = await fetch(`${API_BASE_URL}/finance/expenses`, {
+            const res = await fetch(`${API_BASE_URL}/finance/expenses?page_size=${MAX_PAGE_SIZE}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
-            if (res.ok) setExpenses(await res.json());
+            if (res.ok) setExpenses((await res.json()).items);
         } catch (e) { console.error(e); }
         setLoading(false);
     };
@@ -11,6 +11,7 @@ import {
     Calendar,
     Loader2
 } from 'lucide-react';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 interface InventoryLog {
     id: string;
@@ -36,10 +37,10 @@ export default function InventoryRequestsPage() {
 
     const fetchLogs = async () => {
         try {
-            const res = await fetch(`${API_BASE_URL}/base/inventory/logs`, {
+            const res = await fetch(`${API_BASE_URL}/base/inventory/logs?page_size=${MAX_PAGE_SIZE}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
-            if (res.ok) setLogs(await res.json());
+            if (res.ok) setLogs((await res.json()).items);
         } catch (e) { console.error(e); }
         setLoading(false);
     };
@@ -4,6 +4,7 @@ import { useState, useEffect } from 'react';
 import { useSession } from 'next-auth/react';
 import Link from 'next/link';
 import { API_BASE_URL } from '@/lib/config';
+import { readApiError } from '@/lib/utils';
 import { 
     Box, Search, AlertTriangle, 
     ArrowDownCircle, ArrowUpCircle, // 用箭头图标代替包裹图标，意向更明确
@@ -92,11 +93,11 @@ export default function BaseStockPage() {
                 fetchInventory(); // 刷新列表看最新库存
             } else {
                 // 读取后端返回的错误信息（可选）
-                const err = await res.json().catch(() => ({})); 
-                if (res.status === 409) {
+                const err = await readApiError(res);
+                if (err.code === "stock.insufficient") {
                     alert("❌ 操作失败：库存不足");
                 } else {
-                    alert("❌ 操作失败，请重试");
+                    alert(`❌ 操作失败：${err.message}`);
                 }
             }
         } catch (e) { alert("网络错误"); }
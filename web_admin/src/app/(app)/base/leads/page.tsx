@@ -19,6 +19,7 @@ import CreateLeadModal from './CreateLeadModal';
 // Soft UI Components
 import { SoftPageContainer, SoftHeader, SoftButton, SoftCard } from '@/components/ui/SoftUI';
 import { SOFT_COLORS } from '@/lib/softui-theme';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 interface Lead {
     id: string;
@@ -77,13 +78,14 @@ export default function BaseLeadsPage() {
             // 1. 获取线索数据
             const leadsParams = new URLSearchParams();
             if (statusFilter !== 'all') leadsParams.append('status', statusFilter);
+            leadsParams.append('page_size', String(MAX_PAGE_SIZE));
 
             const leadsRes = await fetch(`${API}/base/leads?${leadsParams.toString()}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
 
             // 2. 获取客户数据
-            const customersRes = await fetch(`${API}/customers`, {
+            const customersRes = await fetch(`${API}/customers?page_size=${MAX_PAGE_SIZE}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
 
@@ -92,7 +94,7 @@ export default function BaseLeadsPage() {
             // 3. 处理线索数据
             if (leadsRes.ok) {
                 const leadsData = await leadsRes.json();
-                combinedData = leadsData.map((lead: any) => ({
+                combinedData = leadsData.items.map((lead: any) => ({
                     ...lead,
                     data_source: 'lead' as const
                 }));
@@ -101,7 +103,7 @@ export default function BaseLeadsPage() {
             // 4. 处理客户数据并转换格式
             if (customersRes.ok) {
                 const customersData = await customersRes.json();
-                const transformedCustomers = customersData.map((customer: any) => ({
+                const transformedCustomers = customersData.items.map((customer: any) => ({
                     id: customer.id,
                     contact_name: customer.name || '未知',
                     phone_number: customer.phone_number,
@@ -14,6 +14,7 @@ import {
     DollarSign, X, CheckCircle, Loader2, AlertCircle, Baby
 } from 'lucide-react';
 import { API_BASE_URL } from '@/lib/config';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 // --- 1. 接口定义 ---
 interface Participant { id: string; name: string; customer_id: string; }
@@ -74,16 +75,16 @@ export default function MembershipsPage() {
             
             // 1. 并行请求所有相关数据
             const [custRes, partRes, cardRes, tierRes] = await Promise.all([
-                fetch(`${API}/customers`, { headers }),
-                fetch(`${API}/participants`, { headers }),
-                fetch(`${API}/base/customer-memberships`, { headers }),
+                fetch(`${API}/customers?page_size=${MAX_PAGE_SIZE}`, { headers }),
+                fetch(`${API}/participants?page_size=${MAX_PAGE_SIZE}`, { headers }),
+                fetch(`${API}/base/customer-memberships?page_size=${MAX_PAGE_SIZE}`, { headers }),
                 fetch(`${API}/membership-tiers`, { headers })
             ]);
 
             if (custRes.ok && partRes.ok && cardRes.ok && tierRes.ok) {
-                const rawCustomers: Customer[] = await custRes.json();
-                const rawParticipants: Participant[] = await partRes.json();
-                const rawCards: MembershipCard[] = await cardRes.json();
+                const rawCustomers: Customer[] = (await custRes.json()).items;
+                const rawParticipants: Participant[] = (await partRes.json()).items;
+                const rawCards: MembershipCard[] = (await cardRes.json()).items;
                 const tiers: MembershipTier[] = await tierRes.json();
 
                 // 2. 在前端进行数据组装 (Aggregation)
@@ -13,6 +13,7 @@ import {
     CreditCard, User, ArrowLeft
 } from 'lucide-react';
 import { API_BASE_URL } from '@/lib/config';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 // --- 类型定义 ---
 interface Customer { id: string; name: string | null; phone_number: string; }
@@ -64,9 +65,9 @@ function NewParticipantContent() {
                 if (parentIdFromUrl) {
                     // 由于目前后端没有 get_customer_by_id 接口，我们暂时拉取列表查找
                     // (生产环境建议增加 GET /api/v1/customers/:id 接口)
-                    const res = await fetch(`${API}/customers`, { headers: { 'Authorization': `Bearer ${token}` } });
+                    const res = await fetch(`${API}/customers?page_size=${MAX_PAGE_SIZE}`, { headers: { 'Authorization': `Bearer ${token}` } });
                     if (res.ok) {
-                        const customers: Customer[] = await res.json();
+                        const customers: Customer[] = (await res.json()).items;
                         const found = customers.find(c => c.id === parentIdFromUrl);
                         if (found) {
                             setCreatedCustomer(found);
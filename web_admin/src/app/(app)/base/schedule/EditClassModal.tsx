@@ -15,6 +15,7 @@ import {
     Search, UserPlus, CreditCard, User, ChevronRight, AlertCircle, ExternalLink, Lock
 } from 'lucide-react';
 import { API_BASE_URL } from '@/lib/config';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 // --- 类型定义 ---
 interface Teacher { user_id: string; full_name: string; }
@@ -79,11 +80,11 @@ export default function EditClassModal({ token, classData, onClose, onSuccess }:
                 const headers = { 'Authorization': `Bearer ${token}` };
                 const [teachRes, studRes] = await Promise.all([
                     fetch(`${API}/base/teachers`, { headers }),
-                    fetch(`${API}/participants`, { headers })
+                    fetch(`${API}/participants?page_size=${MAX_PAGE_SIZE}`, { headers })
                 ]);
 
                 if (teachRes.ok) setTeachers(await teachRes.json());
-                if (studRes.ok) setAllStudents(await studRes.json());
+                if (studRes.ok) setAllStudents((await studRes.json()).items);
 
                 fetchEnrollments();
             } catch (e) { console.error(e); }
@@ -226,7 +226,7 @@ function StaffModal({ token, baseId, mode, initialData, onClose, onSuccess }: Mo
             });
 
             if (res.ok) {
-                if (!isEdit) alert(`✅ 员工创建成功！\n账号: ${formData.email}\n密码: ${formData.password}`);
+                if (!isEdit) alert(`✅ 员工创建成功！\n账号: ${formData.email}\n密码: ${formData.password}\n员工首次登录时需设置新密码。`);
                 else alert("✅ 修改成功！");
                 onSuccess();
                 onClose();
@@ -18,6 +18,7 @@ import StudentProfileDrawer from './StudentProfileDrawer';
 // Soft UI Components
 import { SoftPageContainer, SoftHeader, SoftButton, SoftCard, SoftBadge } from '@/components/ui/SoftUI';
 import { SOFT_COLORS } from '@/lib/softui-theme';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 interface ParticipantDetail {
     id: string;
@@ -49,10 +50,10 @@ export default function CampusStudentsPage() {
         if (!token) return;
         setIsLoading(true);
         try {
-            const res = await fetch(`${API}/base/participants`, {
+            const res = await fetch(`${API}/base/participants?page_size=${MAX_PAGE_SIZE}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
-            if (res.ok) setStudents(await res.json());
+            if (res.ok) setStudents((await res.json()).items);
         } catch (e) { console.error(e); }
         finally { setIsLoading(false); }
     };
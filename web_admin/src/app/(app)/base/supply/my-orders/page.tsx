@@ -14,6 +14,7 @@ import {
     Search,
     ExternalLink
 } from 'lucide-react';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 interface SupplyOrder {
     id: string;
@@ -44,10 +45,10 @@ export default function MySupplyOrdersPage() {
 
     const fetchOrders = async () => {
         try {
-            const res = await fetch(`${API_BASE_URL}/supply/orders`, {
+            const res = await fetch(`${API_BASE_URL}/supply/orders?page_size=${MAX_PAGE_SIZE}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
-            if (res.ok) setOrders(await res.json());
+            if (res.ok) setOrders((await res.json()).items);
         } catch (e) { console.error(e); }
         setLoading(false);
     };
@@ -13,6 +13,7 @@ import {
 } from 'lucide-react';
 import CreateTrialClassModal from './CreateTrialClassModal';
 import TrialClassDetailDrawer from './TrialClassDetailDrawer';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 interface TrialClass {
     id: number;
@@ -72,14 +73,14 @@ export default function TrialClassesPage() {
     const fetchTrialClasses = async () => {
         if (!token) return;
         try {
-            const response = await fetch(`${API_BASE_URL}/base/trial-classes`, {
+            const response = await fetch(`${API_BASE_URL}/base/trial-classes?page_size=${MAX_PAGE_SIZE}`, {
                 headers: {
                     'Authorization': `Bearer ${token}`,
                 },
             });
 
             if (response.ok) {
-                const data = await response.json();
+                const data = (await response.json()).items;
                 setTrialClasses(data);
                 calculateStats(data);
             }
@@ -12,6 +12,7 @@ import {
     ArrowRightLeft, Trash2, DollarSign, Tag, AlertCircle,
     QrCode, Download, Printer, Wrench, Settings, X
 } from 'lucide-react';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 // --- 类型定义 ---
 interface AssetDetail {
@@ -59,12 +60,12 @@ export default function TenantAssetsPage() {
         setIsLoading(true);
         try {
             const [assetsRes, typesRes, basesRes] = await Promise.all([
-                fetch(`${API}/hq/assets`, { headers: { 'Authorization': `Bearer ${token}` } }),
+                fetch(`${API}/hq/assets?page_size=${MAX_PAGE_SIZE}`, { headers: { 'Authorization': `Bearer ${token}` } }),
                 fetch(`${API}/asset-types`, { headers: { 'Authorization': `Bearer ${token}` } }),
                 fetch(`${API}/bases`, { headers: { 'Authorization': `Bearer ${token}` } })
             ]);
 
-            if (assetsRes.ok) setAssets(await assetsRes.json());
+            if (assetsRes.ok) setAssets((await assetsRes.json()).items);
             if (typesRes.ok) setTypes(await typesRes.json());
             if (basesRes.ok) setBases(await basesRes.json());
         } catch (e) { console.error(e); }
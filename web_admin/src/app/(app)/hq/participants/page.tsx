@@ -12,6 +12,7 @@ import {
     Calendar, AlertCircle, X, Award, Baby, TrendingUp // (★ 修复: 补上 TrendingUp)
 } from 'lucide-react';
 import ParticipantDrawer from './ParticipantDrawer';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 // --- 类型定义 ---
 interface ParticipantDetail {
@@ -60,13 +61,13 @@ export default function TenantParticipantsPage() {
         try {
             const headers = { 'Authorization': `Bearer ${token}` };
             const [pRes, bRes, rRes, sRes] = await Promise.all([
-                fetch(`${API}/hq/participants`, { headers }),
+                fetch(`${API}/hq/participants?page_size=${MAX_PAGE_SIZE}`, { headers }),
                 fetch(`${API}/bases`, { headers }),
                 fetch(`${API}/honor-ranks`, { headers }),
                 fetch(`${API}/hq/participants/stats`, { headers })
             ]);
 
-            if (pRes.ok) setAllParticipants(await pRes.json());
+            if (pRes.ok) setAllParticipants((await pRes.json()).items);
             if (bRes.ok) setBases(await bRes.json());
             if (rRes.ok) setRanks(await rRes.json());
             if (sRes.ok) setStats(await sRes.json());
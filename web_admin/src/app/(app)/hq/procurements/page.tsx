@@ -13,6 +13,7 @@ import {
     Package, Plus, Clock, CheckCircle, XCircle, Truck, 
     Trash2, ShoppingCart, ExternalLink, Box, X 
 } from 'lucide-react';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 interface Material { id: string; name_key: string; unit_of_measure: string; }
 interface ProcurementOrder { 
@@ -62,10 +63,10 @@ export default function ProcurementPage() {
         try {
             const [matRes, orderRes] = await Promise.all([
                 fetch(`${API}/materials`, { headers: { 'Authorization': `Bearer ${token}` } }),
-                fetch(`${API}/procurements`, { headers: { 'Authorization': `Bearer ${token}` } })
+                fetch(`${API}/procurements?page_size=${MAX_PAGE_SIZE}`, { headers: { 'Authorization': `Bearer ${token}` } })
             ]);
             if (matRes.ok) setMaterials(await matRes.json());
-            if (orderRes.ok) setOrders(await orderRes.json());
+            if (orderRes.ok) setOrders((await orderRes.json()).items);
         } catch (e) { console.error(e); } 
         finally { setIsLoading(false); }
     };
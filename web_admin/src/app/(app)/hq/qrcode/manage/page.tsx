@@ -7,6 +7,7 @@ import {
     Package, Truck, Download, ShieldCheck, 
     Loader2, AlertCircle, BarChart3, Search 
 } from 'lucide-react';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 interface Batch {
     id: string;
@@ -29,11 +30,11 @@ export default function QrBatchManagePage() {
     // 获取列表
     const fetchBatches = async () => {
         try {
-            const res = await fetch(`${API_BASE_URL}/admin/qrcodes/batches`, {
+            const res = await fetch(`${API_BASE_URL}/admin/qrcodes/batches?page_size=${MAX_PAGE_SIZE}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
             if (res.ok) {
-                setBatches(await res.json());
+                setBatches((await res.json()).items);
             }
         } catch (e) {
             console.error(e);
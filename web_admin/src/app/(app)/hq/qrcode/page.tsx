@@ -3,6 +3,7 @@
 import { useState } from 'react';
 import { useSession } from 'next-auth/react';
 import { API_BASE_URL } from '@/lib/config';
+import { readApiError } from '@/lib/utils';
 import { 
     QrCode, Loader2, CheckCircle, AlertCircle, 
     Database, Download, FileSpreadsheet, ShieldCheck 
@@ -51,8 +52,8 @@ export default function QrCodeGeneratorPage() {
                 const data = await res.json();
                 setResult(data);
             } else {
-                const txt = await res.text();
-                setError(`生成失败: ${txt}`);
+                const err = await readApiError(res);
+                setError(`生成失败: ${err.message}`);
             }
         } catch (err: any) {
             console.error(err);
@@ -4,6 +4,7 @@ import { useState, useEffect } from 'react';
 import { useSession } from 'next-auth/react';
 import { API_BASE_URL } from '@/lib/config';
 import { Loader2, CheckCircle, Truck, Package, FileText } from 'lucide-react';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 interface SupplyOrder {
     id: string;
@@ -29,10 +30,10 @@ export default function HQSupplyOrdersPage() {
 
     const fetchOrders = async () => {
         try {
-            const res = await fetch(`${API_BASE_URL}/hq/supply/orders`, {
+            const res = await fetch(`${API_BASE_URL}/hq/supply/orders?page_size=${MAX_PAGE_SIZE}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
-            if (res.ok) setOrders(await res.json());
+            if (res.ok) setOrders((await res.json()).items);
         } catch (e) { console.error(e); }
         setLoading(false);
     };
@@ -24,6 +24,7 @@ import {
     SoftSelect,
 } from '@/components/ui/SoftUI';
 import { SOFT_COLORS } from '@/lib/softui-theme';
+import { MAX_PAGE_SIZE } from '@/lib/utils';
 
 interface User {
     id: string;
@@ -48,8 +49,8 @@ export default function TenantUsersPage() {
         if (!token) return;
         setIsLoading(true);
         try {
-            const res = await fetch(`${API}/hq/users`, { headers: { 'Authorization': `Bearer ${token}` } });
-            if (res.ok) setUsers(await res.json());
+            const res = await fetch(`${API}/hq/users?page_size=${MAX_PAGE_SIZE}`, { headers: { 'Authorization': `Bearer ${token}` } });
+            if (res.ok) setUsers((await res.json()).items);
         } catch (e) { console.error(e); }
         finally { setIsLoading(false); }
     };
@@ -71,6 +72,22 @@ export default function TenantUsersPage() {
         } catch (e) { alert("网络错误"); }
     };
 
+    // 重置密码 (员工忘记密码): 原密码立即失效, 把一次性链接发给员工设置新密码
+    const resetPassword = async (user: User) => {
+        if (!confirm(`确认重置 [${user.full_name}] 的密码吗？\n\n原密码将立即失效，该员工所有设备会被强制下线。`)) return;
+
+        try {
+            const res = await fetch(`${API}/hq/users/${user.id}/password-reset`, {
+                method: 'POST',
+                headers: { 'Authorization': `Bearer ${token}` },
+            });
+            if (!res.ok) { alert("操作失败"); return; }
+            const data = await res.json();
+            const link = `${window.location.origin}/reset-password?token=${encodeURIComponent(data.reset_token)}`;
+            window.prompt("请将以下链接发给员工 (24 小时内有效，仅能使用一次)：", link);
+        } catch (e) { alert("网络错误"); }
+    };
+
     // 角色字典
     const roleMap: Record<string, string> = {
         'role.hq.admin': '总经理 (BOSS)',
@@ -166,6 +183,19 @@ export default function TenantUsersPage() {
                                         {new Date(u.created_at).toLocaleDateString()}
                                     </td>
                                     <td className="p-5 text-right">
+                                        {u.role_name !== 'role.hq.admin' && u.is_active && (
+                                            <button
+                                                onClick={() => resetPassword(u)}
+                                                className="text-sm font-bold px-4 py-2 mr-2 rounded-xl transition-all hover:scale-105"
+                                                style={{
+                                                    background: 'rgba(99, 102, 241, 0.1)',
+                                                    color: SOFT_COLORS.softBlue,
+                                                    border: '1.5px solid rgba(99, 102, 241, 0.3)'
+                                                }}
+                                            >
+                                                重置密码
+                                            </button>
+                                        )}
                                         {u.role_name !== 'role.hq.admin' && ( // 不能封禁老板自己
                                             <button
                                                 onClick={() => toggleStatus(u)}
@@ -217,7 +247,7 @@ function CreateUserModal({ token, onClose, onSuccess }: any) {
                 })
             });
             if (res.ok) {
-                alert(`✅ 创建成功！\n账号: ${email}\n密码: ${password}\n请务必复制发送给员工。`);
+                alert(`✅ 创建成功！\n账号: ${email}\n密码: ${password}\n请务必复制发送给员工，员工首次登录时需设置新密码。`);
                 onSuccess();
                 onClose();
             } else {
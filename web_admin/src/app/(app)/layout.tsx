@@ -246,6 +246,11 @@ export default function AppLayout({ children }: { children: React.ReactNode }) {
     const pathname = usePathname();
     const { data: session } = useSession();
 
+    // 会话已被吊销 (离职、改密码、他处退出) 时回到登录页
+    useEffect(() => {
+        if (session?.error === 'RefreshTokenError') signOut({ callbackUrl: '/login' });
+    }, [session?.error]);
+
     const { visibleNavItems, theme, userTitle, appTitle, isTenantUser } = useMemo(() => {
         const token = session?.user?.rawToken;
         if (!token) return { visibleNavItems: [], theme: 'indigo', userTitle: '访客', appTitle: 'EduSaaS', isTenantUser: false };
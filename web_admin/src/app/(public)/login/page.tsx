@@ -5,6 +5,7 @@ import { useState, FormEvent } from 'react';
 import { signIn } from 'next-auth/react';
 import { useRouter } from 'next/navigation';
 import { Lock, Mail, Loader2, AlertCircle } from 'lucide-react';
+import { PASSWORD_CHANGE_REQUIRED } from '@/lib/config';
 
 export default function LoginPage() {
     const [email, setEmail] = useState('');
@@ -29,8 +30,16 @@ export default function LoginPage() {
                 email, password, redirect: false, 
             });
 
+            // 首次登录 / 密码被管理员重置: 先设置新密码
+            if (result?.error?.startsWith(`${PASSWORD_CHANGE_REQUIRED}:`)) {
+                const resetToken = result.error.slice(PASSWORD_CHANGE_REQUIRED.length + 1);
+                router.push(`/reset-password?token=${encodeURIComponent(resetToken)}`);
+                return;
+            }
+
+            // authorize 抛出的提示 (密码错误 / 账号临时锁定 / 尝试过于频繁)
             if (result?.error) {
-                setError('用户名或密码错误');
+                setError(result.error);
                 setIsLoading(false);
                 return;
             }
@@ -0,0 +1,84 @@
+// web_admin/src/app/(public)/reset-password/page.tsx
+// 设置新密码: 首次登录 (登录页跳转过来) 或管理员重置后发来的链接, 都带一次性 token
+'use client';
+
+import { useState, FormEvent, Suspense } from 'react';
+import { useRouter, useSearchParams } from 'next/navigation';
+import { API_BASE_URL } from '@/lib/config';
+
+function ResetPasswordForm() {
+    const searchParams = useSearchParams();
+    const token = searchParams.get('token') || '';
+    const router = useRouter();
+
+    const [password, setPassword] = useState('');
+    const [confirm, setConfirm] = useState('');
+    const [isLoading, setIsLoading] = useState(false);
+    const [error, setError] = useState('');
+    const [done, setDone] = useState(false);
+
+    const handleSubmit = async (e: FormEvent) => {
+        e.preventDefault();
+        setError('');
+
+        if (password !== confirm) {
+            setError('两次输入的密码不一致');
+            return;
+        }
+
+        setIsLoading(true);
+        try {
+            const res = await fetch(`${API_BASE_URL}/auth/password/reset`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ token, new_password: password }),
+            });
+
+            if (res.status === 400) {
+                setError('密码至少 8 位, 需同时包含字母和数字, 且不能与邮箱相同');
+            } else if (res.status === 401) {
+                setError('链接已失效, 请重新登录或联系管理员重新重置');
+            } else if (!res.ok) {
+                setError('设置失败, 请稍后重试');
+            } else {
+                setDone(true);
+                setTimeout(() => router.push('/login'), 1500);
+            }
+        } catch (err) {
+            setError('网络错误');
+        } finally {
+            setIsLoading(false);
+        }
+    };
+
+    return (
+        <div className="min-h-screen flex items-center justify-center bg-gray-50 p-4">
+             <div className="bg-white w-full max-w-md rounded-2xl shadow-xl overflow-hidden p-8">
+                 <h2 className="text-2xl font-bold text-center mb-2">设置新密码</h2>
+                 <p className="text-sm text-gray-500 text-center mb-6">设置完成后请使用新密码重新登录</p>
+                 {!token && <div className="text-red-500 text-sm mb-4 text-center">链接无效, 缺少重置令牌</div>}
+                 {error && <div className="text-red-500 text-sm mb-4 text-center">{error}</div>}
+                 {done ? (
+                     <div className="text-green-600 text-sm text-center">密码已更新, 正在跳转登录页...</div>
+                 ) : (
+                     <form onSubmit={handleSubmit} className="space-y-4">
+                         <input className="w-full p-3 border rounded" type="password" value={password} onChange={e=>setPassword(e.target.value)} placeholder="新密码 (至少 8 位, 包含字母和数字)" minLength={8} required/>
+                         <input className="w-full p-3 border rounded" type="password" value={confirm} onChange={e=>setConfirm(e.target.value)} placeholder="确认新密码" required/>
+                         <button disabled={isLoading || !token} className="w-full bg-indigo-600 text-white p-3 rounded font-bold disabled:opacity-50">
+                             {isLoading ? "提交中..." : "确认"}
+                         </button>
+                     </form>
+                 )}
+             </div>
+        </div>
+    );
+}
+
+// Next.js 14 要求使用 useSearchParams 的组件包裹在 Suspense 中
+export default function ResetPasswordPage() {
+    return (
+        <Suspense fallback={<div>Loading...</div>}>
+            <ResetPasswordForm />
+        </Suspense>
+    );
+}
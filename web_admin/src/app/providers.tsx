@@ -5,7 +5,8 @@ import { SessionProvider } from "next-auth/react";
 
 export default function Providers({ children }: { children: React.ReactNode }) {
   return (
-    <SessionProvider>
+    // 每 5 分钟拉取一次会话, 让 jwt 回调在 Access Token (15 分钟) 过期前完成续期
+    <SessionProvider refetchInterval={5 * 60}>
       {children}
     </SessionProvider>
   );
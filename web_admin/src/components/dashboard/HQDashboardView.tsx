@@ -70,7 +70,7 @@ export default function TenantDashboardView({ roles = [] }: { roles: string[] })
                 // ✅ Finance specific: Pending payment records for approval
                 if (isBoss || isFinance) {
                     promises.push(
-                        fetch(`${API_BASE_URL}/finance/payments?status=PENDING`, { headers })
+                        fetch(`${API_BASE_URL}/finance/payments?status=PENDING&page_size=3`, { headers })
                             .then(res => res.ok ? res.json().then(data => ({ type: 'pending_payments', data })) : null)
                     );
                 }
@@ -87,7 +87,7 @@ export default function TenantDashboardView({ roles = [] }: { roles: string[] })
                     if (result.type === 'staff') setPendingStaff(result.data);
                     if (result.type === 'pending_payments') {
                         // Attach to basicStats for finance dashboard to consume
-                        setBasicStats((prev: any) => ({ ...prev, pending_payments: result.data }));
+                        setBasicStats((prev: any) => ({ ...prev, pending_payments: result.data.items, pending_payments_total: result.data.total }));
                     }
                 });
 
@@ -63,7 +63,7 @@ export default function FinanceDashboard({ stats }: any) {
                         <h3 className="font-bold text-gray-800 flex items-center gap-2"><FileText size={18} /> 待审批单据</h3>
                         {stats?.pending_payments?.length > 0 && (
                             <span className="bg-red-100 text-red-600 px-2 py-0.5 rounded text-xs font-bold">
-                                {stats.pending_payments.length} 笔
+                                {stats.pending_payments_total ?? stats.pending_payments.length} 笔
                             </span>
                         )}
                     </div>
@@ -3,6 +3,7 @@
 import { useState, useEffect, useRef } from 'react';
 import { useSession } from 'next-auth/react';
 import { API_BASE_URL } from '@/lib/config';
+import { readApiError } from '@/lib/utils';
 import { 
     X, Loader2, User, Tag, Plus, Trash2, FileText, UploadCloud, CheckCircle 
 } from 'lucide-react';
@@ -122,7 +123,7 @@ export default function CreateOrderDrawer({ isOpen, onClose, onSuccess }: Props)
             });
 
             if (res.ok) { onSuccess(); } 
-            else { alert('创建失败: ' + await res.text()); }
+            else { alert('创建失败: ' + (await readApiError(res)).message); }
         } catch (e) { console.error(e); } 
         finally { setSubmitting(false); }
     };
@@ -0,0 +1,175 @@
+'use client';
+
+import { useState, useEffect } from 'react';
+import { useSession } from 'next-auth/react';
+import { API_BASE_URL } from '@/lib/config';
+import { readApiError } from '@/lib/utils';
+import { Order } from './SalesOrderPage';
+import { X, CalendarClock, Plus, Trash2, Loader2 } from 'lucide-react';
+
+interface Installment {
+    id: string;
+    seq: number;
+    due_date: string;
+    amount_cents: number;
+    paid_cents: number;
+    note: string | null;
+    status: 'paid' | 'partial' | 'overdue' | 'due';
+}
+
+interface Row {
+    due_date: string;
+    amount: string;
+    note: string;
+}
+
+interface Props {
+    order: Order | null;
+    isOpen: boolean;
+    onClose: () => void;
+    onSuccess: () => void;
+}
+
+const STATUS_LABEL: Record<Installment['status'], { text: string; className: string }> = {
+    paid: { text: '已收齐', className: 'text-green-600 bg-green-50' },
+    partial: { text: '部分收款', className: 'text-orange-600 bg-orange-50' },
+    overdue: { text: '已逾期', className: 'text-red-600 bg-red-50' },
+    due: { text: '未到期', className: 'text-gray-500 bg-gray-100' },
+};
+
+// 分期收款计划: 各期到期日 + 金额, 合计须等于订单总额; 收款核实后按到期日先后自动核销
+export default function InstallmentModal({ order, isOpen, onClose, onSuccess }: Props) {
+    const { data: session } = useSession();
+    const token = (session?.user as any)?.rawToken;
+
+    const [current, setCurrent] = useState<Installment[]>([]);
+    const [rows, setRows] = useState<Row[]>([]);
+    const [loading, setLoading] = useState(false);
+    const [submitting, setSubmitting] = useState(false);
+
+    useEffect(() => {
+        if (!isOpen || !order || !token) return;
+        setLoading(true);
+        fetch(`${API_BASE_URL}/finance/orders/${order.id}/installments`, {
+            headers: { 'Authorization': `Bearer ${token}` }
+        })
+            .then(res => res.ok ? res.json() : [])
+            .then((data: Installment[]) => {
+                setCurrent(data);
+                setRows(data.map(i => ({ due_date: i.due_date, amount: String(i.amount_cents / 100), note: i.note ?? '' })));
+            })
+            .catch(console.error)
+            .finally(() => setLoading(false));
+    }, [isOpen, order, token]);
+
+    if (!isOpen || !order) return null;
+
+    const totalYuan = order.total_amount_cents / 100;
+    const plannedYuan = rows.reduce((sum, r) => sum + (Number(r.amount) || 0), 0);
+
+    const updateRow = (idx: number, patch: Partial<Row>) => {
+        setRows(rows.map((r, i) => i === idx ? { ...r, ...patch } : r));
+    };
+
+    const handleSubmit = async () => {
+        if (rows.length > 0 && Math.round(plannedYuan * 100) !== order.total_amount_cents) {
+            alert(`各期合计 ¥${plannedYuan.toLocaleString()} 须等于订单总额 ¥${totalYuan.toLocaleString()}`);
+            return;
+        }
+        setSubmitting(true);
+        try {
+            const res = await fetch(`${API_BASE_URL}/finance/orders/${order.id}/installments`, {
+                method: 'PUT',
+                headers: { 'Content-Type': 'application/json', 'Authorization': `Bearer ${token}` },
+                body: JSON.stringify({
+                    installments: rows.map(r => ({ due_date: r.due_date, amount: Number(r.amount), note: r.note || null })),
+                })
+            });
+            if (res.ok) {
+                alert('✅ 分期计划已保存');
+                onSuccess();
+                onClose();
+            } else {
+                const err = await readApiError(res);
+                alert('❌ 保存失败: ' + err.message);
+            }
+        } catch (e) {
+            console.error(e);
+            alert('❌ 网络错误，请稍后重试');
+        } finally {
+            setSubmitting(false);
+        }
+    };
+
+    return (
+        <div className="fixed inset-0 z-50 flex items-center justify-center p-4">
+            <div className="absolute inset-0 bg-black/40 backdrop-blur-sm" onClick={onClose} />
+            <div className="relative w-full max-w-lg bg-white rounded-2xl shadow-xl overflow-hidden animate-in zoom-in-95 duration-200">
+                <div className="bg-indigo-600 p-6 text-white text-center relative">
+                    <button onClick={onClose} className="absolute right-4 top-4 text-indigo-100 hover:text-white"><X size={20} /></button>
+                    <div className="mx-auto w-12 h-12 bg-white/20 rounded-full flex items-center justify-center mb-3">
+                        <CalendarClock size={24} />
+                    </div>
+                    <h3 className="text-xl font-bold">分期收款计划</h3>
+                    <p className="text-indigo-100 text-sm mt-1">{order.order_no} · 总额 ¥{totalYuan.toLocaleString()} · 已付 ¥{(order.paid_amount_cents / 100).toLocaleString()}</p>
+                </div>
+
+                <div className="p-6 space-y-5">
+                    {loading ? (
+                        <div className="flex items-center justify-center py-6 text-gray-400"><Loader2 className="animate-spin" size={18} /></div>
+                    ) : (
+                        <>
+                            {current.length > 0 && (
+                                <div className="space-y-1">
+                                    <label className="block text-xs font-bold text-gray-500 uppercase mb-1">当前核销情况</label>
+                                    {current.map(i => (
+                                        <div key={i.id} className="flex items-center justify-between text-sm">
+                                            <span className="text-gray-700">第 {i.seq} 期 · {i.due_date}</span>
+                                            <span className="flex items-center gap-2">
+                                                <span className="text-gray-500">¥{(i.paid_cents / 100).toLocaleString()} / ¥{(i.amount_cents / 100).toLocaleString()}</span>
+                                                <span className={`text-xs px-2 py-0.5 rounded ${STATUS_LABEL[i.status].className}`}>{STATUS_LABEL[i.status].text}</span>
+                                            </span>
+                                        </div>
+                                    ))}
+                                </div>
+                            )}
+
+                            <div>
+                                <label className="block text-xs font-bold text-gray-500 uppercase mb-1">付款节点 (保存后已收款项按到期日重新核销)</label>
+                                <div className="space-y-2">
+                                    {rows.map((r, idx) => (
+                                        <div key={idx} className="flex items-center gap-2">
+                                            <input type="date" className="p-2 border border-gray-200 rounded-lg text-sm"
+                                                value={r.due_date} onChange={e => updateRow(idx, { due_date: e.target.value })} />
+                                            <input type="number" placeholder="金额" className="w-28 p-2 border border-gray-200 rounded-lg text-sm text-right"
+                                                value={r.amount} onChange={e => updateRow(idx, { amount: e.target.value })} />
+                                            <input placeholder="备注" className="flex-1 p-2 border border-gray-200 rounded-lg text-sm"
+                                                value={r.note} onChange={e => updateRow(idx, { note: e.target.value })} />
+                                            <button onClick={() => setRows(rows.filter((_, i) => i !== idx))} className="p-2 text-gray-400 hover:text-red-500">
+                                                <Trash2 size={16} />
+                                            </button>
+                                        </div>
+                                    ))}
+                                </div>
+                                <div className="flex items-center justify-between mt-3">
+                                    <button onClick={() => setRows([...rows, { due_date: '', amount: '', note: '' }])}
+                                        className="flex items-center gap-1 text-sm text-indigo-600 hover:text-indigo-800">
+                                        <Plus size={14} /> 添加一期
+                                    </button>
+                                    <span className={`text-xs ${Math.round(plannedYuan * 100) === order.total_amount_cents ? 'text-green-600' : 'text-gray-400'}`}>
+                                        合计 ¥{plannedYuan.toLocaleString()} / ¥{totalYuan.toLocaleString()}
+                                    </span>
+                                </div>
+                            </div>
+                        </>
+                    )}
+
+                    <button onClick={handleSubmit} disabled={submitting || loading || rows.some(r => !r.due_date || !r.amount)}
+                        className="w-full py-3.5 bg-indigo-600 text-white font-bold rounded-xl hover:bg-indigo-700 disabled:opacity-50 disabled:cursor-not-allowed transition-all">
+                        {submitting ? '保存中...' : rows.length === 0 ? '取消分期' : '保存分期计划'}
+                    </button>
+                </div>
+            </div>
+        </div>
+    );
+}
@@ -0,0 +1,216 @@
+'use client';
+
+import { useState, useEffect, useCallback } from 'react';
+import { useSession } from 'next-auth/react';
+import { API_BASE_URL } from '@/lib/config';
+import { readApiError } from '@/lib/utils';
+import { Order } from './SalesOrderPage';
+import { X, FileText, Download, RefreshCw, Undo2, Loader2 } from 'lucide-react';
+
+interface Invoice {
+    id: string;
+    kind: 'blue' | 'red';
+    invoice_type: 'normal' | 'special';
+    status: 'pending' | 'issuing' | 'issued' | 'failed' | 'cancelled';
+    buyer_name: string;
+    amount_cents: number;
+    tax_cents: number;
+    invoice_no: string | null;
+    issued_at: string | null;
+    last_error: string | null;
+}
+
+interface Props {
+    order: Order | null;
+    isOpen: boolean;
+    onClose: () => void;
+    onSuccess: () => void;
+}
+
+const STATUS_LABEL: Record<Invoice['status'], { text: string; className: string }> = {
+    pending: { text: '待开具', className: 'text-gray-500 bg-gray-100' },
+    issuing: { text: '开具中', className: 'text-orange-600 bg-orange-50' },
+    issued: { text: '已开具', className: 'text-green-600 bg-green-50' },
+    failed: { text: '开具失败', className: 'text-red-600 bg-red-50' },
+    cancelled: { text: '已撤销', className: 'text-gray-400 bg-gray-50' },
+};
+
+const EMPTY_FORM = { invoice_type: 'normal', buyer_name: '', buyer_tax_id: '', buyer_email: '', tax_rate: '6' };
+
+// 电子发票: 查看订单发票、下载版式文件、重试 / 撤销 / 冲红, 以及按订单剩余可开明细申请开票
+export default function InvoiceModal({ order, isOpen, onClose, onSuccess }: Props) {
+    const { data: session } = useSession();
+    const token = (session?.user as any)?.rawToken;
+
+    const [invoices, setInvoices] = useState<Invoice[]>([]);
+    const [form, setForm] = useState(EMPTY_FORM);
+    const [loading, setLoading] = useState(false);
+    const [submitting, setSubmitting] = useState(false);
+
+    const fetchInvoices = useCallback(async () => {
+        if (!order || !token) return;
+        setLoading(true);
+        try {
+            const res = await fetch(`${API_BASE_URL}/finance/orders/${order.id}/invoices`, {
+                headers: { 'Authorization': `Bearer ${token}` }
+            });
+            if (res.ok) setInvoices(await res.json());
+        } catch (e) {
+            console.error(e);
+        } finally {
+            setLoading(false);
+        }
+    }, [order, token]);
+
+    useEffect(() => {
+        if (!isOpen) return;
+        setForm(EMPTY_FORM);
+        fetchInvoices();
+    }, [isOpen, fetchInvoices]);
+
+    if (!isOpen || !order) return null;
+
+    const post = async (path: string, body: object, okMessage: string) => {
+        setSubmitting(true);
+        try {
+            const res = await fetch(`${API_BASE_URL}${path}`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json', 'Authorization': `Bearer ${token}` },
+                body: JSON.stringify(body)
+            });
+            if (res.ok) {
+                alert(okMessage);
+                onSuccess();
+            } else {
+                const err = await readApiError(res);
+                alert('❌ ' + err.message);
+            }
+        } catch (e) {
+            console.error(e);
+            alert('❌ 网络错误，请稍后重试');
+        } finally {
+            setSubmitting(false);
+            fetchInvoices();
+        }
+    };
+
+    const handleCreate = () => post(`/finance/orders/${order.id}/invoices`, {
+        invoice_type: form.invoice_type,
+        buyer_name: form.buyer_name,
+        buyer_tax_id: form.buyer_tax_id || null,
+        buyer_email: form.buyer_email || null,
+        tax_rate: Number(form.tax_rate),
+    }, '✅ 发票已开具');
+
+    const handleReverse = (invoice: Invoice) => {
+        const reason = prompt(`冲红发票 ${invoice.invoice_no}，请输入原因:`);
+        if (!reason) return;
+        post(`/finance/invoices/${invoice.id}/reverse`, { reason }, '✅ 红字发票已开具');
+    };
+
+    const handleDownload = async (invoice: Invoice, format: 'pdf' | 'xml') => {
+        const res = await fetch(`${API_BASE_URL}/finance/invoices/${invoice.id}/${format}`, {
+            headers: { 'Authorization': `Bearer ${token}` }
+        });
+        if (!res.ok) {
+            const err = await readApiError(res);
+            alert('❌ 下载失败: ' + err.message);
+            return;
+        }
+        const url = URL.createObjectURL(await res.blob());
+        const a = document.createElement('a');
+        a.href = url;
+        a.download = `${invoice.invoice_no}.${format}`;
+        a.click();
+        URL.revokeObjectURL(url);
+    };
+
+    return (
+        <div className="fixed inset-0 z-50 flex items-center justify-center p-4">
+            <div className="absolute inset-0 bg-black/40 backdrop-blur-sm" onClick={onClose} />
+            <div className="relative w-full max-w-xl bg-white rounded-2xl shadow-xl overflow-hidden animate-in zoom-in-95 duration-200">
+                <div className="bg-blue-600 p-6 text-white text-center relative">
+                    <button onClick={onClose} className="absolute right-4 top-4 text-blue-100 hover:text-white"><X size={20} /></button>
+                    <div className="mx-auto w-12 h-12 bg-white/20 rounded-full flex items-center justify-center mb-3">
+                        <FileText size={24} />
+                    </div>
+                    <h3 className="text-xl font-bold">电子发票</h3>
+                    <p className="text-blue-100 text-sm mt-1">{order.order_no} · 总额 ¥{(order.total_amount_cents / 100).toLocaleString()} · 已付 ¥{(order.paid_amount_cents / 100).toLocaleString()}</p>
+                </div>
+
+                <div className="p-6 space-y-5 max-h-[70vh] overflow-y-auto">
+                    <div>
+                        <label className="block text-xs font-bold text-gray-500 uppercase mb-2">已申请的发票</label>
+                        {loading ? (
+                            <div className="flex items-center justify-center py-4 text-gray-400"><Loader2 className="animate-spin" size={18} /></div>
+                        ) : invoices.length === 0 ? (
+                            <p className="text-xs text-gray-400">暂无发票</p>
+                        ) : (
+                            <div className="space-y-2">
+                                {invoices.map(inv => (
+                                    <div key={inv.id} className="flex items-center justify-between text-sm border border-gray-100 rounded-lg p-2">
+                                        <div className="min-w-0">
+                                            <div className="flex items-center gap-2">
+                                                <span className={`text-xs font-bold ${inv.kind === 'red' ? 'text-rose-600' : 'text-blue-600'}`}>{inv.kind === 'red' ? '红字' : '蓝字'}</span>
+                                                <span className="font-mono text-gray-700 truncate">{inv.invoice_no ?? '—'}</span>
+                                                <span className={`text-xs px-2 py-0.5 rounded ${STATUS_LABEL[inv.status].className}`}>{STATUS_LABEL[inv.status].text}</span>
+                                            </div>
+                                            <div className="text-xs text-gray-400 mt-0.5">
+                                                {inv.buyer_name} · ¥{(inv.amount_cents / 100).toLocaleString()} (税 ¥{(inv.tax_cents / 100).toLocaleString()})
+                                                {inv.last_error && <span className="text-red-400 ml-1">{inv.last_error}</span>}
+                                            </div>
+                                        </div>
+                                        <div className="flex items-center gap-1 shrink-0">
+                                            {inv.status === 'issued' && (
+                                                <>
+                                                    <button onClick={() => handleDownload(inv, 'pdf')} className="p-1.5 text-gray-500 hover:text-blue-600" title="下载 PDF"><Download size={14} /></button>
+                                                    <button onClick={() => handleDownload(inv, 'xml')} className="px-1.5 text-[10px] font-bold text-gray-500 hover:text-blue-600" title="下载 XML">XML</button>
+                                                </>
+                                            )}
+                                            {inv.status === 'issued' && inv.kind === 'blue' && (
+                                                <button onClick={() => handleReverse(inv)} disabled={submitting} className="p-1.5 text-gray-500 hover:text-rose-600" title="冲红"><Undo2 size={14} /></button>
+                                            )}
+                                            {(inv.status === 'failed' || inv.status === 'pending') && (
+                                                <>
+                                                    <button onClick={() => post(`/finance/invoices/${inv.id}/issue`, {}, '✅ 发票已开具')} disabled={submitting} className="p-1.5 text-gray-500 hover:text-blue-600" title="重试开具"><RefreshCw size={14} /></button>
+                                                    <button onClick={() => post(`/finance/invoices/${inv.id}/cancel`, {}, '已撤销')} disabled={submitting} className="p-1.5 text-gray-500 hover:text-red-600" title="撤销"><X size={14} /></button>
+                                                </>
+                                            )}
+                                        </div>
+                                    </div>
+                                ))}
+                            </div>
+                        )}
+                    </div>
+
+                    <div className="space-y-3 border-t border-gray-100 pt-4">
+                        <label className="block text-xs font-bold text-gray-500 uppercase">申请开票 (按订单剩余可开明细)</label>
+                        <div className="grid grid-cols-2 gap-3">
+                            <select className="p-2.5 bg-gray-50 border border-gray-200 rounded-xl text-sm outline-none"
+                                value={form.invoice_type} onChange={e => setForm({ ...form, invoice_type: e.target.value })}>
+                                <option value="normal">数电普票</option>
+                                <option value="special">数电专票</option>
+                            </select>
+                            <div className="relative">
+                                <input type="number" className="w-full p-2.5 pr-8 bg-gray-50 border border-gray-200 rounded-xl text-sm outline-none"
+                                    value={form.tax_rate} onChange={e => setForm({ ...form, tax_rate: e.target.value })} />
+                                <span className="absolute right-3 top-2.5 text-gray-400 text-sm">%</span>
+                            </div>
+                        </div>
+                        <input placeholder="购方名称 *" className="w-full p-2.5 bg-gray-50 border border-gray-200 rounded-xl text-sm outline-none"
+                            value={form.buyer_name} onChange={e => setForm({ ...form, buyer_name: e.target.value })} />
+                        <input placeholder={form.invoice_type === 'special' ? '纳税人识别号 *' : '纳税人识别号 (个人可不填)'} className="w-full p-2.5 bg-gray-50 border border-gray-200 rounded-xl text-sm outline-none"
+                            value={form.buyer_tax_id} onChange={e => setForm({ ...form, buyer_tax_id: e.target.value })} />
+                        <input placeholder="接收邮箱" className="w-full p-2.5 bg-gray-50 border border-gray-200 rounded-xl text-sm outline-none"
+                            value={form.buyer_email} onChange={e => setForm({ ...form, buyer_email: e.target.value })} />
+                        <button onClick={handleCreate}
+                            disabled={submitting || !form.buyer_name.trim() || (form.invoice_type === 'special' && !form.buyer_tax_id.trim())}
+                            className="w-full py-3 bg-blue-600 text-white font-bold rounded-xl hover:bg-blue-700 disabled:opacity-50 disabled:cursor-not-allowed transition-all">
+                            {submitting ? '处理中...' : '开具发票'}
+                        </button>
+                    </div>
+                </div>
+            </div>
+        </div>
+    );
+}
@@ -3,6 +3,7 @@
 import { useState, useRef, useEffect } from 'react'; // ★ 引入 useEffect
 import { useSession } from 'next-auth/react';
 import { API_BASE_URL } from '@/lib/config';
+import { readApiError } from '@/lib/utils';
 import { Order } from './SalesOrderPage';
 import {
     X, UploadCloud, CreditCard, Loader2,
@@ -134,8 +135,8 @@ export default function PaymentModal({ order, isOpen, onClose, onSuccess }: Prop
                 onSuccess();
                 onClose();
             } else {
-                const errorText = await res.text();
-                alert('❌ 提交失败: ' + errorText);
+                const err = await readApiError(res);
+                alert('❌ 提交失败: ' + err.message);
             }
         } catch (e) {
             console.error(e);
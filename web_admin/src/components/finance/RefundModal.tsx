@@ -0,0 +1,170 @@
+'use client';
+
+import { useState, useEffect } from 'react';
+import { useSession } from 'next-auth/react';
+import { API_BASE_URL } from '@/lib/config';
+import { readApiError } from '@/lib/utils';
+import { Order } from './SalesOrderPage';
+import { X, RotateCcw, Loader2 } from 'lucide-react';
+
+interface OrderItem {
+    id: string;
+    name: string;
+    quantity: number;
+    unit_price_cents: number;
+    refunded_quantity: number;
+    product_id: string | null;
+    membership_id: string | null;
+}
+
+interface Props {
+    order: Order | null;
+    isOpen: boolean;
+    onClose: () => void;
+    onSuccess: () => void;
+}
+
+// 发起退款申请: 金额 + 退回明细 (退货回库存 / 会员卡停用), 提交后进入工作台退费审批
+export default function RefundModal({ order, isOpen, onClose, onSuccess }: Props) {
+    const { data: session } = useSession();
+    const token = (session?.user as any)?.rawToken;
+
+    const [items, setItems] = useState<OrderItem[]>([]);
+    const [returnQty, setReturnQty] = useState<Record<string, number>>({});
+    const [form, setForm] = useState({ amount: '', channel: 'bank_transfer', reason: '' });
+    const [loading, setLoading] = useState(false);
+    const [submitting, setSubmitting] = useState(false);
+
+    useEffect(() => {
+        if (!isOpen || !order || !token) return;
+        setForm({ amount: '', channel: 'bank_transfer', reason: '' });
+        setReturnQty({});
+        setLoading(true);
+        fetch(`${API_BASE_URL}/finance/orders/${order.id}/items`, {
+            headers: { 'Authorization': `Bearer ${token}` }
+        })
+            .then(res => res.ok ? res.json() : [])
+            .then((data: OrderItem[]) => setItems(data))
+            .catch(console.error)
+            .finally(() => setLoading(false));
+    }, [isOpen, order, token]);
+
+    const handleSubmit = async () => {
+        if (!order) return;
+        const amount = Number(form.amount);
+        if (!(amount > 0) || amount > order.paid_amount_cents / 100) {
+            alert(`退款金额须大于 0 且不超过已付金额 ¥${(order.paid_amount_cents / 100).toLocaleString()}`);
+            return;
+        }
+
+        setSubmitting(true);
+        try {
+            const res = await fetch(`${API_BASE_URL}/finance/orders/${order.id}/refunds`, {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json', 'Authorization': `Bearer ${token}` },
+                body: JSON.stringify({
+                    amount,
+                    channel: form.channel,
+                    reason: form.reason,
+                    items: Object.entries(returnQty)
+                        .filter(([, quantity]) => quantity > 0)
+                        .map(([order_item_id, quantity]) => ({ order_item_id, quantity })),
+                })
+            });
+            if (res.ok) {
+                alert('✅ 退款申请已提交，请等待审批');
+                onSuccess();
+                onClose();
+            } else {
+                const err = await readApiError(res);
+                alert('❌ 提交失败: ' + err.message);
+            }
+        } catch (e) {
+            console.error(e);
+            alert('❌ 网络错误，请稍后重试');
+        } finally {
+            setSubmitting(false);
+        }
+    };
+
+    if (!isOpen || !order) return null;
+
+    return (
+        <div className="fixed inset-0 z-50 flex items-center justify-center p-4">
+            <div className="absolute inset-0 bg-black/40 backdrop-blur-sm" onClick={onClose} />
+            <div className="relative w-full max-w-md bg-white rounded-2xl shadow-xl overflow-hidden animate-in zoom-in-95 duration-200">
+                <div className="bg-rose-600 p-6 text-white text-center relative">
+                    <button onClick={onClose} className="absolute right-4 top-4 text-rose-100 hover:text-white"><X size={20} /></button>
+                    <div className="mx-auto w-12 h-12 bg-white/20 rounded-full flex items-center justify-center mb-3">
+                        <RotateCcw size={24} />
+                    </div>
+                    <h3 className="text-xl font-bold">申请退款</h3>
+                    <p className="text-rose-100 text-sm mt-1">{order.order_no} · 已付 ¥{(order.paid_amount_cents / 100).toLocaleString()}</p>
+                </div>
+
+                <div className="p-6 space-y-5">
+                    <div>
+                        <label className="block text-xs font-bold text-gray-500 uppercase mb-1">退款金额 <span className="text-red-500">*</span></label>
+                        <div className="relative">
+                            <span className="absolute left-3 top-3 text-gray-400 font-bold">¥</span>
+                            <input type="number" className="w-full pl-8 p-3 border-2 border-rose-100 rounded-xl focus:border-rose-500 outline-none font-bold text-lg text-gray-900"
+                                value={form.amount} onChange={e => setForm({ ...form, amount: e.target.value })}
+                            />
+                        </div>
+                    </div>
+
+                    <div>
+                        <label className="block text-xs font-bold text-gray-500 uppercase mb-1">退款方式</label>
+                        <select className="w-full p-3 bg-gray-50 border border-gray-200 rounded-xl text-sm outline-none"
+                            value={form.channel} onChange={e => setForm({ ...form, channel: e.target.value })}>
+                            <option value="bank_transfer">银行转账</option>
+                            <option value="wechat">微信</option>
+                            <option value="alipay">支付宝</option>
+                            <option value="cash">现金</option>
+                        </select>
+                    </div>
+
+                    <div>
+                        <label className="block text-xs font-bold text-gray-500 uppercase mb-1">退回明细 (商品退回库存 / 会员卡停用)</label>
+                        {loading ? (
+                            <div className="flex items-center justify-center py-4 text-gray-400"><Loader2 className="animate-spin" size={18} /></div>
+                        ) : items.length === 0 ? (
+                            <p className="text-xs text-gray-400">该订单没有明细，仅退款</p>
+                        ) : (
+                            <div className="space-y-2">
+                                {items.map(item => {
+                                    const left = item.quantity - item.refunded_quantity;
+                                    return (
+                                        <div key={item.id} className="flex items-center justify-between text-sm">
+                                            <span className="text-gray-700 truncate">
+                                                {item.name}
+                                                <span className="text-xs text-gray-400 ml-1">可退 {left}</span>
+                                            </span>
+                                            <input type="number" min={0} max={left} disabled={left === 0}
+                                                className="w-20 p-1.5 border border-gray-200 rounded-lg text-right disabled:bg-gray-50"
+                                                value={returnQty[item.id] ?? 0}
+                                                onChange={e => setReturnQty({ ...returnQty, [item.id]: Math.min(left, Math.max(0, Number(e.target.value))) })}
+                                            />
+                                        </div>
+                                    );
+                                })}
+                            </div>
+                        )}
+                    </div>
+
+                    <div>
+                        <label className="block text-xs font-bold text-gray-500 uppercase mb-1">退款原因 <span className="text-red-500">*</span></label>
+                        <textarea rows={2} className="w-full p-3 bg-gray-50 border border-gray-200 rounded-xl text-sm outline-none"
+                            value={form.reason} onChange={e => setForm({ ...form, reason: e.target.value })}
+                        />
+                    </div>
+
+                    <button onClick={handleSubmit} disabled={submitting || !form.amount || !form.reason.trim()}
+                        className="w-full py-3.5 bg-rose-600 text-white font-bold rounded-xl hover:bg-rose-700 disabled:opacity-50 disabled:cursor-not-allowed transition-all">
+                        {submitting ? '提交中...' : '提交退款申请'}
+                    </button>
+                </div>
+            </div>
+        </div>
+    );
+}
@@ -3,16 +3,20 @@
 import { useState, useEffect } from 'react';
 import { useSession } from 'next-auth/react';
 import { API_BASE_URL } from '@/lib/config';
+import { MAX_PAGE_SIZE, type Page } from '@/lib/utils';
 import {
     Plus, Search, Users, Calendar,
     MoreHorizontal, FileText, CheckCircle, AlertCircle, Clock,
-    Trash2, Edit, Upload, User, Tag
+    Trash2, Edit, Upload, User, Tag, RotateCcw, CalendarClock
 } from 'lucide-react';
 
 // 引入同级目录下的子组件
 import CreateOrderDrawer from './CreateOrderDrawer';
 import EditOrderDrawer from './EditOrderDrawer';
 import PaymentModal from './PaymentModal';
+import RefundModal from './RefundModal';
+import InstallmentModal from './InstallmentModal';
+import InvoiceModal from './InvoiceModal';
 
 // --- 类型定义 (与后端 OrderDetail 对齐) ---
 export interface Order {
@@ -52,6 +56,9 @@ export default function SalesOrderPage() {
     const [isCreateOpen, setCreateOpen] = useState(false);
     const [isEditOpen, setEditOpen] = useState(false);
     const [isPayOpen, setPayOpen] = useState(false);
+    const [isRefundOpen, setRefundOpen] = useState(false);
+    const [isInstallmentOpen, setInstallmentOpen] = useState(false);
+    const [isInvoiceOpen, setInvoiceOpen] = useState(false);
     const [selectedOrder, setSelectedOrder] = useState<Order | null>(null);
 
     // --- 数据获取 ---
@@ -59,12 +66,12 @@ export default function SalesOrderPage() {
         if (!token) return;
         setLoading(true);
         try {
-            const res = await fetch(`${API_BASE_URL}/finance/orders`, {
+            const res = await fetch(`${API_BASE_URL}/finance/orders?page_size=${MAX_PAGE_SIZE}`, {
                 headers: { 'Authorization': `Bearer ${token}` }
             });
             if (res.ok) {
-                const data = await res.json();
-                setOrders(data);
+                const data: Page<Order> = await res.json();
+                setOrders(data.items);
             } else {
                 console.error("Failed to fetch orders:", await res.text());
             }
@@ -321,6 +328,44 @@ export default function SalesOrderPage() {
                                                 </>
                                             )}
 
+                                            {/* 电子发票 */}
+                                            {order.status !== 'cancelled' && (
+                                                <button
+                                                    onClick={() => { setSelectedOrder(order); setInvoiceOpen(true); }}
+                                                    className="p-2 text-blue-600 bg-blue-50 hover:bg-blue-100 rounded-lg"
+                                                    title="电子发票"
+                                                >
+                                                    <FileText size={16} />
+                                                </button>
+                                            )}
+
+                                            {/* 分期计划 (未付清) */}
+                                            {order.status !== 'cancelled' && order.status !== 'refunded' && order.paid_amount_cents < order.total_amount_cents && (
+                                                <button
+                                                    onClick={() => { setSelectedOrder(order); setInstallmentOpen(true); }}
+                                                    className="p-2 text-indigo-600 bg-indigo-50 hover:bg-indigo-100 rounded-lg"
+                                                    title="分期计划"
+                                                >
+                                                    <CalendarClock size={16} />
+                                                </button>
+                                            )}
+
+                                            {/* 退款按钮 (有已付金额) */}
+                                            {order.status !== 'cancelled' && order.status !== 'refunded' && order.paid_amount_cents > 0 && (
+                                                <button
+                                                    onClick={() => { setSelectedOrder(order); setRefundOpen(true); }}
+                                                    className="p-2 text-rose-600 bg-rose-50 hover:bg-rose-100 rounded-lg"
+                                                    title="申请退款"
+                                                >
+                                                    <RotateCcw size={16} />
+                                                </button>
+                                            )}
+
+                                            {/* 已退款状态展示 */}
+                                            {order.status === 'refunded' && (
+                                                <span className="text-xs text-rose-400 bg-rose-50 px-2 py-1 rounded">已退款</span>
+                                            )}
+
                                             {/* 已取消状态展示 */}
                                             {order.status === 'cancelled' && (
                                                 <span className="text-xs text-gray-400 bg-gray-100 px-2 py-1 rounded">已取消</span>
@@ -354,6 +399,27 @@ export default function SalesOrderPage() {
                 onClose={() => setPayOpen(false)}
                 onSuccess={() => { fetchOrders(); }}
             />
+
+            <RefundModal
+                order={selectedOrder}
+                isOpen={isRefundOpen}
+                onClose={() => setRefundOpen(false)}
+                onSuccess={() => { fetchOrders(); }}
+            />
+
+            <InstallmentModal
+                order={selectedOrder}
+                isOpen={isInstallmentOpen}
+                onClose={() => setInstallmentOpen(false)}
+                onSuccess={() => { fetchOrders(); }}
+            />
+
+            <InvoiceModal
+                order={selectedOrder}
+                isOpen={isInvoiceOpen}
+                onClose={() => setInvoiceOpen(false)}
+                onSuccess={() => { fetchOrders(); }}
+            />
         </div>
     );
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 // src/lib/api.ts
 import { getServerSession } from "next-auth/next"; // (★ V4 导入)
 import { authOptions } from "./authOptions"; // (★ V4 导入)
+import { readApiError } from "./utils";
 
 const API_URL = process.env.CORE_API_URL || "http://edusaas_core_api:8000/api/v1";
 
@@ -22,8 +23,9 @@ async function getApiInstance() {
       console.log(`(API Lib) Fetching: ${url}`);
       const res = await fetch(url, { method: 'GET', headers, cache: 'no-store' });
       if (!res.ok) {
-        console.error(`(API Lib) Error fetching ${url}: ${res.status} ${res.statusText}`);
-        throw new Error(`API Error: ${res.status} ${res.statusText}`);
+        const err = await readApiError(res);
+        console.error(`(API Lib) Error fetching ${url}: ${res.status} ${err.code}`);
+        throw new Error(err.message);
       }
       return res.json() as T;
     },
@@ -1,7 +1,9 @@
 // web_admin/src/lib/authOptions.ts
 import { AuthOptions } from "next-auth";
+import { JWT } from "next-auth/jwt";
 import CredentialsProvider from "next-auth/providers/credentials";
 import { jwtDecode } from "jwt-decode";
+import { PASSWORD_CHANGE_REQUIRED } from "./config";
 
 // 1. 完善接口定义 (与 Rust Claims 对应)
 interface ITokenClaims {
@@ -17,6 +19,40 @@ interface ITokenClaims {
 
 const CORE_API_URL = process.env.CORE_API_URL || "http://edusaas_core_api:8000/api/v1";
 
+// Access Token 只有 15 分钟, 到期前 60 秒用 refresh_token 换新 (后端每次轮换 refresh_token)
+const REFRESH_MARGIN_MS = 60 * 1000;
+
+async function refreshAccessToken(token: JWT): Promise<JWT> {
+  try {
+    const res = await fetch(`${CORE_API_URL}/auth/refresh`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ refresh_token: token.refreshToken }),
+    });
+    if (!res.ok) throw new Error(`refresh failed: ${res.status}`);
+
+    const data = await res.json();
+    // 角色 / 基地可能已调整, 以新 Token 为准
+    const claims = jwtDecode<ITokenClaims>(data.token);
+    return {
+      ...token,
+      rawToken: data.token,
+      refreshToken: data.refresh_token,
+      accessTokenExpires: Date.parse(data.expires_at),
+      hq_id: claims.hq_id,
+      base_id: claims.base_id,
+      base_name: claims.base_name,
+      base_logo: claims.base_logo,
+      roles: claims.roles,
+      error: undefined,
+    };
+  } catch (e) {
+    // 会话已退出 / 被吊销 (离职、改密码): 前端需重新登录
+    console.error("Refresh Token Error:", e);
+    return { ...token, error: "RefreshTokenError" };
+  }
+}
+
 export const authOptions: AuthOptions = {
   providers: [
     CredentialsProvider({
@@ -25,23 +61,36 @@ export const authOptions: AuthOptions = {
         email: { label: "Email", type: "text" },
         password: { label: "Password", type: "password" },
       },
-      async authorize(credentials) {
+      async authorize(credentials, req) {
         if (!credentials) return null;
 
+        // 登录请求由本服务端发出: 转发浏览器的来源 IP, 后端 (TRUSTED_PROXIES) 据此按 IP 限流
+        const forwardedFor = req?.headers?.["x-forwarded-for"];
+        const headers: Record<string, string> = { 'Content-Type': 'application/json' };
+        if (typeof forwardedFor === "string" && forwardedFor) headers['X-Forwarded-For'] = forwardedFor;
+
         try {
           const res = await fetch(`${CORE_API_URL}/auth/login`, {
             method: 'POST',
-            headers: { 'Content-Type': 'application/json' },
+            headers,
             body: JSON.stringify({
               email: credentials.email,
               password: credentials.password,
             }),
           });
           
+          // 连续输错密码: 423 账号临时锁定, 429 尝试过于频繁 (后端逐次延迟)
+          if (res.status === 423) throw new Error("密码错误次数过多, 账号已临时锁定, 请 15 分钟后再试或联系管理员解锁");
+          if (res.status === 429) throw new Error("尝试过于频繁, 请稍后再试");
           if (!res.ok) throw new Error("用户名或密码错误");
 
           const data = await res.json();
+          // 管理员创建 / 重置的账号: 先设置新密码, 登录页据此跳转到 /reset-password
+          if (data.must_change_password) {
+            throw new Error(`${PASSWORD_CHANGE_REQUIRED}:${data.reset_token}`);
+          }
           const token = data.token;
+          const refreshToken = data.refresh_token;
           if (!token) throw new Error("Rust API 未返回 Token");
 
           // 解码 Token
@@ -54,6 +103,8 @@ export const authOptions: AuthOptions = {
             // 展开所有 Claims (roles, base_name, base_logo 等都在这里)
             ...decodedClaims, 
             rawToken: token,
+            refreshToken,
+            accessTokenExpires: Date.parse(data.expires_at),
           };
 
         } catch (e: any) {
@@ -76,8 +127,16 @@ export const authOptions: AuthOptions = {
         // ★ 关键补充
         token.base_name = user.base_name;
         token.base_logo = user.base_logo;
+        token.refreshToken = user.refreshToken;
+        token.accessTokenExpires = user.accessTokenExpires;
+        return token;
       }
-      return token;
+
+      // Access Token 仍有效 (或已刷新失败) 时直接返回
+      if (token.error || Date.now() < token.accessTokenExpires - REFRESH_MARGIN_MS) {
+        return token;
+      }
+      return refreshAccessToken(token);
     },
     
     // ★ 修复: 将 Token 里的新字段暴露给 Session (前端才能拿到)
@@ -90,11 +149,23 @@ export const authOptions: AuthOptions = {
       // ★ 关键补充
       session.user.base_name = token.base_name;
       session.user.base_logo = token.base_logo;
+      session.error = token.error;
       
       return session;
     },
   },
 
+  events: {
+    // 退出登录时吊销后端会话, 失败不影响本地退出
+    async signOut({ token }) {
+      if (!token?.rawToken) return;
+      await fetch(`${CORE_API_URL}/auth/logout`, {
+        method: 'POST',
+        headers: { 'Authorization': `Bearer ${token.rawToken}` },
+      }).catch(() => undefined);
+    },
+  },
+
   session: { strategy: "jwt" },
   secret: process.env.NEXTAUTH_SECRET,
   pages: { signIn: '/login' }
@@ -5,6 +5,9 @@
 // 3. 关键修复: 确保不会返回 undefined
 export const API_BASE_URL = process.env.NEXT_PUBLIC_API_URL || "http://localhost:8000/api/v1";
 
+// 登录需要先改密码时, authorize 抛出的错误前缀 (后面跟一次性令牌), 登录页据此跳转到 /reset-password
+export const PASSWORD_CHANGE_REQUIRED = "PASSWORD_CHANGE_REQUIRED";
+
 // 打印调试信息 (方便在浏览器控制台 F12 查看)
 if (typeof window !== 'undefined') {
     console.log(`🌐 前端 API 配置地址: ${API_BASE_URL}`);
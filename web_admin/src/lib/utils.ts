@@ -3,4 +3,30 @@ import { twMerge } from "tailwind-merge"
 
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
-}
\ No newline at end of file
+}
+
+// 后端错误统一为 { code, message, details? }; 读取失败时退回状态码
+export interface ApiError {
+  code: string
+  message: string
+  details?: unknown
+}
+
+export async function readApiError(res: Response): Promise<ApiError> {
+  const body = await res.json().catch(() => null)
+  if (body && typeof body.code === "string") {
+    return body as ApiError
+  }
+  return { code: "http." + res.status, message: `请求失败 (${res.status})` }
+}
+
+// 列表接口统一返回分页结构; 下拉选项和尚未做翻页的页面按上限一次取满
+export interface Page<T> {
+  items: T[]
+  total: number
+  page: number
+  page_size: number
+  has_more: boolean
+}
+
+export const MAX_PAGE_SIZE = 500
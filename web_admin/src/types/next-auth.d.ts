@@ -18,6 +18,7 @@ declare module "next-auth" {
       base_logo: string | null;
       roles: string[];
     } & DefaultSession["user"];
+    error?: "RefreshTokenError"; // 会话已失效, 需要重新登录
   }
 
   /**
@@ -31,6 +32,8 @@ declare module "next-auth" {
     base_name: string | null;
     base_logo: string | null;
     roles: string[];
+    refreshToken: string;
+    accessTokenExpires: number;
   }
 }
 
@@ -46,5 +49,8 @@ declare module "next-auth/jwt" {
     base_name: string | null;
     base_logo: string | null;
     roles: string[];
+    refreshToken: string;
+    accessTokenExpires: number; // ms 时间戳
+    error?: "RefreshTokenError";
   }
 }
\ No newline at end of file